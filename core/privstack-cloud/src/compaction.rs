@@ -6,9 +6,10 @@
 
 use crate::api_client::CloudApiClient;
 use crate::credential_manager::CredentialManager;
-use crate::error::{CloudError, CloudResult};
+use crate::dek_registry::encrypt_with_dek;
+use crate::error::CloudResult;
 use crate::s3_transport::S3Transport;
-use privstack_crypto::{encrypt, DerivedKey};
+use privstack_crypto::DerivedKey;
 use tracing::{debug, info};
 
 /// Threshold: trigger compaction after this many batches per entity.
@@ -69,11 +70,8 @@ pub async fn create_snapshot(
     serialized_state: &[u8],
     cursor_position: i64,
 ) -> CloudResult<()> {
-    // Encrypt snapshot with entity DEK
-    let encrypted = encrypt(entity_dek, serialized_state)
-        .map_err(|e| CloudError::Envelope(format!("snapshot encryption failed: {e}")))?;
-
-    let snapshot_bytes = serde_json::to_vec(&encrypted)?;
+    // Encrypt snapshot with entity DEK, tagged with its key id
+    let snapshot_bytes = encrypt_with_dek(entity_dek, serialized_state)?;
     let s3_key = snapshot_s3_key(user_id, workspace_id, entity_id, cursor_position);
 
     // Upload to S3
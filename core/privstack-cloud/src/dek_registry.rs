@@ -3,9 +3,25 @@
 //! The sync engine reads DEKs from this registry to encrypt outgoing
 //! batches and decrypt incoming batches. The application layer populates
 //! it when entities are loaded, created, or received via sharing.
+//!
+//! Every key that passes through the registry is also kept in a key
+//! history indexed by its fingerprint. Batches and snapshots are written
+//! as [`KeyedCiphertext`], tagged with the fingerprint of the DEK that
+//! sealed them, so data written before a rotation stays readable after
+//! [`DekRegistry::rotate`] installs a fresh key for new writes.
+//!
+//! The history itself lives in memory. What survives a restart is the
+//! entity's share envelope: a rotation seals the new key together with the
+//! keys it replaced (see [`encode_dek_chain`]), and when [`DekRegistry::decrypt`]
+//! meets a key id it does not know it asks its [`DekSource`] for that chain.
 
 use crate::error::{CloudError, CloudResult};
-use privstack_crypto::DerivedKey;
+use async_trait::async_trait;
+use privstack_crypto::{
+    decrypt, encrypt, generate_random_key, DerivedKey, EncryptedData, KEY_SIZE,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -19,6 +35,21 @@ use tokio::sync::RwLock;
 pub struct DekRegistry {
     deks: Arc<RwLock<HashMap<String, DerivedKey>>>,
     default_dek: Arc<RwLock<Option<DerivedKey>>>,
+    /// Every key ever registered, by [`dek_fingerprint`].
+    key_history: Arc<RwLock<HashMap<String, DerivedKey>>>,
+    /// Fingerprints of the per-entity keys each entity used before its
+    /// current one, newest first.
+    earlier: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    source: Option<Arc<dyn DekSource>>,
+}
+
+/// Fetches the keys sealed for an entity when the registry meets a key id
+/// it has never seen, e.g. a batch written before a rotation, read after
+/// a restart.
+#[async_trait]
+pub trait DekSource: Send + Sync {
+    /// Returns the entity's key chain, current key first.
+    async fn fetch_entity_keys(&self, entity_id: &str) -> CloudResult<Vec<DerivedKey>>;
 }
 
 impl DekRegistry {
@@ -26,18 +57,138 @@ impl DekRegistry {
         Self {
             deks: Arc::new(RwLock::new(HashMap::new())),
             default_dek: Arc::new(RwLock::new(None)),
+            key_history: Arc::new(RwLock::new(HashMap::new())),
+            earlier: Arc::new(RwLock::new(HashMap::new())),
+            source: None,
+        }
+    }
+
+    /// Creates a registry that fetches keys it does not know yet from
+    /// `source` (see [`Self::decrypt`]).
+    pub fn with_source(source: Arc<dyn DekSource>) -> Self {
+        Self {
+            source: Some(source),
+            ..Self::new()
         }
     }
 
     /// Sets the workspace-level default DEK used for all entities that
     /// don't have a per-entity key registered.
     pub async fn set_default(&self, dek: DerivedKey) {
+        self.remember(&dek).await;
         *self.default_dek.write().await = Some(dek);
     }
 
     /// Registers a DEK for an entity.
+    ///
+    /// The previous key (if any) stays in the key history so data it
+    /// sealed can still be decrypted, and is carried along by
+    /// [`Self::entity_keys`] when the entity is re-sealed.
     pub async fn insert(&self, entity_id: String, dek: DerivedKey) {
-        self.deks.write().await.insert(entity_id, dek);
+        self.remember(&dek).await;
+        let current = dek_fingerprint(&dek);
+        let prior = self.deks.write().await.insert(entity_id.clone(), dek);
+        let prior = prior.map(|p| dek_fingerprint(&p)).into_iter().collect();
+        self.record_earlier(&entity_id, &current, prior).await;
+    }
+
+    /// Installs a key chain opened from an entity's envelope: the first
+    /// key becomes the entity's current key, the rest its earlier keys.
+    pub async fn install_chain(&self, entity_id: &str, chain: Vec<DerivedKey>) {
+        let Some((current, earlier)) = chain.split_first() else {
+            return;
+        };
+        for dek in earlier {
+            self.remember(dek).await;
+        }
+        let earlier = earlier.iter().map(dek_fingerprint).collect();
+        self.insert(entity_id.to_string(), current.clone()).await;
+        self.record_earlier(entity_id, &dek_fingerprint(current), earlier)
+            .await;
+    }
+
+    /// Returns the entity's own keys, current first, followed by every
+    /// per-entity key it used before. The workspace default DEK is never
+    /// included, so the result is safe to seal for share recipients.
+    pub async fn entity_keys(&self, entity_id: &str) -> Vec<DerivedKey> {
+        let mut keys: Vec<DerivedKey> =
+            self.deks.read().await.get(entity_id).cloned().into_iter().collect();
+        let ids = self.earlier.read().await.get(entity_id).cloned();
+        let history = self.key_history.read().await;
+        for id in ids.unwrap_or_default() {
+            keys.extend(history.get(&id).cloned());
+        }
+        keys
+    }
+
+    /// Puts `newer` in front of an entity's earlier keys, dropping
+    /// duplicates and the current key.
+    async fn record_earlier(&self, entity_id: &str, current: &str, newer: Vec<String>) {
+        let mut earlier = self.earlier.write().await;
+        let ids = earlier.entry(entity_id.to_string()).or_default();
+        let mut merged: Vec<String> = Vec::with_capacity(newer.len() + ids.len());
+        for id in newer.into_iter().chain(ids.drain(..)) {
+            if id != current && !merged.contains(&id) {
+                merged.push(id);
+            }
+        }
+        *ids = merged;
+    }
+
+    /// Generates a fresh DEK for an entity and installs it for new writes.
+    ///
+    /// Returns the new key so the caller can re-seal it for the remaining
+    /// recipients. Older keys remain available via [`Self::get_by_id`].
+    pub async fn rotate(&self, entity_id: &str) -> DerivedKey {
+        let dek = generate_random_key();
+        self.insert(entity_id.to_string(), dek.clone()).await;
+        dek
+    }
+
+    /// Returns the fingerprint of the key currently used for an entity.
+    pub async fn current_key_id(&self, entity_id: &str) -> CloudResult<String> {
+        Ok(dek_fingerprint(&self.get(entity_id).await?))
+    }
+
+    /// Looks up a historical key by its fingerprint.
+    pub async fn get_by_id(&self, key_id: &str) -> Option<DerivedKey> {
+        self.key_history.read().await.get(key_id).cloned()
+    }
+
+    /// Decrypts a [`KeyedCiphertext`] written for an entity.
+    ///
+    /// Tagged ciphertext is opened with the key named by its `key_id`;
+    /// untagged (legacy) ciphertext falls back to the entity's current key.
+    /// A `key_id` missing from the history is fetched from the
+    /// [`DekSource`], if one is set, before giving up.
+    pub async fn decrypt(&self, entity_id: &str, bytes: &[u8]) -> CloudResult<Vec<u8>> {
+        let keyed: KeyedCiphertext = serde_json::from_slice(bytes)?;
+        let dek = match &keyed.key_id {
+            Some(key_id) => match self.get_by_id(key_id).await {
+                Some(dek) => dek,
+                None => self.fetch_by_id(entity_id, key_id).await?,
+            },
+            None => self.get(entity_id).await?,
+        };
+        decrypt(&dek, &keyed.data)
+            .map_err(|e| CloudError::Envelope(format!("decryption failed: {e}")))
+    }
+
+    async fn fetch_by_id(&self, entity_id: &str, key_id: &str) -> CloudResult<DerivedKey> {
+        if let Some(source) = &self.source {
+            let chain = source.fetch_entity_keys(entity_id).await?;
+            self.install_chain(entity_id, chain).await;
+        }
+        self.get_by_id(key_id).await.ok_or_else(|| {
+            CloudError::Envelope(format!("unknown DEK {key_id} for entity {entity_id}"))
+        })
+    }
+
+    async fn remember(&self, dek: &DerivedKey) {
+        self.key_history
+            .write()
+            .await
+            .insert(dek_fingerprint(dek), dek.clone());
     }
 
     /// Retrieves a cloned DEK for an entity.
@@ -57,7 +208,9 @@ impl DekRegistry {
             })
     }
 
-    /// Removes a DEK (e.g. after entity deletion or DEK rotation).
+    /// Removes an entity's current DEK (e.g. after entity deletion).
+    ///
+    /// The key stays in the key history.
     pub async fn remove(&self, entity_id: &str) -> Option<DerivedKey> {
         self.deks.write().await.remove(entity_id)
     }
@@ -78,3 +231,59 @@ impl Default for DekRegistry {
         Self::new()
     }
 }
+
+/// Ciphertext tagged with the fingerprint of the DEK that sealed it.
+///
+/// Serializes as the plain [`EncryptedData`] JSON plus a `key_id` field,
+/// so untagged payloads written before key history existed still parse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyedCiphertext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    #[serde(flatten)]
+    pub data: EncryptedData,
+}
+
+/// Returns a stable, non-secret identifier for a DEK.
+pub fn dek_fingerprint(dek: &DerivedKey) -> String {
+    hex::encode(&Sha256::digest(dek.as_bytes())[..16])
+}
+
+/// Packs an entity's keys, current first, into the payload sealed in its
+/// share envelope.
+///
+/// A chain of one key is byte-identical to a bare DEK, so envelopes
+/// sealed before rotation carried earlier keys still decode.
+pub fn encode_dek_chain(keys: &[DerivedKey]) -> Vec<u8> {
+    keys.iter().flat_map(|k| k.as_bytes().iter().copied()).collect()
+}
+
+/// Unpacks a payload written by [`encode_dek_chain`].
+pub fn decode_dek_chain(bytes: &[u8]) -> CloudResult<Vec<DerivedKey>> {
+    if bytes.is_empty() || bytes.len() % KEY_SIZE != 0 {
+        return Err(CloudError::Envelope(format!(
+            "DEK chain of {} bytes is not a multiple of {KEY_SIZE}",
+            bytes.len()
+        )));
+    }
+    Ok(bytes
+        .chunks_exact(KEY_SIZE)
+        .map(|chunk| {
+            let mut key = [0u8; KEY_SIZE];
+            key.copy_from_slice(chunk);
+            DerivedKey::from_bytes(key)
+        })
+        .collect())
+}
+
+/// Encrypts `plaintext` under `dek` and serializes it as a
+/// [`KeyedCiphertext`] tagged with the key's fingerprint.
+pub fn encrypt_with_dek(dek: &DerivedKey, plaintext: &[u8]) -> CloudResult<Vec<u8>> {
+    let data = encrypt(dek, plaintext)
+        .map_err(|e| CloudError::Envelope(format!("encryption failed: {e}")))?;
+    let keyed = KeyedCiphertext {
+        key_id: Some(dek_fingerprint(dek)),
+        data,
+    };
+    Ok(serde_json::to_vec(&keyed)?)
+}
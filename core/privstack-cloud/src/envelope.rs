@@ -5,11 +5,14 @@
 //! received envelopes, and coordinating DEK rotation on revocation.
 
 use crate::api_client::CloudApiClient;
+use crate::dek_registry::{decode_dek_chain, DekSource};
 use crate::error::{CloudError, CloudResult};
+use async_trait::async_trait;
 use crypto_box::PublicKey;
 use privstack_crypto::envelope::{self as crypto_env, CloudKeyPair};
-use privstack_crypto::SealedEnvelope;
+use privstack_crypto::{DerivedKey, SealedEnvelope};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::debug;
 
 /// Manages envelope encryption for entity sharing.
@@ -83,8 +86,21 @@ impl EnvelopeManager {
     }
 
     /// Retrieves and opens a DEK envelope for an entity shared with us.
+    ///
+    /// Envelopes sealed by a rotation carry a key chain; see
+    /// [`decode_dek_chain`].
     pub async fn retrieve_and_open_dek(&self, entity_id: &str) -> CloudResult<Vec<u8>> {
         let envelope = self.api.get_share_key(entity_id).await?;
         self.open_dek(&envelope)
     }
 }
+
+/// Lets a [`crate::dek_registry::DekRegistry`] recover keys from before a
+/// rotation by opening the entity's current envelope.
+#[async_trait]
+impl DekSource for Mutex<EnvelopeManager> {
+    async fn fetch_entity_keys(&self, entity_id: &str) -> CloudResult<Vec<DerivedKey>> {
+        let payload = self.lock().await.retrieve_and_open_dek(entity_id).await?;
+        decode_dek_chain(&payload)
+    }
+}
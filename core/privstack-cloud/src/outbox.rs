@@ -5,10 +5,23 @@
 //! - **Solo mode**: 60s intervals or 50KB threshold (crash protection)
 //! - **Collab mode**: 5s intervals or 5KB threshold (near-real-time)
 //! - **Empty buffers**: Never flushed ($0.00 cost when idle)
+//!
+//! A durable outbox ([`Outbox::durable`]) also writes every event through
+//! to the local `cloud_outbox` table and only forgets it once the server
+//! has acknowledged the batch that carries it. Rows survive crashes and
+//! restarts and are replayed when the engine starts. Each event tracks its
+//! own delivery state: `pending` until it is assigned to a batch, then
+//! `in_flight` under a fixed S3 key and cursor range so that a retry
+//! re-sends exactly the same batch. Failed deliveries back off
+//! exponentially with jitter (see [`retry_delay`]).
 
+use crate::error::CloudError;
+use privstack_storage::{CloudOutboxRow, EntityStore};
 use privstack_types::Event;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
 /// Flush mode determined by collaboration context.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,6 +32,23 @@ pub enum FlushMode {
     Collab,
 }
 
+/// Fixed S3 key and cursor range an in-flight event was assigned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchAssignment {
+    pub batch_key: String,
+    pub cursor_start: i64,
+    pub cursor_end: i64,
+}
+
+/// Delivery bookkeeping for an event that has not been acknowledged yet.
+#[derive(Debug, Clone, Default)]
+struct DeliveryMeta {
+    attempts: u32,
+    /// Unix millis before which the event is not handed out again.
+    not_before: i64,
+    batch: Option<BatchAssignment>,
+}
+
 /// Adaptive event outbox that batches events before S3 upload.
 pub struct Outbox {
     pending_events: Vec<Event>,
@@ -26,6 +56,11 @@ pub struct Outbox {
     flush_mode: FlushMode,
     last_flush: Instant,
     collab_cooldown: Option<Instant>,
+    /// Delivery state for every unacknowledged event, keyed by event ID.
+    /// Entries outlive `take_pending` and are dropped by `mark_delivered`.
+    delivery: HashMap<String, DeliveryMeta>,
+    /// Write-through persistence; `None` for a purely in-memory outbox.
+    store: Option<(Arc<EntityStore>, String)>,
 }
 
 const SOLO_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
@@ -33,6 +68,35 @@ const COLLAB_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
 const SOLO_SIZE_THRESHOLD: usize = 50 * 1024; // 50KB
 const COLLAB_SIZE_THRESHOLD: usize = 5 * 1024; // 5KB
 const COLLAB_COOLDOWN: Duration = Duration::from_secs(300); // 5 min
+const RETRY_BASE_TRANSIENT: Duration = Duration::from_secs(2);
+const RETRY_BASE_PERMANENT: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(900); // 15 min
+
+/// Computes how long to wait before retrying a delivery that has now
+/// failed `attempts` times.
+///
+/// A server-provided `retry_after` wins. Otherwise the delay doubles per
+/// attempt from a short base for transient errors and a longer one for
+/// everything else, capped at 15 minutes, with "equal jitter" (half fixed,
+/// half random) so devices that failed together don't retry together.
+pub fn retry_delay(attempts: u32, err: &CloudError) -> Duration {
+    if let Some(after) = err.retry_after() {
+        return after;
+    }
+    let base = if err.is_transient() {
+        RETRY_BASE_TRANSIENT
+    } else {
+        RETRY_BASE_PERMANENT
+    };
+    let doublings = attempts.saturating_sub(1).min(16);
+    let capped = base.saturating_mul(1u32 << doublings).min(RETRY_MAX);
+    let half = capped / 2;
+    half + half.mul_f64(rand::random::<f64>())
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
 
 impl Outbox {
     pub fn new() -> Self {
@@ -42,11 +106,76 @@ impl Outbox {
             flush_mode: FlushMode::Solo,
             last_flush: Instant::now(),
             collab_cooldown: None,
+            delivery: HashMap::new(),
+            store: None,
+        }
+    }
+
+    /// Creates an outbox persisted in `store` for `workspace_id`, replaying
+    /// any events a previous run left undelivered.
+    ///
+    /// Replayed events are flushed on the next cycle rather than waiting
+    /// for the regular interval.
+    pub fn durable(store: Arc<EntityStore>, workspace_id: String) -> Self {
+        let rows = match store.load_cloud_outbox(&workspace_id) {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!("failed to load persisted cloud outbox: {e}");
+                Vec::new()
+            }
+        };
+        let mut outbox = Self::new();
+        let mut replayed = 0;
+        for row in rows {
+            match serde_json::from_str::<Event>(&row.event_json) {
+                Ok(event) => {
+                    outbox.delivery.insert(row.event_id.clone(), meta_from_row(&row));
+                    outbox.buffer(event);
+                    replayed += 1;
+                }
+                Err(e) => warn!("dropping unreadable outbox row {}: {e}", row.event_id),
+            }
         }
+        if replayed > 0 {
+            info!("replaying {replayed} undelivered cloud outbox events");
+            if let Some(t) = Instant::now().checked_sub(SOLO_FLUSH_INTERVAL) {
+                outbox.last_flush = t;
+            }
+        }
+        outbox.store = Some((store, workspace_id));
+        outbox
     }
 
     /// Adds an event to the outbox buffer.
+    ///
+    /// New events are persisted first when the outbox is durable. Pushing
+    /// an event back after a failed flush keeps its delivery state.
     pub fn push(&mut self, event: Event) {
+        let event_id = event.id.to_string();
+        if !self.delivery.contains_key(&event_id) {
+            if let Some((store, workspace_id)) = &self.store {
+                let persisted = serde_json::to_string(&event)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| {
+                        store
+                            .enqueue_cloud_outbox(
+                                workspace_id,
+                                &event_id,
+                                &event.entity_id.to_string(),
+                                &json,
+                            )
+                            .map_err(|e| e.to_string())
+                    });
+                if let Err(e) = persisted {
+                    warn!("failed to persist outbox event {event_id}: {e}");
+                }
+            }
+            self.delivery.insert(event_id, DeliveryMeta::default());
+        }
+        self.buffer(event);
+    }
+
+    fn buffer(&mut self, event: Event) {
         let size = serde_json::to_vec(&event).map(|v| v.len()).unwrap_or(128);
         self.pending_size += size;
         self.pending_events.push(event);
@@ -54,8 +183,8 @@ impl Outbox {
 
     /// Returns true if the outbox should flush now.
     pub fn should_flush(&self) -> bool {
-        // Never flush an empty buffer
-        if self.pending_events.is_empty() {
+        // Never flush an empty buffer, or one that is entirely backing off
+        if !self.has_due() {
             return false;
         }
 
@@ -86,11 +215,130 @@ impl Outbox {
         // Stay in Collab mode for 5 min after last collab activity
     }
 
-    /// Takes all pending events, resetting the buffer.
+    /// Takes all pending events except those of entities that are backing
+    /// off after a failed delivery.
+    ///
+    /// Backoff holds back a whole entity so that new events are never
+    /// batched ahead of a batch still waiting for its retry. Taken events
+    /// stay unacknowledged until [`Self::mark_delivered`]; a durable outbox
+    /// replays them after a crash.
     pub fn take_pending(&mut self) -> Vec<Event> {
         self.last_flush = Instant::now();
+        let waiting_entities = self.waiting_entities();
+        let (due, waiting): (Vec<Event>, Vec<Event>) = std::mem::take(&mut self.pending_events)
+            .into_iter()
+            .partition(|e| !waiting_entities.contains(&e.entity_id.to_string()));
         self.pending_size = 0;
-        std::mem::take(&mut self.pending_events)
+        for event in waiting {
+            self.buffer(event);
+        }
+        due
+    }
+
+    /// Returns the batch an event was assigned to by an earlier attempt.
+    pub fn assignment(&self, event: &Event) -> Option<&BatchAssignment> {
+        self.delivery
+            .get(&event.id.to_string())
+            .and_then(|m| m.batch.as_ref())
+    }
+
+    /// Marks events as in flight under `batch`, persisting the assignment
+    /// before anything is uploaded.
+    pub fn assign_batch(&mut self, events: &[Event], batch: BatchAssignment) {
+        let ids = event_ids(events);
+        if let Some((store, workspace_id)) = &self.store {
+            if let Err(e) = store.assign_cloud_outbox_batch(
+                workspace_id,
+                &ids,
+                &batch.batch_key,
+                batch.cursor_start,
+                batch.cursor_end,
+            ) {
+                warn!("failed to persist outbox batch {}: {e}", batch.batch_key);
+            }
+        }
+        for id in ids {
+            self.delivery.entry(id).or_default().batch = Some(batch.clone());
+        }
+    }
+
+    /// Records a failed delivery attempt and schedules the retry.
+    ///
+    /// The events are not re-buffered; push them back separately.
+    pub fn record_failure(&mut self, events: &[Event], err: &CloudError) {
+        let ids = event_ids(events);
+        let attempts = ids
+            .iter()
+            .filter_map(|id| self.delivery.get(id))
+            .map(|m| m.attempts)
+            .max()
+            .unwrap_or(0)
+            .saturating_add(1);
+        let not_before = now_millis() + retry_delay(attempts, err).as_millis() as i64;
+        if let Some((store, workspace_id)) = &self.store {
+            if let Err(e) = store.record_cloud_outbox_failure(
+                workspace_id,
+                &ids,
+                attempts,
+                not_before,
+                &err.to_string(),
+            ) {
+                warn!("failed to persist outbox failure: {e}");
+            }
+        }
+        for id in ids {
+            let meta = self.delivery.entry(id).or_default();
+            meta.attempts = attempts;
+            meta.not_before = not_before;
+        }
+    }
+
+    /// Forgets events whose batch the server has acknowledged.
+    pub fn mark_delivered(&mut self, events: &[Event]) {
+        let ids = event_ids(events);
+        if let Some((store, workspace_id)) = &self.store {
+            if let Err(e) = store.remove_cloud_outbox(workspace_id, &ids) {
+                warn!("failed to remove delivered outbox events: {e}");
+            }
+        }
+        for id in &ids {
+            self.delivery.remove(id);
+        }
+    }
+
+    /// Lets every buffered event be retried immediately (e.g. on a
+    /// user-initiated sync).
+    pub fn clear_backoff(&mut self) {
+        for meta in self.delivery.values_mut() {
+            meta.not_before = 0;
+        }
+    }
+
+    /// Returns the number of failed attempts recorded for an event.
+    pub fn attempts(&self, event: &Event) -> u32 {
+        self.delivery
+            .get(&event.id.to_string())
+            .map_or(0, |m| m.attempts)
+    }
+
+    fn waiting_entities(&self) -> HashSet<String> {
+        let now = now_millis();
+        self.pending_events
+            .iter()
+            .filter(|e| {
+                self.delivery
+                    .get(&e.id.to_string())
+                    .is_some_and(|m| m.not_before > now)
+            })
+            .map(|e| e.entity_id.to_string())
+            .collect()
+    }
+
+    fn has_due(&self) -> bool {
+        let waiting_entities = self.waiting_entities();
+        self.pending_events
+            .iter()
+            .any(|e| !waiting_entities.contains(&e.entity_id.to_string()))
     }
 
     /// Returns the number of pending events.
@@ -126,6 +374,26 @@ impl Outbox {
     }
 }
 
+fn meta_from_row(row: &CloudOutboxRow) -> DeliveryMeta {
+    let batch = match (&row.batch_key, row.cursor_start, row.cursor_end) {
+        (Some(batch_key), Some(cursor_start), Some(cursor_end)) => Some(BatchAssignment {
+            batch_key: batch_key.clone(),
+            cursor_start,
+            cursor_end,
+        }),
+        _ => None,
+    };
+    DeliveryMeta {
+        attempts: row.attempts,
+        not_before: row.next_attempt_at,
+        batch,
+    }
+}
+
+fn event_ids(events: &[Event]) -> Vec<String> {
+    events.iter().map(|e| e.id.to_string()).collect()
+}
+
 impl Default for Outbox {
     fn default() -> Self {
         Self::new()
@@ -2,13 +2,32 @@
 //!
 //! Orchestrates the sharing lifecycle by coordinating between the API client
 //! (share CRUD, limits) and the envelope manager (DEK encryption).
+//!
+//! Revoking a share rotates the entity DEK: a fresh key is sealed for the
+//! owner and every recipient the server still lists for the entity, then
+//! installed in the [`DekRegistry`] so later batches and snapshots use it.
+//! Each new envelope carries the keys the fresh one replaced, so earlier
+//! data stays readable to the remaining members after a restart. The
+//! revoked user keeps only the old keys.
 
 use crate::api_client::CloudApiClient;
+use crate::dek_registry::{dek_fingerprint, encode_dek_chain, DekRegistry};
 use crate::envelope::EnvelopeManager;
-use crate::error::CloudResult;
+use crate::error::{CloudError, CloudResult};
 use crate::types::*;
+use privstack_crypto::generate_random_key;
+use serde::Serialize;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{debug, info};
+
+/// Outcome of rotating an entity DEK.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DekRotation {
+    /// Fingerprint of the newly installed DEK.
+    pub key_id: String,
+    /// Number of envelopes sealed for the new DEK (owner included).
+    pub resealed: usize,
+}
 
 /// Orchestrates entity sharing workflows.
 pub struct ShareManager {
@@ -52,7 +71,10 @@ impl ShareManager {
         Ok(())
     }
 
-    /// Revokes a share. The caller must then rotate the entity DEK.
+    /// Revokes a share without touching the entity DEK.
+    ///
+    /// The revoked user can still decrypt anything written under the
+    /// current key; prefer [`Self::revoke_share_and_rotate`].
     pub async fn revoke_share(
         &self,
         entity_id: &str,
@@ -63,6 +85,69 @@ impl ShareManager {
         Ok(())
     }
 
+    /// Revokes a share and rotates the entity DEK for the remaining
+    /// recipients.
+    pub async fn revoke_share_and_rotate(
+        &self,
+        envelope_mgr: &EnvelopeManager,
+        dek_registry: &DekRegistry,
+        entity_id: &str,
+        recipient_email: &str,
+    ) -> CloudResult<DekRotation> {
+        self.revoke_share(entity_id, recipient_email).await?;
+        self.rotate_entity_dek(envelope_mgr, dek_registry, entity_id)
+            .await
+    }
+
+    /// Generates a new DEK for an entity and re-seals it for the owner and
+    /// every recipient of a share that is not revoked.
+    ///
+    /// The envelopes carry the new key followed by the entity's earlier
+    /// keys. The key is only installed in the registry once every envelope
+    /// has been stored, so a failed rotation leaves the current key in place.
+    pub async fn rotate_entity_dek(
+        &self,
+        envelope_mgr: &EnvelopeManager,
+        dek_registry: &DekRegistry,
+        entity_id: &str,
+    ) -> CloudResult<DekRotation> {
+        let owner_id = self.api.user_id().await.ok_or(CloudError::AuthRequired)?;
+        let shares = self.api.get_entity_shares(entity_id).await?;
+        let dek = generate_random_key();
+
+        let mut recipients = vec![owner_id];
+        for share in shares.iter().filter(|s| s.status != ShareStatus::Revoked) {
+            match share.recipient_user_id {
+                Some(user_id) if !recipients.contains(&user_id) => recipients.push(user_id),
+                Some(_) => {}
+                None => debug!(
+                    "share {} for entity {entity_id} has no recipient account yet",
+                    share.share_id
+                ),
+            }
+        }
+
+        let mut chain = vec![dek.clone()];
+        chain.extend(dek_registry.entity_keys(entity_id).await);
+        let payload = encode_dek_chain(&chain);
+        for &user_id in &recipients {
+            envelope_mgr
+                .create_and_store_envelope(entity_id, &payload, user_id)
+                .await?;
+        }
+
+        let key_id = dek_fingerprint(&dek);
+        dek_registry.insert(entity_id.to_string(), dek).await;
+        info!(
+            "rotated DEK for entity {entity_id} to {key_id} ({} envelopes)",
+            recipients.len()
+        );
+        Ok(DekRotation {
+            key_id,
+            resealed: recipients.len(),
+        })
+    }
+
     /// Gets all shares for an entity owned by the current user.
     pub async fn get_entity_shares(&self, entity_id: &str) -> CloudResult<Vec<ShareInfo>> {
         self.api.get_entity_shares(entity_id).await
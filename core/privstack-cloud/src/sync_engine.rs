@@ -11,14 +11,14 @@
 use crate::api_client::CloudApiClient;
 use crate::compaction::batch_s3_key;
 use crate::credential_manager::CredentialManager;
-use crate::dek_registry::DekRegistry;
+use crate::dek_registry::{encrypt_with_dek, DekRegistry};
 use crate::error::{CloudError, CloudResult};
-use crate::outbox::Outbox;
+use crate::outbox::{BatchAssignment, Outbox};
 use crate::s3_transport::S3Transport;
 use crate::types::*;
 
 use chrono::{DateTime, Utc};
-use privstack_crypto::{decrypt, encrypt, EncryptedData};
+use privstack_crypto::DerivedKey;
 use privstack_storage::EntityStore;
 use privstack_types::Event;
 use std::collections::HashMap;
@@ -171,7 +171,7 @@ pub fn create_cloud_sync_engine(
         transport,
         cred_manager,
         dek_registry,
-        outbox: Outbox::new(),
+        outbox: Outbox::durable(entity_store.clone(), workspace_id.clone()),
         command_rx,
         inbound_rx,
         event_tx,
@@ -307,6 +307,7 @@ impl CloudSyncEngine {
                                 warn!("force flush skipped: rate limited");
                                 continue;
                             }
+                            self.outbox.clear_backoff();
                             if !self.outbox.is_empty() {
                                 if let Err(e) = self.flush_outbox().await {
                                     if !e.is_rate_limited() {
@@ -350,6 +351,10 @@ impl CloudSyncEngine {
     }
 
     /// Flushes pending events as encrypted per-entity batches to S3.
+    ///
+    /// Events stay in the outbox until the server acknowledges their
+    /// batch. Each batch is assigned its S3 key and cursor range before
+    /// upload, and a retry re-sends the same batch under the same key.
     async fn flush_outbox(&mut self) -> CloudResult<()> {
         let events = self.outbox.take_pending();
         if events.is_empty() {
@@ -370,9 +375,7 @@ impl CloudSyncEngine {
         if entities_to_flush.len() > batch_size {
             let overflow = entities_to_flush.split_off(batch_size);
             for (_, events) in overflow {
-                for ev in events {
-                    self.outbox.push(ev);
-                }
+                self.requeue(events);
             }
             debug!(
                 "flush batch capped at {batch_size} entities, {} events re-queued for next cycle",
@@ -380,7 +383,15 @@ impl CloudSyncEngine {
             );
         }
 
-        let creds = self.cred_manager.get_credentials().await?;
+        let creds = match self.cred_manager.get_credentials().await {
+            Ok(creds) => creds,
+            Err(e) => {
+                for (_, events) in entities_to_flush {
+                    self.requeue(events);
+                }
+                return Err(e);
+            }
+        };
 
         let mut last_err: Option<CloudError> = None;
         let inter_delay = Duration::from_millis(self.rate_limits.inter_entity_delay_ms);
@@ -399,71 +410,46 @@ impl CloudSyncEngine {
                 Ok(dek) => dek,
                 Err(e) => {
                     warn!("skipping flush for entity {entity_id} (no DEK): {e}");
-                    for ev in entity_events {
-                        self.outbox.push(ev);
-                    }
+                    self.outbox.record_failure(&entity_events, &e);
+                    self.requeue(entity_events);
                     last_err = Some(e);
                     continue;
                 }
             };
 
-            let event_count = entity_events.len();
-            let cursor_start = self.cursors.get(&entity_id).copied().unwrap_or(0);
-            let cursor_end = cursor_start + event_count as i64;
-
-            let serialized = serde_json::to_vec(&entity_events)?;
-            let encrypted = encrypt(&dek, &serialized)
-                .map_err(|e| CloudError::Envelope(format!("batch encryption failed: {e}")))?;
-            let encrypted_bytes = serde_json::to_vec(&encrypted)?;
-
-            let s3_key = batch_s3_key(
-                self.user_id,
-                &self.workspace_id,
-                &entity_id,
-                cursor_start,
-                cursor_end,
-            );
-
-            self.transport
-                .upload(&creds, &s3_key, encrypted_bytes.clone())
-                .await?;
-
-            let cursor_req = AdvanceCursorRequest {
-                workspace_id: self.workspace_id.clone(),
-                device_id: self.device_id.clone(),
-                entity_id: entity_id.clone(),
-                cursor_position: cursor_end,
-                batch_key: s3_key,
-                size_bytes: encrypted_bytes.len() as u64,
-                event_count: event_count as u32,
-            };
-
-            match self.api.advance_cursor(&cursor_req).await {
-                Ok(()) => {
-                    self.cursors.insert(entity_id.clone(), cursor_end);
-                    if let Err(e) = self.entity_store.save_cloud_cursor(&entity_id, cursor_end) {
-                        warn!("failed to persist cursor for entity {entity_id}: {e}");
-                    }
-                    debug!("flushed {event_count} events for entity {entity_id} (cursor -> {cursor_end})");
-                }
-                Err(e) if e.is_rate_limited() => {
-                    warn!("rate limited during flush, re-queuing current + {} remaining entities", entities_to_flush.len());
-                    for ev in entity_events {
-                        self.outbox.push(ev);
+            let mut batches = self.plan_batches(&entity_id, entity_events).into_iter();
+            while let Some((batch, batch_events)) = batches.next() {
+                match self.send_batch(&creds, &entity_id, &dek, &batch, &batch_events).await {
+                    Ok(()) => {
+                        self.outbox.mark_delivered(&batch_events);
+                        debug!(
+                            "flushed {} events for entity {entity_id} (cursor -> {})",
+                            batch_events.len(),
+                            batch.cursor_end
+                        );
                     }
-                    for (_, remaining_events) in entities_to_flush {
-                        for ev in remaining_events {
-                            self.outbox.push(ev);
+                    Err(e) => {
+                        // Later batches for this entity depend on this one's
+                        // cursor range, so they wait for the retry too.
+                        self.outbox.record_failure(&batch_events, &e);
+                        self.requeue(batch_events);
+                        for (_, rest) in batches.by_ref() {
+                            self.requeue(rest);
+                        }
+                        if e.is_rate_limited() || e.is_transient() {
+                            warn!(
+                                "flush interrupted ({e}), re-queuing current + {} remaining entities",
+                                entities_to_flush.len()
+                            );
+                            for (_, remaining_events) in entities_to_flush {
+                                self.requeue(remaining_events);
+                            }
+                            return Err(e);
                         }
+                        warn!("flush failed for entity {entity_id}: {e}");
+                        last_err = Some(e);
+                        break;
                     }
-                    return Err(e);
-                }
-                Err(e) => {
-                    warn!("cursor advance failed for entity {entity_id}: {e}");
-                    for ev in entity_events {
-                        self.outbox.push(ev);
-                    }
-                    last_err = Some(e);
                 }
             }
         }
@@ -483,6 +469,103 @@ impl CloudSyncEngine {
         }
     }
 
+    /// Splits an entity's events into batches: first any batches assigned
+    /// by an earlier attempt (reusing their keys), then one new batch for
+    /// the rest. New batches are assigned and persisted here.
+    ///
+    /// Batches the cursor has already moved past were acknowledged before
+    /// a crash and are dropped without re-sending.
+    fn plan_batches(
+        &mut self,
+        entity_id: &str,
+        events: Vec<Event>,
+    ) -> Vec<(BatchAssignment, Vec<Event>)> {
+        let mut assigned: Vec<(BatchAssignment, Vec<Event>)> = Vec::new();
+        let mut fresh = Vec::new();
+        for event in events {
+            match self.outbox.assignment(&event).cloned() {
+                Some(batch) => match assigned.iter_mut().find(|(b, _)| *b == batch) {
+                    Some((_, group)) => group.push(event),
+                    None => assigned.push((batch, vec![event])),
+                },
+                None => fresh.push(event),
+            }
+        }
+        assigned.sort_by_key(|(b, _)| b.cursor_start);
+
+        let mut cursor = self.cursors.get(entity_id).copied().unwrap_or(0);
+        let mut batches = Vec::new();
+        for (batch, group) in assigned {
+            if batch.cursor_end <= cursor {
+                debug!("batch {} already acknowledged, dropping replayed events", batch.batch_key);
+                self.outbox.mark_delivered(&group);
+                continue;
+            }
+            cursor = batch.cursor_end;
+            batches.push((batch, group));
+        }
+
+        if !fresh.is_empty() {
+            let cursor_end = cursor + fresh.len() as i64;
+            let batch = BatchAssignment {
+                batch_key: batch_s3_key(
+                    self.user_id,
+                    &self.workspace_id,
+                    entity_id,
+                    cursor,
+                    cursor_end,
+                ),
+                cursor_start: cursor,
+                cursor_end,
+            };
+            self.outbox.assign_batch(&fresh, batch.clone());
+            batches.push((batch, fresh));
+        }
+        batches
+    }
+
+    /// Encrypts, uploads and acknowledges one batch, then advances the
+    /// local cursor.
+    async fn send_batch(
+        &mut self,
+        creds: &StsCredentials,
+        entity_id: &str,
+        dek: &DerivedKey,
+        batch: &BatchAssignment,
+        events: &[Event],
+    ) -> CloudResult<()> {
+        let serialized = serde_json::to_vec(events)?;
+        let encrypted_bytes = encrypt_with_dek(dek, &serialized)?;
+        let size_bytes = encrypted_bytes.len() as u64;
+
+        self.transport
+            .upload(creds, &batch.batch_key, encrypted_bytes)
+            .await?;
+
+        let cursor_req = AdvanceCursorRequest {
+            workspace_id: self.workspace_id.clone(),
+            device_id: self.device_id.clone(),
+            entity_id: entity_id.to_string(),
+            cursor_position: batch.cursor_end,
+            batch_key: batch.batch_key.clone(),
+            size_bytes,
+            event_count: events.len() as u32,
+        };
+        self.api.advance_cursor(&cursor_req).await?;
+
+        self.cursors.insert(entity_id.to_string(), batch.cursor_end);
+        if let Err(e) = self.entity_store.save_cloud_cursor(entity_id, batch.cursor_end) {
+            warn!("failed to persist cursor for entity {entity_id}: {e}");
+        }
+        Ok(())
+    }
+
+    fn requeue(&mut self, events: Vec<Event>) {
+        for ev in events {
+            self.outbox.push(ev);
+        }
+    }
+
     /// Polls for new data from other devices, decrypts, and applies it.
     async fn poll_and_apply(&mut self) -> CloudResult<()> {
         let pending = self
@@ -503,13 +586,10 @@ impl CloudSyncEngine {
         }
 
         for entity in &pending.pending {
-            let dek = match self.dek_registry.get(&entity.entity_id).await {
-                Ok(dek) => dek,
-                Err(e) => {
-                    warn!("skipping entity {} (no DEK available): {e}", entity.entity_id);
-                    continue;
-                }
-            };
+            if let Err(e) = self.dek_registry.get(&entity.entity_id).await {
+                warn!("skipping entity {} (no DEK available): {e}", entity.entity_id);
+                continue;
+            }
 
             // Fetch batch metadata for this entity from the server
             let batches = self
@@ -529,16 +609,9 @@ impl CloudSyncEngine {
                 let creds = self.cred_manager.get_credentials().await?;
                 let data = self.transport.download(&creds, &batch.s3_key).await?;
 
-                // Deserialize encrypted envelope and decrypt with entity DEK
-                let encrypted: EncryptedData = match serde_json::from_slice(&data) {
-                    Ok(enc) => enc,
-                    Err(e) => {
-                        warn!("failed to deserialize encrypted batch {}: {e}", batch.s3_key);
-                        continue;
-                    }
-                };
-
-                let plaintext = match decrypt(&dek, &encrypted) {
+                // Decrypt with the DEK that sealed the batch; older batches
+                // may predate a rotation and name a historical key.
+                let plaintext = match self.dek_registry.decrypt(&entity.entity_id, &data).await {
                     Ok(pt) => pt,
                     Err(e) => {
                         warn!("failed to decrypt batch {}: {e}", batch.s3_key);
@@ -106,6 +106,9 @@ pub struct ShareInfo {
     pub entity_type: String,
     pub entity_name: Option<String>,
     pub recipient_email: String,
+    /// Set once the recipient has an account; envelopes are sealed per user.
+    #[serde(default)]
+    pub recipient_user_id: Option<i64>,
     pub permission: SharePermission,
     pub status: ShareStatus,
     pub created_at: DateTime<Utc>,
@@ -0,0 +1,304 @@
+//! Tests for DEK rotation: key history, keyed ciphertext, key chains, and
+//! re-sealing the rotated key for remaining share recipients.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use privstack_cloud::api_client::CloudApiClient;
+use privstack_cloud::config::CloudConfig;
+use privstack_cloud::dek_registry::{
+    decode_dek_chain, dek_fingerprint, encode_dek_chain, encrypt_with_dek, DekRegistry,
+    KeyedCiphertext,
+};
+use privstack_cloud::envelope::EnvelopeManager;
+use privstack_cloud::error::CloudError;
+use privstack_cloud::sharing::ShareManager;
+use privstack_crypto::envelope::{generate_cloud_keypair, open_dek, CloudKeyPair};
+use privstack_crypto::{encrypt, generate_random_key, SealedEnvelope};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn setup() -> (MockServer, Arc<CloudApiClient>) {
+    let server = MockServer::start().await;
+    let config = CloudConfig {
+        api_base_url: server.uri(),
+        s3_bucket: "test".into(),
+        s3_region: "us-east-2".into(),
+        s3_endpoint_override: None,
+        credential_refresh_margin_secs: 60,
+        poll_interval_secs: 5,
+    };
+    let api = Arc::new(CloudApiClient::new(config));
+    api.set_tokens("at".into(), "rt".into(), 1).await;
+    (server, api)
+}
+
+async fn mount_public_key(server: &MockServer, user_id: i64, keypair: &CloudKeyPair) {
+    Mock::given(method("GET"))
+        .and(path(format!("/api/cloud/keys/public/{user_id}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            serde_json::json!({ "public_key": STANDARD.encode(keypair.public_bytes()) }),
+        ))
+        .mount(server)
+        .await;
+}
+
+async fn mount_shares(server: &MockServer, entity_id: &str, shares: serde_json::Value) {
+    Mock::given(method("GET"))
+        .and(path(format!("/api/share/entity/{entity_id}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            serde_json::json!({ "shares": shares }),
+        ))
+        .mount(server)
+        .await;
+}
+
+fn share(share_id: i64, email: &str, user_id: Option<i64>, status: &str) -> serde_json::Value {
+    serde_json::json!({
+        "share_id": share_id,
+        "entity_id": "ent-1",
+        "entity_type": "note",
+        "entity_name": null,
+        "recipient_email": email,
+        "recipient_user_id": user_id,
+        "permission": "read",
+        "status": status,
+        "created_at": "2026-01-01T00:00:00Z",
+        "accepted_at": null,
+    })
+}
+
+/// Returns (recipient_user_id, envelope) for every stored share key.
+async fn stored_envelopes(server: &MockServer) -> Vec<(i64, SealedEnvelope)> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/api/share/keys/store")
+        .map(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            let envelope =
+                serde_json::from_str(body["encrypted_dek"].as_str().unwrap()).unwrap();
+            (body["recipient_user_id"].as_i64().unwrap(), envelope)
+        })
+        .collect()
+}
+
+// ── registry key history ───────────────────────────────────────────────
+
+#[tokio::test]
+async fn rotate_installs_new_key_and_keeps_old_one() {
+    let registry = DekRegistry::new();
+    let old = generate_random_key();
+    registry.insert("ent-1".into(), old.clone()).await;
+
+    let new = registry.rotate("ent-1").await;
+    assert_ne!(new.as_bytes(), old.as_bytes());
+    assert_eq!(registry.get("ent-1").await.unwrap().as_bytes(), new.as_bytes());
+    assert_eq!(registry.current_key_id("ent-1").await.unwrap(), dek_fingerprint(&new));
+
+    let kept = registry.get_by_id(&dek_fingerprint(&old)).await.unwrap();
+    assert_eq!(kept.as_bytes(), old.as_bytes());
+}
+
+#[tokio::test]
+async fn ciphertext_from_before_rotation_still_decrypts() {
+    let registry = DekRegistry::new();
+    let old = generate_random_key();
+    registry.insert("ent-1".into(), old.clone()).await;
+    let before = encrypt_with_dek(&old, b"before").unwrap();
+
+    let new = registry.rotate("ent-1").await;
+    let after = encrypt_with_dek(&new, b"after").unwrap();
+
+    assert_eq!(registry.decrypt("ent-1", &before).await.unwrap(), b"before");
+    assert_eq!(registry.decrypt("ent-1", &after).await.unwrap(), b"after");
+}
+
+#[tokio::test]
+async fn new_ciphertext_is_tagged_with_current_key_id() {
+    let registry = DekRegistry::new();
+    let new = registry.rotate("ent-1").await;
+    let bytes = encrypt_with_dek(&new, b"payload").unwrap();
+
+    let keyed: KeyedCiphertext = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(keyed.key_id.as_deref(), Some(dek_fingerprint(&new).as_str()));
+}
+
+#[tokio::test]
+async fn untagged_legacy_ciphertext_uses_current_key() {
+    let registry = DekRegistry::new();
+    let dek = generate_random_key();
+    registry.set_default(dek.clone()).await;
+
+    let legacy = serde_json::to_vec(&encrypt(&dek, b"legacy").unwrap()).unwrap();
+    assert_eq!(registry.decrypt("ent-1", &legacy).await.unwrap(), b"legacy");
+}
+
+#[tokio::test]
+async fn entity_keys_lists_current_then_earlier_without_default() {
+    let registry = DekRegistry::new();
+    registry.set_default(generate_random_key()).await;
+    assert!(registry.entity_keys("ent-1").await.is_empty());
+
+    let first = generate_random_key();
+    registry.insert("ent-1".into(), first.clone()).await;
+    let second = registry.rotate("ent-1").await;
+
+    let keys: Vec<_> = registry
+        .entity_keys("ent-1")
+        .await
+        .iter()
+        .map(dek_fingerprint)
+        .collect();
+    assert_eq!(keys, vec![dek_fingerprint(&second), dek_fingerprint(&first)]);
+}
+
+#[test]
+fn single_key_chain_is_a_bare_dek() {
+    let dek = generate_random_key();
+    assert_eq!(encode_dek_chain(std::slice::from_ref(&dek)), dek.as_bytes().to_vec());
+
+    let decoded = decode_dek_chain(dek.as_bytes()).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].as_bytes(), dek.as_bytes());
+    assert!(decode_dek_chain(&[0u8; 40]).is_err());
+}
+
+#[tokio::test]
+async fn unknown_key_id_is_an_envelope_error() {
+    let registry = DekRegistry::new();
+    registry.rotate("ent-1").await;
+    let stranger = generate_random_key();
+    let bytes = encrypt_with_dek(&stranger, b"secret").unwrap();
+
+    let err = registry.decrypt("ent-1", &bytes).await.unwrap_err();
+    assert!(matches!(err, CloudError::Envelope(msg) if msg.contains("unknown DEK")));
+}
+
+// ── rotate_entity_dek / revoke_share_and_rotate ────────────────────────
+
+#[tokio::test]
+async fn revoke_and_rotate_reseals_for_owner_and_remaining_recipients() {
+    let (server, api) = setup().await;
+    let owner = generate_cloud_keypair();
+    let carol = generate_cloud_keypair();
+    mount_public_key(&server, 1, &owner).await;
+    mount_public_key(&server, 7, &carol).await;
+    mount_shares(
+        &server,
+        "ent-1",
+        serde_json::json!([
+            share(1, "bob@example.com", Some(9), "revoked"),
+            share(2, "carol@example.com", Some(7), "accepted"),
+            share(3, "dave@example.com", None, "pending"),
+        ]),
+    )
+    .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/share/revoke"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/share/keys/store"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let registry = DekRegistry::new();
+    let old = generate_random_key();
+    registry.insert("ent-1".into(), old.clone()).await;
+
+    let envelope_mgr = EnvelopeManager::new(api.clone());
+    let share_mgr = ShareManager::new(api);
+    let rotation = share_mgr
+        .revoke_share_and_rotate(&envelope_mgr, &registry, "ent-1", "bob@example.com")
+        .await
+        .unwrap();
+
+    assert_eq!(rotation.resealed, 2);
+    assert_eq!(registry.current_key_id("ent-1").await.unwrap(), rotation.key_id);
+    assert_ne!(rotation.key_id, dek_fingerprint(&old));
+
+    let new = registry.get("ent-1").await.unwrap();
+    let expected = encode_dek_chain(&[new, old]);
+    let mut sealed_for = Vec::new();
+    for (user_id, envelope) in stored_envelopes(&server).await {
+        let secret = if user_id == 1 { &owner.secret } else { &carol.secret };
+        assert_eq!(open_dek(&envelope, secret).unwrap(), expected);
+        sealed_for.push(user_id);
+    }
+    sealed_for.sort();
+    assert_eq!(sealed_for, vec![1, 7]);
+}
+
+#[tokio::test]
+async fn fresh_registry_decrypts_batch_from_before_rotation() {
+    let (server, api) = setup().await;
+    let owner = generate_cloud_keypair();
+    mount_public_key(&server, 1, &owner).await;
+    mount_shares(&server, "ent-1", serde_json::json!([])).await;
+    Mock::given(method("POST"))
+        .and(path("/api/share/keys/store"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+        .mount(&server)
+        .await;
+
+    let registry = DekRegistry::new();
+    let old = generate_random_key();
+    registry.insert("ent-1".into(), old.clone()).await;
+    let before = encrypt_with_dek(&old, b"before").unwrap();
+
+    let mut envelope_mgr = EnvelopeManager::new(api.clone());
+    envelope_mgr.set_keypair(owner);
+    ShareManager::new(api)
+        .rotate_entity_dek(&envelope_mgr, &registry, "ent-1")
+        .await
+        .unwrap();
+
+    // The server now hands out the envelope sealed by the rotation.
+    let (_, envelope) = stored_envelopes(&server).await.pop().unwrap();
+    Mock::given(method("GET"))
+        .and(path("/api/share/keys/ent-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&envelope))
+        .mount(&server)
+        .await;
+
+    let restarted = DekRegistry::with_source(Arc::new(Mutex::new(envelope_mgr)));
+    assert_eq!(restarted.decrypt("ent-1", &before).await.unwrap(), b"before");
+    assert_eq!(
+        restarted.current_key_id("ent-1").await.unwrap(),
+        registry.current_key_id("ent-1").await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn failed_reseal_leaves_current_key_in_place() {
+    let (server, api) = setup().await;
+    let owner = generate_cloud_keypair();
+    mount_public_key(&server, 1, &owner).await;
+    mount_shares(&server, "ent-1", serde_json::json!([])).await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/share/keys/store"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let registry = DekRegistry::new();
+    let old = generate_random_key();
+    registry.insert("ent-1".into(), old.clone()).await;
+
+    let envelope_mgr = EnvelopeManager::new(api.clone());
+    let share_mgr = ShareManager::new(api);
+    assert!(share_mgr
+        .rotate_entity_dek(&envelope_mgr, &registry, "ent-1")
+        .await
+        .is_err());
+    assert_eq!(registry.current_key_id("ent-1").await.unwrap(), dek_fingerprint(&old));
+}
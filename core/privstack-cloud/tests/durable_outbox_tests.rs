@@ -0,0 +1,291 @@
+//! Crash-injection tests for the durable cloud outbox.
+//!
+//! A wiremock server stands in for both the PrivStack API and S3 (path-style
+//! PUTs). The "crash" is an aborted engine task; "restart" reopens the same
+//! on-disk entity store and builds a fresh engine on top of it.
+
+use privstack_cloud::api_client::CloudApiClient;
+use privstack_cloud::compaction::batch_s3_key;
+use privstack_cloud::config::CloudConfig;
+use privstack_cloud::credential_manager::CredentialManager;
+use privstack_cloud::dek_registry::DekRegistry;
+use privstack_cloud::s3_transport::S3Transport;
+use privstack_cloud::sync_engine::{create_cloud_sync_engine, CloudSyncHandle};
+use privstack_crypto::generate_random_key;
+use privstack_storage::EntityStore;
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const WORKSPACE: &str = "ws-1";
+const BUCKET: &str = "test-bucket";
+
+struct Running {
+    handle: CloudSyncHandle,
+    inbound: mpsc::Sender<Event>,
+    task: JoinHandle<()>,
+    store: Arc<EntityStore>,
+}
+
+async fn mount_s3_and_credentials(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/api/cloud/credentials"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_key_id": "ak",
+            "secret_access_key": "sk",
+            "session_token": "st",
+            "expiration": "2099-01-01T00:00:00Z",
+            "bucket": BUCKET,
+            "region": "us-east-2",
+        })))
+        .mount(server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path_regex(format!("^/{BUCKET}/")))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(server)
+        .await;
+}
+
+async fn mount_advance(server: &MockServer, status: u16) {
+    Mock::given(method("POST"))
+        .and(path("/api/cloud/cursors/advance"))
+        .respond_with(ResponseTemplate::new(status).set_body_json(serde_json::json!({})))
+        .mount(server)
+        .await;
+}
+
+async fn start(server: &MockServer, db: &Path) -> Running {
+    let config = CloudConfig {
+        api_base_url: server.uri(),
+        s3_bucket: BUCKET.into(),
+        s3_region: "us-east-2".into(),
+        s3_endpoint_override: Some(server.uri()),
+        credential_refresh_margin_secs: 60,
+        poll_interval_secs: 3600,
+    };
+    let api = Arc::new(CloudApiClient::new(config));
+    api.set_tokens("at".into(), "rt".into(), 1).await;
+    let transport = Arc::new(S3Transport::new(
+        BUCKET.into(),
+        "us-east-2".into(),
+        Some(server.uri()),
+    ));
+    let cred_manager = Arc::new(CredentialManager::new(api.clone(), WORKSPACE.into(), 60));
+    let dek_registry = DekRegistry::new();
+    dek_registry.set_default(generate_random_key()).await;
+    let store = Arc::new(EntityStore::open(db).unwrap());
+    let (event_tx, _event_rx) = mpsc::channel(16);
+
+    let (handle, inbound, mut engine) = create_cloud_sync_engine(
+        api,
+        transport,
+        cred_manager,
+        dek_registry,
+        event_tx,
+        1,
+        WORKSPACE.into(),
+        "device-1".into(),
+        Duration::from_secs(3600),
+        store.clone(),
+    );
+    let task = tokio::spawn(async move { engine.run().await });
+    Running {
+        handle,
+        inbound,
+        task,
+        store,
+    }
+}
+
+/// Kills the engine without letting it flush on the way out.
+async fn crash(running: Running) -> Arc<EntityStore> {
+    running.task.abort();
+    let _ = running.task.await;
+    running.store
+}
+
+fn event(entity_id: EntityId) -> Event {
+    Event::new(
+        entity_id,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityCreated {
+            entity_type: "note".into(),
+            json_data: "{}".into(),
+        },
+    )
+}
+
+async fn wait_until<F: Fn() -> bool>(what: &str, cond: F) {
+    for _ in 0..200 {
+        if cond() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+/// Returns the `s3_key` of every cursor-advance request, in order.
+async fn advanced_keys(server: &MockServer) -> Vec<String> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/api/cloud/cursors/advance")
+        .map(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            body["s3_key"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+async fn wait_for_advances(server: &MockServer, n: usize) -> Vec<String> {
+    for _ in 0..200 {
+        let keys = advanced_keys(server).await;
+        if keys.len() >= n {
+            return keys;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("timed out waiting for {n} cursor advances");
+}
+
+#[tokio::test]
+async fn events_pushed_before_a_crash_are_delivered_after_restart() {
+    let server = MockServer::start().await;
+    mount_s3_and_credentials(&server).await;
+    mount_advance(&server, 200).await;
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("data.db");
+
+    let running = start(&server, &db).await;
+    let entity = EntityId::new();
+    running.inbound.send(event(entity)).await.unwrap();
+    running.inbound.send(event(entity)).await.unwrap();
+    let store = running.store.clone();
+    wait_until("events persisted", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().len() == 2
+    })
+    .await;
+    crash(running).await;
+    assert!(advanced_keys(&server).await.is_empty());
+
+    let running = start(&server, &db).await;
+    running.handle.force_flush().await.unwrap();
+    let keys = wait_for_advances(&server, 1).await;
+    assert_eq!(keys, [batch_s3_key(1, WORKSPACE, &entity.to_string(), 0, 2)]);
+
+    let store = running.store.clone();
+    wait_until("outbox drained", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().is_empty()
+    })
+    .await;
+    crash(running).await;
+}
+
+#[tokio::test]
+async fn failed_flush_then_crash_retries_with_the_same_batch_key() {
+    let server = MockServer::start().await;
+    mount_s3_and_credentials(&server).await;
+    mount_advance(&server, 500).await;
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("data.db");
+
+    let running = start(&server, &db).await;
+    let entity = EntityId::new();
+    running.inbound.send(event(entity)).await.unwrap();
+    let store = running.store.clone();
+    wait_until("event persisted", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().len() == 1
+    })
+    .await;
+    running.handle.force_flush().await.unwrap();
+    let failed = wait_for_advances(&server, 1).await;
+    wait_until("failure recorded", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap()[0].attempts == 1
+    })
+    .await;
+    let store = crash(running).await;
+
+    let row = &store.load_cloud_outbox(WORKSPACE).unwrap()[0];
+    assert_eq!(row.state, "in_flight");
+    assert_eq!(row.batch_key.as_deref(), Some(failed[0].as_str()));
+    assert!(row.next_attempt_at > 0);
+    assert!(row.last_error.is_some());
+
+    server.reset().await;
+    mount_s3_and_credentials(&server).await;
+    mount_advance(&server, 200).await;
+
+    let running = start(&server, &db).await;
+    // A new edit made after the restart goes into a later batch.
+    running.inbound.send(event(entity)).await.unwrap();
+    let store = running.store.clone();
+    wait_until("new event persisted", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().len() == 2
+    })
+    .await;
+    running.handle.force_flush().await.unwrap();
+    let keys = wait_for_advances(&server, 2).await;
+    let entity_key = entity.to_string();
+    assert_eq!(keys[0], failed[0], "retry must reuse the assigned key");
+    assert_eq!(keys[0], batch_s3_key(1, WORKSPACE, &entity_key, 0, 1));
+    assert_eq!(keys[1], batch_s3_key(1, WORKSPACE, &entity_key, 1, 2));
+
+    wait_until("outbox drained", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().is_empty()
+    })
+    .await;
+    assert_eq!(
+        store.load_cloud_cursors().unwrap().into_iter().find(|(k, _)| *k == entity.to_string()),
+        Some((entity.to_string(), 2))
+    );
+    crash(running).await;
+}
+
+#[tokio::test]
+async fn acknowledged_batch_left_in_outbox_is_not_resent() {
+    let server = MockServer::start().await;
+    mount_s3_and_credentials(&server).await;
+    mount_advance(&server, 200).await;
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("data.db");
+
+    // Crash window: the server acknowledged the batch and the cursor was
+    // saved, but the process died before the rows were removed.
+    let entity = EntityId::new();
+    {
+        let store = EntityStore::open(&db).unwrap();
+        let ev = event(entity);
+        store
+            .enqueue_cloud_outbox(
+                WORKSPACE,
+                &ev.id.to_string(),
+                &entity.to_string(),
+                &serde_json::to_string(&ev).unwrap(),
+            )
+            .unwrap();
+        store
+            .assign_cloud_outbox_batch(WORKSPACE, &[ev.id.to_string()], "acked-key", 0, 1)
+            .unwrap();
+        store.save_cloud_cursor(&entity.to_string(), 1).unwrap();
+    }
+
+    let running = start(&server, &db).await;
+    running.handle.force_flush().await.unwrap();
+    let store = running.store.clone();
+    wait_until("stale rows dropped", || {
+        store.load_cloud_outbox(WORKSPACE).unwrap().is_empty()
+    })
+    .await;
+    assert!(advanced_keys(&server).await.is_empty());
+    crash(running).await;
+}
@@ -1,5 +1,9 @@
-use privstack_cloud::outbox::{FlushMode, Outbox};
+use privstack_cloud::error::CloudError;
+use privstack_cloud::outbox::{retry_delay, BatchAssignment, FlushMode, Outbox};
+use privstack_storage::EntityStore;
 use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::sync::Arc;
+use std::time::Duration;
 
 fn make_event() -> Event {
     Event::new(
@@ -133,3 +137,94 @@ fn should_flush_solo_just_pushed_returns_false() {
     // Solo mode: 60s interval, small buffer — should not flush
     assert!(!outbox.should_flush());
 }
+
+// ── Delivery state & backoff ───────────────────────────────────────────
+
+fn event_for(entity_id: EntityId) -> Event {
+    Event::new(
+        entity_id,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityCreated {
+            entity_type: "test".into(),
+            json_data: "{}".into(),
+        },
+    )
+}
+
+#[test]
+fn retry_delay_honors_retry_after() {
+    let err = CloudError::RateLimited { retry_after_secs: 42 };
+    assert_eq!(retry_delay(1, &err), Duration::from_secs(42));
+    assert_eq!(retry_delay(9, &err), Duration::from_secs(42));
+}
+
+#[test]
+fn retry_delay_grows_with_jitter_and_caps() {
+    let transient = CloudError::S3("503 service error".into());
+    let permanent = CloudError::Api("500 internal".into());
+
+    let first = retry_delay(1, &transient);
+    assert!(first >= Duration::from_secs(1) && first <= Duration::from_secs(2));
+    let fourth = retry_delay(4, &transient);
+    assert!(fourth >= Duration::from_secs(8) && fourth <= Duration::from_secs(16));
+    assert!(retry_delay(1, &permanent) >= Duration::from_secs(15));
+    assert!(retry_delay(60, &permanent) <= Duration::from_secs(900));
+}
+
+#[test]
+fn failed_entity_is_held_back_until_backoff_clears() {
+    let mut outbox = Outbox::new();
+    let failing = EntityId::new();
+    let healthy = EntityId::new();
+    let first = event_for(failing);
+    outbox.push(first.clone());
+
+    let taken = outbox.take_pending();
+    outbox.record_failure(&taken, &CloudError::Api("500".into()));
+    for ev in taken {
+        outbox.push(ev);
+    }
+    // A new event for the failing entity waits with it; others flow.
+    outbox.push(event_for(failing));
+    outbox.push(event_for(healthy));
+
+    let taken = outbox.take_pending();
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].entity_id, healthy);
+    assert_eq!(outbox.pending_count(), 2);
+    assert_eq!(outbox.attempts(&first), 1);
+
+    outbox.clear_backoff();
+    assert_eq!(outbox.take_pending().len(), 2);
+}
+
+#[test]
+fn durable_outbox_replays_events_and_assignments() {
+    let store = Arc::new(EntityStore::open_in_memory().unwrap());
+    let delivered = make_event();
+    let in_flight = make_event();
+    let batch = BatchAssignment {
+        batch_key: "batch-key".into(),
+        cursor_start: 0,
+        cursor_end: 1,
+    };
+    {
+        let mut outbox = Outbox::durable(store.clone(), "ws".into());
+        outbox.push(delivered.clone());
+        outbox.push(in_flight.clone());
+        let taken = outbox.take_pending();
+        outbox.assign_batch(&taken[1..], batch.clone());
+        outbox.mark_delivered(&taken[..1]);
+        // dropped without re-queuing: simulates a crash mid-flush
+    }
+
+    let mut outbox = Outbox::durable(store.clone(), "ws".into());
+    assert_eq!(outbox.pending_count(), 1);
+    assert!(outbox.should_flush(), "replayed events flush without waiting");
+    let replayed = outbox.take_pending();
+    assert_eq!(replayed[0].id, in_flight.id);
+    assert_eq!(outbox.assignment(&replayed[0]), Some(&batch));
+
+    assert!(Outbox::durable(store, "other-ws".into()).is_empty());
+}
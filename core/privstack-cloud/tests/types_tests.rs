@@ -113,6 +113,7 @@ fn share_info_roundtrip() {
         entity_type: "note".into(),
         entity_name: Some("My Note".into()),
         recipient_email: "bob@example.com".into(),
+        recipient_user_id: None,
         permission: SharePermission::Read,
         status: ShareStatus::Pending,
         created_at: Utc::now(),
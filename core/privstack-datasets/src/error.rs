@@ -17,9 +17,21 @@ pub enum DatasetError {
     #[error("Import failed: {0}")]
     ImportFailed(String),
 
+    #[error("Export failed: {0}")]
+    ExportFailed(String),
+
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
 
+    #[error("Not authorized: {0}")]
+    NotAuthorized(String),
+
+    #[error("Constraint violated: {}", describe_violations(.0))]
+    ConstraintViolated(Vec<crate::types::ConstraintViolation>),
+
+    #[error("Query timed out after {0} ms")]
+    QueryTimeout(u64),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -31,3 +43,12 @@ pub enum DatasetError {
 }
 
 pub type DatasetResult<T> = Result<T, DatasetError>;
+
+/// The first violation, and how many more there are.
+fn describe_violations(violations: &[crate::types::ConstraintViolation]) -> String {
+    match violations {
+        [] => "no details".to_string(),
+        [only] => only.to_string(),
+        [first, rest @ ..] => format!("{first} (and {} more)", rest.len()),
+    }
+}
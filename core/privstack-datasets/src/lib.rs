@@ -7,8 +7,9 @@
 //! # Architecture
 //!
 //! Unlike the entity system (which encrypts all data), datasets are stored
-//! as plain SQLite tables for maximum query performance. Each imported CSV
-//! becomes a native SQLite table named `ds_<uuid>`.
+//! as plain SQLite tables for maximum query performance. Each imported file
+//! (CSV, TSV, JSON, XLSX or Parquet) becomes a native SQLite table named
+//! `ds_<uuid>`.
 
 mod error;
 mod schema;
@@ -17,10 +18,14 @@ mod types;
 
 pub use error::{DatasetError, DatasetResult};
 pub use schema::{dataset_table_name, initialize_datasets_schema};
-pub use store::DatasetStore;
+pub use store::{DatasetStore, EntitySource};
 pub use types::{
-    ColumnDef, DatasetColumn, DatasetColumnType, DatasetId, DatasetMeta, DatasetQueryResult,
-    DatasetRelation, DatasetView, FilterOperator, MutationResult, PreprocessedSql, RelationType,
-    RowPageLink, SavedQuery, SortDirection, SqlExecutionResult, StatementType, ViewConfig,
-    ViewFilter, ViewSort,
+    Aggregation, ColumnConstraints, ColumnDef, ConstraintKind, ConstraintViolation, DatasetChange,
+    DatasetColumn, DatasetColumnType, DatasetId, DatasetMeta, DatasetOp, DatasetQueryResult,
+    DatasetRelation, DatasetView, DateBucket, EntityTable, ExportSource, FileFormat, FilterNode,
+    FilterOperator, FormulaColumn, HistoryEntry, HistoryEntryKind, HistoryLimits, ImportOptions,
+    ImportProgress, Materialization, MutationResult, OpStamp, PivotAggregation, PivotConfig,
+    PivotDimension, PivotMeasure, PivotResult, PivotRow, PivotRowKind, PreprocessedSql,
+    RefreshPolicy, RelationType, RowPageLink, SavedQuery, SortDirection, SqlExecutionResult,
+    SqlLimits, StatementType, ValidationReport, ViewConfig, ViewFilter, ViewSort,
 };
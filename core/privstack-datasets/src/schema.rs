@@ -70,6 +70,100 @@ CREATE TABLE IF NOT EXISTS _dataset_saved_queries (
 );
 "#;
 
+/// Replication state DDL — stable row and column IDs, the stamps that decide
+/// concurrent edits, and the journal of local changes not yet published.
+/// A dataset is replicated once it has a `_dataset_repl_datasets` row.
+const DATASET_REPLICATION_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS _dataset_repl_datasets (
+    dataset_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    category TEXT,
+    meta_stamp TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS _dataset_repl_columns (
+    dataset_id TEXT NOT NULL,
+    column_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    name_stamp TEXT NOT NULL,
+    physical TEXT,
+    column_type TEXT NOT NULL DEFAULT 'TEXT',
+    dropped INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (dataset_id, column_id)
+);
+CREATE TABLE IF NOT EXISTS _dataset_repl_rows (
+    dataset_id TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    row_ref INTEGER,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (dataset_id, row_id)
+);
+CREATE INDEX IF NOT EXISTS idx_dataset_repl_rows_ref ON _dataset_repl_rows(dataset_id, row_ref);
+CREATE TABLE IF NOT EXISTS _dataset_repl_cells (
+    dataset_id TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    column_id TEXT NOT NULL,
+    stamp TEXT NOT NULL,
+    PRIMARY KEY (dataset_id, row_id, column_id)
+);
+CREATE TABLE IF NOT EXISTS _dataset_repl_outbox (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    dataset_id TEXT NOT NULL,
+    op_json TEXT NOT NULL,
+    stamp_json TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+"#;
+
+/// Change history DDL — each dataset's undo/redo journal. An edit keeps what
+/// reverses it: row pre-images in `image_json`, or a copy of the whole table
+/// named by `snapshot_table`.
+const DATASET_HISTORY_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS _dataset_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    dataset_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    label TEXT NOT NULL,
+    image_json TEXT,
+    snapshot_table TEXT,
+    undone INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_dataset_history_dataset ON _dataset_history(dataset_id, id);
+"#;
+
+/// Materialized saved queries DDL — each materialized query's rows cached in
+/// `cache_table`, and the datasets it reads. Every change to a dataset
+/// touches its `_datasets_meta` row, so triggers there mark the
+/// materializations reading it stale.
+const DATASET_MATERIALIZATIONS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS _dataset_materializations (
+    query_id TEXT PRIMARY KEY,
+    cache_table TEXT NOT NULL,
+    columns_json TEXT NOT NULL,
+    row_count INTEGER NOT NULL,
+    refreshed_at INTEGER NOT NULL,
+    stale INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS _dataset_materialization_sources (
+    query_id TEXT NOT NULL,
+    dataset_id TEXT NOT NULL,
+    PRIMARY KEY (query_id, dataset_id)
+);
+CREATE INDEX IF NOT EXISTS idx_dataset_materialization_sources_dataset
+    ON _dataset_materialization_sources(dataset_id);
+CREATE TRIGGER IF NOT EXISTS _dataset_materializations_stale_on_update
+AFTER UPDATE ON _datasets_meta BEGIN
+    UPDATE _dataset_materializations SET stale = 1 WHERE stale = 0 AND query_id IN
+        (SELECT query_id FROM _dataset_materialization_sources WHERE dataset_id = OLD.id);
+END;
+CREATE TRIGGER IF NOT EXISTS _dataset_materializations_stale_on_delete
+AFTER DELETE ON _datasets_meta BEGIN
+    UPDATE _dataset_materializations SET stale = 1 WHERE stale = 0 AND query_id IN
+        (SELECT query_id FROM _dataset_materialization_sources WHERE dataset_id = OLD.id);
+END;
+"#;
+
 /// Initialize all dataset schema tables.
 pub fn initialize_datasets_schema(conn: &Connection) -> DatasetResult<()> {
     conn.execute_batch(DATASETS_META_DDL)?;
@@ -77,6 +171,9 @@ pub fn initialize_datasets_schema(conn: &Connection) -> DatasetResult<()> {
     conn.execute_batch(DATASET_ROW_PAGES_DDL)?;
     conn.execute_batch(DATASET_VIEWS_DDL)?;
     conn.execute_batch(DATASET_SAVED_QUERIES_DDL)?;
+    conn.execute_batch(DATASET_REPLICATION_DDL)?;
+    conn.execute_batch(DATASET_HISTORY_DDL)?;
+    conn.execute_batch(DATASET_MATERIALIZATIONS_DDL)?;
 
     // Migrations — use privstack_db helpers for safe ADD COLUMN
     privstack_db::add_column_if_not_exists(
@@ -86,6 +183,36 @@ pub fn initialize_datasets_schema(conn: &Connection) -> DatasetResult<()> {
         "INTEGER DEFAULT 0",
     )?;
     privstack_db::add_column_if_not_exists(conn, "_datasets_meta", "category", "TEXT")?;
+    privstack_db::add_column_if_not_exists(
+        conn,
+        "_datasets_meta",
+        "formulas_json",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    privstack_db::add_column_if_not_exists(
+        conn,
+        "_dataset_repl_datasets",
+        "formulas_json",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    privstack_db::add_column_if_not_exists(
+        conn,
+        "_datasets_meta",
+        "constraints_json",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    privstack_db::add_column_if_not_exists(
+        conn,
+        "_dataset_repl_datasets",
+        "constraints_json",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    privstack_db::add_column_if_not_exists(
+        conn,
+        "_dataset_saved_queries",
+        "materialized_json",
+        "TEXT",
+    )?;
     Ok(())
 }
 
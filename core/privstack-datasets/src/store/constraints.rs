@@ -0,0 +1,655 @@
+//! Column constraints: rules on the values of a dataset's stored columns.
+//!
+//! Constraints live in `_datasets_meta.constraints_json`. They are checked
+//! after each write, against the rows the write touched: `insert_row` and
+//! `update_cell` know their row, SQL mutations find theirs through
+//! temporary triggers that note each row they insert or update, and imports
+//! check every row. A write whose rows break a rule is rolled back and fails
+//! with [`DatasetError::ConstraintViolated`].
+//!
+//! Rows are only checked when written, so data from before a constraint was
+//! set may break it; [`DatasetStore::validate_dataset`] reports such values.
+
+use super::helpers::{introspect_columns, now_millis, row_value_to_json, sanitize_identifier};
+use super::replication::{dataset_id_for_table, table_exists};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{
+    ColumnConstraints, ConstraintKind, ConstraintViolation, DatasetColumn, DatasetColumnType,
+    DatasetId, ValidationReport,
+};
+use privstack_db::rusqlite::types::Value as SqlValue;
+use privstack_db::rusqlite::{params, Connection, OptionalExtension};
+use regex_lite::Regex;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Prefix of the temporary triggers that note rows written by SQL.
+const CHANGE_TRIGGER_PREFIX: &str = "constraint_changes_";
+
+impl DatasetStore {
+    /// Set the constraints of a stored column, replacing any it had.
+    /// Constraints without rules remove them. Existing values are not
+    /// checked; see [`Self::validate_dataset`].
+    pub fn set_column_constraints(
+        &self,
+        id: &DatasetId,
+        constraints: &ColumnConstraints,
+    ) -> DatasetResult<()> {
+        let conn = self.lock_conn();
+        let stored = introspect_columns(&conn, &dataset_table_name(id))?;
+        let mut all = load_constraints(&conn, id)?;
+        all.retain(|c| !c.column.eq_ignore_ascii_case(&constraints.column));
+        if !constraints.is_empty() {
+            all.push(check_constraints(&conn, id, &stored, constraints)?);
+        }
+        save_constraints(&conn, id, &all)?;
+        self.record_meta_changed(&conn, id)
+    }
+
+    /// List a dataset's column constraints.
+    pub fn list_column_constraints(&self, id: &DatasetId) -> DatasetResult<Vec<ColumnConstraints>> {
+        let conn = self.lock_conn();
+        load_constraints(&conn, id)
+    }
+
+    /// Check every row of a dataset against its column constraints.
+    pub fn validate_dataset(&self, id: &DatasetId) -> DatasetResult<ValidationReport> {
+        let conn = self.lock_conn();
+        let checker = Checker::new(&conn, id)?;
+        let (rows_checked, violations, truncated) =
+            checker.check(&conn, None, ValidationReport::MAX_VIOLATIONS)?;
+        Ok(ValidationReport {
+            dataset_id: id.clone(),
+            rows_checked,
+            violations,
+            truncated,
+        })
+    }
+}
+
+/// A dataset's column constraints. Fails if the dataset does not exist.
+pub(crate) fn load_constraints(conn: &Connection, id: &DatasetId) -> DatasetResult<Vec<ColumnConstraints>> {
+    let json: String = conn
+        .query_row(
+            "SELECT constraints_json FROM _datasets_meta WHERE id = ?1",
+            params![id.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| DatasetError::NotFound(id.to_string()))?;
+    Ok(serde_json::from_str(&json).unwrap_or_default())
+}
+
+pub(crate) fn save_constraints(
+    conn: &Connection,
+    id: &DatasetId,
+    constraints: &[ColumnConstraints],
+) -> DatasetResult<()> {
+    conn.execute(
+        "UPDATE _datasets_meta SET constraints_json = ?1, modified_at = ?2 WHERE id = ?3",
+        params![serde_json::to_string(constraints)?, now_millis(), id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Checks that constraints name a stored column and that their rules can
+/// be applied. Returns them with the column named as stored.
+pub(crate) fn check_constraints(
+    conn: &Connection,
+    id: &DatasetId,
+    stored: &[DatasetColumn],
+    constraints: &ColumnConstraints,
+) -> DatasetResult<ColumnConstraints> {
+    let column = stored
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(&constraints.column))
+        .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown column: {}", constraints.column)))?;
+    if let Some(pattern) = &constraints.pattern {
+        compile_pattern(pattern)?;
+    }
+    if let (Some(min), Some(max)) = (constraints.min, constraints.max) {
+        if min > max {
+            return Err(DatasetError::InvalidQuery(format!(
+                "Minimum {min} of column '{}' is greater than its maximum {max}",
+                column.name
+            )));
+        }
+    }
+    if constraints.options.as_ref().is_some_and(Vec::is_empty) {
+        return Err(DatasetError::InvalidQuery(format!(
+            "Column '{}' allows no options",
+            column.name
+        )));
+    }
+    if constraints.foreign_key && relation_targets(conn, id, &column.name)?.is_empty() {
+        return Err(DatasetError::InvalidQuery(format!(
+            "Column '{}' has no relation to check its values against",
+            column.name
+        )));
+    }
+    Ok(ColumnConstraints {
+        column: column.name.clone(),
+        ..constraints.clone()
+    })
+}
+
+/// Renames a column in a dataset's constraints, or removes its constraints
+/// when `new_name` is `None`. Returns whether any constraint changed.
+pub(crate) fn rename_constrained_column(
+    conn: &Connection,
+    id: &DatasetId,
+    old_name: &str,
+    new_name: Option<&str>,
+) -> DatasetResult<bool> {
+    let mut constraints = load_constraints(conn, id)?;
+    let Some(index) = constraints.iter().position(|c| c.column.eq_ignore_ascii_case(old_name)) else {
+        return Ok(false);
+    };
+    match new_name {
+        Some(name) => constraints[index].column = name.to_string(),
+        None => {
+            constraints.remove(index);
+        }
+    }
+    save_constraints(conn, id, &constraints)?;
+    Ok(true)
+}
+
+/// Fails if any of the given rows breaks a constraint of the dataset.
+pub(crate) fn check_rows(conn: &Connection, id: &DatasetId, rowids: &[i64]) -> DatasetResult<()> {
+    let checker = Checker::new(conn, id)?;
+    let (_, violations, _) = checker.check(conn, Some(rowids), ValidationReport::MAX_VIOLATIONS)?;
+    violated(violations)
+}
+
+/// Fails if any row of the dataset breaks one of its constraints.
+pub(crate) fn check_all_rows(conn: &Connection, id: &DatasetId) -> DatasetResult<()> {
+    let checker = Checker::new(conn, id)?;
+    let (_, violations, _) = checker.check(conn, None, ValidationReport::MAX_VIOLATIONS)?;
+    violated(violations)
+}
+
+fn violated(violations: Vec<ConstraintViolation>) -> DatasetResult<()> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(DatasetError::ConstraintViolated(violations))
+    }
+}
+
+/// Runs a write in a savepoint, keeping its changes only if it succeeds.
+pub(crate) fn in_savepoint<T>(
+    conn: &Connection,
+    write: impl FnOnce() -> DatasetResult<T>,
+) -> DatasetResult<T> {
+    conn.execute_batch("SAVEPOINT dataset_constraints")?;
+    match write() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE SAVEPOINT dataset_constraints")?;
+            Ok(value)
+        }
+        Err(e) => {
+            conn.execute_batch(
+                "ROLLBACK TO SAVEPOINT dataset_constraints; RELEASE SAVEPOINT dataset_constraints",
+            )?;
+            Err(e)
+        }
+    }
+}
+
+// -- Tracking rows written by SQL --
+
+/// Whether `name` is one of the triggers a [`ChangeTracker`] installs.
+pub(crate) fn is_change_trigger(name: &str) -> bool {
+    name.starts_with(CHANGE_TRIGGER_PREFIX)
+}
+
+/// Temporary triggers noting the rows SQL inserts or updates in datasets
+/// with constraints; removed when dropped.
+pub(crate) struct ChangeTracker<'c> {
+    conn: &'c Connection,
+    triggers: Vec<String>,
+}
+
+impl<'c> ChangeTracker<'c> {
+    /// Installs triggers on every dataset table with constraints.
+    pub(crate) fn install(conn: &'c Connection) -> DatasetResult<Self> {
+        let mut tracker = Self { conn, triggers: Vec::new() };
+        let ids: Vec<String> = {
+            let mut stmt = conn.prepare("SELECT id FROM _datasets_meta WHERE constraints_json <> '[]'")?;
+            let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>();
+            ids?
+        };
+        let tables: Vec<String> = ids
+            .iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .map(|id| dataset_table_name(&DatasetId(id)))
+            .collect();
+        if tables.is_empty() {
+            return Ok(tracker);
+        }
+
+        conn.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS constraint_changes (dataset_table TEXT NOT NULL, row INTEGER NOT NULL)",
+        )?;
+        for table in tables {
+            if !table_exists(conn, &table)? {
+                continue;
+            }
+            for event in ["insert", "update"] {
+                let trigger = format!("{CHANGE_TRIGGER_PREFIX}{table}_{event}");
+                conn.execute_batch(&format!(
+                    "CREATE TEMP TRIGGER {trigger} AFTER {event} ON {table} \
+                     BEGIN INSERT INTO constraint_changes VALUES ('{table}', NEW.rowid); END"
+                ))?;
+                tracker.triggers.push(trigger);
+            }
+        }
+        Ok(tracker)
+    }
+
+    /// Fails if any row written since the triggers were installed breaks a
+    /// constraint of its dataset.
+    pub(crate) fn check(&self) -> DatasetResult<()> {
+        if self.triggers.is_empty() {
+            return Ok(());
+        }
+        let mut changed: HashMap<String, Vec<i64>> = HashMap::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT DISTINCT dataset_table, row FROM constraint_changes ORDER BY row")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+            for row in rows {
+                let (table, rowid) = row?;
+                changed.entry(table).or_default().push(rowid);
+            }
+        }
+        let mut violations = Vec::new();
+        for (table, rowids) in changed {
+            let Some(id) = dataset_id_for_table(&table) else {
+                continue;
+            };
+            let checker = Checker::new(self.conn, &id)?;
+            violations.extend(checker.check(self.conn, Some(&rowids), ValidationReport::MAX_VIOLATIONS)?.1);
+        }
+        violated(violations)
+    }
+}
+
+impl Drop for ChangeTracker<'_> {
+    fn drop(&mut self) {
+        if self.triggers.is_empty() {
+            return;
+        }
+        for trigger in &self.triggers {
+            let _ = self.conn.execute_batch(&format!("DROP TRIGGER IF EXISTS temp.{trigger}"));
+        }
+        let _ = self.conn.execute_batch("DROP TABLE IF EXISTS temp.constraint_changes");
+    }
+}
+
+// -- Checking values --
+
+/// The constraints of one column, ready to check values against.
+struct Rules {
+    constraints: ColumnConstraints,
+    column_type: DatasetColumnType,
+    pattern: Option<Regex>,
+    /// Table and column of each relation target, for `foreign_key`.
+    targets: Vec<(String, String)>,
+}
+
+/// Checks rows of a dataset against its column constraints.
+struct Checker {
+    table: String,
+    rules: Vec<Rules>,
+}
+
+impl Checker {
+    fn new(conn: &Connection, id: &DatasetId) -> DatasetResult<Self> {
+        let table = dataset_table_name(id);
+        let columns = introspect_columns(conn, &table)?;
+        let mut rules = Vec::new();
+        for constraints in load_constraints(conn, id)? {
+            // Constraints of a column that is gone no longer apply.
+            let Some(column) = columns.iter().find(|c| c.name.eq_ignore_ascii_case(&constraints.column)) else {
+                continue;
+            };
+            let pattern = constraints.pattern.as_deref().map(compile_pattern).transpose()?;
+            let targets = if constraints.foreign_key {
+                relation_targets(conn, id, &column.name)?
+            } else {
+                Vec::new()
+            };
+            rules.push(Rules {
+                column_type: column.column_type.clone(),
+                constraints: ColumnConstraints {
+                    column: column.name.clone(),
+                    ..constraints
+                },
+                pattern,
+                targets,
+            });
+        }
+        Ok(Self { table, rules })
+    }
+
+    /// Checks the rows with the given rowids, or every row. Returns the
+    /// number of rows checked and at most `max` violations in row order,
+    /// and whether there were more.
+    fn check(
+        &self,
+        conn: &Connection,
+        rowids: Option<&[i64]>,
+        max: usize,
+    ) -> DatasetResult<(i64, Vec<ConstraintViolation>, bool)> {
+        if self.rules.is_empty() {
+            return Ok((0, Vec::new(), false));
+        }
+        let table = &self.table;
+        let mut select = vec!["rowid".to_string()];
+        select.extend(
+            self.rules
+                .iter()
+                .map(|r| format!("\"{}\"", sanitize_identifier(&r.constraints.column))),
+        );
+        let select = select.join(", ");
+        let read = |row: &privstack_db::rusqlite::Row<'_>| {
+            let values = (1..=self.rules.len())
+                .map(|i| Ok((row.get::<_, SqlValue>(i)?, row_value_to_json(row, i))))
+                .collect::<privstack_db::rusqlite::Result<Vec<_>>>()?;
+            Ok((row.get::<_, i64>(0)?, values))
+        };
+
+        let mut scan = Scan {
+            checker: self,
+            conn,
+            max,
+            rows_checked: 0,
+            violations: Vec::new(),
+            truncated: false,
+            seen: vec![HashSet::new(); self.rules.len()],
+            found: HashMap::new(),
+        };
+        match rowids {
+            Some(rowids) => {
+                let mut stmt = conn.prepare(&format!("SELECT {select} FROM {table} WHERE rowid = ?1"))?;
+                for &rowid in rowids {
+                    if let Some((rowid, values)) = stmt.query_row(params![rowid], read).optional()? {
+                        scan.row(rowid, None, values)?;
+                    }
+                    if scan.truncated {
+                        break;
+                    }
+                }
+            }
+            None => {
+                let mut stmt = conn.prepare(&format!("SELECT {select} FROM {table} ORDER BY rowid"))?;
+                let rows = stmt.query_map([], read)?;
+                for (index, row) in rows.enumerate() {
+                    let (rowid, values) = row?;
+                    scan.row(rowid, Some(index as i64), values)?;
+                    if scan.truncated {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok((scan.rows_checked, scan.violations, scan.truncated))
+    }
+}
+
+/// State of one pass of a [`Checker`] over rows.
+struct Scan<'a> {
+    checker: &'a Checker,
+    conn: &'a Connection,
+    max: usize,
+    rows_checked: i64,
+    violations: Vec<ConstraintViolation>,
+    truncated: bool,
+    /// Values seen so far in each unique column, when reading every row.
+    seen: Vec<HashSet<String>>,
+    /// Whether each value of a foreign key column has a match.
+    found: HashMap<(usize, String), bool>,
+}
+
+impl Scan<'_> {
+    /// Checks one row's values. `position` is the row's index when every
+    /// row is read in order; otherwise it is looked up if needed.
+    fn row(
+        &mut self,
+        rowid: i64,
+        position: Option<i64>,
+        values: Vec<(SqlValue, serde_json::Value)>,
+    ) -> DatasetResult<()> {
+        self.rows_checked += 1;
+        let conn = self.conn;
+        let table = &self.checker.table;
+        let mut row_index = position;
+        for (i, ((value, json), rules)) in values.into_iter().zip(&self.checker.rules).enumerate() {
+            let mut broken = rules.check_value(&value);
+            if !is_missing(&value) {
+                let key = format!("{value:?}");
+                if rules.constraints.unique {
+                    let duplicate = match position {
+                        // Rows are read in order, so later copies are the duplicates.
+                        Some(_) => !self.seen[i].insert(key.clone()),
+                        None => conn.query_row(
+                            &format!(
+                                "SELECT COUNT(*) > 1 FROM {table} WHERE \"{}\" = ?1",
+                                sanitize_identifier(&rules.constraints.column)
+                            ),
+                            params![value],
+                            |row| row.get::<_, bool>(0),
+                        )?,
+                    };
+                    if duplicate {
+                        broken.push((ConstraintKind::Unique, "duplicates another row".to_string()));
+                    }
+                }
+                if !rules.targets.is_empty() {
+                    let exists = match self.found.get(&(i, key.clone())) {
+                        Some(&exists) => exists,
+                        None => {
+                            let exists = rules.references_exist(conn, &value)?;
+                            self.found.insert((i, key), exists);
+                            exists
+                        }
+                    };
+                    if !exists {
+                        broken.push((
+                            ConstraintKind::ForeignKey,
+                            "has no match in the related dataset".to_string(),
+                        ));
+                    }
+                }
+            }
+            for (kind, message) in broken {
+                if self.violations.len() == self.max {
+                    self.truncated = true;
+                    return Ok(());
+                }
+                let index = match row_index {
+                    Some(index) => index,
+                    None => {
+                        let index = conn.query_row(
+                            &format!("SELECT COUNT(*) FROM {table} WHERE rowid < ?1"),
+                            params![rowid],
+                            |row| row.get(0),
+                        )?;
+                        *row_index.insert(index)
+                    }
+                };
+                self.violations.push(ConstraintViolation {
+                    row_index: index,
+                    column: rules.constraints.column.clone(),
+                    value: json.clone(),
+                    kind,
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Rules {
+    /// The rules a value breaks, other than `unique` and `foreign_key`,
+    /// which need the rest of the data.
+    fn check_value(&self, value: &SqlValue) -> Vec<(ConstraintKind, String)> {
+        let c = &self.constraints;
+        let mut broken = Vec::new();
+        if is_missing(value) {
+            if c.not_null {
+                broken.push((ConstraintKind::NotNull, "is required".to_string()));
+            }
+            return broken;
+        }
+        if c.strict_type && !has_type(value, &self.column_type) {
+            broken.push((ConstraintKind::StrictType, format!("is not {}", type_noun(&self.column_type))));
+        }
+        let text = value_text(value);
+        if let (Some(options), Some(text)) = (&c.options, &text) {
+            if !options.iter().any(|o| o == text) {
+                broken.push((ConstraintKind::Options, format!("must be one of: {}", options.join(", "))));
+            }
+        }
+        if let (Some(pattern), Some(text)) = (&self.pattern, &text) {
+            if !pattern.is_match(text) {
+                broken.push((ConstraintKind::Pattern, format!("does not match {}", pattern.as_str())));
+            }
+        }
+        if c.min.is_some() || c.max.is_some() {
+            match value_number(value) {
+                None => {
+                    let kind = if c.min.is_some() { ConstraintKind::Min } else { ConstraintKind::Max };
+                    broken.push((kind, "is not a number".to_string()));
+                }
+                Some(n) => {
+                    if let Some(min) = c.min.filter(|&min| n < min) {
+                        broken.push((ConstraintKind::Min, format!("must be at least {min}")));
+                    }
+                    if let Some(max) = c.max.filter(|&max| n > max) {
+                        broken.push((ConstraintKind::Max, format!("must be at most {max}")));
+                    }
+                }
+            }
+        }
+        broken
+    }
+
+    /// Whether a value exists in the target column of every relation.
+    fn references_exist(&self, conn: &Connection, value: &SqlValue) -> DatasetResult<bool> {
+        for (table, column) in &self.targets {
+            let exists: bool = conn.query_row(
+                &format!(
+                    "SELECT EXISTS (SELECT 1 FROM {table} WHERE \"{}\" = ?1)",
+                    sanitize_identifier(column)
+                ),
+                params![value],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Whether a value counts as missing: NULL, or empty text, which is what
+/// `insert_row` stores for a JSON null.
+fn is_missing(value: &SqlValue) -> bool {
+    match value {
+        SqlValue::Null => true,
+        SqlValue::Text(text) => text.is_empty(),
+        _ => false,
+    }
+}
+
+fn has_type(value: &SqlValue, column_type: &DatasetColumnType) -> bool {
+    use DatasetColumnType as T;
+    match (column_type, value) {
+        (T::Integer, SqlValue::Integer(_)) => true,
+        (T::Float, SqlValue::Integer(_) | SqlValue::Real(_)) => true,
+        (T::Boolean, SqlValue::Integer(0 | 1)) => true,
+        (T::Boolean, SqlValue::Text(text)) => {
+            text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false")
+        }
+        (T::Date, SqlValue::Text(text)) => chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+        (T::Timestamp, SqlValue::Text(text)) => {
+            chrono::DateTime::parse_from_rfc3339(text).is_ok()
+                || ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                    .iter()
+                    .any(|format| chrono::NaiveDateTime::parse_from_str(text, format).is_ok())
+        }
+        (T::Integer | T::Float | T::Boolean | T::Date | T::Timestamp, _) => false,
+        (T::Text | T::Blob | T::Unknown, _) => true,
+    }
+}
+
+fn type_noun(column_type: &DatasetColumnType) -> &'static str {
+    match column_type {
+        DatasetColumnType::Integer => "an integer",
+        DatasetColumnType::Float => "a number",
+        DatasetColumnType::Boolean => "a boolean",
+        DatasetColumnType::Date => "a date (YYYY-MM-DD)",
+        DatasetColumnType::Timestamp => "a timestamp",
+        DatasetColumnType::Text | DatasetColumnType::Blob | DatasetColumnType::Unknown => "valid",
+    }
+}
+
+/// A value as text, for options and patterns. Blobs have none.
+fn value_text(value: &SqlValue) -> Option<String> {
+    match value {
+        SqlValue::Integer(i) => Some(i.to_string()),
+        SqlValue::Real(f) => Some(f.to_string()),
+        SqlValue::Text(text) => Some(text.clone()),
+        SqlValue::Null | SqlValue::Blob(_) => None,
+    }
+}
+
+fn value_number(value: &SqlValue) -> Option<f64> {
+    match value {
+        SqlValue::Integer(i) => Some(*i as f64),
+        SqlValue::Real(f) => Some(*f),
+        SqlValue::Text(text) => text.trim().parse().ok(),
+        SqlValue::Null | SqlValue::Blob(_) => None,
+    }
+}
+
+fn compile_pattern(pattern: &str) -> DatasetResult<Regex> {
+    Regex::new(pattern).map_err(|e| DatasetError::InvalidQuery(format!("Invalid pattern '{pattern}': {e}")))
+}
+
+/// Table and column of each relation target of a dataset's column. Targets
+/// whose dataset is gone are left out.
+fn relation_targets(
+    conn: &Connection,
+    id: &DatasetId,
+    column: &str,
+) -> DatasetResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT target_dataset_id, target_column FROM _dataset_relations \
+         WHERE source_dataset_id = ?1 AND source_column = ?2 COLLATE NOCASE ORDER BY created_at",
+    )?;
+    let relations = stmt
+        .query_map(params![id.to_string(), column], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut targets = Vec::new();
+    for (target, column) in relations {
+        let Ok(target) = Uuid::parse_str(&target) else {
+            continue;
+        };
+        let table = dataset_table_name(&DatasetId(target));
+        if table_exists(conn, &table)? {
+            targets.push((table, column));
+        }
+    }
+    Ok(targets)
+}
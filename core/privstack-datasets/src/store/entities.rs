@@ -0,0 +1,106 @@
+//! Entity tables: plugin entities joined with datasets in SQL.
+//!
+//! `entity:task` in a statement stands for the entities of type `task`.
+//! Entities live in another database, so before the statement runs each
+//! referenced type is loaded from an [`EntitySource`] into a temporary
+//! `entity_<uuid>` table on the datasets connection, and dropped again once
+//! it has. The sandbox lets statements read these tables but never write
+//! them.
+
+use super::helpers::sanitize_identifier;
+use super::replication::json_to_sql;
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::EntityTable;
+use privstack_db::rusqlite::params_from_iter;
+use uuid::Uuid;
+
+/// Where entity tables come from. The source decides what the caller may
+/// see: it leaves out trashed entities, and types the caller has no
+/// permission to read.
+pub trait EntitySource {
+    /// The entities of `entity_type`. Local-only entities are left out
+    /// unless `include_local_only` is set; statements that write datasets
+    /// never see them, so they can't be copied into data that syncs.
+    ///
+    /// Fails with `NotFound` for an unknown type and `NotAuthorized` for
+    /// one the caller may not read.
+    fn entity_table(&self, entity_type: &str, include_local_only: bool) -> DatasetResult<EntityTable>;
+}
+
+/// Source for callers without access to entities.
+pub(crate) struct NoEntities;
+
+impl EntitySource for NoEntities {
+    fn entity_table(&self, entity_type: &str, _include_local_only: bool) -> DatasetResult<EntityTable> {
+        Err(DatasetError::NotAuthorized(format!(
+            "entity:{entity_type} is not available here"
+        )))
+    }
+}
+
+/// A fresh name for the temporary table of an entity type.
+pub(crate) fn entity_table_name() -> String {
+    format!("entity_{}", Uuid::new_v4().simple())
+}
+
+/// Whether `name` is a temporary entity table (`entity_<uuid>`).
+pub(crate) fn is_entity_table(name: &str) -> bool {
+    name.strip_prefix("entity_")
+        .is_some_and(|id| id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Entity tables loaded for one statement; dropped with it.
+pub(crate) struct LoadedEntities<'s> {
+    store: &'s DatasetStore,
+    tables: Vec<String>,
+}
+
+impl Drop for LoadedEntities<'_> {
+    fn drop(&mut self) {
+        let conn = self.store.lock_conn();
+        for table in &self.tables {
+            let _ = conn.execute_batch(&format!("DROP TABLE IF EXISTS temp.{table}"));
+        }
+    }
+}
+
+impl DatasetStore {
+    /// Loads each referenced entity type into its temporary table.
+    pub(crate) fn load_entities(
+        &self,
+        referenced: &[(String, String)],
+        entities: &dyn EntitySource,
+        include_local_only: bool,
+    ) -> DatasetResult<LoadedEntities<'_>> {
+        let mut loaded = LoadedEntities { store: self, tables: Vec::new() };
+        for (entity_type, table) in referenced {
+            let entity_table = entities.entity_table(entity_type, include_local_only)?;
+            if entity_table.columns.is_empty() {
+                return Err(DatasetError::InvalidQuery(format!(
+                    "entity:{entity_type} has no columns"
+                )));
+            }
+
+            let conn = self.lock_conn();
+            let columns: Vec<String> = entity_table
+                .columns
+                .iter()
+                .map(|c| format!("\"{}\" {}", sanitize_identifier(&c.name), c.column_type))
+                .collect();
+            conn.execute_batch(&format!("CREATE TEMP TABLE {table} ({})", columns.join(", ")))?;
+            loaded.tables.push(table.clone());
+
+            let placeholders = vec!["?"; entity_table.columns.len()].join(", ");
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = tx.prepare(&format!("INSERT INTO temp.{table} VALUES ({placeholders})"))?;
+                for row in &entity_table.rows {
+                    stmt.execute(params_from_iter(row.iter().map(json_to_sql)))?;
+                }
+            }
+            tx.commit()?;
+        }
+        Ok(loaded)
+    }
+}
@@ -0,0 +1,498 @@
+//! Export of a dataset, a saved view or a saved query result to CSV, TSV,
+//! JSON/NDJSON, XLSX or Parquet.
+//!
+//! Rows are streamed from SQLite straight into the file writer. Parquet
+//! needs its column types up front, so it reads the rows twice: once to
+//! find each column's storage classes, once to write them.
+
+use super::formulas::{dataset_columns, with_computed_columns};
+use super::helpers::{build_typed_select, build_view_clauses};
+use super::sandbox::{Sandbox, SqlAccess};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{
+    DatasetColumn, DatasetColumnType, DatasetId, ExportSource, FileFormat, ViewConfig,
+};
+use privstack_db::rusqlite::types::{Value, ValueRef};
+use privstack_db::rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// Rows per Parquet row group.
+const PARQUET_ROW_GROUP: usize = 65_536;
+
+/// Data rows that fit on an XLSX worksheet below its header row.
+const XLSX_MAX_ROWS: u64 = 1_048_575;
+
+impl DatasetStore {
+    /// Export a dataset, view or saved query result to a file. Returns the
+    /// number of rows written.
+    ///
+    /// Saved queries run in the SQL sandbox, like any other user SQL.
+    pub fn export(
+        &self,
+        source: &ExportSource,
+        format: FileFormat,
+        path: &Path,
+    ) -> DatasetResult<u64> {
+        let rows = match source {
+            ExportSource::Dataset { dataset_id } => {
+                let meta = self.get(dataset_id)?;
+                let conn = self.lock_conn();
+                let columns = table_columns(&conn, dataset_id, &meta.columns)?;
+                let sql = with_computed_columns(
+                    &conn,
+                    &format!(
+                        "SELECT {} FROM {}",
+                        build_typed_select(&columns),
+                        dataset_table_name(dataset_id)
+                    ),
+                )?;
+                let plan = ExportPlan { sql, params: Vec::new(), columns };
+                write_file(&conn, &plan, format, path)?
+            }
+            ExportSource::View { view_id } => {
+                let (dataset_id, config) = self.view_config(view_id)?;
+                let meta = self.get(&dataset_id)?;
+                let conn = self.lock_conn();
+                let all_columns = table_columns(&conn, &dataset_id, &meta.columns)?;
+                let (clauses, params) = build_view_clauses(&all_columns, &config)?;
+                let columns: Vec<DatasetColumn> = match &config.visible_columns {
+                    Some(visible) => all_columns
+                        .into_iter()
+                        .filter(|c| visible.contains(&c.name))
+                        .collect(),
+                    None => all_columns,
+                };
+                if columns.is_empty() {
+                    return Err(DatasetError::ExportFailed("View has no visible columns".to_string()));
+                }
+                let sql = with_computed_columns(
+                    &conn,
+                    &format!(
+                        "SELECT {} FROM {}{clauses}",
+                        build_typed_select(&columns),
+                        dataset_table_name(&dataset_id)
+                    ),
+                )?;
+                let plan = ExportPlan { sql, params, columns };
+                write_file(&conn, &plan, format, path)?
+            }
+            ExportSource::SavedQuery { query_id } => {
+                let sql = self.saved_query_select(query_id, "exported")?.sql;
+                let limits = self.sql_limits();
+                let conn = self.lock_conn();
+                let sql = with_computed_columns(&conn, &sql)?;
+                let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+                query_plan(&conn, sql)
+                    .and_then(|plan| write_file(&conn, &plan, format, path))
+                    .map_err(|e| sandbox.explain(e))?
+            }
+        };
+        info!(?format, rows, path = %path.display(), "Dataset exported");
+        Ok(rows)
+    }
+
+    pub(crate) fn view_config(&self, view_id: &str) -> DatasetResult<(DatasetId, ViewConfig)> {
+        let conn = self.lock_conn();
+        let (dataset_id, config_json): (String, String) = conn
+            .query_row(
+                "SELECT dataset_id, config_json FROM _dataset_views WHERE id = ?1",
+                params![view_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| DatasetError::NotFound(format!("view {view_id}")))?;
+        let dataset_id = Uuid::parse_str(&dataset_id)
+            .map(DatasetId)
+            .map_err(|e| DatasetError::InvalidQuery(format!("Invalid dataset ID: {e}")))?;
+        Ok((dataset_id, serde_json::from_str(&config_json)?))
+    }
+}
+
+/// A query whose rows are exported, with the columns it yields.
+struct ExportPlan {
+    sql: String,
+    params: Vec<Value>,
+    columns: Vec<DatasetColumn>,
+}
+
+/// The table's columns and formula columns, typed from the dataset
+/// metadata where it knows better than the declared SQLite type (booleans
+/// are stored as integers).
+fn table_columns(
+    conn: &Connection,
+    id: &DatasetId,
+    meta_columns: &[DatasetColumn],
+) -> DatasetResult<Vec<DatasetColumn>> {
+    let mut columns = dataset_columns(conn, id)?;
+    if columns.is_empty() {
+        return Err(DatasetError::NotFound(id.to_string()));
+    }
+    for column in &mut columns {
+        if let Some(meta) = meta_columns.iter().find(|m| m.name == column.name) {
+            column.column_type = meta.column_type.clone();
+        }
+    }
+    Ok(columns)
+}
+
+fn query_plan(conn: &Connection, sql: String) -> DatasetResult<ExportPlan> {
+    let stmt = conn.prepare(&sql)?;
+    if !stmt.readonly() {
+        return Err(DatasetError::InvalidQuery(
+            "Only SELECT statements are allowed".to_string(),
+        ));
+    }
+    let columns = stmt
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(i, c)| DatasetColumn {
+            name: c.name().to_string(),
+            column_type: c
+                .decl_type()
+                .map_or(DatasetColumnType::Unknown, DatasetColumnType::from_sqlite),
+            ordinal: i as i32,
+        })
+        .collect();
+    drop(stmt);
+    Ok(ExportPlan { sql, params: Vec::new(), columns })
+}
+
+/// Runs the plan's query, calling `each` with every row's values.
+fn for_each_row(
+    conn: &Connection,
+    plan: &ExportPlan,
+    mut each: impl FnMut(&[ValueRef<'_>]) -> DatasetResult<()>,
+) -> DatasetResult<u64> {
+    let mut stmt = conn.prepare(&plan.sql)?;
+    let mut rows = stmt.query(params_from_iter(&plan.params))?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let values = (0..plan.columns.len())
+            .map(|i| row.get_ref(i))
+            .collect::<Result<Vec<_>, _>>()?;
+        each(&values)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn write_file(
+    conn: &Connection,
+    plan: &ExportPlan,
+    format: FileFormat,
+    path: &Path,
+) -> DatasetResult<u64> {
+    match format {
+        FileFormat::Csv => write_delimited(conn, plan, b',', path),
+        FileFormat::Tsv => write_delimited(conn, plan, b'\t', path),
+        FileFormat::Json => write_json(conn, plan, true, path),
+        FileFormat::Ndjson => write_json(conn, plan, false, path),
+        FileFormat::Xlsx => write_xlsx(conn, plan, path),
+        FileFormat::Parquet => write_parquet(conn, plan, path),
+    }
+}
+
+/// A value as text, for formats without types. Nulls are empty.
+fn text(value: &ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        // Debug keeps a decimal point on whole numbers, so they read back
+        // as floats.
+        ValueRef::Real(f) => format!("{f:?}"),
+        ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        ValueRef::Blob(bytes) => format!("<blob:{} bytes>", bytes.len()),
+    }
+}
+
+fn write_delimited(
+    conn: &Connection,
+    plan: &ExportPlan,
+    delimiter: u8,
+    path: &Path,
+) -> DatasetResult<u64> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(path)?;
+    writer.write_record(plan.columns.iter().map(|c| &c.name))?;
+    let rows = for_each_row(conn, plan, |values| {
+        writer.write_record(values.iter().map(text))?;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(rows)
+}
+
+/// Writes a JSON array of objects, or one object per line (NDJSON). Keys
+/// keep the column order.
+fn write_json(
+    conn: &Connection,
+    plan: &ExportPlan,
+    array: bool,
+    path: &Path,
+) -> DatasetResult<u64> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let keys: Vec<String> = plan
+        .columns
+        .iter()
+        .map(|c| serde_json::to_string(&c.name))
+        .collect::<Result<_, _>>()?;
+    if array {
+        out.write_all(b"[")?;
+    }
+    let mut first = true;
+    let rows = for_each_row(conn, plan, |values| {
+        if array && !first {
+            out.write_all(b",")?;
+        }
+        if array || !first {
+            out.write_all(b"\n")?;
+        }
+        first = false;
+        out.write_all(b"{")?;
+        for (i, ((key, column), value)) in keys.iter().zip(&plan.columns).zip(values).enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            let value = match (value, &column.column_type) {
+                (ValueRef::Integer(i), DatasetColumnType::Boolean) => serde_json::Value::Bool(*i != 0),
+                (ValueRef::Integer(i), _) => serde_json::Value::from(*i),
+                (ValueRef::Real(f), _) => serde_json::Value::from(*f),
+                (ValueRef::Null, _) => serde_json::Value::Null,
+                (other, _) => serde_json::Value::String(text(other)),
+            };
+            write!(out, "{key}:{value}")?;
+        }
+        out.write_all(b"}")?;
+        Ok(())
+    })?;
+    if array {
+        out.write_all(if rows == 0 { b"]" } else { b"\n]" })?;
+    }
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(rows)
+}
+
+fn write_xlsx(conn: &Connection, plan: &ExportPlan, path: &Path) -> DatasetResult<u64> {
+    use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+    let failed = |e: XlsxError| DatasetError::ExportFailed(format!("Failed to write XLSX: {e}"));
+    if plan.columns.len() > usize::from(u16::MAX) {
+        return Err(DatasetError::ExportFailed(
+            "Too many columns for an XLSX worksheet".to_string(),
+        ));
+    }
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet_with_constant_memory();
+    let bold = Format::new().set_bold();
+    for (col, column) in plan.columns.iter().enumerate() {
+        sheet
+            .write_string_with_format(0, col as u16, &column.name, &bold)
+            .map_err(failed)?;
+    }
+
+    let mut row = 0u32;
+    let rows = for_each_row(conn, plan, |values| {
+        if u64::from(row) >= XLSX_MAX_ROWS {
+            return Err(DatasetError::ExportFailed(format!(
+                "XLSX worksheets hold at most {XLSX_MAX_ROWS} rows"
+            )));
+        }
+        row += 1;
+        for (col, (value, column)) in values.iter().zip(&plan.columns).enumerate() {
+            let col = col as u16;
+            match (value, &column.column_type) {
+                (ValueRef::Null, _) => continue,
+                (ValueRef::Integer(i), DatasetColumnType::Boolean) => {
+                    sheet.write_boolean(row, col, *i != 0)
+                }
+                (ValueRef::Integer(i), _) => sheet.write_number(row, col, *i as f64),
+                (ValueRef::Real(f), _) => sheet.write_number(row, col, *f),
+                (other, _) => sheet.write_string(row, col, text(other)),
+            }
+            .map_err(failed)?;
+        }
+        Ok(())
+    })?;
+
+    workbook.save(path).map_err(failed)?;
+    Ok(rows)
+}
+
+/// The Parquet type a column is written as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParquetKind {
+    Boolean,
+    Int64,
+    Double,
+    Utf8,
+}
+
+/// A row group's worth of one column's values, with their definition
+/// levels (0 for null, 1 for a value).
+enum ParquetBuffer {
+    Boolean(Vec<bool>),
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    Utf8(Vec<parquet::data_type::ByteArray>),
+}
+
+impl ParquetBuffer {
+    fn new(kind: ParquetKind) -> Self {
+        match kind {
+            ParquetKind::Boolean => Self::Boolean(Vec::new()),
+            ParquetKind::Int64 => Self::Int64(Vec::new()),
+            ParquetKind::Double => Self::Double(Vec::new()),
+            ParquetKind::Utf8 => Self::Utf8(Vec::new()),
+        }
+    }
+
+    /// Buffers a non-null value, converting it to the column's type.
+    fn push(&mut self, value: &ValueRef<'_>) {
+        match (self, value) {
+            (Self::Boolean(v), ValueRef::Integer(i)) => v.push(*i != 0),
+            (Self::Int64(v), ValueRef::Integer(i)) => v.push(*i),
+            (Self::Double(v), ValueRef::Integer(i)) => v.push(*i as f64),
+            (Self::Double(v), ValueRef::Real(f)) => v.push(*f),
+            (Self::Utf8(v), value) => v.push(text(value).into_bytes().into()),
+            // The column's kind was chosen to hold every value it has.
+            _ => unreachable!("value does not match its Parquet column type"),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Self::Boolean(v) => v.clear(),
+            Self::Int64(v) => v.clear(),
+            Self::Double(v) => v.clear(),
+            Self::Utf8(v) => v.clear(),
+        }
+    }
+}
+
+fn write_parquet(conn: &Connection, plan: &ExportPlan, path: &Path) -> DatasetResult<u64> {
+    use parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+    use parquet::data_type::{BoolType, ByteArrayType, DoubleType, Int64Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::types::Type;
+
+    let failed = |e: ParquetError| DatasetError::ExportFailed(format!("Failed to write Parquet: {e}"));
+
+    // First pass: the narrowest type holding every value of each column.
+    let mut seen = vec![(false, false, false); plan.columns.len()];
+    for_each_row(conn, plan, |values| {
+        for (value, (int, real, other)) in values.iter().zip(&mut seen) {
+            match value {
+                ValueRef::Null => {}
+                ValueRef::Integer(_) => *int = true,
+                ValueRef::Real(_) => *real = true,
+                ValueRef::Text(_) | ValueRef::Blob(_) => *other = true,
+            }
+        }
+        Ok(())
+    })?;
+    let kinds: Vec<ParquetKind> = plan
+        .columns
+        .iter()
+        .zip(&seen)
+        .map(|(column, seen)| match (seen, &column.column_type) {
+            ((_, false, false), DatasetColumnType::Boolean) => ParquetKind::Boolean,
+            ((_, false, false), DatasetColumnType::Float) => ParquetKind::Double,
+            ((_, false, false), DatasetColumnType::Integer) | ((true, false, false), _) => {
+                ParquetKind::Int64
+            }
+            ((_, true, false), _) => ParquetKind::Double,
+            _ => ParquetKind::Utf8,
+        })
+        .collect();
+
+    let fields = plan
+        .columns
+        .iter()
+        .zip(&kinds)
+        .map(|(column, kind)| {
+            let (physical, logical) = match kind {
+                ParquetKind::Boolean => (PhysicalType::BOOLEAN, None),
+                ParquetKind::Int64 => (PhysicalType::INT64, None),
+                ParquetKind::Double => (PhysicalType::DOUBLE, None),
+                ParquetKind::Utf8 => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+            };
+            Type::primitive_type_builder(&column.name, physical)
+                .with_repetition(Repetition::OPTIONAL)
+                .with_logical_type(logical)
+                .build()
+                .map(Arc::new)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(failed)?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()
+        .map_err(failed)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = SerializedFileWriter::new(File::create(path)?, Arc::new(schema), Arc::new(props))
+        .map_err(failed)?;
+
+    // Second pass: buffer a row group at a time and write it out.
+    let mut buffers: Vec<ParquetBuffer> = kinds.iter().map(|k| ParquetBuffer::new(*k)).collect();
+    let mut levels: Vec<Vec<i16>> = vec![Vec::new(); kinds.len()];
+    let mut buffered = 0;
+    let mut flush = |buffers: &mut [ParquetBuffer], levels: &mut [Vec<i16>]| -> DatasetResult<()> {
+        let mut group = writer.next_row_group().map_err(failed)?;
+        for (buffer, levels) in buffers.iter_mut().zip(levels.iter_mut()) {
+            let Some(mut column) = group.next_column().map_err(failed)? else {
+                break;
+            };
+            let def = Some(levels.as_slice());
+            match buffer {
+                ParquetBuffer::Boolean(v) => column.typed::<BoolType>().write_batch(v, def, None),
+                ParquetBuffer::Int64(v) => column.typed::<Int64Type>().write_batch(v, def, None),
+                ParquetBuffer::Double(v) => column.typed::<DoubleType>().write_batch(v, def, None),
+                ParquetBuffer::Utf8(v) => column.typed::<ByteArrayType>().write_batch(v, def, None),
+            }
+            .map_err(failed)?;
+            column.close().map_err(failed)?;
+            buffer.clear();
+            levels.clear();
+        }
+        group.close().map_err(failed)?;
+        Ok(())
+    };
+
+    let rows = for_each_row(conn, plan, |values| {
+        for ((value, buffer), levels) in values.iter().zip(&mut buffers).zip(&mut levels) {
+            if matches!(value, ValueRef::Null) {
+                levels.push(0);
+            } else {
+                levels.push(1);
+                buffer.push(value);
+            }
+        }
+        buffered += 1;
+        if buffered == PARQUET_ROW_GROUP {
+            flush(&mut buffers, &mut levels)?;
+            buffered = 0;
+        }
+        Ok(())
+    })?;
+    if buffered > 0 {
+        flush(&mut buffers, &mut levels)?;
+    }
+    writer.close().map_err(failed)?;
+    Ok(rows)
+}
+
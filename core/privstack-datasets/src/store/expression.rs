@@ -0,0 +1,587 @@
+//! Formula expression language: parsing and compilation to SQL.
+//!
+//! Formulas are spreadsheet-like expressions over a dataset's columns:
+//!
+//! - Columns by name, `price`, or in brackets when the name has spaces or
+//!   symbols, `[Unit Price]`.
+//! - Literals: `12`, `3.5`, `'text'` (`''` for a quote), `TRUE`, `FALSE`,
+//!   `NULL`.
+//! - Operators, loosest first: `OR`; `AND`; `NOT`; comparisons `=` `<>`
+//!   `!=` `<` `<=` `>` `>=`; `&` (text concatenation); `+` `-`; `*` `/`
+//!   `%`; unary `-`. Division is never integer division.
+//! - Functions, listed in [`FUNCTIONS`], plus `LOOKUP(Dataset, column)` and
+//!   `ROLLUP(Dataset, column, 'sum')` for values of related datasets.
+//!
+//! Compilation turns an expression into a SQL expression and its column
+//! type. Column references and related values are resolved by a [`Scope`].
+
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::DatasetColumnType;
+
+/// A parsed formula expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+    Null,
+    Column(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A function call; the name is uppercased.
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Or => "OR",
+            Self::And => "AND",
+            Self::Eq => "=",
+            Self::NotEq => "<>",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::Concat => "||",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+        }
+    }
+}
+
+/// A compiled expression: SQL and the type of its values.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Compiled {
+    pub sql: String,
+    pub column_type: DatasetColumnType,
+}
+
+impl Compiled {
+    fn new(sql: String, column_type: DatasetColumnType) -> Self {
+        Self { sql, column_type }
+    }
+}
+
+/// Resolves the names a formula refers to.
+pub(crate) trait Scope {
+    /// A column of the formula's dataset, stored or computed.
+    fn column(&mut self, name: &str) -> DatasetResult<Compiled>;
+
+    /// A column of a related dataset: the value on the related row
+    /// (`aggregate` is `None`), or an aggregate over all related rows.
+    fn related(
+        &mut self,
+        dataset: &str,
+        column: &str,
+        aggregate: Option<Rollup>,
+    ) -> DatasetResult<Compiled>;
+}
+
+/// Aggregates `ROLLUP` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rollup {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+impl Rollup {
+    pub(crate) fn sql_function(self) -> &'static str {
+        match self {
+            Self::Sum => "SUM",
+            Self::Avg => "AVG",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+            Self::Count => "COUNT",
+        }
+    }
+}
+
+/// Functions formulas may call, with their argument counts (min, max).
+pub(crate) const FUNCTIONS: &[(&str, usize, usize)] = &[
+    ("IF", 3, 3),
+    ("COALESCE", 1, usize::MAX),
+    ("ROUND", 1, 2),
+    ("ABS", 1, 1),
+    ("FLOOR", 1, 1),
+    ("CEIL", 1, 1),
+    ("MIN", 2, usize::MAX),
+    ("MAX", 2, usize::MAX),
+    ("LEN", 1, 1),
+    ("UPPER", 1, 1),
+    ("LOWER", 1, 1),
+    ("TRIM", 1, 1),
+    ("LEFT", 2, 2),
+    ("RIGHT", 2, 2),
+    ("CONCAT", 1, usize::MAX),
+    ("TODAY", 0, 0),
+    ("NOW", 0, 0),
+    ("YEAR", 1, 1),
+    ("MONTH", 1, 1),
+    ("DAY", 1, 1),
+    ("DAYS", 2, 2),
+    ("ADD_DAYS", 2, 2),
+    ("LOOKUP", 2, 2),
+    ("ROLLUP", 3, 3),
+];
+
+fn invalid(message: impl Into<String>) -> DatasetError {
+    DatasetError::InvalidQuery(message.into())
+}
+
+// -- Parsing --
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    /// A bare word: a column, function or keyword.
+    Word(String),
+    /// A `[bracketed]` column or dataset name.
+    Name(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "==", "(", ")", ",", "=", "<", ">", "&", "+", "-", "*", "/", "%",
+];
+
+fn tokenize(source: &str) -> DatasetResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c == '\'' {
+            let mut text = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '\'')) if rest[1 + i + 1..].starts_with('\'') => {
+                        text.push('\'');
+                        chars.next();
+                    }
+                    Some((i, '\'')) => break 1 + i + 1,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(invalid("Unterminated text in formula")),
+                }
+            };
+            tokens.push(Token::Text(text));
+            rest = &rest[end..];
+        } else if c == '[' {
+            let end = rest
+                .find(']')
+                .ok_or_else(|| invalid("Unterminated [name] in formula"))?;
+            tokens.push(Token::Name(rest[1..end].to_string()));
+            rest = &rest[end + 1..];
+        } else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|d: char| d.is_ascii_digit())) {
+            let end = rest
+                .find(|d: char| !(d.is_ascii_digit() || d == '.'))
+                .unwrap_or(rest.len());
+            let number = &rest[..end];
+            let token = match number.parse::<i64>() {
+                Ok(i) => Token::Integer(i),
+                Err(_) => Token::Float(
+                    number
+                        .parse()
+                        .map_err(|_| invalid(format!("Invalid number in formula: {number}")))?,
+                ),
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|w: char| !(w.is_alphanumeric() || w == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(invalid(format!("Unexpected '{c}' in formula")));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a formula expression.
+pub(crate) fn parse(source: &str) -> DatasetResult<Expr> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Err(invalid("Formula is empty"));
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(invalid(format!("Unexpected {token:?} in formula"))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> DatasetResult<Expr> {
+        let mut left = self.and()?;
+        while self.eat_keyword("OR") {
+            left = Expr::Binary(BinaryOp::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> DatasetResult<Expr> {
+        let mut left = self.not()?;
+        while self.eat_keyword("AND") {
+            left = Expr::Binary(BinaryOp::And, Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> DatasetResult<Expr> {
+        if self.eat_keyword("NOT") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> DatasetResult<Expr> {
+        let left = self.concat()?;
+        let op = match self.peek() {
+            Some(Token::Symbol("=" | "==")) => BinaryOp::Eq,
+            Some(Token::Symbol("<>" | "!=")) => BinaryOp::NotEq,
+            Some(Token::Symbol("<")) => BinaryOp::Lt,
+            Some(Token::Symbol("<=")) => BinaryOp::LtEq,
+            Some(Token::Symbol(">")) => BinaryOp::Gt,
+            Some(Token::Symbol(">=")) => BinaryOp::GtEq,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.concat()?)))
+    }
+
+    fn concat(&mut self) -> DatasetResult<Expr> {
+        let mut left = self.additive()?;
+        while self.eat_symbol("&") {
+            left = Expr::Binary(BinaryOp::Concat, Box::new(left), Box::new(self.additive()?));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> DatasetResult<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = if self.eat_symbol("+") {
+                BinaryOp::Add
+            } else if self.eat_symbol("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> DatasetResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat_symbol("*") {
+                BinaryOp::Mul
+            } else if self.eat_symbol("/") {
+                BinaryOp::Div
+            } else if self.eat_symbol("%") {
+                BinaryOp::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> DatasetResult<Expr> {
+        if self.eat_symbol("-") {
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else if self.eat_symbol("+") {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> DatasetResult<Expr> {
+        match self.next() {
+            Some(Token::Integer(i)) => Ok(Expr::Integer(i)),
+            Some(Token::Float(f)) => Ok(Expr::Float(f)),
+            Some(Token::Text(s)) => Ok(Expr::Text(s)),
+            Some(Token::Name(name)) => Ok(Expr::Column(name)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.or()?;
+                if !self.eat_symbol(")") {
+                    return Err(invalid("Missing ')' in formula"));
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(word)) => {
+                if self.eat_symbol("(") {
+                    return self.call(word.to_ascii_uppercase());
+                }
+                match word.to_ascii_uppercase().as_str() {
+                    "TRUE" => Ok(Expr::Bool(true)),
+                    "FALSE" => Ok(Expr::Bool(false)),
+                    "NULL" => Ok(Expr::Null),
+                    _ => Ok(Expr::Column(word)),
+                }
+            }
+            Some(token) => Err(invalid(format!("Unexpected {token:?} in formula"))),
+            None => Err(invalid("Formula ends unexpectedly")),
+        }
+    }
+
+    fn call(&mut self, name: String) -> DatasetResult<Expr> {
+        let mut args = Vec::new();
+        if !self.eat_symbol(")") {
+            loop {
+                args.push(self.or()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                if !self.eat_symbol(",") {
+                    return Err(invalid(format!("Expected ',' or ')' in call to {name}")));
+                }
+            }
+        }
+        Ok(Expr::Call(name, args))
+    }
+}
+
+// -- Compilation --
+
+/// Compiles an expression to SQL, resolving names through `scope`.
+pub(crate) fn compile(expr: &Expr, scope: &mut dyn Scope) -> DatasetResult<Compiled> {
+    use DatasetColumnType as T;
+    Ok(match expr {
+        Expr::Integer(i) => Compiled::new(i.to_string(), T::Integer),
+        Expr::Float(f) => Compiled::new(format!("{f:?}"), T::Float),
+        Expr::Text(s) => Compiled::new(quote(s), T::Text),
+        Expr::Bool(b) => Compiled::new((*b as i32).to_string(), T::Boolean),
+        Expr::Null => Compiled::new("NULL".to_string(), T::Text),
+        Expr::Column(name) => scope.column(name)?,
+        Expr::Negate(inner) => {
+            let inner = compile(inner, scope)?;
+            let column_type = numeric(&inner.column_type, &inner.column_type);
+            Compiled::new(format!("(-{})", inner.sql), column_type)
+        }
+        Expr::Not(inner) => {
+            let inner = compile(inner, scope)?;
+            Compiled::new(format!("(NOT {})", inner.sql), T::Boolean)
+        }
+        Expr::Binary(op, left, right) => {
+            let left = compile(left, scope)?;
+            let right = compile(right, scope)?;
+            let (l, r) = (&left.sql, &right.sql);
+            match op {
+                BinaryOp::Concat => Compiled::new(
+                    format!("(COALESCE({l}, '') || COALESCE({r}, ''))"),
+                    T::Text,
+                ),
+                BinaryOp::Div => Compiled::new(format!("(CAST({l} AS REAL) / {r})"), T::Float),
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Rem => Compiled::new(
+                    format!("({l} {} {r})", op.sql()),
+                    numeric(&left.column_type, &right.column_type),
+                ),
+                _ => Compiled::new(format!("({l} {} {r})", op.sql()), T::Boolean),
+            }
+        }
+        Expr::Call(name, args) => compile_call(name, args, scope)?,
+    })
+}
+
+/// The type of arithmetic on two values.
+fn numeric(left: &DatasetColumnType, right: &DatasetColumnType) -> DatasetColumnType {
+    use DatasetColumnType as T;
+    match (left, right) {
+        (T::Integer | T::Boolean, T::Integer | T::Boolean) => T::Integer,
+        _ => T::Float,
+    }
+}
+
+/// The type holding values of both branches of a choice.
+fn common(left: &DatasetColumnType, right: &DatasetColumnType) -> DatasetColumnType {
+    use DatasetColumnType as T;
+    match (left, right) {
+        (a, b) if a == b => a.clone(),
+        (T::Integer | T::Float, T::Integer | T::Float) => T::Float,
+        _ => T::Text,
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// A name given as an argument: a bare word, a `[name]` or a text literal.
+fn name_arg(expr: &Expr, function: &str) -> DatasetResult<String> {
+    match expr {
+        Expr::Column(name) | Expr::Text(name) => Ok(name.clone()),
+        _ => Err(invalid(format!("{function} expects a dataset or column name"))),
+    }
+}
+
+fn compile_call(name: &str, args: &[Expr], scope: &mut dyn Scope) -> DatasetResult<Compiled> {
+    use DatasetColumnType as T;
+
+    let &(_, min, max) = FUNCTIONS
+        .iter()
+        .find(|(f, _, _)| *f == name)
+        .ok_or_else(|| invalid(format!("Unknown function in formula: {name}")))?;
+    if args.len() < min || args.len() > max {
+        return Err(invalid(format!("Wrong number of arguments to {name}")));
+    }
+
+    // Related values name their dataset and column rather than compute them.
+    match name {
+        "LOOKUP" => {
+            return scope.related(&name_arg(&args[0], name)?, &name_arg(&args[1], name)?, None);
+        }
+        "ROLLUP" => {
+            let Expr::Text(aggregate) = &args[2] else {
+                return Err(invalid("ROLLUP expects 'sum', 'avg', 'min', 'max' or 'count'"));
+            };
+            let rollup = match aggregate.to_ascii_lowercase().as_str() {
+                "sum" => Rollup::Sum,
+                "avg" => Rollup::Avg,
+                "min" => Rollup::Min,
+                "max" => Rollup::Max,
+                "count" => Rollup::Count,
+                other => return Err(invalid(format!("Unknown ROLLUP aggregate: {other}"))),
+            };
+            return scope.related(
+                &name_arg(&args[0], name)?,
+                &name_arg(&args[1], name)?,
+                Some(rollup),
+            );
+        }
+        _ => {}
+    }
+
+    let args = args
+        .iter()
+        .map(|a| compile(a, scope))
+        .collect::<DatasetResult<Vec<_>>>()?;
+    let sql: Vec<&str> = args.iter().map(|a| a.sql.as_str()).collect();
+    let first_type = args.first().map(|a| a.column_type.clone());
+    let widest = || {
+        args.iter()
+            .skip(1)
+            .fold(first_type.clone().unwrap_or(T::Text), |acc, a| common(&acc, &a.column_type))
+    };
+
+    Ok(match name {
+        "IF" => Compiled::new(
+            format!("(CASE WHEN {} THEN {} ELSE {} END)", sql[0], sql[1], sql[2]),
+            common(&args[1].column_type, &args[2].column_type),
+        ),
+        "COALESCE" => Compiled::new(format!("COALESCE({}, NULL)", sql.join(", ")), widest()),
+        "ROUND" => match sql.get(1) {
+            Some(digits) => Compiled::new(format!("ROUND({}, {digits})", sql[0]), T::Float),
+            None => Compiled::new(format!("CAST(ROUND({}) AS INTEGER)", sql[0]), T::Integer),
+        },
+        "ABS" => Compiled::new(format!("ABS({})", sql[0]), numeric(&args[0].column_type, &T::Integer)),
+        "FLOOR" => Compiled::new(format!("CAST(FLOOR({}) AS INTEGER)", sql[0]), T::Integer),
+        "CEIL" => Compiled::new(format!("CAST(CEIL({}) AS INTEGER)", sql[0]), T::Integer),
+        "MIN" => Compiled::new(format!("MIN({})", sql.join(", ")), widest()),
+        "MAX" => Compiled::new(format!("MAX({})", sql.join(", ")), widest()),
+        "LEN" => Compiled::new(format!("LENGTH({})", sql[0]), T::Integer),
+        "UPPER" => Compiled::new(format!("UPPER({})", sql[0]), T::Text),
+        "LOWER" => Compiled::new(format!("LOWER({})", sql[0]), T::Text),
+        "TRIM" => Compiled::new(format!("TRIM({})", sql[0]), T::Text),
+        "LEFT" => Compiled::new(format!("SUBSTR({}, 1, {})", sql[0], sql[1]), T::Text),
+        "RIGHT" => Compiled::new(
+            format!("(CASE WHEN {1} > 0 THEN SUBSTR({0}, -({1})) ELSE '' END)", sql[0], sql[1]),
+            T::Text,
+        ),
+        "CONCAT" => Compiled::new(
+            format!(
+                "({})",
+                sql.iter()
+                    .map(|s| format!("COALESCE({s}, '')"))
+                    .collect::<Vec<_>>()
+                    .join(" || ")
+            ),
+            T::Text,
+        ),
+        "TODAY" => Compiled::new("DATE('now')".to_string(), T::Date),
+        "NOW" => Compiled::new("DATETIME('now')".to_string(), T::Timestamp),
+        "YEAR" => Compiled::new(format!("CAST(STRFTIME('%Y', {}) AS INTEGER)", sql[0]), T::Integer),
+        "MONTH" => Compiled::new(format!("CAST(STRFTIME('%m', {}) AS INTEGER)", sql[0]), T::Integer),
+        "DAY" => Compiled::new(format!("CAST(STRFTIME('%d', {}) AS INTEGER)", sql[0]), T::Integer),
+        "DAYS" => Compiled::new(
+            format!("(JULIANDAY({}) - JULIANDAY({}))", sql[0], sql[1]),
+            T::Float,
+        ),
+        "ADD_DAYS" => Compiled::new(
+            format!("DATE({}, PRINTF('%+d days', {}))", sql[0], sql[1]),
+            T::Date,
+        ),
+        other => return Err(invalid(format!("Unknown function in formula: {other}"))),
+    })
+}
@@ -0,0 +1,297 @@
+//! Compiles view filter trees to SQL.
+//!
+//! Each condition becomes a WHERE clause fragment whose values are bound
+//! as parameters, typed after the column they're compared with; nothing a
+//! user typed is spliced into the SQL. Regular expressions go through the
+//! `regexp` function registered on the store's connection, which also
+//! backs `REGEXP` in user SQL.
+
+use super::helpers::sanitize_identifier;
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::{DatasetColumn, DatasetColumnType, FilterNode, FilterOperator, ViewFilter};
+use privstack_db::rusqlite::functions::FunctionFlags;
+use privstack_db::rusqlite::types::{Value, ValueRef};
+use privstack_db::rusqlite::Connection;
+use regex_lite::Regex;
+use serde_json::Value as Json;
+
+/// Registers `regexp(pattern, text)`, which SQLite calls for
+/// `text REGEXP pattern`. Each pattern is compiled once per statement.
+pub(crate) fn register_functions(conn: &Connection) -> DatasetResult<()> {
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let regex = ctx.get_or_create_aux(0, |pattern| -> Result<Regex, BoxError> {
+                Ok(Regex::new(pattern.as_str()?)?)
+            })?;
+            let text = match ctx.get_raw(1) {
+                ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                ValueRef::Integer(i) => i.to_string(),
+                ValueRef::Real(f) => f.to_string(),
+                ValueRef::Null | ValueRef::Blob(_) => return Ok(None),
+            };
+            Ok(Some(regex.is_match(&text)))
+        },
+    )?;
+    Ok(())
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The SQL condition for a filter tree over `columns`, with its
+/// parameters appended to `params`.
+pub(crate) fn compile_filter(
+    columns: &[DatasetColumn],
+    node: &FilterNode,
+    params: &mut Vec<Value>,
+) -> DatasetResult<String> {
+    Compiler { columns, params }.node(node)
+}
+
+/// The SQL condition for a full-text search over the text columns of
+/// `columns`, with its parameters appended to `params`.
+pub(crate) fn compile_search(
+    columns: &[DatasetColumn],
+    text: &str,
+    params: &mut Vec<Value>,
+) -> String {
+    Compiler { columns, params }.search(text)
+}
+
+struct Compiler<'a> {
+    columns: &'a [DatasetColumn],
+    params: &'a mut Vec<Value>,
+}
+
+impl Compiler<'_> {
+    fn node(&mut self, node: &FilterNode) -> DatasetResult<String> {
+        match node {
+            FilterNode::And(nodes) => self.group(nodes, " AND ", "1"),
+            FilterNode::Or(nodes) => self.group(nodes, " OR ", "0"),
+            // A condition on a NULL is unknown, and so is its negation;
+            // NOT counts it as false, so the row passes.
+            FilterNode::Not(node) => Ok(format!("NOT coalesce({}, 0)", self.node(node)?)),
+            FilterNode::Condition(filter) => self.condition(filter),
+            FilterNode::Search(text) => Ok(self.search(text)),
+        }
+    }
+
+    fn group(&mut self, nodes: &[FilterNode], separator: &str, empty: &str) -> DatasetResult<String> {
+        if nodes.is_empty() {
+            return Ok(empty.to_string());
+        }
+        let conditions = nodes.iter().map(|n| self.node(n)).collect::<DatasetResult<Vec<_>>>()?;
+        Ok(format!("({})", conditions.join(separator)))
+    }
+
+    /// Each word must appear in one of the text columns; words are matched
+    /// against the columns joined by spaces, so none spans two columns.
+    fn search(&mut self, text: &str) -> String {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            return "1".to_string();
+        }
+        let text_columns: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|c| c.column_type == DatasetColumnType::Text)
+            .map(|c| format!("coalesce(\"{}\", '')", sanitize_identifier(&c.name)))
+            .collect();
+        if text_columns.is_empty() {
+            return "0".to_string();
+        }
+        let haystack = format!("lower({})", text_columns.join(" || ' ' || "));
+        let conditions: Vec<String> = words
+            .into_iter()
+            .map(|word| {
+                self.params.push(Value::Text(word.to_string()));
+                format!("instr({haystack}, lower(?)) > 0")
+            })
+            .collect();
+        format!("({})", conditions.join(" AND "))
+    }
+
+    fn condition(&mut self, filter: &ViewFilter) -> DatasetResult<String> {
+        let col = self
+            .columns
+            .iter()
+            .find(|c| c.name == filter.column)
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown column: {}", filter.column)))?;
+        let quoted = format!("\"{}\"", sanitize_identifier(&col.name));
+        // Dates and timestamps compare as points in time, whatever their
+        // text looks like.
+        let (operand, placeholder) = match col.column_type {
+            DatasetColumnType::Date | DatasetColumnType::Timestamp => {
+                (format!("julianday({quoted})"), "julianday(?)")
+            }
+            _ => (quoted.clone(), "?"),
+        };
+
+        let condition = match filter.operator {
+            FilterOperator::Equals => {
+                self.bind(col, scalar(filter)?)?;
+                format!("{quoted} = ?")
+            }
+            FilterOperator::NotEquals => {
+                self.bind(col, scalar(filter)?)?;
+                format!("({quoted} IS NULL OR {quoted} <> ?)")
+            }
+            FilterOperator::GreaterThan
+            | FilterOperator::LessThan
+            | FilterOperator::GreaterOrEqual
+            | FilterOperator::LessOrEqual => {
+                let op = match filter.operator {
+                    FilterOperator::GreaterThan => ">",
+                    FilterOperator::LessThan => "<",
+                    FilterOperator::GreaterOrEqual => ">=",
+                    _ => "<=",
+                };
+                self.bind(col, scalar(filter)?)?;
+                format!("{operand} {op} {placeholder}")
+            }
+            FilterOperator::Between => {
+                let bounds = list(filter)?;
+                let [low, high] = bounds else {
+                    return Err(invalid(filter, "expects a [low, high] pair"));
+                };
+                self.bind(col, low)?;
+                self.bind(col, high)?;
+                format!("{operand} BETWEEN {placeholder} AND {placeholder}")
+            }
+            FilterOperator::In | FilterOperator::NotIn => {
+                let values = list(filter)?;
+                let negated = filter.operator == FilterOperator::NotIn;
+                if values.is_empty() {
+                    return Ok(if negated { "1" } else { "0" }.to_string());
+                }
+                for value in values {
+                    self.bind(col, value)?;
+                }
+                let placeholders = vec!["?"; values.len()].join(", ");
+                if negated {
+                    format!("({quoted} IS NULL OR {quoted} NOT IN ({placeholders}))")
+                } else {
+                    format!("{quoted} IN ({placeholders})")
+                }
+            }
+            FilterOperator::Contains => {
+                self.params.push(Value::Text(text(filter)?));
+                format!("instr(lower({quoted}), lower(?)) > 0")
+            }
+            FilterOperator::StartsWith => {
+                self.params.push(Value::Text(format!("{}%", escape_like(&text(filter)?))));
+                format!("{quoted} LIKE ? ESCAPE '\\'")
+            }
+            FilterOperator::EndsWith => {
+                self.params.push(Value::Text(format!("%{}", escape_like(&text(filter)?))));
+                format!("{quoted} LIKE ? ESCAPE '\\'")
+            }
+            FilterOperator::Matches => {
+                let pattern = text(filter)?;
+                Regex::new(&pattern).map_err(|e| invalid(filter, &format!("has an invalid pattern: {e}")))?;
+                self.params.push(Value::Text(pattern));
+                format!("{quoted} REGEXP ?")
+            }
+            FilterOperator::Before | FilterOperator::After => {
+                let op = if filter.operator == FilterOperator::Before { "<" } else { ">" };
+                self.params.push(Value::Text(text(filter)?));
+                format!("julianday({quoted}) {op} julianday(?)")
+            }
+            FilterOperator::InLast | FilterOperator::InNext => {
+                let (amount, unit) = period(filter)?;
+                if filter.operator == FilterOperator::InLast {
+                    self.params.push(Value::Text(format!("-{amount} {unit}")));
+                    format!("date({quoted}) BETWEEN date('now', ?) AND date('now')")
+                } else {
+                    self.params.push(Value::Text(format!("+{amount} {unit}")));
+                    format!("date({quoted}) BETWEEN date('now') AND date('now', ?)")
+                }
+            }
+            FilterOperator::IsEmpty => format!("({quoted} IS NULL OR {quoted} = '')"),
+            FilterOperator::IsNotEmpty => format!("({quoted} IS NOT NULL AND {quoted} <> '')"),
+        };
+        Ok(condition)
+    }
+
+    /// Binds a value typed after the column: numeric text compares as a
+    /// number in numeric columns, and booleans as 0 or 1.
+    fn bind(&mut self, col: &DatasetColumn, value: &Json) -> DatasetResult<()> {
+        let value = match value {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Integer(i64::from(*b)),
+            Json::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            Json::String(s) => match col.column_type {
+                DatasetColumnType::Integer | DatasetColumnType::Boolean => {
+                    s.trim().parse().map(Value::Integer).unwrap_or_else(|_| Value::Text(s.clone()))
+                }
+                DatasetColumnType::Float => {
+                    s.trim().parse().map(Value::Real).unwrap_or_else(|_| Value::Text(s.clone()))
+                }
+                _ => Value::Text(s.clone()),
+            },
+            Json::Array(_) | Json::Object(_) => {
+                return Err(DatasetError::InvalidQuery(format!(
+                    "Filter on {} expects single values, got {value}",
+                    col.name
+                )))
+            }
+        };
+        self.params.push(value);
+        Ok(())
+    }
+}
+
+fn invalid(filter: &ViewFilter, problem: &str) -> DatasetError {
+    DatasetError::InvalidQuery(format!("Filter {:?} on {} {problem}", filter.operator, filter.column))
+}
+
+fn scalar(filter: &ViewFilter) -> DatasetResult<&Json> {
+    match &filter.value {
+        Json::Array(_) | Json::Object(_) => Err(invalid(filter, "expects a single value")),
+        value => Ok(value),
+    }
+}
+
+fn list(filter: &ViewFilter) -> DatasetResult<&[Json]> {
+    match &filter.value {
+        Json::Array(values) => Ok(values),
+        _ => Err(invalid(filter, "expects an array of values")),
+    }
+}
+
+fn text(filter: &ViewFilter) -> DatasetResult<String> {
+    match &filter.value {
+        Json::String(s) => Ok(s.clone()),
+        Json::Number(n) => Ok(n.to_string()),
+        Json::Bool(b) => Ok(b.to_string()),
+        _ => Err(invalid(filter, "expects a text value")),
+    }
+}
+
+/// Parses a period like `30 days` or `1 week` into a count and a unit
+/// SQLite's date modifiers understand; weeks become days.
+fn period(filter: &ViewFilter) -> DatasetResult<(u32, &'static str)> {
+    let value = text(filter)?;
+    let mut parts = value.split_whitespace();
+    let (Some(amount), Some(unit), None) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid(filter, "expects a period like \"30 days\""));
+    };
+    let amount: u32 = amount.parse().map_err(|_| invalid(filter, "expects a whole number of periods"))?;
+    match unit.to_ascii_lowercase().trim_end_matches('s') {
+        "day" => Ok((amount, "days")),
+        "week" => Ok((amount.saturating_mul(7), "days")),
+        "month" => Ok((amount, "months")),
+        "year" => Ok((amount, "years")),
+        _ => Err(invalid(filter, "expects days, weeks, months or years")),
+    }
+}
+
+/// Escapes LIKE wildcards, with `\` as the escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
@@ -0,0 +1,406 @@
+//! Formula columns: definition, dependency checks, and the computed view of
+//! a dataset that reads see.
+//!
+//! Formulas live in `_datasets_meta.formulas_json`. Nothing is stored per
+//! row: before a read runs, [`with_computed_columns`] prefixes it with a CTE
+//! named after each dataset table it mentions. The CTE selects the stored
+//! columns plus one compiled expression per formula, so the statement sees
+//! computed columns wherever it reads the table — in queries, views,
+//! aggregates and raw SQL alike. Related values compile to correlated
+//! subqueries over the other dataset's table, joined through a declared
+//! relation.
+
+use super::expression::{self, Compiled, Rollup, Scope};
+use super::helpers::{introspect_columns, now_millis, sanitize_identifier};
+use super::sandbox::is_dataset_table;
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{DatasetColumn, DatasetColumnType, DatasetId, FormulaColumn};
+use privstack_db::rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeSet, HashMap};
+use tracing::warn;
+use uuid::Uuid;
+
+impl DatasetStore {
+    /// Add a formula column to a dataset, or change the expression of an
+    /// existing one. Fails if the expression does not compile, refers to
+    /// unknown columns or unrelated datasets, or makes formulas depend on
+    /// each other in a cycle.
+    pub fn set_formula_column(
+        &self,
+        id: &DatasetId,
+        name: &str,
+        expression: &str,
+    ) -> DatasetResult<FormulaColumn> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DatasetError::InvalidQuery("Formula column name is empty".to_string()));
+        }
+        let conn = self.lock_conn();
+        let stored = introspect_columns(&conn, &dataset_table_name(id))?;
+        if stored.iter().any(|c| c.name.eq_ignore_ascii_case(name)) {
+            return Err(DatasetError::InvalidQuery(format!(
+                "Column '{name}' already exists"
+            )));
+        }
+
+        let mut formulas = load_formulas(&conn, id)?;
+        let formula = FormulaColumn {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            column_type: DatasetColumnType::Text,
+        };
+        match formulas.iter_mut().find(|f| f.name.eq_ignore_ascii_case(name)) {
+            Some(existing) => *existing = formula,
+            None => formulas.push(formula),
+        }
+        let formulas = check_formulas(&conn, id, &stored, formulas)?;
+        save_formulas(&conn, id, &formulas)?;
+        self.record_meta_changed(&conn, id)?;
+
+        let saved = formulas
+            .into_iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+            .expect("formula was just saved");
+        Ok(saved)
+    }
+
+    /// Remove a formula column. Fails if other formulas use it.
+    pub fn remove_formula_column(&self, id: &DatasetId, name: &str) -> DatasetResult<()> {
+        let conn = self.lock_conn();
+        let stored = introspect_columns(&conn, &dataset_table_name(id))?;
+        let mut formulas = load_formulas(&conn, id)?;
+        let before = formulas.len();
+        formulas.retain(|f| !f.name.eq_ignore_ascii_case(name));
+        if formulas.len() == before {
+            return Err(DatasetError::NotFound(format!("formula column {name}")));
+        }
+        let formulas = check_formulas(&conn, id, &stored, formulas).map_err(|e| {
+            DatasetError::InvalidQuery(format!("Formula column '{name}' is in use: {e}"))
+        })?;
+        save_formulas(&conn, id, &formulas)?;
+        self.record_meta_changed(&conn, id)
+    }
+
+    /// List a dataset's formula columns.
+    pub fn list_formula_columns(&self, id: &DatasetId) -> DatasetResult<Vec<FormulaColumn>> {
+        let conn = self.lock_conn();
+        load_formulas(&conn, id)
+    }
+}
+
+/// A dataset's formula columns. Fails if the dataset does not exist.
+pub(crate) fn load_formulas(conn: &Connection, id: &DatasetId) -> DatasetResult<Vec<FormulaColumn>> {
+    let json: String = conn
+        .query_row(
+            "SELECT formulas_json FROM _datasets_meta WHERE id = ?1",
+            params![id.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| DatasetError::NotFound(id.to_string()))?;
+    Ok(serde_json::from_str(&json).unwrap_or_default())
+}
+
+fn save_formulas(conn: &Connection, id: &DatasetId, formulas: &[FormulaColumn]) -> DatasetResult<()> {
+    conn.execute(
+        "UPDATE _datasets_meta SET formulas_json = ?1, modified_at = ?2 WHERE id = ?3",
+        params![serde_json::to_string(formulas)?, now_millis(), id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Compiles every formula strictly and checks the resulting SQL against
+/// the database. Returns the formulas with their inferred types.
+fn check_formulas(
+    conn: &Connection,
+    id: &DatasetId,
+    stored: &[DatasetColumn],
+    mut formulas: Vec<FormulaColumn>,
+) -> DatasetResult<Vec<FormulaColumn>> {
+    let compiled = compile_formulas(conn, id, stored, &formulas);
+    let mut selected = Vec::with_capacity(compiled.len());
+    for (formula, result) in formulas.iter_mut().zip(compiled) {
+        let result = result.map_err(|e| match e {
+            DatasetError::InvalidQuery(msg) => {
+                DatasetError::InvalidQuery(format!("Formula '{}': {msg}", formula.name))
+            }
+            other => other,
+        })?;
+        formula.column_type = result.column_type;
+        selected.push(format!("{} AS \"{}\"", result.sql, sanitize_identifier(&formula.name)));
+    }
+    if !selected.is_empty() {
+        let table = dataset_table_name(id);
+        conn.prepare(&format!(
+            "SELECT {} FROM main.{table} AS t LIMIT 0",
+            selected.join(", ")
+        ))
+        .map_err(|e| DatasetError::InvalidQuery(format!("Formula does not compile: {e}")))?;
+    }
+    Ok(formulas)
+}
+
+/// Compiles each formula of a dataset, in order.
+fn compile_formulas(
+    conn: &Connection,
+    id: &DatasetId,
+    stored: &[DatasetColumn],
+    formulas: &[FormulaColumn],
+) -> Vec<DatasetResult<Compiled>> {
+    let mut scope = FormulaScope {
+        conn,
+        dataset_id: id,
+        stored,
+        formulas,
+        compiled: HashMap::new(),
+        visiting: Vec::new(),
+        subqueries: 0,
+    };
+    (0..formulas.len()).map(|i| scope.formula(i)).collect()
+}
+
+/// Resolves the names in a dataset's formulas.
+struct FormulaScope<'a> {
+    conn: &'a Connection,
+    dataset_id: &'a DatasetId,
+    stored: &'a [DatasetColumn],
+    formulas: &'a [FormulaColumn],
+    /// Formulas compiled so far, by index.
+    compiled: HashMap<usize, Compiled>,
+    /// Formulas being compiled, outermost first, to detect cycles.
+    visiting: Vec<usize>,
+    /// Subqueries emitted so far, to give each an alias of its own.
+    subqueries: usize,
+}
+
+impl FormulaScope<'_> {
+    fn formula(&mut self, index: usize) -> DatasetResult<Compiled> {
+        if let Some(compiled) = self.compiled.get(&index) {
+            return Ok(compiled.clone());
+        }
+        if let Some(start) = self.visiting.iter().position(|&i| i == index) {
+            let cycle: Vec<&str> = self.visiting[start..]
+                .iter()
+                .chain(std::iter::once(&index))
+                .map(|&i| self.formulas[i].name.as_str())
+                .collect();
+            return Err(DatasetError::InvalidQuery(format!(
+                "Formula columns form a cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        self.visiting.push(index);
+        let result = expression::parse(&self.formulas[index].expression)
+            .and_then(|expr| expression::compile(&expr, self));
+        self.visiting.pop();
+
+        let compiled = result?;
+        self.compiled.insert(index, compiled.clone());
+        Ok(compiled)
+    }
+
+    /// Finds the relation linking this dataset to another, as the column
+    /// on each side: this dataset's first, the other's second.
+    fn relation_to(&self, other: &DatasetId) -> DatasetResult<Option<(String, String)>> {
+        let this = self.dataset_id.to_string();
+        let other = other.to_string();
+        let forward = self
+            .conn
+            .query_row(
+                "SELECT source_column, target_column FROM _dataset_relations WHERE source_dataset_id = ?1 AND target_dataset_id = ?2 ORDER BY created_at LIMIT 1",
+                params![this, other],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        if forward.is_some() {
+            return Ok(forward);
+        }
+        let reverse = self
+            .conn
+            .query_row(
+                "SELECT target_column, source_column FROM _dataset_relations WHERE source_dataset_id = ?1 AND target_dataset_id = ?2 ORDER BY created_at LIMIT 1",
+                params![other, this],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(reverse)
+    }
+}
+
+impl Scope for FormulaScope<'_> {
+    fn column(&mut self, name: &str) -> DatasetResult<Compiled> {
+        let stored = self
+            .stored
+            .iter()
+            .find(|c| c.name == name)
+            .or_else(|| self.stored.iter().find(|c| c.name.eq_ignore_ascii_case(name)));
+        if let Some(column) = stored {
+            return Ok(Compiled {
+                sql: format!("t.\"{}\"", sanitize_identifier(&column.name)),
+                column_type: column.column_type.clone(),
+            });
+        }
+        let formula = self
+            .formulas
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown column: {name}")))?;
+        let compiled = self.formula(formula)?;
+        Ok(Compiled {
+            sql: format!("({})", compiled.sql),
+            column_type: compiled.column_type,
+        })
+    }
+
+    fn related(
+        &mut self,
+        dataset: &str,
+        column: &str,
+        aggregate: Option<Rollup>,
+    ) -> DatasetResult<Compiled> {
+        let other: String = self
+            .conn
+            .query_row(
+                "SELECT id FROM _datasets_meta WHERE name = ?1 ORDER BY created_at LIMIT 1",
+                params![dataset],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown dataset: {dataset}")))?;
+        let other = Uuid::parse_str(&other)
+            .map(DatasetId)
+            .map_err(|e| DatasetError::InvalidQuery(format!("Invalid dataset ID: {e}")))?;
+        let (this_column, other_column) = self.relation_to(&other)?.ok_or_else(|| {
+            DatasetError::InvalidQuery(format!("No relation links this dataset to '{dataset}'"))
+        })?;
+        if !self.stored.iter().any(|c| c.name == this_column) {
+            return Err(DatasetError::InvalidQuery(format!(
+                "Relation column '{this_column}' no longer exists"
+            )));
+        }
+
+        // Related datasets contribute stored columns only, which keeps
+        // dependencies within one dataset.
+        let other_table = dataset_table_name(&other);
+        let target = introspect_columns(self.conn, &other_table)?
+            .into_iter()
+            .find(|c| c.name == column)
+            .ok_or_else(|| {
+                DatasetError::InvalidQuery(format!("Unknown column '{column}' in '{dataset}'"))
+            })?;
+
+        self.subqueries += 1;
+        let alias = format!("r{}", self.subqueries);
+        let value = format!("{alias}.\"{}\"", sanitize_identifier(&target.name));
+        let join = format!(
+            "FROM main.{other_table} AS {alias} WHERE {alias}.\"{}\" = t.\"{}\"",
+            sanitize_identifier(&other_column),
+            sanitize_identifier(&this_column)
+        );
+        Ok(match aggregate {
+            None => Compiled {
+                sql: format!("(SELECT {value} {join} LIMIT 1)"),
+                column_type: target.column_type,
+            },
+            Some(rollup) => {
+                let column_type = match rollup {
+                    Rollup::Count => DatasetColumnType::Integer,
+                    Rollup::Avg => DatasetColumnType::Float,
+                    Rollup::Sum if target.column_type == DatasetColumnType::Integer => {
+                        DatasetColumnType::Integer
+                    }
+                    Rollup::Sum => DatasetColumnType::Float,
+                    Rollup::Min | Rollup::Max => target.column_type,
+                };
+                let mut sql = format!("(SELECT {}({value}) {join})", rollup.sql_function());
+                if rollup == Rollup::Sum {
+                    // Rows without related rows total zero, not null.
+                    sql = format!("COALESCE({sql}, 0)");
+                }
+                Compiled { sql, column_type }
+            }
+        })
+    }
+}
+
+/// A dataset's columns as reads see them: stored columns, then formula
+/// columns.
+pub(crate) fn dataset_columns(conn: &Connection, id: &DatasetId) -> DatasetResult<Vec<DatasetColumn>> {
+    let mut columns = introspect_columns(conn, &dataset_table_name(id))?;
+    if columns.is_empty() {
+        return Ok(columns);
+    }
+    let formulas = load_formulas(conn, id).unwrap_or_default();
+    let first = columns.len() as i32;
+    columns.extend(formulas.into_iter().enumerate().map(|(i, f)| DatasetColumn {
+        name: f.name,
+        column_type: f.column_type,
+        ordinal: first + i as i32,
+    }));
+    Ok(columns)
+}
+
+/// Prefixes a read-only statement with the computed view of every dataset
+/// table it mentions that has formula columns. A formula that no longer
+/// compiles, say because a column it uses was dropped, reads as null.
+pub(crate) fn with_computed_columns(conn: &Connection, sql: &str) -> DatasetResult<String> {
+    let re = regex_lite::Regex::new(r"(?i)\bds_[0-9a-f]{32}\b")
+        .map_err(|e| DatasetError::InvalidQuery(format!("Regex error: {e}")))?;
+    let tables: BTreeSet<String> = re
+        .find_iter(sql)
+        .map(|m| m.as_str().to_ascii_lowercase())
+        .filter(|t| is_dataset_table(t))
+        .collect();
+
+    let mut ctes = Vec::new();
+    for table in tables {
+        let Ok(uuid) = Uuid::parse_str(&table["ds_".len()..]) else {
+            continue;
+        };
+        let id = DatasetId(uuid);
+        let Ok(formulas) = load_formulas(conn, &id) else {
+            continue;
+        };
+        if formulas.is_empty() {
+            continue;
+        }
+        let stored = introspect_columns(conn, &table)?;
+        let compiled = compile_formulas(conn, &id, &stored, &formulas);
+        let mut selected = vec!["t.*".to_string()];
+        for (formula, result) in formulas.iter().zip(compiled) {
+            let sql = result.map(|c| c.sql).unwrap_or_else(|e| {
+                warn!(dataset_id = %id, formula = %formula.name, "Formula no longer compiles: {e}");
+                "NULL".to_string()
+            });
+            selected.push(format!("{sql} AS \"{}\"", sanitize_identifier(&formula.name)));
+        }
+        ctes.push(format!(
+            "{table} AS (SELECT {} FROM main.{table} AS t)",
+            selected.join(", ")
+        ));
+    }
+    if ctes.is_empty() {
+        return Ok(sql.to_string());
+    }
+
+    let ctes = ctes.join(", ");
+    let trimmed = sql.trim_start();
+    Ok(match strip_keyword(trimmed, "WITH") {
+        Some(rest) => match strip_keyword(rest, "RECURSIVE") {
+            Some(rest) => format!("WITH RECURSIVE {ctes}, {rest}"),
+            None => format!("WITH {ctes}, {rest}"),
+        },
+        None => format!("WITH {ctes} {trimmed}"),
+    })
+}
+
+/// The rest of `sql` after a leading keyword and the whitespace after it.
+fn strip_keyword<'s>(sql: &'s str, keyword: &str) -> Option<&'s str> {
+    let head = sql.get(..keyword.len())?;
+    let rest = &sql[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace))
+        .then(|| rest.trim_start())
+}
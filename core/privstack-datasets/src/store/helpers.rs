@@ -1,7 +1,9 @@
 //! Shared helper functions for dataset store operations.
 
-use crate::error::DatasetResult;
-use crate::types::{DatasetColumn, DatasetColumnType};
+use super::filters::{compile_filter, compile_search};
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::{DatasetColumn, DatasetColumnType, SortDirection, ViewConfig};
+use privstack_db::rusqlite::types::Value;
 use privstack_db::rusqlite::Connection;
 
 /// Introspect column names and types from an existing table via `PRAGMA table_info()`.
@@ -30,31 +32,63 @@ pub(crate) fn introspect_columns(
     Ok(columns)
 }
 
-/// Build a WHERE clause that LIKEs the filter text across all text columns.
+/// Build a WHERE clause that searches the filter text across all text
+/// columns, with its parameters.
 pub(crate) fn build_filter_clause(
     columns: &[DatasetColumn],
     filter_text: Option<&str>,
-) -> String {
+) -> (String, Vec<Value>) {
+    let mut params = Vec::new();
     match filter_text {
-        Some(text) if !text.is_empty() => {
-            let escaped = text.replace('\'', "''");
-            let text_cols: Vec<&DatasetColumn> = columns
-                .iter()
-                .filter(|c| c.column_type == DatasetColumnType::Text)
-                .collect();
-            if text_cols.is_empty() {
-                return String::new();
-            }
-            let conditions: Vec<String> = text_cols
-                .iter()
-                .map(|c| format!("\"{}\" LIKE '%{escaped}%'", sanitize_identifier(&c.name)))
-                .collect();
-            format!(" WHERE {}", conditions.join(" OR "))
+        Some(text) if !text.trim().is_empty() => {
+            let condition = compile_search(columns, text, &mut params);
+            (format!(" WHERE {condition}"), params)
         }
-        _ => String::new(),
+        _ => (String::new(), params),
     }
 }
 
+/// Build the WHERE and ORDER BY clauses of a saved view. Filters compile
+/// to conditions with their values bound as parameters; rows are ordered
+/// by the group-by column first, then by the view's sorts.
+pub(crate) fn build_view_clauses(
+    columns: &[DatasetColumn],
+    config: &ViewConfig,
+) -> DatasetResult<(String, Vec<Value>)> {
+    let column = |name: &str| {
+        columns
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown column: {name}")))
+    };
+
+    let mut clauses = String::new();
+    let mut params = Vec::new();
+    if let Some(filter) = config.filter_tree() {
+        let condition = compile_filter(columns, &filter, &mut params)?;
+        clauses.push_str(&format!(" WHERE {condition}"));
+    }
+
+    let mut order = Vec::new();
+    if let Some(group_by) = &config.group_by {
+        let col = column(group_by)?;
+        order.push(format!("\"{}\" ASC", sanitize_identifier(&col.name)));
+    }
+    for sort in &config.sorts {
+        let col = column(&sort.column)?;
+        let dir = match sort.direction {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+        order.push(format!("\"{}\" {dir}", sanitize_identifier(&col.name)));
+    }
+
+    if !order.is_empty() {
+        clauses.push_str(&format!(" ORDER BY {}", order.join(", ")));
+    }
+    Ok((clauses, params))
+}
+
 /// Escape a column name for use in double-quoted SQL identifiers.
 /// We only need to escape embedded double quotes (by doubling them).
 pub(crate) fn sanitize_identifier(name: &str) -> String {
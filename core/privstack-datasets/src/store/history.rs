@@ -0,0 +1,473 @@
+//! Change history: undo, redo and restore points for dataset edits.
+//!
+//! Edits made through the store are journaled in `_dataset_history` with
+//! the pre-image that reverses them. Cell and row edits keep the rows they
+//! touched; column changes and SQL mutations keep a copy of the whole
+//! table, in a `_dataset_snap_<uuid>` table. Undoing an edit swaps its
+//! pre-image with the table's current state, which leaves in the entry
+//! exactly what redoing it needs. A new edit discards the edits that were
+//! undone.
+//!
+//! Undo and redo change the table like any other edit, so replication
+//! publishes what they change.
+
+use super::helpers::{introspect_columns, now_millis, row_value_to_json, sanitize_identifier};
+use super::replication::{declared_columns, json_to_sql};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{DatasetId, HistoryEntry, HistoryEntryKind, HistoryLimits};
+use privstack_db::rusqlite::types::Value as SqlValue;
+use privstack_db::rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Rows an edit touched, by rowid, as they were before it: their values,
+/// or `None` for rows the edit inserted.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RowImage {
+    columns: Vec<String>,
+    rows: Vec<(i64, Option<Vec<Value>>)>,
+}
+
+impl RowImage {
+    /// An empty image of the table's rows.
+    pub(crate) fn new(conn: &Connection, table: &str) -> DatasetResult<Self> {
+        let columns = declared_columns(conn, table)?.into_iter().map(|(name, _)| name).collect();
+        Ok(Self { columns, rows: Vec::new() })
+    }
+
+    /// Keeps a row's current values, before an edit changes or deletes it.
+    pub(crate) fn capture(&mut self, conn: &Connection, table: &str, rowid: i64) -> DatasetResult<()> {
+        let values = read_row(conn, table, &self.columns, rowid)?;
+        self.rows.push((rowid, values));
+        Ok(())
+    }
+
+    /// Notes a row an edit inserted.
+    pub(crate) fn inserted(&mut self, rowid: i64) {
+        self.rows.push((rowid, None));
+    }
+
+    /// Writes the image into the table. Returns the image of what it
+    /// replaced.
+    fn swap(self, conn: &Connection, table: &str) -> DatasetResult<Self> {
+        let quoted: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| format!("\"{}\"", sanitize_identifier(c)))
+            .collect();
+        let placeholders: Vec<String> = (1..=self.columns.len() + 1).map(|i| format!("?{i}")).collect();
+        let insert = format!(
+            "INSERT OR REPLACE INTO {table} (rowid, {}) VALUES ({})",
+            quoted.join(", "),
+            placeholders.join(", ")
+        );
+
+        let mut replaced = Vec::with_capacity(self.rows.len());
+        for (rowid, values) in self.rows {
+            replaced.push((rowid, read_row(conn, table, &self.columns, rowid)?));
+            match values {
+                Some(values) => {
+                    let params = std::iter::once(SqlValue::Integer(rowid))
+                        .chain(values.iter().map(json_to_sql));
+                    conn.execute(&insert, params_from_iter(params))?;
+                }
+                None => {
+                    conn.execute(&format!("DELETE FROM {table} WHERE rowid = ?1"), params![rowid])?;
+                }
+            }
+        }
+        Ok(Self { columns: self.columns, rows: replaced })
+    }
+}
+
+/// A copy of a dataset table, taken before an edit. The copy is dropped
+/// again unless the edit is recorded.
+pub(crate) struct TableCopy<'c> {
+    conn: &'c Connection,
+    name: Option<String>,
+}
+
+impl<'c> TableCopy<'c> {
+    pub(crate) fn take(conn: &'c Connection, table: &str) -> DatasetResult<Self> {
+        let name = format!("_dataset_snap_{}", Uuid::new_v4().simple());
+        let columns = declared_columns(conn, table)?;
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(column, column_type)| format!("\"{}\" {column_type}", sanitize_identifier(column)))
+            .collect();
+        let quoted: Vec<String> = columns
+            .iter()
+            .map(|(column, _)| format!("\"{}\"", sanitize_identifier(column)))
+            .collect();
+        conn.execute_batch(&format!(
+            "CREATE TABLE {name} ({});\n\
+             INSERT INTO {name} (rowid, {cols}) SELECT rowid, {cols} FROM {table};",
+            definitions.join(", "),
+            cols = quoted.join(", "),
+        ))?;
+        Ok(Self { conn, name: Some(name) })
+    }
+
+    fn keep(mut self) -> String {
+        self.name.take().expect("table copy is kept once")
+    }
+}
+
+impl Drop for TableCopy<'_> {
+    fn drop(&mut self) {
+        if let Some(name) = self.name.take() {
+            let _ = self.conn.execute_batch(&format!("DROP TABLE IF EXISTS {name}"));
+        }
+    }
+}
+
+/// The pre-image an edit is recorded with.
+pub(crate) enum Image<'c> {
+    Rows(RowImage),
+    Table(TableCopy<'c>),
+}
+
+impl DatasetStore {
+    /// The change history of a dataset, oldest first.
+    pub fn history(&self, id: &DatasetId) -> DatasetResult<Vec<HistoryEntry>> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, dataset_id, kind, label, undone, created_at FROM _dataset_history WHERE dataset_id = ?1 ORDER BY id",
+        )?;
+        let entries = stmt
+            .query_map(params![id.to_string()], entry_from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(entries)
+    }
+
+    /// Undo the latest edit of a dataset. Returns the edit undone, or
+    /// `None` if there is nothing to undo.
+    pub fn undo(&self, id: &DatasetId) -> DatasetResult<Option<HistoryEntry>> {
+        let conn = self.lock_conn();
+        let latest: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM _dataset_history WHERE dataset_id = ?1 AND kind = 'edit' AND undone = 0 ORDER BY id DESC LIMIT 1",
+                params![id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(entry_id) = latest else {
+            return Ok(None);
+        };
+        self.swap_entry(&conn, id, entry_id, true)?;
+        load_entry(&conn, entry_id).map(Some)
+    }
+
+    /// Redo the edit of a dataset undone last. Returns the edit redone, or
+    /// `None` if there is nothing to redo.
+    pub fn redo(&self, id: &DatasetId) -> DatasetResult<Option<HistoryEntry>> {
+        let conn = self.lock_conn();
+        let next: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM _dataset_history WHERE dataset_id = ?1 AND kind = 'edit' AND undone = 1 ORDER BY id LIMIT 1",
+                params![id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(entry_id) = next else {
+            return Ok(None);
+        };
+        self.swap_entry(&conn, id, entry_id, false)?;
+        load_entry(&conn, entry_id).map(Some)
+    }
+
+    /// Mark the dataset's current state as a named restore point.
+    pub fn create_restore_point(&self, id: &DatasetId, name: &str) -> DatasetResult<HistoryEntry> {
+        let conn = self.lock_conn();
+        let exists: Option<String> = conn
+            .query_row(
+                "SELECT id FROM _datasets_meta WHERE id = ?1",
+                params![id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if exists.is_none() {
+            return Err(DatasetError::NotFound(id.to_string()));
+        }
+        let entry_id = self.record_restore_point(&conn, id, name)?;
+        load_entry(&conn, entry_id)
+    }
+
+    /// Bring a dataset back to its state right after a history entry:
+    /// undo the edits made since, or redo undone edits up to it. Returns
+    /// the number of edits undone or redone.
+    pub fn restore(&self, id: &DatasetId, entry_id: i64) -> DatasetResult<usize> {
+        let conn = self.lock_conn();
+        let entry = load_entry(&conn, entry_id)?;
+        if entry.dataset_id != *id {
+            return Err(DatasetError::NotFound(format!("history entry {entry_id}")));
+        }
+
+        let to_undo = entry_ids(
+            &conn,
+            "SELECT id FROM _dataset_history WHERE dataset_id = ?1 AND kind = 'edit' AND undone = 0 AND id > ?2 ORDER BY id DESC",
+            id,
+            entry_id,
+        )?;
+        let to_redo = entry_ids(
+            &conn,
+            "SELECT id FROM _dataset_history WHERE dataset_id = ?1 AND kind = 'edit' AND undone = 1 AND id <= ?2 ORDER BY id",
+            id,
+            entry_id,
+        )?;
+        for &edit in &to_undo {
+            self.swap_entry(&conn, id, edit, true)?;
+        }
+        for &edit in &to_redo {
+            self.swap_entry(&conn, id, edit, false)?;
+        }
+        Ok(to_undo.len() + to_redo.len())
+    }
+
+    /// Forget a dataset's change history.
+    pub fn clear_history(&self, id: &DatasetId) -> DatasetResult<()> {
+        let conn = self.lock_conn();
+        clear(&conn, id)
+    }
+
+    /// Prune every dataset's history to the store's history limits.
+    /// Returns the number of entries removed.
+    pub fn prune_history(&self) -> DatasetResult<usize> {
+        let limits = self.history_limits();
+        let conn = self.lock_conn();
+        prune(&conn, None, limits)
+    }
+
+    /// Journals an edit just made to a dataset. Edits that were undone can
+    /// no longer be redone.
+    pub(crate) fn record_edit(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        label: &str,
+        image: Image<'_>,
+    ) -> DatasetResult<()> {
+        discard_undone(conn, id)?;
+        let (image_json, snapshot) = match image {
+            Image::Rows(rows) => (Some(serde_json::to_string(&rows)?), None),
+            Image::Table(copy) => (None, Some(copy.keep())),
+        };
+        conn.execute(
+            "INSERT INTO _dataset_history (dataset_id, kind, label, image_json, snapshot_table, created_at) VALUES (?1, 'edit', ?2, ?3, ?4, ?5)",
+            params![id.to_string(), label, image_json, snapshot, now_millis()],
+        )?;
+        prune(conn, Some(id), self.history_limits())?;
+        Ok(())
+    }
+
+    /// Journals a restore point. Returns its entry ID.
+    pub(crate) fn record_restore_point(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        name: &str,
+    ) -> DatasetResult<i64> {
+        discard_undone(conn, id)?;
+        conn.execute(
+            "INSERT INTO _dataset_history (dataset_id, kind, label, created_at) VALUES (?1, 'restore_point', ?2, ?3)",
+            params![id.to_string(), name, now_millis()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Undoes or redoes one edit by swapping its pre-image with the table.
+    fn swap_entry(&self, conn: &Connection, id: &DatasetId, entry_id: i64, undo: bool) -> DatasetResult<()> {
+        let table = dataset_table_name(id);
+        let (image_json, snapshot): (Option<String>, Option<String>) = conn.query_row(
+            "SELECT image_json, snapshot_table FROM _dataset_history WHERE id = ?1",
+            params![entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let before = self.snapshot_for_diff(conn, &table)?;
+
+        conn.execute_batch("SAVEPOINT dataset_history")?;
+        let swapped = (|| {
+            if let Some(json) = image_json {
+                let image: RowImage = serde_json::from_str(&json)?;
+                let replaced = image.swap(conn, &table)?;
+                conn.execute(
+                    "UPDATE _dataset_history SET image_json = ?1 WHERE id = ?2",
+                    params![serde_json::to_string(&replaced)?, entry_id],
+                )?;
+            } else if let Some(copy) = snapshot {
+                let spare = format!("{copy}_swap");
+                conn.execute_batch(&format!(
+                    "ALTER TABLE {table} RENAME TO {spare};\n\
+                     ALTER TABLE {copy} RENAME TO {table};\n\
+                     ALTER TABLE {spare} RENAME TO {copy};"
+                ))?;
+            }
+            conn.execute(
+                "UPDATE _dataset_history SET undone = ?1 WHERE id = ?2",
+                params![undo, entry_id],
+            )?;
+            refresh_meta(conn, id, &table)
+        })();
+        if let Err(e) = swapped {
+            conn.execute_batch("ROLLBACK TO SAVEPOINT dataset_history; RELEASE SAVEPOINT dataset_history")?;
+            return Err(DatasetError::InvalidQuery(format!(
+                "Change can no longer be {}: {e}",
+                if undo { "undone" } else { "redone" }
+            )));
+        }
+        conn.execute_batch("RELEASE SAVEPOINT dataset_history")?;
+
+        if let Some(before) = before {
+            self.record_diff(conn, before)?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops a dataset's history and its table copies.
+pub(crate) fn clear(conn: &Connection, id: &DatasetId) -> DatasetResult<()> {
+    let doomed = entry_snapshots(
+        conn,
+        "SELECT id, snapshot_table FROM _dataset_history WHERE dataset_id = ?1",
+        params![id.to_string()],
+    )?;
+    remove_entries(conn, &doomed)
+}
+
+/// Removes the edits that were undone, and the restore points after them.
+fn discard_undone(conn: &Connection, id: &DatasetId) -> DatasetResult<()> {
+    let doomed = entry_snapshots(
+        conn,
+        "SELECT id, snapshot_table FROM _dataset_history WHERE dataset_id = ?1 AND id >= (SELECT MIN(id) FROM _dataset_history WHERE dataset_id = ?1 AND kind = 'edit' AND undone = 1)",
+        params![id.to_string()],
+    )?;
+    remove_entries(conn, &doomed)
+}
+
+/// Removes entries beyond the limits, for one dataset or all of them.
+/// Returns the number removed.
+fn prune(conn: &Connection, id: Option<&DatasetId>, limits: HistoryLimits) -> DatasetResult<usize> {
+    let cutoff = now_millis() - limits.max_age.as_millis() as i64;
+    let max_entries = limits.max_entries as i64;
+    let mut doomed = match id {
+        Some(id) => entry_snapshots(
+            conn,
+            "SELECT id, snapshot_table FROM _dataset_history WHERE dataset_id = ?1 AND created_at < ?2",
+            params![id.to_string(), cutoff],
+        )?,
+        None => entry_snapshots(
+            conn,
+            "SELECT id, snapshot_table FROM _dataset_history WHERE created_at < ?1",
+            params![cutoff],
+        )?,
+    };
+
+    let datasets: Vec<String> = match id {
+        Some(id) => vec![id.to_string()],
+        None => {
+            let mut stmt = conn.prepare("SELECT DISTINCT dataset_id FROM _dataset_history")?;
+            stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?
+        }
+    };
+    for dataset in datasets {
+        doomed.extend(entry_snapshots(
+            conn,
+            "SELECT id, snapshot_table FROM _dataset_history WHERE dataset_id = ?1 AND created_at >= ?2 ORDER BY id DESC LIMIT -1 OFFSET ?3",
+            params![dataset, cutoff, max_entries],
+        )?);
+    }
+
+    remove_entries(conn, &doomed)?;
+    Ok(doomed.len())
+}
+
+fn entry_snapshots(
+    conn: &Connection,
+    sql: &str,
+    params: impl privstack_db::rusqlite::Params,
+) -> DatasetResult<Vec<(i64, Option<String>)>> {
+    let mut stmt = conn.prepare(sql)?;
+    let entries = stmt
+        .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(entries)
+}
+
+fn remove_entries(conn: &Connection, entries: &[(i64, Option<String>)]) -> DatasetResult<()> {
+    for (entry_id, snapshot) in entries {
+        if let Some(snapshot) = snapshot {
+            conn.execute_batch(&format!("DROP TABLE IF EXISTS {snapshot}"))?;
+        }
+        conn.execute("DELETE FROM _dataset_history WHERE id = ?1", params![entry_id])?;
+    }
+    Ok(())
+}
+
+fn entry_ids(conn: &Connection, sql: &str, id: &DatasetId, entry_id: i64) -> DatasetResult<Vec<i64>> {
+    let mut stmt = conn.prepare(sql)?;
+    let ids = stmt
+        .query_map(params![id.to_string(), entry_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(ids)
+}
+
+fn load_entry(conn: &Connection, entry_id: i64) -> DatasetResult<HistoryEntry> {
+    conn.query_row(
+        "SELECT id, dataset_id, kind, label, undone, created_at FROM _dataset_history WHERE id = ?1",
+        params![entry_id],
+        entry_from_row,
+    )
+    .optional()?
+    .ok_or_else(|| DatasetError::NotFound(format!("history entry {entry_id}")))
+}
+
+fn entry_from_row(row: &Row<'_>) -> privstack_db::rusqlite::Result<HistoryEntry> {
+    let dataset_id: String = row.get(1)?;
+    let kind: String = row.get(2)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        dataset_id: DatasetId(Uuid::parse_str(&dataset_id).unwrap_or_default()),
+        kind: match kind.as_str() {
+            "restore_point" => HistoryEntryKind::RestorePoint,
+            _ => HistoryEntryKind::Edit,
+        },
+        label: row.get(3)?,
+        undone: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+/// A row's values in the given columns, or `None` if there is no such row.
+fn read_row(
+    conn: &Connection,
+    table: &str,
+    columns: &[String],
+    rowid: i64,
+) -> DatasetResult<Option<Vec<Value>>> {
+    let select: Vec<String> = columns
+        .iter()
+        .map(|c| format!("\"{}\"", sanitize_identifier(c)))
+        .collect();
+    let values = conn
+        .query_row(
+            &format!("SELECT {} FROM {table} WHERE rowid = ?1", select.join(", ")),
+            params![rowid],
+            |row| Ok((0..columns.len()).map(|i| row_value_to_json(row, i)).collect()),
+        )
+        .optional()?;
+    Ok(values)
+}
+
+/// Brings the dataset's metadata in line with its table after a swap.
+fn refresh_meta(conn: &Connection, id: &DatasetId, table: &str) -> DatasetResult<()> {
+    let columns = introspect_columns(conn, table)?;
+    let row_count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))?;
+    conn.execute(
+        "UPDATE _datasets_meta SET columns_json = ?1, row_count = ?2, modified_at = ?3 WHERE id = ?4",
+        params![serde_json::to_string(&columns)?, row_count, now_millis(), id.to_string()],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,859 @@
+//! File import: streaming readers for CSV, TSV, JSON/NDJSON, XLSX and
+//! Parquet, all feeding one table builder.
+//!
+//! Readers hand rows to the builder as they go, so a file is never held in
+//! memory whole. The builder keeps the first rows as a sample, infers column
+//! types from them, creates the table and from then on inserts directly. The
+//! whole import runs in one transaction: a failed import leaves nothing
+//! behind.
+
+use super::constraints::{check_all_rows, check_constraints, save_constraints};
+use super::helpers::now_millis;
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{
+    ColumnConstraints, DatasetColumn, DatasetColumnType, DatasetId, DatasetMeta, FileFormat,
+    ImportOptions, ImportProgress,
+};
+use privstack_db::rusqlite::types::Value;
+use privstack_db::rusqlite::{params, Connection};
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::rc::Rc;
+use tracing::info;
+
+/// Rows sampled to infer column types before the table is created.
+const SAMPLE_ROWS: usize = 100;
+
+/// Rows imported between progress reports.
+const PROGRESS_EVERY: u64 = 10_000;
+
+impl DatasetStore {
+    /// Import a CSV file into a new dataset. The file is streamed, not read
+    /// into memory.
+    pub fn import_csv(&self, file_path: &Path, name: &str) -> DatasetResult<DatasetMeta> {
+        let options = ImportOptions {
+            format: Some(FileFormat::Csv),
+            ..ImportOptions::default()
+        };
+        self.import_file(file_path, name, &options)
+    }
+
+    /// Import dataset from CSV content string (for clipboard paste).
+    pub fn import_csv_content(
+        &self,
+        csv_content: &str,
+        name: &str,
+        category: Option<&str>,
+    ) -> DatasetResult<DatasetMeta> {
+        self.import_content(csv_content, FileFormat::Csv, name, category)
+    }
+
+    /// Import text content (CSV, TSV, JSON or NDJSON) into a new dataset.
+    pub fn import_content(
+        &self,
+        content: &str,
+        format: FileFormat,
+        name: &str,
+        category: Option<&str>,
+    ) -> DatasetResult<DatasetMeta> {
+        let mut ignore = |_: &ImportProgress| {};
+        let progress = Progress::new(&mut ignore, None, None);
+        self.import_with(name, category, None, &[], progress, |builder| {
+            read_text(content.as_bytes(), format, builder)
+        })
+    }
+
+    /// Import a file into a new dataset.
+    pub fn import_file(
+        &self,
+        file_path: &Path,
+        name: &str,
+        options: &ImportOptions,
+    ) -> DatasetResult<DatasetMeta> {
+        self.import_file_with_progress(file_path, name, options, |_| {})
+    }
+
+    /// Import a file into a new dataset, calling `on_progress` every few
+    /// thousand rows and once at the end.
+    pub fn import_file_with_progress(
+        &self,
+        file_path: &Path,
+        name: &str,
+        options: &ImportOptions,
+        mut on_progress: impl FnMut(&ImportProgress),
+    ) -> DatasetResult<DatasetMeta> {
+        if !file_path.exists() {
+            return Err(DatasetError::ImportFailed(format!(
+                "File not found: {}",
+                file_path.display()
+            )));
+        }
+        let format = options
+            .format
+            .or_else(|| FileFormat::from_path(file_path))
+            .ok_or_else(|| {
+                DatasetError::ImportFailed(format!(
+                    "Unknown file format: {}",
+                    file_path.display()
+                ))
+            })?;
+        let source_file_name = file_path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string());
+        let category = options.category.as_deref();
+        let constraints = &options.constraints;
+
+        match format {
+            FileFormat::Xlsx => {
+                let progress = Progress::new(&mut on_progress, None, None);
+                self.import_with(name, category, source_file_name, constraints, progress, |builder| {
+                    read_xlsx(file_path, options.sheet.as_deref(), builder)
+                })
+            }
+            FileFormat::Parquet => {
+                let progress = Progress::new(&mut on_progress, None, None);
+                self.import_with(name, category, source_file_name, constraints, progress, |builder| {
+                    read_parquet(file_path, builder)
+                })
+            }
+            _ => {
+                let file = File::open(file_path).map_err(|e| {
+                    DatasetError::ImportFailed(format!("Failed to open file: {e}"))
+                })?;
+                let total_bytes = file.metadata().map(|m| m.len()).ok();
+                let bytes_read = Rc::new(std::cell::Cell::new(0));
+                let reader = CountingReader {
+                    inner: file,
+                    count: Rc::clone(&bytes_read),
+                };
+                let progress = Progress::new(&mut on_progress, Some(bytes_read), total_bytes);
+                self.import_with(name, category, source_file_name, constraints, progress, |builder| {
+                    read_text(reader, format, builder)
+                })
+            }
+        }
+    }
+
+    /// Creates a dataset from the rows `fill` feeds to a table builder.
+    /// Fails, creating nothing, if a row breaks one of `constraints`.
+    fn import_with(
+        &self,
+        name: &str,
+        category: Option<&str>,
+        source_file_name: Option<String>,
+        constraints: &[ColumnConstraints],
+        progress: Progress<'_>,
+        fill: impl FnOnce(&mut TableBuilder<'_, '_>) -> DatasetResult<()>,
+    ) -> DatasetResult<DatasetMeta> {
+        let id = DatasetId::new();
+        let table = dataset_table_name(&id);
+        let now = now_millis();
+
+        let conn = self.lock_conn();
+        conn.execute_batch("BEGIN")?;
+        let result = (|| {
+            let mut builder = TableBuilder::new(&conn, &table, progress);
+            fill(&mut builder)?;
+            let (columns, row_count) = builder.finish()?;
+            let columns_json = serde_json::to_string(&columns)?;
+            conn.execute(
+                r#"INSERT INTO _datasets_meta (id, name, source_file_name, row_count, columns_json, category, created_at, modified_at)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"#,
+                params![
+                    id.to_string(),
+                    name,
+                    source_file_name,
+                    row_count,
+                    columns_json,
+                    category,
+                    now,
+                    now,
+                ],
+            )?;
+            if !constraints.is_empty() {
+                let checked = constraints
+                    .iter()
+                    .map(|c| check_constraints(&conn, &id, &columns, c))
+                    .collect::<DatasetResult<Vec<_>>>()?;
+                save_constraints(&conn, &id, &checked)?;
+                check_all_rows(&conn, &id)?;
+            }
+            Ok((columns, row_count))
+        })();
+        let (columns, row_count) = match result {
+            Ok(imported) => {
+                conn.execute_batch("COMMIT")?;
+                imported
+            }
+            Err(e) => {
+                conn.execute_batch("ROLLBACK")?;
+                return Err(e);
+            }
+        };
+
+        self.track(&conn, &id)?;
+        info!(dataset_id = %id, name, row_count, "Dataset imported");
+
+        Ok(DatasetMeta {
+            id,
+            name: name.to_string(),
+            source_file_name,
+            row_count,
+            columns,
+            category: category.map(|s| s.to_string()),
+            created_at: now,
+            modified_at: now,
+        })
+    }
+}
+
+// -- Table builder --
+
+/// A value read from an import source.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    /// Untyped text from CSV or TSV; its type is inferred by parsing it.
+    Raw(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    /// ISO 8601 date (`2024-01-31`).
+    Date(String),
+    /// ISO 8601 date and time (`2024-01-31T12:00:00`).
+    Timestamp(String),
+}
+
+impl Cell {
+    /// The narrowest column type holding this value, or `None` for nulls.
+    fn column_type(&self) -> Option<DatasetColumnType> {
+        match self {
+            Self::Null => None,
+            Self::Raw(s) => {
+                let s = s.trim();
+                if s.is_empty() {
+                    None
+                } else if s.parse::<i64>().is_ok() {
+                    Some(DatasetColumnType::Integer)
+                } else if s.parse::<f64>().is_ok() {
+                    Some(DatasetColumnType::Float)
+                } else {
+                    Some(DatasetColumnType::Text)
+                }
+            }
+            Self::Int(_) => Some(DatasetColumnType::Integer),
+            Self::Float(_) => Some(DatasetColumnType::Float),
+            Self::Bool(_) => Some(DatasetColumnType::Boolean),
+            Self::Text(_) => Some(DatasetColumnType::Text),
+            Self::Date(_) => Some(DatasetColumnType::Date),
+            Self::Timestamp(_) => Some(DatasetColumnType::Timestamp),
+        }
+    }
+
+    /// The value to store in a column of the given type. Values that do not
+    /// fit the type are stored as they are, as SQLite allows.
+    fn into_sql(self, column_type: &DatasetColumnType) -> Value {
+        use DatasetColumnType as T;
+        match (self, column_type) {
+            (Self::Null, _) => Value::Null,
+            (Self::Raw(s), _) => {
+                let s = s.trim();
+                if s.is_empty() {
+                    return Value::Null;
+                }
+                match column_type {
+                    T::Integer => s.parse().map(Value::Integer).unwrap_or_else(|_| Value::Text(s.to_string())),
+                    T::Float => s.parse().map(Value::Real).unwrap_or_else(|_| Value::Text(s.to_string())),
+                    _ => Value::Text(s.to_string()),
+                }
+            }
+            (Self::Int(i), T::Float) => Value::Real(i as f64),
+            (Self::Int(i), T::Integer | T::Boolean) => Value::Integer(i),
+            (Self::Int(i), _) => Value::Text(i.to_string()),
+            (Self::Float(f), T::Integer | T::Float) => Value::Real(f),
+            (Self::Float(f), _) => Value::Text(f.to_string()),
+            (Self::Bool(b), T::Integer | T::Float | T::Boolean) => Value::Integer(b as i64),
+            (Self::Bool(b), _) => Value::Text(b.to_string()),
+            (Self::Text(s) | Self::Date(s) | Self::Timestamp(s), _) => Value::Text(s),
+        }
+    }
+}
+
+/// Widens a column's type so that it also holds values of `seen`.
+fn widen(current: Option<DatasetColumnType>, seen: DatasetColumnType) -> DatasetColumnType {
+    use DatasetColumnType as T;
+    match (current, seen) {
+        (None, seen) => seen,
+        (Some(a), b) if a == b => a,
+        (Some(T::Integer), T::Float) | (Some(T::Float), T::Integer) => T::Float,
+        (Some(T::Date), T::Timestamp) | (Some(T::Timestamp), T::Date) => T::Timestamp,
+        _ => T::Text,
+    }
+}
+
+/// Reports import progress to the caller.
+struct Progress<'f> {
+    callback: &'f mut dyn FnMut(&ImportProgress),
+    bytes_read: Option<Rc<std::cell::Cell<u64>>>,
+    state: ImportProgress,
+}
+
+impl<'f> Progress<'f> {
+    fn new(
+        callback: &'f mut dyn FnMut(&ImportProgress),
+        bytes_read: Option<Rc<std::cell::Cell<u64>>>,
+        total_bytes: Option<u64>,
+    ) -> Self {
+        Self {
+            callback,
+            bytes_read,
+            state: ImportProgress {
+                total_bytes,
+                ..ImportProgress::default()
+            },
+        }
+    }
+
+    fn set_total_rows(&mut self, total_rows: u64) {
+        self.state.total_rows = Some(total_rows);
+    }
+
+    fn row_imported(&mut self) {
+        self.state.rows_imported += 1;
+        if self.state.rows_imported % PROGRESS_EVERY == 0 {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.state.bytes_read = self.bytes_read.as_ref().map(|b| b.get());
+        (self.callback)(&self.state);
+    }
+}
+
+/// Builds a dataset table from rows read off an import source.
+struct TableBuilder<'c, 'f> {
+    conn: &'c Connection,
+    table: String,
+    /// Column names, unique ignoring case as SQLite requires.
+    names: Vec<String>,
+    /// Column index by source key, for sources with keyed rows (JSON).
+    keys: HashMap<String, usize>,
+    types: Vec<Option<DatasetColumnType>>,
+    /// Rows held back until the column types are known.
+    sample: Vec<Vec<Cell>>,
+    created: bool,
+    progress: Progress<'f>,
+}
+
+impl<'c, 'f> TableBuilder<'c, 'f> {
+    fn new(conn: &'c Connection, table: &str, progress: Progress<'f>) -> Self {
+        Self {
+            conn,
+            table: table.to_string(),
+            names: Vec::new(),
+            keys: HashMap::new(),
+            types: Vec::new(),
+            sample: Vec::new(),
+            created: false,
+            progress,
+        }
+    }
+
+    /// Declares the columns of a source with positional rows.
+    fn set_headers(&mut self, headers: impl IntoIterator<Item = String>) {
+        for header in headers {
+            self.add_column(&header);
+        }
+    }
+
+    /// Adds a column, making its name unique. Returns its index.
+    fn add_column(&mut self, name: &str) -> usize {
+        let base = if name.trim().is_empty() {
+            format!("column_{}", self.names.len() + 1)
+        } else {
+            name.to_string()
+        };
+        let taken = |n: &str| self.names.iter().any(|existing| existing.eq_ignore_ascii_case(n));
+        let mut unique = base.clone();
+        let mut suffix = 2;
+        while taken(&unique) {
+            unique = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        self.names.push(unique);
+        self.types.push(None);
+        self.names.len() - 1
+    }
+
+    /// Adds a positional row. Missing trailing cells are null; extra cells
+    /// are dropped.
+    fn push_row(&mut self, mut row: Vec<Cell>) -> DatasetResult<()> {
+        row.resize(self.names.len(), Cell::Null);
+        self.push(row)
+    }
+
+    /// Adds a keyed row. Keys not seen before become new columns.
+    fn push_record(&mut self, record: Vec<(String, Cell)>) -> DatasetResult<()> {
+        let mut row = vec![Cell::Null; self.names.len()];
+        for (key, cell) in record {
+            let index = match self.keys.get(&key) {
+                Some(&index) => index,
+                None => {
+                    let index = self.add_column(&key);
+                    if self.created {
+                        // Columns first seen after the sample hold text.
+                        self.types[index] = Some(DatasetColumnType::Text);
+                        let name = super::helpers::sanitize_identifier(&self.names[index]);
+                        self.conn.execute_batch(&format!(
+                            "ALTER TABLE {} ADD COLUMN \"{name}\" TEXT",
+                            self.table
+                        ))?;
+                    }
+                    self.keys.insert(key, index);
+                    index
+                }
+            };
+            if index >= row.len() {
+                row.resize(index + 1, Cell::Null);
+            }
+            row[index] = cell;
+        }
+        self.push(row)
+    }
+
+    fn push(&mut self, row: Vec<Cell>) -> DatasetResult<()> {
+        if self.created {
+            self.insert(row)?;
+        } else {
+            self.sample.push(row);
+            if self.sample.len() >= SAMPLE_ROWS {
+                self.create()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Infers column types from the sample, creates the table and inserts
+    /// the sampled rows.
+    fn create(&mut self) -> DatasetResult<()> {
+        if self.names.is_empty() {
+            return Err(DatasetError::ImportFailed("File has no columns".to_string()));
+        }
+        for row in &self.sample {
+            for (i, cell) in row.iter().enumerate() {
+                if let Some(seen) = cell.column_type() {
+                    self.types[i] = Some(widen(self.types[i].take(), seen));
+                }
+            }
+        }
+        for column_type in &mut self.types {
+            column_type.get_or_insert(DatasetColumnType::Text);
+        }
+
+        let col_defs: Vec<String> = self
+            .names
+            .iter()
+            .zip(&self.types)
+            .map(|(name, column_type)| {
+                let safe_name = super::helpers::sanitize_identifier(name);
+                let sqlite_type = column_type.as_ref().map_or("TEXT", |t| t.to_sqlite_type());
+                format!("\"{safe_name}\" {sqlite_type}")
+            })
+            .collect();
+        self.conn
+            .execute_batch(&format!("CREATE TABLE {} ({})", self.table, col_defs.join(", ")))
+            .map_err(|e| DatasetError::ImportFailed(format!("Failed to create table: {e}")))?;
+        self.created = true;
+
+        for row in std::mem::take(&mut self.sample) {
+            self.insert(row)?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, mut row: Vec<Cell>) -> DatasetResult<()> {
+        row.resize(self.names.len(), Cell::Null);
+        let columns: Vec<String> = self
+            .names
+            .iter()
+            .map(|n| format!("\"{}\"", super::helpers::sanitize_identifier(n)))
+            .collect();
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "INSERT INTO {} ({}) VALUES ({placeholders})",
+            self.table,
+            columns.join(", ")
+        ))?;
+        let values: Vec<Value> = row
+            .into_iter()
+            .zip(&self.types)
+            .map(|(cell, column_type)| {
+                cell.into_sql(column_type.as_ref().unwrap_or(&DatasetColumnType::Text))
+            })
+            .collect();
+        stmt.execute(privstack_db::rusqlite::params_from_iter(values))?;
+        self.progress.row_imported();
+        Ok(())
+    }
+
+    /// Creates the table if the source had fewer rows than the sample, and
+    /// returns the columns and row count.
+    fn finish(mut self) -> DatasetResult<(Vec<DatasetColumn>, i64)> {
+        if !self.created {
+            self.create()?;
+        }
+        self.progress.report();
+        let columns = self
+            .names
+            .into_iter()
+            .zip(self.types)
+            .enumerate()
+            .map(|(i, (name, column_type))| DatasetColumn {
+                name,
+                column_type: column_type.unwrap_or(DatasetColumnType::Text),
+                ordinal: i as i32,
+            })
+            .collect();
+        Ok((columns, self.progress.state.rows_imported as i64))
+    }
+}
+
+/// Counts the bytes read through it, for progress reports.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+// -- Readers --
+
+/// Reads a text format: CSV, TSV, JSON or NDJSON.
+fn read_text(
+    reader: impl Read,
+    format: FileFormat,
+    builder: &mut TableBuilder<'_, '_>,
+) -> DatasetResult<()> {
+    match format {
+        FileFormat::Csv => read_delimited(reader, b',', builder),
+        FileFormat::Tsv => read_delimited(reader, b'\t', builder),
+        FileFormat::Json | FileFormat::Ndjson => read_json(BufReader::new(reader), builder),
+        FileFormat::Xlsx | FileFormat::Parquet => Err(DatasetError::ImportFailed(format!(
+            "{format:?} is a binary format; import it from a file"
+        ))),
+    }
+}
+
+fn read_delimited(
+    reader: impl Read,
+    delimiter: u8,
+    builder: &mut TableBuilder<'_, '_>,
+) -> DatasetResult<()> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(reader);
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| DatasetError::ImportFailed(format!("Failed to read CSV headers: {e}")))?
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    if headers.is_empty() {
+        return Err(DatasetError::ImportFailed("CSV has no columns".to_string()));
+    }
+    builder.set_headers(headers);
+
+    // Malformed records are skipped rather than failing the whole import.
+    for record in reader.records().filter_map(|r| r.ok()) {
+        builder.push_row(record.iter().map(|f| Cell::Raw(f.to_string())).collect())?;
+    }
+    Ok(())
+}
+
+/// Reads a JSON array of objects, or objects one after another (NDJSON).
+fn read_json(mut reader: impl BufRead, builder: &mut TableBuilder<'_, '_>) -> DatasetResult<()> {
+    // Peek past whitespace and a byte order mark to tell the two apart.
+    let is_array = loop {
+        let buf = reader.fill_buf()?;
+        let Some(&first) = buf.first() else {
+            return Err(DatasetError::ImportFailed("JSON file is empty".to_string()));
+        };
+        if first.is_ascii_whitespace() {
+            reader.consume(1);
+        } else if buf.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            reader.consume(3);
+        } else {
+            break first == b'[';
+        }
+    };
+
+    if is_array {
+        let mut failure = None;
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let result = de.deserialize_seq(ArrayVisitor {
+            builder,
+            failure: &mut failure,
+        });
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        result?;
+        de.end()?;
+    } else {
+        for object in serde_json::Deserializer::from_reader(reader).into_iter::<JsonObject>() {
+            builder.push_record(object?.into_record())?;
+        }
+    }
+    Ok(())
+}
+
+/// A JSON object with its keys in document order.
+struct JsonObject(Vec<(String, serde_json::Value)>);
+
+impl JsonObject {
+    fn into_record(self) -> Vec<(String, Cell)> {
+        self.0
+            .into_iter()
+            .map(|(key, value)| (key, json_cell(value)))
+            .collect()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for JsonObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = JsonObject;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a JSON object per row")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonObject, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(JsonObject(entries))
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+/// Feeds the objects of a JSON array to the builder one at a time.
+struct ArrayVisitor<'b, 'c, 'f> {
+    builder: &'b mut TableBuilder<'c, 'f>,
+    /// Why the builder failed, since visitor errors can only carry text.
+    failure: &'b mut Option<DatasetError>,
+}
+
+impl<'de> Visitor<'de> for ArrayVisitor<'_, '_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a JSON array of objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(object) = seq.next_element::<JsonObject>()? {
+            if let Err(e) = self.builder.push_record(object.into_record()) {
+                *self.failure = Some(e);
+                return Err(de::Error::custom("import aborted"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn json_cell(value: serde_json::Value) -> Cell {
+    use serde_json::Value as J;
+    match value {
+        J::Null => Cell::Null,
+        J::Bool(b) => Cell::Bool(b),
+        J::Number(n) => match n.as_i64() {
+            Some(i) => Cell::Int(i),
+            None => n.as_f64().map_or(Cell::Null, Cell::Float),
+        },
+        J::String(s) => Cell::Text(s),
+        // Nested values are kept as JSON text.
+        nested => Cell::Text(nested.to_string()),
+    }
+}
+
+/// Reads a worksheet of an XLSX workbook. Its first non-empty row holds the
+/// column names.
+fn read_xlsx(
+    path: &Path,
+    sheet: Option<&str>,
+    builder: &mut TableBuilder<'_, '_>,
+) -> DatasetResult<()> {
+    use calamine::{Reader, Xlsx};
+
+    let failed = |e: calamine::XlsxError| DatasetError::ImportFailed(format!("Failed to read XLSX: {e}"));
+    let mut workbook: Xlsx<_> = calamine::open_workbook(path).map_err(failed)?;
+    let sheet = match sheet {
+        Some(sheet) => sheet.to_string(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| DatasetError::ImportFailed("Workbook has no sheets".to_string()))?,
+    };
+    let mut cells = workbook.worksheet_cells_reader(&sheet).map_err(failed)?;
+    let dims = cells.dimensions();
+    builder
+        .progress
+        .set_total_rows(u64::from(dims.end.0.saturating_sub(dims.start.0)));
+
+    let mut headers: Option<Vec<String>> = None;
+    let mut current: Option<(u32, Vec<Cell>)> = None;
+    let flush = |row: Vec<Cell>, headers: &mut Option<Vec<String>>, builder: &mut TableBuilder<'_, '_>| {
+        if row.iter().all(|c| *c == Cell::Null) {
+            return Ok(());
+        }
+        match headers {
+            None => {
+                let names: Vec<String> = row.into_iter().map(header_text).collect();
+                builder.set_headers(names.clone());
+                *headers = Some(names);
+                Ok(())
+            }
+            Some(_) => builder.push_row(row),
+        }
+    };
+
+    while let Some(cell) = cells.next_cell().map_err(failed)? {
+        let (row, col) = cell.get_position();
+        let col = col.saturating_sub(dims.start.1) as usize;
+        if current.as_ref().is_some_and(|(r, _)| *r != row) {
+            let (_, cells) = current.take().unwrap_or_default();
+            flush(cells, &mut headers, builder)?;
+        }
+        let (_, cells) = current.get_or_insert_with(|| (row, Vec::new()));
+        // Data cells beyond the header row's last column have no name.
+        if headers.as_ref().is_some_and(|h| col >= h.len()) {
+            continue;
+        }
+        if cells.len() <= col {
+            cells.resize(col + 1, Cell::Null);
+        }
+        cells[col] = xlsx_cell(cell.get_value());
+    }
+    if let Some((_, cells)) = current {
+        flush(cells, &mut headers, builder)?;
+    }
+    if headers.is_none() {
+        return Err(DatasetError::ImportFailed(format!("Sheet '{sheet}' is empty")));
+    }
+    Ok(())
+}
+
+fn header_text(cell: Cell) -> String {
+    match cell {
+        Cell::Null => String::new(),
+        Cell::Int(i) => i.to_string(),
+        Cell::Float(f) => f.to_string(),
+        Cell::Bool(b) => b.to_string(),
+        Cell::Raw(s) | Cell::Text(s) | Cell::Date(s) | Cell::Timestamp(s) => s,
+    }
+}
+
+fn xlsx_cell(value: &calamine::DataRef<'_>) -> Cell {
+    use calamine::DataRef as D;
+    match value {
+        D::Empty | D::Error(_) => Cell::Null,
+        D::Int(i) => Cell::Int(*i),
+        // Excel stores every number as a float; whole ones are integers.
+        D::Float(f) if f.fract() == 0.0 && f.abs() < 9.0e15 => Cell::Int(*f as i64),
+        D::Float(f) => Cell::Float(*f),
+        D::Bool(b) => Cell::Bool(*b),
+        D::String(s) => Cell::Text(s.clone()),
+        D::SharedString(s) => Cell::Text(s.to_string()),
+        D::DateTime(dt) if dt.is_datetime() => match dt.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => {
+                Cell::Date(dt.date().format("%Y-%m-%d").to_string())
+            }
+            Some(dt) => Cell::Timestamp(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            None => Cell::Float(dt.as_f64()),
+        },
+        D::DateTime(duration) => Cell::Float(duration.as_f64()),
+        D::DateTimeIso(s) if s.contains('T') => Cell::Timestamp(s.clone()),
+        D::DateTimeIso(s) => Cell::Date(s.clone()),
+        D::DurationIso(s) => Cell::Text(s.clone()),
+    }
+}
+
+/// Reads a Parquet file row group by row group. Top-level fields become
+/// columns; nested ones are kept as JSON text.
+fn read_parquet(path: &Path, builder: &mut TableBuilder<'_, '_>) -> DatasetResult<()> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let failed = |e: parquet::errors::ParquetError| {
+        DatasetError::ImportFailed(format!("Failed to read Parquet: {e}"))
+    };
+    let file = File::open(path)?;
+    let reader = SerializedFileReader::new(file).map_err(failed)?;
+    let metadata = reader.metadata().file_metadata();
+    builder
+        .progress
+        .set_total_rows(metadata.num_rows().max(0) as u64);
+    builder.set_headers(
+        metadata
+            .schema()
+            .get_fields()
+            .iter()
+            .map(|f| f.name().to_string()),
+    );
+
+    for row in reader.get_row_iter(None).map_err(failed)? {
+        let row = row.map_err(failed)?;
+        builder.push_row(row.get_column_iter().map(|(_, field)| parquet_cell(field)).collect())?;
+    }
+    Ok(())
+}
+
+fn parquet_cell(field: &parquet::record::Field) -> Cell {
+    use parquet::record::Field as F;
+    match field {
+        F::Null => Cell::Null,
+        F::Bool(b) => Cell::Bool(*b),
+        F::Byte(i) => Cell::Int(i64::from(*i)),
+        F::Short(i) => Cell::Int(i64::from(*i)),
+        F::Int(i) => Cell::Int(i64::from(*i)),
+        F::Long(i) => Cell::Int(*i),
+        F::UByte(i) => Cell::Int(i64::from(*i)),
+        F::UShort(i) => Cell::Int(i64::from(*i)),
+        F::UInt(i) => Cell::Int(i64::from(*i)),
+        F::ULong(i) => i64::try_from(*i).map_or(Cell::Float(*i as f64), Cell::Int),
+        F::Float(f) => Cell::Float(f64::from(*f)),
+        F::Double(f) => Cell::Float(*f),
+        F::Str(s) => Cell::Text(s.clone()),
+        F::Date(days) => chrono::DateTime::from_timestamp(i64::from(*days) * 86_400, 0)
+            .map_or(Cell::Null, |dt| Cell::Date(dt.format("%Y-%m-%d").to_string())),
+        F::TimestampMillis(ms) => chrono::DateTime::from_timestamp_millis(*ms)
+            .map_or(Cell::Null, |dt| Cell::Timestamp(dt.format("%Y-%m-%dT%H:%M:%S%.3f").to_string())),
+        F::TimestampMicros(us) => chrono::DateTime::from_timestamp_micros(*us)
+            .map_or(Cell::Null, |dt| Cell::Timestamp(dt.format("%Y-%m-%dT%H:%M:%S%.6f").to_string())),
+        // Decimals, half floats, byte arrays and nested groups, lists and maps.
+        other => json_cell(other.to_json_value()),
+    }
+}
@@ -0,0 +1,367 @@
+//! Materialized saved queries: a query's rows cached in a table, so that
+//! opening it doesn't run it again.
+//!
+//! Building the cache runs the query in the SQL sandbox, like any user
+//! SQL, and records every dataset the sandbox sees it read — including
+//! those a formula column reaches through a relation. Triggers on
+//! `_datasets_meta` mark the cache stale when one of them changes; every
+//! row edit, SQL mutation and relation change touches that table. The
+//! query's [`RefreshPolicy`] decides when a stale cache is rebuilt. A
+//! rebuild fills a new table and swaps it in, so a failed one leaves the
+//! old rows. Queries over `entity:` tables aren't materialized: entities
+//! live in another database, which tells this one nothing when they change.
+
+use super::formulas::with_computed_columns;
+use super::helpers::{now_millis, row_value_to_json};
+use super::replication::dataset_id_for_table;
+use super::sandbox::{Sandbox, SqlAccess};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::{
+    DatasetColumn, DatasetId, DatasetQueryResult, Materialization, RefreshPolicy, SqlExecutionResult,
+    SqlLimits,
+};
+use privstack_db::rusqlite::types::Value;
+use privstack_db::rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+impl DatasetStore {
+    /// Materializes a saved query's rows, rebuilt as `refresh` says, or
+    /// drops them when `refresh` is `None`. Materializing runs the query
+    /// at once.
+    pub fn set_saved_query_materialization(
+        &self,
+        query_id: &str,
+        refresh: Option<RefreshPolicy>,
+    ) -> DatasetResult<Option<Materialization>> {
+        match refresh {
+            Some(refresh) => self.materialize(query_id, refresh).map(Some),
+            None => {
+                let conn = self.lock_conn();
+                drop_materialization(&conn, query_id)?;
+                conn.execute(
+                    "UPDATE _dataset_saved_queries SET materialized_json = NULL WHERE id = ?1",
+                    params![query_id],
+                )?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Rebuilds a materialized saved query's rows now.
+    pub fn refresh_saved_query(&self, query_id: &str) -> DatasetResult<Materialization> {
+        let refresh = self
+            .refresh_policy(query_id)?
+            .ok_or_else(|| not_materialized(query_id))?;
+        self.materialize(query_id, refresh)
+    }
+
+    /// State of a saved query's materialized rows; `None` if the query
+    /// isn't materialized.
+    pub fn saved_query_materialization(&self, query_id: &str) -> DatasetResult<Option<Materialization>> {
+        let Some(refresh) = self.refresh_policy(query_id)? else {
+            return Ok(None);
+        };
+        let conn = self.lock_conn();
+        Ok(cache(&conn, query_id, refresh)?.map(|c| c.status))
+    }
+
+    /// Rebuilds the rows of scheduled materializations older than their
+    /// interval. Meant to be called by the host on a timer; returns the
+    /// queries refreshed. A query that fails to refresh keeps its old rows
+    /// and doesn't stop the others.
+    pub fn refresh_due_saved_queries(&self) -> DatasetResult<Vec<String>> {
+        let scheduled: Vec<(String, RefreshPolicy, Option<i64>)> = {
+            let conn = self.lock_conn();
+            let mut stmt = conn.prepare(
+                "SELECT q.id, q.materialized_json, m.refreshed_at FROM _dataset_saved_queries q \
+                 LEFT JOIN _dataset_materializations m ON m.query_id = q.id \
+                 WHERE q.materialized_json IS NOT NULL",
+            )?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get(2)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.into_iter()
+                .filter_map(|(id, json, refreshed_at)| {
+                    serde_json::from_str(&json).ok().map(|refresh| (id, refresh, refreshed_at))
+                })
+                .collect()
+        };
+
+        let now = now_millis();
+        let mut refreshed = Vec::new();
+        for (query_id, refresh, refreshed_at) in scheduled {
+            let RefreshPolicy::Every { interval_secs } = refresh else {
+                continue;
+            };
+            let interval_ms = i64::try_from(interval_secs.saturating_mul(1000)).unwrap_or(i64::MAX);
+            if refreshed_at.is_some_and(|at| now.saturating_sub(at) < interval_ms) {
+                continue;
+            }
+            match self.materialize(&query_id, refresh) {
+                Ok(_) => refreshed.push(query_id),
+                Err(e) => warn!(%query_id, error = %e, "Scheduled refresh of a saved query failed"),
+            }
+        }
+        Ok(refreshed)
+    }
+
+    /// Opens a saved query: a page of its materialized rows, rebuilt first
+    /// if the query refreshes on change and they're stale, or of its live
+    /// results if it isn't materialized.
+    pub fn query_saved_query(
+        &self,
+        query_id: &str,
+        page: i64,
+        page_size: i64,
+    ) -> DatasetResult<DatasetQueryResult> {
+        let Some(refresh) = self.refresh_policy(query_id)? else {
+            return self.query_saved_query_live(query_id, page, page_size);
+        };
+        let cached = {
+            let conn = self.lock_conn();
+            cache(&conn, query_id, refresh)?
+        };
+        let rebuild = match &cached {
+            Some(cached) => cached.status.stale && refresh == RefreshPolicy::OnChange,
+            None => true,
+        };
+        if rebuild {
+            self.materialize(query_id, refresh)?;
+        }
+
+        let page_size = page_size.min(self.sql_limits().max_rows as i64);
+        let conn = self.lock_conn();
+        let cached = cache(&conn, query_id, refresh)?.ok_or_else(|| not_materialized(query_id))?;
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {} LIMIT ?1 OFFSET ?2", cached.table))?;
+        let rows = stmt
+            .query_map(params![page_size, page * page_size], |row| {
+                Ok((0..cached.columns.len()).map(|i| row_value_to_json(row, i)).collect())
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(DatasetQueryResult {
+            columns: cached.columns.iter().map(|c| c.name.clone()).collect(),
+            column_types: cached.columns.iter().map(|c| c.column_type.clone()).collect(),
+            rows,
+            total_count: cached.status.row_count,
+            page,
+            page_size,
+            materialization: Some(cached.status),
+        })
+    }
+
+    fn query_saved_query_live(
+        &self,
+        query_id: &str,
+        page: i64,
+        page_size: i64,
+    ) -> DatasetResult<DatasetQueryResult> {
+        let sql = self.saved_query_select(query_id, "opened")?.sql;
+        match self.execute_sql_v2(&sql, page, page_size, false)? {
+            SqlExecutionResult::Query(result) => Ok(result),
+            SqlExecutionResult::Mutation(_) => Err(DatasetError::InvalidQuery(
+                "Only SELECT queries can be opened".to_string(),
+            )),
+        }
+    }
+
+    fn refresh_policy(&self, query_id: &str) -> DatasetResult<Option<RefreshPolicy>> {
+        let conn = self.lock_conn();
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT materialized_json FROM _dataset_saved_queries WHERE id = ?1",
+                params![query_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| DatasetError::NotFound(format!("saved query {query_id}")))?;
+        Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    /// Runs the query into a new cache table, then swaps it in with the
+    /// query's policy and sources.
+    fn materialize(&self, query_id: &str, refresh: RefreshPolicy) -> DatasetResult<Materialization> {
+        let preprocessed = self.saved_query_select(query_id, "materialized")?;
+        let mut sources: Vec<DatasetId> = Vec::new();
+        for (_, id) in preprocessed.referenced_datasets {
+            if !sources.contains(&id) {
+                sources.push(id);
+            }
+        }
+
+        let limits = self.sql_limits();
+        let conn = self.lock_conn();
+        let sql = with_computed_columns(&conn, &preprocessed.sql)?;
+        let columns = {
+            let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+            let columns =
+                Self::describe_query_columns(&conn, &sql).map_err(|e| sandbox.explain(e))?;
+            for id in sandbox.read_tables().iter().filter_map(|t| dataset_id_for_table(t)) {
+                if !sources.contains(&id) {
+                    sources.push(id);
+                }
+            }
+            columns
+        };
+        let columns: Vec<DatasetColumn> = columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, column_type))| DatasetColumn { name, column_type, ordinal: i as i32 })
+            .collect();
+
+        // Cache columns are positional: query results may repeat a name.
+        let table = format!("_dataset_cache_{}", Uuid::new_v4().simple());
+        let definitions: Vec<String> = (0..columns.len()).map(|i| format!("c{i}")).collect();
+        conn.execute_batch(&format!("CREATE TABLE {table} ({})", definitions.join(", ")))?;
+        let filled = fill(&conn, &table, &sql, columns.len(), limits).and_then(|row_count| {
+            let status = Materialization {
+                refresh,
+                refreshed_at: now_millis(),
+                stale: false,
+                sources,
+                row_count,
+            };
+            conn.execute_batch("SAVEPOINT dataset_materialize")?;
+            match swap_in(&conn, query_id, &table, &columns, &status) {
+                Ok(()) => conn.execute_batch("RELEASE SAVEPOINT dataset_materialize")?,
+                Err(e) => {
+                    conn.execute_batch(
+                        "ROLLBACK TO SAVEPOINT dataset_materialize; RELEASE SAVEPOINT dataset_materialize",
+                    )?;
+                    return Err(e);
+                }
+            }
+            Ok(status)
+        });
+        if filled.is_err() {
+            conn.execute_batch(&format!("DROP TABLE IF EXISTS {table}"))?;
+        }
+        let status = filled?;
+        info!(%query_id, rows = status.row_count, "Saved query materialized");
+        Ok(status)
+    }
+}
+
+/// A materialization's cache table, columns and state.
+struct Cache {
+    table: String,
+    columns: Vec<DatasetColumn>,
+    status: Materialization,
+}
+
+fn cache(conn: &Connection, query_id: &str, refresh: RefreshPolicy) -> DatasetResult<Option<Cache>> {
+    let Some((table, columns_json, row_count, refreshed_at, stale)) = conn
+        .query_row(
+            "SELECT cache_table, columns_json, row_count, refreshed_at, stale \
+             FROM _dataset_materializations WHERE query_id = ?1",
+            params![query_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get::<_, bool>(4)?,
+                ))
+            },
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare(
+        "SELECT dataset_id FROM _dataset_materialization_sources WHERE query_id = ?1 ORDER BY rowid",
+    )?;
+    let sources = stmt
+        .query_map(params![query_id], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .filter_map(|id| Uuid::parse_str(&id).ok().map(DatasetId))
+        .collect();
+    Ok(Some(Cache {
+        table,
+        columns: serde_json::from_str(&columns_json)?,
+        status: Materialization { refresh, refreshed_at, stale, sources, row_count },
+    }))
+}
+
+/// Copies the query's rows into `table`. They're read in the SQL sandbox
+/// and written once it's left: entering or leaving it expires prepared
+/// statements, so an insert prepared outside would be vetted again. The
+/// rows are held in memory in between, so a query returning more than
+/// [`SqlLimits::max_materialized_rows`] fails instead.
+fn fill(conn: &Connection, table: &str, sql: &str, width: usize, limits: SqlLimits) -> DatasetResult<i64> {
+    let max_rows = limits.max_materialized_rows;
+    let rows = {
+        let sandbox = Sandbox::enter(conn, SqlAccess::Read, limits)?;
+        let read = (|| {
+            let mut select = conn.prepare(sql)?;
+            let rows = select
+                .query_map([], |row| (0..width).map(|i| row.get::<_, Value>(i)).collect::<Result<Vec<_>, _>>())?
+                .take(max_rows.saturating_add(1))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })();
+        read.map_err(|e| sandbox.explain(e))?
+    };
+    if rows.len() > max_rows {
+        return Err(DatasetError::InvalidQuery(format!(
+            "Query returns more than {max_rows} rows, too many to materialize"
+        )));
+    }
+    let placeholders = vec!["?"; width].join(", ");
+    let mut insert = conn.prepare(&format!("INSERT INTO {table} VALUES ({placeholders})"))?;
+    for values in &rows {
+        insert.execute(params_from_iter(values))?;
+    }
+    Ok(rows.len() as i64)
+}
+
+/// Replaces the query's cache table and record with the new ones.
+fn swap_in(
+    conn: &Connection,
+    query_id: &str,
+    table: &str,
+    columns: &[DatasetColumn],
+    status: &Materialization,
+) -> DatasetResult<()> {
+    drop_materialization(conn, query_id)?;
+    conn.execute(
+        "INSERT INTO _dataset_materializations (query_id, cache_table, columns_json, row_count, refreshed_at, stale) \
+         VALUES (?1, ?2, ?3, ?4, ?5, 0)",
+        params![query_id, table, serde_json::to_string(columns)?, status.row_count, status.refreshed_at],
+    )?;
+    for source in &status.sources {
+        conn.execute(
+            "INSERT OR IGNORE INTO _dataset_materialization_sources (query_id, dataset_id) VALUES (?1, ?2)",
+            params![query_id, source.to_string()],
+        )?;
+    }
+    conn.execute(
+        "UPDATE _dataset_saved_queries SET materialized_json = ?1 WHERE id = ?2",
+        params![serde_json::to_string(&status.refresh)?, query_id],
+    )?;
+    Ok(())
+}
+
+/// Drops a saved query's cached rows and their record, if it has any.
+pub(crate) fn drop_materialization(conn: &Connection, query_id: &str) -> DatasetResult<()> {
+    let table: Option<String> = conn
+        .query_row(
+            "SELECT cache_table FROM _dataset_materializations WHERE query_id = ?1",
+            params![query_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(table) = table {
+        conn.execute_batch(&format!("DROP TABLE IF EXISTS {table}"))?;
+    }
+    conn.execute("DELETE FROM _dataset_materializations WHERE query_id = ?1", params![query_id])?;
+    conn.execute("DELETE FROM _dataset_materialization_sources WHERE query_id = ?1", params![query_id])?;
+    Ok(())
+}
+
+fn not_materialized(query_id: &str) -> DatasetError {
+    DatasetError::InvalidQuery(format!("Saved query {query_id} is not materialized"))
+}
@@ -1,25 +1,47 @@
 //! Core dataset store — thread-safe SQLite wrapper with modular operations.
 
 mod crud;
+mod entities;
+mod constraints;
+mod export;
+mod expression;
+mod filters;
+mod formulas;
+mod history;
 pub(crate) mod helpers;
+mod import;
+mod materialized;
 mod mutations;
+mod pivot;
 pub(crate) mod preprocessor;
 mod query;
 mod relations;
+mod replication;
 mod row_pages;
+mod sandbox;
 mod saved_queries;
 mod views;
 
 use crate::error::DatasetResult;
 use crate::schema::initialize_datasets_schema;
+use crate::types::{HistoryLimits, SqlLimits};
 use privstack_db::rusqlite::Connection;
+use replication::ReplicaClock;
 use std::path::Path;
 use std::sync::{Arc, Mutex, MutexGuard};
 
+pub use entities::EntitySource;
+
 /// Thread-safe store for tabular datasets backed by SQLite.
 #[derive(Clone)]
 pub struct DatasetStore {
     conn: Arc<Mutex<Connection>>,
+    /// Clock for stamping local changes, once replication is enabled.
+    replica: Arc<Mutex<Option<ReplicaClock>>>,
+    /// Limits applied to user-supplied SQL.
+    limits: Arc<Mutex<SqlLimits>>,
+    /// How much change history each dataset keeps.
+    history_limits: Arc<Mutex<HistoryLimits>>,
 }
 
 impl DatasetStore {
@@ -27,8 +49,12 @@ impl DatasetStore {
     pub fn open(path: &Path) -> DatasetResult<Self> {
         let conn = privstack_db::open_db_unencrypted(path)?;
         initialize_datasets_schema(&conn)?;
+        filters::register_functions(&conn)?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            replica: Arc::new(Mutex::new(None)),
+            limits: Arc::new(Mutex::new(SqlLimits::default())),
+            history_limits: Arc::new(Mutex::new(HistoryLimits::default())),
         })
     }
 
@@ -36,16 +62,24 @@ impl DatasetStore {
     pub fn open_in_memory() -> DatasetResult<Self> {
         let conn = privstack_db::open_in_memory()?;
         initialize_datasets_schema(&conn)?;
+        filters::register_functions(&conn)?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            replica: Arc::new(Mutex::new(None)),
+            limits: Arc::new(Mutex::new(SqlLimits::default())),
+            history_limits: Arc::new(Mutex::new(HistoryLimits::default())),
         })
     }
 
     /// Open from an existing connection (for integration with external connection management).
     pub fn open_with_conn(conn: Connection) -> DatasetResult<Self> {
         initialize_datasets_schema(&conn)?;
+        filters::register_functions(&conn)?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            replica: Arc::new(Mutex::new(None)),
+            limits: Arc::new(Mutex::new(SqlLimits::default())),
+            history_limits: Arc::new(Mutex::new(HistoryLimits::default())),
         })
     }
 
@@ -58,6 +92,27 @@ impl DatasetStore {
         })
     }
 
+    /// Sets the limits applied to user-supplied SQL.
+    pub fn set_sql_limits(&self, limits: SqlLimits) {
+        *self.limits.lock().unwrap_or_else(|p| p.into_inner()) = limits;
+    }
+
+    /// Limits currently applied to user-supplied SQL.
+    pub fn sql_limits(&self) -> SqlLimits {
+        *self.limits.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Sets how much change history each dataset keeps. Takes effect as
+    /// edits are recorded, or at once through [`Self::prune_history`].
+    pub fn set_history_limits(&self, limits: HistoryLimits) {
+        *self.history_limits.lock().unwrap_or_else(|p| p.into_inner()) = limits;
+    }
+
+    /// How much change history each dataset keeps.
+    pub fn history_limits(&self) -> HistoryLimits {
+        *self.history_limits.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
     /// Flushes the WAL to the main database file.
     pub fn checkpoint(&self) -> DatasetResult<()> {
         let conn = self.lock_conn();
@@ -1,11 +1,17 @@
 //! Mutation operations: dataset creation, row CRUD, column CRUD, SQL mutations with dry-run.
 
+use super::constraints::{check_rows, in_savepoint, rename_constrained_column, ChangeTracker};
 use super::helpers::{introspect_columns, now_millis, row_value_to_json, sanitize_identifier};
+use super::history::{Image, RowImage, TableCopy};
+use super::replication::dataset_id_for_table;
+use super::sandbox::{Sandbox, SqlAccess};
 use super::DatasetStore;
 use crate::error::{DatasetError, DatasetResult};
 use crate::schema::dataset_table_name;
-use crate::types::{ColumnDef, DatasetId, DatasetMeta, DatasetQueryResult, MutationResult};
-use privstack_db::rusqlite::params;
+use crate::types::{
+    ColumnDef, DatasetId, DatasetMeta, DatasetQueryResult, MutationResult, SqlLimits,
+};
+use privstack_db::rusqlite::{params, Connection, OptionalExtension};
 use tracing::info;
 
 impl DatasetStore {
@@ -50,6 +56,7 @@ impl DatasetStore {
             params![id.to_string(), name, columns_json, category, now, now],
         )?;
 
+        self.track(&conn, &id)?;
         info!(dataset_id = %id, name, "Empty dataset created");
 
         Ok(DatasetMeta {
@@ -77,14 +84,14 @@ impl DatasetStore {
 
         let conn = self.lock_conn();
 
-        // Read source category before duplicating
-        let source_category: Option<String> = conn
+        // Read source category, formula columns and constraints before duplicating
+        let (source_category, formulas_json, constraints_json): (Option<String>, String, String) = conn
             .query_row(
-                "SELECT category FROM _datasets_meta WHERE id = ?1",
+                "SELECT category, formulas_json, constraints_json FROM _datasets_meta WHERE id = ?1",
                 params![source_id.to_string()],
-                |row| row.get(0),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
             )
-            .unwrap_or(None);
+            .unwrap_or((None, "[]".to_string(), "[]".to_string()));
 
         let create_sql = format!("CREATE TABLE {new_table} AS SELECT * FROM {source_table}");
         conn.execute_batch(&create_sql).map_err(|e| {
@@ -99,11 +106,12 @@ impl DatasetStore {
         let columns_json = serde_json::to_string(&columns)?;
 
         conn.execute(
-            r#"INSERT INTO _datasets_meta (id, name, source_file_name, row_count, columns_json, category, created_at, modified_at)
-               VALUES (?1, ?2, NULL, ?3, ?4, ?5, ?6, ?7)"#,
-            params![new_id.to_string(), new_name, row_count, columns_json, source_category, now, now],
+            r#"INSERT INTO _datasets_meta (id, name, source_file_name, row_count, columns_json, category, formulas_json, constraints_json, created_at, modified_at)
+               VALUES (?1, ?2, NULL, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
+            params![new_id.to_string(), new_name, row_count, columns_json, source_category, formulas_json, constraints_json, now, now],
         )?;
 
+        self.track(&conn, &new_id)?;
         info!(dataset_id = %new_id, new_name, "Dataset duplicated from {}", source_id);
 
         Ok(DatasetMeta {
@@ -118,17 +126,8 @@ impl DatasetStore {
         })
     }
 
-    /// Import dataset from CSV content string (for clipboard paste).
-    pub fn import_csv_content(
-        &self,
-        csv_content: &str,
-        name: &str,
-        category: Option<&str>,
-    ) -> DatasetResult<DatasetMeta> {
-        self.import_csv_content_inner(csv_content, name, category, None)
-    }
-
-    /// Insert a new row into a dataset.
+    /// Insert a new row into a dataset. Fails, inserting nothing, if the
+    /// row breaks a column constraint.
     pub fn insert_row(
         &self,
         id: &DatasetId,
@@ -161,13 +160,22 @@ impl DatasetStore {
             .map(|s| s as &dyn privstack_db::rusqlite::types::ToSql)
             .collect();
 
-        conn.execute(&sql, param_refs.as_slice())?;
+        in_savepoint(&conn, || {
+            conn.execute(&sql, param_refs.as_slice())?;
+            let rowid = conn.last_insert_rowid();
+            check_rows(&conn, id, &[rowid])?;
+            self.record_row_inserted(&conn, id, rowid)?;
+            let mut image = RowImage::new(&conn, &table)?;
+            image.inserted(rowid);
+            self.record_edit(&conn, id, "Insert row", Image::Rows(image))
+        })?;
         self.update_row_count_and_meta(&conn, id, &table, now)?;
 
         Ok(())
     }
 
-    /// Update a single cell value.
+    /// Update a single cell value. Fails, changing nothing, if the value
+    /// breaks a column constraint.
     pub fn update_cell(
         &self,
         id: &DatasetId,
@@ -181,12 +189,27 @@ impl DatasetStore {
         let val_str = json_value_to_sql_string(&value);
 
         // Use SQLite's rowid pseudo-column for stable row addressing
-        let sql = format!(
-            "UPDATE {table} SET \"{col}\" = ?1 WHERE rowid = (SELECT rowid FROM {table} LIMIT 1 OFFSET ?2)"
-        );
-
         let conn = self.lock_conn();
-        conn.execute(&sql, params![val_str, row_index])?;
+        let rowid: Option<i64> = conn
+            .query_row(
+                &format!("SELECT rowid FROM {table} LIMIT 1 OFFSET ?1"),
+                params![row_index],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(rowid) = rowid {
+            in_savepoint(&conn, || {
+                let mut image = RowImage::new(&conn, &table)?;
+                image.capture(&conn, &table, rowid)?;
+                conn.execute(
+                    &format!("UPDATE {table} SET \"{col}\" = ?1 WHERE rowid = ?2"),
+                    params![val_str, rowid],
+                )?;
+                check_rows(&conn, id, &[rowid])?;
+                self.record_cell_updated(&conn, id, rowid, &col)?;
+                self.record_edit(&conn, id, &format!("Edit {col}"), Image::Rows(image))
+            })?;
+        }
 
         conn.execute(
             "UPDATE _datasets_meta SET modified_at = ?1 WHERE id = ?2",
@@ -210,13 +233,27 @@ impl DatasetStore {
         let now = now_millis();
         let conn = self.lock_conn();
 
-        // Delete using rowid-based subqueries for each index
+        // Resolve each index to its rowid, then delete by rowid
+        let mut image = RowImage::new(&conn, &table)?;
         for &idx in row_indices {
-            let sql = format!(
-                "DELETE FROM {table} WHERE rowid = (SELECT rowid FROM {table} LIMIT 1 OFFSET ?1)"
-            );
-            conn.execute(&sql, params![idx])?;
+            let rowid: Option<i64> = conn
+                .query_row(
+                    &format!("SELECT rowid FROM {table} LIMIT 1 OFFSET ?1"),
+                    params![idx],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(rowid) = rowid {
+                image.capture(&conn, &table, rowid)?;
+                self.record_row_deleted(&conn, id, rowid)?;
+                conn.execute(&format!("DELETE FROM {table} WHERE rowid = ?1"), params![rowid])?;
+            }
         }
+        let label = match row_indices.len() {
+            1 => "Delete row".to_string(),
+            n => format!("Delete {n} rows"),
+        };
+        self.record_edit(&conn, id, &label, Image::Rows(image))?;
 
         self.update_row_count_and_meta(&conn, id, &table, now)?;
         Ok(())
@@ -243,7 +280,10 @@ impl DatasetStore {
         let sql = format!("ALTER TABLE {table} ADD COLUMN \"{col}\" {dtype}{default_clause}");
 
         let conn = self.lock_conn();
+        let copy = TableCopy::take(&conn, &table)?;
         conn.execute_batch(&sql)?;
+        self.record_column_added(&conn, id, &col, &dtype, default)?;
+        self.record_edit(&conn, id, &format!("Add column {col}"), Image::Table(copy))?;
 
         // Refresh column metadata
         let columns = introspect_columns(&conn, &table)?;
@@ -267,7 +307,13 @@ impl DatasetStore {
         let sql = format!("ALTER TABLE {table} DROP COLUMN \"{col}\"");
 
         let conn = self.lock_conn();
+        let copy = TableCopy::take(&conn, &table)?;
         conn.execute_batch(&sql)?;
+        self.record_column_dropped(&conn, id, &col)?;
+        self.record_edit(&conn, id, &format!("Drop column {col}"), Image::Table(copy))?;
+        if rename_constrained_column(&conn, id, &col, None)? {
+            self.record_meta_changed(&conn, id)?;
+        }
 
         let columns = introspect_columns(&conn, &table)?;
         let columns_json = serde_json::to_string(&columns)?;
@@ -307,6 +353,8 @@ impl DatasetStore {
         let sqlite_type = normalize_sql_type(&dtype);
 
         let conn = self.lock_conn();
+        let before = self.snapshot_for_diff(&conn, &table)?;
+        let copy = TableCopy::take(&conn, &table)?;
 
         // Get current columns
         let current_columns = introspect_columns(&conn, &table)?;
@@ -325,8 +373,9 @@ impl DatasetStore {
             })
             .collect();
 
-        // Build SELECT with CAST for the target column
-        let select_cols: Vec<String> = current_columns
+        // Build SELECT with CAST for the target column, keeping rowids stable
+        let mut select_cols: Vec<String> = vec!["rowid".to_string()];
+        select_cols.extend(current_columns
             .iter()
             .map(|c| {
                 let safe_name = sanitize_identifier(&c.name);
@@ -335,19 +384,32 @@ impl DatasetStore {
                 } else {
                     format!("\"{safe_name}\"")
                 }
-            })
+            }));
+        let insert_cols: Vec<String> = current_columns
+            .iter()
+            .map(|c| format!("\"{}\"", sanitize_identifier(&c.name)))
             .collect();
 
         let rebuild_sql = format!(
             "CREATE TABLE {tmp_table} ({});\n\
-             INSERT INTO {tmp_table} SELECT {} FROM {table};\n\
+             INSERT INTO {tmp_table} (rowid, {}) SELECT {} FROM {table};\n\
              DROP TABLE {table};\n\
              ALTER TABLE {tmp_table} RENAME TO {table};",
             col_defs.join(", "),
+            insert_cols.join(", "),
             select_cols.join(", "),
         );
 
         conn.execute_batch(&rebuild_sql)?;
+        if let Some(before) = before {
+            self.record_diff(&conn, before)?;
+        }
+        self.record_edit(
+            &conn,
+            id,
+            &format!("Change type of {target_col} to {dtype}"),
+            Image::Table(copy),
+        )?;
 
         let columns = introspect_columns(&conn, &table)?;
         let columns_json = serde_json::to_string(&columns)?;
@@ -374,7 +436,18 @@ impl DatasetStore {
         let sql = format!("ALTER TABLE {table} RENAME COLUMN \"{old_col}\" TO \"{new_col}\"");
 
         let conn = self.lock_conn();
+        let copy = TableCopy::take(&conn, &table)?;
         conn.execute_batch(&sql)?;
+        self.record_column_renamed(&conn, id, &old_col, &new_col)?;
+        self.record_edit(
+            &conn,
+            id,
+            &format!("Rename column {old_col} to {new_col}"),
+            Image::Table(copy),
+        )?;
+        if rename_constrained_column(&conn, id, &old_col, Some(&new_col))? {
+            self.record_meta_changed(&conn, id)?;
+        }
 
         let columns = introspect_columns(&conn, &table)?;
         let columns_json = serde_json::to_string(&columns)?;
@@ -386,25 +459,34 @@ impl DatasetStore {
         Ok(())
     }
 
-    /// Execute a SQL mutation (INSERT/UPDATE/DELETE/CREATE/ALTER) with optional dry-run.
+    /// Execute a SQL mutation (INSERT/UPDATE/DELETE/ALTER) with optional dry-run.
     ///
     /// When `dry_run` is true, the mutation is executed inside a SAVEPOINT that is
     /// rolled back, returning a preview of affected rows without persisting changes.
+    ///
+    /// The statement runs in the SQL sandbox: it may only change rows of
+    /// dataset tables and alter their columns. It fails, changing nothing,
+    /// if a row it inserts or updates breaks a column constraint. Each
+    /// dataset the sandbox sees it write gets a restore point and an undo
+    /// entry, whatever form the statement takes.
     pub fn execute_mutation(
         &self,
         sql: &str,
         dry_run: bool,
     ) -> DatasetResult<MutationResult> {
         let stmt_type = classify_statement(sql);
+        let limits = self.sql_limits();
         let conn = self.lock_conn();
 
+        let written = written_tables(&conn, sql, limits)?;
+
         if dry_run {
             conn.execute_batch("SAVEPOINT dry_run")?;
 
-            let execute_result = conn.execute(sql, []);
+            let execute_result = execute_checked(&conn, sql, &stmt_type, limits);
             match execute_result {
                 Ok(affected) => {
-                    let preview = self.query_mutation_preview(&conn, sql, &stmt_type);
+                    let preview = self.query_mutation_preview(&conn, written.first());
                     conn.execute_batch("ROLLBACK TO SAVEPOINT dry_run")?;
                     conn.execute_batch("RELEASE SAVEPOINT dry_run")?;
 
@@ -418,11 +500,31 @@ impl DatasetStore {
                 Err(e) => {
                     conn.execute_batch("ROLLBACK TO SAVEPOINT dry_run")?;
                     conn.execute_batch("RELEASE SAVEPOINT dry_run")?;
-                    Err(DatasetError::Sqlite(e))
+                    Err(e)
                 }
             }
         } else {
-            let affected = conn.execute(sql, [])?;
+            let mut before = Vec::new();
+            for table in &written {
+                before.extend(self.snapshot_for_diff(&conn, table)?);
+            }
+            // Bulk SQL is undone from a copy of each table it writes.
+            let mut copies = Vec::new();
+            for table in &written {
+                if let Some(id) = dataset_id_for_table(table) {
+                    copies.push((id, TableCopy::take(&conn, table)?));
+                }
+            }
+            let affected = in_savepoint(&conn, || execute_checked(&conn, sql, &stmt_type, limits))?;
+            for before in before {
+                self.record_diff(&conn, before)?;
+            }
+            let label = statement_label(sql);
+            for (id, copy) in copies {
+                self.record_restore_point(&conn, &id, &format!("Before {label}"))?;
+                self.record_edit(&conn, &id, &label, Image::Table(copy))?;
+                self.update_row_count_and_meta(&conn, &id, &dataset_table_name(&id), now_millis())?;
+            }
             Ok(MutationResult {
                 affected_rows: affected as i64,
                 statement_type: stmt_type,
@@ -436,12 +538,11 @@ impl DatasetStore {
     fn query_mutation_preview(
         &self,
         conn: &privstack_db::rusqlite::Connection,
-        sql: &str,
-        _stmt_type: &str,
+        table: Option<&String>,
     ) -> DatasetResult<DatasetQueryResult> {
-        // Try to extract table name and query it for preview
-        let table_name = extract_table_name(sql);
-        if let Some(table) = table_name {
+        // Preview the table the statement writes. The preview runs outside
+        // the sandbox, but the sandbox only reports dataset tables.
+        if let Some(table) = table {
             // Get column names via PRAGMA
             let col_names = {
                 let mut pragma_stmt = conn.prepare(&format!("PRAGMA table_info('{table}')"))?;
@@ -477,6 +578,7 @@ impl DatasetStore {
                 total_count: total,
                 page: 0,
                 page_size: 50,
+                materialization: None,
             });
         }
 
@@ -487,6 +589,7 @@ impl DatasetStore {
             total_count: 0,
             page: 0,
             page_size: 0,
+            materialization: None,
         })
     }
 
@@ -512,6 +615,17 @@ impl DatasetStore {
     }
 }
 
+/// A history label for a SQL statement: the statement on one line, cut
+/// short if long.
+fn statement_label(sql: &str) -> String {
+    const MAX_CHARS: usize = 80;
+    let statement = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    match statement.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}…", &statement[..end]),
+        None => statement,
+    }
+}
+
 /// Classify a SQL statement by its first keyword.
 fn classify_statement(sql: &str) -> String {
     let upper = sql.trim().to_uppercase();
@@ -532,29 +646,38 @@ fn classify_statement(sql: &str) -> String {
     }
 }
 
-/// Extract the target table name from a SQL statement (best-effort).
-fn extract_table_name(sql: &str) -> Option<String> {
-    let upper = sql.trim().to_uppercase();
-    let tokens: Vec<&str> = sql.split_whitespace().collect();
+/// Execute a user-supplied statement inside the SQL sandbox.
+fn execute_sandboxed(conn: &Connection, sql: &str, limits: SqlLimits) -> DatasetResult<usize> {
+    let sandbox = Sandbox::enter(conn, SqlAccess::Write, limits)?;
+    conn.execute(sql, []).map_err(|e| sandbox.explain(e.into()))
+}
 
-    if upper.starts_with("INSERT INTO") {
-        tokens.get(2).map(|t| t.trim_matches('(').to_string())
-    } else if upper.starts_with("UPDATE") {
-        tokens.get(1).map(|s| s.to_string())
-    } else if upper.starts_with("DELETE FROM") {
-        tokens.get(2).map(|s| s.to_string())
-    } else if upper.starts_with("CREATE TABLE") {
-        // Skip IF NOT EXISTS
-        if upper.contains("IF NOT EXISTS") {
-            tokens.get(5).map(|t| t.trim_matches('(').to_string())
-        } else {
-            tokens.get(2).map(|t| t.trim_matches('(').to_string())
-        }
-    } else if upper.starts_with("ALTER TABLE") {
-        tokens.get(2).map(|s| s.to_string())
-    } else {
-        None
+/// The dataset tables a statement writes, as the sandbox sees them when it
+/// prepares the statement. Fails as running it would if the sandbox refuses
+/// it.
+fn written_tables(conn: &Connection, sql: &str, limits: SqlLimits) -> DatasetResult<Vec<String>> {
+    let sandbox = Sandbox::enter(conn, SqlAccess::Write, limits)?;
+    conn.prepare(sql).map_err(|e| sandbox.explain(e.into()))?;
+    Ok(sandbox.written_tables())
+}
+
+/// Execute a user-supplied statement in the sandbox, then check the rows it
+/// inserted or updated against their column constraints. Schema changes
+/// can't break constraints, and would trip over the triggers that track
+/// rows, so they run untracked.
+fn execute_checked(
+    conn: &Connection,
+    sql: &str,
+    stmt_type: &str,
+    limits: SqlLimits,
+) -> DatasetResult<usize> {
+    if matches!(stmt_type, "ALTER" | "CREATE") {
+        return execute_sandboxed(conn, sql, limits);
     }
+    let tracker = ChangeTracker::install(conn)?;
+    let affected = execute_sandboxed(conn, sql, limits)?;
+    tracker.check()?;
+    Ok(affected)
 }
 
 /// Convert a serde_json::Value to a SQL-safe string for parameterized queries.
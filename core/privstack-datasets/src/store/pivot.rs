@@ -0,0 +1,516 @@
+//! Pivot tables: measures aggregated over row and column dimensions, with
+//! date bucketing, top-N buckets, subtotals and totals.
+//!
+//! One sandboxed query reads the dimension keys and measured values of the
+//! matching rows, with dates bucketed in SQL. They are aggregated here:
+//! medians and percentiles aren't SQLite aggregates, and no subtotal of
+//! them can be derived from per-group results.
+
+use super::formulas::{dataset_columns, with_computed_columns};
+use super::helpers::{build_view_clauses, row_value_to_json, sanitize_identifier};
+use super::sandbox::{Sandbox, SqlAccess};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{
+    DatasetColumn, DatasetColumnType, DatasetId, DateBucket, FilterNode, PivotAggregation,
+    PivotConfig, PivotDimension, PivotResult, PivotRow, PivotRowKind, ViewConfig,
+};
+use privstack_db::rusqlite::params_from_iter;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Label of the bucket a top-N dimension puts its other values in.
+const OTHER_BUCKET: &str = "Other";
+
+/// A dimension value; `None` is the other bucket of a top-N dimension.
+type Key = Vec<Option<Value>>;
+
+impl DatasetStore {
+    /// Pivots the rows of a dataset that pass `filter`. Data rows are
+    /// capped at the store's row limit.
+    pub fn pivot(
+        &self,
+        dataset_id: &DatasetId,
+        config: &PivotConfig,
+        filter: Option<&FilterNode>,
+    ) -> DatasetResult<PivotResult> {
+        let limits = self.sql_limits();
+        let conn = self.lock_conn();
+        let columns = dataset_columns(&conn, dataset_id)?;
+        validate(config, &columns)?;
+
+        let view = ViewConfig {
+            visible_columns: None,
+            filters: Vec::new(),
+            sorts: Vec::new(),
+            group_by: None,
+            pivot: None,
+            filter: filter.cloned(),
+        };
+        let (clauses, params) = build_view_clauses(&columns, &view)?;
+        let dimensions: Vec<&PivotDimension> = config.rows.iter().chain(&config.columns).collect();
+        let mut selected: Vec<String> = dimensions.iter().copied().map(dimension_sql).collect();
+        selected.extend(config.measures.iter().map(|m| match m.column.as_str() {
+            "*" => "1".to_string(),
+            column => format!("\"{}\"", sanitize_identifier(column)),
+        }));
+        let sql = with_computed_columns(
+            &conn,
+            &format!(
+                "SELECT {} FROM {}{clauses}",
+                selected.join(", "),
+                dataset_table_name(dataset_id)
+            ),
+        )?;
+
+        let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+        let records = read_records(&conn, &sql, &params, dimensions.len(), selected.len())
+            .map_err(|e| sandbox.explain(e))?;
+        drop(sandbox);
+
+        Ok(Pivot::new(config, limits.max_rows).build(records))
+    }
+
+    /// Runs the pivot table saved with a view, over the view's filtered
+    /// rows.
+    pub fn pivot_view(&self, view_id: &str) -> DatasetResult<PivotResult> {
+        let (dataset_id, config) = self.view_config(view_id)?;
+        let pivot = config
+            .pivot
+            .as_ref()
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("View {view_id} has no pivot table")))?;
+        self.pivot(&dataset_id, pivot, config.filter_tree().as_ref())
+    }
+}
+
+/// Checks that a pivot table only uses columns of the dataset, and
+/// buckets only columns that can hold dates. Imported dates are stored as
+/// text, so text columns can be bucketed; values that aren't dates fall
+/// into a NULL bucket.
+fn validate(config: &PivotConfig, columns: &[DatasetColumn]) -> DatasetResult<()> {
+    let column = |name: &str| {
+        columns
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| DatasetError::InvalidQuery(format!("Unknown column: {name}")))
+    };
+    if config.measures.is_empty() {
+        return Err(DatasetError::InvalidQuery("A pivot table needs a measure".to_string()));
+    }
+    for dimension in config.rows.iter().chain(&config.columns) {
+        let col = column(&dimension.column)?;
+        let holds_dates = matches!(
+            col.column_type,
+            DatasetColumnType::Date | DatasetColumnType::Timestamp | DatasetColumnType::Text
+        );
+        if dimension.bucket.is_some() && !holds_dates {
+            return Err(DatasetError::InvalidQuery(format!(
+                "Only date, timestamp and text columns can be bucketed: {}",
+                col.name
+            )));
+        }
+    }
+    for measure in &config.measures {
+        if let PivotAggregation::Percentile(p) = measure.aggregation {
+            if !(0.0..=100.0).contains(&p) {
+                return Err(DatasetError::InvalidQuery(format!(
+                    "Percentile must be between 0 and 100: {p}"
+                )));
+            }
+        }
+        match measure.column.as_str() {
+            "*" if measure.aggregation != PivotAggregation::Count => {
+                return Err(DatasetError::InvalidQuery("Only count can measure *".to_string()));
+            }
+            "*" => {}
+            name => {
+                column(name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The SQL of a dimension's key: the column, or its date bucket.
+fn dimension_sql(dimension: &PivotDimension) -> String {
+    let col = format!("\"{}\"", sanitize_identifier(&dimension.column));
+    match dimension.bucket {
+        None => col,
+        Some(DateBucket::Day) => format!("date({col})"),
+        Some(DateBucket::Week) => format!("date({col}, '-6 days', 'weekday 1')"),
+        Some(DateBucket::Month) => format!("strftime('%Y-%m', {col})"),
+        Some(DateBucket::Quarter) => format!(
+            "strftime('%Y', {col}) || '-Q' || ((CAST(strftime('%m', {col}) AS INTEGER) + 2) / 3)"
+        ),
+        Some(DateBucket::Year) => format!("strftime('%Y', {col})"),
+    }
+}
+
+/// A row read for the pivot table: its dimension keys, then the values of
+/// its measures.
+struct Record {
+    keys: Key,
+    values: Vec<Value>,
+}
+
+fn read_records(
+    conn: &privstack_db::rusqlite::Connection,
+    sql: &str,
+    params: &[privstack_db::rusqlite::types::Value],
+    key_count: usize,
+    column_count: usize,
+) -> DatasetResult<Vec<Record>> {
+    let mut stmt = conn.prepare(sql)?;
+    let records = stmt
+        .query_map(params_from_iter(params.iter()), |row| {
+            let mut values: Vec<Value> = (0..column_count).map(|i| row_value_to_json(row, i)).collect();
+            let values_after_keys = values.split_off(key_count);
+            Ok(Record {
+                keys: values.into_iter().map(Some).collect(),
+                values: values_after_keys,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+/// Aggregates records into a pivot table.
+struct Pivot<'c> {
+    config: &'c PivotConfig,
+    max_rows: usize,
+    /// Accumulators by row key prefix and column key; `None` for the total
+    /// across column keys.
+    groups: HashMap<String, Vec<Accumulator>>,
+}
+
+impl<'c> Pivot<'c> {
+    fn new(config: &'c PivotConfig, max_rows: usize) -> Self {
+        Self { config, max_rows, groups: HashMap::new() }
+    }
+
+    fn build(mut self, mut records: Vec<Record>) -> PivotResult {
+        let row_count = self.config.rows.len();
+        let dimensions: Vec<&PivotDimension> = self.config.rows.iter().chain(&self.config.columns).collect();
+        for (index, dimension) in dimensions.iter().enumerate() {
+            if let Some(n) = dimension.top_n {
+                self.keep_top(&mut records, index, n);
+            }
+        }
+
+        let totals = self.config.totals;
+        let row_total = totals && !self.config.columns.is_empty();
+        let mut row_keys: Vec<Key> = Vec::new();
+        let mut column_keys: Vec<Key> = Vec::new();
+        let mut seen_rows = HashSet::new();
+        let mut seen_columns = HashSet::new();
+        for record in &records {
+            let (row_key, column_key) = record.keys.split_at(row_count);
+            if seen_rows.insert(group_id(row_key, None)) {
+                row_keys.push(row_key.to_vec());
+            }
+            if seen_columns.insert(group_id(column_key, None)) {
+                column_keys.push(column_key.to_vec());
+            }
+            let levels = if totals { 0..=row_count } else { row_count..=row_count };
+            for level in levels {
+                let prefix = &row_key[..level];
+                self.add(prefix, Some(column_key), &record.values);
+                if row_total {
+                    self.add(prefix, None, &record.values);
+                }
+            }
+        }
+        row_keys.sort_by(|a, b| compare_keys(a, b));
+        row_keys.truncate(self.max_rows);
+        column_keys.sort_by(|a, b| compare_keys(a, b));
+        if column_keys.is_empty() {
+            column_keys.push(Vec::new());
+        }
+
+        let mut rows = Vec::new();
+        if row_count > 0 {
+            let mut previous: Option<&Key> = None;
+            for key in &row_keys {
+                if let Some(previous) = previous {
+                    let shared = previous.iter().zip(key).take_while(|(a, b)| a == b).count();
+                    self.subtotals(&mut rows, previous, shared + 1, &column_keys, row_total);
+                }
+                rows.push(self.row(key, key.len(), PivotRowKind::Data, &column_keys, row_total));
+                previous = Some(key);
+            }
+            if let Some(previous) = previous {
+                self.subtotals(&mut rows, previous, 1, &column_keys, row_total);
+            }
+        }
+        if totals || row_count == 0 {
+            let key = vec![None; row_count];
+            rows.push(self.row(&key, 0, PivotRowKind::Total, &column_keys, row_total));
+        }
+
+        PivotResult {
+            row_dimensions: self.config.rows.iter().map(|d| d.column.clone()).collect(),
+            column_dimensions: self.config.columns.iter().map(|d| d.column.clone()).collect(),
+            column_keys: column_keys.iter().map(|k| key_values(k)).collect(),
+            measures: self.config.measures.iter().map(measure_label).collect(),
+            rows,
+        }
+    }
+
+    /// Puts the values of dimension `index` outside the `n` with the
+    /// largest first measure into the other bucket.
+    fn keep_top(&self, records: &mut [Record], index: usize, n: usize) {
+        let aggregation = self.config.measures[0].aggregation;
+        let mut ranked: HashMap<String, (Option<Value>, Accumulator)> = HashMap::new();
+        for record in records.iter() {
+            let key = &record.keys[index];
+            ranked
+                .entry(group_id(std::slice::from_ref(key), None))
+                .or_insert_with(|| (key.clone(), Accumulator::default()))
+                .1
+                .add(aggregation, &record.values[0]);
+        }
+        let mut ranked: Vec<(Option<Value>, Value)> = ranked
+            .into_values()
+            .map(|(key, acc)| (key, acc.finish(aggregation)))
+            .collect();
+        ranked.sort_by(|(ka, a), (kb, b)| compare_values(b, a).then_with(|| compare_key(ka, kb)));
+        let kept: HashSet<String> = ranked
+            .iter()
+            .take(n)
+            .map(|(key, _)| group_id(std::slice::from_ref(key), None))
+            .collect();
+        for record in records.iter_mut() {
+            if !kept.contains(&group_id(std::slice::from_ref(&record.keys[index]), None)) {
+                record.keys[index] = None;
+            }
+        }
+    }
+
+    fn add(&mut self, prefix: &[Option<Value>], column_key: Option<&[Option<Value>]>, values: &[Value]) {
+        let measures = &self.config.measures;
+        let accumulators = self
+            .groups
+            .entry(group_id(prefix, column_key))
+            .or_insert_with(|| measures.iter().map(|_| Accumulator::default()).collect());
+        for ((measure, acc), value) in measures.iter().zip(accumulators).zip(values) {
+            acc.add(measure.aggregation, value);
+        }
+    }
+
+    /// Subtotal rows for the groups `key` closes, deepest first, down to
+    /// those sharing its first `level` keys.
+    fn subtotals(
+        &self,
+        rows: &mut Vec<PivotRow>,
+        key: &Key,
+        level: usize,
+        column_keys: &[Key],
+        row_total: bool,
+    ) {
+        if !self.config.totals {
+            return;
+        }
+        for depth in (level..key.len()).rev() {
+            let mut subtotal_key = key[..depth].to_vec();
+            subtotal_key.resize(key.len(), None);
+            rows.push(self.row(&subtotal_key, depth, PivotRowKind::Subtotal, column_keys, row_total));
+        }
+    }
+
+    /// A pivot table row for the group of the first `depth` keys of `key`.
+    fn row(&self, key: &Key, depth: usize, kind: PivotRowKind, column_keys: &[Key], row_total: bool) -> PivotRow {
+        let prefix = &key[..depth];
+        let cells = |column_key: Option<&Key>| -> Vec<Value> {
+            let group = self.groups.get(&group_id(prefix, column_key.map(Vec::as_slice)));
+            self.config
+                .measures
+                .iter()
+                .enumerate()
+                .map(|(i, measure)| match group {
+                    Some(accumulators) => accumulators[i].finish(measure.aggregation),
+                    None => Accumulator::default().finish(measure.aggregation),
+                })
+                .collect()
+        };
+        let keys = match kind {
+            PivotRowKind::Data => key_values(key),
+            PivotRowKind::Subtotal | PivotRowKind::Total => {
+                let mut keys = key_values(prefix);
+                keys.resize(key.len(), Value::Null);
+                keys
+            }
+        };
+        PivotRow {
+            keys,
+            kind,
+            cells: column_keys.iter().map(|k| cells(Some(k))).collect(),
+            total: row_total.then(|| cells(None)),
+        }
+    }
+}
+
+/// Identifies a group of records by row key prefix and column key.
+fn group_id(prefix: &[Option<Value>], column_key: Option<&[Option<Value>]>) -> String {
+    format!("{prefix:?}|{column_key:?}")
+}
+
+fn key_values(key: &[Option<Value>]) -> Vec<Value> {
+    key.iter()
+        .map(|k| k.clone().unwrap_or_else(|| Value::String(OTHER_BUCKET.to_string())))
+        .collect()
+}
+
+fn measure_label(measure: &crate::types::PivotMeasure) -> String {
+    if let Some(label) = &measure.label {
+        return label.clone();
+    }
+    let aggregation = match measure.aggregation {
+        PivotAggregation::Count => "count".to_string(),
+        PivotAggregation::CountDistinct => "count_distinct".to_string(),
+        PivotAggregation::Sum => "sum".to_string(),
+        PivotAggregation::Avg => "avg".to_string(),
+        PivotAggregation::Min => "min".to_string(),
+        PivotAggregation::Max => "max".to_string(),
+        PivotAggregation::Median => "median".to_string(),
+        PivotAggregation::Percentile(p) => format!("p{p}"),
+    };
+    format!("{aggregation}({})", measure.column)
+}
+
+/// Orders keys value by value, with the other bucket last.
+fn compare_keys(a: &[Option<Value>], b: &[Option<Value>]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare_key(a, b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn compare_key(a: &Option<Value>, b: &Option<Value>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_values(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Orders values like SQLite: NULL, then numbers, then text.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Bool(_) | Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Array(_) | Value::Object(_) => 3,
+        }
+    }
+    match (a, b) {
+        (Value::Number(_) | Value::Bool(_), Value::Number(_) | Value::Bool(_)) => {
+            number(a).partial_cmp(&number(b)).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)).then_with(|| a.to_string().cmp(&b.to_string())),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(f64::from(u8::from(*b))),
+        _ => None,
+    }
+}
+
+/// Running state of one measure over one group.
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    distinct: HashSet<String>,
+    /// Sum while every number is an integer.
+    int_sum: Option<i64>,
+    float_sum: f64,
+    numbers: u64,
+    all_integers: bool,
+    min: Option<Value>,
+    max: Option<Value>,
+    values: Vec<f64>,
+}
+
+impl Accumulator {
+    fn add(&mut self, aggregation: PivotAggregation, value: &Value) {
+        if value.is_null() {
+            return;
+        }
+        self.count += 1;
+        match aggregation {
+            PivotAggregation::Count => {}
+            PivotAggregation::CountDistinct => {
+                self.distinct.insert(value.to_string());
+            }
+            PivotAggregation::Min => {
+                if self.min.as_ref().map_or(true, |min| compare_values(value, min).is_lt()) {
+                    self.min = Some(value.clone());
+                }
+            }
+            PivotAggregation::Max => {
+                if self.max.as_ref().map_or(true, |max| compare_values(value, max).is_gt()) {
+                    self.max = Some(value.clone());
+                }
+            }
+            PivotAggregation::Sum | PivotAggregation::Avg => {
+                let Value::Number(n) = value else {
+                    return;
+                };
+                if self.numbers == 0 {
+                    self.all_integers = true;
+                    self.int_sum = Some(0);
+                }
+                self.numbers += 1;
+                self.float_sum += n.as_f64().unwrap_or_default();
+                match n.as_i64() {
+                    Some(i) if self.all_integers => self.int_sum = self.int_sum.and_then(|s| s.checked_add(i)),
+                    _ => self.all_integers = false,
+                }
+            }
+            PivotAggregation::Median | PivotAggregation::Percentile(_) => {
+                if let Some(n) = value.as_f64() {
+                    self.values.push(n);
+                }
+            }
+        }
+    }
+
+    fn finish(&self, aggregation: PivotAggregation) -> Value {
+        match aggregation {
+            PivotAggregation::Count => Value::from(self.count),
+            PivotAggregation::CountDistinct => Value::from(self.distinct.len()),
+            PivotAggregation::Min => self.min.clone().unwrap_or(Value::Null),
+            PivotAggregation::Max => self.max.clone().unwrap_or(Value::Null),
+            PivotAggregation::Sum if self.numbers == 0 => Value::Null,
+            PivotAggregation::Sum => match self.int_sum {
+                Some(sum) if self.all_integers => Value::from(sum),
+                _ => Value::from(self.float_sum),
+            },
+            PivotAggregation::Avg if self.numbers == 0 => Value::Null,
+            PivotAggregation::Avg => Value::from(self.float_sum / self.numbers as f64),
+            PivotAggregation::Median => percentile(&self.values, 50.0),
+            PivotAggregation::Percentile(p) => percentile(&self.values, p),
+        }
+    }
+}
+
+/// The `p`th percentile of `values`, interpolating linearly between the
+/// closest ranks.
+fn percentile(values: &[f64], p: f64) -> Value {
+    if values.is_empty() {
+        return Value::Null;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    Value::from(sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64))
+}
@@ -1,10 +1,14 @@
-//! SQL preprocessor: resolves `source:Name` aliases to dataset table names.
+//! SQL preprocessor: resolves `source:Name` aliases to dataset table names
+//! and `entity:type` aliases to entity tables.
 
+use super::entities::entity_table_name;
 use crate::error::{DatasetError, DatasetResult};
 use crate::types::{DatasetId, PreprocessedSql, StatementType};
 
 /// Preprocess SQL by resolving `source:Name` or `source:"Quoted Name"` aliases
-/// into their actual SQLite table names (`ds_<uuid>`).
+/// into their actual SQLite table names (`ds_<uuid>`), and `entity:type` or
+/// `entity:"quoted.type"` aliases into the names of the temporary tables
+/// the entity types are loaded into (`entity_<uuid>`).
 pub fn preprocess_sql(
     raw_sql: &str,
     resolver: impl Fn(&str) -> Option<DatasetId>,
@@ -42,12 +46,27 @@ pub fn preprocess_sql(
         }
     }
 
+    let entity_re = regex_lite::Regex::new(r#"entity:("([^"]+)"|([A-Za-z0-9_]+))"#)
+        .map_err(|e| DatasetError::InvalidQuery(format!("Regex error: {e}")))?;
+    let mut referenced_entities: Vec<(String, String)> = Vec::new();
+    let resolved = entity_re.replace_all(&sql, |cap: &regex_lite::Captures<'_>| {
+        let entity_type = cap.get(2).or_else(|| cap.get(3)).map_or("", |m| m.as_str());
+        if let Some((_, table)) = referenced_entities.iter().find(|(t, _)| t == entity_type) {
+            return table.clone();
+        }
+        let table = entity_table_name();
+        referenced_entities.push((entity_type.to_string(), table.clone()));
+        table
+    });
+    let sql = resolved.into_owned();
+
     let statement_type = classify_sql(&sql);
 
     Ok(PreprocessedSql {
         sql,
         statement_type,
         referenced_datasets,
+        referenced_entities,
     })
 }
 
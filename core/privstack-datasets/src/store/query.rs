@@ -1,13 +1,21 @@
 //! Query operations: paginated dataset queries, column introspection, raw SQL, aggregations.
 
+use super::entities::{EntitySource, NoEntities};
+use super::formulas::{dataset_columns, with_computed_columns};
 use super::helpers::{
-    build_filter_clause, build_typed_select, introspect_columns, row_value_to_json,
+    build_filter_clause, build_typed_select, build_view_clauses, row_value_to_json,
     sanitize_identifier,
 };
+use super::sandbox::{Sandbox, SqlAccess};
 use super::DatasetStore;
 use crate::error::{DatasetError, DatasetResult};
 use crate::schema::dataset_table_name;
-use crate::types::{DatasetColumn, DatasetColumnType, DatasetId, DatasetQueryResult, SqlExecutionResult};
+use crate::types::{
+    Aggregation, DatasetColumn, DatasetColumnType, DatasetId, DatasetQueryResult,
+    SqlExecutionResult, StatementType, ViewConfig,
+};
+use privstack_db::rusqlite::types::Value;
+use privstack_db::rusqlite::{params_from_iter, Connection, Row};
 
 impl DatasetStore {
     /// Paginated query against a dataset with optional filter and sort.
@@ -23,11 +31,15 @@ impl DatasetStore {
         let table = dataset_table_name(id);
         let conn = self.lock_conn();
 
-        let columns = introspect_columns(&conn, &table)?;
-        let where_clause = build_filter_clause(&columns, filter_text);
+        let columns = dataset_columns(&conn, id)?;
+        let (where_clause, params) = build_filter_clause(&columns, filter_text);
 
-        let count_sql = format!("SELECT COUNT(*) FROM {table}{where_clause}");
-        let total_count: i64 = conn.query_row(&count_sql, [], |row| row.get(0))?;
+        let count_sql = with_computed_columns(
+            &conn,
+            &format!("SELECT COUNT(*) FROM {table}{where_clause}"),
+        )?;
+        let total_count: i64 =
+            conn.query_row(&count_sql, params_from_iter(&params), |row| row.get(0))?;
 
         let order_by = match sort_column {
             Some(col) => {
@@ -40,8 +52,10 @@ impl DatasetStore {
 
         let offset = page * page_size;
         let select_clause = build_typed_select(&columns);
-        let data_sql =
-            format!("SELECT {select_clause} FROM {table}{where_clause}{order_by} LIMIT {page_size} OFFSET {offset}");
+        let data_sql = with_computed_columns(
+            &conn,
+            &format!("SELECT {select_clause} FROM {table}{where_clause}{order_by} LIMIT {page_size} OFFSET {offset}"),
+        )?;
 
         let col_count = columns.len();
         let col_names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
@@ -50,7 +64,7 @@ impl DatasetStore {
 
         let mut stmt = conn.prepare(&data_sql)?;
         let rows: Vec<Vec<serde_json::Value>> = stmt
-            .query_map([], |row| {
+            .query_map(params_from_iter(&params), |row| {
                 let mut vals = Vec::with_capacity(col_count);
                 for i in 0..col_count {
                     let val = row_value_to_json(row, i);
@@ -68,18 +82,69 @@ impl DatasetStore {
             total_count,
             page,
             page_size,
+            materialization: None,
         })
     }
 
-    /// Get column metadata for a dataset.
-    pub fn get_columns(&self, id: &DatasetId) -> DatasetResult<Vec<DatasetColumn>> {
+    /// Paginated query against a dataset as a view shows it: only its
+    /// visible columns, and rows passing its filters in its sort order.
+    pub fn query_view(
+        &self,
+        id: &DatasetId,
+        config: &ViewConfig,
+        page: i64,
+        page_size: i64,
+    ) -> DatasetResult<DatasetQueryResult> {
         let table = dataset_table_name(id);
         let conn = self.lock_conn();
-        introspect_columns(&conn, &table)
+
+        let all_columns = dataset_columns(&conn, id)?;
+        let (clauses, params) = build_view_clauses(&all_columns, config)?;
+        let columns: Vec<DatasetColumn> = match &config.visible_columns {
+            Some(visible) => all_columns.into_iter().filter(|c| visible.contains(&c.name)).collect(),
+            None => all_columns,
+        };
+        if columns.is_empty() {
+            return Err(DatasetError::InvalidQuery("View has no visible columns".to_string()));
+        }
+
+        let count_sql = with_computed_columns(&conn, &format!("SELECT COUNT(*) FROM {table}{clauses}"))?;
+        let total_count: i64 =
+            conn.query_row(&count_sql, params_from_iter(&params), |row| row.get(0))?;
+
+        let offset = page * page_size;
+        let data_sql = with_computed_columns(
+            &conn,
+            &format!(
+                "SELECT {} FROM {table}{clauses} LIMIT {page_size} OFFSET {offset}",
+                build_typed_select(&columns)
+            ),
+        )?;
+        let rows = query_rows(&conn, &data_sql, &params, |row| {
+            Ok((0..columns.len()).map(|i| row_value_to_json(row, i)).collect())
+        })?;
+
+        Ok(DatasetQueryResult {
+            columns: columns.iter().map(|c| c.name.clone()).collect(),
+            column_types: columns.iter().map(|c| c.column_type.clone()).collect(),
+            rows,
+            total_count,
+            page,
+            page_size,
+            materialization: None,
+        })
+    }
+
+    /// Get column metadata for a dataset, formula columns last.
+    pub fn get_columns(&self, id: &DatasetId) -> DatasetResult<Vec<DatasetColumn>> {
+        let conn = self.lock_conn();
+        dataset_columns(&conn, id)
     }
 
     /// Execute an arbitrary read-only SQL query.
-    /// Only SELECT statements are allowed.
+    /// Only SELECT statements are allowed. The query runs in the SQL sandbox:
+    /// it may only read dataset tables, and pages are capped at the
+    /// store's row limit.
     pub fn execute_raw_query(
         &self,
         sql: &str,
@@ -94,35 +159,41 @@ impl DatasetStore {
             ));
         }
 
+        let limits = self.sql_limits();
+        let page_size = page_size.min(limits.max_rows as i64);
         let conn = self.lock_conn();
+        let sql = with_computed_columns(&conn, sql)?;
+        let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+        Self::run_raw_query(&conn, &sql, page, page_size).map_err(|e| sandbox.explain(e))
+    }
 
+    fn run_raw_query(
+        conn: &Connection,
+        sql: &str,
+        page: i64,
+        page_size: i64,
+    ) -> DatasetResult<DatasetQueryResult> {
         // Get column names and types by executing a LIMIT 0 query
-        let described = Self::describe_query_columns(&conn, sql)?;
+        let described = Self::describe_query_columns(conn, sql)?;
         let col_count = described.len();
         let col_names: Vec<String> = described.iter().map(|(n, _)| n.clone()).collect();
         let col_types: Vec<DatasetColumnType> = described.iter().map(|(_, t)| t.clone()).collect();
 
         let count_sql = format!("SELECT COUNT(*) FROM ({sql}) AS q");
-        let total_count: i64 = conn
-            .query_row(&count_sql, [], |row| row.get(0))
-            .unwrap_or(0);
+        let total_count: i64 = conn.query_row(&count_sql, [], |row| row.get(0))?;
 
         let offset = page * page_size;
         let data_sql = format!(
             "SELECT * FROM ({sql}) LIMIT {page_size} OFFSET {offset}"
         );
 
-        let mut stmt = conn.prepare(&data_sql)?;
-        let rows: Vec<Vec<serde_json::Value>> = stmt
-            .query_map([], |row| {
-                let mut vals = Vec::with_capacity(col_count);
-                for i in 0..col_count {
-                    vals.push(row_value_to_json(row, i));
-                }
-                Ok(vals)
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
+        let rows = query_rows(conn, &data_sql, &[], |row| {
+            let mut vals = Vec::with_capacity(col_count);
+            for i in 0..col_count {
+                vals.push(row_value_to_json(row, i));
+            }
+            Ok(vals)
+        })?;
 
         Ok(DatasetQueryResult {
             columns: col_names,
@@ -131,18 +202,26 @@ impl DatasetStore {
             total_count,
             page,
             page_size,
+            materialization: None,
         })
     }
 
     /// Get column names and types for an arbitrary SELECT query.
     ///
     /// SQLite doesn't have DESCRIBE, so we prepare the statement and
-    /// read column metadata from the prepared statement handle.
-    fn describe_query_columns(
-        conn: &privstack_db::rusqlite::Connection,
+    /// read column metadata from the prepared statement handle. Statements
+    /// that would write (such as a `WITH` clause feeding a `DELETE`) are
+    /// rejected here.
+    pub(super) fn describe_query_columns(
+        conn: &Connection,
         sql: &str,
     ) -> DatasetResult<Vec<(String, DatasetColumnType)>> {
         let stmt = conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(DatasetError::InvalidQuery(
+                "Only SELECT statements are allowed".to_string(),
+            ));
+        }
         let columns = stmt.columns();
         if columns.is_empty() {
             return Err(DatasetError::InvalidQuery(
@@ -164,71 +243,75 @@ impl DatasetStore {
     }
 
     /// Execute an aggregate query for charts/visualizations.
+    /// Results are capped at the store's row limit.
     pub fn aggregate_query(
         &self,
         dataset_id: &DatasetId,
         x_column: &str,
         y_column: &str,
-        aggregation: Option<&str>,
+        aggregation: Option<Aggregation>,
         group_by: Option<&str>,
         filter_text: Option<&str>,
     ) -> DatasetResult<Vec<(serde_json::Value, serde_json::Value)>> {
         let table = dataset_table_name(dataset_id);
+        let limits = self.sql_limits();
         let conn = self.lock_conn();
-        let columns = introspect_columns(&conn, &table)?;
-        let where_clause = build_filter_clause(&columns, filter_text);
+        let columns = dataset_columns(&conn, dataset_id)?;
+        let (where_clause, params) = build_filter_clause(&columns, filter_text);
 
         let x_col = sanitize_identifier(x_column);
         let y_col = sanitize_identifier(y_column);
 
         let x_expr = format!("\"{x_col}\"");
+        let limit = limits.max_rows;
 
-        let sql = match (aggregation, group_by) {
+        let sql = match (aggregation.map(Aggregation::sql_function), group_by) {
             (Some(agg), Some(grp)) => {
                 let grp_col = sanitize_identifier(grp);
                 let grp_expr = format!("\"{grp_col}\"");
                 format!(
-                    "SELECT {grp_expr}, {agg}(\"{y_col}\") FROM {table}{where_clause} GROUP BY \"{grp_col}\" ORDER BY \"{grp_col}\""
+                    "SELECT {grp_expr}, {agg}(\"{y_col}\") FROM {table}{where_clause} GROUP BY \"{grp_col}\" ORDER BY \"{grp_col}\" LIMIT {limit}"
                 )
             }
             (Some(agg), None) => {
                 format!(
-                    "SELECT {x_expr}, {agg}(\"{y_col}\") FROM {table}{where_clause} GROUP BY \"{x_col}\" ORDER BY \"{x_col}\""
+                    "SELECT {x_expr}, {agg}(\"{y_col}\") FROM {table}{where_clause} GROUP BY \"{x_col}\" ORDER BY \"{x_col}\" LIMIT {limit}"
                 )
             }
             _ => {
                 format!(
-                    "SELECT {x_expr}, \"{y_col}\" FROM {table}{where_clause} ORDER BY \"{x_col}\""
+                    "SELECT {x_expr}, \"{y_col}\" FROM {table}{where_clause} ORDER BY \"{x_col}\" LIMIT {limit}"
                 )
             }
         };
 
-        let mut stmt = conn.prepare(&sql)?;
-        let rows: Vec<(serde_json::Value, serde_json::Value)> = stmt
-            .query_map([], |row| {
-                Ok((row_value_to_json(row, 0), row_value_to_json(row, 1)))
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
+        let sql = with_computed_columns(&conn, &sql)?;
+        let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+        let rows = query_rows(&conn, &sql, &params, |row| {
+            Ok((row_value_to_json(row, 0), row_value_to_json(row, 1)))
+        })
+        .map_err(|e| sandbox.explain(e))?;
 
         Ok(rows)
     }
 
     /// Execute a grouped aggregate query for multi-series charts.
-    /// Returns (x_value, group_value, y_value) triples.
+    /// Returns (x_value, group_value, y_value) triples, capped at the
+    /// store's row limit.
     pub fn aggregate_query_grouped(
         &self,
         dataset_id: &DatasetId,
         x_column: &str,
         y_column: &str,
         group_column: &str,
-        aggregation: Option<&str>,
+        aggregation: Option<Aggregation>,
         filter_text: Option<&str>,
     ) -> DatasetResult<Vec<(serde_json::Value, serde_json::Value, serde_json::Value)>> {
         let table = dataset_table_name(dataset_id);
+        let limits = self.sql_limits();
         let conn = self.lock_conn();
-        let columns = introspect_columns(&conn, &table)?;
-        let where_clause = build_filter_clause(&columns, filter_text);
+        let columns = dataset_columns(&conn, dataset_id)?;
+        let (where_clause, params) = build_filter_clause(&columns, filter_text);
 
         let x_col = sanitize_identifier(x_column);
         let y_col = sanitize_identifier(y_column);
@@ -238,37 +321,52 @@ impl DatasetStore {
         let grp_expr = format!("\"{grp_col}\"");
 
         let agg_expr = match aggregation {
-            Some(agg) => format!("{agg}(\"{y_col}\")"),
+            Some(agg) => format!("{}(\"{y_col}\")", agg.sql_function()),
             None => format!("\"{y_col}\""),
         };
 
         let sql = format!(
             "SELECT {x_expr}, {grp_expr}, {agg_expr} FROM {table}{where_clause} \
-             GROUP BY \"{x_col}\", \"{grp_col}\" ORDER BY \"{x_col}\", \"{grp_col}\""
+             GROUP BY \"{x_col}\", \"{grp_col}\" ORDER BY \"{x_col}\", \"{grp_col}\" LIMIT {}",
+            limits.max_rows
         );
 
-        let mut stmt = conn.prepare(&sql)?;
-        let rows: Vec<(serde_json::Value, serde_json::Value, serde_json::Value)> = stmt
-            .query_map([], |row| {
-                Ok((
-                    row_value_to_json(row, 0),
-                    row_value_to_json(row, 1),
-                    row_value_to_json(row, 2),
-                ))
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
+        let sql = with_computed_columns(&conn, &sql)?;
+        let sandbox = Sandbox::enter(&conn, SqlAccess::Read, limits)?;
+        let rows = query_rows(&conn, &sql, &params, |row| {
+            Ok((
+                row_value_to_json(row, 0),
+                row_value_to_json(row, 1),
+                row_value_to_json(row, 2),
+            ))
+        })
+        .map_err(|e| sandbox.explain(e))?;
 
         Ok(rows)
     }
 
-    /// Unified SQL entry point: routes through preprocessor, then to read or mutation path.
+    /// Unified SQL entry point: routes through preprocessor, then to read or
+    /// mutation path. `entity:` aliases are refused; see
+    /// [`Self::execute_sql_with_entities`].
     pub fn execute_sql_v2(
         &self,
         raw_sql: &str,
         page: i64,
         page_size: i64,
         dry_run: bool,
+    ) -> DatasetResult<SqlExecutionResult> {
+        self.execute_sql_with_entities(raw_sql, page, page_size, dry_run, &NoEntities)
+    }
+
+    /// Like [`Self::execute_sql_v2`], with `entity:type` aliases resolved
+    /// against `entities`.
+    pub fn execute_sql_with_entities(
+        &self,
+        raw_sql: &str,
+        page: i64,
+        page_size: i64,
+        dry_run: bool,
+        entities: &dyn EntitySource,
     ) -> DatasetResult<SqlExecutionResult> {
         use super::preprocessor::preprocess_sql;
 
@@ -279,21 +377,34 @@ impl DatasetStore {
             return Err(DatasetError::InvalidQuery("Empty SQL".to_string()));
         }
 
-        // Resolve source: aliases
+        // Resolve source: and entity: aliases
         let datasets = self.list()?;
         let preprocessed = preprocess_sql(cleaned, |name| {
             datasets.iter().find(|d| d.name == name).map(|d| d.id.clone())
         })?;
 
-        match preprocessed.statement_type {
-            crate::types::StatementType::Select => {
-                let result = self.execute_raw_query(&preprocessed.sql, page, page_size)?;
-                Ok(SqlExecutionResult::Query(result))
-            }
-            _ => {
-                let result = self.execute_mutation(&preprocessed.sql, dry_run)?;
-                Ok(SqlExecutionResult::Mutation(result))
-            }
+        let select = preprocessed.statement_type == StatementType::Select;
+        let _loaded = self.load_entities(&preprocessed.referenced_entities, entities, select)?;
+        if select {
+            let result = self.execute_raw_query(&preprocessed.sql, page, page_size)?;
+            Ok(SqlExecutionResult::Query(result))
+        } else {
+            let result = self.execute_mutation(&preprocessed.sql, dry_run)?;
+            Ok(SqlExecutionResult::Mutation(result))
         }
     }
 }
+
+/// Runs a query and collects its rows. Unlike `filter_map(|r| r.ok())`,
+/// this fails on any row error, so an interrupted query is not mistaken
+/// for a short result.
+fn query_rows<T>(
+    conn: &Connection,
+    sql: &str,
+    params: &[Value],
+    map: impl FnMut(&Row<'_>) -> privstack_db::rusqlite::Result<T>,
+) -> DatasetResult<Vec<T>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params_from_iter(params), map)?.collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
@@ -8,7 +8,8 @@ use privstack_db::rusqlite::params;
 use uuid::Uuid;
 
 impl DatasetStore {
-    /// Create a cross-dataset relation.
+    /// Create a cross-dataset relation. Both datasets count as changed, so
+    /// materializations reading through the relation go stale.
     pub fn create_relation(
         &self,
         source_dataset_id: &DatasetId,
@@ -23,6 +24,10 @@ impl DatasetStore {
             "INSERT INTO _dataset_relations (id, source_dataset_id, source_column, target_dataset_id, target_column, relation_type, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![id, source_dataset_id.to_string(), source_column, target_dataset_id.to_string(), target_column, "many_to_one", now],
         )?;
+        conn.execute(
+            "UPDATE _datasets_meta SET modified_at = ?1 WHERE id IN (?2, ?3)",
+            params![now, source_dataset_id.to_string(), target_dataset_id.to_string()],
+        )?;
         Ok(DatasetRelation {
             id,
             source_dataset_id: source_dataset_id.clone(),
@@ -34,9 +39,16 @@ impl DatasetStore {
         })
     }
 
-    /// Delete a relation by ID.
+    /// Delete a relation by ID. Like creating one, this counts as a change
+    /// to both datasets.
     pub fn delete_relation(&self, relation_id: &str) -> DatasetResult<()> {
         let conn = self.lock_conn();
+        conn.execute(
+            "UPDATE _datasets_meta SET modified_at = ?1 WHERE id IN \
+             (SELECT source_dataset_id FROM _dataset_relations WHERE id = ?2 \
+              UNION SELECT target_dataset_id FROM _dataset_relations WHERE id = ?2)",
+            params![now_millis(), relation_id],
+        )?;
         conn.execute(
             "DELETE FROM _dataset_relations WHERE id = ?1",
             params![relation_id],
@@ -0,0 +1,1072 @@
+//! Dataset replication: stable row and column IDs, the journal of local
+//! changes, and the merge of changes made on other devices.
+//!
+//! Merge rules, applied identically on every device:
+//! - Each cell is a last-writer-wins register keyed by (row ID, column ID)
+//!   and ordered by [`OpStamp`].
+//! - A column's name is a last-writer-wins register. When live columns claim
+//!   the same name, the earliest claim keeps it and the others get part of
+//!   their column ID appended.
+//! - Dataset name and category form one last-writer-wins register.
+//! - Deleting a row, a column or a dataset is final: later changes to it are
+//!   ignored, and adding it back creates a new ID.
+//!
+//! Local mutations of a replicated dataset are journaled as they happen.
+//! Raw SQL mutations are journaled by diffing, before and after, every
+//! dataset table the SQL sandbox saw the statement write, so a column
+//! renamed in SQL replicates as a rename only when it is the
+//! statement's sole schema change. Column type changes stay local; values
+//! they rewrite replicate as cell edits.
+
+use super::helpers::{now_millis, row_value_to_json, sanitize_identifier};
+use super::DatasetStore;
+use crate::error::{DatasetError, DatasetResult};
+use crate::schema::dataset_table_name;
+use crate::types::{DatasetChange, DatasetId, DatasetOp, OpStamp};
+use privstack_db::rusqlite::types::Value as SqlValue;
+use privstack_db::rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Name given to a dataset whose columns arrive before its name does.
+const PLACEHOLDER_NAME: &str = "Untitled dataset";
+
+/// Hybrid logical clock stamping local changes.
+pub(crate) struct ReplicaClock {
+    replica: String,
+    wall_ms: u64,
+    logical: u32,
+}
+
+impl ReplicaClock {
+    fn new(replica: &str) -> Self {
+        Self {
+            replica: replica.to_string(),
+            wall_ms: 0,
+            logical: 0,
+        }
+    }
+
+    /// Stamps a local change after every stamp seen so far.
+    fn tick(&mut self) -> OpStamp {
+        let now = now_millis().max(0) as u64;
+        if now > self.wall_ms {
+            self.wall_ms = now;
+            self.logical = 0;
+        } else {
+            self.logical += 1;
+        }
+        OpStamp::new(self.wall_ms, self.logical, self.replica.clone())
+    }
+
+    /// Moves past a replicated stamp, so changes made after receiving it
+    /// order after it everywhere.
+    fn observe(&mut self, stamp: &OpStamp) {
+        if (stamp.wall_ms, stamp.logical) > (self.wall_ms, self.logical) {
+            self.wall_ms = stamp.wall_ms;
+            self.logical = stamp.logical;
+        }
+    }
+}
+
+/// Journals local changes to one replicated dataset.
+struct Recorder<'a> {
+    conn: &'a Connection,
+    clock: &'a mut ReplicaClock,
+    dataset_id: String,
+    table: String,
+}
+
+impl Recorder<'_> {
+    fn journal(&mut self, op: DatasetOp) -> DatasetResult<OpStamp> {
+        let stamp = self.clock.tick();
+        self.conn.execute(
+            "INSERT INTO _dataset_repl_outbox (dataset_id, op_json, stamp_json, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.dataset_id,
+                serde_json::to_string(&op)?,
+                serde_json::to_string(&stamp)?,
+                now_millis()
+            ],
+        )?;
+        Ok(stamp)
+    }
+
+    /// Journals the current values of some cells of a row, assigning the
+    /// row an ID (and journaling all of it) if it has none yet.
+    fn record_cells(&mut self, rowid: i64, physical: &[String]) -> DatasetResult<()> {
+        let row_id = match row_id_for_ref(self.conn, &self.dataset_id, rowid)? {
+            Some(id) => id,
+            None => return self.record_new_row(rowid),
+        };
+        let columns: Vec<ReplColumn> = live_columns(self.conn, &self.dataset_id)?
+            .into_iter()
+            .filter(|c| physical.contains(&c.physical))
+            .collect();
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let cells = read_cells(self.conn, &self.table, rowid, &columns)?;
+        let stamp = self.journal(DatasetOp::UpsertRow {
+            row_id: row_id.clone(),
+            cells,
+        })?;
+        for c in &columns {
+            set_cell_stamp(self.conn, &self.dataset_id, &row_id, &c.column_id, &stamp)?;
+        }
+        Ok(())
+    }
+
+    /// Gives a row an ID and journals all of its cells.
+    fn record_new_row(&mut self, rowid: i64) -> DatasetResult<()> {
+        let row_id = Uuid::new_v4().to_string();
+        self.conn.execute(
+            "INSERT OR REPLACE INTO _dataset_repl_rows (dataset_id, row_id, row_ref, deleted) VALUES (?1, ?2, ?3, 0)",
+            params![self.dataset_id, row_id, rowid],
+        )?;
+        let columns = live_columns(self.conn, &self.dataset_id)?;
+        let cells = read_cells(self.conn, &self.table, rowid, &columns)?;
+        let stamp = self.journal(DatasetOp::UpsertRow {
+            row_id: row_id.clone(),
+            cells,
+        })?;
+        for c in &columns {
+            set_cell_stamp(self.conn, &self.dataset_id, &row_id, &c.column_id, &stamp)?;
+        }
+        Ok(())
+    }
+
+    fn record_row_deleted(&mut self, rowid: i64) -> DatasetResult<()> {
+        // A row without an ID was never published.
+        let Some(row_id) = row_id_for_ref(self.conn, &self.dataset_id, rowid)? else {
+            return Ok(());
+        };
+        self.journal(DatasetOp::DeleteRow { row_id: row_id.clone() })?;
+        tombstone_row(self.conn, &self.dataset_id, &row_id)
+    }
+
+    fn record_column_added(&mut self, name: &str, column_type: &str, default: Option<&str>) -> DatasetResult<()> {
+        let column_id = Uuid::new_v4().to_string();
+        let stamp = self.journal(DatasetOp::AddColumn {
+            column_id: column_id.clone(),
+            name: name.to_string(),
+            column_type: column_type.to_string(),
+            default: default.map(str::to_string),
+        })?;
+        self.conn.execute(
+            "INSERT INTO _dataset_repl_columns (dataset_id, column_id, name, name_stamp, physical, column_type) VALUES (?1, ?2, ?3, ?4, ?3, ?5)",
+            params![self.dataset_id, column_id, name, stamp.encode(), column_type],
+        )?;
+        Ok(())
+    }
+
+    fn record_column_renamed(&mut self, old: &str, new: &str) -> DatasetResult<()> {
+        let Some(column_id) = column_id_for_physical(self.conn, &self.dataset_id, old)? else {
+            return Ok(());
+        };
+        let stamp = self.journal(DatasetOp::RenameColumn {
+            column_id: column_id.clone(),
+            name: new.to_string(),
+        })?;
+        self.conn.execute(
+            "UPDATE _dataset_repl_columns SET name = ?1, name_stamp = ?2, physical = ?1 WHERE dataset_id = ?3 AND column_id = ?4",
+            params![new, stamp.encode(), self.dataset_id, column_id],
+        )?;
+        Ok(())
+    }
+
+    fn record_column_dropped(&mut self, name: &str) -> DatasetResult<()> {
+        let Some(column_id) = column_id_for_physical(self.conn, &self.dataset_id, name)? else {
+            return Ok(());
+        };
+        self.journal(DatasetOp::DropColumn { column_id: column_id.clone() })?;
+        mark_column_dropped(self.conn, &self.dataset_id, &column_id)
+    }
+
+    fn record_meta(&mut self) -> DatasetResult<()> {
+        let (name, category, formulas_json, constraints_json): (String, Option<String>, String, String) =
+            self.conn.query_row(
+                "SELECT name, category, formulas_json, constraints_json FROM _datasets_meta WHERE id = ?1",
+                params![self.dataset_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+        let stamp = self.journal(DatasetOp::SetMeta {
+            name: name.clone(),
+            category: category.clone(),
+            formulas: serde_json::from_str(&formulas_json).unwrap_or_default(),
+            constraints: serde_json::from_str(&constraints_json).unwrap_or_default(),
+        })?;
+        self.conn.execute(
+            "UPDATE _dataset_repl_datasets SET name = ?1, category = ?2, formulas_json = ?3, constraints_json = ?4, meta_stamp = ?5 WHERE dataset_id = ?6",
+            params![name, category, formulas_json, constraints_json, stamp.encode(), self.dataset_id],
+        )?;
+        Ok(())
+    }
+}
+
+/// A replicated column as recorded locally.
+struct ReplColumn {
+    column_id: String,
+    name: String,
+    name_stamp: String,
+    physical: String,
+}
+
+/// Table contents before a raw SQL mutation, to journal what it changed.
+pub(crate) struct TableSnapshot {
+    dataset_id: DatasetId,
+    columns: Vec<String>,
+    rows: TableRows,
+}
+
+impl DatasetStore {
+    /// Enables replication. From now on, changes to replicated datasets are
+    /// journaled under `replica_id` (this device's peer ID) and new datasets
+    /// are replicated as they are created.
+    pub fn enable_replication(&self, replica_id: &str) {
+        let mut replica = self.replica.lock().unwrap_or_else(|p| p.into_inner());
+        match replica.as_ref() {
+            Some(clock) if clock.replica == replica_id => {}
+            _ => *replica = Some(ReplicaClock::new(replica_id)),
+        }
+    }
+
+    /// Starts replicating a dataset: journals its name, columns and rows so
+    /// other devices can build it. Returns false if it already replicates or
+    /// replication is not enabled.
+    pub fn replicate_dataset(&self, id: &DatasetId) -> DatasetResult<bool> {
+        let conn = self.lock_conn();
+        self.track(&conn, id)
+    }
+
+    /// Starts replicating every dataset that does not yet. Returns how many
+    /// datasets were added.
+    pub fn replicate_all(&self) -> DatasetResult<usize> {
+        let mut added = 0;
+        for meta in self.list()? {
+            if self.replicate_dataset(&meta.id)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Removes and returns up to `limit` journaled local changes, oldest
+    /// first, for the sync layer to publish.
+    pub fn take_pending_changes(&self, limit: usize) -> DatasetResult<Vec<DatasetChange>> {
+        let conn = self.lock_conn();
+        let rows = {
+            let mut stmt = conn.prepare(
+                "SELECT seq, dataset_id, op_json, stamp_json FROM _dataset_repl_outbox ORDER BY seq LIMIT ?1",
+            )?;
+            stmt.query_map(params![limit as i64], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut changes = Vec::with_capacity(rows.len());
+        for (seq, dataset_id, op_json, stamp_json) in rows {
+            let uuid = Uuid::parse_str(&dataset_id)
+                .map_err(|e| DatasetError::InvalidQuery(format!("Invalid UUID: {e}")))?;
+            changes.push(DatasetChange {
+                seq,
+                dataset_id: DatasetId(uuid),
+                op: serde_json::from_str(&op_json)?,
+                stamp: serde_json::from_str(&stamp_json)?,
+            });
+        }
+        if let Some(last) = changes.last() {
+            conn.execute("DELETE FROM _dataset_repl_outbox WHERE seq <= ?1", params![last.seq])?;
+        }
+        Ok(changes)
+    }
+
+    /// Applies a change made on another device. Returns true if it changed
+    /// anything; replays and changes that lost to later ones return false.
+    pub fn apply_replicated(
+        &self,
+        dataset_id: &DatasetId,
+        op: &DatasetOp,
+        stamp: &OpStamp,
+    ) -> DatasetResult<bool> {
+        let conn = self.lock_conn();
+        if let Some(clock) = self.replica.lock().unwrap_or_else(|p| p.into_inner()).as_mut() {
+            clock.observe(stamp);
+        }
+
+        conn.execute_batch("SAVEPOINT apply_replicated")?;
+        match apply_op(&conn, dataset_id, op, stamp) {
+            Ok(changed) => {
+                conn.execute_batch("RELEASE SAVEPOINT apply_replicated")?;
+                if changed {
+                    debug!(dataset_id = %dataset_id, ?op, "Applied replicated dataset change");
+                }
+                Ok(changed)
+            }
+            Err(e) => {
+                conn.execute_batch("ROLLBACK TO SAVEPOINT apply_replicated")?;
+                conn.execute_batch("RELEASE SAVEPOINT apply_replicated")?;
+                Err(e)
+            }
+        }
+    }
+
+    // -- Hooks for local mutations. Each is a no-op unless replication is
+    // enabled and the dataset replicates. --
+
+    /// Starts replicating a dataset if replication is enabled.
+    pub(crate) fn track(&self, conn: &Connection, id: &DatasetId) -> DatasetResult<bool> {
+        let mut replica = self.replica.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(clock) = replica.as_mut() else {
+            return Ok(false);
+        };
+        let ds = id.to_string();
+        if dataset_state(conn, &ds)?.is_some() {
+            return Ok(false);
+        }
+        let (name, category): (String, Option<String>) = conn.query_row(
+            "SELECT name, category FROM _datasets_meta WHERE id = ?1",
+            params![ds],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        conn.execute(
+            "INSERT INTO _dataset_repl_datasets (dataset_id, name, category, meta_stamp) VALUES (?1, ?2, ?3, '')",
+            params![ds, name, category],
+        )?;
+
+        let mut rec = Recorder {
+            conn,
+            clock,
+            dataset_id: ds,
+            table: dataset_table_name(id),
+        };
+        rec.record_meta()?;
+        for (name, column_type) in declared_columns(conn, &rec.table)? {
+            rec.record_column_added(&name, &column_type, None)?;
+        }
+        let rowids: Vec<i64> = {
+            let mut stmt = conn.prepare(&format!("SELECT rowid FROM {} ORDER BY rowid", rec.table))?;
+            stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?
+        };
+        for rowid in &rowids {
+            rec.record_new_row(*rowid)?;
+        }
+        info!(dataset_id = %id, rows = rowids.len(), "Dataset replication started");
+        Ok(true)
+    }
+
+    /// Runs `f` with a recorder for the dataset, if its changes are journaled.
+    fn with_recorder(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        f: impl FnOnce(&mut Recorder<'_>) -> DatasetResult<()>,
+    ) -> DatasetResult<()> {
+        let mut replica = self.replica.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(clock) = replica.as_mut() else {
+            return Ok(());
+        };
+        let ds = id.to_string();
+        if dataset_state(conn, &ds)? != Some(false) {
+            return Ok(());
+        }
+        f(&mut Recorder {
+            conn,
+            clock,
+            dataset_id: ds,
+            table: dataset_table_name(id),
+        })
+    }
+
+    pub(crate) fn record_meta_changed(&self, conn: &Connection, id: &DatasetId) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_meta())
+    }
+
+    pub(crate) fn record_dataset_deleted(&self, conn: &Connection, id: &DatasetId) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| {
+            rec.journal(DatasetOp::DeleteDataset)?;
+            tombstone_dataset(rec.conn, &rec.dataset_id)
+        })
+    }
+
+    pub(crate) fn record_row_inserted(&self, conn: &Connection, id: &DatasetId, rowid: i64) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_new_row(rowid))
+    }
+
+    pub(crate) fn record_cell_updated(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        rowid: i64,
+        column: &str,
+    ) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_cells(rowid, &[column.to_string()]))
+    }
+
+    /// Call before the row is deleted.
+    pub(crate) fn record_row_deleted(&self, conn: &Connection, id: &DatasetId, rowid: i64) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_row_deleted(rowid))
+    }
+
+    pub(crate) fn record_column_added(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        name: &str,
+        column_type: &str,
+        default: Option<&str>,
+    ) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_column_added(name, column_type, default))
+    }
+
+    pub(crate) fn record_column_renamed(
+        &self,
+        conn: &Connection,
+        id: &DatasetId,
+        old: &str,
+        new: &str,
+    ) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_column_renamed(old, new))
+    }
+
+    pub(crate) fn record_column_dropped(&self, conn: &Connection, id: &DatasetId, name: &str) -> DatasetResult<()> {
+        self.with_recorder(conn, id, |rec| rec.record_column_dropped(name))
+    }
+
+    /// Captures a replicated dataset's table before a raw SQL mutation of it.
+    pub(crate) fn snapshot_for_diff(
+        &self,
+        conn: &Connection,
+        table: &str,
+    ) -> DatasetResult<Option<TableSnapshot>> {
+        if self.replica.lock().unwrap_or_else(|p| p.into_inner()).is_none() {
+            return Ok(None);
+        }
+        let Some(id) = dataset_id_for_table(table) else {
+            return Ok(None);
+        };
+        if dataset_state(conn, &id.to_string())? != Some(false) {
+            return Ok(None);
+        }
+        let (columns, rows) = read_table(conn, &dataset_table_name(&id))?;
+        Ok(Some(TableSnapshot {
+            dataset_id: id,
+            columns,
+            rows,
+        }))
+    }
+
+    /// Journals what a raw SQL mutation changed since `before`.
+    pub(crate) fn record_diff(&self, conn: &Connection, before: TableSnapshot) -> DatasetResult<()> {
+        let id = before.dataset_id.clone();
+        self.with_recorder(conn, &id, |rec| {
+            let (columns, rows) = read_table(conn, &rec.table)?;
+
+            // Schema: a lone drop-and-add is taken as a rename.
+            let removed: Vec<&String> = before.columns.iter().filter(|c| !columns.contains(c)).collect();
+            let added: Vec<&String> = columns.iter().filter(|c| !before.columns.contains(c)).collect();
+            if removed.len() == 1 && added.len() == 1 {
+                rec.record_column_renamed(removed[0], added[0])?;
+            } else {
+                for name in removed {
+                    rec.record_column_dropped(name)?;
+                }
+                let types: HashMap<String, String> = declared_columns(conn, &rec.table)?.into_iter().collect();
+                for name in added {
+                    let column_type = types.get(name).map(String::as_str).unwrap_or("TEXT");
+                    rec.record_column_added(name, column_type, None)?;
+                }
+            }
+
+            // Rows: compare cells by column name.
+            let old_index: HashMap<&str, usize> =
+                before.columns.iter().enumerate().map(|(i, c)| (c.as_str(), i)).collect();
+            for (rowid, values) in &rows {
+                let Some(old) = before.rows.get(rowid) else {
+                    rec.record_new_row(*rowid)?;
+                    continue;
+                };
+                let changed: Vec<String> = columns
+                    .iter()
+                    .zip(values)
+                    .filter(|(name, value)| match old_index.get(name.as_str()) {
+                        Some(&i) => &old[i] != *value,
+                        // New columns were journaled with their default.
+                        None => false,
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                if !changed.is_empty() {
+                    rec.record_cells(*rowid, &changed)?;
+                }
+            }
+            for rowid in before.rows.keys().filter(|r| !rows.contains_key(r)) {
+                rec.record_row_deleted(*rowid)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// -- Applying replicated changes --
+
+fn apply_op(conn: &Connection, id: &DatasetId, op: &DatasetOp, stamp: &OpStamp) -> DatasetResult<bool> {
+    let ds = id.to_string();
+    let table = dataset_table_name(id);
+    let s = stamp.encode();
+    let state = dataset_state(conn, &ds)?;
+    if state == Some(true) {
+        return Ok(false);
+    }
+
+    match op {
+        DatasetOp::SetMeta { name, category, formulas, constraints } => {
+            let formulas = serde_json::to_string(formulas)?;
+            let constraints = serde_json::to_string(constraints)?;
+            match state {
+                None => {
+                    conn.execute(
+                        "INSERT INTO _dataset_repl_datasets (dataset_id, name, category, formulas_json, constraints_json, meta_stamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![ds, name, category, formulas, constraints, s],
+                    )?;
+                }
+                Some(_) => {
+                    let updated = conn.execute(
+                        "UPDATE _dataset_repl_datasets SET name = ?1, category = ?2, formulas_json = ?3, constraints_json = ?4, meta_stamp = ?5 WHERE dataset_id = ?6 AND meta_stamp < ?5",
+                        params![name, category, formulas, constraints, s, ds],
+                    )?;
+                    if updated == 0 {
+                        return Ok(false);
+                    }
+                }
+            }
+            conn.execute(
+                "UPDATE _datasets_meta SET name = ?1, category = ?2, formulas_json = ?3, constraints_json = ?4, modified_at = ?5 WHERE id = ?6",
+                params![name, category, formulas, constraints, now_millis(), ds],
+            )?;
+            Ok(true)
+        }
+
+        DatasetOp::DeleteDataset => {
+            if state.is_none() {
+                conn.execute(
+                    "INSERT INTO _dataset_repl_datasets (dataset_id, name, meta_stamp) VALUES (?1, '', ?2)",
+                    params![ds, s],
+                )?;
+            }
+            super::crud::remove_dataset(conn, id)?;
+            tombstone_dataset(conn, &ds)?;
+            Ok(true)
+        }
+
+        DatasetOp::AddColumn { column_id, name, column_type, default } => {
+            if state.is_none() {
+                conn.execute(
+                    "INSERT INTO _dataset_repl_datasets (dataset_id, name, meta_stamp) VALUES (?1, ?2, '')",
+                    params![ds, PLACEHOLDER_NAME],
+                )?;
+            }
+            // A rename that arrived first leaves a placeholder to fill in.
+            let existing: Option<(String, Option<String>, bool)> = conn
+                .query_row(
+                    "SELECT name_stamp, physical, dropped FROM _dataset_repl_columns WHERE dataset_id = ?1 AND column_id = ?2",
+                    params![ds, column_id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            let temp = temp_column_name(column_id);
+            match existing {
+                None => {
+                    conn.execute(
+                        "INSERT INTO _dataset_repl_columns (dataset_id, column_id, name, name_stamp, physical, column_type) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![ds, column_id, name, s, temp, column_type],
+                    )?;
+                }
+                Some((_, Some(_), _)) | Some((_, None, true)) => return Ok(false),
+                Some((name_stamp, None, false)) => {
+                    if s > name_stamp {
+                        conn.execute(
+                            "UPDATE _dataset_repl_columns SET name = ?1, name_stamp = ?2 WHERE dataset_id = ?3 AND column_id = ?4",
+                            params![name, s, ds, column_id],
+                        )?;
+                    }
+                    conn.execute(
+                        "UPDATE _dataset_repl_columns SET physical = ?1, column_type = ?2 WHERE dataset_id = ?3 AND column_id = ?4",
+                        params![temp, column_type, ds, column_id],
+                    )?;
+                }
+            }
+
+            let default_clause = match default {
+                Some(d) => format!(" DEFAULT '{}'", d.replace('\'', "''")),
+                None => String::new(),
+            };
+            let column_type = sql_type(column_type);
+            if table_exists(conn, &table)? {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE {table} ADD COLUMN \"{temp}\" {column_type}{default_clause}"
+                ))?;
+            } else {
+                conn.execute_batch(&format!(
+                    "CREATE TABLE {table} (\"{temp}\" {column_type}{default_clause})"
+                ))?;
+                let (meta_name, category, formulas, constraints): (String, Option<String>, String, String) =
+                    conn.query_row(
+                        "SELECT name, category, formulas_json, constraints_json FROM _dataset_repl_datasets WHERE dataset_id = ?1",
+                        params![ds],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                    )?;
+                let now = now_millis();
+                conn.execute(
+                    r#"INSERT OR REPLACE INTO _datasets_meta (id, name, source_file_name, row_count, columns_json, category, formulas_json, constraints_json, created_at, modified_at)
+                       VALUES (?1, ?2, NULL, 0, '[]', ?3, ?4, ?5, ?6, ?6)"#,
+                    params![ds, meta_name, category, formulas, constraints, now],
+                )?;
+            }
+            resolve_column_names(conn, &ds, &table)?;
+            Ok(true)
+        }
+
+        DatasetOp::RenameColumn { column_id, name } => {
+            let existing: Option<(String, bool)> = conn
+                .query_row(
+                    "SELECT name_stamp, dropped FROM _dataset_repl_columns WHERE dataset_id = ?1 AND column_id = ?2",
+                    params![ds, column_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match existing {
+                None => {
+                    conn.execute(
+                        "INSERT INTO _dataset_repl_columns (dataset_id, column_id, name, name_stamp) VALUES (?1, ?2, ?3, ?4)",
+                        params![ds, column_id, name, s],
+                    )?;
+                    Ok(true)
+                }
+                Some((_, true)) => Ok(false),
+                Some((name_stamp, false)) => {
+                    if s <= name_stamp {
+                        return Ok(false);
+                    }
+                    conn.execute(
+                        "UPDATE _dataset_repl_columns SET name = ?1, name_stamp = ?2 WHERE dataset_id = ?3 AND column_id = ?4",
+                        params![name, s, ds, column_id],
+                    )?;
+                    resolve_column_names(conn, &ds, &table)?;
+                    Ok(true)
+                }
+            }
+        }
+
+        DatasetOp::DropColumn { column_id } => {
+            let existing: Option<(Option<String>, bool)> = conn
+                .query_row(
+                    "SELECT physical, dropped FROM _dataset_repl_columns WHERE dataset_id = ?1 AND column_id = ?2",
+                    params![ds, column_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match existing {
+                None => {
+                    conn.execute(
+                        "INSERT INTO _dataset_repl_columns (dataset_id, column_id, name, name_stamp, dropped) VALUES (?1, ?2, '', ?3, 1)",
+                        params![ds, column_id, s],
+                    )?;
+                    Ok(true)
+                }
+                Some((_, true)) => Ok(false),
+                Some((physical, false)) => {
+                    // SQLite cannot drop a table's last column; it stays
+                    // in the table but no longer replicates.
+                    if let Some(physical) = physical {
+                        if live_columns(conn, &ds)?.len() > 1 {
+                            conn.execute_batch(&format!(
+                                "ALTER TABLE {table} DROP COLUMN \"{}\"",
+                                sanitize_identifier(&physical)
+                            ))?;
+                        }
+                    }
+                    mark_column_dropped(conn, &ds, column_id)?;
+                    resolve_column_names(conn, &ds, &table)?;
+                    Ok(true)
+                }
+            }
+        }
+
+        DatasetOp::UpsertRow { row_id, cells } => {
+            if !table_exists(conn, &table)? {
+                debug!(dataset_id = %id, "Row change for a dataset without columns; ignored");
+                return Ok(false);
+            }
+            let row: Option<(Option<i64>, bool)> = conn
+                .query_row(
+                    "SELECT row_ref, deleted FROM _dataset_repl_rows WHERE dataset_id = ?1 AND row_id = ?2",
+                    params![ds, row_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let physical: HashMap<String, String> = live_columns(conn, &ds)?
+                .into_iter()
+                .map(|c| (c.column_id, c.physical))
+                .collect();
+
+            let changed = match row {
+                Some((_, true)) => return Ok(false),
+                Some((Some(rowid), false)) => {
+                    let mut set = Vec::new();
+                    for (column_id, value) in cells {
+                        let Some(col) = physical.get(column_id) else { continue };
+                        let current: Option<String> = conn
+                            .query_row(
+                                "SELECT stamp FROM _dataset_repl_cells WHERE dataset_id = ?1 AND row_id = ?2 AND column_id = ?3",
+                                params![ds, row_id, column_id],
+                                |row| row.get(0),
+                            )
+                            .optional()?;
+                        if current.is_some_and(|c| s <= c) {
+                            continue;
+                        }
+                        set.push((col.clone(), json_to_sql(value)));
+                        set_cell_stamp(conn, &ds, row_id, column_id, stamp)?;
+                    }
+                    if !set.is_empty() {
+                        let assignments: Vec<String> = set
+                            .iter()
+                            .enumerate()
+                            .map(|(i, (col, _))| format!("\"{}\" = ?{}", sanitize_identifier(col), i + 1))
+                            .collect();
+                        let mut values: Vec<SqlValue> = set.into_iter().map(|(_, v)| v).collect();
+                        values.push(SqlValue::Integer(rowid));
+                        conn.execute(
+                            &format!(
+                                "UPDATE {table} SET {} WHERE rowid = ?{}",
+                                assignments.join(", "),
+                                values.len()
+                            ),
+                            privstack_db::rusqlite::params_from_iter(values),
+                        )?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                _ => {
+                    let known: Vec<(&String, &String, &serde_json::Value)> = cells
+                        .iter()
+                        .filter_map(|(id, v)| physical.get(id).map(|p| (id, p, v)))
+                        .collect();
+                    if known.is_empty() {
+                        conn.execute_batch(&format!("INSERT INTO {table} DEFAULT VALUES"))?;
+                    } else {
+                        let names: Vec<String> = known
+                            .iter()
+                            .map(|(_, p, _)| format!("\"{}\"", sanitize_identifier(p)))
+                            .collect();
+                        let placeholders: Vec<String> = (1..=known.len()).map(|i| format!("?{i}")).collect();
+                        conn.execute(
+                            &format!(
+                                "INSERT INTO {table} ({}) VALUES ({})",
+                                names.join(", "),
+                                placeholders.join(", ")
+                            ),
+                            privstack_db::rusqlite::params_from_iter(known.iter().map(|(_, _, v)| json_to_sql(v))),
+                        )?;
+                    }
+                    let rowid = conn.last_insert_rowid();
+                    conn.execute(
+                        "INSERT OR REPLACE INTO _dataset_repl_rows (dataset_id, row_id, row_ref, deleted) VALUES (?1, ?2, ?3, 0)",
+                        params![ds, row_id, rowid],
+                    )?;
+                    for (column_id, _, _) in &known {
+                        set_cell_stamp(conn, &ds, row_id, column_id, stamp)?;
+                    }
+                    true
+                }
+            };
+            if changed {
+                refresh_row_count(conn, &ds, &table)?;
+            }
+            Ok(changed)
+        }
+
+        DatasetOp::DeleteRow { row_id } => {
+            let row: Option<(Option<i64>, bool)> = conn
+                .query_row(
+                    "SELECT row_ref, deleted FROM _dataset_repl_rows WHERE dataset_id = ?1 AND row_id = ?2",
+                    params![ds, row_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match row {
+                Some((_, true)) => Ok(false),
+                Some((rowid, false)) => {
+                    if let Some(rowid) = rowid {
+                        conn.execute(&format!("DELETE FROM {table} WHERE rowid = ?1"), params![rowid])?;
+                    }
+                    tombstone_row(conn, &ds, row_id)?;
+                    refresh_row_count(conn, &ds, &table)?;
+                    Ok(true)
+                }
+                None => {
+                    tombstone_row(conn, &ds, row_id)?;
+                    Ok(true)
+                }
+            }
+        }
+    }
+}
+
+/// Renames physical columns to the names the merge rules give them: each
+/// claimed name goes to its earliest live claim, by (stamp, column ID);
+/// later claims get the first characters of their column ID appended.
+fn resolve_column_names(conn: &Connection, ds: &str, table: &str) -> DatasetResult<()> {
+    let mut columns = live_columns(conn, ds)?;
+    columns.sort_by(|a, b| (&a.name_stamp, &a.column_id).cmp(&(&b.name_stamp, &b.column_id)));
+
+    let mut claimed = HashSet::new();
+    let mut changes = Vec::new();
+    for c in &columns {
+        let desired = if claimed.insert(c.name.clone()) {
+            c.name.clone()
+        } else {
+            format!("{} ({})", c.name, &c.column_id[..c.column_id.len().min(8)])
+        };
+        if desired != c.physical {
+            changes.push((c, desired));
+        }
+    }
+    if changes.is_empty() {
+        return refresh_columns_meta(conn, ds, table);
+    }
+
+    // Move every column aside first, so swapped names never collide.
+    for (c, _) in &changes {
+        let temp = temp_column_name(&c.column_id);
+        if c.physical != temp {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {table} RENAME COLUMN \"{}\" TO \"{temp}\"",
+                sanitize_identifier(&c.physical)
+            ))?;
+        }
+    }
+    for (c, desired) in &changes {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} RENAME COLUMN \"{}\" TO \"{}\"",
+            temp_column_name(&c.column_id),
+            sanitize_identifier(desired)
+        ))?;
+        conn.execute(
+            "UPDATE _dataset_repl_columns SET physical = ?1 WHERE dataset_id = ?2 AND column_id = ?3",
+            params![desired, ds, c.column_id],
+        )?;
+    }
+    refresh_columns_meta(conn, ds, table)
+}
+
+fn refresh_columns_meta(conn: &Connection, ds: &str, table: &str) -> DatasetResult<()> {
+    let columns = super::helpers::introspect_columns(conn, table)?;
+    conn.execute(
+        "UPDATE _datasets_meta SET columns_json = ?1, modified_at = ?2 WHERE id = ?3",
+        params![serde_json::to_string(&columns)?, now_millis(), ds],
+    )?;
+    Ok(())
+}
+
+fn refresh_row_count(conn: &Connection, ds: &str, table: &str) -> DatasetResult<()> {
+    let row_count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))?;
+    conn.execute(
+        "UPDATE _datasets_meta SET row_count = ?1, modified_at = ?2 WHERE id = ?3",
+        params![row_count, now_millis(), ds],
+    )?;
+    Ok(())
+}
+
+// -- Replication state --
+
+/// `None` if the dataset does not replicate, otherwise whether it is deleted.
+fn dataset_state(conn: &Connection, ds: &str) -> DatasetResult<Option<bool>> {
+    Ok(conn
+        .query_row(
+            "SELECT deleted FROM _dataset_repl_datasets WHERE dataset_id = ?1",
+            params![ds],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn tombstone_dataset(conn: &Connection, ds: &str) -> DatasetResult<()> {
+    conn.execute("UPDATE _dataset_repl_datasets SET deleted = 1 WHERE dataset_id = ?1", params![ds])?;
+    for table in ["_dataset_repl_columns", "_dataset_repl_rows", "_dataset_repl_cells"] {
+        conn.execute(&format!("DELETE FROM {table} WHERE dataset_id = ?1"), params![ds])?;
+    }
+    Ok(())
+}
+
+fn live_columns(conn: &Connection, ds: &str) -> DatasetResult<Vec<ReplColumn>> {
+    let mut stmt = conn.prepare(
+        "SELECT column_id, name, name_stamp, physical FROM _dataset_repl_columns \
+         WHERE dataset_id = ?1 AND dropped = 0 AND physical IS NOT NULL",
+    )?;
+    let columns = stmt
+        .query_map(params![ds], |row| {
+            Ok(ReplColumn {
+                column_id: row.get(0)?,
+                name: row.get(1)?,
+                name_stamp: row.get(2)?,
+                physical: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(columns)
+}
+
+fn column_id_for_physical(conn: &Connection, ds: &str, physical: &str) -> DatasetResult<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT column_id FROM _dataset_repl_columns WHERE dataset_id = ?1 AND physical = ?2 AND dropped = 0",
+            params![ds, physical],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn mark_column_dropped(conn: &Connection, ds: &str, column_id: &str) -> DatasetResult<()> {
+    conn.execute(
+        "UPDATE _dataset_repl_columns SET dropped = 1, physical = NULL WHERE dataset_id = ?1 AND column_id = ?2",
+        params![ds, column_id],
+    )?;
+    conn.execute(
+        "DELETE FROM _dataset_repl_cells WHERE dataset_id = ?1 AND column_id = ?2",
+        params![ds, column_id],
+    )?;
+    Ok(())
+}
+
+fn row_id_for_ref(conn: &Connection, ds: &str, rowid: i64) -> DatasetResult<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT row_id FROM _dataset_repl_rows WHERE dataset_id = ?1 AND row_ref = ?2 AND deleted = 0",
+            params![ds, rowid],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn tombstone_row(conn: &Connection, ds: &str, row_id: &str) -> DatasetResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO _dataset_repl_rows (dataset_id, row_id, row_ref, deleted) VALUES (?1, ?2, NULL, 1)",
+        params![ds, row_id],
+    )?;
+    conn.execute(
+        "DELETE FROM _dataset_repl_cells WHERE dataset_id = ?1 AND row_id = ?2",
+        params![ds, row_id],
+    )?;
+    Ok(())
+}
+
+fn set_cell_stamp(conn: &Connection, ds: &str, row_id: &str, column_id: &str, stamp: &OpStamp) -> DatasetResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO _dataset_repl_cells (dataset_id, row_id, column_id, stamp) VALUES (?1, ?2, ?3, ?4)",
+        params![ds, row_id, column_id, stamp.encode()],
+    )?;
+    Ok(())
+}
+
+// -- Table access --
+
+fn temp_column_name(column_id: &str) -> String {
+    format!("__repl_{column_id}")
+}
+
+pub(crate) fn table_exists(conn: &Connection, table: &str) -> DatasetResult<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![table],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Maps a `ds_<uuid>` table name back to its dataset ID.
+pub(crate) fn dataset_id_for_table(table: &str) -> Option<DatasetId> {
+    let hex = table.trim_matches('"').strip_prefix("ds_")?;
+    Uuid::parse_str(hex).ok().map(DatasetId)
+}
+
+/// Column names with their declared SQLite types, in table order.
+pub(crate) fn declared_columns(conn: &Connection, table: &str) -> DatasetResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info('{table}')"))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(columns)
+}
+
+/// Maps a replicated column type to a plain SQLite type name.
+fn sql_type(column_type: &str) -> &'static str {
+    use crate::types::DatasetColumnType;
+    match DatasetColumnType::from_sqlite(column_type) {
+        DatasetColumnType::Integer | DatasetColumnType::Boolean => "INTEGER",
+        DatasetColumnType::Float => "REAL",
+        DatasetColumnType::Blob => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+fn read_cells(
+    conn: &Connection,
+    table: &str,
+    rowid: i64,
+    columns: &[ReplColumn],
+) -> DatasetResult<BTreeMap<String, serde_json::Value>> {
+    let select: Vec<String> = columns
+        .iter()
+        .map(|c| format!("\"{}\"", sanitize_identifier(&c.physical)))
+        .collect();
+    let values = conn.query_row(
+        &format!("SELECT {} FROM {table} WHERE rowid = ?1", select.join(", ")),
+        params![rowid],
+        |row| Ok((0..columns.len()).map(|i| row_value_to_json(row, i)).collect::<Vec<_>>()),
+    )?;
+    Ok(columns.iter().map(|c| c.column_id.clone()).zip(values).collect())
+}
+
+/// Cell values by rowid, in column order.
+type TableRows = BTreeMap<i64, Vec<serde_json::Value>>;
+
+/// Reads a whole table: its column names and its rows.
+fn read_table(conn: &Connection, table: &str) -> DatasetResult<(Vec<String>, TableRows)> {
+    let columns: Vec<String> = declared_columns(conn, table)?.into_iter().map(|(n, _)| n).collect();
+    let mut select = vec!["rowid".to_string()];
+    select.extend(columns.iter().map(|c| format!("\"{}\"", sanitize_identifier(c))));
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM {table}", select.join(", ")))?;
+    let rows = stmt
+        .query_map([], |row| {
+            let values = (1..=columns.len()).map(|i| row_value_to_json(row, i)).collect();
+            Ok((row.get::<_, i64>(0)?, values))
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok((columns, rows))
+}
+
+pub(crate) fn json_to_sql(value: &serde_json::Value) -> SqlValue {
+    match value {
+        serde_json::Value::Null => SqlValue::Null,
+        serde_json::Value::Bool(b) => SqlValue::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
@@ -0,0 +1,331 @@
+//! SQL sandbox for statements supplied by the user.
+//!
+//! While a [`Sandbox`] is held, the connection's authorizer only lets
+//! statements read (and, for mutations, write) dataset tables and call the
+//! functions in [`ALLOWED_FUNCTIONS`]. Entity tables loaded for the
+//! statement may be read, never written. The triggers that track rows for
+//! constraint checks may do what they need. Everything else — other tables, the
+//! schema, `PRAGMA`, `ATTACH`, transactions, DDL beyond `ALTER TABLE` on a
+//! dataset — is refused when the statement is prepared. A progress handler
+//! interrupts statements that run past the time limit.
+//!
+//! The authorizer also notes which dataset tables a statement reads and
+//! writes, so callers learn a statement's targets from SQLite rather than
+//! from its text.
+//!
+//! An `ALTER TABLE` rewrites the schema through statements SQLite runs on
+//! its own behalf; once the alter itself is allowed, so are their schema
+//! table accesses and [`SCHEMA_FUNCTIONS`].
+
+use super::constraints::is_change_trigger;
+use super::entities::is_entity_table;
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::SqlLimits;
+use privstack_db::rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use privstack_db::rusqlite::{Connection, ErrorCode};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Number of VM instructions between time limit checks.
+const PROGRESS_INTERVAL: i32 = 1000;
+
+/// Functions sandboxed statements may call: SQLite's core, date, math,
+/// aggregate, window and JSON functions, minus anything that touches the
+/// filesystem, extensions or internals, or allocates unbounded memory, plus
+/// the store's `regexp`.
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    // Core scalar functions
+    "abs", "char", "coalesce", "concat", "concat_ws", "format", "glob", "hex", "ifnull", "iif",
+    "instr", "length", "like", "likelihood", "likely", "lower", "ltrim", "max", "min", "nullif",
+    "octet_length", "printf", "quote", "random", "replace", "round", "rtrim", "sign", "substr",
+    "substring", "trim", "typeof", "unhex", "unicode", "unlikely", "upper",
+    // Aggregates
+    "avg", "count", "group_concat", "string_agg", "sum", "total",
+    // Window functions
+    "row_number", "rank", "dense_rank", "percent_rank", "cume_dist", "ntile", "lag", "lead",
+    "first_value", "last_value", "nth_value",
+    // Date and time
+    "date", "time", "datetime", "julianday", "unixepoch", "strftime", "timediff",
+    // Math
+    "acos", "acosh", "asin", "asinh", "atan", "atan2", "atanh", "ceil", "ceiling", "cos", "cosh",
+    "degrees", "exp", "floor", "ln", "log", "log10", "log2", "mod", "pi", "pow", "power",
+    "radians", "sin", "sinh", "sqrt", "tan", "tanh", "trunc",
+    // JSON
+    "json", "json_array", "json_array_length", "json_extract", "json_group_array",
+    "json_group_object", "json_insert", "json_object", "json_patch", "json_quote", "json_remove",
+    "json_replace", "json_set", "json_type", "json_valid", "->", "->>",
+    // Registered by the store
+    "regexp",
+];
+
+/// Internal functions SQLite calls while it rewrites the schema for an
+/// `ALTER TABLE`.
+const SCHEMA_FUNCTIONS: &[&str] = &[
+    "sqlite_drop_column",
+    "sqlite_rename_column",
+    "sqlite_rename_quotefix",
+    "sqlite_rename_table",
+    "sqlite_rename_test",
+];
+
+/// What a sandboxed statement may do to dataset tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SqlAccess {
+    /// Read dataset tables only.
+    Read,
+    /// Also insert, update and delete rows, and alter dataset tables.
+    Write,
+}
+
+/// Why the sandbox stopped a statement, for error reporting.
+#[derive(Default)]
+struct Verdict {
+    denied: Option<String>,
+    timed_out: bool,
+}
+
+/// Dataset tables the statements run in a sandbox touched, in the order
+/// SQLite first reported them. Writes made by triggers aren't counted;
+/// reads are, whatever view or CTE they're made through.
+#[derive(Default)]
+struct Touched {
+    read: Vec<String>,
+    written: Vec<String>,
+}
+
+impl Touched {
+    fn note(&mut self, ctx: &AuthContext<'_>) {
+        let in_main = ctx.accessor.is_none() && matches!(ctx.database_name, None | Some("main"));
+        let (tables, table_name) = match ctx.action {
+            AuthAction::Read { table_name, .. } if ctx.database_name == Some("main") => {
+                (&mut self.read, table_name)
+            }
+            AuthAction::Insert { table_name }
+            | AuthAction::Delete { table_name }
+            | AuthAction::Update { table_name, .. }
+                if in_main =>
+            {
+                (&mut self.written, table_name)
+            }
+            AuthAction::AlterTable { database_name: "main", table_name }
+                if ctx.accessor.is_none() =>
+            {
+                (&mut self.written, table_name)
+            }
+            _ => return,
+        };
+        if is_dataset_table(table_name) && !tables.iter().any(|t| t == table_name) {
+            tables.push(table_name.to_string());
+        }
+    }
+}
+
+/// Sandbox installed on a connection; removed again when dropped.
+pub(crate) struct Sandbox<'c> {
+    conn: &'c Connection,
+    verdict: Arc<Mutex<Verdict>>,
+    touched: Arc<Mutex<Touched>>,
+    limits: SqlLimits,
+}
+
+impl<'c> Sandbox<'c> {
+    /// Installs the authorizer and time limit on `conn`. The time limit
+    /// counts from now, so it covers every statement run in the sandbox.
+    pub(crate) fn enter(
+        conn: &'c Connection,
+        access: SqlAccess,
+        limits: SqlLimits,
+    ) -> DatasetResult<Self> {
+        let policy = Policy {
+            access,
+            stored_tables: stored_tables(conn)?,
+        };
+        let verdict = Arc::new(Mutex::new(Verdict::default()));
+        let touched = Arc::new(Mutex::new(Touched::default()));
+
+        let auth_verdict = Arc::clone(&verdict);
+        let auth_touched = Arc::clone(&touched);
+        let mut altering = false;
+        conn.authorizer(Some(move |ctx: AuthContext<'_>| {
+            let decision = if altering && is_schema_rewrite(&ctx) {
+                Ok(())
+            } else {
+                policy.authorize(&ctx)
+            };
+            match decision {
+                Ok(()) => {
+                    altering |= matches!(ctx.action, AuthAction::AlterTable { .. });
+                    auth_touched.lock().unwrap_or_else(|p| p.into_inner()).note(&ctx);
+                    Authorization::Allow
+                }
+                Err(reason) => {
+                    let mut verdict = auth_verdict.lock().unwrap_or_else(|p| p.into_inner());
+                    verdict.denied.get_or_insert(reason);
+                    Authorization::Deny
+                }
+            }
+        }));
+
+        let deadline = Instant::now() + limits.timeout;
+        let progress_verdict = Arc::clone(&verdict);
+        conn.progress_handler(
+            PROGRESS_INTERVAL,
+            Some(move || {
+                if Instant::now() < deadline {
+                    return false;
+                }
+                progress_verdict.lock().unwrap_or_else(|p| p.into_inner()).timed_out = true;
+                true
+            }),
+        );
+
+        Ok(Self { conn, verdict, touched, limits })
+    }
+
+    /// Dataset tables the statements prepared so far read.
+    pub(crate) fn read_tables(&self) -> Vec<String> {
+        self.touched.lock().unwrap_or_else(|p| p.into_inner()).read.clone()
+    }
+
+    /// Dataset tables the statements prepared so far insert into, update,
+    /// delete from or alter.
+    pub(crate) fn written_tables(&self) -> Vec<String> {
+        self.touched.lock().unwrap_or_else(|p| p.into_inner()).written.clone()
+    }
+
+    /// Maps an error from a sandboxed statement to the reason the sandbox
+    /// stopped it, if it did. SQLite reports some denials (such as a
+    /// refused function) as plain errors, so any error after a denial is
+    /// put down to it.
+    pub(crate) fn explain(&self, err: DatasetError) -> DatasetError {
+        let DatasetError::Sqlite(sqlite_err) = &err else {
+            return err;
+        };
+        let verdict = self.verdict.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(reason) = &verdict.denied {
+            return DatasetError::NotAuthorized(reason.clone());
+        }
+        match sqlite_err.sqlite_error_code() {
+            Some(ErrorCode::OperationInterrupted) if verdict.timed_out => {
+                DatasetError::QueryTimeout(self.limits.timeout.as_millis() as u64)
+            }
+            _ => err,
+        }
+    }
+}
+
+impl Drop for Sandbox<'_> {
+    fn drop(&mut self) {
+        self.conn
+            .authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        self.conn.progress_handler(0, None::<fn() -> bool>);
+    }
+}
+
+/// What the authorizer checks statements against.
+struct Policy {
+    access: SqlAccess,
+    /// Lowercased names of every table and view in the database. A read of
+    /// any other name is a read of a CTE.
+    stored_tables: HashSet<String>,
+}
+
+impl Policy {
+    /// Decides whether a sandboxed statement may perform an action.
+    fn authorize(&self, ctx: &AuthContext<'_>) -> Result<(), String> {
+        let writable = self.access == SqlAccess::Write;
+        if ctx.accessor.is_some_and(is_change_trigger) {
+            return Ok(());
+        }
+        match ctx.action {
+            AuthAction::Select | AuthAction::Recursive => Ok(()),
+            AuthAction::Read { table_name, .. } => {
+                let is_cte = !self.stored_tables.contains(&table_name.to_ascii_lowercase());
+                let in_temp = matches!(ctx.database_name, None | Some("temp"));
+                if is_cte || (in_temp && is_entity_table(table_name)) {
+                    Ok(())
+                } else {
+                    dataset_table(ctx, table_name)
+                }
+            }
+            AuthAction::Insert { table_name }
+            | AuthAction::Delete { table_name }
+            | AuthAction::Update { table_name, .. }
+                if writable =>
+            {
+                dataset_table(ctx, table_name)
+            }
+            AuthAction::AlterTable { database_name, table_name } if writable => {
+                if database_name == "main" && is_dataset_table(table_name) {
+                    Ok(())
+                } else {
+                    Err(format!("table '{table_name}' is not a dataset"))
+                }
+            }
+            AuthAction::Function { function_name } => {
+                let name = function_name.to_ascii_lowercase();
+                if ALLOWED_FUNCTIONS.contains(&name.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("function '{function_name}' is not allowed"))
+                }
+            }
+            AuthAction::Insert { .. } | AuthAction::Delete { .. } | AuthAction::Update { .. } => {
+                Err("statement modifies data; only read-only queries are allowed here".to_string())
+            }
+            AuthAction::Pragma { pragma_name, .. } => Err(format!("PRAGMA {pragma_name} is not allowed")),
+            AuthAction::Attach { .. } => Err("ATTACH is not allowed".to_string()),
+            AuthAction::Detach { .. } => Err("DETACH is not allowed".to_string()),
+            AuthAction::Transaction { .. } | AuthAction::Savepoint { .. } => {
+                Err("transaction control is not allowed".to_string())
+            }
+            other => Err(format!("{other:?} is not allowed")),
+        }
+    }
+}
+
+/// Whether an action is part of SQLite rewriting the schema for an allowed
+/// `ALTER TABLE`.
+fn is_schema_rewrite(ctx: &AuthContext<'_>) -> bool {
+    match ctx.action {
+        AuthAction::Read { table_name, .. } | AuthAction::Update { table_name, .. } => {
+            matches!(table_name, "sqlite_master" | "sqlite_temp_master" | "sqlite_schema")
+        }
+        AuthAction::Function { function_name } => SCHEMA_FUNCTIONS.contains(&function_name),
+        _ => false,
+    }
+}
+
+fn dataset_table(ctx: &AuthContext<'_>, table_name: &str) -> Result<(), String> {
+    let in_main = matches!(ctx.database_name, None | Some("main"));
+    if in_main && is_dataset_table(table_name) {
+        Ok(())
+    } else if is_entity_table(table_name) {
+        Err("entity tables are read-only".to_string())
+    } else {
+        Err(format!("table '{table_name}' is not a dataset"))
+    }
+}
+
+/// Lowercased names of the tables and views in the database, including
+/// SQLite's schema tables.
+fn stored_tables(conn: &Connection) -> DatasetResult<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT lower(name) FROM sqlite_master WHERE type IN ('table', 'view') \
+         UNION SELECT lower(name) FROM sqlite_temp_master WHERE type IN ('table', 'view')",
+    )?;
+    let mut names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<HashSet<_>, _>>()?;
+    for schema_table in ["sqlite_master", "sqlite_schema", "sqlite_temp_master", "sqlite_temp_schema"] {
+        names.insert(schema_table.to_string());
+    }
+    Ok(names)
+}
+
+/// Whether `name` is a dataset's data table (`ds_<uuid>`).
+pub(crate) fn is_dataset_table(name: &str) -> bool {
+    name.strip_prefix("ds_")
+        .is_some_and(|id| id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
@@ -1,10 +1,12 @@
 //! Saved query CRUD operations.
 
 use super::helpers::now_millis;
+use super::materialized::drop_materialization;
+use super::preprocessor::preprocess_sql;
 use super::DatasetStore;
-use crate::error::DatasetResult;
-use crate::types::SavedQuery;
-use privstack_db::rusqlite::params;
+use crate::error::{DatasetError, DatasetResult};
+use crate::types::{PreprocessedSql, SavedQuery, StatementType};
+use privstack_db::rusqlite::{params, OptionalExtension};
 use uuid::Uuid;
 
 impl DatasetStore {
@@ -31,10 +33,11 @@ impl DatasetStore {
             is_view,
             created_at: now,
             modified_at: now,
+            materialized: None,
         })
     }
 
-    /// Update a saved query.
+    /// Update a saved query. Its materialized rows, if any, go stale.
     pub fn update_saved_query(
         &self,
         query_id: &str,
@@ -49,12 +52,17 @@ impl DatasetStore {
             "UPDATE _dataset_saved_queries SET name = ?1, sql = ?2, description = ?3, is_view = ?4, modified_at = ?5 WHERE id = ?6",
             params![name, sql, description, is_view as i32, now, query_id],
         )?;
+        conn.execute(
+            "UPDATE _dataset_materializations SET stale = 1 WHERE query_id = ?1",
+            params![query_id],
+        )?;
         Ok(())
     }
 
-    /// Delete a saved query.
+    /// Delete a saved query, with its materialized rows.
     pub fn delete_saved_query(&self, query_id: &str) -> DatasetResult<()> {
         let conn = self.lock_conn();
+        drop_materialization(&conn, query_id)?;
         conn.execute(
             "DELETE FROM _dataset_saved_queries WHERE id = ?1",
             params![query_id],
@@ -66,7 +74,7 @@ impl DatasetStore {
     pub fn list_saved_queries(&self) -> DatasetResult<Vec<SavedQuery>> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, name, sql, description, COALESCE(is_view, 0), created_at, modified_at FROM _dataset_saved_queries ORDER BY name",
+            "SELECT id, name, sql, description, COALESCE(is_view, 0), created_at, modified_at, materialized_json FROM _dataset_saved_queries ORDER BY name",
         )?;
         let rows = stmt
             .query_map([], |row| {
@@ -78,10 +86,11 @@ impl DatasetStore {
                     row.get::<_, i32>(4)?,
                     row.get::<_, i64>(5)?,
                     row.get::<_, i64>(6)?,
+                    row.get::<_, Option<String>>(7)?,
                 ))
             })?
             .filter_map(|r| r.ok())
-            .map(|(id, name, sql, desc, is_view, created, modified)| SavedQuery {
+            .map(|(id, name, sql, desc, is_view, created, modified, materialized)| SavedQuery {
                 id,
                 name,
                 sql,
@@ -89,8 +98,42 @@ impl DatasetStore {
                 is_view: is_view != 0,
                 created_at: created,
                 modified_at: modified,
+                materialized: materialized.and_then(|json| serde_json::from_str(&json).ok()),
             })
             .collect();
         Ok(rows)
     }
+
+    /// The SQL of a saved query, as written.
+    pub(crate) fn saved_query_sql(&self, query_id: &str) -> DatasetResult<String> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            "SELECT sql FROM _dataset_saved_queries WHERE id = ?1",
+            params![query_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| DatasetError::NotFound(format!("saved query {query_id}")))
+    }
+
+    /// The SQL of a saved query, with `source:` aliases resolved, for
+    /// running outside the SQL console: only SELECT queries that read no
+    /// entity tables can be `purpose` (e.g. "exported").
+    pub(crate) fn saved_query_select(&self, query_id: &str, purpose: &str) -> DatasetResult<PreprocessedSql> {
+        let sql = self.saved_query_sql(query_id)?;
+        let cleaned = sql.trim().trim_end_matches(';').trim();
+        let datasets = self.list()?;
+        let preprocessed = preprocess_sql(cleaned, |name| {
+            datasets.iter().find(|d| d.name == name).map(|d| d.id.clone())
+        })?;
+        if preprocessed.statement_type != StatementType::Select {
+            return Err(DatasetError::InvalidQuery(format!("Only SELECT queries can be {purpose}")));
+        }
+        if !preprocessed.referenced_entities.is_empty() {
+            return Err(DatasetError::InvalidQuery(format!(
+                "Queries over entity: tables can't be {purpose}"
+            )));
+        }
+        Ok(preprocessed)
+    }
 }
@@ -1,14 +1,17 @@
 //! Saved view CRUD operations.
 
+use super::filters::compile_filter;
+use super::formulas::dataset_columns;
 use super::helpers::now_millis;
 use super::DatasetStore;
 use crate::error::DatasetResult;
 use crate::types::{DatasetId, DatasetView, ViewConfig};
-use privstack_db::rusqlite::params;
+use privstack_db::rusqlite::{params, Connection};
 use uuid::Uuid;
 
 impl DatasetStore {
-    /// Create a saved view for a dataset.
+    /// Create a saved view for a dataset. Fails if its filters don't fit
+    /// the dataset's columns.
     pub fn create_view(
         &self,
         dataset_id: &DatasetId,
@@ -19,6 +22,7 @@ impl DatasetStore {
         let now = now_millis();
         let config_json = serde_json::to_string(config)?;
         let conn = self.lock_conn();
+        check_filters(&conn, dataset_id, config)?;
         conn.execute(
             "INSERT INTO _dataset_views (id, dataset_id, name, config_json, is_default, sort_order, created_at, modified_at) VALUES (?1, ?2, ?3, ?4, 0, 0, ?5, ?6)",
             params![id, dataset_id.to_string(), name, config_json, now, now],
@@ -35,11 +39,14 @@ impl DatasetStore {
         })
     }
 
-    /// Update a view's configuration.
+    /// Update a view's configuration. Fails if its filters don't fit the
+    /// dataset's columns.
     pub fn update_view(&self, view_id: &str, config: &ViewConfig) -> DatasetResult<()> {
+        let (dataset_id, _) = self.view_config(view_id)?;
         let now = now_millis();
         let config_json = serde_json::to_string(config)?;
         let conn = self.lock_conn();
+        check_filters(&conn, &dataset_id, config)?;
         conn.execute(
             "UPDATE _dataset_views SET config_json = ?1, modified_at = ?2 WHERE id = ?3",
             params![config_json, now, view_id],
@@ -87,6 +94,8 @@ impl DatasetStore {
                             filters: vec![],
                             sorts: vec![],
                             group_by: None,
+                            pivot: None,
+                            filter: None,
                         });
                     DatasetView {
                         id,
@@ -104,3 +113,12 @@ impl DatasetStore {
         Ok(rows)
     }
 }
+
+/// Compiles a view's filters against the dataset's columns, so a view
+/// that can't run is refused when it's saved.
+fn check_filters(conn: &Connection, dataset_id: &DatasetId, config: &ViewConfig) -> DatasetResult<()> {
+    if let Some(filter) = config.filter_tree() {
+        compile_filter(&dataset_columns(conn, dataset_id)?, &filter, &mut Vec::new())?;
+    }
+    Ok(())
+}
@@ -1,6 +1,9 @@
 //! Core data types for the datasets module.
 
+use crate::error::DatasetError;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
 /// Strongly-typed dataset identifier (NewType pattern).
@@ -107,6 +110,9 @@ pub struct DatasetQueryResult {
     pub total_count: i64,
     pub page: i64,
     pub page_size: i64,
+    /// Set when the rows were read from a saved query's materialized cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialization: Option<Materialization>,
 }
 
 // -- Phase 5: Relations --
@@ -174,16 +180,40 @@ pub struct DatasetView {
     pub modified_at: i64,
 }
 
-/// View configuration: column visibility, filters, sorts, grouping.
+/// View configuration: column visibility, filters, sorts, grouping, and
+/// optionally a pivot table over the filtered rows.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ViewConfig {
     pub visible_columns: Option<Vec<String>>,
+    /// Conditions rows must all pass; AND-ed with `filter`.
     pub filters: Vec<ViewFilter>,
     pub sorts: Vec<ViewSort>,
     pub group_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pivot: Option<PivotConfig>,
+    /// Further conditions as a tree, for OR, NOT and full-text search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<FilterNode>,
 }
 
-/// Filter operator for view filters.
+impl ViewConfig {
+    /// All of the view's filters as one tree, if it has any.
+    pub fn filter_tree(&self) -> Option<FilterNode> {
+        FilterNode::all(
+            self.filters
+                .iter()
+                .cloned()
+                .map(FilterNode::Condition)
+                .chain(self.filter.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Filter operator for view filters. Comparisons follow the column's type:
+/// numbers compare as numbers, and dates and timestamps as points in time.
+/// Text comparisons (`contains`, `starts_with`, `ends_with`) ignore ASCII
+/// case.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum FilterOperator {
@@ -194,14 +224,65 @@ pub enum FilterOperator {
     LessThan,
     IsEmpty,
     IsNotEmpty,
+    GreaterOrEqual,
+    LessOrEqual,
+    /// Equal to one of the values of an array.
+    In,
+    NotIn,
+    /// Between the two values of a `[low, high]` array, inclusive.
+    Between,
+    StartsWith,
+    EndsWith,
+    /// Matches a regular expression.
+    Matches,
+    /// Dates and timestamps before the given one.
+    Before,
+    /// Dates and timestamps after the given one.
+    After,
+    /// Dates from a period ago up to today (UTC), e.g. `"30 days"`.
+    /// Periods are counted in days, weeks, months or years.
+    InLast,
+    /// Dates from today (UTC) up to a period ahead, e.g. `"2 weeks"`.
+    InNext,
 }
 
-/// A single filter condition in a view.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A single filter condition in a view. The value is usually a string or
+/// number; `in`, `not_in` and `between` take an array, and `is_empty` and
+/// `is_not_empty` none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ViewFilter {
     pub column: String,
     pub operator: FilterOperator,
-    pub value: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+/// A tree of filter conditions, e.g.
+/// `{"or": [{"condition": {...}}, {"not": {"search": "draft"}}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterNode {
+    /// Rows passing every filter; an empty group passes all rows.
+    And(Vec<FilterNode>),
+    /// Rows passing any filter; an empty group passes none.
+    Or(Vec<FilterNode>),
+    Not(Box<FilterNode>),
+    Condition(ViewFilter),
+    /// Full-text search: rows where each word of the text appears in one
+    /// of the dataset's text columns, ignoring ASCII case.
+    Search(String),
+}
+
+impl FilterNode {
+    /// Rows passing all of `nodes`: `None` when there are none, the node
+    /// itself when there's one.
+    pub fn all(mut nodes: Vec<FilterNode>) -> Option<FilterNode> {
+        match nodes.len() {
+            0 => None,
+            1 => nodes.pop(),
+            _ => Some(FilterNode::And(nodes)),
+        }
+    }
 }
 
 /// Sort direction.
@@ -219,6 +300,115 @@ pub struct ViewSort {
     pub direction: SortDirection,
 }
 
+// -- Pivot tables --
+
+/// How a pivot measure aggregates its column. Sums, averages, medians and
+/// percentiles skip values that aren't numbers; every aggregation skips
+/// NULLs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PivotAggregation {
+    Count,
+    CountDistinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Median,
+    /// The given percentile, from 0 to 100, interpolated between values.
+    Percentile(f64),
+}
+
+/// A value computed for each cell of a pivot table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotMeasure {
+    /// Column to aggregate; `*` counts rows.
+    pub column: String,
+    pub aggregation: PivotAggregation,
+    /// Display name; defaults to e.g. `sum(amount)`.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Period a date dimension is bucketed into. Weeks start on Monday and are
+/// keyed by that day; months read `2024-03`, quarters `2024-Q1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateBucket {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+/// A column a pivot table groups by, along its rows or its columns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotDimension {
+    pub column: String,
+    /// Buckets a `Date` or `Timestamp` column by period.
+    #[serde(default)]
+    pub bucket: Option<DateBucket>,
+    /// Keeps the values with the largest first measure and puts the rest
+    /// in one `"Other"` bucket.
+    #[serde(default)]
+    pub top_n: Option<usize>,
+}
+
+/// A pivot table over a dataset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotConfig {
+    #[serde(default)]
+    pub rows: Vec<PivotDimension>,
+    #[serde(default)]
+    pub columns: Vec<PivotDimension>,
+    pub measures: Vec<PivotMeasure>,
+    /// Adds subtotal rows, a grand total row and a total per row across
+    /// the column keys.
+    #[serde(default)]
+    pub totals: bool,
+}
+
+/// What a pivot table row holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PivotRowKind {
+    Data,
+    /// Totals over the rows that share its leading keys; the other keys
+    /// are NULL.
+    Subtotal,
+    /// Totals over all rows; every key is NULL.
+    Total,
+}
+
+/// One row of a pivot table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotRow {
+    /// One value per row dimension.
+    pub keys: Vec<serde_json::Value>,
+    pub kind: PivotRowKind,
+    /// Per column key, one value per measure.
+    pub cells: Vec<Vec<serde_json::Value>>,
+    /// Per measure, the total across all column keys, with
+    /// [`PivotConfig::totals`] and column dimensions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<Vec<serde_json::Value>>,
+}
+
+/// Result of a pivot query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotResult {
+    /// Names of the row dimensions.
+    pub row_dimensions: Vec<String>,
+    /// Names of the column dimensions.
+    pub column_dimensions: Vec<String>,
+    /// Each combination of column dimension values, in cell order.
+    pub column_keys: Vec<Vec<serde_json::Value>>,
+    /// Measure labels, in the order of each cell's values.
+    pub measures: Vec<String>,
+    pub rows: Vec<PivotRow>,
+}
+
 // -- Phase 10: Saved Queries --
 
 /// A user-authored SQL query saved for reuse.
@@ -233,6 +423,38 @@ pub struct SavedQuery {
     pub is_view: bool,
     pub created_at: i64,
     pub modified_at: i64,
+    /// Set when the query's rows are materialized: when the cache is
+    /// rebuilt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialized: Option<RefreshPolicy>,
+}
+
+/// When a materialized saved query's cached rows are rebuilt. A cache goes
+/// stale when a dataset the query reads changes, or the query itself does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshPolicy {
+    /// Only through `refresh_saved_query`; stale rows are served as they
+    /// are, flagged stale.
+    OnDemand,
+    /// When the query is opened while its rows are stale.
+    OnChange,
+    /// Once the rows are older than the interval, whenever the host calls
+    /// `refresh_due_saved_queries`.
+    Every { interval_secs: u64 },
+}
+
+/// State of a saved query's materialized rows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Materialization {
+    pub refresh: RefreshPolicy,
+    /// When the rows were last rebuilt, in milliseconds since the epoch.
+    pub refreshed_at: i64,
+    /// Whether a dataset the query reads, or the query, changed since.
+    pub stale: bool,
+    /// Datasets the query reads.
+    pub sources: Vec<DatasetId>,
+    pub row_count: i64,
 }
 
 // -- Mutations & SQL v2 --
@@ -261,12 +483,15 @@ pub enum SqlExecutionResult {
     Mutation(MutationResult),
 }
 
-/// Preprocessed SQL with resolved `source:` aliases.
+/// Preprocessed SQL with resolved `source:` and `entity:` aliases.
 #[derive(Debug, Clone)]
 pub struct PreprocessedSql {
     pub sql: String,
     pub statement_type: StatementType,
     pub referenced_datasets: Vec<(String, DatasetId)>,
+    /// Entity types the SQL reads, with the temporary table each one was
+    /// resolved to.
+    pub referenced_entities: Vec<(String, String)>,
 }
 
 /// SQL statement classification.
@@ -281,3 +506,389 @@ pub enum StatementType {
     AlterTable,
     Other,
 }
+
+// -- Formula columns --
+
+/// A computed column of a dataset. Its expression is evaluated whenever the
+/// dataset is read, so queries, views and aggregates see it like a stored
+/// column. See the expression language in `store/expression.rs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormulaColumn {
+    pub name: String,
+    pub expression: String,
+    /// Type of the computed values, inferred from the expression.
+    pub column_type: DatasetColumnType,
+}
+
+// -- Column constraints --
+
+/// Rules on the values of a stored column, checked whenever rows are
+/// written. Missing values (NULL or empty text) only break `not_null`; the
+/// other rules apply to values that are present.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnConstraints {
+    pub column: String,
+    #[serde(default)]
+    pub not_null: bool,
+    #[serde(default)]
+    pub unique: bool,
+    /// Values must have the column's type: integers in an Integer column,
+    /// numbers in a Float column, and so on.
+    #[serde(default)]
+    pub strict_type: bool,
+    /// The values allowed, compared as text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    /// A regular expression values must match; anchor it to match the
+    /// whole value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Values must exist in the target column of each relation declared
+    /// from this column.
+    #[serde(default)]
+    pub foreign_key: bool,
+}
+
+impl ColumnConstraints {
+    /// Whether the constraints set no rule at all.
+    pub fn is_empty(&self) -> bool {
+        !self.not_null
+            && !self.unique
+            && !self.strict_type
+            && self.options.is_none()
+            && self.pattern.is_none()
+            && self.min.is_none()
+            && self.max.is_none()
+            && !self.foreign_key
+    }
+}
+
+/// The rule a value broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintKind {
+    NotNull,
+    Unique,
+    StrictType,
+    Options,
+    Pattern,
+    Min,
+    Max,
+    ForeignKey,
+}
+
+/// A value that breaks a column constraint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstraintViolation {
+    /// Index of the row, as `update_cell` and `delete_rows` address it.
+    pub row_index: i64,
+    pub column: String,
+    pub value: serde_json::Value,
+    pub kind: ConstraintKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "row {}, column {}: {}", self.row_index, self.column, self.message)
+    }
+}
+
+/// The values of a dataset that break its column constraints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub dataset_id: DatasetId,
+    pub rows_checked: i64,
+    /// Violations in row order, at most [`ValidationReport::MAX_VIOLATIONS`].
+    pub violations: Vec<ConstraintViolation>,
+    /// Whether there were more violations than reported.
+    pub truncated: bool,
+}
+
+impl ValidationReport {
+    /// Most violations a report lists.
+    pub const MAX_VIOLATIONS: usize = 1000;
+}
+
+// -- Change history --
+
+/// Kind of a change history entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEntryKind {
+    /// An edit that can be undone and redone.
+    Edit,
+    /// A named point to restore the dataset to.
+    RestorePoint,
+}
+
+/// An entry in a dataset's change history, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub dataset_id: DatasetId,
+    pub kind: HistoryEntryKind,
+    /// What the edit did, or the restore point's name.
+    pub label: String,
+    /// Whether the edit is undone, and so can be redone.
+    pub undone: bool,
+    pub created_at: i64,
+}
+
+/// How much change history each dataset keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryLimits {
+    /// Most entries kept per dataset; the oldest go first.
+    pub max_entries: usize,
+    /// Entries older than this are pruned.
+    pub max_age: Duration,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 100,
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+// -- Entity tables --
+
+/// The rows of one entity type, as SQL sees them through `entity:<type>`.
+#[derive(Debug, Clone, Default)]
+pub struct EntityTable {
+    pub columns: Vec<ColumnDef>,
+    /// One value per column, in column order.
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+// -- Import & export --
+
+/// File format of a dataset import or export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Csv,
+    Tsv,
+    /// A JSON array of objects. Imports also accept newline-delimited JSON.
+    Json,
+    /// Newline-delimited JSON: one object per line.
+    Ndjson,
+    Xlsx,
+    Parquet,
+}
+
+impl FileFormat {
+    /// Guess the format from a file extension (`.csv`, `.jsonl`, ...).
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "xlsx" | "xlsm" => Some(Self::Xlsx),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Options for importing a file into a new dataset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// Format of the file; guessed from its extension when absent.
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Worksheet to import from an XLSX workbook; the first one when absent.
+    #[serde(default)]
+    pub sheet: Option<String>,
+    /// Constraints for the new dataset's columns. The import fails if any
+    /// imported value breaks them.
+    #[serde(default)]
+    pub constraints: Vec<ColumnConstraints>,
+}
+
+/// Progress of a running import, reported every few thousand rows.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub rows_imported: u64,
+    /// Bytes of the file read so far, for formats read front to back
+    /// (CSV, TSV, JSON).
+    pub bytes_read: Option<u64>,
+    pub total_bytes: Option<u64>,
+    /// Rows in the file, for formats that record it (XLSX, Parquet).
+    pub total_rows: Option<u64>,
+}
+
+impl ImportProgress {
+    /// Fraction of the file imported so far, between 0 and 1, if known.
+    pub fn fraction(&self) -> Option<f64> {
+        let (done, total) = match (self.bytes_read, self.total_bytes, self.total_rows) {
+            (Some(read), Some(total), _) => (read, total),
+            (_, _, Some(total)) => (self.rows_imported, total),
+            _ => return None,
+        };
+        if total == 0 {
+            return Some(1.0);
+        }
+        Some((done as f64 / total as f64).min(1.0))
+    }
+}
+
+/// What to export: a whole dataset, a saved view of one, or the result of a
+/// saved query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportSource {
+    Dataset { dataset_id: DatasetId },
+    /// Exports the view's visible columns, filtered and sorted as it is.
+    View { view_id: String },
+    SavedQuery { query_id: String },
+}
+
+// -- SQL sandbox --
+
+/// Limits on SQL supplied by the user: raw queries, mutations and
+/// aggregations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqlLimits {
+    /// How long a query may run before it is interrupted.
+    pub timeout: Duration,
+    /// Most rows a query returns at once; larger pages are clamped.
+    pub max_rows: usize,
+    /// Most rows a materialized saved query may cache.
+    pub max_materialized_rows: usize,
+}
+
+impl Default for SqlLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            max_rows: 10_000,
+            max_materialized_rows: 100_000,
+        }
+    }
+}
+
+/// Aggregate function applied to the value column of a chart query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    /// The SQLite function implementing this aggregation.
+    pub fn sql_function(self) -> &'static str {
+        match self {
+            Self::Count => "COUNT",
+            Self::Sum => "SUM",
+            Self::Avg => "AVG",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+        }
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = DatasetError;
+
+    /// Parses an aggregation name, ignoring case (`"sum"`, `"SUM"`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "count" => Ok(Self::Count),
+            "sum" => Ok(Self::Sum),
+            "avg" => Ok(Self::Avg),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            _ => Err(DatasetError::InvalidQuery(format!("Unknown aggregation: {s}"))),
+        }
+    }
+}
+
+// -- Replication --
+
+/// Ordering key of a replicated change: hybrid-clock time, then the replica
+/// that made it. Of two concurrent changes to the same value, the later
+/// stamp wins on every device.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OpStamp {
+    pub wall_ms: u64,
+    pub logical: u32,
+    pub replica: String,
+}
+
+impl OpStamp {
+    pub fn new(wall_ms: u64, logical: u32, replica: impl Into<String>) -> Self {
+        Self {
+            wall_ms,
+            logical,
+            replica: replica.into(),
+        }
+    }
+
+    /// Text form stored with replicated state. Sorts like the stamp itself.
+    pub(crate) fn encode(&self) -> String {
+        format!("{:016x}{:08x}{}", self.wall_ms, self.logical, self.replica)
+    }
+}
+
+/// A replicated change to a dataset. Rows and columns are addressed by
+/// stable IDs, so changes still land after concurrent renames and deletes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DatasetOp {
+    /// Creates the dataset, or sets its name, category, formula columns and
+    /// column constraints.
+    SetMeta {
+        name: String,
+        category: Option<String>,
+        #[serde(default)]
+        formulas: Vec<FormulaColumn>,
+        #[serde(default)]
+        constraints: Vec<ColumnConstraints>,
+    },
+    /// Deletes the dataset. Final: later changes to it are ignored.
+    DeleteDataset,
+    AddColumn {
+        column_id: String,
+        name: String,
+        /// SQLite column type.
+        column_type: String,
+        default: Option<String>,
+    },
+    RenameColumn {
+        column_id: String,
+        name: String,
+    },
+    /// Drops a column. Final, like a row delete.
+    DropColumn { column_id: String },
+    /// Inserts a row or sets some of its cells, keyed by column ID.
+    UpsertRow {
+        row_id: String,
+        cells: std::collections::BTreeMap<String, serde_json::Value>,
+    },
+    /// Deletes a row. Deletes win over concurrent edits of the row.
+    DeleteRow { row_id: String },
+}
+
+/// A local change waiting to be published to other devices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetChange {
+    pub seq: i64,
+    pub dataset_id: DatasetId,
+    pub op: DatasetOp,
+    pub stamp: OpStamp,
+}
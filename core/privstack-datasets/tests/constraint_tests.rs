@@ -0,0 +1,262 @@
+//! Column constraints: rules checked on every write path, violations
+//! reported with their row and column, and reports on existing data.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+use std::io::Write;
+
+// -- Helpers --
+
+/// Products with a SKU, a category, a price and a stock count.
+fn products(store: &DatasetStore) -> DatasetMeta {
+    let meta = store
+        .create_empty(
+            "products",
+            &[
+                ColumnDef { name: "sku".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "category".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "price".into(), column_type: "REAL".into() },
+                ColumnDef { name: "stock".into(), column_type: "INTEGER".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (sku, category, price, stock) in [("AB-1", "pen", 2.5, 10), ("AB-2", "ink", 7.0, 3)] {
+        store
+            .insert_row(
+                &meta.id,
+                &[("sku", json!(sku)), ("category", json!(category)), ("price", json!(price)), ("stock", json!(stock))],
+            )
+            .unwrap();
+    }
+    meta
+}
+
+fn constraints(column: &str) -> ColumnConstraints {
+    ColumnConstraints { column: column.into(), ..ColumnConstraints::default() }
+}
+
+fn constrain(store: &DatasetStore, id: &DatasetId) {
+    for c in [
+        ColumnConstraints {
+            not_null: true,
+            unique: true,
+            pattern: Some("^[A-Z]{2}-[0-9]+$".into()),
+            ..constraints("sku")
+        },
+        ColumnConstraints { options: Some(vec!["pen".into(), "ink".into(), "book".into()]), ..constraints("category") },
+        ColumnConstraints { min: Some(0.0), max: Some(1000.0), ..constraints("price") },
+        ColumnConstraints { strict_type: true, ..constraints("stock") },
+    ] {
+        store.set_column_constraints(id, &c).unwrap();
+    }
+}
+
+/// Each violation's row, column and rule.
+fn broken(result: DatasetResult<impl std::fmt::Debug>) -> Vec<(i64, String, ConstraintKind)> {
+    match result {
+        Err(DatasetError::ConstraintViolated(violations)) => {
+            violations.into_iter().map(|v| (v.row_index, v.column, v.kind)).collect()
+        }
+        other => panic!("expected a constraint violation, got {other:?}"),
+    }
+}
+
+fn rows(store: &DatasetStore, id: &DatasetId) -> Vec<Vec<Value>> {
+    store.query_dataset(id, 0, 100, None, None, false).unwrap().rows
+}
+
+// -- Tests --
+
+#[test]
+fn inserts_and_edits_that_break_a_rule_change_nothing() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = products(&store);
+    constrain(&store, &meta.id);
+    let before = rows(&store, &meta.id);
+
+    let insert = |sku: Value, category: &str, price: Value, stock: Value| {
+        store.insert_row(
+            &meta.id,
+            &[("sku", sku), ("category", json!(category)), ("price", price), ("stock", stock)],
+        )
+    };
+    assert_eq!(
+        broken(insert(json!("AB-1"), "pen", json!(1.0), json!(1))),
+        vec![(2, "sku".to_string(), ConstraintKind::Unique)]
+    );
+    assert_eq!(
+        broken(insert(Value::Null, "pen", json!(1.0), json!(1))),
+        vec![(2, "sku".to_string(), ConstraintKind::NotNull)]
+    );
+    assert_eq!(
+        broken(insert(json!("ab-3"), "pencil", json!(-1.0), json!("lots"))),
+        vec![
+            (2, "sku".to_string(), ConstraintKind::Pattern),
+            (2, "category".to_string(), ConstraintKind::Options),
+            (2, "price".to_string(), ConstraintKind::Min),
+            (2, "stock".to_string(), ConstraintKind::StrictType),
+        ]
+    );
+    assert_eq!(
+        broken(store.update_cell(&meta.id, 1, "price", json!(5000))),
+        vec![(1, "price".to_string(), ConstraintKind::Max)]
+    );
+    assert_eq!(rows(&store, &meta.id), before);
+    assert_eq!(store.history(&meta.id).unwrap().len(), 2);
+
+    insert(json!("AB-3"), "book", json!(12.0), json!(4)).unwrap();
+    store.update_cell(&meta.id, 0, "stock", json!(9)).unwrap();
+    assert_eq!(store.get(&meta.id).unwrap().row_count, 3);
+
+    let message = insert(json!("AB-3"), "book", json!(1.0), json!(1)).unwrap_err().to_string();
+    assert_eq!(message, "Constraint violated: row 3, column sku: duplicates another row");
+}
+
+#[test]
+fn sql_mutations_are_checked_row_by_row() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = products(&store);
+    constrain(&store, &meta.id);
+    let before = rows(&store, &meta.id);
+
+    for dry_run in [true, false] {
+        let result = store.execute_sql_v2("UPDATE source:products SET category = 'toy' WHERE stock > 5", 0, 100, dry_run);
+        assert_eq!(broken(result), vec![(0, "category".to_string(), ConstraintKind::Options)]);
+    }
+    let result = store.execute_sql_v2(
+        "INSERT INTO source:products (sku, category, price, stock) SELECT sku, category, price, stock FROM source:products",
+        0,
+        100,
+        false,
+    );
+    assert_eq!(
+        broken(result),
+        vec![(2, "sku".to_string(), ConstraintKind::Unique), (3, "sku".to_string(), ConstraintKind::Unique)]
+    );
+    assert_eq!(rows(&store, &meta.id), before);
+
+    // Statements that keep to the rules go through, schema changes too.
+    store
+        .execute_sql_v2("UPDATE source:products SET price = price * 2", 0, 100, false)
+        .unwrap();
+    store.execute_sql_v2("DELETE FROM source:products WHERE sku = 'AB-2'", 0, 100, false).unwrap();
+    store.execute_sql_v2("ALTER TABLE source:products ADD COLUMN note TEXT", 0, 100, false).unwrap();
+    assert_eq!(
+        rows(&store, &meta.id),
+        vec![vec![json!("AB-1"), json!("pen"), json!(5.0), json!(10), Value::Null]]
+    );
+}
+
+#[test]
+fn foreign_keys_follow_declared_relations() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = products(&store);
+    let categories = store
+        .create_empty("categories", &[ColumnDef { name: "name".into(), column_type: "TEXT".into() }], None)
+        .unwrap();
+    for name in ["pen", "ink"] {
+        store.insert_row(&categories.id, &[("name", json!(name))]).unwrap();
+    }
+
+    let foreign_key = ColumnConstraints { foreign_key: true, ..constraints("category") };
+    assert!(matches!(
+        store.set_column_constraints(&meta.id, &foreign_key),
+        Err(DatasetError::InvalidQuery(_))
+    ));
+    store.create_relation(&meta.id, "category", &categories.id, "name").unwrap();
+    store.set_column_constraints(&meta.id, &foreign_key).unwrap();
+
+    let result = store.insert_row(&meta.id, &[("sku", json!("AB-3")), ("category", json!("book"))]);
+    assert_eq!(broken(result), vec![(2, "category".to_string(), ConstraintKind::ForeignKey)]);
+    store.insert_row(&meta.id, &[("sku", json!("AB-3")), ("category", json!("ink"))]).unwrap();
+    // Missing values are left to `not_null`.
+    store.insert_row(&meta.id, &[("sku", json!("AB-4"))]).unwrap();
+}
+
+#[test]
+fn imports_apply_their_constraints() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("people.csv");
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(b"email,age\nann@example.com,31\nbob,27\ncid@example.com,\n")
+        .unwrap();
+
+    let mut options = ImportOptions {
+        constraints: vec![
+            ColumnConstraints { pattern: Some("^[^@]+@[^@]+$".into()), ..constraints("email") },
+            ColumnConstraints { not_null: true, ..constraints("age") },
+        ],
+        ..ImportOptions::default()
+    };
+    assert_eq!(
+        broken(store.import_file(&path, "people", &options)),
+        vec![(1, "email".to_string(), ConstraintKind::Pattern), (2, "age".to_string(), ConstraintKind::NotNull)]
+    );
+    assert!(store.list().unwrap().is_empty());
+
+    options.constraints.truncate(1);
+    options.constraints[0].pattern = Some("^[^@]+(@[^@]+)?$".into());
+    let meta = store.import_file(&path, "people", &options).unwrap();
+    assert_eq!(store.list_column_constraints(&meta.id).unwrap(), options.constraints);
+}
+
+#[test]
+fn existing_data_is_validated_on_request() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = products(&store);
+    store.insert_row(&meta.id, &[("sku", json!("AB-1")), ("category", json!("toy")), ("price", json!(3.0))]).unwrap();
+
+    // Setting constraints doesn't check the rows already there.
+    constrain(&store, &meta.id);
+    let report = store.validate_dataset(&meta.id).unwrap();
+    assert_eq!(report.rows_checked, 3);
+    assert!(!report.truncated);
+    assert_eq!(
+        report.violations,
+        vec![
+            ConstraintViolation {
+                row_index: 2,
+                column: "sku".into(),
+                value: json!("AB-1"),
+                kind: ConstraintKind::Unique,
+                message: "duplicates another row".into(),
+            },
+            ConstraintViolation {
+                row_index: 2,
+                column: "category".into(),
+                value: json!("toy"),
+                kind: ConstraintKind::Options,
+                message: "must be one of: pen, ink, book".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn constraints_follow_their_column() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = products(&store);
+    constrain(&store, &meta.id);
+
+    store.rename_column(&meta.id, "sku", "code").unwrap();
+    store.drop_column(&meta.id, "price").unwrap();
+    let columns: Vec<String> =
+        store.list_column_constraints(&meta.id).unwrap().into_iter().map(|c| c.column).collect();
+    assert_eq!(columns, vec!["code", "category", "stock"]);
+    let result = store.insert_row(&meta.id, &[("code", json!("AB-1")), ("category", json!("pen"))]);
+    assert_eq!(broken(result), vec![(2, "code".to_string(), ConstraintKind::Unique)]);
+
+    // Constraints without rules are removed.
+    store.set_column_constraints(&meta.id, &constraints("code")).unwrap();
+    assert_eq!(store.list_column_constraints(&meta.id).unwrap().len(), 2);
+
+    let invalid = |c: ColumnConstraints| matches!(store.set_column_constraints(&meta.id, &c), Err(DatasetError::InvalidQuery(_)));
+    assert!(invalid(ColumnConstraints { not_null: true, ..constraints("missing") }));
+    assert!(invalid(ColumnConstraints { pattern: Some("(".into()), ..constraints("code") }));
+    assert!(invalid(ColumnConstraints { min: Some(5.0), max: Some(1.0), ..constraints("stock") }));
+    assert!(invalid(ColumnConstraints { options: Some(Vec::new()), ..constraints("category") }));
+}
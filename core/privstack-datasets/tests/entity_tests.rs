@@ -0,0 +1,132 @@
+//! Entity tables: `entity:type` aliases that join plugin entities with
+//! datasets, read-only and scoped by what the source lets the caller see.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+
+// -- Helpers --
+
+/// Tasks, some local-only, and notes the caller may not read.
+struct Tasks;
+
+impl EntitySource for Tasks {
+    fn entity_table(&self, entity_type: &str, include_local_only: bool) -> DatasetResult<EntityTable> {
+        match entity_type {
+            "task" => {
+                let tasks = [
+                    ("t1", "Design", "Website", false),
+                    ("t2", "Build", "Website", false),
+                    ("t3", "Draft", "Book", false),
+                    ("t4", "Secret", "Book", true),
+                ];
+                Ok(EntityTable {
+                    columns: vec![
+                        ColumnDef { name: "id".into(), column_type: "TEXT".into() },
+                        ColumnDef { name: "title".into(), column_type: "TEXT".into() },
+                        ColumnDef { name: "project".into(), column_type: "TEXT".into() },
+                    ],
+                    rows: tasks
+                        .into_iter()
+                        .filter(|(.., local_only)| include_local_only || !local_only)
+                        .map(|(id, title, project, _)| vec![json!(id), json!(title), json!(project)])
+                        .collect(),
+                })
+            }
+            "note" => Err(DatasetError::NotAuthorized(format!("cannot read {entity_type}"))),
+            other => Err(DatasetError::NotFound(format!("entity type {other}"))),
+        }
+    }
+}
+
+fn budget(store: &DatasetStore) -> DatasetMeta {
+    let meta = store
+        .create_empty(
+            "budget",
+            &[
+                ColumnDef { name: "project".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "amount".into(), column_type: "REAL".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (project, amount) in [("Website", 5000.0), ("Book", 1200.0)] {
+        store.insert_row(&meta.id, &[("project", json!(project)), ("amount", json!(amount))]).unwrap();
+    }
+    meta
+}
+
+fn query(store: &DatasetStore, sql: &str) -> DatasetResult<Vec<Vec<Value>>> {
+    match store.execute_sql_with_entities(sql, 0, 100, false, &Tasks)? {
+        SqlExecutionResult::Query(result) => Ok(result.rows),
+        other => panic!("expected a query result, got {other:?}"),
+    }
+}
+
+// -- Tests --
+
+#[test]
+fn entities_join_with_datasets() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    budget(&store);
+
+    let rows = query(
+        &store,
+        "SELECT b.project, COUNT(t.id) AS tasks, b.amount \
+         FROM source:budget b JOIN entity:task t ON t.project = b.project \
+         GROUP BY b.project ORDER BY b.project",
+    )
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            vec![json!("Book"), json!(2), json!(1200.0)],
+            vec![json!("Website"), json!(2), json!(5000.0)],
+        ]
+    );
+
+    // Each reference to a type reads the same table.
+    let rows = query(&store, "SELECT COUNT(*) FROM entity:task a JOIN entity:\"task\" b ON a.id = b.id").unwrap();
+    assert_eq!(rows, vec![vec![json!(4)]]);
+}
+
+#[test]
+fn writes_never_see_local_only_entities() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .create_empty("titles", &[ColumnDef { name: "title".into(), column_type: "TEXT".into() }], None)
+        .unwrap();
+
+    store
+        .execute_sql_with_entities("INSERT INTO source:titles (title) SELECT title FROM entity:task", 0, 100, false, &Tasks)
+        .unwrap();
+    let rows = store.query_dataset(&meta.id, 0, 100, None, Some("title"), false).unwrap().rows;
+    assert_eq!(rows, vec![vec![json!("Build")], vec![json!("Design")], vec![json!("Draft")]]);
+}
+
+#[test]
+fn entity_tables_are_read_only() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    for sql in ["UPDATE entity:task SET title = 'x'", "DELETE FROM entity:task"] {
+        let result = store.execute_sql_with_entities(sql, 0, 100, false, &Tasks);
+        assert!(
+            matches!(&result, Err(DatasetError::NotAuthorized(m)) if m.contains("read-only")),
+            "{sql}: {result:?}"
+        );
+    }
+    // The tables are gone once the statement has run.
+    assert_eq!(query(&store, "SELECT COUNT(*) FROM entity:task").unwrap(), vec![vec![json!(4)]]);
+}
+
+#[test]
+fn the_source_decides_what_is_readable() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    assert!(matches!(query(&store, "SELECT * FROM entity:note"), Err(DatasetError::NotAuthorized(_))));
+    assert!(matches!(query(&store, "SELECT * FROM entity:nothing"), Err(DatasetError::NotFound(_))));
+
+    // Without a source, entity tables aren't available at all.
+    assert!(matches!(
+        store.execute_sql_v2("SELECT * FROM entity:task", 0, 100, false),
+        Err(DatasetError::NotAuthorized(_))
+    ));
+}
@@ -0,0 +1,215 @@
+//! View filters: AND/OR/NOT trees, typed and date operators, full-text
+//! search, and views saved before filter trees existed.
+
+use chrono::{Duration, Utc};
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+
+// -- Helpers --
+
+/// Tasks due relative to today, plus one long past.
+fn tasks(store: &DatasetStore) -> DatasetMeta {
+    let meta = store
+        .create_empty(
+            "tasks",
+            &[
+                ColumnDef { name: "title".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "status".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "points".into(), column_type: "INTEGER".into() },
+                ColumnDef { name: "due".into(), column_type: "DATE".into() },
+                ColumnDef { name: "owner".into(), column_type: "TEXT".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    let day = |offset: i64| json!((Utc::now() + Duration::days(offset)).format("%Y-%m-%d").to_string());
+    for (title, status, points, due, owner) in [
+        ("Write docs", "open", 3, day(-5), json!("Ann")),
+        ("Fix login bug", "in_progress", 8, day(3), json!("Bob")),
+        ("Release 1.0", "done", 5, day(-40), json!("Ann")),
+        ("Plan Q3", "open", 1, day(20), Value::Null),
+        ("O'Brien's report", "blocked", 13, json!("2023-12-31"), json!("Cid")),
+    ] {
+        store
+            .insert_row(
+                &meta.id,
+                &[
+                    ("title", json!(title)),
+                    ("status", json!(status)),
+                    ("points", json!(points)),
+                    ("due", due),
+                    ("owner", owner),
+                ],
+            )
+            .unwrap();
+    }
+    meta
+}
+
+fn condition(column: &str, operator: FilterOperator, value: Value) -> FilterNode {
+    FilterNode::Condition(ViewFilter { column: column.into(), operator, value })
+}
+
+fn view(filter: FilterNode) -> ViewConfig {
+    ViewConfig {
+        visible_columns: Some(vec!["title".into()]),
+        filters: Vec::new(),
+        sorts: vec![ViewSort { column: "points".into(), direction: SortDirection::Asc }],
+        group_by: None,
+        pivot: None,
+        filter: Some(filter),
+    }
+}
+
+/// Titles of the rows passing `filter`, by points.
+fn titles(store: &DatasetStore, id: &DatasetId, filter: FilterNode) -> Vec<String> {
+    let result = store.query_view(id, &view(filter), 0, 100).unwrap();
+    assert_eq!(result.total_count as usize, result.rows.len());
+    result.rows.into_iter().map(|r| r[0].as_str().unwrap().to_string()).collect()
+}
+
+// -- Tests --
+
+#[test]
+fn trees_combine_typed_conditions() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = tasks(&store);
+    let id = &meta.id;
+
+    let status = condition("status", FilterOperator::In, json!(["open", "blocked"]));
+    assert_eq!(titles(&store, id, status.clone()), ["Plan Q3", "Write docs", "O'Brien's report"]);
+    assert_eq!(
+        titles(&store, id, condition("status", FilterOperator::NotIn, json!(["open", "blocked"]))),
+        ["Release 1.0", "Fix login bug"]
+    );
+
+    // Numeric text compares as a number in an integer column.
+    let tree = FilterNode::Or(vec![
+        FilterNode::And(vec![status, condition("points", FilterOperator::GreaterOrEqual, json!("3"))]),
+        condition("title", FilterOperator::StartsWith, json!("fix")),
+    ]);
+    assert_eq!(titles(&store, id, tree), ["Write docs", "Fix login bug", "O'Brien's report"]);
+
+    assert_eq!(
+        titles(&store, id, condition("points", FilterOperator::Between, json!([3, 8]))),
+        ["Write docs", "Release 1.0", "Fix login bug"]
+    );
+    assert_eq!(titles(&store, id, condition("title", FilterOperator::EndsWith, json!("_docs"))), Vec::<String>::new());
+    assert_eq!(
+        titles(&store, id, condition("title", FilterOperator::Matches, json!(r"\d\.\d|Q\d"))),
+        ["Plan Q3", "Release 1.0"]
+    );
+
+    // NOT keeps rows whose condition is unknown because of a NULL.
+    let not_ann = FilterNode::Not(Box::new(condition("owner", FilterOperator::Equals, json!("Ann"))));
+    assert_eq!(titles(&store, id, not_ann), ["Plan Q3", "Fix login bug", "O'Brien's report"]);
+    assert_eq!(titles(&store, id, FilterNode::Or(Vec::new())), Vec::<String>::new());
+    assert_eq!(titles(&store, id, FilterNode::And(Vec::new())).len(), 5);
+}
+
+#[test]
+fn dates_compare_as_dates_and_relative_to_today() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = tasks(&store);
+    let id = &meta.id;
+
+    assert_eq!(
+        titles(&store, id, condition("due", FilterOperator::InLast, json!("30 days"))),
+        ["Write docs"]
+    );
+    assert_eq!(
+        titles(&store, id, condition("due", FilterOperator::InNext, json!("1 week"))),
+        ["Fix login bug"]
+    );
+    assert_eq!(
+        titles(&store, id, condition("due", FilterOperator::InLast, json!("2 months"))),
+        ["Write docs", "Release 1.0"]
+    );
+    assert_eq!(
+        titles(&store, id, condition("due", FilterOperator::Before, json!("2024-01-01T00:00:00"))),
+        ["O'Brien's report"]
+    );
+    assert_eq!(
+        titles(&store, id, condition("due", FilterOperator::Between, json!(["2023-12-31", "2024-01-01"]))),
+        ["O'Brien's report"]
+    );
+    assert_eq!(titles(&store, id, condition("due", FilterOperator::After, json!("2024-01-01"))).len(), 4);
+}
+
+#[test]
+fn search_matches_every_word_across_text_columns() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = tasks(&store);
+    let id = &meta.id;
+
+    let search = |text: &str| titles(&store, id, FilterNode::Search(text.into()));
+    assert_eq!(search("ann"), ["Write docs", "Release 1.0"]);
+    assert_eq!(search("ANN docs"), ["Write docs"]);
+    assert_eq!(search("docs bob"), Vec::<String>::new());
+    assert_eq!(search("o'brien's"), ["O'Brien's report"]);
+    assert_eq!(search("  ").len(), 5);
+
+    // The dataset query's filter text is the same search.
+    let result = store.query_dataset(id, 0, 10, Some("'brien"), None, false).unwrap();
+    assert_eq!(result.total_count, 1);
+    let result = store.query_dataset(id, 0, 10, Some("open plan"), None, false).unwrap();
+    assert_eq!(result.rows[0][0], json!("Plan Q3"));
+}
+
+#[test]
+fn views_are_checked_when_saved_and_pivot_through_their_tree() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = tasks(&store);
+    let id = &meta.id;
+
+    let invalid = |filter: FilterNode| {
+        matches!(store.create_view(id, "v", &view(filter)), Err(DatasetError::InvalidQuery(_)))
+    };
+    assert!(invalid(condition("missing", FilterOperator::Equals, json!("x"))));
+    assert!(invalid(condition("title", FilterOperator::Matches, json!("("))));
+    assert!(invalid(condition("points", FilterOperator::Between, json!([1]))));
+    assert!(invalid(condition("points", FilterOperator::In, json!(1))));
+    assert!(invalid(condition("due", FilterOperator::InLast, json!("3 fortnights"))));
+    assert!(invalid(FilterNode::Not(Box::new(condition("status", FilterOperator::Equals, json!(["open"]))))));
+
+    let mut config = view(FilterNode::Search("ann".into()));
+    config.filters.push(ViewFilter {
+        column: "points".into(),
+        operator: FilterOperator::LessThan,
+        value: json!(5),
+    });
+    config.pivot = Some(PivotConfig {
+        rows: vec![PivotDimension { column: "status".into(), bucket: None, top_n: None }],
+        columns: Vec::new(),
+        measures: vec![PivotMeasure { column: "*".into(), aggregation: PivotAggregation::Count, label: None }],
+        totals: false,
+    });
+    let saved = store.create_view(id, "ann", &config).unwrap();
+    let pivot = store.pivot_view(&saved.id).unwrap();
+    let keys: Vec<Vec<Value>> = pivot.rows.into_iter().map(|r| r.keys).collect();
+    assert_eq!(keys, vec![vec![json!("open")]]);
+}
+
+#[test]
+fn views_saved_with_flat_filters_still_load() {
+    let legacy = r#"{
+        "visible_columns": ["title"],
+        "filters": [{"column": "points", "operator": "greater_than", "value": "4"}],
+        "sorts": [{"column": "points", "direction": "desc"}],
+        "group_by": null
+    }"#;
+    let config: ViewConfig = serde_json::from_str(legacy).unwrap();
+    assert_eq!(config.filter, None);
+    assert_eq!(config.filters[0].value, json!("4"));
+
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = tasks(&store);
+    let result = store.query_view(&meta.id, &config, 0, 2).unwrap();
+    assert_eq!(result.total_count, 3);
+    assert_eq!(result.rows, vec![vec![json!("O'Brien's report")], vec![json!("Fix login bug")]]);
+
+    // Without a tree, the saved JSON keeps its old shape.
+    let saved = serde_json::to_value(&config).unwrap();
+    assert!(saved.get("filter").is_none());
+}
@@ -0,0 +1,255 @@
+//! Formula columns: definition and validation, and how computed values show
+//! up in queries, raw SQL, aggregates, exports and replication.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+
+// -- Helpers --
+
+fn orders() -> (DatasetStore, DatasetMeta) {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .create_empty(
+            "orders",
+            &[
+                ColumnDef { name: "item".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "qty".into(), column_type: "INTEGER".into() },
+                ColumnDef { name: "price".into(), column_type: "REAL".into() },
+                ColumnDef { name: "customer_id".into(), column_type: "INTEGER".into() },
+                ColumnDef { name: "ordered".into(), column_type: "DATE".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (item, qty, price, customer, ordered) in [
+        ("pen", 3, 1.5, 1, "2024-03-01"),
+        ("book", 1, 12.0, 2, "2024-03-15"),
+        ("ink", 2, 4.25, 1, "2024-04-02"),
+    ] {
+        store
+            .insert_row(
+                &meta.id,
+                &[
+                    ("item", json!(item)),
+                    ("qty", json!(qty)),
+                    ("price", json!(price)),
+                    ("customer_id", json!(customer)),
+                    ("ordered", json!(ordered)),
+                ],
+            )
+            .unwrap();
+    }
+    (store, meta)
+}
+
+fn customers(store: &DatasetStore) -> DatasetMeta {
+    let meta = store
+        .create_empty(
+            "customers",
+            &[
+                ColumnDef { name: "id".into(), column_type: "INTEGER".into() },
+                ColumnDef { name: "name".into(), column_type: "TEXT".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (id, name) in [(1, "Ada"), (2, "Grace"), (3, "Linus")] {
+        store.insert_row(&meta.id, &[("id", json!(id)), ("name", json!(name))]).unwrap();
+    }
+    meta
+}
+
+fn column(store: &DatasetStore, id: &DatasetId, name: &str) -> Vec<Value> {
+    let result = store.query_dataset(id, 0, 1000, None, None, false).unwrap();
+    let index = result.columns.iter().position(|c| c == name).unwrap();
+    result.rows.into_iter().map(|mut row| row.swap_remove(index)).collect()
+}
+
+fn invalid(result: DatasetResult<FormulaColumn>) -> String {
+    match result {
+        Err(DatasetError::InvalidQuery(message)) => message,
+        other => panic!("expected an invalid formula, got {other:?}"),
+    }
+}
+
+// -- Definition --
+
+#[test]
+fn formulas_compute_on_read_and_report_their_type() {
+    let (store, meta) = orders();
+    let total = store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    assert_eq!(total.column_type, DatasetColumnType::Float);
+    store
+        .set_formula_column(&meta.id, "label", "UPPER(item) & ' x' & qty")
+        .unwrap();
+    store.set_formula_column(&meta.id, "month", "MONTH(ordered)").unwrap();
+
+    assert_eq!(column(&store, &meta.id, "total"), vec![json!(4.5), json!(12.0), json!(8.5)]);
+    assert_eq!(
+        column(&store, &meta.id, "label"),
+        vec![json!("PEN x3"), json!("BOOK x1"), json!("INK x2")]
+    );
+    assert_eq!(column(&store, &meta.id, "month"), vec![json!(3), json!(3), json!(4)]);
+
+    let columns: Vec<(String, DatasetColumnType)> = store
+        .get_columns(&meta.id)
+        .unwrap()
+        .into_iter()
+        .skip(5)
+        .map(|c| (c.name, c.column_type))
+        .collect();
+    assert_eq!(
+        columns,
+        vec![
+            ("total".to_string(), DatasetColumnType::Float),
+            ("label".to_string(), DatasetColumnType::Text),
+            ("month".to_string(), DatasetColumnType::Integer),
+        ]
+    );
+}
+
+#[test]
+fn formulas_can_use_other_formulas_but_not_in_a_cycle() {
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    store
+        .set_formula_column(&meta.id, "big", "IF(total > 5, 'yes', 'no')")
+        .unwrap();
+    assert_eq!(column(&store, &meta.id, "big"), vec![json!("no"), json!("yes"), json!("yes")]);
+
+    let message = invalid(store.set_formula_column(&meta.id, "total", "big & ''"));
+    assert!(message.contains("cycle: total -> big -> total"), "{message}");
+    // The rejected change leaves the old expression in place.
+    assert_eq!(column(&store, &meta.id, "total"), vec![json!(4.5), json!(12.0), json!(8.5)]);
+}
+
+#[test]
+fn invalid_formulas_are_rejected() {
+    let (store, meta) = orders();
+    assert!(invalid(store.set_formula_column(&meta.id, "x", "qty * discount")).contains("Unknown column: discount"));
+    assert!(invalid(store.set_formula_column(&meta.id, "x", "SHELL('rm')")).contains("Unknown function"));
+    invalid(store.set_formula_column(&meta.id, "x", "qty *"));
+    assert!(invalid(store.set_formula_column(&meta.id, "qty", "1")).contains("already exists"));
+    assert!(store.list_formula_columns(&meta.id).unwrap().is_empty());
+}
+
+#[test]
+fn removing_a_formula_in_use_fails() {
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    store.set_formula_column(&meta.id, "doubled", "total * 2").unwrap();
+
+    assert!(matches!(
+        store.remove_formula_column(&meta.id, "total"),
+        Err(DatasetError::InvalidQuery(_))
+    ));
+    store.remove_formula_column(&meta.id, "doubled").unwrap();
+    store.remove_formula_column(&meta.id, "total").unwrap();
+    assert!(store.list_formula_columns(&meta.id).unwrap().is_empty());
+    assert_eq!(store.get_columns(&meta.id).unwrap().len(), 5);
+}
+
+// -- Related datasets --
+
+#[test]
+fn lookup_and_rollup_follow_relations() {
+    let (store, orders) = orders();
+    let customers = customers(&store);
+    store.create_relation(&orders.id, "customer_id", &customers.id, "id").unwrap();
+
+    store
+        .set_formula_column(&orders.id, "customer", "LOOKUP(customers, name)")
+        .unwrap();
+    assert_eq!(
+        column(&store, &orders.id, "customer"),
+        vec![json!("Ada"), json!("Grace"), json!("Ada")]
+    );
+
+    let spent = store
+        .set_formula_column(&customers.id, "orders", "ROLLUP(orders, qty, 'sum')")
+        .unwrap();
+    assert_eq!(spent.column_type, DatasetColumnType::Integer);
+    assert_eq!(column(&store, &customers.id, "orders"), vec![json!(5), json!(1), json!(0)]);
+
+    let message = invalid(store.set_formula_column(&orders.id, "x", "LOOKUP(orders, qty)"));
+    assert!(message.contains("No relation"), "{message}");
+}
+
+// -- Reads --
+
+#[test]
+fn raw_sql_and_aggregates_see_formula_columns() {
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+
+    let result = store
+        .execute_sql_v2("SELECT item, total FROM source:orders WHERE total > 5 ORDER BY total", 0, 100, false)
+        .unwrap();
+    let SqlExecutionResult::Query(result) = result else {
+        panic!("expected a query result");
+    };
+    assert_eq!(result.rows, vec![vec![json!("ink"), json!(8.5)], vec![json!("book"), json!(12.0)]]);
+
+    let with = store
+        .execute_sql_v2(
+            "WITH big AS (SELECT * FROM source:orders WHERE total > 5) SELECT COUNT(*) AS n FROM big",
+            0,
+            100,
+            false,
+        )
+        .unwrap();
+    let SqlExecutionResult::Query(with) = with else {
+        panic!("expected a query result");
+    };
+    assert_eq!(with.rows, vec![vec![json!(2)]]);
+
+    let sums = store
+        .aggregate_query(&meta.id, "customer_id", "total", Some(Aggregation::Sum), None, None)
+        .unwrap();
+    assert_eq!(sums, vec![(json!(1), json!(13.0)), (json!(2), json!(12.0))]);
+}
+
+#[test]
+fn view_exports_include_formula_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    let view = store
+        .create_view(
+            &meta.id,
+            "totals",
+            &ViewConfig {
+                visible_columns: Some(vec!["item".into(), "total".into()]),
+                filters: Vec::new(),
+                sorts: vec![ViewSort { column: "total".into(), direction: SortDirection::Desc }],
+                group_by: None,
+                pivot: None,
+                filter: None,
+            },
+        )
+        .unwrap();
+
+    let path = dir.path().join("totals.csv");
+    store.export(&ExportSource::View { view_id: view.id }, FileFormat::Csv, &path).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "item,total\nbook,12.0\nink,8.5\npen,4.5\n"
+    );
+}
+
+#[test]
+fn formulas_broken_by_a_dropped_column_read_as_null() {
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    store.drop_column(&meta.id, "price").unwrap();
+    assert_eq!(column(&store, &meta.id, "total"), vec![Value::Null, Value::Null, Value::Null]);
+}
+
+#[test]
+fn duplicates_keep_formula_columns() {
+    let (store, meta) = orders();
+    store.set_formula_column(&meta.id, "total", "qty * price").unwrap();
+    let copy = store.duplicate(&meta.id, "orders copy").unwrap();
+    assert_eq!(column(&store, &copy.id, "total"), vec![json!(4.5), json!(12.0), json!(8.5)]);
+}
@@ -0,0 +1,233 @@
+//! Change history: undo and redo of every kind of edit, restore points
+//! around bulk SQL, and pruning.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+// -- Helpers --
+
+fn parts() -> (DatasetStore, DatasetId) {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .create_empty(
+            "parts",
+            &[
+                ColumnDef { name: "item".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "qty".into(), column_type: "INTEGER".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (item, qty) in [("bolt", 10), ("nut", 20), ("washer", 30)] {
+        store.insert_row(&meta.id, &[("item", json!(item)), ("qty", json!(qty))]).unwrap();
+    }
+    store.clear_history(&meta.id).unwrap();
+    (store, meta.id)
+}
+
+fn rows(store: &DatasetStore, id: &DatasetId) -> Vec<Vec<Value>> {
+    store.query_dataset(id, 0, 1000, None, None, false).unwrap().rows
+}
+
+fn labels(store: &DatasetStore, id: &DatasetId) -> Vec<(String, bool)> {
+    store
+        .history(id)
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.label, e.undone))
+        .collect()
+}
+
+fn column_types(columns: &[DatasetColumn]) -> Vec<(String, DatasetColumnType)> {
+    columns.iter().map(|c| (c.name.clone(), c.column_type.clone())).collect()
+}
+
+fn sql(store: &DatasetStore, statement: &str) {
+    store.execute_sql_v2(statement, 0, 100, false).unwrap();
+}
+
+// -- Row edits --
+
+#[test]
+fn cell_edits_undo_and_redo() {
+    let (store, id) = parts();
+    let original = rows(&store, &id);
+    store.update_cell(&id, 1, "qty", json!(99)).unwrap();
+    let edited = rows(&store, &id);
+
+    let undone = store.undo(&id).unwrap().unwrap();
+    assert_eq!(undone.label, "Edit qty");
+    assert!(undone.undone);
+    assert_eq!(rows(&store, &id), original);
+
+    store.redo(&id).unwrap().unwrap();
+    assert_eq!(rows(&store, &id), edited);
+    assert_eq!(store.redo(&id).unwrap(), None);
+}
+
+#[test]
+fn deleted_and_inserted_rows_undo() {
+    let (store, id) = parts();
+    let original = rows(&store, &id);
+
+    store.delete_rows(&id, &[0]).unwrap();
+    store.insert_row(&id, &[("item", json!("gear")), ("qty", json!(5))]).unwrap();
+    assert_eq!(store.get(&id).unwrap().row_count, 3);
+
+    store.undo(&id).unwrap();
+    store.undo(&id).unwrap();
+    assert_eq!(rows(&store, &id), original);
+    assert_eq!(store.get(&id).unwrap().row_count, 3);
+    assert_eq!(store.undo(&id).unwrap(), None);
+}
+
+#[test]
+fn a_new_edit_discards_undone_edits() {
+    let (store, id) = parts();
+    store.update_cell(&id, 0, "qty", json!(1)).unwrap();
+    store.update_cell(&id, 0, "qty", json!(2)).unwrap();
+    store.undo(&id).unwrap();
+    assert_eq!(
+        labels(&store, &id),
+        vec![("Edit qty".to_string(), false), ("Edit qty".to_string(), true)]
+    );
+
+    store.update_cell(&id, 1, "item", json!("pin")).unwrap();
+    assert_eq!(
+        labels(&store, &id),
+        vec![("Edit qty".to_string(), false), ("Edit item".to_string(), false)]
+    );
+    assert_eq!(store.redo(&id).unwrap(), None);
+}
+
+// -- Column changes --
+
+#[test]
+fn column_changes_undo_from_a_table_copy() {
+    let (store, id) = parts();
+    let original = rows(&store, &id);
+    let columns = column_types(&store.get_columns(&id).unwrap());
+
+    store.alter_column_type(&id, "item", "INTEGER").unwrap();
+    store.drop_column(&id, "qty").unwrap();
+    store.add_column(&id, "note", "TEXT", Some("-")).unwrap();
+    assert_eq!(rows(&store, &id), vec![vec![json!(0), json!("-")]; 3]);
+
+    for _ in 0..3 {
+        store.undo(&id).unwrap().unwrap();
+    }
+    assert_eq!(rows(&store, &id), original);
+    assert_eq!(column_types(&store.get_columns(&id).unwrap()), columns);
+    assert_eq!(column_types(&store.get(&id).unwrap().columns), columns);
+
+    store.redo(&id).unwrap();
+    assert_eq!(rows(&store, &id), vec![vec![json!(0), json!(10)], vec![json!(0), json!(20)], vec![json!(0), json!(30)]]);
+}
+
+// -- SQL mutations --
+
+#[test]
+fn bulk_sql_is_preceded_by_a_restore_point() {
+    let (store, id) = parts();
+    let original = rows(&store, &id);
+
+    sql(&store, "UPDATE source:parts SET qty = 0");
+    sql(&store, "DELETE FROM source:parts WHERE item = 'nut'");
+    let history = store.history(&id).unwrap();
+    let kinds: Vec<HistoryEntryKind> = history.iter().map(|e| e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            HistoryEntryKind::RestorePoint,
+            HistoryEntryKind::Edit,
+            HistoryEntryKind::RestorePoint,
+            HistoryEntryKind::Edit,
+        ]
+    );
+    assert!(history[0].label.starts_with("Before UPDATE ds_"), "{}", history[0].label);
+
+    assert_eq!(store.restore(&id, history[0].id).unwrap(), 2);
+    assert_eq!(rows(&store, &id), original);
+
+    // Restoring forward redoes the edits up to the entry.
+    assert_eq!(store.restore(&id, history[1].id).unwrap(), 1);
+    assert_eq!(rows(&store, &id), vec![vec![json!("bolt"), json!(0)], vec![json!("nut"), json!(0)], vec![json!("washer"), json!(0)]]);
+}
+
+#[test]
+fn bulk_sql_undoes_whatever_its_form() {
+    let (store, id) = parts();
+    let original = rows(&store, &id);
+    let table = id.table_name();
+
+    // A trailing `;` and a leading `WITH` still name the dataset written.
+    for statement in [
+        format!("UPDATE {table} SET qty = 0;"),
+        format!(
+            "WITH doomed AS (SELECT 'nut' AS item) \
+             DELETE FROM {table} WHERE item IN (SELECT item FROM doomed)"
+        ),
+    ] {
+        store.execute_mutation(&statement, false).unwrap();
+    }
+    assert_eq!(
+        labels(&store, &id).len(),
+        4,
+        "each statement records a restore point and an edit"
+    );
+    assert_eq!(rows(&store, &id), vec![vec![json!("bolt"), json!(0)], vec![json!("washer"), json!(0)]]);
+
+    store.undo(&id).unwrap().unwrap();
+    assert_eq!(rows(&store, &id).len(), 3);
+    store.undo(&id).unwrap().unwrap();
+    assert_eq!(rows(&store, &id), original);
+}
+
+#[test]
+fn dry_runs_leave_no_history() {
+    let (store, id) = parts();
+    store.execute_sql_v2("DELETE FROM source:parts", 0, 100, true).unwrap();
+    assert!(store.history(&id).unwrap().is_empty());
+}
+
+#[test]
+fn named_restore_points() {
+    let (store, id) = parts();
+    let point = store.create_restore_point(&id, "before cleanup").unwrap();
+    assert_eq!(point.kind, HistoryEntryKind::RestorePoint);
+    store.delete_rows(&id, &[0, 0]).unwrap();
+    store.update_cell(&id, 0, "qty", json!(1)).unwrap();
+
+    assert_eq!(store.restore(&id, point.id).unwrap(), 2);
+    assert_eq!(store.get(&id).unwrap().row_count, 3);
+
+    let other = parts().1;
+    assert!(matches!(store.restore(&other, point.id), Err(DatasetError::NotFound(_))));
+}
+
+// -- Pruning --
+
+#[test]
+fn history_is_pruned_to_the_limits() {
+    let (store, id) = parts();
+    store.set_history_limits(HistoryLimits { max_entries: 2, ..HistoryLimits::default() });
+    for qty in 1..=5 {
+        store.update_cell(&id, 0, "qty", json!(qty)).unwrap();
+    }
+    assert_eq!(store.history(&id).unwrap().len(), 2);
+
+    store.set_history_limits(HistoryLimits { max_entries: 10, max_age: Duration::ZERO });
+    std::thread::sleep(Duration::from_millis(2));
+    assert_eq!(store.prune_history().unwrap(), 2);
+    assert_eq!(store.undo(&id).unwrap(), None);
+}
+
+#[test]
+fn deleting_a_dataset_drops_its_history() {
+    let (store, id) = parts();
+    store.drop_column(&id, "qty").unwrap();
+    store.delete(&id).unwrap();
+    assert!(store.history(&id).unwrap().is_empty());
+}
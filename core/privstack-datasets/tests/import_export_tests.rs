@@ -0,0 +1,280 @@
+//! File import and export: streaming readers for every supported format,
+//! and exports of datasets, views and saved queries that read back the
+//! same.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// -- Helpers --
+
+fn people() -> (DatasetStore, DatasetMeta) {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .create_empty(
+            "people",
+            &[
+                ColumnDef { name: "name".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "age".into(), column_type: "INTEGER".into() },
+                ColumnDef { name: "score".into(), column_type: "REAL".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (name, age, score) in [("Alice", 30, 9.5), ("Bob", 25, 7.25)] {
+        store
+            .insert_row(&meta.id, &[("name", json!(name)), ("age", json!(age)), ("score", json!(score))])
+            .unwrap();
+    }
+    // No age: the column stays null.
+    store.insert_row(&meta.id, &[("name", json!("Carol")), ("score", json!(8.0))]).unwrap();
+    (store, meta)
+}
+
+fn rows(store: &DatasetStore, id: &DatasetId) -> Vec<Vec<Value>> {
+    store.query_dataset(id, 0, 1000, None, None, false).unwrap().rows
+}
+
+fn column_types(meta: &DatasetMeta) -> Vec<(String, DatasetColumnType)> {
+    meta.columns.iter().map(|c| (c.name.clone(), c.column_type.clone())).collect()
+}
+
+fn write_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+    path
+}
+
+// -- Import --
+
+#[test]
+fn tsv_and_ndjson_content_infer_column_types() {
+    let store = DatasetStore::open_in_memory().unwrap();
+
+    let tsv = store
+        .import_content("city\tpop\tarea\nOslo\t700000\t454.0\nBergen\t285000\t465.3\n", FileFormat::Tsv, "cities", None)
+        .unwrap();
+    assert_eq!(
+        column_types(&tsv),
+        vec![
+            ("city".to_string(), DatasetColumnType::Text),
+            ("pop".to_string(), DatasetColumnType::Integer),
+            ("area".to_string(), DatasetColumnType::Float),
+        ]
+    );
+    assert_eq!(tsv.row_count, 2);
+
+    let ndjson = "{\"id\": 1, \"ok\": true, \"tags\": [\"a\"]}\n{\"id\": 2, \"ok\": false, \"note\": \"late\"}\n";
+    let meta = store.import_content(ndjson, FileFormat::Ndjson, "events", None).unwrap();
+    assert_eq!(
+        column_types(&meta),
+        vec![
+            ("id".to_string(), DatasetColumnType::Integer),
+            ("ok".to_string(), DatasetColumnType::Boolean),
+            ("tags".to_string(), DatasetColumnType::Text),
+            ("note".to_string(), DatasetColumnType::Text),
+        ]
+    );
+    assert_eq!(
+        rows(&store, &meta.id),
+        vec![
+            vec![json!(1), json!(1), json!("[\"a\"]"), Value::Null],
+            vec![json!(2), json!(0), Value::Null, json!("late")],
+        ]
+    );
+}
+
+#[test]
+fn json_keys_first_seen_after_the_sample_become_text_columns() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let mut objects: Vec<Value> = (0..150).map(|i| json!({ "n": i })).collect();
+    objects.push(json!({ "n": 150, "extra": 42 }));
+    let meta = store
+        .import_content(&Value::Array(objects).to_string(), FileFormat::Json, "late", None)
+        .unwrap();
+
+    assert_eq!(meta.row_count, 151);
+    assert_eq!(
+        column_types(&meta),
+        vec![
+            ("n".to_string(), DatasetColumnType::Integer),
+            ("extra".to_string(), DatasetColumnType::Text),
+        ]
+    );
+    let last = store.query_dataset(&meta.id, 0, 1, None, Some("n"), true).unwrap().rows;
+    assert_eq!(last, vec![vec![json!(150), json!("42")]]);
+}
+
+#[test]
+fn duplicate_and_blank_headers_are_made_unique() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store.import_csv_content("a,A,,a\n1,2,3,4\n", "dupes", None).unwrap();
+    let names: Vec<&str> = meta.columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["a", "A_2", "column_3", "a_3"]);
+}
+
+#[test]
+fn large_file_import_reports_progress() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut csv = String::from("id,label\n");
+    for i in 0..25_000 {
+        csv.push_str(&format!("{i},row {i}\n"));
+    }
+    let path = write_file(dir.path(), "big.csv", &csv);
+
+    let mut reports = Vec::new();
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .import_file_with_progress(&path, "big", &ImportOptions::default(), |p| reports.push(p.clone()))
+        .unwrap();
+
+    assert_eq!(meta.row_count, 25_000);
+    assert_eq!(meta.source_file_name.as_deref(), Some("big.csv"));
+    let imported: Vec<u64> = reports.iter().map(|p| p.rows_imported).collect();
+    assert_eq!(imported, vec![10_000, 20_000, 25_000]);
+    let last = reports.last().unwrap();
+    assert_eq!(last.bytes_read, Some(csv.len() as u64));
+    assert_eq!(last.fraction(), Some(1.0));
+}
+
+#[test]
+fn failed_import_leaves_nothing_behind() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let broken = "[{\"a\": 1}, {\"a\": 2}, {\"a\": ";
+    assert!(store.import_content(broken, FileFormat::Json, "broken", None).is_err());
+    assert!(store.list().unwrap().is_empty());
+}
+
+#[test]
+fn unknown_extension_needs_an_explicit_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "data.txt", "a\tb\n1\t2\n");
+    let store = DatasetStore::open_in_memory().unwrap();
+
+    assert!(matches!(
+        store.import_file(&path, "data", &ImportOptions::default()),
+        Err(DatasetError::ImportFailed(_))
+    ));
+    let options = ImportOptions { format: Some(FileFormat::Tsv), ..ImportOptions::default() };
+    assert_eq!(store.import_file(&path, "data", &options).unwrap().row_count, 1);
+}
+
+// -- Export --
+
+#[test]
+fn every_format_round_trips_a_dataset() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, meta) = people();
+    let expected = rows(&store, &meta.id);
+
+    for (format, file) in [
+        (FileFormat::Csv, "people.csv"),
+        (FileFormat::Tsv, "people.tsv"),
+        (FileFormat::Json, "people.json"),
+        (FileFormat::Ndjson, "people.ndjson"),
+        (FileFormat::Xlsx, "people.xlsx"),
+        (FileFormat::Parquet, "people.parquet"),
+    ] {
+        let path = dir.path().join(file);
+        let source = ExportSource::Dataset { dataset_id: meta.id.clone() };
+        assert_eq!(store.export(&source, format, &path).unwrap(), 3, "{file}");
+
+        let imported = store.import_file(&path, file, &ImportOptions::default()).unwrap();
+        assert_eq!(
+            column_types(&imported),
+            vec![
+                ("name".to_string(), DatasetColumnType::Text),
+                ("age".to_string(), DatasetColumnType::Integer),
+                ("score".to_string(), DatasetColumnType::Float),
+            ],
+            "{file}"
+        );
+        assert_eq!(rows(&store, &imported.id), expected, "{file}");
+    }
+}
+
+#[test]
+fn boolean_columns_export_as_booleans() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .import_content("[{\"flag\": true}, {\"flag\": false}]", FileFormat::Json, "flags", None)
+        .unwrap();
+
+    let path = dir.path().join("flags.json");
+    store
+        .export(&ExportSource::Dataset { dataset_id: meta.id.clone() }, FileFormat::Json, &path)
+        .unwrap();
+    let written: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written, json!([{ "flag": true }, { "flag": false }]));
+
+    let path = dir.path().join("flags.parquet");
+    store
+        .export(&ExportSource::Dataset { dataset_id: meta.id }, FileFormat::Parquet, &path)
+        .unwrap();
+    let imported = store.import_file(&path, "again", &ImportOptions::default()).unwrap();
+    assert_eq!(column_types(&imported), vec![("flag".to_string(), DatasetColumnType::Boolean)]);
+}
+
+#[test]
+fn view_export_honours_columns_filters_and_sorts() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, meta) = people();
+    let view = store
+        .create_view(
+            &meta.id,
+            "scored",
+            &ViewConfig {
+                visible_columns: Some(vec!["name".into(), "score".into()]),
+                filters: vec![ViewFilter {
+                    column: "score".into(),
+                    operator: FilterOperator::GreaterThan,
+                    value: "7.5".into(),
+                }],
+                sorts: vec![ViewSort { column: "score".into(), direction: SortDirection::Asc }],
+                group_by: None,
+                pivot: None,
+                filter: None,
+            },
+        )
+        .unwrap();
+
+    let path = dir.path().join("view.csv");
+    let written = store.export(&ExportSource::View { view_id: view.id }, FileFormat::Csv, &path).unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "name,score\nCarol,8.0\nAlice,9.5\n");
+}
+
+#[test]
+fn saved_query_export_runs_sandboxed_selects_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, _) = people();
+    let path = dir.path().join("query.ndjson");
+
+    let query = store
+        .create_saved_query("adults", "SELECT name FROM source:people WHERE age >= 30;", None, false)
+        .unwrap();
+    let written = store
+        .export(&ExportSource::SavedQuery { query_id: query.id }, FileFormat::Ndjson, &path)
+        .unwrap();
+    assert_eq!(written, 1);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"name\":\"Alice\"}\n");
+
+    let mutation = store
+        .create_saved_query("wipe", "DELETE FROM source:people", None, false)
+        .unwrap();
+    assert!(matches!(
+        store.export(&ExportSource::SavedQuery { query_id: mutation.id }, FileFormat::Csv, &path),
+        Err(DatasetError::InvalidQuery(_))
+    ));
+
+    let escape = store
+        .create_saved_query("meta", "SELECT * FROM _datasets_meta", None, false)
+        .unwrap();
+    assert!(matches!(
+        store.export(&ExportSource::SavedQuery { query_id: escape.id }, FileFormat::Csv, &path),
+        Err(DatasetError::NotAuthorized(_))
+    ));
+}
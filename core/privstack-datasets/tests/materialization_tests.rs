@@ -0,0 +1,258 @@
+//! Materialized saved queries: cached rows, invalidation when a source
+//! dataset changes, and the refresh policies.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+
+// -- Helpers --
+
+fn orders(store: &DatasetStore) -> DatasetMeta {
+    let meta = store
+        .create_empty(
+            "orders",
+            &[
+                ColumnDef { name: "customer".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "total".into(), column_type: "INTEGER".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (customer, total) in [("Ann", 10), ("Bob", 25), ("Ann", 5)] {
+        store
+            .insert_row(&meta.id, &[("customer", json!(customer)), ("total", json!(total))])
+            .unwrap();
+    }
+    meta
+}
+
+fn totals(store: &DatasetStore) -> SavedQuery {
+    store
+        .create_saved_query(
+            "totals",
+            "SELECT customer, SUM(total) AS total FROM source:orders GROUP BY customer ORDER BY customer",
+            None,
+            false,
+        )
+        .unwrap()
+}
+
+fn rows(store: &DatasetStore, query_id: &str) -> Vec<Vec<Value>> {
+    store.query_saved_query(query_id, 0, 100).unwrap().rows
+}
+
+fn stale(store: &DatasetStore, query_id: &str) -> bool {
+    store.saved_query_materialization(query_id).unwrap().unwrap().stale
+}
+
+// -- Tests --
+
+#[test]
+fn cached_rows_are_served_and_go_stale_when_a_source_changes() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let query = totals(&store);
+
+    let status = store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnDemand))
+        .unwrap()
+        .unwrap();
+    assert_eq!(status.sources, vec![meta.id.clone()]);
+    assert_eq!(status.row_count, 2);
+    assert!(!status.stale);
+
+    let result = store.query_saved_query(&query.id, 0, 100).unwrap();
+    assert_eq!(result.columns, ["customer", "total"]);
+    assert_eq!(result.rows, vec![vec![json!("Ann"), json!(15)], vec![json!("Bob"), json!(25)]]);
+    assert_eq!(result.materialization, Some(status));
+
+    // Row edits and SQL mutations both invalidate the cache; on demand,
+    // the old rows are served until a refresh.
+    store.insert_row(&meta.id, &[("customer", json!("Cid")), ("total", json!(7))]).unwrap();
+    assert!(stale(&store, &query.id));
+    assert_eq!(rows(&store, &query.id).len(), 2);
+
+    store.refresh_saved_query(&query.id).unwrap();
+    assert!(!stale(&store, &query.id));
+    assert_eq!(rows(&store, &query.id).len(), 3);
+
+    store.execute_sql_v2("UPDATE source:orders SET total = total * 2", 0, 10, false).unwrap();
+    assert!(stale(&store, &query.id));
+    store.refresh_saved_query(&query.id).unwrap();
+    assert_eq!(rows(&store, &query.id)[0], vec![json!("Ann"), json!(30)]);
+
+    // A dataset the query doesn't read leaves it fresh.
+    let other = store
+        .create_empty("other", &[ColumnDef { name: "x".into(), column_type: "INTEGER".into() }], None)
+        .unwrap();
+    store.insert_row(&other.id, &[("x", json!(1))]).unwrap();
+    assert!(!stale(&store, &query.id));
+
+    // However the statement is written, writing the dataset counts.
+    store.execute_mutation(&format!("DELETE FROM {};", meta.id.table_name()), false).unwrap();
+    assert!(stale(&store, &query.id));
+}
+
+#[test]
+fn datasets_read_through_relations_are_sources() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let customers = store
+        .create_empty(
+            "customers",
+            &[
+                ColumnDef { name: "name".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "city".into(), column_type: "TEXT".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (name, city) in [("Ann", "Oslo"), ("Bob", "Rome")] {
+        store.insert_row(&customers.id, &[("name", json!(name)), ("city", json!(city))]).unwrap();
+    }
+    let relation = store.create_relation(&meta.id, "customer", &customers.id, "name").unwrap();
+    store.set_formula_column(&meta.id, "city", "LOOKUP(customers, city)").unwrap();
+
+    let query = store
+        .create_saved_query(
+            "cities",
+            "SELECT DISTINCT city FROM source:orders ORDER BY city",
+            None,
+            false,
+        )
+        .unwrap();
+    let status = store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnDemand))
+        .unwrap()
+        .unwrap();
+    assert_eq!(status.sources, vec![meta.id.clone(), customers.id.clone()]);
+    assert_eq!(rows(&store, &query.id), vec![vec![json!("Oslo")], vec![json!("Rome")]]);
+
+    store
+        .execute_mutation(&format!("UPDATE {} SET city = 'Lima'", customers.id.table_name()), false)
+        .unwrap();
+    assert!(stale(&store, &query.id));
+    store.refresh_saved_query(&query.id).unwrap();
+    assert_eq!(rows(&store, &query.id), vec![vec![json!("Lima")]]);
+
+    // Dropping the relation changes what the lookup sees.
+    store.delete_relation(&relation.id).unwrap();
+    assert!(stale(&store, &query.id));
+}
+
+#[test]
+fn oversized_refreshes_fail_and_keep_the_old_rows() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let query = totals(&store);
+    store.set_sql_limits(SqlLimits { max_materialized_rows: 2, ..SqlLimits::default() });
+    store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnDemand))
+        .unwrap();
+
+    store.insert_row(&meta.id, &[("customer", json!("Cid")), ("total", json!(7))]).unwrap();
+    let result = store.refresh_saved_query(&query.id);
+    assert!(matches!(result, Err(DatasetError::InvalidQuery(m)) if m.contains("too many")));
+    assert_eq!(rows(&store, &query.id).len(), 2);
+    assert!(stale(&store, &query.id));
+}
+
+#[test]
+fn on_change_queries_rebuild_when_opened_stale() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let query = totals(&store);
+    store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnChange))
+        .unwrap();
+
+    store.delete_rows(&meta.id, &[1]).unwrap();
+    assert!(stale(&store, &query.id));
+    let result = store.query_saved_query(&query.id, 0, 100).unwrap();
+    assert_eq!(result.rows, vec![vec![json!("Ann"), json!(15)]]);
+    assert!(!result.materialization.unwrap().stale);
+
+    // Editing the query's SQL invalidates it too.
+    store
+        .update_saved_query(&query.id, "totals", "SELECT COUNT(*) AS n FROM source:orders", None, false)
+        .unwrap();
+    assert!(stale(&store, &query.id));
+    let result = store.query_saved_query(&query.id, 0, 100).unwrap();
+    assert_eq!(result.columns, ["n"]);
+    assert_eq!(result.rows, vec![vec![json!(2)]]);
+}
+
+#[test]
+fn scheduled_queries_refresh_when_their_interval_has_passed() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let due = totals(&store);
+    let later = totals(&store);
+    let on_demand = totals(&store);
+    store
+        .set_saved_query_materialization(&due.id, Some(RefreshPolicy::Every { interval_secs: 0 }))
+        .unwrap();
+    store
+        .set_saved_query_materialization(&later.id, Some(RefreshPolicy::Every { interval_secs: 3600 }))
+        .unwrap();
+    store
+        .set_saved_query_materialization(&on_demand.id, Some(RefreshPolicy::OnDemand))
+        .unwrap();
+
+    store.insert_row(&meta.id, &[("customer", json!("Cid")), ("total", json!(7))]).unwrap();
+    assert_eq!(store.refresh_due_saved_queries().unwrap(), vec![due.id.clone()]);
+    assert_eq!(rows(&store, &due.id).len(), 3);
+    assert!(stale(&store, &later.id));
+    assert_eq!(rows(&store, &later.id).len(), 2);
+    assert!(stale(&store, &on_demand.id));
+
+    // The policy is saved with the query.
+    let saved = store.list_saved_queries().unwrap();
+    let policy = |id: &str| saved.iter().find(|q| q.id == id).unwrap().materialized;
+    assert_eq!(policy(&later.id), Some(RefreshPolicy::Every { interval_secs: 3600 }));
+}
+
+#[test]
+fn dematerialized_and_deleted_queries_drop_their_rows() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    orders(&store);
+    let query = totals(&store);
+    store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnDemand))
+        .unwrap();
+
+    assert_eq!(store.set_saved_query_materialization(&query.id, None).unwrap(), None);
+    assert_eq!(store.saved_query_materialization(&query.id).unwrap(), None);
+    assert!(matches!(store.refresh_saved_query(&query.id), Err(DatasetError::InvalidQuery(_))));
+
+    // Unmaterialized queries run live.
+    let result = store.query_saved_query(&query.id, 0, 1).unwrap();
+    assert_eq!(result.total_count, 2);
+    assert_eq!(result.rows, vec![vec![json!("Ann"), json!(15)]]);
+    assert_eq!(result.materialization, None);
+
+    store
+        .set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnChange))
+        .unwrap();
+    store.delete_saved_query(&query.id).unwrap();
+    assert!(matches!(store.saved_query_materialization(&query.id), Err(DatasetError::NotFound(_))));
+}
+
+#[test]
+fn only_dataset_selects_can_be_materialized() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = orders(&store);
+    let materialize = |sql: &str| {
+        let query = store.create_saved_query("q", sql, None, false).unwrap();
+        store.set_saved_query_materialization(&query.id, Some(RefreshPolicy::OnDemand))
+    };
+
+    assert!(matches!(materialize("DELETE FROM source:orders"), Err(DatasetError::InvalidQuery(_))));
+    assert!(matches!(materialize("SELECT * FROM entity:task"), Err(DatasetError::InvalidQuery(_))));
+    assert!(matches!(materialize("SELECT * FROM _datasets_meta"), Err(DatasetError::NotAuthorized(_))));
+    assert_eq!(store.get(&meta.id).unwrap().row_count, 3);
+
+    let wipe = store.create_saved_query("wipe", "DELETE FROM source:orders", None, false).unwrap();
+    assert!(matches!(store.query_saved_query(&wipe.id, 0, 10), Err(DatasetError::InvalidQuery(_))));
+    assert_eq!(store.get(&meta.id).unwrap().row_count, 3);
+}
@@ -0,0 +1,275 @@
+//! Pivot tables: measures over row and column dimensions, date buckets,
+//! top-N buckets, subtotals and totals, and pivots saved with views.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+
+// -- Helpers --
+
+fn sales() -> (DatasetStore, DatasetId) {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let csv = "region,product,sold,amount,qty\n\
+               north,pen,2024-01-05,10.0,1\n\
+               north,ink,2024-02-10,20.0,2\n\
+               south,pen,2024-01-20,30.0,3\n\
+               south,book,2024-04-02,40.0,4\n\
+               east,pen,2024-05-01,5.0,1\n";
+    let meta = store.import_csv_content(csv, "sales", None).unwrap();
+    (store, meta.id)
+}
+
+fn dimension(column: &str) -> PivotDimension {
+    PivotDimension { column: column.into(), bucket: None, top_n: None }
+}
+
+fn measure(column: &str, aggregation: PivotAggregation) -> PivotMeasure {
+    PivotMeasure { column: column.into(), aggregation, label: None }
+}
+
+/// Each row's kind and keys, with its cells flattened.
+fn table(result: &PivotResult) -> Vec<(PivotRowKind, Vec<Value>, Vec<Value>)> {
+    result
+        .rows
+        .iter()
+        .map(|r| (r.kind, r.keys.clone(), r.cells.concat()))
+        .collect()
+}
+
+// -- Tests --
+
+#[test]
+fn rows_by_columns_with_date_buckets_and_totals() {
+    let (store, id) = sales();
+    let config = PivotConfig {
+        rows: vec![dimension("region")],
+        columns: vec![PivotDimension { bucket: Some(DateBucket::Quarter), ..dimension("sold") }],
+        measures: vec![measure("amount", PivotAggregation::Sum)],
+        totals: true,
+    };
+    let result = store.pivot(&id, &config, None).unwrap();
+
+    assert_eq!(result.column_keys, vec![vec![json!("2024-Q1")], vec![json!("2024-Q2")]]);
+    assert_eq!(result.measures, vec!["sum(amount)"]);
+    assert_eq!(
+        table(&result),
+        vec![
+            (PivotRowKind::Data, vec![json!("east")], vec![Value::Null, json!(5.0)]),
+            (PivotRowKind::Data, vec![json!("north")], vec![json!(30.0), Value::Null]),
+            (PivotRowKind::Data, vec![json!("south")], vec![json!(30.0), json!(40.0)]),
+            (PivotRowKind::Total, vec![Value::Null], vec![json!(60.0), json!(45.0)]),
+        ]
+    );
+    let totals: Vec<Option<Vec<Value>>> = result.rows.iter().map(|r| r.total.clone()).collect();
+    assert_eq!(
+        totals,
+        vec![
+            Some(vec![json!(5.0)]),
+            Some(vec![json!(30.0)]),
+            Some(vec![json!(70.0)]),
+            Some(vec![json!(105.0)]),
+        ]
+    );
+}
+
+#[test]
+fn typed_aggregations() {
+    let (store, id) = sales();
+    let config = PivotConfig {
+        rows: vec![dimension("region")],
+        columns: Vec::new(),
+        measures: vec![
+            measure("*", PivotAggregation::Count),
+            measure("product", PivotAggregation::CountDistinct),
+            measure("amount", PivotAggregation::Median),
+            measure("amount", PivotAggregation::Percentile(75.0)),
+            measure("sold", PivotAggregation::Min),
+            measure("sold", PivotAggregation::Max),
+            measure("qty", PivotAggregation::Avg),
+            PivotMeasure { label: Some("units".into()), ..measure("qty", PivotAggregation::Sum) },
+        ],
+        totals: false,
+    };
+    let result = store.pivot(&id, &config, None).unwrap();
+
+    assert_eq!(
+        result.measures,
+        vec![
+            "count(*)",
+            "count_distinct(product)",
+            "median(amount)",
+            "p75(amount)",
+            "min(sold)",
+            "max(sold)",
+            "avg(qty)",
+            "units"
+        ]
+    );
+    assert_eq!(result.column_keys, vec![Vec::<Value>::new()]);
+    assert_eq!(
+        table(&result),
+        vec![
+            (
+                PivotRowKind::Data,
+                vec![json!("east")],
+                vec![json!(1), json!(1), json!(5.0), json!(5.0), json!("2024-05-01"), json!("2024-05-01"), json!(1.0), json!(1)],
+            ),
+            (
+                PivotRowKind::Data,
+                vec![json!("north")],
+                vec![json!(2), json!(2), json!(15.0), json!(17.5), json!("2024-01-05"), json!("2024-02-10"), json!(1.5), json!(3)],
+            ),
+            (
+                PivotRowKind::Data,
+                vec![json!("south")],
+                vec![json!(2), json!(2), json!(35.0), json!(37.5), json!("2024-01-20"), json!("2024-04-02"), json!(3.5), json!(7)],
+            ),
+        ]
+    );
+    assert!(result.rows.iter().all(|r| r.total.is_none()));
+}
+
+#[test]
+fn subtotals_follow_their_groups() {
+    let (store, id) = sales();
+    let config = PivotConfig {
+        rows: vec![dimension("region"), dimension("product")],
+        columns: Vec::new(),
+        measures: vec![measure("qty", PivotAggregation::Sum)],
+        totals: true,
+    };
+    let result = store.pivot(&id, &config, None).unwrap();
+
+    let data = PivotRowKind::Data;
+    let subtotal = PivotRowKind::Subtotal;
+    assert_eq!(
+        table(&result),
+        vec![
+            (data, vec![json!("east"), json!("pen")], vec![json!(1)]),
+            (subtotal, vec![json!("east"), Value::Null], vec![json!(1)]),
+            (data, vec![json!("north"), json!("ink")], vec![json!(2)]),
+            (data, vec![json!("north"), json!("pen")], vec![json!(1)]),
+            (subtotal, vec![json!("north"), Value::Null], vec![json!(3)]),
+            (data, vec![json!("south"), json!("book")], vec![json!(4)]),
+            (data, vec![json!("south"), json!("pen")], vec![json!(3)]),
+            (subtotal, vec![json!("south"), Value::Null], vec![json!(7)]),
+            (PivotRowKind::Total, vec![Value::Null, Value::Null], vec![json!(11)]),
+        ]
+    );
+}
+
+#[test]
+fn top_n_puts_the_rest_in_other() {
+    let (store, id) = sales();
+    let config = PivotConfig {
+        rows: vec![PivotDimension { top_n: Some(1), ..dimension("product") }],
+        columns: Vec::new(),
+        measures: vec![measure("amount", PivotAggregation::Sum), measure("*", PivotAggregation::Count)],
+        totals: false,
+    };
+    let result = store.pivot(&id, &config, None).unwrap();
+    assert_eq!(
+        table(&result),
+        vec![
+            (PivotRowKind::Data, vec![json!("pen")], vec![json!(45.0), json!(3)]),
+            (PivotRowKind::Data, vec![json!("Other")], vec![json!(60.0), json!(2)]),
+        ]
+    );
+}
+
+#[test]
+fn dates_bucket_by_day_week_month_and_year() {
+    let (store, id) = sales();
+    let keys = |bucket| {
+        let config = PivotConfig {
+            rows: vec![PivotDimension { bucket: Some(bucket), ..dimension("sold") }],
+            columns: Vec::new(),
+            measures: vec![measure("*", PivotAggregation::Count)],
+            totals: false,
+        };
+        let result = store.pivot(&id, &config, None).unwrap();
+        result.rows.into_iter().map(|r| (r.keys[0].clone(), r.cells[0][0].clone())).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        keys(DateBucket::Month),
+        vec![
+            (json!("2024-01"), json!(2)),
+            (json!("2024-02"), json!(1)),
+            (json!("2024-04"), json!(1)),
+            (json!("2024-05"), json!(1)),
+        ]
+    );
+    assert_eq!(keys(DateBucket::Year), vec![(json!("2024"), json!(5))]);
+    // Weeks are keyed by their Monday.
+    assert_eq!(keys(DateBucket::Week)[0], (json!("2024-01-01"), json!(1)));
+    assert_eq!(keys(DateBucket::Day)[4], (json!("2024-05-01"), json!(1)));
+}
+
+#[test]
+fn invalid_pivots_are_rejected() {
+    let (store, id) = sales();
+    let base = PivotConfig {
+        rows: vec![dimension("region")],
+        columns: Vec::new(),
+        measures: vec![measure("amount", PivotAggregation::Sum)],
+        totals: false,
+    };
+    let invalid = |config: PivotConfig| matches!(store.pivot(&id, &config, None), Err(DatasetError::InvalidQuery(_)));
+
+    assert!(invalid(PivotConfig {
+        rows: vec![PivotDimension { bucket: Some(DateBucket::Month), ..dimension("amount") }],
+        ..base.clone()
+    }));
+    assert!(invalid(PivotConfig { measures: vec![measure("amount", PivotAggregation::Percentile(150.0))], ..base.clone() }));
+    assert!(invalid(PivotConfig { measures: vec![measure("*", PivotAggregation::Sum)], ..base.clone() }));
+    assert!(invalid(PivotConfig { rows: vec![dimension("city")], ..base.clone() }));
+    assert!(invalid(PivotConfig { measures: Vec::new(), ..base }));
+}
+
+#[test]
+fn pivots_are_saved_with_views() {
+    let (store, id) = sales();
+    let pivot = PivotConfig {
+        rows: vec![dimension("product")],
+        columns: Vec::new(),
+        measures: vec![measure("qty", PivotAggregation::Sum)],
+        totals: false,
+    };
+    let view = store
+        .create_view(
+            &id,
+            "north by product",
+            &ViewConfig {
+                visible_columns: None,
+                filters: vec![ViewFilter {
+                    column: "region".into(),
+                    operator: FilterOperator::Equals,
+                    value: "north".into(),
+                }],
+                sorts: Vec::new(),
+                group_by: None,
+                pivot: Some(pivot.clone()),
+                filter: None,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(store.list_views(&id).unwrap()[0].config.pivot, Some(pivot));
+    assert_eq!(
+        table(&store.pivot_view(&view.id).unwrap()),
+        vec![
+            (PivotRowKind::Data, vec![json!("ink")], vec![json!(2)]),
+            (PivotRowKind::Data, vec![json!("pen")], vec![json!(1)]),
+        ]
+    );
+
+    let plain = store
+        .create_view(
+            &id,
+            "plain",
+            &ViewConfig { visible_columns: None, filters: Vec::new(), sorts: Vec::new(), group_by: None, pivot: None, filter: None },
+        )
+        .unwrap();
+    assert!(matches!(store.pivot_view(&plain.id), Err(DatasetError::InvalidQuery(_))));
+}
@@ -0,0 +1,329 @@
+//! Dataset replication: journaling local changes and merging replicated
+//! ones so that devices converge.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+// -- Helpers --
+
+fn replica(id: &str) -> DatasetStore {
+    let store = DatasetStore::open_in_memory().unwrap();
+    store.enable_replication(id);
+    store
+}
+
+/// Delivers every pending change from `from` to `to`.
+fn deliver(from: &DatasetStore, to: &DatasetStore) -> usize {
+    let changes = from.take_pending_changes(1000).unwrap();
+    for change in &changes {
+        to.apply_replicated(&change.dataset_id, &change.op, &change.stamp).unwrap();
+    }
+    changes.len()
+}
+
+/// Exchanges changes in both directions until neither side has any.
+fn sync(a: &DatasetStore, b: &DatasetStore) {
+    while deliver(a, b) + deliver(b, a) > 0 {}
+}
+
+/// The dataset's rows as column → value maps, sorted, so that replicas with
+/// different column orders compare equal.
+fn contents(store: &DatasetStore, id: &DatasetId) -> Vec<BTreeMap<String, Value>> {
+    let result = store.query_dataset(id, 0, 1000, None, None, false).unwrap();
+    let mut rows: Vec<BTreeMap<String, Value>> = result
+        .rows
+        .into_iter()
+        .map(|row| result.columns.iter().cloned().zip(row).collect())
+        .collect();
+    rows.sort_by_key(|r| serde_json::to_string(r).unwrap());
+    rows
+}
+
+fn shared_dataset(a: &DatasetStore, b: &DatasetStore) -> DatasetId {
+    let meta = a
+        .create_empty(
+            "Inventory",
+            &[
+                ColumnDef { name: "item".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "qty".into(), column_type: "INTEGER".into() },
+            ],
+            Some("stock"),
+        )
+        .unwrap();
+    a.insert_row(&meta.id, &[("item", json!("bolt")), ("qty", json!(10))]).unwrap();
+    a.insert_row(&meta.id, &[("item", json!("nut")), ("qty", json!(20))]).unwrap();
+    sync(a, b);
+    meta.id
+}
+
+fn column_names(store: &DatasetStore, id: &DatasetId) -> Vec<String> {
+    let mut names: Vec<String> = store.get_columns(id).unwrap().into_iter().map(|c| c.name).collect();
+    names.sort();
+    names
+}
+
+// -- Tests --
+
+#[test]
+fn nothing_is_journaled_without_replication() {
+    let store = DatasetStore::open_in_memory().unwrap();
+    store
+        .create_empty("t", &[ColumnDef { name: "a".into(), column_type: "TEXT".into() }], None)
+        .unwrap();
+    assert!(store.take_pending_changes(100).unwrap().is_empty());
+}
+
+#[test]
+fn new_dataset_replicates_with_rows_and_meta() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    let meta = b.get(&id).unwrap();
+    assert_eq!(meta.name, "Inventory");
+    assert_eq!(meta.category.as_deref(), Some("stock"));
+    assert_eq!(meta.row_count, 2);
+    assert_eq!(contents(&a, &id), contents(&b, &id));
+
+    // Replays change nothing.
+    a.insert_row(&id, &[("item", json!("washer")), ("qty", json!(5))]).unwrap();
+    let changes = a.take_pending_changes(100).unwrap();
+    for change in changes.iter().chain(changes.iter()) {
+        b.apply_replicated(&change.dataset_id, &change.op, &change.stamp).unwrap();
+    }
+    assert_eq!(b.get(&id).unwrap().row_count, 3);
+}
+
+#[test]
+fn existing_datasets_replicate_when_adopted() {
+    let a = DatasetStore::open_in_memory().unwrap();
+    let meta = a.import_csv_content("x,y\n1,2\n3,4\n", "Imported", None).unwrap();
+    a.enable_replication("a");
+    assert_eq!(a.replicate_all().unwrap(), 1);
+    assert_eq!(a.replicate_all().unwrap(), 0);
+
+    let b = replica("b");
+    sync(&a, &b);
+    assert_eq!(b.get(&meta.id).unwrap().name, "Imported");
+    assert_eq!(contents(&a, &meta.id), contents(&b, &meta.id));
+}
+
+#[test]
+fn concurrent_cell_edits_converge_on_the_later_one() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    a.update_cell(&id, 0, "qty", json!(11)).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    b.update_cell(&id, 0, "qty", json!(12)).unwrap();
+    // Different cells of the same row both survive.
+    a.update_cell(&id, 1, "item", json!("hex nut")).unwrap();
+    sync(&a, &b);
+
+    let rows = contents(&a, &id);
+    assert_eq!(rows, contents(&b, &id));
+    assert!(rows.iter().any(|r| r["qty"] == json!(12)));
+    assert!(rows.iter().any(|r| r["item"] == json!("hex nut")));
+}
+
+#[test]
+fn row_delete_wins_over_concurrent_edit() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    a.delete_rows(&id, &[0]).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    b.update_cell(&id, 0, "qty", json!(99)).unwrap();
+    sync(&a, &b);
+
+    assert_eq!(a.get(&id).unwrap().row_count, 1);
+    assert_eq!(contents(&a, &id), contents(&b, &id));
+}
+
+#[test]
+fn column_changes_merge_by_id() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    // A renames a column while B edits a cell in it.
+    a.rename_column(&id, "qty", "quantity").unwrap();
+    b.update_cell(&id, 0, "qty", json!(7)).unwrap();
+    sync(&a, &b);
+    assert_eq!(column_names(&a, &id), ["item", "quantity"]);
+    assert_eq!(column_names(&b, &id), ["item", "quantity"]);
+    assert_eq!(contents(&a, &id), contents(&b, &id));
+    assert!(contents(&a, &id).iter().any(|r| r["quantity"] == json!(7)));
+
+    // Concurrent renames of one column: the later name wins.
+    a.rename_column(&id, "item", "part").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    b.rename_column(&id, "item", "sku").unwrap();
+    sync(&a, &b);
+    assert_eq!(column_names(&a, &id), ["quantity", "sku"]);
+    assert_eq!(column_names(&b, &id), ["quantity", "sku"]);
+
+    // A dropped column takes concurrent edits to it along.
+    a.drop_column(&id, "quantity").unwrap();
+    b.update_cell(&id, 1, "quantity", json!(1)).unwrap();
+    sync(&a, &b);
+    assert_eq!(column_names(&b, &id), ["sku"]);
+    assert_eq!(contents(&a, &id), contents(&b, &id));
+}
+
+#[test]
+fn concurrently_added_columns_with_one_name_both_survive() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    a.add_column(&id, "price", "REAL", None).unwrap();
+    b.add_column(&id, "price", "TEXT", Some("n/a")).unwrap();
+    sync(&a, &b);
+
+    let names = column_names(&a, &id);
+    assert_eq!(names, column_names(&b, &id));
+    assert_eq!(names.len(), 4);
+    assert!(names.contains(&"price".to_string()));
+    assert!(names.iter().any(|n| n.starts_with("price (")));
+    assert_eq!(contents(&a, &id), contents(&b, &id));
+}
+
+#[test]
+fn meta_changes_and_dataset_delete_replicate() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    a.rename(&id, "Parts").unwrap();
+    b.set_category(&id, None).unwrap();
+    sync(&a, &b);
+    let (ma, mb) = (a.get(&id).unwrap(), b.get(&id).unwrap());
+    assert_eq!((ma.name.as_str(), ma.category.as_deref()), (mb.name.as_str(), mb.category.as_deref()));
+
+    a.delete(&id).unwrap();
+    b.insert_row(&id, &[("item", json!("late")), ("qty", json!(1))]).unwrap();
+    sync(&a, &b);
+    assert!(matches!(b.get(&id), Err(DatasetError::NotFound(_))));
+    assert!(matches!(a.get(&id), Err(DatasetError::NotFound(_))));
+}
+
+#[test]
+fn formula_columns_replicate_with_meta() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    a.set_formula_column(&id, "double", "qty * 2").unwrap();
+    sync(&a, &b);
+    assert_eq!(b.list_formula_columns(&id).unwrap(), a.list_formula_columns(&id).unwrap());
+    let doubled = b.query_dataset(&id, 0, 10, None, Some("qty"), false).unwrap().rows;
+    assert_eq!(doubled[0][2], json!(20));
+
+    b.remove_formula_column(&id, "double").unwrap();
+    sync(&a, &b);
+    assert!(a.list_formula_columns(&id).unwrap().is_empty());
+}
+
+#[test]
+fn column_constraints_replicate_with_meta() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+
+    let positive = ColumnConstraints { column: "qty".into(), min: Some(0.0), ..ColumnConstraints::default() };
+    a.set_column_constraints(&id, &positive).unwrap();
+    sync(&a, &b);
+    assert_eq!(b.list_column_constraints(&id).unwrap(), vec![positive]);
+    assert!(matches!(
+        b.insert_row(&id, &[("item", json!("bolt")), ("qty", json!(-1))]),
+        Err(DatasetError::ConstraintViolated(_))
+    ));
+}
+
+#[test]
+fn undo_replicates_like_an_edit() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+    let original = contents(&a, &id);
+
+    a.execute_sql_v2(&format!("DELETE FROM {}", dataset_table_name(&id)), 0, 10, false).unwrap();
+    sync(&a, &b);
+    assert!(contents(&b, &id).is_empty());
+
+    a.undo(&id).unwrap().unwrap();
+    sync(&a, &b);
+    assert_eq!(contents(&b, &id), original);
+}
+
+#[test]
+fn sql_mutations_replicate_as_row_and_column_changes() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+    let table = id.table_name();
+
+    a.execute_mutation(&format!("UPDATE {table} SET qty = qty * 2 WHERE item = 'bolt'"), false).unwrap();
+    a.execute_mutation(&format!("INSERT INTO {table} (item, qty) VALUES ('gear', 3)"), false).unwrap();
+    a.execute_mutation(&format!("DELETE FROM {table} WHERE item = 'nut'"), false).unwrap();
+    a.execute_mutation(&format!("ALTER TABLE {table} RENAME COLUMN qty TO count"), false).unwrap();
+    // Dry runs journal nothing.
+    a.execute_mutation(&format!("DELETE FROM {table}"), true).unwrap();
+    sync(&a, &b);
+
+    assert_eq!(column_names(&b, &id), ["count", "item"]);
+    assert_eq!(
+        contents(&b, &id),
+        vec![
+            BTreeMap::from([("count".to_string(), json!(20)), ("item".to_string(), json!("bolt"))]),
+            BTreeMap::from([("count".to_string(), json!(3)), ("item".to_string(), json!("gear"))]),
+        ]
+    );
+}
+
+#[test]
+fn sql_mutations_replicate_whatever_their_form() {
+    let (a, b) = (replica("a"), replica("b"));
+    let id = shared_dataset(&a, &b);
+    let table = id.table_name();
+
+    for statement in [
+        format!("UPDATE OR IGNORE {table} SET qty = qty + 1 WHERE item = 'bolt'"),
+        format!(
+            "WITH picked AS (SELECT 'nut' AS item) \
+             UPDATE {table} SET qty = 0 WHERE item IN (SELECT item FROM picked)"
+        ),
+        format!("REPLACE INTO {table} (item, qty) VALUES ('gear', 3)"),
+        format!("INSERT OR REPLACE INTO {table} (item, qty) VALUES ('cog', 4)"),
+        format!("DELETE FROM {table} WHERE item = 'gear';"),
+    ] {
+        a.execute_mutation(&statement, false).unwrap();
+    }
+    sync(&a, &b);
+
+    let row = |item: &str, qty: i64| {
+        BTreeMap::from([("item".to_string(), json!(item)), ("qty".to_string(), json!(qty))])
+    };
+    assert_eq!(contents(&b, &id), vec![row("bolt", 11), row("cog", 4), row("nut", 0)]);
+
+    a.execute_mutation(&format!("DELETE FROM {table};"), false).unwrap();
+    sync(&a, &b);
+    assert_eq!(contents(&b, &id), vec![]);
+}
+
+#[test]
+fn changes_arriving_out_of_order_still_converge() {
+    let (a, b) = (replica("a"), replica("b"));
+    let meta = a
+        .create_empty("Log", &[ColumnDef { name: "msg".into(), column_type: "TEXT".into() }], None)
+        .unwrap();
+    a.insert_row(&meta.id, &[("msg", json!("hello"))]).unwrap();
+    a.rename_column(&meta.id, "msg", "message").unwrap();
+
+    // Deliver the rename before the column it renames.
+    let mut changes = a.take_pending_changes(100).unwrap();
+    let rename = changes.iter().position(|c| matches!(c.op, DatasetOp::RenameColumn { .. })).unwrap();
+    let rename = changes.remove(rename);
+    b.apply_replicated(&rename.dataset_id, &rename.op, &rename.stamp).unwrap();
+    for change in &changes {
+        b.apply_replicated(&change.dataset_id, &change.op, &change.stamp).unwrap();
+    }
+
+    assert_eq!(column_names(&b, &meta.id), ["message"]);
+    assert_eq!(contents(&a, &meta.id), contents(&b, &meta.id));
+}
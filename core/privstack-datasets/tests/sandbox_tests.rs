@@ -0,0 +1,182 @@
+//! SQL sandbox: user-supplied SQL may only touch dataset tables, call
+//! whitelisted functions and run within the store's limits.
+
+use pretty_assertions::assert_eq;
+use privstack_datasets::*;
+use serde_json::json;
+use std::time::Duration;
+
+// -- Helpers --
+
+fn populated() -> (DatasetStore, DatasetMeta) {
+    let store = DatasetStore::open_in_memory().unwrap();
+    let meta = store
+        .create_empty(
+            "people",
+            &[
+                ColumnDef { name: "name".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "age".into(), column_type: "INTEGER".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 35)] {
+        store.insert_row(&meta.id, &[("name", json!(name)), ("age", json!(age))]).unwrap();
+    }
+    (store, meta)
+}
+
+fn assert_not_authorized<T: std::fmt::Debug>(result: DatasetResult<T>) {
+    match result {
+        Err(DatasetError::NotAuthorized(_)) => {}
+        other => panic!("expected NotAuthorized, got {other:?}"),
+    }
+}
+
+// -- Reads --
+
+#[test]
+fn queries_over_datasets_and_allowed_functions_run() {
+    let (store, meta) = populated();
+    let table = meta.id.table_name();
+    let result = store
+        .execute_raw_query(
+            &format!(
+                "WITH older AS (SELECT name, age FROM {table} WHERE age > 26) \
+                 SELECT upper(name) AS n, round(avg(age), 1) AS a, count(*) AS c FROM older"
+            ),
+            0,
+            10,
+        )
+        .unwrap();
+    assert_eq!(result.rows, vec![vec![json!("ALICE"), json!(32.5), json!(2)]]);
+}
+
+#[test]
+fn hostile_reads_are_refused() {
+    let (store, meta) = populated();
+    let table = meta.id.table_name();
+    let hostile = [
+        "SELECT * FROM _datasets_meta".to_string(),
+        "SELECT sql FROM sqlite_master".to_string(),
+        "SELECT * FROM pragma_table_info('_datasets_meta')".to_string(),
+        format!("SELECT load_extension('/tmp/evil.so') FROM {table}"),
+        "SELECT sqlite_version()".to_string(),
+        "SELECT hex(randomblob(1000000))".to_string(),
+    ];
+    for sql in &hostile {
+        assert_not_authorized(store.execute_raw_query(sql, 0, 10));
+    }
+
+    // Escaping the pagination wrapper fails: the query is prepared on its own first.
+    let escape = format!("SELECT name FROM {table}) UNION SELECT id FROM (SELECT id FROM _datasets_meta");
+    assert!(store.execute_raw_query(&escape, 0, 10).is_err());
+}
+
+#[test]
+fn writable_statements_are_refused_on_the_read_path() {
+    let (store, meta) = populated();
+    let table = meta.id.table_name();
+
+    let cte_delete = format!("WITH x AS (SELECT 1) DELETE FROM {table}");
+    assert!(matches!(
+        store.execute_raw_query(&cte_delete, 0, 10),
+        Err(DatasetError::InvalidQuery(_) | DatasetError::NotAuthorized(_))
+    ));
+    // The unified entry point classifies it as a query and refuses it too.
+    assert!(store.execute_sql_v2(&cte_delete, 0, 10, false).is_err());
+    assert!(store.execute_raw_query("PRAGMA writable_schema = ON", 0, 10).is_err());
+    assert_eq!(store.get(&meta.id).unwrap().row_count, 3);
+}
+
+#[test]
+fn page_size_is_capped_at_the_row_limit() {
+    let (store, meta) = populated();
+    store.set_sql_limits(SqlLimits { max_rows: 2, ..SqlLimits::default() });
+    let table = meta.id.table_name();
+    let result = store.execute_raw_query(&format!("SELECT * FROM {table}"), 0, 100).unwrap();
+    assert_eq!(result.rows.len(), 2);
+    assert_eq!(result.total_count, 3);
+    assert_eq!(store.aggregate_query(&meta.id, "name", "age", None, None, None).unwrap().len(), 2);
+}
+
+#[test]
+fn runaway_queries_time_out() {
+    let (store, _) = populated();
+    store.set_sql_limits(SqlLimits { timeout: Duration::from_millis(50), ..SqlLimits::default() });
+    let endless = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) \
+                   SELECT count(*) FROM n";
+    match store.execute_raw_query(endless, 0, 10) {
+        Err(DatasetError::QueryTimeout(50)) => {}
+        other => panic!("expected QueryTimeout, got {other:?}"),
+    }
+}
+
+// -- Mutations --
+
+#[test]
+fn hostile_mutations_are_refused() {
+    let (store, meta) = populated();
+    let table = meta.id.table_name();
+    let hostile = [
+        "ATTACH DATABASE '/tmp/stolen.db' AS stolen".to_string(),
+        "PRAGMA journal_mode = OFF".to_string(),
+        "DELETE FROM _datasets_meta".to_string(),
+        "UPDATE _datasets_meta SET name = 'pwned'".to_string(),
+        format!("INSERT INTO {table} (name) SELECT id FROM _datasets_meta"),
+        format!("UPDATE {table} SET name = load_extension('/tmp/evil.so')"),
+        "CREATE TABLE ds_00000000000000000000000000000000 (x)".to_string(),
+        format!("DROP TABLE {table}"),
+        format!("CREATE TRIGGER t AFTER INSERT ON {table} BEGIN DELETE FROM _datasets_meta; END"),
+        "BEGIN".to_string(),
+    ];
+    for sql in &hostile {
+        for dry_run in [true, false] {
+            assert_not_authorized(store.execute_mutation(sql, dry_run));
+        }
+    }
+    assert_eq!(store.get(&meta.id).unwrap().name, "people");
+    assert_eq!(store.list().unwrap().len(), 1);
+}
+
+#[test]
+fn dataset_mutations_run_and_hooks_are_removed_afterwards() {
+    let (store, meta) = populated();
+    let table = meta.id.table_name();
+    store
+        .execute_mutation(&format!("UPDATE {table} SET age = abs(age - 100) WHERE name = 'Bob'"), false)
+        .unwrap();
+    store
+        .execute_mutation(&format!("ALTER TABLE {table} ADD COLUMN email TEXT"), false)
+        .unwrap();
+    store
+        .execute_mutation(&format!("ALTER TABLE {table} RENAME COLUMN email TO mail"), false)
+        .unwrap();
+    assert_not_authorized(store.execute_mutation("DELETE FROM _datasets_meta", false));
+
+    // Internal operations on metadata tables are unaffected by the sandbox.
+    store.rename(&meta.id, "staff").unwrap();
+    let columns: Vec<String> =
+        store.get_columns(&meta.id).unwrap().into_iter().map(|c| c.name).collect();
+    assert_eq!(columns, ["name", "age", "mail"]);
+    let rows = store.query_dataset(&meta.id, 0, 10, Some("Bob"), None, false).unwrap();
+    assert_eq!(rows.rows[0][1], json!(75));
+}
+
+// -- Aggregations --
+
+#[test]
+fn aggregations_parse_case_insensitively_and_reject_unknown_names() {
+    assert_eq!("SUM".parse::<Aggregation>().unwrap(), Aggregation::Sum);
+    assert_eq!("avg".parse::<Aggregation>().unwrap(), Aggregation::Avg);
+    for hostile in ["sum(1)); DROP TABLE x; --", "load_extension", "median", ""] {
+        assert!(hostile.parse::<Aggregation>().is_err());
+    }
+
+    let (store, meta) = populated();
+    let total = store
+        .aggregate_query(&meta.id, "name", "age", Some(Aggregation::Count), None, None)
+        .unwrap();
+    assert_eq!(total.len(), 3);
+    assert!(total.iter().all(|(_, count)| *count == json!(1)));
+}
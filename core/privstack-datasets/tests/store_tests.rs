@@ -148,7 +148,7 @@ fn delete_cascades_relations_and_views() {
     let ds1 = create_test_dataset(&s);
     let ds2 = s.create_empty("ds2", &[ColumnDef { name: "x".into(), column_type: "INTEGER".into() }], None).unwrap();
     s.create_relation(&ds1.id, "name", &ds2.id, "x").unwrap();
-    s.create_view(&ds1.id, "view1", &ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None }).unwrap();
+    s.create_view(&ds1.id, "view1", &ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None, pivot: None, filter: None }).unwrap();
     s.link_row_to_page(&ds1.id, "row-1", "page-1").unwrap();
 
     s.delete(&ds1.id).unwrap();
@@ -484,7 +484,7 @@ fn aggregate_query_no_aggregation() {
 fn aggregate_query_with_sum() {
     let s = store();
     let meta = create_and_populate(&s);
-    let result = s.aggregate_query(&meta.id, "name", "score", Some("SUM"), None, None).unwrap();
+    let result = s.aggregate_query(&meta.id, "name", "score", Some(Aggregation::Sum), None, None).unwrap();
     assert_eq!(result.len(), 3); // 3 unique names, each with their sum
 }
 
@@ -494,7 +494,7 @@ fn aggregate_query_with_sum() {
 fn aggregate_query_grouped_basic() {
     let s = store();
     let meta = create_and_populate(&s);
-    let result = s.aggregate_query_grouped(&meta.id, "name", "score", "age", Some("SUM"), None).unwrap();
+    let result = s.aggregate_query_grouped(&meta.id, "name", "score", "age", Some(Aggregation::Sum), None).unwrap();
     assert_eq!(result.len(), 3); // Each name+age combo is unique
 }
 
@@ -708,6 +708,8 @@ fn create_and_list_views() {
             direction: SortDirection::Asc,
         }],
         group_by: None,
+        pivot: None,
+        filter: None,
     };
     let view = s.create_view(&meta.id, "filtered_view", &config).unwrap();
     assert_eq!(view.name, "filtered_view");
@@ -723,7 +725,7 @@ fn create_and_list_views() {
 fn update_view_config() {
     let s = store();
     let meta = create_test_dataset(&s);
-    let config = ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None };
+    let config = ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None, pivot: None, filter: None };
     let view = s.create_view(&meta.id, "v", &config).unwrap();
 
     let new_config = ViewConfig {
@@ -731,6 +733,8 @@ fn update_view_config() {
         filters: vec![],
         sorts: vec![],
         group_by: Some("name".into()),
+        pivot: None,
+        filter: None,
     };
     s.update_view(&view.id, &new_config).unwrap();
 
@@ -743,7 +747,7 @@ fn update_view_config() {
 fn delete_view() {
     let s = store();
     let meta = create_test_dataset(&s);
-    let config = ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None };
+    let config = ViewConfig { visible_columns: None, filters: vec![], sorts: vec![], group_by: None, pivot: None, filter: None };
     let view = s.create_view(&meta.id, "v", &config).unwrap();
     s.delete_view(&view.id).unwrap();
     let views = s.list_views(&meta.id).unwrap();
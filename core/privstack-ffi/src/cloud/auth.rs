@@ -47,11 +47,13 @@ pub unsafe extern "C" fn privstack_cloudsync_configure(
     let envelope_mgr = Arc::new(TokioMutex::new(EnvelopeManager::new(api.clone())));
     let share_mgr = Arc::new(ShareManager::new(api.clone()));
 
+    let dek_registry = DekRegistry::with_source(envelope_mgr.clone());
+
     handle.cloud_api = Some(api);
     handle.cloud_envelope_mgr = Some(envelope_mgr);
     handle.cloud_share_mgr = Some(share_mgr);
     handle.cloud_config = Some(config);
-    handle.cloud_dek_registry = Some(DekRegistry::new());
+    handle.cloud_dek_registry = Some(dek_registry);
 
     PrivStackError::Ok
 }
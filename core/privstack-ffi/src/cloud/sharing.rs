@@ -120,6 +120,60 @@ pub unsafe extern "C" fn privstack_cloudsync_revoke_share(
     }
 }
 
+/// Revokes a share and rotates the entity DEK for the remaining recipients.
+///
+/// The remaining recipients are the entity's shares the server still lists.
+/// On success `out_json` receives the rotation summary.
+///
+/// # Safety
+/// - `entity_id`, `recipient_email` must be valid null-terminated UTF-8 strings.
+/// - `out_json` must be a valid pointer. Result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloudsync_revoke_share_and_rotate(
+    entity_id: *const c_char,
+    recipient_email: *const c_char,
+    out_json: *mut *mut c_char,
+) -> PrivStackError {
+    let eid = match unsafe { parse_cstr(entity_id) } {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+    let email = match unsafe { parse_cstr(recipient_email) } {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = lock_handle();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let (share_mgr, envelope_mgr, dek_registry) = match (
+        handle.cloud_share_mgr.as_ref(),
+        handle.cloud_envelope_mgr.as_ref(),
+        handle.cloud_dek_registry.as_ref(),
+    ) {
+        (Some(s), Some(e), Some(d)) => (s.clone(), e.clone(), d.clone()),
+        _ => return PrivStackError::NotInitialized,
+    };
+
+    let result = handle.runtime.block_on(async {
+        let envelope_mgr = envelope_mgr.lock().await;
+        share_mgr
+            .revoke_share_and_rotate(&envelope_mgr, &dek_registry, eid, email)
+            .await
+    });
+
+    match result {
+        Ok(rotation) => write_json_out(out_json, &rotation),
+        Err(e) => cloud_err(&e),
+    }
+}
+
 /// Accepts a share invitation by token.
 ///
 /// # Safety
@@ -11,6 +11,7 @@ use privstack_cloud::types::*;
 use privstack_crypto::{DerivedKey, KEY_SIZE};
 use privstack_model::{Entity, EntitySchema};
 use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp};
+use privstack_sync::dataset_applicator::apply_dataset_event;
 use std::collections::HashMap;
 use std::ffi::c_char;
 use std::sync::Arc;
@@ -117,8 +118,16 @@ pub unsafe extern "C" fn privstack_cloudsync_start_sync(
     let inbound_store = handle.entity_store.clone();
     let inbound_schemas = handle.entity_registry.clone_schemas();
     let inbound_device_id = handle.peer_id.to_string();
+    let inbound_datasets = handle.dataset_store.clone();
     handle.runtime.spawn(async move {
-        consume_inbound_events(event_rx, inbound_store, inbound_schemas, inbound_device_id).await;
+        consume_inbound_events(
+            event_rx,
+            inbound_store,
+            inbound_datasets,
+            inbound_schemas,
+            inbound_device_id,
+        )
+        .await;
     });
 
     handle.cloud_sync_handle = Some(sync_handle);
@@ -126,6 +135,7 @@ pub unsafe extern "C" fn privstack_cloudsync_start_sync(
     handle.cloud_blob_mgr = Some(blob_mgr);
     handle.cloud_user_id = Some(user_id);
     handle.cloud_active_workspace = Some(active_ws_id);
+    crate::datasets::publish_dataset_changes(handle);
     PrivStackError::Ok
 }
 
@@ -441,11 +451,12 @@ pub unsafe extern "C" fn privstack_cloudsync_request_compaction(
 // ── Inbound Event Consumer ──
 
 /// Reads events pulled from S3 by the sync engine and applies them to the
-/// local entity store.  Runs as a background tokio task for the lifetime of
-/// the sync engine.
+/// local entity and dataset stores.  Runs as a background tokio task for the
+/// lifetime of the sync engine.
 async fn consume_inbound_events(
     mut rx: mpsc::Receiver<Event>,
     store: Arc<privstack_storage::EntityStore>,
+    datasets: Option<privstack_datasets::DatasetStore>,
     schemas: HashMap<String, EntitySchema>,
     device_id: String,
 ) {
@@ -456,11 +467,12 @@ async fn consume_inbound_events(
         }
 
         let store = store.clone();
+        let datasets = datasets.clone();
         let schemas = schemas.clone();
 
         // Entity store operations acquire a Mutex — run on a blocking thread.
         let result = tokio::task::spawn_blocking(move || {
-            apply_inbound_event(&event, &store, &schemas)
+            apply_inbound_event(&event, &store, datasets.as_ref(), &schemas)
         })
         .await;
 
@@ -478,12 +490,23 @@ async fn consume_inbound_events(
     ffi_info!("[cloud sync] inbound event consumer stopped");
 }
 
-/// Apply a single inbound event to the local entity store.
+/// Apply a single inbound event to the local entity and dataset stores.
 fn apply_inbound_event(
     event: &Event,
     store: &privstack_storage::EntityStore,
+    datasets: Option<&privstack_datasets::DatasetStore>,
     schemas: &HashMap<String, EntitySchema>,
 ) -> Result<(), String> {
+    // Dataset row and column changes belong to the dataset store alone;
+    // a dataset's name and category also update its entity below.
+    if let Some(datasets) = datasets {
+        match apply_dataset_event(datasets, event) {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => ffi_warn!("[cloud sync] failed to apply dataset event {}: {e}", event.id),
+        }
+    }
+
     match &event.payload {
         EventPayload::FullSnapshot {
             entity_type,
@@ -496,6 +519,10 @@ fn apply_inbound_event(
         | EventPayload::EntityUpdated {
             entity_type,
             json_data,
+        }
+        | EventPayload::ConflictResolved {
+            entity_type,
+            json_data,
         } => {
             let data: serde_json::Value =
                 serde_json::from_str(json_data).map_err(|e| format!("json parse: {e}"))?;
@@ -1,8 +1,14 @@
-//! FFI: Core CRUD — import, list, get, delete, rename, query, get_columns.
+//! FFI: Core CRUD — import, export, list, get, delete, rename, query, get_columns.
 
-use super::DatasetQueryRequest;
+use super::{DatasetQueryRequest, ExportRequest, ExportResponse, ImportFileRequest};
 use crate::{to_c_string, PrivStackError};
+use privstack_datasets::ImportProgress;
 use std::ffi::{c_char, CStr};
+use std::sync::Mutex;
+
+/// Progress of the running (or last) file import. Kept outside the handle
+/// lock, which the import holds, so the UI can poll it meanwhile.
+static IMPORT_PROGRESS: Mutex<Option<ImportProgress>> = Mutex::new(None);
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn privstack_dataset_import_csv(
@@ -14,7 +20,7 @@ pub unsafe extern "C" fn privstack_dataset_import_csv(
         let file_path = parse_cstr!(file_path, r#"{"error":"null pointer"}"#);
         let name = parse_cstr!(name, r#"{"error":"null pointer"}"#);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             match store.import_csv(std::path::Path::new(file_path), name) {
                 Ok(meta) => {
                     let json =
@@ -30,6 +36,73 @@ pub unsafe extern "C" fn privstack_dataset_import_csv(
     }
 }
 
+/// Import a file (CSV, TSV, JSON, NDJSON, XLSX or Parquet) into a new
+/// dataset. Poll `privstack_dataset_import_progress` while it runs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_import_file(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, ImportFileRequest);
+
+        *IMPORT_PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(ImportProgress::default());
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let result = store.import_file_with_progress(
+                std::path::Path::new(&req.path),
+                &req.name,
+                &req.options,
+                |progress| {
+                    *IMPORT_PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) =
+                        Some(progress.clone());
+                },
+            );
+            match result {
+                Ok(meta) => {
+                    let json =
+                        serde_json::to_string(&meta).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] import_file failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Progress of the running or last file import as JSON, or `null` if no
+/// import has run.
+#[unsafe(no_mangle)]
+pub extern "C" fn privstack_dataset_import_progress() -> *mut c_char {
+    let progress = IMPORT_PROGRESS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    to_c_string(&serde_json::to_string(&progress).unwrap_or_else(|_| "null".to_string()))
+}
+
+/// Export a dataset, view or saved query result to a file.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_export(request_json: *const c_char) -> *mut c_char {
+    unsafe {
+        let req = parse_json_request!(request_json, ExportRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            match store.export(&req.source, req.format, std::path::Path::new(&req.path)) {
+                Ok(rows) => {
+                    let json = serde_json::to_string(&ExportResponse { rows })
+                        .unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] export failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn privstack_dataset_list() -> *mut c_char {
     with_store_json!("[]", |store| {
@@ -102,7 +175,9 @@ pub unsafe extern "C" fn privstack_dataset_delete(dataset_id: *const c_char) ->
             Err(_) => return PrivStackError::InvalidArgument,
         };
 
-        match store.delete(&dataset_id) {
+        let result = store.delete(&dataset_id);
+        super::publish_dataset_changes(handle);
+        match result {
             Ok(()) => PrivStackError::Ok,
             Err(privstack_datasets::DatasetError::NotFound(_)) => PrivStackError::NotFound,
             Err(e) => {
@@ -149,7 +224,9 @@ pub unsafe extern "C" fn privstack_dataset_rename(
             Err(_) => return PrivStackError::InvalidArgument,
         };
 
-        match store.rename(&dataset_id, new_name) {
+        let result = store.rename(&dataset_id, new_name);
+        super::publish_dataset_changes(handle);
+        match result {
             Ok(()) => PrivStackError::Ok,
             Err(privstack_datasets::DatasetError::NotFound(_)) => PrivStackError::NotFound,
             Err(e) => {
@@ -233,3 +310,87 @@ pub unsafe extern "C" fn privstack_dataset_get_columns(
         })
     }
 }
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_list_formula_columns(
+    dataset_id: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.list_formula_columns(&dataset_id) {
+                Ok(formulas) => {
+                    let json =
+                        serde_json::to_string(&formulas).unwrap_or_else(|_| "[]".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] list_formula_columns failed: {e:?}");
+                    to_c_string("[]")
+                }
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_list_column_constraints(
+    dataset_id: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.list_column_constraints(&dataset_id) {
+                Ok(constraints) => {
+                    let json =
+                        serde_json::to_string(&constraints).unwrap_or_else(|_| "[]".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] list_column_constraints failed: {e:?}");
+                    to_c_string("[]")
+                }
+            }
+        })
+    }
+}
+
+/// Check every row of a dataset against its column constraints.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_validate(
+    dataset_id: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.validate_dataset(&dataset_id) {
+                Ok(report) => {
+                    let json = serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] validate failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
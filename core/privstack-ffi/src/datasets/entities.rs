@@ -0,0 +1,132 @@
+//! Entity tables for dataset SQL: the `entity:type` source.
+//!
+//! Each registered entity type reads as a table with `id`, `created_at`
+//! and `modified_at`, then one column per indexed field. Trashed entities
+//! are left out. A query issued on a plugin's behalf sees the entity types
+//! the plugin declared, or every type if it holds `CrossEntityRead`.
+
+use crate::PrivStackHandle;
+use privstack_datasets::{ColumnDef, DatasetError, DatasetResult, EntitySource, EntityTable};
+use privstack_model::{EntitySchema, FieldType};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Entity tables as seen by one caller.
+pub(crate) struct EntityTables<'h> {
+    handle: &'h PrivStackHandle,
+    /// Entity types the caller may read; `None` for all of them.
+    readable: Option<HashSet<String>>,
+}
+
+impl<'h> EntityTables<'h> {
+    /// Entity tables for the user, or for `plugin_id` when a plugin runs
+    /// the query.
+    pub(crate) fn new(handle: &'h PrivStackHandle, plugin_id: Option<&str>) -> Self {
+        let readable = plugin_id.map(|id| plugin_readable_types(handle, id));
+        Self { handle, readable }
+    }
+}
+
+impl EntitySource for EntityTables<'_> {
+    fn entity_table(&self, entity_type: &str, include_local_only: bool) -> DatasetResult<EntityTable> {
+        let schema = self
+            .handle
+            .entity_registry
+            .get_schema(entity_type)
+            .ok_or_else(|| DatasetError::NotFound(format!("entity type {entity_type}")))?;
+        if self.readable.as_ref().is_some_and(|types| !types.contains(entity_type)) {
+            return Err(DatasetError::NotAuthorized(format!(
+                "no permission to read entity:{entity_type}"
+            )));
+        }
+
+        let fields = entity_fields(schema);
+        let mut columns = vec![
+            ColumnDef { name: "id".into(), column_type: "TEXT".into() },
+            ColumnDef { name: "created_at".into(), column_type: "INTEGER".into() },
+            ColumnDef { name: "modified_at".into(), column_type: "INTEGER".into() },
+        ];
+        columns.extend(fields.iter().map(|(_, column)| column.clone()));
+
+        let entities = self
+            .handle
+            .entity_store
+            .list_entities(entity_type, false, None, None)
+            .map_err(|e| DatasetError::InvalidQuery(format!("Failed to read entity:{entity_type}: {e}")))?;
+        let rows = entities
+            .into_iter()
+            .filter(|entity| {
+                include_local_only
+                    || !entity.data.pointer("/local_only").and_then(Value::as_bool).unwrap_or(false)
+            })
+            .map(|entity| {
+                let mut row = vec![
+                    Value::String(entity.id),
+                    Value::from(entity.created_at),
+                    Value::from(entity.modified_at),
+                ];
+                row.extend(
+                    fields
+                        .iter()
+                        .map(|(path, _)| entity.data.pointer(path).cloned().unwrap_or(Value::Null)),
+                );
+                row
+            })
+            .collect();
+        Ok(EntityTable { columns, rows })
+    }
+}
+
+/// The indexed fields of a schema that become columns, with their JSON
+/// pointers. A field is named after its path (`/meta/due` → `meta_due`).
+/// Vector fields are left out, as are fields whose name is already taken.
+fn entity_fields(schema: &EntitySchema) -> Vec<(String, ColumnDef)> {
+    let mut taken: HashSet<String> = ["id", "created_at", "modified_at"].map(String::from).into();
+    let mut fields = Vec::new();
+    for field in &schema.indexed_fields {
+        let column_type = match field.field_type {
+            FieldType::Vector => continue,
+            FieldType::Number | FieldType::Decimal | FieldType::Counter | FieldType::Duration => "NUMERIC",
+            FieldType::Bool => "INTEGER",
+            FieldType::DateTime => "DATETIME",
+            FieldType::Text
+            | FieldType::Tag
+            | FieldType::Relation
+            | FieldType::Json
+            | FieldType::Enum
+            | FieldType::GeoPoint => "TEXT",
+        };
+        let name = field.field_path.trim_matches('/').replace('/', "_");
+        if name.is_empty() || !taken.insert(name.to_ascii_lowercase()) {
+            continue;
+        }
+        fields.push((
+            field.field_path.clone(),
+            ColumnDef { name, column_type: column_type.into() },
+        ));
+    }
+    fields
+}
+
+/// Entity types a plugin may read: the ones it declared, or all of them
+/// with `CrossEntityRead`. A plugin that isn't loaded may read none.
+#[cfg(feature = "wasm-plugins")]
+fn plugin_readable_types(handle: &PrivStackHandle, plugin_id: &str) -> HashSet<String> {
+    use privstack_plugin_host::Permission;
+
+    let Ok(plugin) = handle.plugin_host.get_plugin(plugin_id) else {
+        return HashSet::new();
+    };
+    if plugin.state().permissions.is_granted(Permission::CrossEntityRead) {
+        handle.entity_registry.clone_schemas().into_keys().collect()
+    } else {
+        plugin.declared_entity_types().clone()
+    }
+}
+
+/// Without the plugin host there is no way to check a plugin's
+/// permissions, so it may read no entity types.
+#[cfg(not(feature = "wasm-plugins"))]
+fn plugin_readable_types(_handle: &PrivStackHandle, _plugin_id: &str) -> HashSet<String> {
+    HashSet::new()
+}
@@ -0,0 +1,157 @@
+//! FFI: Change history — undo, redo, restore points and pruning.
+
+use super::{RestorePointRequest, RestoreRequest, RestoreResponse};
+use crate::to_c_string;
+use std::ffi::{c_char, CStr};
+
+/// List a dataset's change history, oldest first.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_history(dataset_id: *const c_char) -> *mut c_char {
+    unsafe {
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.history(&dataset_id) {
+                Ok(entries) => {
+                    let json =
+                        serde_json::to_string(&entries).unwrap_or_else(|_| "[]".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] history failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Undo a dataset's latest edit. Returns the entry undone, or `null`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_undo(dataset_id: *const c_char) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.undo(&dataset_id) {
+                Ok(entry) => {
+                    let json = serde_json::to_string(&entry).unwrap_or_else(|_| "null".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] undo failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Redo a dataset's edit undone last. Returns the entry redone, or `null`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_redo(dataset_id: *const c_char) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let id_str = parse_cstr!(dataset_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(id_str) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.redo(&dataset_id) {
+                Ok(entry) => {
+                    let json = serde_json::to_string(&entry).unwrap_or_else(|_| "null".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] redo failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Mark a dataset's current state as a named restore point.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_create_restore_point(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, RestorePointRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.create_restore_point(&dataset_id, &req.name) {
+                Ok(entry) => {
+                    let json = serde_json::to_string(&entry).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] create_restore_point failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Bring a dataset back to its state right after a history entry.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_restore(request_json: *const c_char) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, RestoreRequest);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.restore(&dataset_id, req.entry_id) {
+                Ok(changes) => {
+                    let json = serde_json::to_string(&RestoreResponse { changes })
+                        .unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] restore failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Prune every dataset's history to the store's history limits.
+#[unsafe(no_mangle)]
+pub extern "C" fn privstack_dataset_prune_history() -> *mut c_char {
+    with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        match store.prune_history() {
+            Ok(pruned) => to_c_string(&format!(r#"{{"pruned":{pruned}}}"#)),
+            Err(e) => {
+                ffi_error!("[FFI DATASET] prune_history failed: {e:?}");
+                to_c_string(&super::error_json(&e.to_string()))
+            }
+        }
+    })
+}
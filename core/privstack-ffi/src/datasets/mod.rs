@@ -11,6 +11,87 @@ pub(crate) fn error_json(msg: &str) -> String {
     format!(r#"{{"error":{escaped}}}"#)
 }
 
+/// Parse the optional aggregation of a chart request. An absent or empty
+/// value means no aggregation.
+pub(crate) fn parse_aggregation(
+    raw: Option<&str>,
+) -> privstack_datasets::DatasetResult<Option<privstack_datasets::Aggregation>> {
+    raw.filter(|a| !a.trim().is_empty()).map(str::parse).transpose()
+}
+
+/// Publishes journaled dataset changes as sync events. Each is signed and
+/// saved like any local event, then handed to the P2P orchestrator and to
+/// cloud sync when they run. Name changes also update the dataset's
+/// `"dataset"` entity, which is what makes entity sync pick the dataset up.
+pub(crate) fn publish_dataset_changes(handle: &crate::PrivStackHandle) {
+    use privstack_datasets::DatasetOp;
+    use privstack_sync::dataset_applicator::{dataset_change_event, DATASET_ENTITY_TYPE};
+
+    let Some(store) = handle.dataset_store.as_ref() else {
+        return;
+    };
+    loop {
+        let changes = match store.take_pending_changes(256) {
+            Ok(changes) if !changes.is_empty() => changes,
+            Ok(_) => return,
+            Err(e) => {
+                ffi_error!("[FFI DATASET] failed to read pending changes: {e:?}");
+                return;
+            }
+        };
+        for change in &changes {
+            let mut event = match dataset_change_event(change, handle.peer_id) {
+                Ok(event) => event,
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] failed to build change event: {e:?}");
+                    continue;
+                }
+            };
+            let entity_id = event.entity_id.to_string();
+            let entity_result = match &change.op {
+                DatasetOp::SetMeta { name, category, formulas, constraints } => {
+                    let now = event.timestamp.wall_time() as i64;
+                    handle.entity_store.save_entity_raw(&privstack_model::Entity {
+                        id: entity_id.clone(),
+                        entity_type: DATASET_ENTITY_TYPE.to_string(),
+                        data: serde_json::json!({
+                            "name": name,
+                            "category": category,
+                            "formulas": formulas,
+                            "constraints": constraints,
+                        }),
+                        created_at: now,
+                        modified_at: now,
+                        created_by: handle.peer_id.to_string(),
+                    })
+                }
+                DatasetOp::DeleteDataset => handle.entity_store.delete_entity(&entity_id),
+                _ => Ok(()),
+            };
+            if let Err(e) = entity_result {
+                ffi_warn!("[FFI DATASET] failed to update dataset entity {entity_id}: {e:?}");
+            }
+
+            crate::sign_local_event(handle, &mut event);
+            if let Err(e) = handle.event_store.save_event(&event) {
+                ffi_error!("[FFI DATASET] failed to save change event: {e:?}");
+                continue;
+            }
+            let _ = handle.entity_store.invalidate_sync_ledger_for_entity(&entity_id);
+            if let Some(orch_handle) = &handle.orchestrator_handle {
+                if let Err(e) = handle.runtime.block_on(orch_handle.record_event(event.clone())) {
+                    ffi_warn!("[FFI DATASET] failed to record change event: {e:?}");
+                }
+            }
+            if let Some(tx) = &handle.cloud_event_tx {
+                if tx.blocking_send(event).is_err() {
+                    ffi_warn!("[FFI DATASET] cloud sync channel closed");
+                }
+            }
+        }
+    }
+}
+
 /// Helper: acquire the HANDLE lock + dataset store, or return an error C string.
 macro_rules! with_store_json {
     ($fallback:expr, |$store:ident| $body:expr) => {{
@@ -27,6 +108,25 @@ macro_rules! with_store_json {
     }};
 }
 
+/// Like `with_store_json!`, for bodies that change datasets: publishes the
+/// resulting dataset changes for sync once the body has run.
+macro_rules! with_store_json_mut {
+    ($fallback:expr, |$store:ident| $body:expr) => {{
+        let handle = crate::lock_handle();
+        let handle = match handle.as_ref() {
+            Some(h) => h,
+            None => return to_c_string($fallback),
+        };
+        let $store = match handle.dataset_store.as_ref() {
+            Some(s) => s,
+            None => return to_c_string($fallback),
+        };
+        let result = $body;
+        $crate::datasets::publish_dataset_changes(handle);
+        result
+    }};
+}
+
 /// Helper: parse a C string into &str, or return error JSON.
 macro_rules! parse_cstr {
     ($ptr:expr, $fallback:expr) => {{
@@ -70,6 +170,8 @@ macro_rules! check_license_json {
 }
 
 mod crud;
+mod entities;
+mod history;
 mod mutations;
 mod queries;
 mod relations;
@@ -108,6 +210,14 @@ pub(crate) struct UpdateViewRequest {
     pub config: privstack_datasets::ViewConfig,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct ViewQueryRequest {
+    pub dataset_id: String,
+    pub config: privstack_datasets::ViewConfig,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct AggregateQueryRequest {
     pub dataset_id: String,
@@ -147,6 +257,17 @@ pub(crate) struct GroupedAggregateQueryResponse {
     pub series: Vec<AggregateSeriesData>,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct PivotRequest {
+    pub dataset_id: String,
+    pub config: privstack_datasets::PivotConfig,
+    #[serde(default)]
+    pub filters: Vec<privstack_datasets::ViewFilter>,
+    /// A filter tree, AND-ed with `filters`.
+    #[serde(default)]
+    pub filter: Option<privstack_datasets::FilterNode>,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct RawSqlRequest {
     pub sql: String,
@@ -160,6 +281,9 @@ pub(crate) struct SqlV2Request {
     pub page: Option<i64>,
     pub page_size: Option<i64>,
     pub dry_run: Option<bool>,
+    /// Plugin running the query, whose permissions decide which
+    /// `entity:` tables it may read.
+    pub plugin_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -171,6 +295,20 @@ pub(crate) struct SavedQueryRequest {
     pub is_view: Option<bool>,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct SavedQueryMaterializationRequest {
+    pub query_id: String,
+    /// How the cached rows are rebuilt; `None` stops materializing.
+    pub refresh: Option<privstack_datasets::RefreshPolicy>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SavedQueryPageRequest {
+    pub query_id: String,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct CreateEmptyRequest {
     pub name: String,
@@ -189,6 +327,28 @@ pub(crate) struct ImportContentRequest {
     pub content: String,
     pub name: String,
     pub category: Option<String>,
+    /// Text format of `content`; CSV when absent.
+    pub format: Option<privstack_datasets::FileFormat>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ImportFileRequest {
+    pub path: String,
+    pub name: String,
+    #[serde(default)]
+    pub options: privstack_datasets::ImportOptions,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ExportRequest {
+    pub source: privstack_datasets::ExportSource,
+    pub format: privstack_datasets::FileFormat,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ExportResponse {
+    pub rows: u64,
 }
 
 #[derive(Deserialize)]
@@ -219,3 +379,33 @@ pub(crate) struct ColumnModifyRequest {
     pub default_value: Option<String>,
     pub new_name: Option<String>,
 }
+
+#[derive(Deserialize)]
+pub(crate) struct RestorePointRequest {
+    pub dataset_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RestoreRequest {
+    pub dataset_id: String,
+    pub entry_id: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RestoreResponse {
+    pub changes: usize,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FormulaColumnRequest {
+    pub dataset_id: String,
+    pub name: String,
+    pub expression: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ColumnConstraintsRequest {
+    pub dataset_id: String,
+    pub constraints: privstack_datasets::ColumnConstraints,
+}
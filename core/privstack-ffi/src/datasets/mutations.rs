@@ -1,9 +1,9 @@
 //! FFI: Dataset creation, row CRUD, and column CRUD mutations.
 
 use super::{
-    ColumnModifyRequest, CreateEmptyRequest,
-    DeleteRowsRequest, DuplicateRequest, ImportContentRequest, InsertRowRequest,
-    UpdateCellRequest,
+    ColumnConstraintsRequest, ColumnModifyRequest, CreateEmptyRequest,
+    DeleteRowsRequest, DuplicateRequest, FormulaColumnRequest, ImportContentRequest,
+    InsertRowRequest, UpdateCellRequest,
 };
 use crate::{to_c_string, PrivStackError};
 use std::ffi::{c_char, CStr};
@@ -17,7 +17,7 @@ pub unsafe extern "C" fn privstack_dataset_create_empty(
         check_license_json!();
         let req = parse_json_request!(request_json, CreateEmptyRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             match store.create_empty(&req.name, &req.columns, req.category.as_deref()) {
                 Ok(meta) => {
                     let json =
@@ -42,7 +42,7 @@ pub unsafe extern "C" fn privstack_dataset_duplicate(
         check_license_json!();
         let req = parse_json_request!(request_json, DuplicateRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             let source_id = match uuid::Uuid::parse_str(&req.source_dataset_id) {
                 Ok(u) => privstack_datasets::DatasetId(u),
                 Err(_) => return to_c_string(r#"{"error":"invalid source dataset id"}"#),
@@ -63,7 +63,8 @@ pub unsafe extern "C" fn privstack_dataset_duplicate(
     }
 }
 
-/// Import dataset from CSV content string.
+/// Import dataset from text content: CSV unless the request names another
+/// text format (TSV, JSON or NDJSON).
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn privstack_dataset_import_content(
     request_json: *const c_char,
@@ -72,8 +73,13 @@ pub unsafe extern "C" fn privstack_dataset_import_content(
         check_license_json!();
         let req = parse_json_request!(request_json, ImportContentRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
-            match store.import_csv_content(&req.content, &req.name, req.category.as_deref()) {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            match store.import_content(
+                &req.content,
+                req.format.unwrap_or(privstack_datasets::FileFormat::Csv),
+                &req.name,
+                req.category.as_deref(),
+            ) {
                 Ok(meta) => {
                     let json =
                         serde_json::to_string(&meta).unwrap_or_else(|_| "{}".to_string());
@@ -97,7 +103,7 @@ pub unsafe extern "C" fn privstack_dataset_insert_row(
         check_license_json!();
         let req = parse_json_request!(request_json, InsertRowRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
                 Ok(u) => privstack_datasets::DatasetId(u),
                 Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
@@ -129,7 +135,7 @@ pub unsafe extern "C" fn privstack_dataset_update_cell(
         check_license_json!();
         let req = parse_json_request!(request_json, UpdateCellRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
                 Ok(u) => privstack_datasets::DatasetId(u),
                 Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
@@ -155,7 +161,7 @@ pub unsafe extern "C" fn privstack_dataset_delete_rows(
         check_license_json!();
         let req = parse_json_request!(request_json, DeleteRowsRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
                 Ok(u) => privstack_datasets::DatasetId(u),
                 Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
@@ -181,7 +187,7 @@ pub unsafe extern "C" fn privstack_dataset_add_column(
         check_license_json!();
         let req = parse_json_request!(request_json, ColumnModifyRequest);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
             let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
                 Ok(u) => privstack_datasets::DatasetId(u),
                 Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
@@ -241,7 +247,9 @@ pub unsafe extern "C" fn privstack_dataset_drop_column(
             Err(_) => return PrivStackError::InvalidArgument,
         };
 
-        match store.drop_column(&dataset_id, &req.column_name) {
+        let result = store.drop_column(&dataset_id, &req.column_name);
+        super::publish_dataset_changes(handle);
+        match result {
             Ok(()) => PrivStackError::Ok,
             Err(e) => {
                 ffi_error!("[FFI DATASET] drop_column failed: {e:?}");
@@ -292,7 +300,9 @@ pub unsafe extern "C" fn privstack_dataset_alter_column_type(
             Err(_) => return PrivStackError::InvalidArgument,
         };
 
-        match store.alter_column_type(&dataset_id, &req.column_name, new_type) {
+        let result = store.alter_column_type(&dataset_id, &req.column_name, new_type);
+        super::publish_dataset_changes(handle);
+        match result {
             Ok(()) => PrivStackError::Ok,
             Err(e) => {
                 ffi_error!("[FFI DATASET] alter_column_type failed: {e:?}");
@@ -343,7 +353,9 @@ pub unsafe extern "C" fn privstack_dataset_rename_column(
             Err(_) => return PrivStackError::InvalidArgument,
         };
 
-        match store.rename_column(&dataset_id, &req.column_name, new_name) {
+        let result = store.rename_column(&dataset_id, &req.column_name, new_name);
+        super::publish_dataset_changes(handle);
+        match result {
             Ok(()) => PrivStackError::Ok,
             Err(e) => {
                 ffi_error!("[FFI DATASET] rename_column failed: {e:?}");
@@ -352,3 +364,87 @@ pub unsafe extern "C" fn privstack_dataset_rename_column(
         }
     }
 }
+
+/// Add a formula column to a dataset, or change its expression.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_set_formula_column(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, FormulaColumnRequest);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+            let Some(expression) = req.expression.as_deref() else {
+                return to_c_string(r#"{"error":"missing expression"}"#);
+            };
+
+            match store.set_formula_column(&dataset_id, &req.name, expression) {
+                Ok(formula) => {
+                    let json = serde_json::to_string(&formula).unwrap_or_default();
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] set_formula_column failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Remove a formula column from a dataset.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_remove_formula_column(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, FormulaColumnRequest);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.remove_formula_column(&dataset_id, &req.name) {
+                Ok(()) => to_c_string(r#"{"ok":true}"#),
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] remove_formula_column failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Set the constraints of a column; constraints without rules remove them.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_set_column_constraints(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, ColumnConstraintsRequest);
+
+        with_store_json_mut!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.set_column_constraints(&dataset_id, &req.constraints) {
+                Ok(()) => to_c_string(r#"{"ok":true}"#),
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] set_column_constraints failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
@@ -1,9 +1,10 @@
 //! FFI: Aggregation, raw SQL, SQL v2, and saved queries.
 
+use super::entities::EntityTables;
 use super::{
     AggregateQueryRequest, AggregateQueryResponse, AggregateSeriesData,
-    GroupedAggregateQueryRequest, GroupedAggregateQueryResponse, RawSqlRequest, SavedQueryRequest,
-    SqlV2Request,
+    GroupedAggregateQueryRequest, GroupedAggregateQueryResponse, PivotRequest, RawSqlRequest,
+    SavedQueryMaterializationRequest, SavedQueryPageRequest, SavedQueryRequest, SqlV2Request,
 };
 use crate::{to_c_string, PrivStackError};
 use std::ffi::{c_char, CStr};
@@ -25,12 +26,19 @@ pub unsafe extern "C" fn privstack_dataset_aggregate(
                     )));
                 }
             };
+            let aggregation = match super::parse_aggregation(req.aggregation.as_deref()) {
+                Ok(a) => a,
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] aggregate: {e}");
+                    return to_c_string(&super::error_json(&e.to_string()));
+                }
+            };
 
             match store.aggregate_query(
                 &dataset_id,
                 &req.x_column,
                 &req.y_column,
-                req.aggregation.as_deref(),
+                aggregation,
                 req.group_by.as_deref(),
                 req.filter_text.as_deref(),
             ) {
@@ -80,13 +88,20 @@ pub unsafe extern "C" fn privstack_dataset_aggregate_grouped(
                     )));
                 }
             };
+            let aggregation = match super::parse_aggregation(req.aggregation.as_deref()) {
+                Ok(a) => a,
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] aggregate_grouped: {e}");
+                    return to_c_string(&super::error_json(&e.to_string()));
+                }
+            };
 
             match store.aggregate_query_grouped(
                 &dataset_id,
                 &req.x_column,
                 &req.y_column,
                 &req.group_column,
-                req.aggregation.as_deref(),
+                aggregation,
                 req.filter_text.as_deref(),
             ) {
                 Ok(triples) => {
@@ -154,6 +169,69 @@ pub unsafe extern "C" fn privstack_dataset_aggregate_grouped(
     }
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_pivot(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let req = parse_json_request!(request_json, PivotRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] pivot: invalid dataset id '{}': {e}", req.dataset_id);
+                    return to_c_string(&super::error_json(&format!(
+                        "invalid dataset id '{}': {e}", req.dataset_id
+                    )));
+                }
+            };
+
+            let filter = privstack_datasets::FilterNode::all(
+                req.filters
+                    .into_iter()
+                    .map(privstack_datasets::FilterNode::Condition)
+                    .chain(req.filter)
+                    .collect(),
+            );
+            match store.pivot(&dataset_id, &req.config, filter.as_ref()) {
+                Ok(result) => {
+                    let json =
+                        serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] pivot failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_pivot_view(
+    view_id: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let id_str = parse_cstr!(view_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            match store.pivot_view(id_str) {
+                Ok(result) => {
+                    let json =
+                        serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] pivot_view failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn privstack_dataset_execute_sql(
     request_json: *const c_char,
@@ -189,7 +267,8 @@ pub unsafe extern "C" fn privstack_dataset_execute_sql(
     }
 }
 
-/// Execute SQL v2: supports `source:` aliases, mutations with dry-run, and SELECT queries.
+/// Execute SQL v2: supports `source:` and `entity:` aliases, mutations with
+/// dry-run, and SELECT queries.
 ///
 /// Wraps execution in `catch_unwind` to prevent SQLite panics from aborting the process.
 #[unsafe(no_mangle)]
@@ -199,34 +278,44 @@ pub unsafe extern "C" fn privstack_dataset_execute_sql_v2(
     unsafe {
         let req = parse_json_request!(request_json, SqlV2Request);
 
-        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
-            let sql = req.sql.clone();
-            let page = req.page.unwrap_or(0);
-            let page_size = req.page_size.unwrap_or(100);
-            let dry_run = req.dry_run.unwrap_or(false);
+        let handle = crate::lock_handle();
+        let handle = match handle.as_ref() {
+            Some(h) => h,
+            None => return to_c_string(r#"{"error":"not initialized"}"#),
+        };
+        let store = match handle.dataset_store.as_ref() {
+            Some(s) => s,
+            None => return to_c_string(r#"{"error":"not initialized"}"#),
+        };
+        let sql = req.sql.clone();
+        let page = req.page.unwrap_or(0);
+        let page_size = req.page_size.unwrap_or(100);
+        let dry_run = req.dry_run.unwrap_or(false);
+        let entities = EntityTables::new(handle, req.plugin_id.as_deref());
 
-            // SAFETY: catch_unwind protects the FFI boundary from SQLite panics
-            // (e.g. SQLite 1.4.4 panics on stmt.column_count() before execution).
-            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                store.execute_sql_v2(&sql, page, page_size, dry_run)
-            }));
+        // SAFETY: catch_unwind protects the FFI boundary from SQLite panics
+        // (e.g. SQLite 1.4.4 panics on stmt.column_count() before execution).
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            store.execute_sql_with_entities(&sql, page, page_size, dry_run, &entities)
+        }));
 
-            match result {
-                Ok(Ok(exec_result)) => {
-                    let json = serde_json::to_string(&exec_result)
-                        .unwrap_or_else(|_| "{}".to_string());
-                    to_c_string(&json)
-                }
-                Ok(Err(e)) => {
-                    ffi_error!("[FFI DATASET] execute_sql_v2 failed: {e:?}");
-                    to_c_string(&super::error_json(&e.to_string()))
-                }
-                Err(_) => {
-                    ffi_error!("[FFI DATASET] execute_sql_v2 panicked (caught)");
-                    to_c_string(r#"{"error":"internal error: query execution panicked"}"#)
-                }
+        let response = match result {
+            Ok(Ok(exec_result)) => {
+                let json = serde_json::to_string(&exec_result)
+                    .unwrap_or_else(|_| "{}".to_string());
+                to_c_string(&json)
             }
-        })
+            Ok(Err(e)) => {
+                ffi_error!("[FFI DATASET] execute_sql_v2 failed: {e:?}");
+                to_c_string(&super::error_json(&e.to_string()))
+            }
+            Err(_) => {
+                ffi_error!("[FFI DATASET] execute_sql_v2 panicked (caught)");
+                to_c_string(r#"{"error":"internal error: query execution panicked"}"#)
+            }
+        };
+        super::publish_dataset_changes(handle);
+        response
     }
 }
 
@@ -353,3 +442,96 @@ pub extern "C" fn privstack_dataset_list_saved_queries() -> *mut c_char {
         }
     })
 }
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_set_saved_query_materialization(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let req = parse_json_request!(request_json, SavedQueryMaterializationRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            match store.set_saved_query_materialization(&req.query_id, req.refresh) {
+                Ok(status) => {
+                    let json =
+                        serde_json::to_string(&status).unwrap_or_else(|_| "null".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] set_saved_query_materialization failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_refresh_saved_query(
+    query_id: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        check_license_json!();
+        let id_str = parse_cstr!(query_id, r#"{"error":"null pointer"}"#);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            match store.refresh_saved_query(id_str) {
+                Ok(status) => {
+                    let json =
+                        serde_json::to_string(&status).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] refresh_saved_query failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_query_saved_query(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let req = parse_json_request!(request_json, SavedQueryPageRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            match store.query_saved_query(
+                &req.query_id,
+                req.page.unwrap_or(0),
+                req.page_size.unwrap_or(100),
+            ) {
+                Ok(result) => {
+                    let json =
+                        serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] query_saved_query failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+/// Rebuilds scheduled materializations that are due; the host calls this
+/// on a timer. Returns the ids of the queries refreshed.
+#[unsafe(no_mangle)]
+pub extern "C" fn privstack_dataset_refresh_due_saved_queries() -> *mut c_char {
+    with_store_json!("[]", |store| {
+        match store.refresh_due_saved_queries() {
+            Ok(ids) => {
+                let json = serde_json::to_string(&ids).unwrap_or_else(|_| "[]".to_string());
+                to_c_string(&json)
+            }
+            Err(e) => {
+                ffi_error!("[FFI DATASET] refresh_due_saved_queries failed: {e:?}");
+                to_c_string("[]")
+            }
+        }
+    })
+}
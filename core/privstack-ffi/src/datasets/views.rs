@@ -1,6 +1,6 @@
 //! FFI: Saved view CRUD operations.
 
-use super::{CreateViewRequest, UpdateViewRequest};
+use super::{CreateViewRequest, UpdateViewRequest, ViewQueryRequest};
 use crate::{to_c_string, PrivStackError};
 use std::ffi::{c_char, CStr};
 
@@ -33,6 +33,41 @@ pub unsafe extern "C" fn privstack_dataset_create_view(
     }
 }
 
+/// Query a page of a dataset's rows through a view configuration, saved
+/// or not: its visible columns, filters and sorts.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_dataset_query_view(
+    request_json: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let req = parse_json_request!(request_json, ViewQueryRequest);
+
+        with_store_json!(r#"{"error":"not initialized"}"#, |store| {
+            let dataset_id = match uuid::Uuid::parse_str(&req.dataset_id) {
+                Ok(u) => privstack_datasets::DatasetId(u),
+                Err(_) => return to_c_string(r#"{"error":"invalid dataset id"}"#),
+            };
+
+            match store.query_view(
+                &dataset_id,
+                &req.config,
+                req.page.unwrap_or(0),
+                req.page_size.unwrap_or(100),
+            ) {
+                Ok(result) => {
+                    let json =
+                        serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string());
+                    to_c_string(&json)
+                }
+                Err(e) => {
+                    ffi_error!("[FFI DATASET] query_view failed: {e:?}");
+                    to_c_string(&super::error_json(&e.to_string()))
+                }
+            }
+        })
+    }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn privstack_dataset_update_view(
     request_json: *const c_char,
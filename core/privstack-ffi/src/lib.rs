@@ -72,11 +72,17 @@ use privstack_model::{Entity, EntitySchema, PluginDomainHandler};
 use privstack_plugin_host::PluginHostManager;
 use privstack_storage::{EntityStore, EventStore};
 use privstack_sync::{
-    cloud::{CloudStorage, GoogleDriveConfig, GoogleDriveStorage, ICloudConfig, ICloudStorage},
-    create_personal_orchestrator,
-    pairing::{PairingManager, SyncCode},
+    cloud::{
+        CloudStorage, FileSyncConfig, FileSyncEngine, FileSyncState, FolderKey, GoogleDriveConfig,
+        GoogleDriveStorage, ICloudConfig, ICloudStorage, LocalFolderConfig, LocalFolderStorage,
+        WebDavConfig, WebDavStorage,
+    },
+    create_selective_orchestrator, diagnostics, diff_conflict, export_diagnostics, DatasetApplicator,
+    pairing::{PairingManager, PairingSecretStore, SyncCode},
+    resolve_conflict, stamp_local_event, ApplicatorError, ConflictResolution,
     Keypair, OrchestratorConfig, OrchestratorHandle, P2pConfig, P2pTransport,
-    PersonalSyncPolicy, SyncCommand, SyncConfig, SyncEngine, SyncEvent, SyncTransport,
+    PersonalSyncPolicy, SelectiveSyncPolicy, SyncCommand, SyncConfig, SyncEngine, SyncEvent,
+    SyncScope, SyncTransport, summarize_peers,
 };
 use privstack_types::{EntityId, Event, PeerId};
 use privstack_vault::VaultManager;
@@ -234,9 +240,18 @@ pub struct PrivStackHandle {
     sync_event_rx: Option<mpsc::Receiver<SyncEvent>>,
     pairing_manager: Arc<std::sync::Mutex<PairingManager>>,
     personal_policy: Option<Arc<PersonalSyncPolicy>>,
+    // Per-device sync scope; the live copy is in `selective_policy` while sync runs
+    sync_scope: SyncScope,
+    selective_policy: Option<Arc<SelectiveSyncPolicy>>,
     device_name: String,
     google_drive: Option<GoogleDriveStorage>,
     icloud: Option<ICloudStorage>,
+    webdav: Option<WebDavStorage>,
+    local_folder: Option<LocalFolderStorage>,
+    // File-based sync through the Google Drive / iCloud folder
+    folder_sync: Option<FileSyncEngine>,
+    // Set while a folder unlock or pass runs outside the `HANDLE` lock
+    folder_sync_running: bool,
     pub activation_store: ActivationStore,
     // Generic capabilities — no domain logic
     vault_manager: Arc<VaultManager>,
@@ -350,6 +365,10 @@ pub struct SyncEventDto {
     pub error: Option<String>,
     pub entity_type: Option<String>,
     pub json_data: Option<String>,
+    pub conflict_id: Option<String>,
+    pub entities_done: Option<usize>,
+    pub entities_total: Option<usize>,
+    pub eta_secs: Option<u64>,
 }
 
 impl From<SyncEvent> for SyncEventDto {
@@ -368,6 +387,10 @@ impl From<SyncEvent> for SyncEventDto {
                 error: None,
                 entity_type: None,
                 json_data: None,
+                conflict_id: None,
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
             },
             SyncEvent::SyncStarted { peer_id } => SyncEventDto {
                 event_type: "sync_started".to_string(),
@@ -379,6 +402,10 @@ impl From<SyncEvent> for SyncEventDto {
                 error: None,
                 entity_type: None,
                 json_data: None,
+                conflict_id: None,
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
             },
             SyncEvent::SyncCompleted {
                 peer_id,
@@ -394,6 +421,10 @@ impl From<SyncEvent> for SyncEventDto {
                 error: None,
                 entity_type: None,
                 json_data: None,
+                conflict_id: None,
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
             },
             SyncEvent::SyncFailed { peer_id, error } => SyncEventDto {
                 event_type: "sync_failed".to_string(),
@@ -405,6 +436,30 @@ impl From<SyncEvent> for SyncEventDto {
                 error: Some(error),
                 entity_type: None,
                 json_data: None,
+                conflict_id: None,
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
+            },
+            SyncEvent::SyncProgress {
+                peer_id,
+                entities_done,
+                entities_total,
+                eta_secs,
+            } => SyncEventDto {
+                event_type: "sync_progress".to_string(),
+                peer_id: Some(peer_id.to_string()),
+                device_name: None,
+                entity_id: None,
+                events_sent: None,
+                events_received: None,
+                error: None,
+                entity_type: None,
+                json_data: None,
+                conflict_id: None,
+                entities_done: Some(entities_done),
+                entities_total: Some(entities_total),
+                eta_secs,
             },
             SyncEvent::EntityUpdated { entity_id } => SyncEventDto {
                 event_type: "entity_updated".to_string(),
@@ -416,6 +471,28 @@ impl From<SyncEvent> for SyncEventDto {
                 error: None,
                 entity_type: None,
                 json_data: None,
+                conflict_id: None,
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
+            },
+            SyncEvent::ConflictDetected {
+                entity_id,
+                conflict_id,
+            } => SyncEventDto {
+                event_type: "conflict_detected".to_string(),
+                peer_id: None,
+                device_name: None,
+                entity_id: Some(entity_id.to_string()),
+                events_sent: None,
+                events_received: None,
+                error: None,
+                entity_type: None,
+                json_data: None,
+                conflict_id: Some(conflict_id),
+                entities_done: None,
+                entities_total: None,
+                eta_secs: None,
             },
         }
     }
@@ -438,6 +515,8 @@ pub struct CloudFileInfo {
 pub enum CloudProvider {
     GoogleDrive = 0,
     ICloud = 1,
+    WebDav = 2,
+    LocalFolder = 3,
 }
 
 /// Global handle storage (single instance for now).
@@ -565,6 +644,38 @@ fn load_or_create_keypair(db_path: &str) -> Keypair {
     keypair
 }
 
+/// Loads the sync scope saved alongside the database (e.g. `data.sync_scope`).
+/// A missing or unreadable file means syncing everything.
+fn load_sync_scope(db_path: &str) -> SyncScope {
+    if db_path == ":memory:" {
+        return SyncScope::default();
+    }
+
+    let scope_path = Path::new(db_path).with_extension("sync_scope");
+    match std::fs::read_to_string(&scope_path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            ffi_warn!("[FFI] Corrupt sync scope file at {}, syncing everything: {e}", scope_path.display());
+            SyncScope::default()
+        }),
+        Err(_) => SyncScope::default(),
+    }
+}
+
+/// Saves the sync scope alongside the database, so it survives restarts.
+fn save_sync_scope(db_path: &str, scope: &SyncScope) {
+    if db_path == ":memory:" {
+        return;
+    }
+
+    let scope_path = Path::new(db_path).with_extension("sync_scope");
+    let result = serde_json::to_string(scope)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&scope_path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        ffi_warn!("[FFI] Failed to persist sync scope to {}: {e}", scope_path.display());
+    }
+}
+
 /// Core init logic — sets up vault, blob, entity, event stores, runtime, sync engine.
 /// Used directly when wasm-plugins feature is disabled.
 ///
@@ -717,6 +828,14 @@ pub fn init_core(path: &str) -> PrivStackError {
         }
     };
 
+    // Replicate datasets through sync, stamped with this device's peer ID
+    if let Some(ds) = &dataset_store {
+        ds.enable_replication(&peer_id.to_string());
+        if let Err(e) = ds.replicate_all() {
+            ffi_warn!("[FFI] WARN: Failed to start dataset replication: {e:?}");
+        }
+    }
+
     let mut handle = HANDLE.lock().unwrap();
     *handle = Some(PrivStackHandle {
         db_path: path.to_string(),
@@ -732,9 +851,15 @@ pub fn init_core(path: &str) -> PrivStackError {
         sync_event_rx: None,
         pairing_manager: Arc::new(std::sync::Mutex::new(PairingManager::new())),
         personal_policy: None,
+        sync_scope: load_sync_scope(path),
+        selective_policy: None,
         device_name,
         google_drive: None,
         icloud: None,
+        webdav: None,
+        local_folder: None,
+        folder_sync: None,
+        folder_sync_running: false,
         activation_store,
         vault_manager,
         blob_store,
@@ -903,6 +1028,14 @@ where
         }
     };
 
+    // Replicate datasets through sync, stamped with this device's peer ID
+    if let Some(ds) = &dataset_store {
+        ds.enable_replication(&peer_id.to_string());
+        if let Err(e) = ds.replicate_all() {
+            ffi_warn!("[FFI] WARN: Failed to start dataset replication: {e:?}");
+        }
+    }
+
     let mut handle = HANDLE.lock().unwrap();
     *handle = Some(PrivStackHandle {
         db_path: path.to_string(),
@@ -918,9 +1051,15 @@ where
         sync_event_rx: None,
         pairing_manager: Arc::new(std::sync::Mutex::new(PairingManager::new())),
         personal_policy: None,
+        sync_scope: load_sync_scope(path),
+        selective_policy: None,
         device_name,
         google_drive: None,
         icloud: None,
+        webdav: None,
+        local_folder: None,
+        folder_sync: None,
+        folder_sync_running: false,
         activation_store,
         vault_manager,
         blob_store,
@@ -1236,7 +1375,10 @@ pub unsafe extern "C" fn privstack_auth_unlock(
 
     // Unlock vaults on the (now real) database
     match handle.vault_manager.unlock_all(password) {
-        Ok(_) => PrivStackError::Ok,
+        Ok(_) => {
+            attach_pairing_secrets(handle);
+            PrivStackError::Ok
+        }
         Err(_) => PrivStackError::AuthError,
     }
     })
@@ -2316,6 +2458,7 @@ pub extern "C" fn privstack_sync_start() -> PrivStackError {
         return PrivStackError::SyncAlreadyRunning;
     }
 
+    attach_pairing_secrets(handle);
     let mut config = P2pConfig::default();
 
     if let Some(sync_code) = handle.pairing_manager.lock().unwrap().current_code().cloned() {
@@ -2355,22 +2498,32 @@ pub extern "C" fn privstack_sync_start() -> PrivStackError {
     let orch_entity_store = Arc::clone(&handle.entity_store);
     let orch_event_store = Arc::clone(&handle.event_store);
 
-    // Always use PersonalSyncPolicy + pairing gate
+    // Always use PersonalSyncPolicy + pairing gate, scoped by the device's sync scope
     let policy = Arc::new(PersonalSyncPolicy::new());
     handle.personal_policy = Some(policy.clone());
+    let selective = Arc::new(
+        SelectiveSyncPolicy::new(handle.sync_scope.clone()).with_inner(policy.clone()),
+    );
+    handle.selective_policy = Some(selective.clone());
 
     let (orch_handle, event_rx, command_rx, orchestrator) = {
-        ffi_debug!("[FFI SYNC] privstack_sync_start: using personal orchestrator with pairing");
-        create_personal_orchestrator(
+        ffi_debug!("[FFI SYNC] privstack_sync_start: using selective orchestrator with pairing");
+        create_selective_orchestrator(
             handle.peer_id,
             orch_entity_store,
             orch_event_store,
             OrchestratorConfig::default(),
             policy,
+            selective,
             handle.pairing_manager.clone(),
         )
     };
 
+    let mut orchestrator = orchestrator;
+    if let Some(ds) = &handle.dataset_store {
+        orchestrator.set_dataset_handler(Arc::new(DatasetApplicator::new(ds.clone())));
+    }
+
     let transport_clone = transport.clone();
     handle.runtime.spawn(async move {
         ffi_debug!("[FFI SYNC] Orchestrator task starting...");
@@ -2383,6 +2536,7 @@ pub extern "C" fn privstack_sync_start() -> PrivStackError {
     handle.p2p_transport = Some(transport);
     handle.orchestrator_handle = Some(orch_handle);
     handle.sync_event_rx = Some(event_rx);
+    datasets::publish_dataset_changes(handle);
 
     ffi_debug!("[FFI SYNC] privstack_sync_start: complete");
     PrivStackError::Ok
@@ -2406,6 +2560,12 @@ pub extern "C" fn privstack_sync_stop() -> PrivStackError {
     handle.orchestrator_handle = None;
     handle.sync_event_rx = None;
 
+    // Keep pins made while running (on-demand fetches) for the next start
+    if let Some(policy) = handle.selective_policy.take() {
+        handle.sync_scope = handle.runtime.block_on(policy.scope());
+        save_sync_scope(&handle.db_path, &handle.sync_scope);
+    }
+
     if let Some(ref transport) = handle.p2p_transport {
         ffi_debug!("[FFI SYNC] privstack_sync_stop: stopping transport...");
         let _ = handle.runtime.block_on(async {
@@ -2571,6 +2731,18 @@ pub extern "C" fn privstack_sync_trigger() -> PrivStackError {
     PrivStackError::Ok
 }
 
+/// Signs an event this device authored with the device signing key, so the
+/// copy saved to the event store matches what peers receive.
+fn sign_local_event(handle: &PrivStackHandle, event: &mut Event) {
+    if event.peer_id != handle.peer_id || event.is_signed() {
+        return;
+    }
+    let key = handle.pairing_manager.lock().unwrap().signing_key();
+    if let Err(e) = key.sign(event) {
+        ffi_error!("[FFI SYNC] failed to sign event {}: {:?}", event.id, e);
+    }
+}
+
 /// Publishes an event payload for sync.
 ///
 /// # Safety
@@ -2644,6 +2816,30 @@ pub unsafe extern "C" fn privstack_sync_get_peer_id(
     PrivStackError::Ok
 }}
 
+/// Gets this device's event signing public key as a hex string.
+///
+/// # Safety
+/// - `out_device_key` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_get_device_key(
+    out_device_key: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if out_device_key.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let key = handle.pairing_manager.lock().unwrap().signing_key().public_key();
+    let c_str = CString::new(hex::encode(key)).unwrap();
+    *out_device_key = c_str.into_raw();
+    PrivStackError::Ok
+}}
+
 /// Gets discovered peers as a JSON array.
 ///
 /// # Safety
@@ -2749,6 +2945,120 @@ pub unsafe extern "C" fn privstack_sync_share_document(
     }
 }}
 
+/// Sets this device's sync scope (entity types, tags, modified-within window).
+/// Takes effect immediately if sync is running, and is saved alongside the
+/// database for the next launch.
+///
+/// # Safety
+/// - `scope_json` must be a valid null-terminated UTF-8 string containing a
+///   JSON `SyncScope` object.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_set_scope(scope_json: *const c_char) -> PrivStackError { unsafe {
+    if scope_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let json_str = match CStr::from_ptr(scope_json).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let scope: SyncScope = match serde_json::from_str(json_str) {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::JsonError,
+    };
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    if let Some(policy) = &handle.selective_policy {
+        let widened = handle.runtime.block_on(policy.set_scope(scope.clone()));
+        if let (true, Some(orch)) = (widened, &handle.orchestrator_handle) {
+            if let Err(e) = handle.runtime.block_on(orch.scope_widened()) {
+                ffi_error!("[FFI SYNC] Failed to re-sync widened scope: {e}");
+            }
+        }
+    }
+    save_sync_scope(&handle.db_path, &scope);
+    handle.sync_scope = scope;
+    PrivStackError::Ok
+}}
+
+/// Gets this device's sync scope as JSON, including entities pinned by
+/// on-demand fetches.
+///
+/// # Safety
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_get_scope(out_json: *mut *mut c_char) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let scope = match &handle.selective_policy {
+        Some(policy) => handle.runtime.block_on(policy.scope()),
+        None => handle.sync_scope.clone(),
+    };
+
+    match serde_json::to_string(&scope) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
+}}
+
+/// Fetches an entity outside this device's sync scope from peers, e.g. when
+/// the user opens a link to it. The entity keeps syncing afterwards.
+///
+/// # Safety
+/// - `document_id` must be a valid null-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_fetch_entity(
+    document_id: *const c_char,
+) -> PrivStackError { unsafe {
+    if document_id.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let doc_str = match CStr::from_ptr(document_id).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let eid: EntityId = match doc_str.parse() {
+        Ok(id) => id,
+        Err(_) => return PrivStackError::InvalidArgument,
+    };
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let orch_handle = match &handle.orchestrator_handle {
+        Some(oh) => oh,
+        None => return PrivStackError::SyncNotRunning,
+    };
+
+    match handle.runtime.block_on(orch_handle.fetch_entity(eid)) {
+        Ok(_) => PrivStackError::Ok,
+        Err(_) => PrivStackError::SyncError,
+    }
+}}
+
 /// Records a local event for sync. Takes a document ID and event JSON payload.
 ///
 /// # Safety
@@ -2772,7 +3082,7 @@ pub unsafe extern "C" fn privstack_sync_record_event(
         Err(_) => return PrivStackError::InvalidUtf8,
     };
 
-    let event: Event = match serde_json::from_str(json_str) {
+    let mut event: Event = match serde_json::from_str(json_str) {
         Ok(e) => e,
         Err(_) => return PrivStackError::JsonError,
     };
@@ -2783,6 +3093,13 @@ pub unsafe extern "C" fn privstack_sync_record_event(
         None => return PrivStackError::NotInitialized,
     };
 
+    if event.dependencies.is_empty() && !event.is_signed() {
+        if let Err(e) = stamp_local_event(&handle.entity_store, &mut event) {
+            ffi_warn!("[FFI SYNC] record_event: failed to record entity version: {:?}", e);
+        }
+    }
+    sign_local_event(handle, &mut event);
+
     // Save to event store immediately (same rationale as privstack_sync_snapshot).
     if let Err(e) = handle.event_store.save_event(&event) {
         ffi_error!("[FFI SYNC] record_event: failed to save event to store: {:?}", e);
@@ -2925,7 +3242,11 @@ pub unsafe extern "C" fn privstack_sync_snapshot(
         None => return PrivStackError::NotInitialized,
     };
 
-    let event = Event::full_snapshot(eid, handle.peer_id, etype_str, data_str);
+    let mut event = Event::full_snapshot(eid, handle.peer_id, etype_str, data_str);
+    if let Err(e) = stamp_local_event(&handle.entity_store, &mut event) {
+        ffi_warn!("[FFI SYNC] snapshot: failed to record entity version: {:?}", e);
+    }
+    sign_local_event(handle, &mut event);
 
     // Save to event store immediately so it's visible even if a sync cycle is in
     // progress (periodic_sync holds the command loop, blocking RecordLocalEvent).
@@ -2986,92 +3307,404 @@ pub unsafe extern "C" fn privstack_sync_import_entity(
     }
 }}
 
-// ============================================================================
-// Pairing Functions
-// ============================================================================
-
-/// Generates a new sync code for pairing.
+/// Lists sync conflicts as a JSON array, newest first.
 ///
 /// # Safety
-/// - `out_code` must be a valid pointer. The result must be freed with `privstack_free_string`.
+/// - `entity_id` may be null (all entities) or a valid null-terminated UTF-8 string.
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn privstack_pairing_generate_code(
-    out_code: *mut *mut c_char,
+pub unsafe extern "C" fn privstack_sync_list_conflicts(
+    entity_id: *const c_char,
+    include_resolved: bool,
+    out_json: *mut *mut c_char,
 ) -> PrivStackError { unsafe {
-    if out_code.is_null() {
+    if out_json.is_null() {
         return PrivStackError::NullPointer;
     }
 
-    let mut handle = HANDLE.lock().unwrap();
-    let handle = match handle.as_mut() {
+    let entity_filter = if entity_id.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(entity_id).to_str() {
+            Ok(s) => Some(s),
+            Err(_) => return PrivStackError::InvalidUtf8,
+        }
+    };
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
         Some(h) => h,
         None => return PrivStackError::NotInitialized,
     };
 
-    let code = SyncCode::generate();
-    handle.pairing_manager.lock().unwrap().set_sync_code(code.clone());
-    let json = match serde_json::to_string(&code) {
-        Ok(j) => j,
-        Err(_) => return PrivStackError::JsonError,
+    let conflicts = match handle.entity_store.list_sync_conflicts(entity_filter, include_resolved) {
+        Ok(c) => c,
+        Err(_) => return PrivStackError::StorageError,
     };
-    let c_str = CString::new(json).unwrap();
-    *out_code = c_str.into_raw();
-    PrivStackError::Ok
+
+    match serde_json::to_string(&conflicts) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
 }}
 
-/// Joins a sync group using a code.
+/// Returns a conflict and the top-level fields that differ between its
+/// sides as JSON: `{"conflict": {...}, "fields": [{"field", "mine", "theirs"}]}`.
 ///
 /// # Safety
-/// - `code` must be a valid null-terminated UTF-8 string.
+/// - `conflict_id` must be a valid null-terminated UTF-8 string.
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn privstack_pairing_join_code(code: *const c_char) -> PrivStackError { unsafe {
-    if code.is_null() {
+pub unsafe extern "C" fn privstack_sync_conflict_diff(
+    conflict_id: *const c_char,
+    out_json: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if conflict_id.is_null() || out_json.is_null() {
         return PrivStackError::NullPointer;
     }
 
-    let code_str = match CStr::from_ptr(code).to_str() {
+    let id_str = match CStr::from_ptr(conflict_id).to_str() {
         Ok(s) => s,
         Err(_) => return PrivStackError::InvalidUtf8,
     };
 
-    let sync_code = match SyncCode::from_input(code_str) {
-        Ok(c) => c,
-        Err(_) => return PrivStackError::InvalidSyncCode,
-    };
-
-    let mut handle = HANDLE.lock().unwrap();
-    let handle = match handle.as_mut() {
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
         Some(h) => h,
         None => return PrivStackError::NotInitialized,
     };
 
-    handle.pairing_manager.lock().unwrap().set_sync_code(sync_code);
-    PrivStackError::Ok
+    let conflict = match handle.entity_store.get_sync_conflict(id_str) {
+        Ok(Some(c)) => c,
+        Ok(None) => return PrivStackError::NotFound,
+        Err(_) => return PrivStackError::StorageError,
+    };
+    let fields = match diff_conflict(&conflict) {
+        Ok(f) => f,
+        Err(_) => return PrivStackError::JsonError,
+    };
+
+    match serde_json::to_string(&serde_json::json!({ "conflict": conflict, "fields": fields })) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
 }}
 
-/// Gets the current sync code (if any).
+/// Resolves a sync conflict. `resolution_json` is one of
+/// `{"resolution":"keep_mine"}`, `{"resolution":"keep_theirs"}` or
+/// `{"resolution":"merged","data":{...}}`. The resolution is recorded as a
+/// new event and propagates to peers when sync is running.
 ///
 /// # Safety
-/// - `out_code` must be a valid pointer. The result must be freed with `privstack_free_string`.
+/// - `conflict_id` and `resolution_json` must be valid null-terminated UTF-8 strings.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn privstack_pairing_get_code(
-    out_code: *mut *mut c_char,
+pub unsafe extern "C" fn privstack_sync_resolve_conflict(
+    conflict_id: *const c_char,
+    resolution_json: *const c_char,
 ) -> PrivStackError { unsafe {
-    if out_code.is_null() {
+    if conflict_id.is_null() || resolution_json.is_null() {
         return PrivStackError::NullPointer;
     }
 
+    let id_str = match CStr::from_ptr(conflict_id).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let json_str = match CStr::from_ptr(resolution_json).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let resolution: ConflictResolution = match serde_json::from_str(json_str) {
+        Ok(r) => r,
+        Err(_) => return PrivStackError::JsonError,
+    };
+
     let handle = HANDLE.lock().unwrap();
     let handle = match handle.as_ref() {
         Some(h) => h,
         None => return PrivStackError::NotInitialized,
     };
 
-    let code = handle.pairing_manager.lock().unwrap().current_code().cloned();
-    match code {
-        Some(code) => {
-            let json = match serde_json::to_string(&code) {
-                Ok(j) => j,
+    match resolve_and_publish_conflict(handle, id_str, resolution) {
+        Ok(_) => PrivStackError::Ok,
+        Err(ApplicatorError::EntityNotFound(_)) => PrivStackError::NotFound,
+        Err(ApplicatorError::InvalidOperation(_)) => PrivStackError::InvalidArgument,
+        Err(ApplicatorError::JsonParse(_)) => PrivStackError::JsonError,
+        Err(_) => PrivStackError::StorageError,
+    }
+}}
+
+/// Lists recorded sync sessions as a JSON array, newest first.
+///
+/// # Safety
+/// - `peer_id` may be null (all peers) or a valid null-terminated UTF-8 string.
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_sessions(
+    peer_id: *const c_char,
+    limit: u32,
+    out_json: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let peer_filter = if peer_id.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(peer_id).to_str() {
+            Ok(s) => Some(s),
+            Err(_) => return PrivStackError::InvalidUtf8,
+        }
+    };
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let sessions = match diagnostics::load_sessions(&handle.entity_store, peer_filter, limit as usize) {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::StorageError,
+    };
+
+    match serde_json::to_string(&sessions) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
+}}
+
+/// Returns per-peer sync totals over the session history as a JSON array,
+/// most recently synced peer first.
+///
+/// # Safety
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_peer_metrics(out_json: *mut *mut c_char) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let sessions = match diagnostics::load_sessions(&handle.entity_store, None, usize::MAX) {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::StorageError,
+    };
+
+    match serde_json::to_string(&summarize_peers(&sessions)) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
+}}
+
+/// Exports the session history as a redacted diagnostics bundle (JSON).
+/// Peer and entity IDs are hashed and device names removed, so the bundle
+/// can be attached to a bug report.
+///
+/// # Safety
+/// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_sync_export_diagnostics(out_json: *mut *mut c_char) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let sessions = match diagnostics::load_sessions(&handle.entity_store, None, usize::MAX) {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::StorageError,
+    };
+
+    match serde_json::to_string_pretty(&export_diagnostics(&handle.peer_id, &sessions)) {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
+}}
+
+/// Applies a conflict resolution locally, then signs, stores and hands the
+/// resulting event to the orchestrator so it reaches other devices.
+fn resolve_and_publish_conflict(
+    handle: &PrivStackHandle,
+    conflict_id: &str,
+    resolution: ConflictResolution,
+) -> Result<Event, ApplicatorError> {
+    let schema = handle
+        .entity_store
+        .get_sync_conflict(conflict_id)?
+        .and_then(|c| handle.entity_registry.get_schema(&c.entity_type));
+    let mut event = resolve_conflict(
+        &handle.entity_store,
+        conflict_id,
+        resolution,
+        handle.peer_id,
+        schema,
+    )?;
+    sign_local_event(handle, &mut event);
+
+    if let Err(e) = handle.event_store.save_event(&event) {
+        ffi_error!("[FFI SYNC] resolve_conflict: failed to save event to store: {:?}", e);
+    }
+    if let Some(orch_handle) = &handle.orchestrator_handle {
+        if let Err(e) = handle.runtime.block_on(orch_handle.record_event(event.clone())) {
+            ffi_warn!("[FFI SYNC] resolve_conflict: failed to record event: {:?}", e);
+        }
+    }
+    Ok(event)
+}
+
+// ============================================================================
+// Pairing Functions
+// ============================================================================
+
+/// Vault blob holding the pairing secrets (payload keyring, signing key).
+const PAIRING_SECRETS_BLOB: &str = "sync-pairing-secrets";
+
+/// Keeps the pairing secrets in the default vault instead of the pairing
+/// JSON the host app saves.
+struct VaultSecretStore(Arc<VaultManager>);
+
+impl PairingSecretStore for VaultSecretStore {
+    fn load(&self) -> privstack_sync::SyncResult<Option<Vec<u8>>> {
+        match self.0.read_blob("default", PAIRING_SECRETS_BLOB) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(privstack_vault::VaultError::BlobNotFound(_)) => Ok(None),
+            Err(e) => Err(privstack_sync::SyncError::Storage(e.to_string())),
+        }
+    }
+
+    fn save(&self, secrets: &[u8]) -> privstack_sync::SyncResult<()> {
+        self.0
+            .store_blob("default", PAIRING_SECRETS_BLOB, secrets)
+            .map_err(|e| privstack_sync::SyncError::Storage(e.to_string()))
+    }
+}
+
+/// Attaches the vault as the pairing secret store once it is unlocked.
+fn attach_pairing_secrets(handle: &PrivStackHandle) {
+    let mut pm = handle.pairing_manager.lock().unwrap();
+    if pm.has_secret_store() || !handle.vault_manager.is_unlocked("default") {
+        return;
+    }
+    if let Err(e) = pm.attach_secret_store(Arc::new(VaultSecretStore(handle.vault_manager.clone()))) {
+        ffi_warn!("[FFI SYNC] Failed to load pairing secrets from vault: {e}");
+    }
+}
+
+/// Generates a new sync code for pairing.
+///
+/// # Safety
+/// - `out_code` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_pairing_generate_code(
+    out_code: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if out_code.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let code = SyncCode::generate();
+    handle.pairing_manager.lock().unwrap().set_sync_code(code.clone());
+    let json = match serde_json::to_string(&code) {
+        Ok(j) => j,
+        Err(_) => return PrivStackError::JsonError,
+    };
+    let c_str = CString::new(json).unwrap();
+    *out_code = c_str.into_raw();
+    PrivStackError::Ok
+}}
+
+/// Joins a sync group using a code.
+///
+/// # Safety
+/// - `code` must be a valid null-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_pairing_join_code(code: *const c_char) -> PrivStackError { unsafe {
+    if code.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let code_str = match CStr::from_ptr(code).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let sync_code = match SyncCode::from_input(code_str) {
+        Ok(c) => c,
+        Err(_) => return PrivStackError::InvalidSyncCode,
+    };
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    handle.pairing_manager.lock().unwrap().set_sync_code(sync_code);
+    PrivStackError::Ok
+}}
+
+/// Gets the current sync code (if any).
+///
+/// # Safety
+/// - `out_code` must be a valid pointer. The result must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_pairing_get_code(
+    out_code: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if out_code.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let code = handle.pairing_manager.lock().unwrap().current_code().cloned();
+    match code {
+        Some(code) => {
+            let json = match serde_json::to_string(&code) {
+                Ok(j) => j,
                 Err(_) => return PrivStackError::JsonError,
             };
             let c_str = CString::new(json).unwrap();
@@ -3317,6 +3950,9 @@ pub unsafe extern "C" fn privstack_pairing_is_trusted(
 
 /// Saves the pairing state to JSON.
 ///
+/// The JSON holds the trusted peers and their pinned keys only; this
+/// device's keys are kept in the vault.
+///
 /// # Safety
 /// - `out_json` must be a valid pointer. The result must be freed with `privstack_free_string`.
 #[unsafe(no_mangle)]
@@ -3346,6 +3982,8 @@ pub unsafe extern "C" fn privstack_pairing_save_state(
 
 /// Loads the pairing state from JSON.
 ///
+/// Keys found in JSON saved by older versions are moved into the vault.
+///
 /// # Safety
 /// - `json` must be a valid null-terminated UTF-8 string.
 #[unsafe(no_mangle)]
@@ -3365,11 +4003,9 @@ pub unsafe extern "C" fn privstack_pairing_load_state(json: *const c_char) -> Pr
         None => return PrivStackError::NotInitialized,
     };
 
-    match PairingManager::from_json(json_str) {
-        Ok(manager) => {
-            *handle.pairing_manager.lock().unwrap() = manager;
-            PrivStackError::Ok
-        }
+    attach_pairing_secrets(handle);
+    match handle.pairing_manager.lock().unwrap().load_json(json_str) {
+        Ok(()) => PrivStackError::Ok,
         Err(_) => PrivStackError::JsonError,
     }
 }}
@@ -3647,6 +4283,76 @@ pub unsafe extern "C" fn privstack_cloud_init_icloud(bundle_id: *const c_char) -
     PrivStackError::Ok
 }}
 
+/// Initializes WebDAV storage (Nextcloud, ownCloud, any WebDAV server).
+///
+/// # Safety
+/// - `config_json` must be a valid null-terminated UTF-8 JSON string with
+///   `base_url`, `username`, `password` and optionally `sync_folder`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloud_init_webdav(config_json: *const c_char) -> PrivStackError { unsafe {
+    if config_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let json_str = match CStr::from_ptr(config_json).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let mut value: serde_json::Value = match serde_json::from_str(json_str) {
+        Ok(v) => v,
+        Err(_) => return PrivStackError::JsonError,
+    };
+    // Fill in base storage defaults the caller left out
+    if let (Some(obj), Ok(serde_json::Value::Object(defaults))) = (
+        value.as_object_mut(),
+        serde_json::to_value(WebDavConfig::default()),
+    ) {
+        for (key, default) in defaults {
+            obj.entry(key).or_insert(default);
+        }
+    }
+    let config: WebDavConfig = match serde_json::from_value(value) {
+        Ok(c) => c,
+        Err(_) => return PrivStackError::JsonError,
+    };
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    handle.webdav = Some(WebDavStorage::new(config));
+    PrivStackError::Ok
+}}
+
+/// Initializes storage in a local folder replicated by another tool
+/// (Syncthing, Dropbox, a network share).
+///
+/// # Safety
+/// - `root_path` must be a valid null-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloud_init_local_folder(root_path: *const c_char) -> PrivStackError { unsafe {
+    if root_path.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let root = match CStr::from_ptr(root_path).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    handle.local_folder = Some(LocalFolderStorage::new(LocalFolderConfig::new(root)));
+    PrivStackError::Ok
+}}
+
 /// Starts authentication for a cloud provider.
 ///
 /// # Safety
@@ -3682,6 +4388,20 @@ pub unsafe extern "C" fn privstack_cloud_authenticate(
             };
             handle.runtime.block_on(storage.authenticate())
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_mut() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.authenticate())
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_mut() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.authenticate())
+        }
     };
 
     match result {
@@ -3737,6 +4457,20 @@ pub unsafe extern "C" fn privstack_cloud_complete_auth(
             };
             handle.runtime.block_on(storage.complete_auth(code_str))
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_mut() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.complete_auth(code_str))
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_mut() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.complete_auth(code_str))
+        }
     };
 
     match result {
@@ -3758,11 +4492,19 @@ pub extern "C" fn privstack_cloud_is_authenticated(provider: CloudProvider) -> b
         CloudProvider::GoogleDrive => handle
             .google_drive
             .as_ref()
-            .map_or(false, |s| s.is_authenticated()),
+            .is_some_and(|s| s.is_authenticated()),
         CloudProvider::ICloud => handle
             .icloud
             .as_ref()
-            .map_or(false, |s| s.is_authenticated()),
+            .is_some_and(|s| s.is_authenticated()),
+        CloudProvider::WebDav => handle
+            .webdav
+            .as_ref()
+            .is_some_and(|s| s.is_authenticated()),
+        CloudProvider::LocalFolder => handle
+            .local_folder
+            .as_ref()
+            .is_some_and(|s| s.is_authenticated()),
     }
 }
 
@@ -3801,6 +4543,20 @@ pub unsafe extern "C" fn privstack_cloud_list_files(
             };
             handle.runtime.block_on(storage.list_files())
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.list_files())
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.list_files())
+        }
     };
 
     match result {
@@ -3884,6 +4640,20 @@ pub unsafe extern "C" fn privstack_cloud_upload(
             };
             handle.runtime.block_on(storage.upload(name_str, content))
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.upload(name_str, content))
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.upload(name_str, content))
+        }
     };
 
     match result {
@@ -3959,6 +4729,20 @@ pub unsafe extern "C" fn privstack_cloud_download(
             };
             handle.runtime.block_on(storage.download(file_id_str))
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.download(file_id_str))
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.download(file_id_str))
+        }
     };
 
     match result {
@@ -4012,6 +4796,20 @@ pub unsafe extern "C" fn privstack_cloud_delete(
             };
             handle.runtime.block_on(storage.delete(file_id_str))
         }
+        CloudProvider::WebDav => {
+            let storage = match handle.webdav.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.delete(file_id_str))
+        }
+        CloudProvider::LocalFolder => {
+            let storage = match handle.local_folder.as_ref() {
+                Some(s) => s,
+                None => return PrivStackError::NotInitialized,
+            };
+            handle.runtime.block_on(storage.delete(file_id_str))
+        }
     };
 
     match result {
@@ -4029,9 +4827,243 @@ pub extern "C" fn privstack_cloud_provider_name(provider: CloudProvider) -> *con
     match provider {
         CloudProvider::GoogleDrive => b"Google Drive\0".as_ptr() as *const c_char,
         CloudProvider::ICloud => b"iCloud Drive\0".as_ptr() as *const c_char,
+        CloudProvider::WebDav => b"WebDAV\0".as_ptr() as *const c_char,
+        CloudProvider::LocalFolder => b"Local Folder\0".as_ptr() as *const c_char,
+    }
+}
+
+/// A provider's storage, taken out of the handle so folder sync can do
+/// network I/O without holding `HANDLE`.
+enum LentStorage {
+    GoogleDrive(GoogleDriveStorage),
+    ICloud(ICloudStorage),
+    WebDav(WebDavStorage),
+    LocalFolder(LocalFolderStorage),
+}
+
+impl LentStorage {
+    fn take(handle: &mut PrivStackHandle, provider: CloudProvider) -> Option<Self> {
+        Some(match provider {
+            CloudProvider::GoogleDrive => Self::GoogleDrive(handle.google_drive.take()?),
+            CloudProvider::ICloud => Self::ICloud(handle.icloud.take()?),
+            CloudProvider::WebDav => Self::WebDav(handle.webdav.take()?),
+            CloudProvider::LocalFolder => Self::LocalFolder(handle.local_folder.take()?),
+        })
+    }
+
+    fn storage(&self) -> &dyn CloudStorage {
+        match self {
+            Self::GoogleDrive(s) => s,
+            Self::ICloud(s) => s,
+            Self::WebDav(s) => s,
+            Self::LocalFolder(s) => s,
+        }
+    }
+
+    /// Puts the storage back, unless the provider was configured again
+    /// in the meantime.
+    fn restore(self, handle: &mut PrivStackHandle) {
+        fn put_back<T>(slot: &mut Option<T>, storage: T) {
+            if slot.is_none() {
+                *slot = Some(storage);
+            }
+        }
+        match self {
+            Self::GoogleDrive(s) => put_back(&mut handle.google_drive, s),
+            Self::ICloud(s) => put_back(&mut handle.icloud, s),
+            Self::WebDav(s) => put_back(&mut handle.webdav, s),
+            Self::LocalFolder(s) => put_back(&mut handle.local_folder, s),
+        }
+    }
+}
+
+/// Takes a provider's storage out of the handle and marks folder sync as
+/// running. Undone by [`end_folder_sync`].
+fn begin_folder_sync(
+    provider: CloudProvider,
+) -> Result<(tokio::runtime::Handle, Arc<EntityStore>, LentStorage), PrivStackError> {
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = handle.as_mut().ok_or(PrivStackError::NotInitialized)?;
+    if handle.folder_sync_running {
+        return Err(PrivStackError::SyncAlreadyRunning);
+    }
+    let lent = LentStorage::take(handle, provider).ok_or(PrivStackError::NotInitialized)?;
+    handle.folder_sync_running = true;
+    Ok((handle.runtime.handle().clone(), handle.entity_store.clone(), lent))
+}
+
+/// Returns the storage (and engine, if any) taken by [`begin_folder_sync`]
+/// to the handle, unless the core was reinitialized while they were out.
+fn end_folder_sync(
+    entity_store: &Arc<EntityStore>,
+    lent: LentStorage,
+    engine: Option<FileSyncEngine>,
+) {
+    let mut handle = HANDLE.lock().unwrap();
+    let Some(handle) = handle.as_mut() else { return };
+    if !Arc::ptr_eq(&handle.entity_store, entity_store) {
+        return;
+    }
+    lent.restore(handle);
+    if let Some(engine) = engine {
+        handle.folder_sync = Some(engine);
     }
+    handle.folder_sync_running = false;
 }
 
+/// Unlocks the sync folder of a cloud provider and prepares folder sync.
+///
+/// The first device to unlock a folder sets its passphrase; other devices
+/// must use the same one. `state_json` is the state previously returned by
+/// `privstack_cloud_folder_sync_state`, or null on first use. The provider's
+/// storage is out of the handle while the folder is read, so other calls
+/// to it fail with `NotInitialized` until this returns.
+///
+/// # Safety
+/// - `passphrase` must be a valid null-terminated UTF-8 string.
+/// - `state_json` can be null or a valid null-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloud_folder_sync_init(
+    provider: CloudProvider,
+    passphrase: *const c_char,
+    state_json: *const c_char,
+) -> PrivStackError { unsafe {
+    if passphrase.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let passphrase_str = match CStr::from_ptr(passphrase).to_str() {
+        Ok(s) => s,
+        Err(_) => return PrivStackError::InvalidUtf8,
+    };
+
+    let state = if state_json.is_null() {
+        FileSyncState::default()
+    } else {
+        let json = match CStr::from_ptr(state_json).to_str() {
+            Ok(s) => s,
+            Err(_) => return PrivStackError::InvalidUtf8,
+        };
+        match FileSyncState::from_json(json) {
+            Ok(s) => s,
+            Err(_) => return PrivStackError::JsonError,
+        }
+    };
+
+    let (runtime, entity_store, lent) = match begin_folder_sync(provider) {
+        Ok(begun) => begun,
+        Err(e) => return e,
+    };
+
+    // Unlocking reads the folder and runs the KDF: neither holds `HANDLE`.
+    let params = privstack_crypto::KdfParams::default();
+    let unlocked = runtime.block_on(FolderKey::unlock(lent.storage(), passphrase_str, &params));
+    end_folder_sync(&entity_store, lent, None);
+    let key = match unlocked {
+        Ok(k) => k,
+        Err(privstack_sync::SyncError::Auth(_)) => return PrivStackError::AuthError,
+        Err(e) => {
+            ffi_error!("[FFI] Failed to unlock sync folder: {e}");
+            return PrivStackError::CloudError;
+        }
+    };
+
+    let mut handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+    handle.folder_sync = Some(FileSyncEngine::new(
+        handle.peer_id,
+        key,
+        handle.entity_store.clone(),
+        handle.event_store.clone(),
+        FileSyncConfig::default(),
+        state,
+    ));
+    PrivStackError::Ok
+}}
+
+/// Runs one folder sync pass (upload local events, apply other devices'
+/// files, compact when due) against a cloud provider.
+///
+/// The pass runs without holding `HANDLE`. Until it returns, another pass
+/// or unlock fails with `SyncAlreadyRunning`, and other calls to the
+/// provider's storage fail with `NotInitialized`.
+///
+/// # Safety
+/// - `out_json` will receive a pointer to a JSON report string.
+/// - The returned string must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloud_folder_sync(
+    provider: CloudProvider,
+    out_json: *mut *mut c_char,
+) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let (runtime, entity_store, lent) = match begin_folder_sync(provider) {
+        Ok(begun) => begun,
+        Err(e) => return e,
+    };
+    let engine = HANDLE.lock().unwrap().as_mut().and_then(|h| h.folder_sync.take());
+    let Some(mut engine) = engine else {
+        end_folder_sync(&entity_store, lent, None);
+        return PrivStackError::NotInitialized;
+    };
+
+    let result = runtime.block_on(engine.sync(lent.storage()));
+    end_folder_sync(&entity_store, lent, Some(engine));
+
+    match result {
+        Ok(report) => match serde_json::to_string(&report) {
+            Ok(json) => {
+                let c_json = CString::new(json).unwrap();
+                *out_json = c_json.into_raw();
+                PrivStackError::Ok
+            }
+            Err(_) => PrivStackError::JsonError,
+        },
+        Err(e) => {
+            ffi_error!("[FFI] Folder sync failed: {e}");
+            PrivStackError::CloudError
+        }
+    }
+}}
+
+/// Gets the folder sync state for persistence between runs.
+///
+/// # Safety
+/// - `out_json` will receive a pointer to a JSON string.
+/// - The returned string must be freed with `privstack_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn privstack_cloud_folder_sync_state(out_json: *mut *mut c_char) -> PrivStackError { unsafe {
+    if out_json.is_null() {
+        return PrivStackError::NullPointer;
+    }
+
+    let handle = HANDLE.lock().unwrap();
+    let handle = match handle.as_ref() {
+        Some(h) => h,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    let engine = match handle.folder_sync.as_ref() {
+        Some(e) => e,
+        None => return PrivStackError::NotInitialized,
+    };
+
+    match engine.state().to_json() {
+        Ok(json) => {
+            let c_json = CString::new(json).unwrap();
+            *out_json = c_json.into_raw();
+            PrivStackError::Ok
+        }
+        Err(_) => PrivStackError::JsonError,
+    }
+}}
+
 // ============================================================================
 // License Functions
 // ============================================================================
@@ -4620,6 +5652,7 @@ fn execute_generic(handle: &PrivStackHandle, req: &SdkRequest) -> SdkResponse {
     let is_mutation = matches!(
         req.action.as_str(),
         "create" | "update" | "delete" | "trash" | "restore" | "link" | "unlink"
+            | "resolve_conflict"
     );
     if is_mutation {
         match handle.activation_store.load() {
@@ -4883,6 +5916,72 @@ fn execute_generic(handle: &PrivStackHandle, req: &SdkRequest) -> SdkResponse {
                 Err(e) => SdkResponse::err("storage_error", &format!("Get links failed: {e}")),
             }
         }
+        "list_conflicts" => {
+            let include_resolved = req.parameters.as_ref()
+                .and_then(|p| p.get("include_resolved"))
+                .map(|v| v == "true")
+                .unwrap_or(false);
+            match handle.entity_store.list_sync_conflicts(req.entity_id.as_deref(), include_resolved) {
+                Ok(conflicts) => {
+                    let conflicts: Vec<_> = conflicts
+                        .into_iter()
+                        .filter(|c| c.entity_type == req.entity_type)
+                        .collect();
+                    SdkResponse::ok(serde_json::to_value(conflicts).unwrap_or_default())
+                }
+                Err(e) => SdkResponse::err("storage_error", &format!("List conflicts failed: {e}")),
+            }
+        }
+        "diff_conflict" => {
+            let conflict_id = match req.parameters.as_ref().and_then(|p| p.get("conflict_id")) {
+                Some(id) => id,
+                None => return SdkResponse::err("missing_params", "diff_conflict requires 'conflict_id' parameter"),
+            };
+            let conflict = match handle.entity_store.get_sync_conflict(conflict_id) {
+                Ok(Some(c)) => c,
+                Ok(None) => return SdkResponse::err("not_found", &format!("Conflict not found: {conflict_id}")),
+                Err(e) => return SdkResponse::err("storage_error", &format!("Read failed: {e}")),
+            };
+            match diff_conflict(&conflict) {
+                Ok(fields) => SdkResponse::ok(serde_json::json!({ "conflict": conflict, "fields": fields })),
+                Err(e) => SdkResponse::err("json_error", &format!("Diff failed: {e}")),
+            }
+        }
+        "resolve_conflict" => {
+            let conflict_id = match req.parameters.as_ref().and_then(|p| p.get("conflict_id")) {
+                Some(id) => id,
+                None => return SdkResponse::err("missing_params", "resolve_conflict requires 'conflict_id' parameter"),
+            };
+            let resolution = match req.parameters.as_ref().and_then(|p| p.get("resolution")).map(String::as_str) {
+                Some("keep_mine") => ConflictResolution::KeepMine,
+                Some("keep_theirs") => ConflictResolution::KeepTheirs,
+                Some("merged") => {
+                    let payload = match req.payload.as_deref() {
+                        Some(p) => p,
+                        None => return SdkResponse::err("missing_payload", "A merged resolution requires a payload"),
+                    };
+                    match serde_json::from_str(payload) {
+                        Ok(v) => ConflictResolution::Merged(v),
+                        Err(e) => return SdkResponse::err("json_error", &format!("Invalid JSON: {e}")),
+                    }
+                }
+                _ => return SdkResponse::err("missing_params", "resolution must be keep_mine, keep_theirs or merged"),
+            };
+            match resolve_and_publish_conflict(handle, conflict_id, resolution) {
+                Ok(event) => match handle.entity_store.get_entity(&event.entity_id.to_string()) {
+                    Ok(Some(mut entity)) => {
+                        if let Some(h) = handler {
+                            h.on_after_load(&mut entity);
+                        }
+                        SdkResponse::ok(flatten_entity(&entity))
+                    }
+                    _ => SdkResponse::ok_empty(),
+                },
+                Err(ApplicatorError::EntityNotFound(msg)) => SdkResponse::err("not_found", &msg),
+                Err(ApplicatorError::InvalidOperation(msg)) => SdkResponse::err("invalid_operation", &msg),
+                Err(e) => SdkResponse::err("storage_error", &format!("Resolve failed: {e}")),
+            }
+        }
         "command" => {
             let entity_id = match &req.entity_id {
                 Some(id) => id,
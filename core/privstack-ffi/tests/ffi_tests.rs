@@ -41,9 +41,14 @@ fn test_clear_activation() {
 }
 
 /// Initializes the runtime with an in-memory policy (no filesystem I/O).
-#[cfg(feature = "wasm-plugins")]
 fn test_init() -> PrivStackError {
-    let r = init_with_plugin_host_builder(":memory:", |es, ev| {
+    test_init_at(":memory:")
+}
+
+/// Initializes the runtime on a database at `path`.
+#[cfg(feature = "wasm-plugins")]
+fn test_init_at(path: &str) -> PrivStackError {
+    let r = init_with_plugin_host_builder(path, |es, ev| {
         PluginHostManager::with_policy(es, ev, PolicyEngine::with_config(PolicyConfig::default()))
     });
     if r == PrivStackError::Ok {
@@ -54,8 +59,8 @@ fn test_init() -> PrivStackError {
 
 /// Initializes the runtime without plugin host (no wasm-plugins feature).
 #[cfg(not(feature = "wasm-plugins"))]
-fn test_init() -> PrivStackError {
-    let r = init_core(":memory:");
+fn test_init_at(path: &str) -> PrivStackError {
+    let r = init_core(path);
     if r == PrivStackError::Ok {
         setup_test_activation_store();
     }
@@ -334,6 +339,18 @@ fn cloud_init_google_drive_null() {
     assert_eq!(result, PrivStackError::NullPointer);
 }
 
+#[test]
+fn cloud_init_webdav_null() {
+    let result = unsafe { privstack_cloud_init_webdav(ptr::null()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+fn cloud_init_local_folder_null() {
+    let result = unsafe { privstack_cloud_init_local_folder(ptr::null()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
 #[test]
 fn cloud_authenticate_null() {
     let result = unsafe { privstack_cloud_authenticate(CloudProvider::GoogleDrive, ptr::null_mut()) };
@@ -370,6 +387,24 @@ fn cloud_delete_null() {
     assert_eq!(result, PrivStackError::NullPointer);
 }
 
+#[test]
+fn cloud_folder_sync_init_null() {
+    let result = unsafe { privstack_cloud_folder_sync_init(CloudProvider::ICloud, ptr::null(), ptr::null()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+fn cloud_folder_sync_null() {
+    let result = unsafe { privstack_cloud_folder_sync(CloudProvider::ICloud, ptr::null_mut()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+fn cloud_folder_sync_state_null() {
+    let result = unsafe { privstack_cloud_folder_sync_state(ptr::null_mut()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
 #[test]
 fn license_parse_null() {
     let result = unsafe { privstack_license_parse(ptr::null(), ptr::null_mut()) };
@@ -576,6 +611,32 @@ fn sync_event_dto_entity_updated() {
     assert!(dto.entity_id.is_some());
 }
 
+#[test]
+fn sync_event_dto_conflict_detected() {
+    let dto: SyncEventDto = SyncEvent::ConflictDetected {
+        entity_id: privstack_types::EntityId::new(),
+        conflict_id: "c-1".to_string(),
+    }.into();
+    assert_eq!(dto.event_type, "conflict_detected");
+    assert!(dto.entity_id.is_some());
+    assert_eq!(dto.conflict_id.as_deref(), Some("c-1"));
+}
+
+#[test]
+fn sync_event_dto_sync_progress() {
+    let dto: SyncEventDto = SyncEvent::SyncProgress {
+        peer_id: PeerId::new(),
+        entities_done: 25,
+        entities_total: 100,
+        eta_secs: Some(30),
+    }.into();
+    assert_eq!(dto.event_type, "sync_progress");
+    assert_eq!(dto.entities_done, Some(25));
+    assert_eq!(dto.entities_total, Some(100));
+    assert_eq!(dto.eta_secs, Some(30));
+    assert!(dto.conflict_id.is_none());
+}
+
 // ── Execute / Search null pointer ───────────────────────────
 
 #[test]
@@ -727,6 +788,131 @@ fn cloud_init_icloud_custom_bundle() {
     privstack_shutdown();
 }
 
+#[test]
+#[serial]
+fn cloud_init_webdav_minimal_config() {
+    test_init();
+
+    let config = CString::new(
+        r#"{"base_url":"https://dav.example.com/files/alice","username":"alice","password":"pw"}"#,
+    )
+    .unwrap();
+    let result = unsafe { privstack_cloud_init_webdav(config.as_ptr()) };
+    assert_eq!(result, PrivStackError::Ok);
+    assert!(!privstack_cloud_is_authenticated(CloudProvider::WebDav));
+
+    privstack_shutdown();
+}
+
+#[test]
+#[serial]
+fn cloud_init_webdav_invalid_json() {
+    test_init();
+
+    let config = CString::new("not json").unwrap();
+    let result = unsafe { privstack_cloud_init_webdav(config.as_ptr()) };
+    assert_eq!(result, PrivStackError::JsonError);
+
+    privstack_shutdown();
+}
+
+#[test]
+#[serial]
+fn cloud_local_folder_lifecycle() {
+    test_init();
+
+    let dir = std::env::temp_dir()
+        .join("privstack-ffi-tests")
+        .join(format!("local-folder-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let root = CString::new(dir.to_str().unwrap()).unwrap();
+    let result = unsafe { privstack_cloud_init_local_folder(root.as_ptr()) };
+    assert_eq!(result, PrivStackError::Ok);
+
+    let mut out_auth_url: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_cloud_authenticate(CloudProvider::LocalFolder, &mut out_auth_url) };
+    assert_eq!(result, PrivStackError::Ok);
+    assert!(out_auth_url.is_null());
+    assert!(privstack_cloud_is_authenticated(CloudProvider::LocalFolder));
+
+    let name = CString::new("hello.bin").unwrap();
+    let data = b"local bytes";
+    let mut out_json: *mut c_char = ptr::null_mut();
+    let result = unsafe {
+        privstack_cloud_upload(CloudProvider::LocalFolder, name.as_ptr(), data.as_ptr(), data.len(), &mut out_json)
+    };
+    assert_eq!(result, PrivStackError::Ok);
+    unsafe { privstack_free_string(out_json) };
+
+    let mut out_json: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_cloud_list_files(CloudProvider::LocalFolder, &mut out_json) };
+    assert_eq!(result, PrivStackError::Ok);
+    let json = unsafe { CStr::from_ptr(out_json) }.to_str().unwrap();
+    assert!(json.contains("hello.bin"));
+    unsafe { privstack_free_string(out_json) };
+
+    let name = privstack_cloud_provider_name(CloudProvider::LocalFolder);
+    assert_eq!(unsafe { CStr::from_ptr(name) }.to_str().unwrap(), "Local Folder");
+
+    privstack_shutdown();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[serial]
+fn cloud_folder_sync_returns_storage_to_handle() {
+    test_init();
+
+    let dir = std::env::temp_dir()
+        .join("privstack-ffi-tests")
+        .join(format!("folder-sync-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let root = CString::new(dir.to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { privstack_cloud_init_local_folder(root.as_ptr()) }, PrivStackError::Ok);
+    let mut out_auth_url: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_cloud_authenticate(CloudProvider::LocalFolder, &mut out_auth_url) };
+    assert_eq!(result, PrivStackError::Ok);
+
+    let list = || {
+        let mut out_json: *mut c_char = ptr::null_mut();
+        let result = unsafe { privstack_cloud_list_files(CloudProvider::LocalFolder, &mut out_json) };
+        assert_eq!(result, PrivStackError::Ok);
+        let json = unsafe { CStr::from_ptr(out_json) }.to_str().unwrap().to_string();
+        unsafe { privstack_free_string(out_json) };
+        json
+    };
+
+    // The storage is lent to each pass and comes back, whether the pass
+    // runs, fails or never starts.
+    let mut out_json: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_cloud_folder_sync(CloudProvider::LocalFolder, &mut out_json) };
+    assert_eq!(result, PrivStackError::NotInitialized);
+    list();
+
+    let passphrase = CString::new("folder pass").unwrap();
+    let result = unsafe {
+        privstack_cloud_folder_sync_init(CloudProvider::LocalFolder, passphrase.as_ptr(), ptr::null())
+    };
+    assert_eq!(result, PrivStackError::Ok);
+    assert!(list().contains("ps-folder.json"));
+
+    for _ in 0..2 {
+        let mut out_json: *mut c_char = ptr::null_mut();
+        let result = unsafe { privstack_cloud_folder_sync(CloudProvider::LocalFolder, &mut out_json) };
+        assert_eq!(result, PrivStackError::Ok);
+        unsafe { privstack_free_string(out_json) };
+    }
+    let mut out_json: *mut c_char = ptr::null_mut();
+    assert_eq!(unsafe { privstack_cloud_folder_sync_state(&mut out_json) }, PrivStackError::Ok);
+    unsafe { privstack_free_string(out_json) };
+    list();
+
+    privstack_shutdown();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
 // ── Full lifecycle: device info ─────────────────────────────
 
 #[test]
@@ -1252,6 +1438,194 @@ fn sync_trigger_not_running() {
     privstack_shutdown();
 }
 
+// ── Sync scope ──────────────────────────────────────────────
+
+#[test]
+fn sync_set_scope_null() {
+    let result = unsafe { privstack_sync_set_scope(ptr::null()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+fn sync_get_scope_null() {
+    let result = unsafe { privstack_sync_get_scope(ptr::null_mut()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+#[serial]
+fn sync_set_scope_invalid_json() {
+    test_init();
+
+    let bad = CString::new("not json").unwrap();
+    let result = unsafe { privstack_sync_set_scope(bad.as_ptr()) };
+    assert_eq!(result, PrivStackError::JsonError);
+
+    privstack_shutdown();
+}
+
+#[test]
+#[serial]
+fn sync_scope_roundtrip() {
+    test_init();
+
+    let scope = CString::new(
+        r#"{"exclude_types":["journal"],"include_tags":["work"],"modified_within_days":90}"#,
+    )
+    .unwrap();
+    let result = unsafe { privstack_sync_set_scope(scope.as_ptr()) };
+    assert_eq!(result, PrivStackError::Ok);
+
+    let mut out_json: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_sync_get_scope(&mut out_json) };
+    assert_eq!(result, PrivStackError::Ok);
+    let json = unsafe { CStr::from_ptr(out_json) }.to_str().unwrap();
+    let value: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(value["exclude_types"], serde_json::json!(["journal"]));
+    assert_eq!(value["include_tags"], serde_json::json!(["work"]));
+    assert_eq!(value["modified_within_days"], 90);
+    unsafe { privstack_free_string(out_json) };
+
+    privstack_shutdown();
+}
+
+#[test]
+#[serial]
+fn sync_scope_persists_across_restarts() {
+    let dir = std::env::temp_dir()
+        .join("privstack-ffi-tests")
+        .join(format!("sync-scope-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("data");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(test_init_at(path), PrivStackError::Ok);
+    let scope = CString::new(r#"{"include_types":["note"]}"#).unwrap();
+    assert_eq!(unsafe { privstack_sync_set_scope(scope.as_ptr()) }, PrivStackError::Ok);
+    privstack_shutdown();
+
+    assert_eq!(test_init_at(path), PrivStackError::Ok);
+    let mut out_json: *mut c_char = ptr::null_mut();
+    assert_eq!(unsafe { privstack_sync_get_scope(&mut out_json) }, PrivStackError::Ok);
+    let json = unsafe { CStr::from_ptr(out_json) }.to_str().unwrap();
+    let value: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(value["include_types"], serde_json::json!(["note"]));
+    unsafe { privstack_free_string(out_json) };
+    privstack_shutdown();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sync_fetch_entity_null() {
+    let result = unsafe { privstack_sync_fetch_entity(ptr::null()) };
+    assert_eq!(result, PrivStackError::NullPointer);
+}
+
+#[test]
+#[serial]
+fn sync_fetch_entity_not_running() {
+    test_init();
+
+    let id = CString::new(Uuid::new_v4().to_string()).unwrap();
+    let result = unsafe { privstack_sync_fetch_entity(id.as_ptr()) };
+    assert_eq!(result, PrivStackError::SyncNotRunning);
+
+    let bad = CString::new("not-a-uuid").unwrap();
+    let result = unsafe { privstack_sync_fetch_entity(bad.as_ptr()) };
+    assert_eq!(result, PrivStackError::InvalidArgument);
+
+    privstack_shutdown();
+}
+
+#[test]
+fn sync_conflict_functions_null() {
+    let mut out: *mut c_char = ptr::null_mut();
+    let id = CString::new("c-1").unwrap();
+    assert_eq!(
+        unsafe { privstack_sync_list_conflicts(ptr::null(), false, ptr::null_mut()) },
+        PrivStackError::NullPointer
+    );
+    assert_eq!(
+        unsafe { privstack_sync_conflict_diff(ptr::null(), &mut out) },
+        PrivStackError::NullPointer
+    );
+    assert_eq!(
+        unsafe { privstack_sync_resolve_conflict(id.as_ptr(), ptr::null()) },
+        PrivStackError::NullPointer
+    );
+}
+
+#[test]
+#[serial]
+fn sync_conflicts_empty_and_unknown() {
+    test_init();
+
+    let mut out: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_sync_list_conflicts(ptr::null(), true, &mut out) };
+    assert_eq!(result, PrivStackError::Ok);
+    assert_eq!(unsafe { CStr::from_ptr(out) }.to_str().unwrap(), "[]");
+    unsafe { privstack_free_string(out) };
+
+    let id = CString::new("missing").unwrap();
+    let result = unsafe { privstack_sync_conflict_diff(id.as_ptr(), &mut out) };
+    assert_eq!(result, PrivStackError::NotFound);
+
+    let keep = CString::new(r#"{"resolution":"keep_mine"}"#).unwrap();
+    let result = unsafe { privstack_sync_resolve_conflict(id.as_ptr(), keep.as_ptr()) };
+    assert_eq!(result, PrivStackError::NotFound);
+
+    let bad = CString::new(r#"{"resolution":"flip_a_coin"}"#).unwrap();
+    let result = unsafe { privstack_sync_resolve_conflict(id.as_ptr(), bad.as_ptr()) };
+    assert_eq!(result, PrivStackError::JsonError);
+
+    privstack_shutdown();
+}
+
+#[test]
+fn sync_diagnostics_functions_null() {
+    assert_eq!(
+        unsafe { privstack_sync_sessions(ptr::null(), 10, ptr::null_mut()) },
+        PrivStackError::NullPointer
+    );
+    assert_eq!(
+        unsafe { privstack_sync_peer_metrics(ptr::null_mut()) },
+        PrivStackError::NullPointer
+    );
+    assert_eq!(
+        unsafe { privstack_sync_export_diagnostics(ptr::null_mut()) },
+        PrivStackError::NullPointer
+    );
+}
+
+#[test]
+#[serial]
+fn sync_diagnostics_empty_history() {
+    test_init();
+
+    let mut out: *mut c_char = ptr::null_mut();
+    let result = unsafe { privstack_sync_sessions(ptr::null(), 10, &mut out) };
+    assert_eq!(result, PrivStackError::Ok);
+    assert_eq!(unsafe { CStr::from_ptr(out) }.to_str().unwrap(), "[]");
+    unsafe { privstack_free_string(out) };
+
+    let result = unsafe { privstack_sync_peer_metrics(&mut out) };
+    assert_eq!(result, PrivStackError::Ok);
+    assert_eq!(unsafe { CStr::from_ptr(out) }.to_str().unwrap(), "[]");
+    unsafe { privstack_free_string(out) };
+
+    let result = unsafe { privstack_sync_export_diagnostics(&mut out) };
+    assert_eq!(result, PrivStackError::Ok);
+    let bundle: serde_json::Value =
+        serde_json::from_str(unsafe { CStr::from_ptr(out) }.to_str().unwrap()).unwrap();
+    unsafe { privstack_free_string(out) };
+    assert_eq!(bundle["format_version"], 1);
+    assert!(bundle["local_peer"].as_str().unwrap().starts_with("peer-"));
+
+    privstack_shutdown();
+}
+
 // ── Sync publish event ──────────────────────────────────────
 
 #[test]
@@ -15,6 +15,58 @@ pub struct EntityStore {
     conn: Arc<Mutex<Connection>>,
 }
 
+/// A persisted cloud outbox entry awaiting delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudOutboxRow {
+    pub event_id: String,
+    pub entity_id: String,
+    pub event_json: String,
+    /// `pending` until assigned to a batch, then `in_flight`.
+    pub state: String,
+    pub attempts: u32,
+    /// Unix millis before which the row should not be retried.
+    pub next_attempt_at: i64,
+    pub batch_key: Option<String>,
+    pub cursor_start: Option<i64>,
+    pub cursor_end: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+/// Progress of a multi-cycle sync run with one peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncProgressRow {
+    pub peer_id: String,
+    pub entities_done: u64,
+    pub entities_total: u64,
+    /// Time spent in sync sessions for this run, excluding gaps between them.
+    pub active_ms: u64,
+    pub started_at: i64,
+    pub updated_at: i64,
+}
+
+/// A concurrent edit that sync merged automatically, kept for review.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConflictRecord {
+    pub conflict_id: String,
+    pub entity_id: String,
+    pub entity_type: String,
+    /// Local state at the moment the remote edit arrived.
+    pub local_json: String,
+    /// The remote edit's state.
+    pub remote_json: String,
+    /// Event IDs of the local versions the remote edit did not know about.
+    pub local_heads: Vec<String>,
+    pub remote_event_id: String,
+    pub remote_peer_id: String,
+    /// Which side the automatic merge kept: `local`, `remote` or `merged`.
+    pub winner: String,
+    pub detected_at: i64,
+    pub resolved_at: Option<i64>,
+    /// `keep_mine`, `keep_theirs` or `merged` when resolved here; `remote` when
+    /// a resolution arrived from another device.
+    pub resolution: Option<String>,
+}
+
 impl EntityStore {
     /// Opens or creates an entity store at the given path.
     pub fn open(path: &Path) -> StorageResult<Self> {
@@ -683,6 +735,406 @@ impl EntityStore {
         Ok(())
     }
 
+    // -- Cloud Sync Outbox Persistence --
+
+    /// Persists an outbound cloud event. Re-enqueueing the same event is a no-op.
+    pub fn enqueue_cloud_outbox(
+        &self,
+        workspace_id: &str,
+        event_id: &str,
+        entity_id: &str,
+        event_json: &str,
+    ) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        conn.execute(
+            "INSERT OR IGNORE INTO cloud_outbox
+                (workspace_id, event_id, entity_id, event_json, state, attempts, next_attempt_at, enqueued_at)
+             VALUES (?, ?, ?, ?, 'pending', 0, 0, ?)",
+            params![workspace_id, event_id, entity_id, event_json, now],
+        )?;
+        Ok(())
+    }
+
+    /// Loads all undelivered outbox rows for a workspace in enqueue order.
+    pub fn load_cloud_outbox(&self, workspace_id: &str) -> StorageResult<Vec<CloudOutboxRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT event_id, entity_id, event_json, state, attempts, next_attempt_at,
+                    batch_key, cursor_start, cursor_end, last_error
+             FROM cloud_outbox WHERE workspace_id = ? ORDER BY seq",
+        )?;
+        let rows = stmt
+            .query_map(params![workspace_id], |row| {
+                Ok(CloudOutboxRow {
+                    event_id: row.get(0)?,
+                    entity_id: row.get(1)?,
+                    event_json: row.get(2)?,
+                    state: row.get(3)?,
+                    attempts: row.get::<_, i64>(4)? as u32,
+                    next_attempt_at: row.get(5)?,
+                    batch_key: row.get(6)?,
+                    cursor_start: row.get(7)?,
+                    cursor_end: row.get(8)?,
+                    last_error: row.get(9)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Marks outbox rows as in flight under a fixed batch key and cursor range.
+    ///
+    /// Called before upload so a retry after a crash reuses the same key.
+    pub fn assign_cloud_outbox_batch(
+        &self,
+        workspace_id: &str,
+        event_ids: &[String],
+        batch_key: &str,
+        cursor_start: i64,
+        cursor_end: i64,
+    ) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for event_id in event_ids {
+            tx.execute(
+                "UPDATE cloud_outbox
+                 SET state = 'in_flight', batch_key = ?, cursor_start = ?, cursor_end = ?
+                 WHERE workspace_id = ? AND event_id = ?",
+                params![batch_key, cursor_start, cursor_end, workspace_id, event_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Records a failed delivery attempt and when the rows may be retried.
+    pub fn record_cloud_outbox_failure(
+        &self,
+        workspace_id: &str,
+        event_ids: &[String],
+        attempts: u32,
+        next_attempt_at: i64,
+        error: &str,
+    ) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for event_id in event_ids {
+            tx.execute(
+                "UPDATE cloud_outbox SET attempts = ?, next_attempt_at = ?, last_error = ?
+                 WHERE workspace_id = ? AND event_id = ?",
+                params![attempts as i64, next_attempt_at, error, workspace_id, event_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes delivered rows from the outbox.
+    pub fn remove_cloud_outbox(&self, workspace_id: &str, event_ids: &[String]) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for event_id in event_ids {
+            tx.execute(
+                "DELETE FROM cloud_outbox WHERE workspace_id = ? AND event_id = ?",
+                params![workspace_id, event_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drops every outbox row for a workspace (e.g. when it is deleted).
+    pub fn clear_cloud_outbox(&self, workspace_id: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM cloud_outbox WHERE workspace_id = ?",
+            params![workspace_id],
+        )?;
+        Ok(())
+    }
+
+    // -- Entity Version Heads & Sync Conflicts --
+
+    /// Returns the event IDs of the current version heads of an entity.
+    ///
+    /// More than one head means concurrent edits have been merged but no
+    /// later edit has superseded them yet.
+    pub fn entity_heads(&self, entity_id: &str) -> StorageResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT event_id FROM entity_versions
+             WHERE entity_id = ? AND is_head = 1 ORDER BY event_id",
+        )?;
+        let rows = stmt
+            .query_map(params![entity_id], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Whether an event has already been recorded as a version of an entity.
+    pub fn has_entity_version(&self, entity_id: &str, event_id: &str) -> StorageResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM entity_versions WHERE entity_id = ? AND event_id = ?",
+            params![entity_id, event_id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Records an event as the newest version of an entity.
+    ///
+    /// The heads named in `supersedes` stop being heads. An empty list means
+    /// the event carries no causal history, so it replaces every head.
+    pub fn record_entity_version(
+        &self,
+        entity_id: &str,
+        event_id: &str,
+        supersedes: &[String],
+    ) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        if supersedes.is_empty() {
+            tx.execute(
+                "UPDATE entity_versions SET is_head = 0 WHERE entity_id = ?",
+                params![entity_id],
+            )?;
+        } else {
+            for dep in supersedes {
+                tx.execute(
+                    "UPDATE entity_versions SET is_head = 0 WHERE entity_id = ? AND event_id = ?",
+                    params![entity_id, dep],
+                )?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO entity_versions (entity_id, event_id, is_head) VALUES (?, ?, 1)",
+            params![entity_id, event_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Inserts or replaces a conflict record.
+    pub fn save_sync_conflict(&self, conflict: &ConflictRecord) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_conflicts
+                (conflict_id, entity_id, entity_type, local_json, remote_json, local_heads,
+                 remote_event_id, remote_peer_id, winner, detected_at, resolved_at, resolution)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                conflict.conflict_id,
+                conflict.entity_id,
+                conflict.entity_type,
+                conflict.local_json,
+                conflict.remote_json,
+                serde_json::to_string(&conflict.local_heads)?,
+                conflict.remote_event_id,
+                conflict.remote_peer_id,
+                conflict.winner,
+                conflict.detected_at,
+                conflict.resolved_at,
+                conflict.resolution,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Loads a conflict record by ID.
+    pub fn get_sync_conflict(&self, conflict_id: &str) -> StorageResult<Option<ConflictRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {CONFLICT_COLUMNS} FROM sync_conflicts WHERE conflict_id = ?"
+        ))?;
+        let mut rows = stmt.query_map(params![conflict_id], conflict_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Lists conflicts, newest first, optionally for a single entity.
+    pub fn list_sync_conflicts(
+        &self,
+        entity_id: Option<&str>,
+        include_resolved: bool,
+    ) -> StorageResult<Vec<ConflictRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {CONFLICT_COLUMNS} FROM sync_conflicts
+             WHERE (?1 IS NULL OR entity_id = ?1) AND (?2 OR resolved_at IS NULL)
+             ORDER BY detected_at DESC, conflict_id"
+        ))?;
+        let rows = stmt
+            .query_map(params![entity_id, include_resolved], conflict_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Returns the unresolved conflict for an entity, if any.
+    pub fn open_sync_conflict_for_entity(
+        &self,
+        entity_id: &str,
+    ) -> StorageResult<Option<ConflictRecord>> {
+        Ok(self.list_sync_conflicts(Some(entity_id), false)?.into_iter().next())
+    }
+
+    /// Marks a conflict resolved. Returns false if it was unknown or already resolved.
+    pub fn resolve_sync_conflict(
+        &self,
+        conflict_id: &str,
+        resolution: &str,
+        resolved_at: i64,
+    ) -> StorageResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = conn.execute(
+            "UPDATE sync_conflicts SET resolved_at = ?, resolution = ?
+             WHERE conflict_id = ? AND resolved_at IS NULL",
+            params![resolved_at, resolution, conflict_id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    // -- Sync State Checkpoints --
+
+    /// Upserts serialized per-entity sync state in one transaction.
+    pub fn save_sync_entity_states(&self, states: &[(String, String)]) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO sync_entity_state (entity_id, state_json, updated_at)
+                 VALUES (?, ?, ?)",
+            )?;
+            for (entity_id, state_json) in states {
+                stmt.execute(params![entity_id, state_json, now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Loads all persisted per-entity sync state as `(entity_id, state_json)`.
+    pub fn load_sync_entity_states(&self) -> StorageResult<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT entity_id, state_json FROM sync_entity_state")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Loads the unfinished sync run with a peer, if any.
+    pub fn get_sync_progress(&self, peer_id: &str) -> StorageResult<Option<SyncProgressRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT peer_id, entities_done, entities_total, active_ms, started_at, updated_at
+             FROM sync_progress WHERE peer_id = ?",
+        )?;
+        let mut rows = stmt.query_map(params![peer_id], |row| {
+            Ok(SyncProgressRow {
+                peer_id: row.get(0)?,
+                entities_done: row.get::<_, i64>(1)? as u64,
+                entities_total: row.get::<_, i64>(2)? as u64,
+                active_ms: row.get::<_, i64>(3)? as u64,
+                started_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Saves the progress of a sync run with a peer.
+    pub fn save_sync_progress(&self, progress: &SyncProgressRow) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_progress
+                (peer_id, entities_done, entities_total, active_ms, started_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                progress.peer_id,
+                progress.entities_done as i64,
+                progress.entities_total as i64,
+                progress.active_ms as i64,
+                progress.started_at,
+                progress.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Forgets the sync run with a peer once it has finished.
+    pub fn clear_sync_progress(&self, peer_id: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM sync_progress WHERE peer_id = ?", params![peer_id])?;
+        Ok(())
+    }
+
+    // -- Sync Session History --
+
+    /// Appends a sync session record and drops the oldest rows beyond
+    /// `capacity`, so the history behaves as a bounded ring.
+    pub fn append_sync_session(
+        &self,
+        session_id: &str,
+        peer_id: &str,
+        started_at: i64,
+        record_json: &str,
+        capacity: usize,
+    ) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO sync_sessions (session_id, peer_id, started_at, record_json)
+             VALUES (?, ?, ?, ?)",
+            params![session_id, peer_id, started_at, record_json],
+        )?;
+        tx.execute(
+            "DELETE FROM sync_sessions WHERE seq NOT IN
+                (SELECT seq FROM sync_sessions ORDER BY seq DESC LIMIT ?)",
+            params![capacity as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Lists stored session records as JSON, newest first, optionally for
+    /// one peer only.
+    pub fn list_sync_sessions(
+        &self,
+        peer_id: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT record_json FROM sync_sessions
+             WHERE (?1 IS NULL OR peer_id = ?1)
+             ORDER BY seq DESC LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(params![peer_id, limit as i64], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Deletes the whole session history.
+    pub fn clear_sync_sessions(&self) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM sync_sessions", [])?;
+        Ok(())
+    }
+
     /// Flushes the WAL to the main database file.
     pub fn checkpoint(&self) -> StorageResult<()> {
         let conn = self.conn.lock().unwrap();
@@ -697,6 +1149,8 @@ impl EntityStore {
             "-- Orphaned rows in auxiliary tables (parent entity deleted but these weren't)
              DELETE FROM entity_vectors WHERE entity_id NOT IN (SELECT id FROM entities);
              DELETE FROM sync_ledger WHERE entity_id NOT IN (SELECT id FROM entities);
+             DELETE FROM entity_versions WHERE entity_id NOT IN (SELECT id FROM entities);
+             DELETE FROM sync_entity_state WHERE entity_id NOT IN (SELECT id FROM entities);
              DELETE FROM entity_links WHERE source_id NOT IN (SELECT id FROM entities)
                 OR target_id NOT IN (SELECT id FROM entities);
              -- Transient data that rebuilds automatically on next sync
@@ -1332,6 +1786,27 @@ fn match_filter_value(actual: &serde_json::Value, expected: &serde_json::Value)
     }
 }
 
+const CONFLICT_COLUMNS: &str = "conflict_id, entity_id, entity_type, local_json, remote_json, \
+     local_heads, remote_event_id, remote_peer_id, winner, detected_at, resolved_at, resolution";
+
+fn conflict_from_row(row: &privstack_db::rusqlite::Row<'_>) -> privstack_db::rusqlite::Result<ConflictRecord> {
+    let heads: String = row.get(5)?;
+    Ok(ConflictRecord {
+        conflict_id: row.get(0)?,
+        entity_id: row.get(1)?,
+        entity_type: row.get(2)?,
+        local_json: row.get(3)?,
+        remote_json: row.get(4)?,
+        local_heads: serde_json::from_str(&heads).unwrap_or_default(),
+        remote_event_id: row.get(6)?,
+        remote_peer_id: row.get(7)?,
+        winner: row.get(8)?,
+        detected_at: row.get(9)?,
+        resolved_at: row.get(10)?,
+        resolution: row.get(11)?,
+    })
+}
+
 // -- Schema --
 
 fn initialize_entity_schema(conn: &Connection) -> StorageResult<()> {
@@ -1404,6 +1879,81 @@ fn initialize_entity_schema(conn: &Connection) -> StorageResult<()> {
             updated_at INTEGER NOT NULL
         );
 
+        -- Cloud sync outbox: local events awaiting upload, kept until the
+        -- server acknowledges the batch that carries them.
+        CREATE TABLE IF NOT EXISTS cloud_outbox (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id TEXT NOT NULL,
+            event_id TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            event_json TEXT NOT NULL,
+            state TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at INTEGER NOT NULL DEFAULT 0,
+            batch_key TEXT,
+            cursor_start INTEGER,
+            cursor_end INTEGER,
+            last_error TEXT,
+            enqueued_at INTEGER NOT NULL,
+            UNIQUE (workspace_id, event_id)
+        );
+
+        -- Causal version heads per entity: the events no later edit has
+        -- superseded. Used to tell concurrent edits from sequential ones.
+        CREATE TABLE IF NOT EXISTS entity_versions (
+            entity_id TEXT NOT NULL,
+            event_id TEXT NOT NULL,
+            is_head INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (entity_id, event_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_entity_versions_heads ON entity_versions(entity_id, is_head);
+
+        -- Concurrent edits that sync merged automatically, kept so the user
+        -- can review and override the outcome.
+        CREATE TABLE IF NOT EXISTS sync_conflicts (
+            conflict_id TEXT PRIMARY KEY,
+            entity_id TEXT NOT NULL,
+            entity_type TEXT NOT NULL,
+            local_json TEXT NOT NULL,
+            remote_json TEXT NOT NULL,
+            local_heads TEXT NOT NULL,
+            remote_event_id TEXT NOT NULL,
+            remote_peer_id TEXT NOT NULL,
+            winner TEXT NOT NULL,
+            detected_at INTEGER NOT NULL,
+            resolved_at INTEGER,
+            resolution TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_sync_conflicts_entity ON sync_conflicts(entity_id, resolved_at);
+
+        -- Sync engine state per entity (vector clock, seen events),
+        -- checkpointed so a restart does not lose it.
+        CREATE TABLE IF NOT EXISTS sync_entity_state (
+            entity_id TEXT PRIMARY KEY,
+            state_json TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+
+        -- Unfinished sync runs per peer, for resumable progress reporting.
+        CREATE TABLE IF NOT EXISTS sync_progress (
+            peer_id TEXT PRIMARY KEY,
+            entities_done INTEGER NOT NULL,
+            entities_total INTEGER NOT NULL,
+            active_ms INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+
+        -- Per-session sync diagnostics, trimmed to a fixed number of rows.
+        CREATE TABLE IF NOT EXISTS sync_sessions (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL UNIQUE,
+            peer_id TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            record_json TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sync_sessions_peer ON sync_sessions(peer_id, seq);
+
         -- Plugin fuel consumption history for metrics tracking
         CREATE TABLE IF NOT EXISTS plugin_fuel_history (
             plugin_id TEXT NOT NULL,
@@ -66,14 +66,19 @@ impl EventStore {
         let conn = self.conn.lock().unwrap();
         let payload_json = serde_json::to_string(&event.payload)?;
         let deps_json = serde_json::to_string(&event.dependencies)?;
+        let signature_json = event
+            .signature
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
 
         conn.execute(
             r#"
             INSERT OR IGNORE INTO events (
                 id, entity_id, peer_id,
                 timestamp_wall, timestamp_logical,
-                payload_json, dependencies_json
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                payload_json, dependencies_json, signature_json
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             params![
                 event.id.to_string(),
@@ -83,16 +88,31 @@ impl EventStore {
                 event.timestamp.logical() as i32,
                 payload_json,
                 deps_json,
+                signature_json,
             ],
         )?;
         Ok(())
     }
 
+    /// Attaches an event's signature to the stored copy if that copy is
+    /// unsigned. Existing signatures are never replaced.
+    pub fn attach_signature(&self, event: &Event) -> StorageResult<()> {
+        let Some(signature) = &event.signature else {
+            return Ok(());
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE events SET signature_json = ? WHERE id = ? AND signature_json IS NULL",
+            params![serde_json::to_string(signature)?, event.id.to_string()],
+        )?;
+        Ok(())
+    }
+
     /// Gets events for an entity, ordered by timestamp.
     pub fn get_events_for_entity(&self, entity_id: &EntityId) -> StorageResult<Vec<Event>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json, dependencies_json \
+            "SELECT id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json, dependencies_json, signature_json \
              FROM events WHERE entity_id = ? ORDER BY timestamp_wall, timestamp_logical"
         )?;
 
@@ -104,6 +124,23 @@ impl EventStore {
         Ok(events)
     }
 
+    /// Gets every stored `EntityDeleted` event, ordered by timestamp.
+    pub fn get_deletions(&self) -> StorageResult<Vec<Event>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json, dependencies_json, signature_json \
+             FROM events WHERE json_extract(payload_json, '$.op') = 'EntityDeleted' \
+             ORDER BY timestamp_wall, timestamp_logical"
+        )?;
+
+        let events = stmt
+            .query_map([], row_to_event)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(events)
+    }
+
     /// Gets events newer than a given timestamp from a specific peer.
     pub fn get_events_since(
         &self,
@@ -112,7 +149,7 @@ impl EventStore {
     ) -> StorageResult<Vec<Event>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json, dependencies_json \
+            "SELECT id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json, dependencies_json, signature_json \
              FROM events WHERE peer_id = ? AND (timestamp_wall > ? OR (timestamp_wall = ? AND timestamp_logical > ?)) \
              ORDER BY timestamp_wall, timestamp_logical"
         )?;
@@ -166,6 +203,7 @@ fn row_to_event(row: &privstack_db::rusqlite::Row<'_>) -> privstack_db::rusqlite
     let logical: i32 = row.get(4)?;
     let payload_json: String = row.get(5)?;
     let deps_json: String = row.get(6)?;
+    let signature_json: Option<String> = row.get(7)?;
 
     let id: EventId = id_str.parse().unwrap_or_default();
     let entity_id: EntityId = entity_id_str.parse().unwrap_or_default();
@@ -178,6 +216,7 @@ fn row_to_event(row: &privstack_db::rusqlite::Row<'_>) -> privstack_db::rusqlite
         },
     );
     let dependencies: Vec<EventId> = serde_json::from_str(&deps_json).unwrap_or_default();
+    let signature = signature_json.and_then(|json| serde_json::from_str(&json).ok());
 
     Ok(Event {
         id,
@@ -186,6 +225,7 @@ fn row_to_event(row: &privstack_db::rusqlite::Row<'_>) -> privstack_db::rusqlite
         timestamp,
         payload,
         dependencies,
+        signature,
     })
 }
 
@@ -213,5 +253,8 @@ fn initialize_event_schema(conn: &Connection) -> StorageResult<()> {
         );
         "#,
     )?;
+    // Author signatures were added after the initial schema.
+    privstack_db::add_column_if_not_exists(conn, "events", "signature_json", "TEXT")
+        .map_err(crate::StorageError::Db)?;
     Ok(())
 }
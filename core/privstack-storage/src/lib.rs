@@ -15,6 +15,6 @@ mod error;
 pub mod entity_store;
 mod event_store;
 
-pub use entity_store::{EntityStore, scan_db_file, scan_db_connection, compact_db_file};
+pub use entity_store::{CloudOutboxRow, ConflictRecord, EntityStore, SyncProgressRow, scan_db_file, scan_db_connection, compact_db_file};
 pub use event_store::EventStore;
 pub use error::{StorageError, StorageResult};
@@ -1,5 +1,5 @@
 use privstack_model::{Entity, EntitySchema, FieldType, IndexedField, MergeStrategy};
-use privstack_storage::EntityStore;
+use privstack_storage::{ConflictRecord, EntityStore, SyncProgressRow};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 static NEXT_ID: AtomicU64 = AtomicU64::new(1);
@@ -1109,6 +1109,194 @@ fn clear_cloud_cursors() {
     assert!(cursors.is_empty());
 }
 
+// ── Cloud Sync Outbox ───────────────────────────────────────────
+
+#[test]
+fn cloud_outbox_enqueue_is_idempotent_and_ordered() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.enqueue_cloud_outbox("ws", "ev-1", "ent-1", "{}").unwrap();
+    store.enqueue_cloud_outbox("ws", "ev-2", "ent-1", "{}").unwrap();
+    store.enqueue_cloud_outbox("ws", "ev-1", "ent-1", "{\"dup\":1}").unwrap();
+    store.enqueue_cloud_outbox("other", "ev-3", "ent-9", "{}").unwrap();
+
+    let rows = store.load_cloud_outbox("ws").unwrap();
+    let ids: Vec<_> = rows.iter().map(|r| r.event_id.as_str()).collect();
+    assert_eq!(ids, ["ev-1", "ev-2"]);
+    assert_eq!(rows[0].event_json, "{}");
+    assert_eq!(rows[0].state, "pending");
+    assert_eq!(rows[0].batch_key, None);
+}
+
+#[test]
+fn cloud_outbox_tracks_batch_and_failures() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.enqueue_cloud_outbox("ws", "ev-1", "ent-1", "{}").unwrap();
+    let ids = vec!["ev-1".to_string()];
+
+    store.assign_cloud_outbox_batch("ws", &ids, "key-0-1", 0, 1).unwrap();
+    store.record_cloud_outbox_failure("ws", &ids, 2, 5_000, "boom").unwrap();
+
+    let row = &store.load_cloud_outbox("ws").unwrap()[0];
+    assert_eq!(row.state, "in_flight");
+    assert_eq!(row.batch_key.as_deref(), Some("key-0-1"));
+    assert_eq!((row.cursor_start, row.cursor_end), (Some(0), Some(1)));
+    assert_eq!(row.attempts, 2);
+    assert_eq!(row.next_attempt_at, 5_000);
+    assert_eq!(row.last_error.as_deref(), Some("boom"));
+
+    store.remove_cloud_outbox("ws", &ids).unwrap();
+    assert!(store.load_cloud_outbox("ws").unwrap().is_empty());
+}
+
+#[test]
+fn cloud_outbox_survives_maintenance_and_clears_per_workspace() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.enqueue_cloud_outbox("ws", "ev-1", "ent-1", "{}").unwrap();
+    store.enqueue_cloud_outbox("other", "ev-2", "ent-2", "{}").unwrap();
+
+    store.run_maintenance().unwrap();
+    assert_eq!(store.load_cloud_outbox("ws").unwrap().len(), 1);
+
+    store.clear_cloud_outbox("ws").unwrap();
+    assert!(store.load_cloud_outbox("ws").unwrap().is_empty());
+    assert_eq!(store.load_cloud_outbox("other").unwrap().len(), 1);
+}
+
+// ── Entity Version Heads & Sync Conflicts ──────────────────────
+
+#[test]
+fn entity_versions_track_heads() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.record_entity_version("ent-1", "ev-a", &[]).unwrap();
+    store.record_entity_version("ent-1", "ev-b", &["ev-a".into()]).unwrap();
+    assert_eq!(store.entity_heads("ent-1").unwrap(), ["ev-b"]);
+
+    // A concurrent edit that also descends from ev-a leaves two heads.
+    store.record_entity_version("ent-1", "ev-c", &["ev-a".into()]).unwrap();
+    assert_eq!(store.entity_heads("ent-1").unwrap(), ["ev-b", "ev-c"]);
+    assert!(store.has_entity_version("ent-1", "ev-a").unwrap());
+    assert!(!store.has_entity_version("ent-1", "ev-z").unwrap());
+
+    // No dependencies means the event replaces every head.
+    store.record_entity_version("ent-1", "ev-d", &[]).unwrap();
+    assert_eq!(store.entity_heads("ent-1").unwrap(), ["ev-d"]);
+}
+
+fn conflict(id: &str, entity_id: &str, detected_at: i64) -> ConflictRecord {
+    ConflictRecord {
+        conflict_id: id.into(),
+        entity_id: entity_id.into(),
+        entity_type: "note".into(),
+        local_json: r#"{"title":"mine"}"#.into(),
+        remote_json: r#"{"title":"theirs"}"#.into(),
+        local_heads: vec!["ev-a".into()],
+        remote_event_id: "ev-r".into(),
+        remote_peer_id: "peer-2".into(),
+        winner: "remote".into(),
+        detected_at,
+        resolved_at: None,
+        resolution: None,
+    }
+}
+
+#[test]
+fn sync_conflicts_roundtrip_and_filter() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.save_sync_conflict(&conflict("c-1", "ent-1", 10)).unwrap();
+    store.save_sync_conflict(&conflict("c-2", "ent-2", 20)).unwrap();
+
+    assert_eq!(store.get_sync_conflict("c-1").unwrap(), Some(conflict("c-1", "ent-1", 10)));
+    assert_eq!(store.get_sync_conflict("nope").unwrap(), None);
+
+    let all: Vec<_> = store.list_sync_conflicts(None, false).unwrap()
+        .into_iter().map(|c| c.conflict_id).collect();
+    assert_eq!(all, ["c-2", "c-1"]);
+    assert_eq!(store.list_sync_conflicts(Some("ent-1"), false).unwrap().len(), 1);
+    assert_eq!(
+        store.open_sync_conflict_for_entity("ent-2").unwrap().unwrap().conflict_id,
+        "c-2"
+    );
+}
+
+#[test]
+fn resolving_a_conflict_hides_it_from_open_lists() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store.save_sync_conflict(&conflict("c-1", "ent-1", 10)).unwrap();
+
+    assert!(store.resolve_sync_conflict("c-1", "keep_mine", 50).unwrap());
+    assert!(!store.resolve_sync_conflict("c-1", "keep_theirs", 60).unwrap());
+
+    assert!(store.list_sync_conflicts(None, false).unwrap().is_empty());
+    assert!(store.open_sync_conflict_for_entity("ent-1").unwrap().is_none());
+    let resolved = store.get_sync_conflict("c-1").unwrap().unwrap();
+    assert_eq!(resolved.resolved_at, Some(50));
+    assert_eq!(resolved.resolution.as_deref(), Some("keep_mine"));
+    assert_eq!(store.list_sync_conflicts(None, true).unwrap().len(), 1);
+}
+
+// ── Sync State Checkpoints ──────────────────────────────────────
+
+#[test]
+fn sync_entity_states_upsert_and_load() {
+    let store = EntityStore::open_in_memory().unwrap();
+    store
+        .save_sync_entity_states(&[("ent-1".into(), "{}".into()), ("ent-2".into(), "{}".into())])
+        .unwrap();
+    store.save_sync_entity_states(&[("ent-1".into(), r#"{"n":1}"#.into())]).unwrap();
+
+    let mut states = store.load_sync_entity_states().unwrap();
+    states.sort();
+    assert_eq!(
+        states,
+        [("ent-1".to_string(), r#"{"n":1}"#.to_string()), ("ent-2".to_string(), "{}".to_string())]
+    );
+}
+
+#[test]
+fn sync_progress_roundtrip_and_clear() {
+    let store = EntityStore::open_in_memory().unwrap();
+    assert_eq!(store.get_sync_progress("peer-1").unwrap(), None);
+
+    let progress = SyncProgressRow {
+        peer_id: "peer-1".into(),
+        entities_done: 40,
+        entities_total: 100,
+        active_ms: 2_000,
+        started_at: 10,
+        updated_at: 20,
+    };
+    store.save_sync_progress(&progress).unwrap();
+    assert_eq!(store.get_sync_progress("peer-1").unwrap(), Some(progress));
+    assert_eq!(store.get_sync_progress("peer-2").unwrap(), None);
+
+    store.clear_sync_progress("peer-1").unwrap();
+    assert_eq!(store.get_sync_progress("peer-1").unwrap(), None);
+}
+
+// ── Sync Session History ────────────────────────────────────────
+
+#[test]
+fn sync_sessions_are_a_bounded_ring() {
+    let store = EntityStore::open_in_memory().unwrap();
+    for i in 0..5 {
+        let peer = if i % 2 == 0 { "peer-a" } else { "peer-b" };
+        store
+            .append_sync_session(&format!("s-{i}"), peer, i, &format!("{{\"n\":{i}}}"), 3)
+            .unwrap();
+    }
+
+    // Only the newest three survive, newest first.
+    assert_eq!(
+        store.list_sync_sessions(None, 10).unwrap(),
+        [r#"{"n":4}"#, r#"{"n":3}"#, r#"{"n":2}"#]
+    );
+    assert_eq!(store.list_sync_sessions(Some("peer-b"), 10).unwrap(), [r#"{"n":3}"#]);
+    assert_eq!(store.list_sync_sessions(None, 1).unwrap().len(), 1);
+
+    store.clear_sync_sessions().unwrap();
+    assert!(store.list_sync_sessions(None, 10).unwrap().is_empty());
+}
+
 // ── Plugin Fuel History ─────────────────────────────────────────
 
 #[test]
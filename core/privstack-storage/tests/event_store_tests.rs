@@ -1,5 +1,5 @@
 use privstack_storage::EventStore;
-use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use privstack_types::{EntityId, Event, EventPayload, EventSignature, HybridTimestamp, PeerId};
 
 fn make_event(entity_id: EntityId, peer_id: PeerId, wall: u64) -> Event {
     Event::new(
@@ -81,6 +81,29 @@ fn multiple_entities() {
     assert_eq!(store.get_events_for_entity(&eid2).unwrap().len(), 1);
 }
 
+// ── get_deletions ────────────────────────────────────────────────
+
+#[test]
+fn get_deletions_returns_only_deletes() {
+    let store = EventStore::open_in_memory().unwrap();
+    let pid = PeerId::new();
+    let (kept, gone) = (EntityId::new(), EntityId::new());
+
+    store.save_event(&make_event(kept, pid, 100)).unwrap();
+    store.save_event(&make_event(gone, pid, 100)).unwrap();
+    let delete = Event::new(
+        gone,
+        pid,
+        HybridTimestamp::new(200, 0),
+        EventPayload::EntityDeleted { entity_type: "note".into() },
+    );
+    store.save_event(&delete).unwrap();
+
+    let deletions = store.get_deletions().unwrap();
+    assert_eq!(deletions.len(), 1);
+    assert_eq!(deletions[0].id, delete.id);
+}
+
 // ── get_events_since ─────────────────────────────────────────────
 
 #[test]
@@ -183,3 +206,90 @@ fn save_event_with_dependencies() {
     assert_eq!(events.len(), 2);
     assert_eq!(events[1].dependencies.len(), 1);
 }
+
+// ── Event signatures ─────────────────────────────────────────────
+
+#[test]
+fn save_event_preserves_signature() {
+    let store = EventStore::open_in_memory().unwrap();
+    let eid = EntityId::new();
+
+    let mut signed = make_event(eid, PeerId::new(), 1000);
+    signed.signature = Some(EventSignature {
+        device_key: [3u8; 32],
+        signature: vec![9u8; 64],
+    });
+    let unsigned = make_event(eid, PeerId::new(), 2000);
+
+    store.save_event(&signed).unwrap();
+    store.save_event(&unsigned).unwrap();
+
+    let events = store.get_events_for_entity(&eid).unwrap();
+    assert_eq!(events[0].signature, signed.signature);
+    assert!(events[1].signature.is_none());
+}
+
+#[test]
+fn attach_signature_fills_unsigned_copy_only() {
+    let store = EventStore::open_in_memory().unwrap();
+    let eid = EntityId::new();
+
+    let unsigned = make_event(eid, PeerId::new(), 1000);
+    store.save_event(&unsigned).unwrap();
+
+    let mut signed = unsigned.clone();
+    signed.signature = Some(EventSignature {
+        device_key: [1u8; 32],
+        signature: vec![2u8; 64],
+    });
+    store.attach_signature(&signed).unwrap();
+
+    let mut other = unsigned.clone();
+    other.signature = Some(EventSignature {
+        device_key: [5u8; 32],
+        signature: vec![6u8; 64],
+    });
+    store.attach_signature(&other).unwrap();
+
+    let events = store.get_events_for_entity(&eid).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].signature, signed.signature);
+}
+
+#[test]
+fn legacy_schema_gains_signature_column() {
+    use privstack_db::rusqlite::params;
+    use std::sync::{Arc, Mutex};
+
+    let conn = privstack_db::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE events (
+            id TEXT PRIMARY KEY,
+            entity_id TEXT NOT NULL,
+            peer_id TEXT NOT NULL,
+            timestamp_wall INTEGER NOT NULL,
+            timestamp_logical INTEGER NOT NULL,
+            payload_json TEXT NOT NULL,
+            dependencies_json TEXT NOT NULL DEFAULT '[]'
+        );",
+    )
+    .unwrap();
+    let legacy = make_event(EntityId::new(), PeerId::new(), 1000);
+    conn.execute(
+        "INSERT INTO events (id, entity_id, peer_id, timestamp_wall, timestamp_logical, payload_json) \
+         VALUES (?, ?, ?, ?, 0, ?)",
+        params![
+            legacy.id.to_string(),
+            legacy.entity_id.to_string(),
+            legacy.peer_id.to_string(),
+            1000i64,
+            serde_json::to_string(&legacy.payload).unwrap(),
+        ],
+    )
+    .unwrap();
+
+    let store = EventStore::open_with_conn(Arc::new(Mutex::new(conn))).unwrap();
+    let events = store.get_events_for_entity(&legacy.entity_id).unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].signature.is_none());
+}
@@ -0,0 +1,22 @@
+//! Fuzzes the sync wire decoder with arbitrary length-prefixed input.
+//!
+//! Run with `cargo +nightly fuzz run read_message` from `privstack-sync/`.
+//! Any panic, or a decoded frame that violates the size limits, is a bug.
+
+#![no_main]
+
+use futures::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+use privstack_sync::p2p::codec::{decode_message, read_message, MAX_FRAME_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    // Whole input as a stream: exercises the length prefix checks.
+    let result = futures::executor::block_on(read_message(&mut Cursor::new(data)));
+    if result.is_ok() {
+        let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+        assert!(len <= MAX_FRAME_SIZE, "accepted a frame of {len} bytes");
+    }
+
+    // Whole input as a frame body: exercises JSON/CBOR/zstd decoding directly.
+    let _ = decode_message(data);
+});
@@ -1,116 +1,944 @@
 //! ACL event handler — applies ACL-as-CRDT events to the enterprise policy.
+//!
+//! Every ACL event is authorized against the sender's role *at the event's
+//! causal point*: the applicator keeps the ACL events it has seen ordered by
+//! `(timestamp, event id)`. When one arrives it rewinds the registers to where
+//! the event sorts in and replays from there, so a revoke that arrives late
+//! still invalidates grants the revoked peer issued after it.
+//! Each ACL fact (a peer's role, a team's role, the default role, a team
+//! membership, an entity's parent, an inheritance break) is a
+//! last-writer-wins register under that same order, which makes concurrent
+//! grants and revokes converge identically on every peer.
+//!
+//! The history is persisted in the policy's
+//! [`PolicyStore`](crate::policy_store::PolicyStore), so it survives restarts.
+//! Events further than the stability window (a week by default) behind the
+//! newest ACL event are treated as stable and compacted: they leave the
+//! history, and only the last accepted write to each register is kept. An
+//! event that still arrives below that frontier is ignored if its register
+//! has a newer compacted write, and is otherwise rejected.
+//!
+//! The order is the sender's timestamp, so it is bounded on arrival: an event
+//! below the frontier is rejected rather than authorized against state it
+//! never saw, and so is an event from a peer whose revoke (or demotion, or
+//! removal from a team) this peer has already applied if it is timestamped
+//! more than the clock skew tolerance (5 seconds by default) before that
+//! revoke and the revoke does not list it among its dependencies. A revoked
+//! admin therefore cannot backdate a grant to a time they still held their
+//! role; events within the tolerance are treated as concurrent and replayed.
+//!
+//! Roles are resolved with inheritance at every causal point, so a grant on a
+//! container authorizes changes to its descendants. A limited peer grant
+//! authorizes events timestamped before its expiry; its use count is local to
+//! each peer, and lapses are propagated by the revoke events of the sweeper
+//! on a peer allowed to revoke the grant.
+//!
+//! Authorization rules:
+//! - Entity ACL changes require `Admin` on the entity.
+//! - Granting `Owner`, changing an existing `Owner` entry, or setting a default
+//!   role of `Admin` or above requires `Owner`.
+//! - Team membership changes require `Admin` on the team's own entity (see
+//!   [`TeamId::admin_entity`]) and, for every entity the team holds a role
+//!   on, at least that role (and `Admin`).
+//! - Setting an entity's parent requires `Admin` on the entity and `Editor`
+//!   on the new parent, and is rejected if it would create a cycle.
+//! - Breaking or restoring inheritance requires `Admin` on the entity.
+//!
+//! ACL state present in the policy before an entry is first touched by an
+//! event is treated as the baseline for replay.
 
 use crate::error::SyncError;
-use crate::policy::{EnterpriseSyncPolicy, SyncRole, TeamId};
+use crate::policy::{
+    creates_acl_cycle, resolve_inherited_role, AclSource, AuditAction, AuditDecision,
+    EnterpriseSyncPolicy, EntityAcl, GrantLimits, GrantState, SyncRole, TeamId,
+};
 use async_trait::async_trait;
-use privstack_types::{EntityId, Event, EventPayload, PeerId};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// How far behind the newest ACL event an event must be before it is treated
+/// as stable and compacted out of the history.
+pub const DEFAULT_ACL_STABILITY_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How far an event may be timestamped before an applied revoke of its author
+/// and still be treated as concurrent with it.
+pub const DEFAULT_ACL_CLOCK_SKEW: Duration = Duration::from_secs(5);
 
 /// Trait for handling ACL events that flow through the sync pipeline.
 #[async_trait]
 pub trait AclEventHandler: Send + Sync {
     /// Attempts to handle an event as an ACL event.
     /// Returns `Ok(true)` if handled (callers should skip normal applicator),
-    /// `Ok(false)` if not an ACL event, or `Err` on failure.
+    /// `Ok(false)` if not an ACL event, `Err(SyncError::PolicyDenied)` if the
+    /// sender is not authorized to make the change, or `Err` on failure.
     async fn handle_acl_event(&self, event: &Event) -> Result<bool, SyncError>;
 }
 
+/// A single ACL fact, resolved as a last-writer-wins register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AclKey {
+    PeerRole(EntityId, PeerId),
+    TeamRole(EntityId, TeamId),
+    DefaultRole(EntityId),
+    TeamMember(TeamId, PeerId),
+    Parent(EntityId),
+    Inherits(EntityId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AclValue {
+    Role(Option<SyncRole>),
+    /// A peer role granted with limits.
+    LimitedRole(SyncRole, GrantLimits),
+    Member(bool),
+    Parent(Option<EntityId>),
+    Inherits(bool),
+}
+
+/// A parsed ACL event: which register it writes and the value it writes.
+#[derive(Debug, Clone, Copy)]
+struct AclChange {
+    key: AclKey,
+    value: AclValue,
+}
+
+/// Causal order of ACL events. `EventId` is not ordered, so its string form
+/// breaks timestamp ties.
+type CausalKey = (HybridTimestamp, String);
+
+fn causal_key(event: &Event) -> CausalKey {
+    (event.timestamp, event.id.to_string())
+}
+
+/// An ACL event in the history with the outcome of its last replay.
+struct AclRecord {
+    event: Event,
+    change: AclChange,
+    /// Register value before the event, restored when a replay rewinds past it.
+    prior: AclValue,
+    accepted: bool,
+}
+
+impl AclRecord {
+    /// Whether this record took a role or team membership away from `peer`
+    /// without having seen `event` (listed it as a dependency).
+    fn revokes(&self, peer: PeerId, event: &Event) -> bool {
+        let lowered = match (self.change.key, self.change.value, self.prior) {
+            (AclKey::PeerRole(_, p), new, prior) if p == peer => {
+                value_role(new) < value_role(prior)
+            }
+            (AclKey::TeamMember(_, p), AclValue::Member(false), AclValue::Member(true)) => {
+                p == peer
+            }
+            _ => false,
+        };
+        self.accepted && lowered && !self.event.dependencies.contains(&event.id)
+    }
+}
+
+fn value_role(value: AclValue) -> Option<SyncRole> {
+    match value {
+        AclValue::Role(role) => role,
+        AclValue::LimitedRole(role, _) => Some(role),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct AclHistory {
+    /// Whether the persisted history has been loaded.
+    loaded: bool,
+    /// ACL events above the compaction frontier, in causal order.
+    events: BTreeMap<CausalKey, AclRecord>,
+    /// Register values last written to the policy.
+    applied: HashMap<AclKey, AclValue>,
+    /// Causal point of the last compacted write to each register.
+    compacted: HashMap<AclKey, CausalKey>,
+}
+
 /// Applies ACL events to an `EnterpriseSyncPolicy`.
 pub struct AclApplicator {
     policy: Arc<EnterpriseSyncPolicy>,
+    history: Mutex<AclHistory>,
+    stability_window: Duration,
+    clock_skew: Duration,
 }
 
 impl AclApplicator {
     pub fn new(policy: Arc<EnterpriseSyncPolicy>) -> Self {
-        Self { policy }
+        Self {
+            policy,
+            history: Mutex::new(AclHistory::default()),
+            stability_window: DEFAULT_ACL_STABILITY_WINDOW,
+            clock_skew: DEFAULT_ACL_CLOCK_SKEW,
+        }
+    }
+
+    /// Sets how far an event may be timestamped before an applied revoke of
+    /// its author and still be treated as concurrent with it.
+    pub fn with_clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Sets how far behind the newest ACL event an event must be before it is
+    /// compacted out of the history.
+    pub fn with_stability_window(mut self, window: Duration) -> Self {
+        self.stability_window = window;
+        self
+    }
+
+    /// Loads the persisted history and reads the current value of every
+    /// register it touches.
+    async fn load_history(&self, history: &mut AclHistory) -> Result<(), SyncError> {
+        if let Some(store) = self.policy.store() {
+            for (event, prior, accepted) in store.load_acl_history()? {
+                let (Some(change), Some(prior)) =
+                    (parse_change(&event.payload)?, parse_change(&prior)?)
+                else {
+                    continue;
+                };
+                history.events.insert(
+                    causal_key(&event),
+                    AclRecord {
+                        event,
+                        change,
+                        prior: prior.value,
+                        accepted,
+                    },
+                );
+            }
+            for event in store.load_acl_compacted_writes()? {
+                if let Some(change) = parse_change(&event.payload)? {
+                    history.compacted.insert(change.key, causal_key(&event));
+                }
+            }
+        }
+        let keys: HashSet<AclKey> = history.events.values().map(|r| r.change.key).collect();
+        for key in keys {
+            let value = self.live_value(key).await;
+            history.applied.insert(key, value);
+        }
+        history.loaded = true;
+        Ok(())
+    }
+
+    /// Wall time below which events are stable, if there is any history.
+    fn frontier(&self, history: &AclHistory) -> Option<u64> {
+        let newest = history.events.keys().next_back()?.0.wall_time();
+        Some(newest.saturating_sub(self.stability_window.as_millis() as u64))
+    }
+
+    /// Why a newly arriving event cannot be placed in the history, if it
+    /// cannot: it is below the frontier, or its author was revoked after it.
+    fn out_of_bounds(&self, history: &AclHistory, event: &Event) -> Option<&'static str> {
+        if self
+            .frontier(history)
+            .is_some_and(|frontier| event.timestamp.wall_time() < frontier)
+        {
+            return Some("ACL change predates the stability frontier");
+        }
+        let fenced_after = event
+            .timestamp
+            .wall_time()
+            .saturating_add(self.clock_skew.as_millis() as u64);
+        let later = (event.timestamp, String::new())..;
+        if history
+            .events
+            .range(later)
+            .any(|((ts, _), record)| {
+                ts.wall_time() > fenced_after && record.revokes(event.peer_id, event)
+            })
+        {
+            return Some("ACL change predates the revoke of its author");
+        }
+        None
+    }
+
+    /// Compacts events further than the stability window behind the newest
+    /// one, keeping the last accepted write to each register.
+    fn compact(&self, history: &mut AclHistory) {
+        let Some(frontier) = self.frontier(history) else {
+            return;
+        };
+        while let Some(entry) = history.events.first_entry() {
+            if entry.key().0.wall_time() >= frontier {
+                break;
+            }
+            let (key, record) = entry.remove_entry();
+            let register = record.accepted.then(|| register_id(record.change.key));
+            if record.accepted {
+                history.compacted.insert(record.change.key, key);
+            }
+            if let Some(store) = self.policy.store() {
+                let _ = store.compact_acl_history_event(&record.event, register.as_deref());
+            }
+        }
+    }
+
+    /// Reads a register's current value from the policy.
+    async fn live_value(&self, key: AclKey) -> AclValue {
+        match key {
+            AclKey::TeamMember(team, peer) => {
+                let teams = self.policy.teams.read().await;
+                AclValue::Member(teams.get(&team).is_some_and(|m| m.contains(&peer)))
+            }
+            AclKey::Parent(entity) => AclValue::Parent(self.policy.parent_of(&entity).await),
+            AclKey::Inherits(entity) => AclValue::Inherits(self.policy.inherits(&entity).await),
+            _ => {
+                let acls = self.policy.acls.read().await;
+                let grant_limits = self.policy.grant_limits.read().await;
+                role_value(&acls, &grant_limits, key)
+            }
+        }
+    }
+
+    /// Writes a register value to the policy.
+    async fn write_value(&self, key: AclKey, value: AclValue) {
+        match (key, value) {
+            (AclKey::PeerRole(entity, peer), AclValue::Role(Some(role))) => {
+                self.policy.grant_peer_role(entity, peer, role).await
+            }
+            (AclKey::PeerRole(entity, peer), AclValue::LimitedRole(role, limits)) => {
+                self.policy
+                    .grant_peer_role_limited(entity, peer, role, limits)
+                    .await
+            }
+            (AclKey::PeerRole(entity, peer), AclValue::Role(None)) => {
+                self.policy.revoke_peer_role(entity, peer).await
+            }
+            (AclKey::TeamRole(entity, team), AclValue::Role(Some(role))) => {
+                self.policy.grant_team_role(entity, team, role).await
+            }
+            (AclKey::TeamRole(entity, team), AclValue::Role(None)) => {
+                self.policy.revoke_team_role(entity, team).await
+            }
+            (AclKey::DefaultRole(entity), AclValue::Role(role)) => {
+                self.policy.set_default_role(entity, role).await
+            }
+            (AclKey::TeamMember(team, peer), AclValue::Member(true)) => {
+                self.policy.add_team_member(team, peer).await
+            }
+            (AclKey::TeamMember(team, peer), AclValue::Member(false)) => {
+                self.policy.remove_team_member(team, peer).await
+            }
+            (AclKey::Parent(entity), AclValue::Parent(parent)) => {
+                if let Err(e) = self.policy.set_parent(entity, parent).await {
+                    tracing::warn!("Failed to set ACL parent of {}: {}", entity, e)
+                }
+            }
+            (AclKey::Inherits(entity), AclValue::Inherits(true)) => {
+                self.policy.restore_inheritance(entity).await
+            }
+            (AclKey::Inherits(entity), AclValue::Inherits(false)) => {
+                self.policy.break_inheritance(entity).await
+            }
+            (key, value) => {
+                tracing::warn!("Mismatched ACL register write {:?} = {:?}", key, value)
+            }
+        }
     }
 }
 
 #[async_trait]
 impl AclEventHandler for AclApplicator {
     async fn handle_acl_event(&self, event: &Event) -> Result<bool, SyncError> {
-        match &event.payload {
-            EventPayload::AclGrantPeer {
-                entity_id,
-                peer_id,
-                role,
-            } => {
-                let eid: EntityId = entity_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let pid: PeerId = peer_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let r = parse_role(role)?;
-                self.policy.grant_peer_role(eid, pid, r).await;
+        let change = match parse_change(&event.payload)? {
+            Some(change) => change,
+            // Not an ACL event
+            None => return Ok(false),
+        };
+
+        let mut guard = self.history.lock().await;
+        let history = &mut *guard;
+        if !history.loaded {
+            self.load_history(history).await?;
+        }
+        let causal_key = causal_key(event);
+        if let Some(record) = history.events.get(&causal_key) {
+            // Duplicate delivery: report the verdict of the last replay.
+            return if record.accepted {
                 Ok(true)
+            } else {
+                Err(denied(event, &change))
+            };
+        }
+        if history.compacted.get(&change.key).is_some_and(|last| *last >= causal_key) {
+            // Below the frontier, and a newer write to the register has
+            // already been compacted: the event would lose anyway.
+            return Ok(true);
+        }
+        if let Some(reason) = self.out_of_bounds(history, event) {
+            self.policy
+                .log(
+                    event.peer_id,
+                    key_entity(change.key),
+                    AuditAction::AclChange,
+                    AuditDecision::Denied,
+                    format!("{reason}: {}", describe(&change)),
+                )
+                .await;
+            return Err(denied(event, &change));
+        }
+
+        if let Entry::Vacant(slot) = history.applied.entry(change.key) {
+            slot.insert(self.live_value(change.key).await);
+        }
+        // Rewind to the state just before the new event.
+        let mut state = history.applied.clone();
+        for (_, record) in history.events.range(causal_key.clone()..).rev() {
+            state.insert(record.change.key, record.prior);
+        }
+        history.events.insert(
+            causal_key.clone(),
+            AclRecord {
+                event: event.clone(),
+                change,
+                prior: state[&change.key],
+                accepted: false,
+            },
+        );
+
+        // Replay from the new event on, authorizing each event against the
+        // state produced by everything before it.
+        let mut flipped = Vec::new();
+        {
+            let acls = self.policy.acls.read().await;
+            let teams = self.policy.teams.read().await;
+            let parents = self.policy.parents.read().await;
+            let inheritance_breaks = self.policy.inheritance_breaks.read().await;
+            let grant_limits = self.policy.grant_limits.read().await;
+            for record in history.events.range_mut(causal_key.clone()..).map(|(_, r)| r) {
+                let view = AclView {
+                    state: &state,
+                    acls: &acls,
+                    teams: &teams,
+                    parents: &parents,
+                    inheritance_breaks: &inheritance_breaks,
+                    grant_limits: &grant_limits,
+                    at: UNIX_EPOCH + Duration::from_millis(record.event.timestamp.wall_time()),
+                };
+                let accepted = view.authorize(&record.event, &record.change);
+                record.prior = state[&record.change.key];
+                if accepted {
+                    state.insert(record.change.key, record.change.value);
+                }
+                if record.event.id != event.id && accepted != record.accepted {
+                    flipped.push((record.event.peer_id, record.change, accepted));
+                }
+                record.accepted = accepted;
             }
-            EventPayload::AclRevokePeer {
-                entity_id,
-                peer_id,
-            } => {
-                let eid: EntityId = entity_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let pid: PeerId = peer_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                self.policy.revoke_peer_role(eid, pid).await;
-                Ok(true)
+        }
+        if let Some(store) = self.policy.store() {
+            for record in history.events.range(causal_key.clone()..).map(|(_, r)| r) {
+                let prior = change_payload(AclChange {
+                    key: record.change.key,
+                    value: record.prior,
+                });
+                let _ = store.save_acl_history_event(&record.event, &prior, record.accepted);
             }
-            EventPayload::AclGrantTeam {
-                entity_id,
-                team_id,
-                role,
-            } => {
-                let eid: EntityId = entity_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let tid = parse_team_id(team_id)?;
-                let r = parse_role(role)?;
-                self.policy.grant_team_role(eid, tid, r).await;
-                Ok(true)
+        }
+
+        let changed: Vec<(AclKey, AclValue)> = state
+            .iter()
+            .filter(|(key, value)| history.applied.get(*key) != Some(*value))
+            .map(|(key, value)| (*key, *value))
+            .collect();
+        // Detach re-parented entities first so intermediate links never form
+        // a cycle the policy would refuse.
+        for (key, _) in &changed {
+            if let AclKey::Parent(entity) = key {
+                if history.applied.get(key) != Some(&AclValue::Parent(None)) {
+                    self.write_value(AclKey::Parent(*entity), AclValue::Parent(None)).await;
+                }
             }
-            EventPayload::AclRevokeTeam {
-                entity_id,
-                team_id,
-            } => {
-                let eid: EntityId = entity_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let tid = parse_team_id(team_id)?;
-                self.policy.revoke_team_role(eid, tid).await;
-                Ok(true)
+        }
+        for (key, value) in changed {
+            self.write_value(key, value).await;
+            history.applied.insert(key, value);
+        }
+
+        // Audit the new event and any later event whose verdict flipped.
+        let accepted = history.events[&causal_key].accepted;
+        let (decision, detail) = if accepted {
+            (AuditDecision::Allowed, format!("ACL change: {}", describe(&change)))
+        } else {
+            (AuditDecision::Denied, format!("unauthorized ACL change: {}", describe(&change)))
+        };
+        self.policy
+            .log(event.peer_id, key_entity(change.key), AuditAction::AclChange, decision, detail)
+            .await;
+        for (peer, ch, now_accepted) in flipped {
+            let (decision, detail) = if now_accepted {
+                (AuditDecision::Allowed, format!("ACL change authorized on replay: {}", describe(&ch)))
+            } else {
+                (AuditDecision::Denied, format!("ACL change invalidated on replay: {}", describe(&ch)))
+            };
+            self.policy
+                .log(peer, key_entity(ch.key), AuditAction::AclChange, decision, detail)
+                .await;
+        }
+
+        self.compact(history);
+        if accepted {
+            Ok(true)
+        } else {
+            Err(denied(event, &change))
+        }
+    }
+}
+
+/// ACL state at a causal point: replayed registers over the live policy.
+struct AclView<'a> {
+    state: &'a HashMap<AclKey, AclValue>,
+    acls: &'a HashMap<EntityId, EntityAcl>,
+    teams: &'a HashMap<TeamId, HashSet<PeerId>>,
+    parents: &'a HashMap<EntityId, EntityId>,
+    inheritance_breaks: &'a HashSet<EntityId>,
+    grant_limits: &'a HashMap<(EntityId, PeerId), GrantState>,
+    /// Wall time of the event being authorized.
+    at: SystemTime,
+}
+
+impl AclView<'_> {
+    fn value(&self, key: AclKey) -> AclValue {
+        if let Some(value) = self.state.get(&key) {
+            return *value;
+        }
+        match key {
+            AclKey::TeamMember(team, peer) => {
+                AclValue::Member(self.teams.get(&team).is_some_and(|m| m.contains(&peer)))
             }
-            EventPayload::AclSetDefault { entity_id, role } => {
-                let eid: EntityId = entity_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                let r = match role {
-                    Some(s) if !s.is_empty() => Some(parse_role(s)?),
-                    _ => None,
+            AclKey::Parent(entity) => AclValue::Parent(self.parents.get(&entity).copied()),
+            AclKey::Inherits(entity) => {
+                AclValue::Inherits(!self.inheritance_breaks.contains(&entity))
+            }
+            _ => role_value(self.acls, self.grant_limits, key),
+        }
+    }
+
+    /// The role a register grants at this causal point. A limited grant
+    /// counts until its expiry.
+    fn role(&self, key: AclKey) -> Option<SyncRole> {
+        match self.value(key) {
+            AclValue::Role(role) => role,
+            AclValue::LimitedRole(role, limits) => match limits.expires_at {
+                Some(at) if self.at >= at => None,
+                _ => Some(role),
+            },
+            _ => None,
+        }
+    }
+
+    /// Teams with a (possibly revoked) role entry on `entity`.
+    fn teams_on(&self, entity: EntityId) -> HashSet<TeamId> {
+        let mut teams: HashSet<TeamId> = self
+            .acls
+            .get(&entity)
+            .map(|acl| acl.team_roles.keys().copied().collect())
+            .unwrap_or_default();
+        teams.extend(self.state.keys().filter_map(|key| match key {
+            AclKey::TeamRole(e, t) if *e == entity => Some(*t),
+            _ => None,
+        }));
+        teams
+    }
+
+    /// Entities on which `team` holds a role, with that role.
+    fn team_grants(&self, team: TeamId) -> Vec<(EntityId, SyncRole)> {
+        let mut entities: HashSet<EntityId> = self
+            .acls
+            .iter()
+            .filter(|(_, acl)| acl.team_roles.contains_key(&team))
+            .map(|(entity, _)| *entity)
+            .collect();
+        entities.extend(self.state.keys().filter_map(|key| match key {
+            AclKey::TeamRole(e, t) if *t == team => Some(*e),
+            _ => None,
+        }));
+        entities
+            .into_iter()
+            .filter_map(|e| self.role(AclKey::TeamRole(e, team)).map(|r| (e, r)))
+            .collect()
+    }
+
+    /// Same resolution as `EnterpriseSyncPolicy::resolve_role`.
+    fn resolve_role(&self, peer: PeerId, entity: EntityId) -> Option<SyncRole> {
+        resolve_inherited_role(self, peer, entity)
+    }
+
+    /// Peer and team role changes need `Admin`, or `Owner` when an `Owner`
+    /// entry is granted or replaced.
+    fn authorize_role_change(
+        &self,
+        sender: PeerId,
+        entity: EntityId,
+        key: AclKey,
+        new: Option<SyncRole>,
+    ) -> bool {
+        let current = self.role(key);
+        let required = if new == Some(SyncRole::Owner) || current == Some(SyncRole::Owner) {
+            SyncRole::Owner
+        } else {
+            SyncRole::Admin
+        };
+        self.resolve_role(sender, entity) >= Some(required)
+    }
+
+    fn authorize(&self, event: &Event, change: &AclChange) -> bool {
+        let sender = event.peer_id;
+        match (change.key, change.value) {
+            (AclKey::PeerRole(entity, _), AclValue::LimitedRole(role, _)) => {
+                self.authorize_role_change(sender, entity, change.key, Some(role))
+            }
+            (AclKey::PeerRole(entity, _), AclValue::Role(new))
+            | (AclKey::TeamRole(entity, _), AclValue::Role(new)) => {
+                self.authorize_role_change(sender, entity, change.key, new)
+            }
+            (AclKey::DefaultRole(entity), AclValue::Role(new)) => {
+                let current = self.role(change.key);
+                let required = if new >= Some(SyncRole::Admin) || current >= Some(SyncRole::Admin) {
+                    SyncRole::Owner
+                } else {
+                    SyncRole::Admin
                 };
-                self.policy.set_default_role(eid, r).await;
-                Ok(true)
+                self.resolve_role(sender, entity) >= Some(required)
             }
-            EventPayload::TeamAddPeer { team_id, peer_id } => {
-                let tid = parse_team_id(team_id)?;
-                let pid: PeerId = peer_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                self.policy.add_team_member(tid, pid).await;
-                Ok(true)
+            (AclKey::TeamMember(team, _), AclValue::Member(_)) => {
+                self.resolve_role(sender, team.admin_entity()) >= Some(SyncRole::Admin)
+                    && self.team_grants(team).into_iter().all(|(entity, role)| {
+                        self.resolve_role(sender, entity) >= Some(role.max(SyncRole::Admin))
+                    })
             }
-            EventPayload::TeamRemovePeer { team_id, peer_id } => {
-                let tid = parse_team_id(team_id)?;
-                let pid: PeerId = peer_id.parse().map_err(|e| SyncError::Protocol(format!("{e}")))?;
-                self.policy.remove_team_member(tid, pid).await;
-                Ok(true)
+            (AclKey::Parent(entity), AclValue::Parent(parent)) => {
+                self.resolve_role(sender, entity) >= Some(SyncRole::Admin)
+                    && match parent {
+                        Some(parent) => {
+                            self.resolve_role(sender, parent) >= Some(SyncRole::Editor)
+                                && !creates_acl_cycle(entity, parent, |e| self.parent(e))
+                        }
+                        None => true,
+                    }
             }
-            // Not an ACL event
-            _ => Ok(false),
+            (AclKey::Inherits(entity), AclValue::Inherits(_)) => {
+                self.resolve_role(sender, entity) >= Some(SyncRole::Admin)
+            }
+            _ => false,
         }
     }
 }
 
+impl AclSource for AclView<'_> {
+    fn peer_role(&self, entity: EntityId, peer: PeerId) -> Option<SyncRole> {
+        self.role(AclKey::PeerRole(entity, peer))
+    }
+
+    fn team_roles(&self, entity: EntityId) -> Vec<(TeamId, SyncRole)> {
+        self.teams_on(entity)
+            .into_iter()
+            .filter_map(|team| self.role(AclKey::TeamRole(entity, team)).map(|r| (team, r)))
+            .collect()
+    }
+
+    fn default_role(&self, entity: EntityId) -> Option<SyncRole> {
+        self.role(AclKey::DefaultRole(entity))
+    }
+
+    fn is_member(&self, team: TeamId, peer: PeerId) -> bool {
+        self.value(AclKey::TeamMember(team, peer)) == AclValue::Member(true)
+    }
+
+    fn parent(&self, entity: EntityId) -> Option<EntityId> {
+        match self.value(AclKey::Parent(entity)) {
+            AclValue::Parent(parent) => parent,
+            _ => None,
+        }
+    }
+
+    fn inherits(&self, entity: EntityId) -> bool {
+        self.value(AclKey::Inherits(entity)) != AclValue::Inherits(false)
+    }
+}
+
+fn key_entity(key: AclKey) -> Option<EntityId> {
+    match key {
+        AclKey::PeerRole(entity, _)
+        | AclKey::TeamRole(entity, _)
+        | AclKey::DefaultRole(entity)
+        | AclKey::Parent(entity)
+        | AclKey::Inherits(entity) => Some(entity),
+        AclKey::TeamMember(..) => None,
+    }
+}
+
+/// Reads a role register from the live ACLs, including any grant limits.
+fn role_value(
+    acls: &HashMap<EntityId, EntityAcl>,
+    grant_limits: &HashMap<(EntityId, PeerId), GrantState>,
+    key: AclKey,
+) -> AclValue {
+    let role = key_entity(key)
+        .and_then(|e| acls.get(&e))
+        .and_then(|acl| acl_role(acl, key));
+    match (key, role) {
+        (AclKey::PeerRole(entity, peer), Some(role)) => match grant_limits.get(&(entity, peer)) {
+            Some(state) => AclValue::LimitedRole(role, state.limits),
+            None => AclValue::Role(Some(role)),
+        },
+        _ => AclValue::Role(role),
+    }
+}
+
+fn acl_role(acl: &EntityAcl, key: AclKey) -> Option<SyncRole> {
+    match key {
+        AclKey::PeerRole(_, peer) => acl.peer_roles.get(&peer).copied(),
+        AclKey::TeamRole(_, team) => acl.team_roles.get(&team).copied(),
+        AclKey::DefaultRole(_) => acl.default_role,
+        AclKey::TeamMember(..) | AclKey::Parent(_) | AclKey::Inherits(_) => None,
+    }
+}
+
+/// A stable name for a register, used to key its compacted write.
+fn register_id(key: AclKey) -> String {
+    match key {
+        AclKey::PeerRole(entity, peer) => format!("peer_role:{entity}:{peer}"),
+        AclKey::TeamRole(entity, team) => format!("team_role:{entity}:{team}"),
+        AclKey::DefaultRole(entity) => format!("default_role:{entity}"),
+        AclKey::TeamMember(team, peer) => format!("team_member:{team}:{peer}"),
+        AclKey::Parent(entity) => format!("parent:{entity}"),
+        AclKey::Inherits(entity) => format!("inherits:{entity}"),
+    }
+}
+
+/// The payload that writes `change`; the inverse of [`parse_change`].
+fn change_payload(change: AclChange) -> EventPayload {
+    match (change.key, change.value) {
+        (AclKey::PeerRole(entity, peer), AclValue::Role(Some(role))) => EventPayload::AclGrantPeer {
+            entity_id: entity.to_string(),
+            peer_id: peer.to_string(),
+            role: role.to_string(),
+        },
+        (AclKey::PeerRole(entity, peer), AclValue::LimitedRole(role, limits)) => {
+            EventPayload::AclGrantPeerLimited {
+                entity_id: entity.to_string(),
+                peer_id: peer.to_string(),
+                role: role.to_string(),
+                expires_at_ms: limits.expires_at.map(|at| {
+                    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+                }),
+                max_uses: limits.max_uses,
+            }
+        }
+        (AclKey::TeamRole(entity, team), AclValue::Role(Some(role))) => EventPayload::AclGrantTeam {
+            entity_id: entity.to_string(),
+            team_id: team.to_string(),
+            role: role.to_string(),
+        },
+        (AclKey::TeamRole(entity, team), _) => EventPayload::AclRevokeTeam {
+            entity_id: entity.to_string(),
+            team_id: team.to_string(),
+        },
+        (AclKey::DefaultRole(entity), AclValue::Role(role)) => EventPayload::AclSetDefault {
+            entity_id: entity.to_string(),
+            role: role.map(|r| r.to_string()),
+        },
+        (AclKey::TeamMember(team, peer), AclValue::Member(true)) => EventPayload::TeamAddPeer {
+            team_id: team.to_string(),
+            peer_id: peer.to_string(),
+        },
+        (AclKey::TeamMember(team, peer), _) => EventPayload::TeamRemovePeer {
+            team_id: team.to_string(),
+            peer_id: peer.to_string(),
+        },
+        (AclKey::Parent(entity), AclValue::Parent(parent)) => EventPayload::AclSetParent {
+            entity_id: entity.to_string(),
+            parent_id: parent.map(|p| p.to_string()),
+        },
+        (AclKey::Inherits(entity), AclValue::Inherits(true)) => {
+            EventPayload::AclRestoreInheritance {
+                entity_id: entity.to_string(),
+            }
+        }
+        (AclKey::Inherits(entity), _) => EventPayload::AclBreakInheritance {
+            entity_id: entity.to_string(),
+        },
+        (AclKey::PeerRole(entity, peer), _) => EventPayload::AclRevokePeer {
+            entity_id: entity.to_string(),
+            peer_id: peer.to_string(),
+        },
+        (AclKey::DefaultRole(entity), _) => EventPayload::AclSetDefault {
+            entity_id: entity.to_string(),
+            role: None,
+        },
+        (AclKey::Parent(entity), _) => EventPayload::AclSetParent {
+            entity_id: entity.to_string(),
+            parent_id: None,
+        },
+    }
+}
+
+fn describe(change: &AclChange) -> String {
+    let role = |r: Option<SyncRole>| r.map_or_else(|| "none".to_string(), |r| r.to_string());
+    match (change.key, change.value) {
+        (AclKey::PeerRole(_, peer), AclValue::Role(r)) => format!("peer {peer} role -> {}", role(r)),
+        (AclKey::PeerRole(_, peer), AclValue::LimitedRole(r, limits)) => {
+            let mut detail = format!("peer {peer} role -> {r}");
+            if let Some(at) = limits.expires_at {
+                let ms = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+                detail.push_str(&format!(", expires at {ms}ms"));
+            }
+            if let Some(max) = limits.max_uses {
+                detail.push_str(&format!(", max {max} uses"));
+            }
+            detail
+        }
+        (AclKey::TeamRole(_, team), AclValue::Role(r)) => format!("team {} role -> {}", team.0, role(r)),
+        (AclKey::DefaultRole(_), AclValue::Role(r)) => format!("default role -> {}", role(r)),
+        (AclKey::TeamMember(team, peer), AclValue::Member(true)) => {
+            format!("add peer {peer} to team {}", team.0)
+        }
+        (AclKey::TeamMember(team, peer), AclValue::Member(false)) => {
+            format!("remove peer {peer} from team {}", team.0)
+        }
+        (AclKey::Parent(_), AclValue::Parent(Some(parent))) => format!("parent -> {parent}"),
+        (AclKey::Parent(_), AclValue::Parent(None)) => "parent -> none".to_string(),
+        (AclKey::Inherits(_), AclValue::Inherits(true)) => "restore inheritance".to_string(),
+        (AclKey::Inherits(_), AclValue::Inherits(false)) => "break inheritance".to_string(),
+        (key, value) => format!("{key:?} -> {value:?}"),
+    }
+}
+
+fn denied(event: &Event, change: &AclChange) -> SyncError {
+    SyncError::PolicyDenied {
+        reason: format!(
+            "peer {} is not authorized for ACL change: {}",
+            event.peer_id,
+            describe(change)
+        ),
+    }
+}
+
+fn parse_change(payload: &EventPayload) -> Result<Option<AclChange>, SyncError> {
+    let change = match payload {
+        EventPayload::AclGrantPeer {
+            entity_id,
+            peer_id,
+            role,
+        } => AclChange {
+            key: AclKey::PeerRole(parse_entity_id(entity_id)?, parse_peer_id(peer_id)?),
+            value: AclValue::Role(Some(parse_role(role)?)),
+        },
+        EventPayload::AclGrantPeerLimited {
+            entity_id,
+            peer_id,
+            role,
+            expires_at_ms,
+            max_uses,
+        } => {
+            let limits = GrantLimits {
+                expires_at: expires_at_ms.map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+                max_uses: *max_uses,
+            };
+            let role = parse_role(role)?;
+            AclChange {
+                key: AclKey::PeerRole(parse_entity_id(entity_id)?, parse_peer_id(peer_id)?),
+                value: if limits.is_unlimited() {
+                    AclValue::Role(Some(role))
+                } else {
+                    AclValue::LimitedRole(role, limits)
+                },
+            }
+        }
+        EventPayload::AclRevokePeer {
+            entity_id,
+            peer_id,
+        } => AclChange {
+            key: AclKey::PeerRole(parse_entity_id(entity_id)?, parse_peer_id(peer_id)?),
+            value: AclValue::Role(None),
+        },
+        EventPayload::AclGrantTeam {
+            entity_id,
+            team_id,
+            role,
+        } => AclChange {
+            key: AclKey::TeamRole(parse_entity_id(entity_id)?, parse_team_id(team_id)?),
+            value: AclValue::Role(Some(parse_role(role)?)),
+        },
+        EventPayload::AclRevokeTeam {
+            entity_id,
+            team_id,
+        } => AclChange {
+            key: AclKey::TeamRole(parse_entity_id(entity_id)?, parse_team_id(team_id)?),
+            value: AclValue::Role(None),
+        },
+        EventPayload::AclSetDefault { entity_id, role } => AclChange {
+            key: AclKey::DefaultRole(parse_entity_id(entity_id)?),
+            value: AclValue::Role(match role {
+                Some(s) if !s.is_empty() => Some(parse_role(s)?),
+                _ => None,
+            }),
+        },
+        EventPayload::TeamAddPeer { team_id, peer_id } => AclChange {
+            key: AclKey::TeamMember(parse_team_id(team_id)?, parse_peer_id(peer_id)?),
+            value: AclValue::Member(true),
+        },
+        EventPayload::TeamRemovePeer { team_id, peer_id } => AclChange {
+            key: AclKey::TeamMember(parse_team_id(team_id)?, parse_peer_id(peer_id)?),
+            value: AclValue::Member(false),
+        },
+        EventPayload::AclSetParent {
+            entity_id,
+            parent_id,
+        } => AclChange {
+            key: AclKey::Parent(parse_entity_id(entity_id)?),
+            value: AclValue::Parent(match parent_id {
+                Some(s) if !s.is_empty() => Some(parse_entity_id(s)?),
+                _ => None,
+            }),
+        },
+        EventPayload::AclBreakInheritance { entity_id } => AclChange {
+            key: AclKey::Inherits(parse_entity_id(entity_id)?),
+            value: AclValue::Inherits(false),
+        },
+        EventPayload::AclRestoreInheritance { entity_id } => AclChange {
+            key: AclKey::Inherits(parse_entity_id(entity_id)?),
+            value: AclValue::Inherits(true),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(change))
+}
+
 /// Returns true if the given payload is an ACL-related event.
 pub fn is_acl_event(payload: &EventPayload) -> bool {
     matches!(
         payload,
         EventPayload::AclGrantPeer { .. }
+            | EventPayload::AclGrantPeerLimited { .. }
             | EventPayload::AclRevokePeer { .. }
             | EventPayload::AclGrantTeam { .. }
             | EventPayload::AclRevokeTeam { .. }
             | EventPayload::AclSetDefault { .. }
             | EventPayload::TeamAddPeer { .. }
             | EventPayload::TeamRemovePeer { .. }
+            | EventPayload::AclSetParent { .. }
+            | EventPayload::AclBreakInheritance { .. }
+            | EventPayload::AclRestoreInheritance { .. }
     )
 }
 
+fn parse_entity_id(s: &str) -> Result<EntityId, SyncError> {
+    s.parse().map_err(|e| SyncError::Protocol(format!("{e}")))
+}
+
+fn parse_peer_id(s: &str) -> Result<PeerId, SyncError> {
+    s.parse().map_err(|e| SyncError::Protocol(format!("{e}")))
+}
+
 fn parse_role(s: &str) -> Result<SyncRole, SyncError> {
     match s {
         "Viewer" => Ok(SyncRole::Viewer),
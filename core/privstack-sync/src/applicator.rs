@@ -1,11 +1,15 @@
 //! Event applicator - applies sync events to the entity store.
 //!
 //! Handles entity-level operations: create, update, delete, and full snapshots.
+//! Updates that are concurrent with local edits — the remote event's
+//! dependencies do not cover every local version head — are still merged
+//! automatically, but the losing side is kept as a [`ConflictRecord`] so the
+//! user can review it (see [`crate::conflicts`]).
 //! Plugin-specific domain logic (e.g., block-level CRDT merge for a rich-text
 //! editor) is delegated to the plugin's `PluginDomainHandler`.
 
 use privstack_model::{Entity, EntitySchema, MergeStrategy, PluginDomainHandler};
-use privstack_storage::EntityStore;
+use privstack_storage::{ConflictRecord, EntityStore};
 use privstack_types::{EntityId, Event, EventPayload, PeerId};
 use tracing::{debug, warn};
 
@@ -31,10 +35,18 @@ pub enum ApplicatorError {
     JsonParse(#[from] serde_json::Error),
 }
 
+/// What applying a single event did.
+#[derive(Debug, Default)]
+pub struct ApplyOutcome {
+    /// Whether the store was modified.
+    pub applied: bool,
+    /// A conflict detected by this event, if it opened a new one.
+    pub conflict: Option<ConflictRecord>,
+}
+
 /// Applies sync events to the entity store using schema-driven merge.
 pub struct EventApplicator {
-    /// Local peer ID for creating events.
-    #[allow(dead_code)]
+    /// Local peer ID; our own events are never treated as concurrent.
     local_peer_id: PeerId,
 }
 
@@ -53,25 +65,40 @@ impl EventApplicator {
         schema: Option<&EntitySchema>,
         handler: Option<&dyn PluginDomainHandler>,
     ) -> ApplicatorResult<bool> {
+        Ok(self.apply_event_outcome(event, store, schema, handler)?.applied)
+    }
+
+    /// Applies a single event and reports any conflict it opened.
+    pub fn apply_event_outcome(
+        &self,
+        event: &Event,
+        store: &EntityStore,
+        schema: Option<&EntitySchema>,
+        handler: Option<&dyn PluginDomainHandler>,
+    ) -> ApplicatorResult<ApplyOutcome> {
         debug!("Applying event {:?} to entity {}", event.payload, event.entity_id);
 
+        let applied = |applied| ApplyOutcome { applied, conflict: None };
         match &event.payload {
-            EventPayload::EntityCreated { entity_type, json_data } => {
-                self.apply_entity_created(event, entity_type, json_data, store, schema)
-            }
+            EventPayload::EntityCreated { entity_type, json_data } => self
+                .apply_entity_created(event, entity_type, json_data, store, schema)
+                .map(applied),
             EventPayload::EntityUpdated { entity_type, json_data } => {
                 self.apply_entity_updated(event, entity_type, json_data, store, schema, handler)
             }
             EventPayload::EntityDeleted { entity_type } => {
-                self.apply_entity_deleted(event, entity_type, store)
+                self.apply_entity_deleted(event, entity_type, store).map(applied)
             }
             EventPayload::FullSnapshot { entity_type, json_data } => {
                 self.apply_full_snapshot(event, entity_type, json_data, store, schema, handler)
             }
+            EventPayload::ConflictResolved { entity_type, json_data } => self
+                .apply_conflict_resolved(event, entity_type, json_data, store, schema)
+                .map(applied),
             // ACL events are handled by AclApplicator, not the entity applicator
             _ => {
                 debug!("Skipping non-entity event payload: {:?}", event.payload);
-                Ok(false)
+                Ok(applied(false))
             }
         }
     }
@@ -99,6 +126,7 @@ impl EventApplicator {
         } else {
             store.save_entity_raw(&entity)?;
         }
+        record_version(event, store)?;
 
         debug!("Created entity {} (type={})", event.entity_id, entity_type);
         Ok(true)
@@ -112,7 +140,7 @@ impl EventApplicator {
         store: &EntityStore,
         schema: Option<&EntitySchema>,
         handler: Option<&dyn PluginDomainHandler>,
-    ) -> ApplicatorResult<bool> {
+    ) -> ApplicatorResult<ApplyOutcome> {
         let remote_data: serde_json::Value = serde_json::from_str(json_data)?;
         let remote_entity = Entity {
             id: event.entity_id.to_string(),
@@ -125,9 +153,18 @@ impl EventApplicator {
 
         // Check if entity exists locally for merge
         let existing = store.get_entity(&event.entity_id.to_string())?;
-        let merged = match existing {
-            Some(local) => self.merge_entities(&local, &remote_entity, schema, handler),
-            None => remote_entity,
+        let (merged, conflict) = match existing {
+            Some(local) => {
+                let merged = self.merge_entities(&local, &remote_entity, schema, handler);
+                let conflict = match self.concurrent_heads(event, store)? {
+                    Some(heads) if stored_data(&local) != remote_entity.data => {
+                        record_conflict(event, &local, &remote_entity, &merged, heads, store)?
+                    }
+                    _ => None,
+                };
+                (merged, conflict)
+            }
+            None => (remote_entity, None),
         };
 
         if let Some(s) = schema {
@@ -135,9 +172,41 @@ impl EventApplicator {
         } else {
             store.save_entity_raw(&merged)?;
         }
+        record_version(event, store)?;
 
         debug!("Updated entity {} (type={})", event.entity_id, entity_type);
-        Ok(true)
+        Ok(ApplyOutcome { applied: true, conflict })
+    }
+
+    /// Returns the local heads a remote event did not know about, if any.
+    ///
+    /// Events without dependencies predate version tracking and are never
+    /// reported. Neither are our own events, replays of events already
+    /// applied, or events whose history has not fully arrived yet.
+    fn concurrent_heads(
+        &self,
+        event: &Event,
+        store: &EntityStore,
+    ) -> ApplicatorResult<Option<Vec<String>>> {
+        if event.dependencies.is_empty() || event.peer_id == self.local_peer_id {
+            return Ok(None);
+        }
+        let entity_id = event.entity_id.to_string();
+        if store.has_entity_version(&entity_id, &event.id.to_string())? {
+            return Ok(None);
+        }
+        let deps: Vec<String> = event.dependencies.iter().map(ToString::to_string).collect();
+        for dep in &deps {
+            if !store.has_entity_version(&entity_id, dep)? {
+                return Ok(None);
+            }
+        }
+        let unseen: Vec<String> = store
+            .entity_heads(&entity_id)?
+            .into_iter()
+            .filter(|head| !deps.contains(head))
+            .collect();
+        Ok(if unseen.is_empty() { None } else { Some(unseen) })
     }
 
     fn apply_entity_deleted(
@@ -159,11 +228,60 @@ impl EventApplicator {
         store: &EntityStore,
         schema: Option<&EntitySchema>,
         handler: Option<&dyn PluginDomainHandler>,
-    ) -> ApplicatorResult<bool> {
+    ) -> ApplicatorResult<ApplyOutcome> {
         // FullSnapshot is treated the same as EntityUpdated — merge with local
         self.apply_entity_updated(event, entity_type, json_data, store, schema, handler)
     }
 
+    fn apply_conflict_resolved(
+        &self,
+        event: &Event,
+        entity_type: &str,
+        json_data: &str,
+        store: &EntityStore,
+        schema: Option<&EntitySchema>,
+    ) -> ApplicatorResult<bool> {
+        // The resolution is a deliberate choice, so it replaces the local
+        // state instead of being merged with it.
+        let data: serde_json::Value = serde_json::from_str(json_data)?;
+        let entity_id = event.entity_id.to_string();
+        let existing = store.get_entity(&entity_id)?;
+        let entity = Entity {
+            id: entity_id.clone(),
+            entity_type: entity_type.to_string(),
+            data,
+            created_at: existing
+                .as_ref()
+                .map_or(event.timestamp.wall_time() as i64, |e| e.created_at),
+            modified_at: event.timestamp.wall_time() as i64,
+            created_by: existing
+                .map_or_else(|| event.peer_id.to_string(), |e| e.created_by),
+        };
+
+        if let Some(s) = schema {
+            store.save_entity(&entity, s)?;
+        } else {
+            store.save_entity_raw(&entity)?;
+        }
+        record_version(event, store)?;
+
+        // Close our records of the conflict the resolver saw.
+        let deps: Vec<String> = event.dependencies.iter().map(ToString::to_string).collect();
+        for conflict in store.list_sync_conflicts(Some(&entity_id), false)? {
+            let covered = conflict
+                .local_heads
+                .iter()
+                .chain(std::iter::once(&conflict.remote_event_id))
+                .any(|id| deps.contains(id));
+            if covered {
+                store.resolve_sync_conflict(&conflict.conflict_id, "remote", now_millis())?;
+            }
+        }
+
+        debug!("Resolved conflict on entity {} (type={})", event.entity_id, entity_type);
+        Ok(true)
+    }
+
     /// Merges a local and remote entity based on the schema's merge strategy.
     pub fn merge_entities(
         &self,
@@ -221,6 +339,90 @@ impl EventApplicator {
     }
 }
 
+/// Moves the entity's version heads past an applied event.
+fn record_version(event: &Event, store: &EntityStore) -> ApplicatorResult<()> {
+    let deps: Vec<String> = event.dependencies.iter().map(ToString::to_string).collect();
+    store.record_entity_version(&event.entity_id.to_string(), &event.id.to_string(), &deps)?;
+    Ok(())
+}
+
+/// Keeps the losing side of a concurrent merge.
+///
+/// An entity has at most one open conflict; further concurrent edits fold
+/// into it. Returns the record only when a new conflict was opened.
+fn record_conflict(
+    event: &Event,
+    local: &Entity,
+    remote: &Entity,
+    merged: &Entity,
+    heads: Vec<String>,
+    store: &EntityStore,
+) -> ApplicatorResult<Option<ConflictRecord>> {
+    let local_data = stored_data(local);
+    let merged_data = stored_data(merged);
+    let winner = if merged_data == remote.data {
+        "remote"
+    } else if merged_data == local_data {
+        "local"
+    } else {
+        "merged"
+    };
+
+    if let Some(mut open) = store.open_sync_conflict_for_entity(&local.id)? {
+        for head in heads {
+            if !open.local_heads.contains(&head) {
+                open.local_heads.push(head);
+            }
+        }
+        open.remote_json = remote.data.to_string();
+        open.remote_event_id = event.id.to_string();
+        open.remote_peer_id = event.peer_id.to_string();
+        open.winner = winner.to_string();
+        store.save_sync_conflict(&open)?;
+        return Ok(None);
+    }
+
+    let conflict = ConflictRecord {
+        conflict_id: event.id.to_string(),
+        entity_id: local.id.clone(),
+        entity_type: remote.entity_type.clone(),
+        local_json: local_data.to_string(),
+        remote_json: remote.data.to_string(),
+        local_heads: heads,
+        remote_event_id: event.id.to_string(),
+        remote_peer_id: event.peer_id.to_string(),
+        winner: winner.to_string(),
+        detected_at: now_millis(),
+        resolved_at: None,
+        resolution: None,
+    };
+    store.save_sync_conflict(&conflict)?;
+    warn!(
+        "Concurrent edit on entity {} from peer {} (kept {})",
+        local.id, event.peer_id, winner
+    );
+    Ok(Some(conflict))
+}
+
+/// An entity's data without the `is_trashed: false` the store adds on load,
+/// so it compares equal to the same state as sent over sync.
+fn stored_data(entity: &Entity) -> serde_json::Value {
+    let mut data = entity.data.clone();
+    if let Some(obj) = data.as_object_mut() {
+        if obj.get("is_trashed") == Some(&serde_json::Value::Bool(false)) {
+            obj.remove("is_trashed");
+        }
+    }
+    data
+}
+
+pub(crate) fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
 /// Creates a sync event for an entity operation.
 pub fn create_event(
     entity_id: EntityId,
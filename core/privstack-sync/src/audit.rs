@@ -0,0 +1,290 @@
+//! Tamper-evident audit log primitives.
+//!
+//! Every audit entry persisted by [`PolicyStore`](crate::policy_store::PolicyStore)
+//! is sealed into a hash chain: its hash covers the previous entry's hash, its
+//! sequence number and all of its fields. Editing, reordering or deleting an
+//! entry breaks the chain from that point on.
+//!
+//! # Checkpoints
+//!
+//! Every `checkpoint_interval` entries the store records a checkpoint of the
+//! chain head, signed with the store's audit signer when one is configured.
+//! Checkpoints catch truncation of the newest entries (which leaves a valid but
+//! shorter chain) and, when signed, a chain rebuilt by someone without the key.
+//!
+//! # Retention
+//!
+//! Pruning removes the oldest entries and records an anchor (a checkpoint of the
+//! last removed entry). Verification starts from the anchor, so the remaining
+//! chain stays verifiable.
+
+use crate::error::SyncError;
+use crate::policy::{AuditAction, AuditEntry};
+use privstack_types::{EntityId, PeerId};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ENTRY_DOMAIN: &[u8] = b"privstack-audit-v1\0";
+const CHECKPOINT_DOMAIN: &[u8] = b"privstack-audit-checkpoint-v1\0";
+
+/// Hash that precedes the first entry of a chain.
+pub const GENESIS_HASH: [u8; 32] = [0u8; 32];
+
+/// An audit entry together with its position in the hash chain.
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    /// Sequence number (1-based, no gaps).
+    pub seq: i64,
+    pub entry: AuditEntry,
+    pub prev_hash: [u8; 32],
+    pub hash: [u8; 32],
+}
+
+/// A recorded chain head, optionally signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditCheckpoint {
+    pub seq: i64,
+    pub hash: [u8; 32],
+    pub timestamp: SystemTime,
+    /// Public key of the signer, if signed.
+    pub signer: Option<[u8; 32]>,
+    pub signature: Option<Vec<u8>>,
+}
+
+impl AuditCheckpoint {
+    /// Bytes covered by the checkpoint signature.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CHECKPOINT_DOMAIN.len() + 8 + 32 + 8);
+        bytes.extend_from_slice(CHECKPOINT_DOMAIN);
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.hash);
+        bytes.extend_from_slice(&millis(self.timestamp).to_be_bytes());
+        bytes
+    }
+}
+
+/// Computes the chain hash of an entry.
+pub fn entry_hash(seq: i64, prev_hash: &[u8; 32], entry: &AuditEntry) -> [u8; 32] {
+    let fields = StoredFields::from_entry(entry);
+    fields.hash(seq, prev_hash)
+}
+
+/// The columns of an audit row as stored. Hashing the stored text rather
+/// than parsed values means an edit is caught even if it parses back to the
+/// same value.
+pub(crate) struct StoredFields {
+    pub peer: String,
+    pub entity: Option<String>,
+    pub action: String,
+    pub decision: String,
+    pub detail: String,
+    pub timestamp: String,
+}
+
+impl StoredFields {
+    pub(crate) fn from_entry(entry: &AuditEntry) -> Self {
+        Self {
+            peer: entry.peer.to_string(),
+            entity: entry.entity.map(|e| e.to_string()),
+            action: entry.action.to_string(),
+            decision: format!("{:?}", entry.decision),
+            detail: entry.detail.clone(),
+            timestamp: millis(entry.timestamp).to_string(),
+        }
+    }
+
+    pub(crate) fn hash(&self, seq: i64, prev_hash: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(ENTRY_DOMAIN);
+        hasher.update(prev_hash);
+        hasher.update(seq.to_be_bytes());
+        hash_field(&mut hasher, self.peer.as_bytes());
+        match &self.entity {
+            Some(entity) => {
+                hasher.update([1u8]);
+                hash_field(&mut hasher, entity.as_bytes());
+            }
+            None => hasher.update([0u8]),
+        }
+        hash_field(&mut hasher, self.action.as_bytes());
+        hash_field(&mut hasher, self.decision.as_bytes());
+        hash_field(&mut hasher, self.detail.as_bytes());
+        hash_field(&mut hasher, self.timestamp.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+fn hash_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+/// Milliseconds since the Unix epoch, the precision audit timestamps are stored at.
+pub(crate) fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+// ── Verification ─────────────────────────────────────────────────
+
+/// A problem found while verifying the audit chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// Sequence numbers are missing between two entries (entries deleted).
+    Gap { after: i64, next: i64 },
+    /// An entry's `prev_hash` does not match the preceding entry's hash.
+    BrokenLink { seq: i64 },
+    /// An entry's contents no longer match its hash (entry edited).
+    HashMismatch { seq: i64 },
+    /// A checkpoint does not match the entry at its sequence number.
+    CheckpointMismatch { seq: i64 },
+    /// A checkpoint lies beyond the last entry (newest entries deleted).
+    Truncated { checkpoint_seq: i64, last_seq: i64 },
+    /// A checkpoint's signature does not verify.
+    BadCheckpointSignature { seq: i64 },
+    /// A checkpoint is unsigned or signed by a key that is not trusted.
+    UntrustedCheckpoint { seq: i64 },
+    /// The retention anchor has no matching checkpoint.
+    AnchorMismatch { seq: i64 },
+}
+
+/// Result of verifying the audit chain.
+#[derive(Debug, Clone, Default)]
+pub struct AuditVerification {
+    pub entries_checked: usize,
+    pub checkpoints_checked: usize,
+    /// Sequence number and hash of the newest entry.
+    pub head: Option<(i64, [u8; 32])>,
+    pub issues: Vec<AuditIssue>,
+}
+
+impl AuditVerification {
+    /// Returns true if no issues were found.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+// ── Filtering & retention ────────────────────────────────────────
+
+/// Selects audit entries for loading or export. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub peer: Option<PeerId>,
+    pub entity: Option<EntityId>,
+    pub action: Option<AuditAction>,
+    /// Inclusive lower bound on the entry timestamp.
+    pub since: Option<SystemTime>,
+    /// Exclusive upper bound on the entry timestamp.
+    pub until: Option<SystemTime>,
+}
+
+impl AuditFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_peer(mut self, peer: PeerId) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    pub fn with_entity(mut self, entity: EntityId) -> Self {
+        self.entity = Some(entity);
+        self
+    }
+
+    pub fn with_action(mut self, action: AuditAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    pub fn since(mut self, time: SystemTime) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    pub fn until(mut self, time: SystemTime) -> Self {
+        self.until = Some(time);
+        self
+    }
+}
+
+/// How long audit entries are kept. Entries beyond either limit are pruned.
+#[derive(Debug, Clone, Default)]
+pub struct AuditRetention {
+    /// Maximum age of an entry.
+    pub max_age: Option<Duration>,
+    /// Maximum number of entries kept.
+    pub max_entries: Option<usize>,
+}
+
+// ── Export ───────────────────────────────────────────────────────
+
+/// Output format for audit log exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditExportFormat {
+    /// One JSON object per line.
+    JsonLines,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+const CSV_HEADER: &str = "seq,timestamp_ms,peer,entity,action,decision,detail,prev_hash,hash";
+
+/// Writes audit records in the given format. Each record carries its chain
+/// hashes, so an unfiltered export can be verified independently.
+pub fn write_records<W: Write>(
+    records: &[AuditRecord],
+    format: AuditExportFormat,
+    out: &mut W,
+) -> Result<(), SyncError> {
+    let io_err = |e: std::io::Error| SyncError::Storage(format!("failed to write audit export: {e}"));
+    match format {
+        AuditExportFormat::JsonLines => {
+            for record in records {
+                let line = serde_json::json!({
+                    "seq": record.seq,
+                    "timestamp_ms": millis(record.entry.timestamp),
+                    "peer": record.entry.peer.to_string(),
+                    "entity": record.entry.entity.map(|e| e.to_string()),
+                    "action": record.entry.action.to_string(),
+                    "decision": format!("{:?}", record.entry.decision),
+                    "detail": record.entry.detail,
+                    "prev_hash": hex::encode(record.prev_hash),
+                    "hash": hex::encode(record.hash),
+                });
+                writeln!(out, "{line}").map_err(io_err)?;
+            }
+        }
+        AuditExportFormat::Csv => {
+            writeln!(out, "{CSV_HEADER}").map_err(io_err)?;
+            for record in records {
+                let entity = record.entry.entity.map(|e| e.to_string()).unwrap_or_default();
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{:?},{},{},{}",
+                    record.seq,
+                    millis(record.entry.timestamp),
+                    record.entry.peer,
+                    entity,
+                    record.entry.action,
+                    record.entry.decision,
+                    csv_field(&record.entry.detail),
+                    hex::encode(record.prev_hash),
+                    hex::encode(record.hash),
+                )
+                .map_err(io_err)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,759 @@
+//! File-based sync over any [`CloudStorage`] backend.
+//!
+//! Turns a plain shared folder (Google Drive, iCloud Drive, ...) into an
+//! entity sync transport without any PrivStack server. Every device only
+//! ever writes its own files, so the folder never sees write conflicts:
+//!
+//! ```text
+//! ps-folder.json                      folder manifest (KDF salt + key check)
+//! ps-<peer-id>.<seq>.seg              append-only event segment
+//! ps-<peer-id>.<seq>.snap             full snapshot (written on compaction)
+//! ```
+//!
+//! Segments and snapshots share one per-device sequence. Each file is
+//! zstd-compressed JSON, encrypted with the folder key:
+//!
+//! ```text
+//! [b"PSF1"][12-byte nonce][ChaCha20-Poly1305 ciphertext]
+//! ```
+//!
+//! # Sync pass
+//!
+//! 1. **Push**: local events newer than the last uploaded one are written as
+//!    new segments.
+//! 2. **Pull**: the folder listing is refreshed through `get_changes` cursors.
+//!    For each other device, segments after the last applied sequence are
+//!    downloaded, decrypted and applied with `EventApplicator`. If the next
+//!    segment is gone because the device compacted, its latest snapshot is
+//!    applied instead and reading continues after it.
+//! 3. **Compact**: after `snapshot_interval` segments, a snapshot of every
+//!    syncable entity is written and this device's older files are deleted.
+//!
+//! Snapshots carry the state of every live entity plus the delete event of
+//! every entity known to be deleted, so a device that was offline across a
+//! compaction still removes what was deleted before it.
+//!
+//! The KDF parameters in the folder manifest are clamped to
+//! [`MAX_FOLDER_MEMORY_COST`] and [`MAX_FOLDER_TIME_COST`] before deriving:
+//! anyone who can write to the folder can edit the manifest.
+
+use super::storage::{CloudFile, CloudStorage};
+use crate::applicator::EventApplicator;
+use crate::error::{SyncError, SyncResult};
+use privstack_crypto::{decrypt, derive_key, encrypt, DerivedKey, EncryptedData, KdfParams, Salt};
+use privstack_storage::{EntityStore, EventStore};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Name of the folder manifest.
+pub const FOLDER_MANIFEST_NAME: &str = "ps-folder.json";
+
+/// Prefix of every file the sync engine owns.
+const FILE_PREFIX: &str = "ps-";
+const SEGMENT_EXT: &str = "seg";
+const SNAPSHOT_EXT: &str = "snap";
+const FILE_MAGIC: &[u8; 4] = b"PSF1";
+const NONCE_LEN: usize = 12;
+const FOLDER_FORMAT_VERSION: u32 = 1;
+/// Plaintext encrypted into the manifest to verify the passphrase.
+const KEY_CHECK_PLAINTEXT: &[u8] = b"privstack-folder-key";
+/// zstd level for segment files.
+const ZSTD_LEVEL: i32 = 3;
+/// Most Argon2 memory, in KiB, a folder manifest may ask for (256 MiB).
+pub const MAX_FOLDER_MEMORY_COST: u32 = 256 * 1024;
+/// Most Argon2 iterations a folder manifest may ask for.
+pub const MAX_FOLDER_TIME_COST: u32 = 10;
+/// Most Argon2 lanes a folder manifest may ask for.
+const MAX_FOLDER_PARALLELISM: u32 = 8;
+
+/// Configuration for the file sync engine.
+#[derive(Debug, Clone)]
+pub struct FileSyncConfig {
+    /// Maximum events per segment file.
+    pub max_events_per_segment: usize,
+    /// Number of segments written before this device compacts into a snapshot.
+    /// 0 disables compaction.
+    pub snapshot_interval: u64,
+}
+
+impl Default for FileSyncConfig {
+    fn default() -> Self {
+        Self {
+            max_events_per_segment: 500,
+            snapshot_interval: 50,
+        }
+    }
+}
+
+/// Kind of a sync file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncFileKind {
+    /// Append-only batch of events authored by the device.
+    Segment,
+    /// Full state of every syncable entity at compaction time, plus the
+    /// delete events of entities known to be deleted.
+    Snapshot,
+}
+
+/// A parsed sync file name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncFileName {
+    /// Device that wrote the file.
+    pub peer_id: PeerId,
+    /// Position in that device's sequence.
+    pub seq: u64,
+    /// Segment or snapshot.
+    pub kind: SyncFileKind,
+}
+
+impl SyncFileName {
+    /// Formats the file name.
+    pub fn to_name(&self) -> String {
+        let ext = match self.kind {
+            SyncFileKind::Segment => SEGMENT_EXT,
+            SyncFileKind::Snapshot => SNAPSHOT_EXT,
+        };
+        format!("{FILE_PREFIX}{}.{:012}.{ext}", self.peer_id, self.seq)
+    }
+
+    /// Parses a file name. Returns `None` for files the engine does not own.
+    pub fn parse(name: &str) -> Option<Self> {
+        let rest = name.strip_prefix(FILE_PREFIX)?;
+        let mut parts = rest.split('.');
+        let peer_id = parts.next()?.parse().ok()?;
+        let seq = parts.next()?.parse().ok()?;
+        let kind = match parts.next()? {
+            SEGMENT_EXT => SyncFileKind::Segment,
+            SNAPSHOT_EXT => SyncFileKind::Snapshot,
+            _ => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { peer_id, seq, kind })
+    }
+}
+
+/// Decrypted contents of a sync file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncFileBody {
+    version: u32,
+    peer_id: PeerId,
+    seq: u64,
+    kind: SyncFileKind,
+    events: Vec<Event>,
+}
+
+/// Folder manifest: everything a new device needs to derive the folder key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FolderManifest {
+    version: u32,
+    /// Argon2id salt (hex).
+    salt: String,
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+    /// `KEY_CHECK_PLAINTEXT` encrypted with the folder key (base64).
+    key_check: String,
+}
+
+/// Derives the key that encrypts a sync folder.
+pub struct FolderKey;
+
+impl FolderKey {
+    /// Unlocks a sync folder with the user's sync passphrase.
+    ///
+    /// The first device creates the manifest (random salt, `params`);
+    /// later devices derive the same key from the stored salt and
+    /// parameters. Either way the parameters are clamped to the
+    /// `MAX_FOLDER_*` bounds. Fails with `SyncError::Auth` on a wrong
+    /// passphrase.
+    pub async fn unlock(
+        storage: &dyn CloudStorage,
+        passphrase: &str,
+        params: &KdfParams,
+    ) -> SyncResult<DerivedKey> {
+        storage.ensure_sync_folder().await?;
+        let files = storage.list_files().await?;
+
+        if let Some(file) = files.iter().find(|f| f.name == FOLDER_MANIFEST_NAME) {
+            let bytes = storage.download(&file.id).await?;
+            let manifest: FolderManifest = serde_json::from_slice(&bytes)?;
+            if manifest.version > FOLDER_FORMAT_VERSION {
+                return Err(SyncError::Protocol(format!(
+                    "unsupported sync folder version: {}",
+                    manifest.version
+                )));
+            }
+            let salt: [u8; privstack_crypto::SALT_SIZE] = hex::decode(&manifest.salt)
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| SyncError::Protocol("invalid sync folder salt".to_string()))?;
+            let params = clamp_kdf_params(&KdfParams {
+                memory_cost: manifest.memory_cost,
+                time_cost: manifest.time_cost,
+                parallelism: manifest.parallelism,
+            });
+            let key = derive_key(passphrase, &Salt::from_bytes(salt), &params)
+                .map_err(|e| SyncError::Encryption(e.to_string()))?;
+
+            let check = EncryptedData::from_base64(&manifest.key_check)
+                .map_err(|e| SyncError::Protocol(format!("invalid sync folder key check: {e}")))?;
+            match decrypt(&key, &check) {
+                Ok(plain) if plain == KEY_CHECK_PLAINTEXT => Ok(key),
+                _ => Err(SyncError::Auth("wrong sync folder passphrase".to_string())),
+            }
+        } else {
+            let params = &clamp_kdf_params(params);
+            let salt = Salt::random();
+            let key = derive_key(passphrase, &salt, params)
+                .map_err(|e| SyncError::Encryption(e.to_string()))?;
+            let key_check = encrypt(&key, KEY_CHECK_PLAINTEXT)
+                .map_err(|e| SyncError::Encryption(e.to_string()))?
+                .to_base64();
+            let manifest = FolderManifest {
+                version: FOLDER_FORMAT_VERSION,
+                salt: hex::encode(salt.as_bytes()),
+                memory_cost: params.memory_cost,
+                time_cost: params.time_cost,
+                parallelism: params.parallelism,
+                key_check,
+            };
+            storage
+                .upload(FOLDER_MANIFEST_NAME, &serde_json::to_vec_pretty(&manifest)?)
+                .await?;
+            info!("Created sync folder manifest on {}", storage.provider_name());
+            Ok(key)
+        }
+    }
+}
+
+/// Bounds KDF parameters so a tampered manifest can't make unlocking
+/// allocate or spin without limit.
+fn clamp_kdf_params(params: &KdfParams) -> KdfParams {
+    KdfParams {
+        memory_cost: params.memory_cost.min(MAX_FOLDER_MEMORY_COST),
+        time_cost: params.time_cost.clamp(1, MAX_FOLDER_TIME_COST),
+        parallelism: params.parallelism.clamp(1, MAX_FOLDER_PARALLELISM),
+    }
+}
+
+/// Persistent progress of the file sync engine.
+///
+/// Callers persist this between runs via [`FileSyncState::to_json`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileSyncState {
+    /// Next sequence number for this device's files.
+    #[serde(default)]
+    next_seq: u64,
+    /// Timestamp of the newest local event already uploaded.
+    #[serde(default)]
+    last_uploaded: Option<HybridTimestamp>,
+    /// Segments written since the last snapshot.
+    #[serde(default)]
+    segments_since_snapshot: u64,
+    /// Change cursor from the storage backend.
+    #[serde(default)]
+    cursor: Option<String>,
+    /// Whether the folder has been listed in full at least once.
+    #[serde(default)]
+    listed: bool,
+    /// Highest sequence applied, per remote device.
+    #[serde(default)]
+    applied: HashMap<String, u64>,
+    /// Known sync files in the folder: file ID -> name.
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+}
+
+impl FileSyncState {
+    /// Loads state from JSON.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Serializes state to JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Sequence number the next file from this device will use.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Timestamp of the newest local event already uploaded.
+    pub fn last_uploaded(&self) -> Option<HybridTimestamp> {
+        self.last_uploaded
+    }
+
+    /// Highest sequence applied from a remote device.
+    pub fn applied_seq(&self, peer_id: &PeerId) -> Option<u64> {
+        self.applied.get(&peer_id.to_string()).copied()
+    }
+}
+
+/// Outcome of a sync pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSyncReport {
+    /// Local events uploaded.
+    pub events_uploaded: usize,
+    /// Segment files written.
+    pub segments_written: usize,
+    /// Remote events that changed the local store.
+    pub events_applied: usize,
+    /// Remote files read (segments and snapshots).
+    pub files_read: usize,
+    /// Whether this pass wrote a snapshot and compacted old files.
+    pub compacted: bool,
+    /// Entities changed by remote data.
+    pub updated_entities: Vec<EntityId>,
+}
+
+/// Syncs entities through a shared folder on a [`CloudStorage`] backend.
+///
+/// Holds only local state; the storage backend is passed to each pass so
+/// the caller keeps ownership of it.
+pub struct FileSyncEngine {
+    peer_id: PeerId,
+    key: DerivedKey,
+    entity_store: Arc<EntityStore>,
+    event_store: Arc<EventStore>,
+    config: FileSyncConfig,
+    state: FileSyncState,
+}
+
+impl FileSyncEngine {
+    /// Creates a file sync engine. `key` is the folder key from
+    /// [`FolderKey::unlock`]; `state` is the previously persisted state.
+    pub fn new(
+        peer_id: PeerId,
+        key: DerivedKey,
+        entity_store: Arc<EntityStore>,
+        event_store: Arc<EventStore>,
+        config: FileSyncConfig,
+        state: FileSyncState,
+    ) -> Self {
+        Self {
+            peer_id,
+            key,
+            entity_store,
+            event_store,
+            config,
+            state,
+        }
+    }
+
+    /// Returns the current state (persist it after each pass).
+    pub fn state(&self) -> &FileSyncState {
+        &self.state
+    }
+
+    /// Runs a full pass: push, pull, then compact if due.
+    pub async fn sync(&mut self, storage: &dyn CloudStorage) -> SyncResult<FileSyncReport> {
+        let mut report = FileSyncReport::default();
+        storage.ensure_sync_folder().await?;
+        self.refresh_listing(storage).await?;
+        self.push(storage, &mut report).await?;
+        self.pull(storage, &mut report).await?;
+        if self.config.snapshot_interval > 0
+            && self.state.segments_since_snapshot >= self.config.snapshot_interval
+        {
+            self.compact(storage).await?;
+            report.compacted = true;
+        }
+        info!(
+            "[FILE-SYNC] Pass on {} complete: uploaded={}, applied={}, read={}, compacted={}",
+            storage.provider_name(),
+            report.events_uploaded,
+            report.events_applied,
+            report.files_read,
+            report.compacted
+        );
+        Ok(report)
+    }
+
+    /// Uploads local events that are not in the folder yet.
+    pub async fn push(
+        &mut self,
+        storage: &dyn CloudStorage,
+        report: &mut FileSyncReport,
+    ) -> SyncResult<()> {
+        let since = self.state.last_uploaded.unwrap_or_else(|| HybridTimestamp::new(0, 0));
+        let event_store = self.event_store.clone();
+        let peer_id = self.peer_id;
+        let events = tokio::task::spawn_blocking(move || event_store.get_events_since(&peer_id, &since))
+            .await
+            .map_err(|e| SyncError::Storage(format!("event query panicked: {e}")))?
+            .map_err(|e| SyncError::Storage(e.to_string()))?;
+
+        let Some(newest) = events.last().map(|e| e.timestamp) else {
+            return Ok(());
+        };
+        let events: Vec<Event> = events.into_iter().filter(|e| !is_local_only(e)).collect();
+
+        for chunk in events.chunks(self.config.max_events_per_segment.max(1)) {
+            self.write_file(storage, SyncFileKind::Segment, chunk.to_vec()).await?;
+            self.state.segments_since_snapshot += 1;
+            report.segments_written += 1;
+            report.events_uploaded += chunk.len();
+        }
+        self.state.last_uploaded = Some(newest);
+        Ok(())
+    }
+
+    /// Reads and applies new files from other devices.
+    pub async fn pull(
+        &mut self,
+        storage: &dyn CloudStorage,
+        report: &mut FileSyncReport,
+    ) -> SyncResult<()> {
+        let mut by_device: HashMap<PeerId, Vec<(SyncFileName, String)>> = HashMap::new();
+        for (id, name) in &self.state.files {
+            if let Some(parsed) = SyncFileName::parse(name) {
+                if parsed.peer_id != self.peer_id {
+                    by_device.entry(parsed.peer_id).or_default().push((parsed, id.clone()));
+                }
+            }
+        }
+
+        for (device, mut files) in by_device {
+            files.sort_by_key(|(f, _)| f.seq);
+            let applied = self.state.applied_seq(&device);
+            let next = applied.map_or(0, |s| s + 1);
+            let pending: Vec<_> = files.into_iter().filter(|(f, _)| f.seq >= next).collect();
+            let Some((first, _)) = pending.first() else {
+                continue;
+            };
+
+            // Compacted (or first contact with a device that already
+            // compacted): start from its latest snapshot.
+            let mut contiguous = applied.is_some() && first.seq == next;
+            let start = if first.seq != next {
+                match pending.iter().rposition(|(f, _)| f.kind == SyncFileKind::Snapshot) {
+                    Some(pos) => pos,
+                    None => {
+                        debug!(
+                            "[FILE-SYNC] Waiting for segment {} from {} (folder still replicating)",
+                            next, device
+                        );
+                        continue;
+                    }
+                }
+            } else {
+                0
+            };
+
+            let first_seq = pending[start].0.seq;
+            for (expected, (file, id)) in (first_seq..).zip(&pending[start..]) {
+                if file.seq != expected {
+                    debug!("[FILE-SYNC] Gap before {} from {}, resuming next pass", file.seq, device);
+                    break;
+                }
+                // A snapshot right after segments we already applied adds nothing.
+                if file.kind == SyncFileKind::Segment || !contiguous {
+                    let body = match self.read_file(storage, id, file).await {
+                        Ok(body) => body,
+                        Err(e) => {
+                            warn!("[FILE-SYNC] Failed to read {}: {}", file.to_name(), e);
+                            break;
+                        }
+                    };
+                    report.files_read += 1;
+                    self.apply_events(&body, report).await;
+                }
+                self.state.applied.insert(device.to_string(), file.seq);
+                contiguous = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot of every syncable entity and deletes this device's
+    /// older files.
+    pub async fn compact(&mut self, storage: &dyn CloudStorage) -> SyncResult<()> {
+        let entity_store = self.entity_store.clone();
+        let event_store = self.event_store.clone();
+        let peer_id = self.peer_id;
+        let snapshot = move || snapshot_events(&entity_store, &event_store, peer_id);
+        let events = tokio::task::spawn_blocking(snapshot)
+            .await
+            .map_err(|e| SyncError::Storage(format!("snapshot panicked: {e}")))??;
+
+        let snapshot = self.write_file(storage, SyncFileKind::Snapshot, events).await?;
+
+        let stale: Vec<(String, String)> = self
+            .state
+            .files
+            .iter()
+            .filter(|(_, name)| {
+                SyncFileName::parse(name)
+                    .is_some_and(|f| f.peer_id == self.peer_id && f.seq < snapshot.seq)
+            })
+            .map(|(id, name)| (id.clone(), name.clone()))
+            .collect();
+        for (id, name) in stale {
+            match storage.delete(&id).await {
+                Ok(()) => {
+                    self.state.files.remove(&id);
+                }
+                Err(e) => warn!("[FILE-SYNC] Failed to delete {}: {}", name, e),
+            }
+        }
+
+        self.state.segments_since_snapshot = 0;
+        info!("[FILE-SYNC] Compacted into snapshot {}", snapshot.to_name());
+        Ok(())
+    }
+
+    /// Refreshes the known file listing from the backend.
+    async fn refresh_listing(&mut self, storage: &dyn CloudStorage) -> SyncResult<()> {
+        if !self.state.listed {
+            for file in storage.list_files().await? {
+                self.track_file(&file);
+            }
+            self.state.listed = true;
+        }
+
+        let changes = storage.get_changes(self.state.cursor.as_deref()).await?;
+        for file in &changes.changed {
+            self.track_file(file);
+        }
+        for id in &changes.deleted {
+            self.state.files.remove(id);
+        }
+        if changes.next_cursor.is_some() {
+            self.state.cursor = changes.next_cursor;
+        }
+
+        // Never reuse a sequence number, even if local state was lost.
+        let own_max = self
+            .state
+            .files
+            .values()
+            .filter_map(|n| SyncFileName::parse(n))
+            .filter(|f| f.peer_id == self.peer_id)
+            .map(|f| f.seq + 1)
+            .max()
+            .unwrap_or(0);
+        self.state.next_seq = self.state.next_seq.max(own_max);
+        Ok(())
+    }
+
+    fn track_file(&mut self, file: &CloudFile) {
+        if SyncFileName::parse(&file.name).is_some() {
+            self.state.files.insert(file.id.clone(), file.name.clone());
+        }
+    }
+
+    async fn write_file(
+        &mut self,
+        storage: &dyn CloudStorage,
+        kind: SyncFileKind,
+        events: Vec<Event>,
+    ) -> SyncResult<SyncFileName> {
+        let name = SyncFileName {
+            peer_id: self.peer_id,
+            seq: self.state.next_seq,
+            kind,
+        };
+        let body = SyncFileBody {
+            version: FOLDER_FORMAT_VERSION,
+            peer_id: self.peer_id,
+            seq: name.seq,
+            kind,
+            events,
+        };
+        let bytes = seal_file(&self.key, &body)?;
+        let file = storage.upload(&name.to_name(), &bytes).await?;
+        self.state.files.insert(file.id, name.to_name());
+        self.state.next_seq += 1;
+        debug!("[FILE-SYNC] Wrote {} ({} events)", name.to_name(), body.events.len());
+        Ok(name)
+    }
+
+    async fn read_file(
+        &self,
+        storage: &dyn CloudStorage,
+        id: &str,
+        name: &SyncFileName,
+    ) -> SyncResult<SyncFileBody> {
+        let bytes = storage.download(id).await?;
+        let body = open_file(&self.key, &bytes)?;
+        if body.peer_id != name.peer_id || body.seq != name.seq || body.kind != name.kind {
+            return Err(SyncError::Protocol(format!(
+                "sync file contents do not match its name {}",
+                name.to_name()
+            )));
+        }
+        Ok(body)
+    }
+
+    /// Applies the events of a file. Segment events are also stored so they
+    /// propagate over P2P; synthetic snapshot events are not. The delete
+    /// events a snapshot carries are real, so they're stored too, and
+    /// skipped for entities that are already gone.
+    async fn apply_events(&self, body: &SyncFileBody, report: &mut FileSyncReport) {
+        let entity_store = self.entity_store.clone();
+        let event_store = self.event_store.clone();
+        let events = body.events.clone();
+        let peer_id = self.peer_id;
+        let store_events = body.kind == SyncFileKind::Segment;
+
+        let result = tokio::task::spawn_blocking(move || {
+            let applicator = EventApplicator::new(peer_id);
+            let mut updated = Vec::new();
+            for event in &events {
+                let tombstone = matches!(event.payload, EventPayload::EntityDeleted { .. });
+                if tombstone && !store_events {
+                    if let Err(e) = event_store.save_event(event) {
+                        warn!("[FILE-SYNC] Failed to save event {}: {}", event.id, e);
+                    }
+                    match entity_store.get_entity(&event.entity_id.to_string()) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => continue,
+                        Err(e) => {
+                            warn!("[FILE-SYNC] Failed to read entity {}: {}", event.entity_id, e);
+                            continue;
+                        }
+                    }
+                }
+                match applicator.apply_event(event, &entity_store, None, None) {
+                    Ok(true) => {
+                        if store_events {
+                            if let Err(e) = event_store.save_event(event) {
+                                warn!("[FILE-SYNC] Failed to save event {}: {}", event.id, e);
+                            }
+                        }
+                        let _ = entity_store.invalidate_sync_ledger_for_entity(&event.entity_id.to_string());
+                        updated.push(event.entity_id);
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("[FILE-SYNC] Failed to apply event {}: {}", event.id, e),
+                }
+            }
+            updated
+        })
+        .await;
+
+        match result {
+            Ok(updated) => {
+                report.events_applied += updated.len();
+                for eid in updated {
+                    if !report.updated_entities.contains(&eid) {
+                        report.updated_entities.push(eid);
+                    }
+                }
+            }
+            Err(e) => warn!("[FILE-SYNC] spawn_blocking panicked applying events: {}", e),
+        }
+    }
+}
+
+/// Builds snapshot events for every syncable entity, stamped with the
+/// entity's own modification time so merges keep last-writer-wins order,
+/// followed by the latest delete event of every entity that no longer
+/// exists.
+fn snapshot_events(
+    entity_store: &EntityStore,
+    event_store: &EventStore,
+    peer_id: PeerId,
+) -> SyncResult<Vec<Event>> {
+    let ids = entity_store
+        .list_all_entity_ids()
+        .map_err(|e| SyncError::Storage(e.to_string()))?;
+    let mut events = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let Ok(entity_id) = id.parse::<EntityId>() else { continue };
+        let Some(entity) = entity_store
+            .get_entity(id)
+            .map_err(|e| SyncError::Storage(e.to_string()))?
+        else {
+            continue;
+        };
+        if entity.data.pointer("/local_only").and_then(|v| v.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        events.push(Event::new(
+            entity_id,
+            peer_id,
+            HybridTimestamp::new(entity.modified_at.max(0) as u64, 0),
+            EventPayload::FullSnapshot {
+                entity_type: entity.entity_type,
+                json_data: entity.data.to_string(),
+            },
+        ));
+    }
+
+    let live: HashSet<&String> = ids.iter().collect();
+    let mut tombstones: HashMap<EntityId, Event> = HashMap::new();
+    for event in event_store
+        .get_deletions()
+        .map_err(|e| SyncError::Storage(e.to_string()))?
+    {
+        if !live.contains(&event.entity_id.to_string()) {
+            tombstones.insert(event.entity_id, event);
+        }
+    }
+    let mut tombstones: Vec<Event> = tombstones.into_values().collect();
+    tombstones.sort_by_key(|e| e.timestamp);
+    events.extend(tombstones);
+    Ok(events)
+}
+
+/// Whether an event belongs to an entity marked `local_only`.
+fn is_local_only(event: &Event) -> bool {
+    let json = match &event.payload {
+        EventPayload::EntityCreated { json_data, .. }
+        | EventPayload::EntityUpdated { json_data, .. }
+        | EventPayload::FullSnapshot { json_data, .. }
+        | EventPayload::ConflictResolved { json_data, .. } => json_data,
+        _ => return false,
+    };
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()
+        .and_then(|v| v.pointer("/local_only").and_then(|b| b.as_bool()))
+        .unwrap_or(false)
+}
+
+fn seal_file(key: &DerivedKey, body: &SyncFileBody) -> SyncResult<Vec<u8>> {
+    let json = serde_json::to_vec(body)?;
+    let compressed = zstd::bulk::compress(&json, ZSTD_LEVEL)
+        .map_err(|e| SyncError::Storage(format!("zstd compress error: {e}")))?;
+    let encrypted = encrypt(key, &compressed).map_err(|e| SyncError::Encryption(e.to_string()))?;
+
+    let mut bytes = Vec::with_capacity(FILE_MAGIC.len() + encrypted.len());
+    bytes.extend_from_slice(FILE_MAGIC);
+    bytes.extend_from_slice(&encrypted.nonce);
+    bytes.extend_from_slice(&encrypted.ciphertext);
+    Ok(bytes)
+}
+
+fn open_file(key: &DerivedKey, bytes: &[u8]) -> SyncResult<SyncFileBody> {
+    let rest = bytes
+        .strip_prefix(FILE_MAGIC)
+        .ok_or_else(|| SyncError::Protocol("not a PrivStack sync file".to_string()))?;
+    if rest.len() < NONCE_LEN {
+        return Err(SyncError::Protocol("truncated sync file".to_string()));
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let encrypted = EncryptedData {
+        nonce: nonce.try_into().expect("split at nonce length"),
+        ciphertext: ciphertext.to_vec(),
+    };
+    let compressed = decrypt(key, &encrypted).map_err(|e| SyncError::Encryption(e.to_string()))?;
+    let json = zstd::stream::decode_all(compressed.as_slice())
+        .map_err(|e| SyncError::Protocol(format!("zstd decode error: {e}")))?;
+    let body: SyncFileBody = serde_json::from_slice(&json)?;
+    if body.version > FOLDER_FORMAT_VERSION {
+        return Err(SyncError::Protocol(format!(
+            "unsupported sync file version: {}",
+            body.version
+        )));
+    }
+    Ok(body)
+}
@@ -12,6 +12,12 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
+/// MIME type Google Drive uses for folders.
+const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// File fields requested wherever a file resource is returned.
+const FILE_FIELDS: &str = "id,name,size,modifiedTime,md5Checksum,mimeType,parents,trashed";
+
 /// Google Drive specific configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GoogleDriveConfig {
@@ -67,13 +73,16 @@ struct DriveFile {
     id: String,
     name: String,
     #[serde(rename = "mimeType")]
-    #[allow(dead_code)]
     mime_type: String,
     size: Option<String>,
     #[serde(rename = "modifiedTime")]
     modified_time: Option<String>,
     #[serde(rename = "md5Checksum")]
     md5_checksum: Option<String>,
+    #[serde(default)]
+    parents: Vec<String>,
+    #[serde(default)]
+    trashed: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -243,8 +252,9 @@ impl GoogleDriveStorage {
 
             // Search for existing folder
             let query = format!(
-                "name = '{}' and mimeType = 'application/vnd.google-apps.folder' and '{}' in parents and trashed = false",
-                folder_name, parent_id
+                "name = '{}' and mimeType = '{FOLDER_MIME_TYPE}' and '{}' in parents and trashed = false",
+                escape_query(folder_name),
+                parent_id
             );
 
             let response = self
@@ -267,7 +277,7 @@ impl GoogleDriveStorage {
                 // Create the folder
                 let metadata = serde_json::json!({
                     "name": folder_name,
-                    "mimeType": "application/vnd.google-apps.folder",
+                    "mimeType": FOLDER_MIME_TYPE,
                     "parents": [parent_id]
                 });
 
@@ -300,6 +310,38 @@ impl GoogleDriveStorage {
         Ok(parent_id)
     }
 
+    /// Finds the file with the given name in the sync folder.
+    async fn find_file(&self, access_token: &str, folder_id: &str, name: &str) -> SyncResult<Option<DriveFile>> {
+        let query = format!(
+            "name = '{}' and '{}' in parents and trashed = false and mimeType != '{FOLDER_MIME_TYPE}'",
+            escape_query(name),
+            folder_id
+        );
+
+        let response = self
+            .client
+            .get(format!("{}/drive/v3/files", self.config.api_base_url))
+            .bearer_auth(access_token)
+            .query(&[("q", query.as_str()), ("fields", &format!("files({FILE_FIELDS})"))])
+            .send()
+            .await
+            .map_err(|e| SyncError::Network(format!("file search failed: {e}")))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.unwrap_or_default();
+            return Err(SyncError::Network(format!("file search failed: {error}")));
+        }
+
+        let file_list: DriveFileList = response
+            .json()
+            .await
+            .map_err(|e| SyncError::Network(format!("failed to parse file search: {e}")))?;
+
+        // Drive matches names exactly; checking again keeps a lenient server
+        // from handing back an unrelated file.
+        Ok(file_list.files.into_iter().find(|f| f.name == name && f.mime_type != FOLDER_MIME_TYPE))
+    }
+
     fn drive_file_to_cloud_file(&self, file: DriveFile) -> CloudFile {
         let size = file.size.and_then(|s| s.parse().ok()).unwrap_or(0);
         let modified_at = file
@@ -468,6 +510,10 @@ impl CloudStorage for GoogleDriveStorage {
                 .to_string()
         };
 
+        // Changes cover the whole Drive; once the sync folder is known, only
+        // its files are reported.
+        let folder_id = self.sync_folder_id.read().await.clone();
+
         let mut changed = Vec::new();
         let mut deleted = Vec::new();
         let mut page_token = Some(start_token);
@@ -480,7 +526,7 @@ impl CloudStorage for GoogleDriveStorage {
                 .bearer_auth(&access_token)
                 .query(&[
                     ("pageToken", token.as_str()),
-                    ("fields", "nextPageToken,newStartPageToken,changes(removed,fileId,file(id,name,size,modifiedTime,md5Checksum,mimeType))"),
+                    ("fields", &format!("nextPageToken,newStartPageToken,changes(removed,fileId,file({FILE_FIELDS}))")),
                     ("pageSize", "100"),
                 ])
                 .send()
@@ -501,7 +547,20 @@ impl CloudStorage for GoogleDriveStorage {
                 if change.removed.unwrap_or(false) {
                     deleted.push(change.file_id);
                 } else if let Some(file) = change.file {
-                    changed.push(self.drive_file_to_cloud_file(file));
+                    if file.mime_type == FOLDER_MIME_TYPE {
+                        continue;
+                    }
+                    let outside = folder_id
+                        .as_ref()
+                        .is_some_and(|id| !file.parents.is_empty() && !file.parents.contains(id));
+                    if outside {
+                        continue;
+                    }
+                    if file.trashed {
+                        deleted.push(change.file_id);
+                    } else {
+                        changed.push(self.drive_file_to_cloud_file(file));
+                    }
                 }
             }
 
@@ -524,7 +583,38 @@ impl CloudStorage for GoogleDriveStorage {
 
         debug!("Uploading file: {} ({} bytes)", name, content.len());
 
-        // Use multipart upload for files
+        // Drive allows several files with one name, so an existing file is
+        // updated in place rather than uploaded again.
+        if let Some(existing) = self.find_file(&access_token, &folder_id, name).await? {
+            let response = self
+                .client
+                .patch(format!(
+                    "{}/upload/drive/v3/files/{}",
+                    self.config.api_base_url, existing.id
+                ))
+                .bearer_auth(&access_token)
+                .query(&[("uploadType", "media"), ("fields", FILE_FIELDS)])
+                .header("Content-Type", "application/octet-stream")
+                .body(content.to_vec())
+                .send()
+                .await
+                .map_err(|e| SyncError::Network(format!("upload failed: {e}")))?;
+
+            if !response.status().is_success() {
+                let error = response.text().await.unwrap_or_default();
+                return Err(SyncError::Network(format!("upload failed: {error}")));
+            }
+
+            let file: DriveFile = response
+                .json()
+                .await
+                .map_err(|e| SyncError::Network(format!("parse upload response failed: {e}")))?;
+
+            info!("Updated file: {} (id: {})", name, file.id);
+            return Ok(self.drive_file_to_cloud_file(file));
+        }
+
+        // Use multipart upload for new files
         let metadata = serde_json::json!({
             "name": name,
             "parents": [folder_id]
@@ -542,8 +632,9 @@ impl CloudStorage for GoogleDriveStorage {
 
         let response = self
             .client
-            .post(format!("{}/upload/drive/v3/files?uploadType=multipart", self.config.api_base_url))
+            .post(format!("{}/upload/drive/v3/files", self.config.api_base_url))
             .bearer_auth(&access_token)
+            .query(&[("uploadType", "multipart"), ("fields", FILE_FIELDS)])
             .header("Content-Type", format!("multipart/related; boundary={boundary}"))
             .body(body)
             .send()
@@ -623,3 +714,8 @@ impl CloudStorage for GoogleDriveStorage {
         Ok(())
     }
 }
+
+/// Quotes a value for a Drive search query string.
+fn escape_query(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
@@ -0,0 +1,233 @@
+//! Local folder storage implementation.
+//!
+//! Stores sync files in a plain directory that another tool replicates
+//! between devices (Syncthing, Dropbox, a network share, ...).
+
+use super::storage::{
+    diff_listing, ChangeSet, CloudFile, CloudStorage, CloudStorageConfig, ListingFingerprints,
+};
+use crate::error::{SyncError, SyncResult};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Local folder specific configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalFolderConfig {
+    /// The replicated directory, e.g. `~/Sync` or `~/Dropbox`.
+    pub root: PathBuf,
+    /// Base cloud storage config. `sync_folder` is relative to `root`.
+    #[serde(flatten)]
+    pub base: CloudStorageConfig,
+}
+
+impl LocalFolderConfig {
+    /// Creates a config for a replicated directory with default settings.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            base: CloudStorageConfig::default(),
+        }
+    }
+}
+
+/// Local folder storage implementation.
+///
+/// File IDs are file names. Writes go to a hidden temporary file that is
+/// renamed into place, so replication tools never pick up partial files.
+pub struct LocalFolderStorage {
+    config: LocalFolderConfig,
+    /// Fingerprints from the last listing, for cursor-less change detection.
+    known_files: Arc<RwLock<ListingFingerprints>>,
+}
+
+impl LocalFolderStorage {
+    /// Creates a new local folder storage instance.
+    pub fn new(config: LocalFolderConfig) -> Self {
+        Self {
+            config,
+            known_files: Arc::new(RwLock::new(ListingFingerprints::new())),
+        }
+    }
+
+    fn sync_folder(&self) -> PathBuf {
+        self.config.root.join(&self.config.base.sync_folder)
+    }
+
+    /// Path of a file in the sync folder. Rejects names that would escape it.
+    fn file_path(&self, name: &str) -> SyncResult<PathBuf> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(SyncError::Storage(format!("invalid file name: {name}")));
+        }
+        Ok(self.sync_folder().join(name))
+    }
+
+    async fn path_to_cloud_file(&self, path: &Path) -> SyncResult<CloudFile> {
+        let metadata = fs::metadata(path)
+            .await
+            .map_err(|e| SyncError::Storage(format!("failed to get file metadata: {e}")))?;
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(CloudFile {
+            id: name.clone(),
+            path: format!("{}/{}", self.config.base.sync_folder, name),
+            name,
+            size: metadata.len(),
+            modified_at: metadata.modified().unwrap_or(SystemTime::now()),
+            content_hash: None,
+        })
+    }
+}
+
+#[async_trait]
+impl CloudStorage for LocalFolderStorage {
+    fn provider_name(&self) -> &'static str {
+        "Local Folder"
+    }
+
+    fn is_authenticated(&self) -> bool {
+        // "Authenticated" if the replicated directory exists
+        self.config.root.is_dir()
+    }
+
+    async fn authenticate(&mut self) -> SyncResult<Option<String>> {
+        if !self.config.root.is_dir() {
+            return Err(SyncError::Auth(format!(
+                "sync directory not found at {:?}",
+                self.config.root
+            )));
+        }
+
+        self.ensure_sync_folder().await?;
+        info!("Local folder ready: {:?}", self.config.root);
+        Ok(None)
+    }
+
+    async fn complete_auth(&mut self, _auth_code: &str) -> SyncResult<()> {
+        // No-op for a local folder
+        Ok(())
+    }
+
+    async fn list_files(&self) -> SyncResult<Vec<CloudFile>> {
+        let sync_folder = self.sync_folder();
+
+        let mut files = Vec::new();
+        let mut read_dir = fs::read_dir(&sync_folder)
+            .await
+            .map_err(|e| SyncError::Storage(format!("failed to read sync folder: {e}")))?;
+
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .map_err(|e| SyncError::Storage(format!("failed to read directory entry: {e}")))?
+        {
+            let path = entry.path();
+
+            // Skip directories, hidden files and our own temporary files
+            if path.is_dir()
+                || path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().starts_with('.'))
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+
+            match self.path_to_cloud_file(&path).await {
+                Ok(file) => files.push(file),
+                Err(e) => warn!("Skipping file due to error: {e}"),
+            }
+        }
+
+        Ok(files)
+    }
+
+    async fn get_changes(&self, cursor: Option<&str>) -> SyncResult<ChangeSet> {
+        let files = self.list_files().await?;
+
+        let previous = match cursor.map(serde_json::from_str::<ListingFingerprints>) {
+            Some(Ok(previous)) => previous,
+            Some(Err(e)) => {
+                warn!("Ignoring invalid local folder change cursor: {e}");
+                self.known_files.read().await.clone()
+            }
+            None => self.known_files.read().await.clone(),
+        };
+
+        let (changes, current) = diff_listing(files, &previous);
+        *self.known_files.write().await = current;
+        Ok(changes)
+    }
+
+    async fn upload(&self, name: &str, content: &[u8]) -> SyncResult<CloudFile> {
+        self.ensure_sync_folder().await?;
+        let file_path = self.file_path(name)?;
+        let temp_path = self.sync_folder().join(format!(".{name}.partial"));
+
+        debug!("Writing to local folder: {:?} ({} bytes)", file_path, content.len());
+
+        fs::write(&temp_path, content)
+            .await
+            .map_err(|e| SyncError::Storage(format!("failed to write file: {e}")))?;
+        if let Err(e) = fs::rename(&temp_path, &file_path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(SyncError::Storage(format!("failed to move file into place: {e}")));
+        }
+
+        let file = self.path_to_cloud_file(&file_path).await?;
+        self.known_files
+            .write()
+            .await
+            .insert(file.id.clone(), file.fingerprint());
+        info!("Wrote file to local folder: {}", name);
+        Ok(file)
+    }
+
+    async fn download(&self, file_id: &str) -> SyncResult<Vec<u8>> {
+        let file_path = self.file_path(file_id)?;
+
+        debug!("Reading from local folder: {:?}", file_path);
+
+        match fs::read(&file_path).await {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(SyncError::Storage(format!("file not found: {file_id}")))
+            }
+            Err(e) => Err(SyncError::Storage(format!("failed to read file: {e}"))),
+        }
+    }
+
+    async fn delete(&self, file_id: &str) -> SyncResult<()> {
+        let file_path = self.file_path(file_id)?;
+
+        match fs::remove_file(&file_path).await {
+            Ok(()) => info!("Deleted file from local folder: {:?}", file_path),
+            // File doesn't exist - that's fine for delete
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SyncError::Storage(format!("failed to delete file: {e}"))),
+        }
+
+        self.known_files.write().await.remove(file_id);
+        Ok(())
+    }
+
+    async fn ensure_sync_folder(&self) -> SyncResult<()> {
+        let sync_folder = self.sync_folder();
+        if !sync_folder.exists() {
+            fs::create_dir_all(&sync_folder)
+                .await
+                .map_err(|e| SyncError::Storage(format!("failed to create sync folder: {e}")))?;
+            info!("Created local sync folder: {:?}", sync_folder);
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,22 @@
 //! Cloud storage transports for sync.
 //!
 //! Provides file-based sync using cloud storage providers like
-//! Google Drive and iCloud as the transport layer.
+//! Google Drive, iCloud, WebDAV servers and replicated local folders
+//! as the transport layer.
 
+pub mod file_sync;
 pub mod google_drive;
 pub mod icloud;
+pub mod local_folder;
 pub mod storage;
+pub mod webdav;
 
+pub use file_sync::{
+    FileSyncConfig, FileSyncEngine, FileSyncReport, FileSyncState, FolderKey, SyncFileKind,
+    SyncFileName, FOLDER_MANIFEST_NAME, MAX_FOLDER_MEMORY_COST, MAX_FOLDER_TIME_COST,
+};
 pub use google_drive::{GoogleDriveConfig, GoogleDriveStorage};
 pub use icloud::{ICloudConfig, ICloudStorage};
+pub use local_folder::{LocalFolderConfig, LocalFolderStorage};
 pub use storage::{CloudFile, CloudStorage, CloudStorageConfig};
+pub use webdav::{WebDavConfig, WebDavStorage};
@@ -5,7 +5,8 @@
 use crate::error::SyncResult;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Configuration for cloud storage.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,6 +57,59 @@ pub struct ChangeSet {
     pub next_cursor: Option<String>,
 }
 
+/// File ID -> version fingerprint of a full listing.
+///
+/// Backends without a server-side change feed serialize this as their
+/// change cursor and diff each new listing against it.
+pub(crate) type ListingFingerprints = BTreeMap<String, String>;
+
+impl CloudFile {
+    /// Version fingerprint: the content hash (e.g. an ETag) when the backend
+    /// provides one, otherwise size and modification time.
+    pub(crate) fn fingerprint(&self) -> String {
+        match &self.content_hash {
+            Some(hash) => hash.clone(),
+            None => {
+                let nanos = self
+                    .modified_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_nanos())
+                    .unwrap_or(0);
+                format!("{}-{}", self.size, nanos)
+            }
+        }
+    }
+}
+
+/// Diffs a full listing against the previous fingerprints.
+pub(crate) fn diff_listing(
+    files: Vec<CloudFile>,
+    previous: &ListingFingerprints,
+) -> (ChangeSet, ListingFingerprints) {
+    let current: ListingFingerprints = files
+        .iter()
+        .map(|f| (f.id.clone(), f.fingerprint()))
+        .collect();
+    let deleted = previous
+        .keys()
+        .filter(|id| !current.contains_key(*id))
+        .cloned()
+        .collect();
+    let changed = files
+        .into_iter()
+        .filter(|f| previous.get(&f.id) != current.get(&f.id))
+        .collect();
+    let next_cursor = serde_json::to_string(&current).ok();
+    (
+        ChangeSet {
+            changed,
+            deleted,
+            next_cursor,
+        },
+        current,
+    )
+}
+
 /// Abstract cloud storage interface.
 #[async_trait]
 pub trait CloudStorage: Send + Sync {
@@ -0,0 +1,683 @@
+//! WebDAV storage implementation.
+//!
+//! Works with any WebDAV server (Nextcloud, ownCloud, Apache `mod_dav`,
+//! `rclone serve webdav`, ...). Files are listed with `PROPFIND`, changes
+//! are detected by ETag, and requests authenticate with HTTP Digest or
+//! Basic auth, whichever the server challenges for.
+
+use super::storage::{
+    diff_listing, ChangeSet, CloudFile, CloudStorage, CloudStorageConfig, ListingFingerprints,
+};
+use crate::error::{SyncError, SyncResult};
+use async_trait::async_trait;
+use md5::Md5;
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::Reader;
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, ETAG, WWW_AUTHENTICATE};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Properties requested when listing the sync folder.
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+    <d:getetag/>
+  </d:prop>
+</d:propfind>"#;
+
+/// WebDAV specific configuration.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    /// Root URL of the WebDAV share, e.g.
+    /// `https://cloud.example.com/remote.php/dav/files/alice`.
+    pub base_url: String,
+    /// Account user name.
+    pub username: String,
+    /// Account password (or a Nextcloud/ownCloud app password).
+    pub password: String,
+    /// Base cloud storage config. `sync_folder` is relative to `base_url`.
+    #[serde(flatten)]
+    pub base: CloudStorageConfig,
+}
+
+impl fmt::Debug for WebDavConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebDavConfig")
+            .field("base_url", &self.base_url)
+            .field("username", &self.username)
+            .field("password", &"[REDACTED]")
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+/// Hash algorithm of a Digest challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "MD5-SESS" => Some(Self::Md5Sess),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-256-SESS" => Some(Self::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn is_session(self) -> bool {
+        matches!(self, Self::Md5Sess | Self::Sha256Sess)
+    }
+
+    fn hash(self, data: &str) -> String {
+        match self {
+            Self::Md5 | Self::Md5Sess => hex::encode(Md5::digest(data.as_bytes())),
+            Self::Sha256 | Self::Sha256Sess => hex::encode(Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// A Digest challenge from `WWW-Authenticate` (RFC 7616).
+#[derive(Debug, Clone)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    /// Whether the server offered `qop=auth`.
+    qop_auth: bool,
+    algorithm: DigestAlgorithm,
+    /// Nonce count, incremented for every request with this nonce.
+    nonce_count: u32,
+}
+
+/// The authentication scheme the server asked for.
+#[derive(Debug, Clone)]
+enum AuthScheme {
+    Basic,
+    Digest(DigestChallenge),
+}
+
+/// WebDAV storage implementation.
+pub struct WebDavStorage {
+    config: WebDavConfig,
+    client: Client,
+    /// Scheme from the last challenge; reused so later requests
+    /// authenticate on the first try.
+    auth: Arc<RwLock<Option<AuthScheme>>>,
+    /// Whether the sync folder is known to exist.
+    folder_ready: Arc<RwLock<bool>>,
+    /// Whether the server accepted our last request.
+    authenticated: AtomicBool,
+    /// Fingerprints from the last listing, for cursor-less change detection.
+    known_files: Arc<RwLock<ListingFingerprints>>,
+}
+
+impl WebDavStorage {
+    /// Creates a new WebDAV storage instance.
+    pub fn new(config: WebDavConfig) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .expect("failed to create HTTP client");
+
+        Self {
+            config,
+            client,
+            auth: Arc::new(RwLock::new(None)),
+            folder_ready: Arc::new(RwLock::new(false)),
+            authenticated: AtomicBool::new(false),
+            known_files: Arc::new(RwLock::new(ListingFingerprints::new())),
+        }
+    }
+
+    /// URL of the sync folder, with a trailing slash.
+    fn folder_url(&self) -> String {
+        let mut url = self.config.base_url.trim_end_matches('/').to_string();
+        for segment in self.config.base.sync_folder.split('/').filter(|s| !s.is_empty()) {
+            url.push('/');
+            url.push_str(&urlencoding::encode(segment));
+        }
+        url.push('/');
+        url
+    }
+
+    /// URL of a file in the sync folder. File IDs are plain file names.
+    fn file_url(&self, name: &str) -> SyncResult<String> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(SyncError::Storage(format!("invalid file name: {name}")));
+        }
+        Ok(format!("{}{}", self.folder_url(), urlencoding::encode(name)))
+    }
+
+    /// Sends a request, answering an authentication challenge once.
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        depth: Option<&str>,
+        body: Option<Vec<u8>>,
+    ) -> SyncResult<Response> {
+        for attempt in 0..2 {
+            let mut request = self.client.request(method.clone(), url);
+            if let Some(depth) = depth {
+                request = request.header("Depth", depth);
+            }
+            if let Some(body) = &body {
+                let content_type = if method.as_str() == "PROPFIND" {
+                    "application/xml; charset=utf-8"
+                } else {
+                    "application/octet-stream"
+                };
+                request = request.header(CONTENT_TYPE, content_type).body(body.clone());
+            }
+            request = match self.auth.write().await.as_mut() {
+                Some(AuthScheme::Basic) => {
+                    request.basic_auth(&self.config.username, Some(&self.config.password))
+                }
+                Some(AuthScheme::Digest(challenge)) => {
+                    let header = self.digest_authorization(challenge, &method, url)?;
+                    request.header(AUTHORIZATION, header)
+                }
+                None => request,
+            };
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| SyncError::Network(format!("{method} {url} failed: {e}")))?;
+
+            if response.status() != StatusCode::UNAUTHORIZED {
+                self.authenticated.store(true, Ordering::Relaxed);
+                return Ok(response);
+            }
+            if attempt > 0 {
+                break;
+            }
+
+            // Fresh challenge (first contact, or an expired nonce): retry once.
+            let scheme = parse_challenge(response.headers()).ok_or_else(|| {
+                SyncError::Auth("WebDAV server requires an unsupported authentication scheme".to_string())
+            })?;
+            debug!("WebDAV server challenged with {:?}", scheme);
+            *self.auth.write().await = Some(scheme);
+        }
+
+        self.authenticated.store(false, Ordering::Relaxed);
+        Err(SyncError::Auth("WebDAV server rejected the credentials".to_string()))
+    }
+
+    /// Builds a Digest `Authorization` header and bumps the nonce count.
+    fn digest_authorization(
+        &self,
+        challenge: &mut DigestChallenge,
+        method: &Method,
+        url: &str,
+    ) -> SyncResult<String> {
+        let parsed = Url::parse(url).map_err(|e| SyncError::Network(format!("invalid URL {url}: {e}")))?;
+        let uri = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+
+        challenge.nonce_count += 1;
+        let nc = format!("{:08x}", challenge.nonce_count);
+        let cnonce = hex::encode(rand::random::<[u8; 16]>());
+        let alg = challenge.algorithm;
+
+        let mut ha1 = alg.hash(&format!(
+            "{}:{}:{}",
+            self.config.username, challenge.realm, self.config.password
+        ));
+        if alg.is_session() {
+            ha1 = alg.hash(&format!("{ha1}:{}:{cnonce}", challenge.nonce));
+        }
+        let ha2 = alg.hash(&format!("{method}:{uri}"));
+        let response = if challenge.qop_auth {
+            alg.hash(&format!("{ha1}:{}:{nc}:{cnonce}:auth:{ha2}", challenge.nonce))
+        } else {
+            alg.hash(&format!("{ha1}:{}:{ha2}", challenge.nonce))
+        };
+
+        let mut header = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+            quote(&self.config.username),
+            quote(&challenge.realm),
+            quote(&challenge.nonce),
+            quote(&uri),
+            alg.name(),
+            response
+        );
+        if challenge.qop_auth {
+            header.push_str(&format!(r#", qop=auth, nc={nc}, cnonce="{cnonce}""#));
+        }
+        if let Some(opaque) = &challenge.opaque {
+            header.push_str(&format!(r#", opaque="{}""#, quote(opaque)));
+        }
+        Ok(header)
+    }
+
+    /// Runs a `PROPFIND` and returns the non-collection entries.
+    async fn propfind(&self, url: &str, depth: &str) -> SyncResult<Vec<CloudFile>> {
+        let response = self
+            .send(
+                Method::from_bytes(b"PROPFIND").expect("valid method"),
+                url,
+                Some(depth),
+                Some(PROPFIND_BODY.as_bytes().to_vec()),
+            )
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(SyncError::Storage(format!("not found: {url}")));
+        }
+        if status.as_u16() != 207 && !status.is_success() {
+            return Err(SyncError::Network(format!("PROPFIND failed: {status}")));
+        }
+
+        let xml = response
+            .text()
+            .await
+            .map_err(|e| SyncError::Network(format!("read PROPFIND body failed: {e}")))?;
+
+        let files = parse_multistatus(&xml)?
+            .into_iter()
+            .filter(|entry| !entry.is_collection)
+            .filter_map(|entry| self.entry_to_cloud_file(entry))
+            .collect();
+        Ok(files)
+    }
+
+    fn entry_to_cloud_file(&self, entry: DavEntry) -> Option<CloudFile> {
+        let name = href_file_name(&entry.href)?;
+        if name.starts_with('.') {
+            return None;
+        }
+        let modified_at = entry
+            .last_modified
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc2822(t).ok())
+            .map(|dt| UNIX_EPOCH + Duration::from_secs(dt.timestamp().max(0) as u64))
+            .unwrap_or(SystemTime::now());
+
+        Some(CloudFile {
+            id: name.clone(),
+            path: format!("{}/{}", self.config.base.sync_folder, name),
+            name,
+            size: entry.content_length.unwrap_or(0),
+            modified_at,
+            content_hash: entry.etag.map(|e| normalize_etag(&e)),
+        })
+    }
+
+    /// Creates the sync folder one path segment at a time.
+    async fn create_sync_folder(&self) -> SyncResult<()> {
+        let mut url = self.config.base_url.trim_end_matches('/').to_string();
+        for segment in self.config.base.sync_folder.split('/').filter(|s| !s.is_empty()) {
+            url.push('/');
+            url.push_str(&urlencoding::encode(segment));
+            let collection = format!("{url}/");
+
+            let response = self
+                .send(Method::from_bytes(b"MKCOL").expect("valid method"), &collection, None, None)
+                .await?;
+            let status = response.status();
+            // 405: the collection already exists
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(SyncError::Network(format!(
+                    "failed to create folder {segment}: {status}"
+                )));
+            }
+        }
+        info!("Created WebDAV sync folder: {}", self.config.base.sync_folder);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CloudStorage for WebDavStorage {
+    fn provider_name(&self) -> &'static str {
+        "WebDAV"
+    }
+
+    fn is_authenticated(&self) -> bool {
+        self.authenticated.load(Ordering::Relaxed)
+    }
+
+    async fn authenticate(&mut self) -> SyncResult<Option<String>> {
+        // Credentials are part of the config; reaching the folder proves them.
+        self.ensure_sync_folder().await?;
+        info!("WebDAV authenticated at {}", self.config.base_url);
+        Ok(None)
+    }
+
+    async fn complete_auth(&mut self, _auth_code: &str) -> SyncResult<()> {
+        // No-op for WebDAV - there is no OAuth flow
+        Ok(())
+    }
+
+    async fn list_files(&self) -> SyncResult<Vec<CloudFile>> {
+        self.ensure_sync_folder().await?;
+        self.propfind(&self.folder_url(), "1").await
+    }
+
+    async fn get_changes(&self, cursor: Option<&str>) -> SyncResult<ChangeSet> {
+        let files = self.list_files().await?;
+
+        let previous = match cursor.map(serde_json::from_str::<ListingFingerprints>) {
+            Some(Ok(previous)) => previous,
+            Some(Err(e)) => {
+                warn!("Ignoring invalid WebDAV change cursor: {e}");
+                self.known_files.read().await.clone()
+            }
+            None => self.known_files.read().await.clone(),
+        };
+
+        let (changes, current) = diff_listing(files, &previous);
+        *self.known_files.write().await = current;
+        Ok(changes)
+    }
+
+    async fn upload(&self, name: &str, content: &[u8]) -> SyncResult<CloudFile> {
+        self.ensure_sync_folder().await?;
+        let url = self.file_url(name)?;
+
+        debug!("Uploading to WebDAV: {} ({} bytes)", name, content.len());
+
+        let response = self.send(Method::PUT, &url, None, Some(content.to_vec())).await?;
+        if !response.status().is_success() {
+            return Err(SyncError::Network(format!("upload failed: {}", response.status())));
+        }
+
+        let file = match etag_header(response.headers()) {
+            Some(etag) => CloudFile {
+                id: name.to_string(),
+                name: name.to_string(),
+                path: format!("{}/{}", self.config.base.sync_folder, name),
+                size: content.len() as u64,
+                modified_at: SystemTime::now(),
+                content_hash: Some(etag),
+            },
+            // Not every server returns an ETag on PUT; ask for it.
+            None => self
+                .propfind(&url, "0")
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| SyncError::Storage(format!("uploaded file missing: {name}")))?,
+        };
+
+        self.known_files
+            .write()
+            .await
+            .insert(file.id.clone(), file.fingerprint());
+        info!("Uploaded file to WebDAV: {}", name);
+        Ok(file)
+    }
+
+    async fn download(&self, file_id: &str) -> SyncResult<Vec<u8>> {
+        let url = self.file_url(file_id)?;
+
+        debug!("Downloading from WebDAV: {}", file_id);
+
+        let response = self.send(Method::GET, &url, None, None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(SyncError::Storage(format!("file not found: {file_id}")));
+        }
+        if !response.status().is_success() {
+            return Err(SyncError::Network(format!("download failed: {}", response.status())));
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| SyncError::Network(format!("read download body failed: {e}")))?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn delete(&self, file_id: &str) -> SyncResult<()> {
+        let url = self.file_url(file_id)?;
+
+        debug!("Deleting from WebDAV: {}", file_id);
+
+        let response = self.send(Method::DELETE, &url, None, None).await?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(SyncError::Network(format!("delete failed: {}", response.status())));
+        }
+
+        self.known_files.write().await.remove(file_id);
+        info!("Deleted file from WebDAV: {}", file_id);
+        Ok(())
+    }
+
+    async fn ensure_sync_folder(&self) -> SyncResult<()> {
+        if *self.folder_ready.read().await {
+            return Ok(());
+        }
+
+        match self.propfind(&self.folder_url(), "0").await {
+            Ok(_) => {}
+            Err(SyncError::Storage(_)) => self.create_sync_folder().await?,
+            Err(e) => return Err(e),
+        }
+
+        *self.folder_ready.write().await = true;
+        Ok(())
+    }
+}
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+/// One `<response>` of a `207 Multi-Status` body.
+#[derive(Debug, Default)]
+struct DavEntry {
+    href: String,
+    is_collection: bool,
+    content_length: Option<u64>,
+    last_modified: Option<String>,
+    etag: Option<String>,
+}
+
+/// Parses a `207 Multi-Status` body. Namespace prefixes vary between
+/// servers, so elements are matched by local name.
+fn parse_multistatus(xml: &str) -> SyncResult<Vec<DavEntry>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut current: Option<DavEntry> = None;
+    let mut element = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(XmlEvent::Start(e)) => {
+                element = e.local_name().as_ref().to_vec();
+                match element.as_slice() {
+                    b"response" => current = Some(DavEntry::default()),
+                    b"collection" => {
+                        if let Some(entry) = current.as_mut() {
+                            entry.is_collection = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::Empty(e)) => {
+                if e.local_name().as_ref() == b"collection" {
+                    if let Some(entry) = current.as_mut() {
+                        entry.is_collection = true;
+                    }
+                }
+            }
+            Ok(XmlEvent::Text(text)) => {
+                let Some(entry) = current.as_mut() else { continue };
+                let text = text
+                    .unescape()
+                    .map_err(|e| SyncError::Protocol(format!("invalid PROPFIND response: {e}")))?
+                    .into_owned();
+                match element.as_slice() {
+                    b"href" => entry.href = text,
+                    b"getcontentlength" => entry.content_length = text.parse().ok(),
+                    b"getlastmodified" => entry.last_modified = Some(text),
+                    b"getetag" => entry.etag = Some(text),
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::End(e)) => {
+                if e.local_name().as_ref() == b"response" {
+                    if let Some(entry) = current.take() {
+                        entries.push(entry);
+                    }
+                }
+                element.clear();
+            }
+            Ok(XmlEvent::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                return Err(SyncError::Protocol(format!("invalid PROPFIND response: {e}")));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Decoded last path segment of an href (absolute URL or path).
+fn href_file_name(href: &str) -> Option<String> {
+    let segment = href.trim_end_matches('/').rsplit('/').next()?;
+    let name = urlencoding::decode(segment).ok()?.into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Strips the weak prefix and quotes from an ETag.
+fn normalize_etag(etag: &str) -> String {
+    etag.trim().trim_start_matches("W/").trim_matches('"').to_string()
+}
+
+fn etag_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(normalize_etag)
+}
+
+/// Escapes a value for a quoted-string header parameter.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Picks the strongest supported scheme from `WWW-Authenticate` headers.
+fn parse_challenge(headers: &HeaderMap) -> Option<AuthScheme> {
+    let mut basic = false;
+    for value in headers.get_all(WWW_AUTHENTICATE) {
+        let Ok(value) = value.to_str() else { continue };
+        let value = value.trim();
+        let (scheme, params) = value.split_once(' ').unwrap_or((value, ""));
+        if scheme.eq_ignore_ascii_case("digest") {
+            if let Some(challenge) = parse_digest_challenge(params) {
+                return Some(AuthScheme::Digest(challenge));
+            }
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            basic = true;
+        }
+    }
+    basic.then_some(AuthScheme::Basic)
+}
+
+fn parse_digest_challenge(params: &str) -> Option<DigestChallenge> {
+    let params = parse_auth_params(params);
+    let get = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    };
+
+    let algorithm = match get("algorithm") {
+        Some(alg) => DigestAlgorithm::parse(&alg)?,
+        None => DigestAlgorithm::Md5,
+    };
+    let qop_auth = match get("qop") {
+        Some(qop) => {
+            if !qop.split(',').any(|q| q.trim().eq_ignore_ascii_case("auth")) {
+                // Only auth-int offered, which we don't implement
+                return None;
+            }
+            true
+        }
+        None => false,
+    };
+
+    Some(DigestChallenge {
+        realm: get("realm").unwrap_or_default(),
+        nonce: get("nonce")?,
+        opaque: get("opaque"),
+        qop_auth,
+        algorithm,
+        nonce_count: 0,
+    })
+}
+
+/// Splits `key=value, key="quoted, value"` auth parameters.
+fn parse_auth_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.trim().is_empty() {
+            break;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.peek() {
+                if *c == ',' {
+                    break;
+                }
+                value.push(*c);
+                chars.next();
+            }
+        }
+        params.push((key.trim().to_string(), value.trim().to_string()));
+    }
+
+    params
+}
@@ -0,0 +1,144 @@
+//! Manual conflict resolution.
+//!
+//! The [`EventApplicator`] still merges concurrent edits automatically, but
+//! it keeps the losing side as a [`ConflictRecord`]. This module lets the
+//! user inspect those records and override the outcome. A resolution is an
+//! ordinary `ConflictResolved` event: it depends on every version it
+//! supersedes, so it syncs like any other edit and closes the same conflict
+//! on the devices that receive it.
+
+use crate::applicator::{now_millis, ApplicatorError, ApplicatorResult, EventApplicator};
+use privstack_model::EntitySchema;
+use privstack_storage::{ConflictRecord, EntityStore};
+use privstack_types::{EntityId, Event, EventId, EventPayload, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// How the user settled a conflict.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "resolution", content = "data", rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Restore the local state from before the remote edit arrived.
+    KeepMine,
+    /// Take the remote edit as it was sent.
+    KeepTheirs,
+    /// Use a state the user assembled from both sides.
+    Merged(Value),
+}
+
+impl ConflictResolution {
+    /// The name stored on the resolved conflict record.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::KeepMine => "keep_mine",
+            Self::KeepTheirs => "keep_theirs",
+            Self::Merged(_) => "merged",
+        }
+    }
+}
+
+/// A top-level field whose value differs between the two sides.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldDiff {
+    /// Field name; empty when the documents are not JSON objects.
+    pub field: String,
+    /// Local value, or `None` if the field is absent locally.
+    pub mine: Option<Value>,
+    /// Remote value, or `None` if the field is absent remotely.
+    pub theirs: Option<Value>,
+}
+
+/// Lists the top-level fields that differ between the two sides of a conflict.
+pub fn diff_conflict(conflict: &ConflictRecord) -> ApplicatorResult<Vec<FieldDiff>> {
+    let mine: Value = serde_json::from_str(&conflict.local_json)?;
+    let theirs: Value = serde_json::from_str(&conflict.remote_json)?;
+
+    let (Some(mine_obj), Some(theirs_obj)) = (mine.as_object(), theirs.as_object()) else {
+        if mine == theirs {
+            return Ok(Vec::new());
+        }
+        return Ok(vec![FieldDiff {
+            field: String::new(),
+            mine: Some(mine),
+            theirs: Some(theirs),
+        }]);
+    };
+
+    let fields: BTreeSet<&String> = mine_obj.keys().chain(theirs_obj.keys()).collect();
+    Ok(fields
+        .into_iter()
+        .filter(|f| mine_obj.get(*f) != theirs_obj.get(*f))
+        .map(|f| FieldDiff {
+            field: f.clone(),
+            mine: mine_obj.get(f).cloned(),
+            theirs: theirs_obj.get(f).cloned(),
+        })
+        .collect())
+}
+
+/// Points a locally authored event at the entity's current version heads.
+///
+/// Call before signing. Peers use the dependencies to tell whether the
+/// event saw their latest edits or was made concurrently with them. Only
+/// events that carry entity state are versioned; others are left alone.
+pub fn stamp_local_event(store: &EntityStore, event: &mut Event) -> ApplicatorResult<()> {
+    if !matches!(
+        event.payload,
+        EventPayload::EntityCreated { .. }
+            | EventPayload::EntityUpdated { .. }
+            | EventPayload::FullSnapshot { .. }
+            | EventPayload::ConflictResolved { .. }
+    ) {
+        return Ok(());
+    }
+    let entity_id = event.entity_id.to_string();
+    let heads = store.entity_heads(&entity_id)?;
+    event.dependencies = heads.iter().filter_map(|h| h.parse::<EventId>().ok()).collect();
+    store.record_entity_version(&entity_id, &event.id.to_string(), &heads)?;
+    Ok(())
+}
+
+/// Applies the user's choice for an open conflict.
+///
+/// Returns the `ConflictResolved` event; the caller signs, stores and
+/// propagates it like any other locally authored event.
+pub fn resolve_conflict(
+    store: &EntityStore,
+    conflict_id: &str,
+    resolution: ConflictResolution,
+    peer_id: PeerId,
+    schema: Option<&EntitySchema>,
+) -> ApplicatorResult<Event> {
+    let conflict = store
+        .get_sync_conflict(conflict_id)?
+        .ok_or_else(|| ApplicatorError::EntityNotFound(format!("conflict {conflict_id}")))?;
+    if conflict.resolved_at.is_some() {
+        return Err(ApplicatorError::InvalidOperation(format!(
+            "conflict {conflict_id} is already resolved"
+        )));
+    }
+    let entity_id = EntityId::parse(&conflict.entity_id)
+        .map_err(|e| ApplicatorError::InvalidOperation(e.to_string()))?;
+
+    let kind = resolution.as_str();
+    let data = match resolution {
+        ConflictResolution::KeepMine => serde_json::from_str(&conflict.local_json)?,
+        ConflictResolution::KeepTheirs => serde_json::from_str(&conflict.remote_json)?,
+        ConflictResolution::Merged(data) => data,
+    };
+
+    // Depend on both sides of the conflict as well as the current heads, so
+    // every device that recorded it can match the resolution to it.
+    let mut deps: BTreeSet<String> = store.entity_heads(&conflict.entity_id)?.into_iter().collect();
+    deps.extend(conflict.local_heads.iter().cloned());
+    deps.insert(conflict.remote_event_id.clone());
+
+    let mut event =
+        Event::conflict_resolved(entity_id, peer_id, &conflict.entity_type, data.to_string());
+    event.dependencies = deps.iter().filter_map(|d| d.parse::<EventId>().ok()).collect();
+
+    store.resolve_sync_conflict(conflict_id, kind, now_millis())?;
+    EventApplicator::new(peer_id).apply_event(&event, store, schema, None)?;
+    Ok(event)
+}
@@ -0,0 +1,193 @@
+//! Dataset event handler — applies replicated dataset changes to the
+//! dataset store.
+//!
+//! A dataset travels as two kinds of events on the dataset's entity ID:
+//! - a `"dataset"` entity carrying its name, category, formula columns and
+//!   column constraints, which also goes through the entity applicator so
+//!   the dataset is known to entity sync;
+//! - `Dataset*` row and column events, which only the dataset store applies.
+//!
+//! The dataset store merges changes by their stamp, built from the event
+//! timestamp and author (see `privstack_datasets::OpStamp`), so every peer
+//! converges on the same table whatever order events arrive in.
+
+use crate::error::SyncError;
+use async_trait::async_trait;
+use privstack_datasets::{
+    ColumnConstraints, DatasetChange, DatasetId, DatasetOp, DatasetStore, FormulaColumn, OpStamp,
+};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Entity type carrying a dataset's name and category.
+pub const DATASET_ENTITY_TYPE: &str = "dataset";
+
+/// Trait for handling dataset events that flow through the sync pipeline.
+#[async_trait]
+pub trait DatasetEventHandler: Send + Sync {
+    /// Applies an event to the dataset store if it concerns a dataset.
+    /// Returns `Ok(true)` for row and column events (callers should skip the
+    /// normal applicator) and `Ok(false)` for everything else, including the
+    /// `"dataset"` entity events, which the entity applicator also applies.
+    async fn handle_dataset_event(&self, event: &Event) -> Result<bool, SyncError>;
+}
+
+/// Applies dataset events to a `DatasetStore`.
+pub struct DatasetApplicator {
+    store: DatasetStore,
+}
+
+impl DatasetApplicator {
+    pub fn new(store: DatasetStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl DatasetEventHandler for DatasetApplicator {
+    async fn handle_dataset_event(&self, event: &Event) -> Result<bool, SyncError> {
+        if dataset_op_from_event(event)?.is_none() {
+            return Ok(false);
+        }
+        let store = self.store.clone();
+        let event = event.clone();
+        tokio::task::spawn_blocking(move || apply_dataset_event(&store, &event))
+            .await
+            .map_err(|e| SyncError::Storage(format!("dataset apply task failed: {e}")))?
+    }
+}
+
+/// Name, category, formula columns and column constraints of a dataset, as
+/// stored in its `"dataset"` entity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DatasetEntity {
+    name: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    formulas: Vec<FormulaColumn>,
+    #[serde(default)]
+    constraints: Vec<ColumnConstraints>,
+}
+
+/// Applies a dataset event to the store. Returns whether the event belongs
+/// to the dataset store alone, as [`DatasetEventHandler`] does.
+pub fn apply_dataset_event(store: &DatasetStore, event: &Event) -> Result<bool, SyncError> {
+    let Some(op) = dataset_op_from_event(event)? else {
+        return Ok(false);
+    };
+    let dataset_id = DatasetId(event.entity_id.as_uuid());
+    let stamp = OpStamp::new(
+        event.timestamp.wall_time(),
+        event.timestamp.logical(),
+        event.peer_id.to_string(),
+    );
+    if let Err(e) = store.apply_replicated(&dataset_id, &op, &stamp) {
+        warn!("Failed to apply dataset event {:?}: {}", event.id, e);
+        return Err(SyncError::Storage(e.to_string()));
+    }
+    Ok(!matches!(op, DatasetOp::SetMeta { .. } | DatasetOp::DeleteDataset))
+}
+
+/// Reads the dataset change an event carries, if any.
+pub fn dataset_op_from_event(event: &Event) -> Result<Option<DatasetOp>, SyncError> {
+    let op = match &event.payload {
+        EventPayload::DatasetRowUpserted { row_id, cells_json } => DatasetOp::UpsertRow {
+            row_id: row_id.clone(),
+            cells: serde_json::from_str(cells_json)?,
+        },
+        EventPayload::DatasetRowDeleted { row_id } => DatasetOp::DeleteRow {
+            row_id: row_id.clone(),
+        },
+        EventPayload::DatasetColumnAdded {
+            column_id,
+            name,
+            column_type,
+            default_value,
+        } => DatasetOp::AddColumn {
+            column_id: column_id.clone(),
+            name: name.clone(),
+            column_type: column_type.clone(),
+            default: default_value.clone(),
+        },
+        EventPayload::DatasetColumnRenamed { column_id, name } => DatasetOp::RenameColumn {
+            column_id: column_id.clone(),
+            name: name.clone(),
+        },
+        EventPayload::DatasetColumnDropped { column_id } => DatasetOp::DropColumn {
+            column_id: column_id.clone(),
+        },
+        EventPayload::EntityCreated { entity_type, json_data }
+        | EventPayload::EntityUpdated { entity_type, json_data }
+        | EventPayload::FullSnapshot { entity_type, json_data }
+        | EventPayload::ConflictResolved { entity_type, json_data }
+            if entity_type == DATASET_ENTITY_TYPE =>
+        {
+            let entity: DatasetEntity = serde_json::from_str(json_data)?;
+            DatasetOp::SetMeta {
+                name: entity.name,
+                category: entity.category,
+                formulas: entity.formulas,
+                constraints: entity.constraints,
+            }
+        }
+        EventPayload::EntityDeleted { entity_type } if entity_type == DATASET_ENTITY_TYPE => {
+            DatasetOp::DeleteDataset
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(op))
+}
+
+/// Builds the event publishing a local dataset change. The event carries
+/// the change's stamp as its timestamp, so it must be authored by the peer
+/// the stamp names. Name changes are `"dataset"` entity snapshots; the
+/// caller saves the entity before publishing them.
+pub fn dataset_change_event(change: &DatasetChange, peer_id: PeerId) -> Result<Event, SyncError> {
+    let payload = match &change.op {
+        DatasetOp::SetMeta { name, category, formulas, constraints } => EventPayload::FullSnapshot {
+            entity_type: DATASET_ENTITY_TYPE.to_string(),
+            json_data: serde_json::to_string(&DatasetEntity {
+                name: name.clone(),
+                category: category.clone(),
+                formulas: formulas.clone(),
+                constraints: constraints.clone(),
+            })?,
+        },
+        DatasetOp::DeleteDataset => EventPayload::EntityDeleted {
+            entity_type: DATASET_ENTITY_TYPE.to_string(),
+        },
+        DatasetOp::AddColumn {
+            column_id,
+            name,
+            column_type,
+            default,
+        } => EventPayload::DatasetColumnAdded {
+            column_id: column_id.clone(),
+            name: name.clone(),
+            column_type: column_type.clone(),
+            default_value: default.clone(),
+        },
+        DatasetOp::RenameColumn { column_id, name } => EventPayload::DatasetColumnRenamed {
+            column_id: column_id.clone(),
+            name: name.clone(),
+        },
+        DatasetOp::DropColumn { column_id } => EventPayload::DatasetColumnDropped {
+            column_id: column_id.clone(),
+        },
+        DatasetOp::UpsertRow { row_id, cells } => EventPayload::DatasetRowUpserted {
+            row_id: row_id.clone(),
+            cells_json: serde_json::to_string(cells)?,
+        },
+        DatasetOp::DeleteRow { row_id } => EventPayload::DatasetRowDeleted {
+            row_id: row_id.clone(),
+        },
+    };
+    Ok(Event::new(
+        EntityId::from_uuid(change.dataset_id.0),
+        peer_id,
+        HybridTimestamp::new(change.stamp.wall_ms, change.stamp.logical),
+        payload,
+    ))
+}
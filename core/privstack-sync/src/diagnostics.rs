@@ -0,0 +1,531 @@
+//! Sync observability: per-session records, peer metrics and a redacted
+//! diagnostics bundle.
+//!
+//! The orchestrator drives a [`SessionRecorder`] through every sync session,
+//! outbound and inbound, and persists the finished [`SyncSessionRecord`] in
+//! the entity store's `sync_sessions` table. The table is trimmed to a fixed
+//! number of rows, so the history is a bounded ring of the latest sessions.
+//!
+//! [`PeerSyncMetrics`] summarizes the history per peer for the UI.
+//! [`DiagnosticsBundle`] is what a user attaches to a bug report: peer and
+//! entity IDs are replaced by salted hashes, device names are dropped and
+//! IDs or network addresses inside error messages are masked.
+
+use crate::protocol::SyncMessage;
+use crate::transport::{DiscoveredPeer, DiscoveryMethod};
+use crate::{SyncError, SyncResult};
+use privstack_storage::EntityStore;
+use privstack_types::{EntityId, PeerId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Number of session records kept unless configured otherwise.
+pub const DEFAULT_SESSION_HISTORY: usize = 500;
+
+/// Apply errors kept per session; further errors are only counted.
+const MAX_APPLY_ERRORS: usize = 50;
+
+/// Version of the diagnostics bundle layout.
+pub const DIAGNOSTICS_FORMAT_VERSION: u32 = 1;
+
+/// How the session's peer was reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportKind {
+    /// Local network, found via mDNS.
+    Mdns,
+    /// Wide area, found via the DHT.
+    Dht,
+    /// Through a relay (libp2p circuit or cloud relay).
+    Relay,
+    /// A manually added address.
+    Manual,
+    /// The peer was not in the discovery table.
+    Unknown,
+}
+
+impl TransportKind {
+    /// Classifies a discovered peer. A relayed address wins over the
+    /// discovery method, since that is the path the traffic takes.
+    pub fn of_peer(peer: &DiscoveredPeer) -> Self {
+        if peer.addresses.iter().any(|a| a.contains("/p2p-circuit")) {
+            return Self::Relay;
+        }
+        match peer.discovery_method {
+            DiscoveryMethod::Mdns => Self::Mdns,
+            DiscoveryMethod::Dht => Self::Dht,
+            DiscoveryMethod::Manual => Self::Manual,
+            DiscoveryMethod::CloudRelay => Self::Relay,
+        }
+    }
+}
+
+/// Which side opened the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionDirection {
+    /// This device initiated the sync.
+    Outbound,
+    /// The peer initiated the sync and this device answered.
+    Inbound,
+}
+
+/// A step of a sync session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPhase {
+    /// Hello / HelloAck.
+    Handshake,
+    /// Payload key exchange for end-to-end encryption.
+    KeyExchange,
+    /// SyncRequest / SyncState.
+    StateExchange,
+    /// Event batches and acks in both directions.
+    EventExchange,
+    /// Ledger update after the exchange.
+    Finalize,
+}
+
+/// How a session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Completed,
+    Failed,
+}
+
+/// Time spent in one phase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub phase: SyncPhase,
+    pub duration_ms: u64,
+}
+
+/// Traffic in one direction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficCounts {
+    /// Events carried.
+    pub events: u64,
+    /// Approximate bytes, measured as the JSON size of each message.
+    pub bytes: u64,
+    /// Events withheld or dropped by the sync policy.
+    pub policy_filtered: u64,
+}
+
+/// An event that could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyErrorRecord {
+    pub entity_id: String,
+    pub error: String,
+}
+
+/// One sync session with one peer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncSessionRecord {
+    pub session_id: String,
+    pub peer_id: String,
+    pub device_name: Option<String>,
+    pub transport: TransportKind,
+    pub direction: SessionDirection,
+    /// Unix millis.
+    pub started_at: i64,
+    /// Unix millis.
+    pub finished_at: i64,
+    pub duration_ms: u64,
+    pub phases: Vec<PhaseTiming>,
+    pub sent: TrafficCounts,
+    pub received: TrafficCounts,
+    /// Received events dropped because their signature did not match a
+    /// device of their author.
+    pub events_rejected: u64,
+    pub entities_requested: u64,
+    pub entities_synced: u64,
+    pub entities_skipped: u64,
+    pub apply_errors: Vec<ApplyErrorRecord>,
+    /// Apply errors beyond the per-session cap.
+    pub apply_errors_dropped: u64,
+    pub outcome: SessionOutcome,
+    pub error: Option<String>,
+    pub failed_phase: Option<SyncPhase>,
+}
+
+impl SyncSessionRecord {
+    /// Total apply errors, including those not kept.
+    pub fn apply_error_count(&self) -> u64 {
+        self.apply_errors.len() as u64 + self.apply_errors_dropped
+    }
+}
+
+/// Counters the engine collects while it builds or applies batches, taken
+/// by the orchestrator after each call.
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    /// Outgoing events the policy withheld.
+    pub filtered_out: u64,
+    /// Incoming events the policy dropped.
+    pub filtered_in: u64,
+    /// Incoming events dropped by the authorship check.
+    pub rejected: u64,
+    /// Incoming events that failed to apply.
+    pub apply_errors: Vec<(EntityId, String)>,
+}
+
+/// Builds a [`SyncSessionRecord`] while a session runs.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    record: SyncSessionRecord,
+    started: Instant,
+    phase: Option<(SyncPhase, Instant)>,
+    last_activity: Instant,
+}
+
+impl SessionRecorder {
+    /// Starts recording a session with `peer_id`.
+    pub fn new(peer_id: PeerId, direction: SessionDirection) -> Self {
+        let now = Instant::now();
+        Self {
+            record: SyncSessionRecord {
+                session_id: uuid::Uuid::new_v4().to_string(),
+                peer_id: peer_id.to_string(),
+                device_name: None,
+                transport: TransportKind::Unknown,
+                direction,
+                started_at: now_millis(),
+                finished_at: 0,
+                duration_ms: 0,
+                phases: Vec::new(),
+                sent: TrafficCounts::default(),
+                received: TrafficCounts::default(),
+                events_rejected: 0,
+                entities_requested: 0,
+                entities_synced: 0,
+                entities_skipped: 0,
+                apply_errors: Vec::new(),
+                apply_errors_dropped: 0,
+                outcome: SessionOutcome::Completed,
+                error: None,
+                failed_phase: None,
+            },
+            started: now,
+            phase: None,
+            last_activity: now,
+        }
+    }
+
+    /// Sets how the peer was reached and its device name.
+    pub fn set_peer(&mut self, transport: TransportKind, device_name: Option<String>) {
+        self.record.transport = transport;
+        if device_name.is_some() {
+            self.record.device_name = device_name;
+        }
+    }
+
+    /// Ends the current phase, if any, and starts timing `phase`.
+    pub fn begin_phase(&mut self, phase: SyncPhase) {
+        self.end_phase();
+        self.phase = Some((phase, Instant::now()));
+    }
+
+    /// Stops timing the current phase.
+    pub fn end_phase(&mut self) {
+        let Some((phase, since)) = self.phase.take() else { return };
+        let elapsed = since.elapsed().as_millis() as u64;
+        match self.record.phases.iter_mut().find(|t| t.phase == phase) {
+            Some(timing) => timing.duration_ms += elapsed,
+            None => self.record.phases.push(PhaseTiming { phase, duration_ms: elapsed }),
+        }
+    }
+
+    /// Counts a message sent to the peer carrying `events` events.
+    pub fn message_sent(&mut self, message: &SyncMessage, events: usize) {
+        self.record.sent.bytes += message_size(message);
+        self.record.sent.events += events as u64;
+        self.last_activity = Instant::now();
+    }
+
+    /// Counts a message received from the peer carrying `events` events.
+    pub fn message_received(&mut self, message: &SyncMessage, events: usize) {
+        self.record.received.bytes += message_size(message);
+        self.record.received.events += events as u64;
+        self.last_activity = Instant::now();
+    }
+
+    /// Counts events received and applied, beyond those counted with
+    /// their message.
+    pub fn events_received(&mut self, count: usize) {
+        self.record.received.events += count as u64;
+    }
+
+    /// Counts outgoing events the policy withheld.
+    pub fn filtered_out(&mut self, count: u64) {
+        self.record.sent.policy_filtered += count;
+    }
+
+    /// Counts incoming events the policy dropped.
+    pub fn filtered_in(&mut self, count: u64) {
+        self.record.received.policy_filtered += count;
+    }
+
+    /// Counts incoming events dropped by the authorship check.
+    pub fn rejected(&mut self, count: u64) {
+        self.record.events_rejected += count;
+    }
+
+    /// Records an event that failed to apply.
+    pub fn apply_error(&mut self, entity_id: EntityId, error: impl Into<String>) {
+        if self.record.apply_errors.len() < MAX_APPLY_ERRORS {
+            self.record.apply_errors.push(ApplyErrorRecord {
+                entity_id: entity_id.to_string(),
+                error: error.into(),
+            });
+        } else {
+            self.record.apply_errors_dropped += 1;
+        }
+    }
+
+    /// Folds the engine's counters into the session.
+    pub fn merge_report(&mut self, report: BatchReport) {
+        self.filtered_out(report.filtered_out);
+        self.filtered_in(report.filtered_in);
+        self.rejected(report.rejected);
+        for (entity_id, error) in report.apply_errors {
+            self.apply_error(entity_id, error);
+        }
+    }
+
+    /// Sets the number of entities the session covers.
+    pub fn entities_requested(&mut self, count: usize) {
+        self.record.entities_requested = count as u64;
+    }
+
+    /// Counts an entity whose exchange completed.
+    pub fn entity_synced(&mut self) {
+        self.record.entities_synced += 1;
+    }
+
+    /// Counts an entity both sides already agreed on.
+    pub fn entity_skipped(&mut self) {
+        self.record.entities_skipped += 1;
+    }
+
+    /// Marks the session failed in the current phase.
+    pub fn fail(&mut self, error: impl Into<String>) {
+        self.record.outcome = SessionOutcome::Failed;
+        self.record.error = Some(error.into());
+        self.record.failed_phase = self.phase.map(|(phase, _)| phase);
+    }
+
+    /// Time since the last message in either direction.
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// The record so far.
+    pub fn record(&self) -> &SyncSessionRecord {
+        &self.record
+    }
+
+    /// Closes the current phase and returns the finished record.
+    pub fn finish(mut self) -> SyncSessionRecord {
+        self.end_phase();
+        self.record.finished_at = now_millis();
+        self.record.duration_ms = self.started.elapsed().as_millis() as u64;
+        self.record
+    }
+}
+
+/// Approximate size of a message: its JSON encoding. Binary wire formats
+/// are smaller, so this is an upper bound.
+pub fn message_size(message: &SyncMessage) -> u64 {
+    serde_json::to_vec(message).map(|v| v.len() as u64).unwrap_or(0)
+}
+
+/// Appends a finished session to the stored history, keeping at most
+/// `capacity` records.
+pub fn save_session(
+    store: &EntityStore,
+    record: &SyncSessionRecord,
+    capacity: usize,
+) -> SyncResult<()> {
+    let json = serde_json::to_string(record)?;
+    store
+        .append_sync_session(&record.session_id, &record.peer_id, record.started_at, &json, capacity)
+        .map_err(|e| SyncError::Storage(e.to_string()))
+}
+
+/// Loads stored sessions, newest first. Records that no longer parse are
+/// skipped.
+pub fn load_sessions(
+    store: &EntityStore,
+    peer_id: Option<&str>,
+    limit: usize,
+) -> SyncResult<Vec<SyncSessionRecord>> {
+    let rows = store
+        .list_sync_sessions(peer_id, limit)
+        .map_err(|e| SyncError::Storage(e.to_string()))?;
+    Ok(rows.iter().filter_map(|json| serde_json::from_str(json).ok()).collect())
+}
+
+/// Sync totals for one peer over the stored history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerSyncMetrics {
+    pub peer_id: String,
+    pub device_name: Option<String>,
+    pub sessions: u64,
+    pub failed_sessions: u64,
+    pub events_sent: u64,
+    pub events_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub policy_filtered: u64,
+    pub apply_errors: u64,
+    pub avg_duration_ms: u64,
+    /// Transport of the latest session.
+    pub last_transport: TransportKind,
+    pub last_sync_at: i64,
+    pub last_success_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+/// Summarizes sessions per peer, most recently synced peer first.
+pub fn summarize_peers(records: &[SyncSessionRecord]) -> Vec<PeerSyncMetrics> {
+    let mut by_peer: BTreeMap<&str, Vec<&SyncSessionRecord>> = BTreeMap::new();
+    for record in records {
+        by_peer.entry(record.peer_id.as_str()).or_default().push(record);
+    }
+
+    let mut metrics: Vec<PeerSyncMetrics> = by_peer
+        .into_iter()
+        .map(|(peer_id, mut sessions)| {
+            sessions.sort_by_key(|s| s.started_at);
+            let latest = sessions[sessions.len() - 1];
+            let count = sessions.len() as u64;
+            let sum = |f: fn(&SyncSessionRecord) -> u64| sessions.iter().map(|s| f(s)).sum::<u64>();
+            PeerSyncMetrics {
+                peer_id: peer_id.to_string(),
+                device_name: sessions.iter().rev().find_map(|s| s.device_name.clone()),
+                sessions: count,
+                failed_sessions: sum(|s| u64::from(s.outcome == SessionOutcome::Failed)),
+                events_sent: sum(|s| s.sent.events),
+                events_received: sum(|s| s.received.events),
+                bytes_sent: sum(|s| s.sent.bytes),
+                bytes_received: sum(|s| s.received.bytes),
+                policy_filtered: sum(|s| s.sent.policy_filtered + s.received.policy_filtered),
+                apply_errors: sum(SyncSessionRecord::apply_error_count),
+                avg_duration_ms: sum(|s| s.duration_ms) / count,
+                last_transport: latest.transport,
+                last_sync_at: latest.finished_at,
+                last_success_at: sessions
+                    .iter()
+                    .rev()
+                    .find(|s| s.outcome == SessionOutcome::Completed)
+                    .map(|s| s.finished_at),
+                last_error: sessions.iter().rev().find_map(|s| s.error.clone()),
+            }
+        })
+        .collect();
+    metrics.sort_by_key(|m| std::cmp::Reverse(m.last_sync_at));
+    metrics
+}
+
+/// A redacted export of the sync history for bug reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsBundle {
+    pub format_version: u32,
+    pub generated_at: i64,
+    /// Hash of this device's peer ID.
+    pub local_peer: String,
+    pub crate_version: String,
+    pub sessions: Vec<SyncSessionRecord>,
+    pub peers: Vec<PeerSyncMetrics>,
+}
+
+/// Builds a redacted bundle from stored sessions.
+///
+/// IDs are hashed with a salt drawn for this bundle, so the same peer maps
+/// to the same token within one bundle but bundles cannot be linked.
+pub fn export_diagnostics(local_peer: &PeerId, sessions: &[SyncSessionRecord]) -> DiagnosticsBundle {
+    let redactor = Redactor::new(rand::random());
+    let sessions: Vec<SyncSessionRecord> = sessions.iter().map(|s| redactor.session(s)).collect();
+    DiagnosticsBundle {
+        format_version: DIAGNOSTICS_FORMAT_VERSION,
+        generated_at: now_millis(),
+        local_peer: redactor.token("peer", &local_peer.to_string()),
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        peers: summarize_peers(&sessions),
+        sessions,
+    }
+}
+
+struct Redactor {
+    salt: [u8; 16],
+}
+
+impl Redactor {
+    fn new(salt: [u8; 16]) -> Self {
+        Self { salt }
+    }
+
+    fn token(&self, kind: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(value.as_bytes());
+        format!("{kind}-{}", &hex::encode(hasher.finalize())[..12])
+    }
+
+    fn session(&self, record: &SyncSessionRecord) -> SyncSessionRecord {
+        let mut out = record.clone();
+        out.peer_id = self.token("peer", &record.peer_id);
+        out.device_name = None;
+        out.error = record.error.as_deref().map(|e| self.text(e));
+        for err in &mut out.apply_errors {
+            err.entity_id = self.token("entity", &err.entity_id);
+            err.error = self.text(&err.error);
+        }
+        out
+    }
+
+    /// Masks UUIDs and network addresses in free text.
+    fn text(&self, text: &str) -> String {
+        text.split(' ')
+            .map(|word| {
+                if word.starts_with("/ip4/") || word.starts_with("/ip6/") || word.starts_with("/dns") {
+                    "<addr>".to_string()
+                } else {
+                    self.mask_uuids(word)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn mask_uuids(&self, word: &str) -> String {
+        const LEN: usize = 36;
+        let bytes = word.as_bytes();
+        let mut out = String::with_capacity(word.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if i + LEN <= bytes.len() && word.is_char_boundary(i + LEN) {
+                let candidate = &word[i..i + LEN];
+                if uuid::Uuid::parse_str(candidate).is_ok() {
+                    out.push_str(&self.token("id", candidate));
+                    i += LEN;
+                    continue;
+                }
+            }
+            let ch = word[i..].chars().next().unwrap();
+            out.push(ch);
+            i += ch.len_utf8();
+        }
+        out
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
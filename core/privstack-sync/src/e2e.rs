@@ -0,0 +1,406 @@
+//! Application-layer encryption of sync payloads.
+//!
+//! Transport security (Noise/QUIC) protects events between two libp2p
+//! endpoints, but relays, the DHT and any misconfigured transport still see
+//! whatever we hand them. This module encrypts the `EventPayload` of every
+//! event leaving the device so only holders of the payload key can read it.
+//!
+//! # Keys
+//!
+//! - **Group key**: one symmetric key shared by all trusted peers of this
+//!   device. Used for every entity without a more specific key.
+//! - **Entity key**: an optional per-entity key, created when an entity is
+//!   shared with specific peers, and only distributed to those peers.
+//!
+//! Keys are versioned by epoch. The current key of a scope is the one with the
+//! highest `(epoch, key_id)`, so devices that exchange keys converge on the
+//! same choice. Older epochs are kept so in-flight events remain readable.
+//!
+//! # Distribution
+//!
+//! Each device holds an X25519 exchange keypair. Peers advertise the public
+//! half in `HelloMessage`/`HelloAckMessage`, then swap their current keys in a
+//! `KeyShare` round trip, each key sealed with `privstack_crypto::seal_dek`
+//! for the recipient's exchange key.
+//!
+//! # Rotation
+//!
+//! `PairingManager::remove_trusted_peer` calls [`PayloadKeyring::rotate`],
+//! which creates a new epoch for every scope. The removed peer never receives
+//! the new keys, so nothing encrypted after the removal is readable by it.
+
+use crate::error::{SyncError, SyncResult};
+use privstack_crypto::{encrypt, decrypt, open_dek, seal_dek, DerivedKey, EncryptedData, SealedEnvelope};
+use privstack_types::{EntityId, Event, EventId, EventPayload};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a payload key protects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "entity_id", rename_all = "snake_case")]
+pub enum KeyScope {
+    /// Every entity without an entity-specific key.
+    Group,
+    /// A single shared entity.
+    Entity(EntityId),
+}
+
+/// A symmetric payload key at a given epoch.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PayloadKey {
+    /// Fingerprint of the key material (first 16 bytes of SHA-256, hex).
+    pub key_id: String,
+    /// What this key protects.
+    pub scope: KeyScope,
+    /// Rotation epoch; higher epochs supersede lower ones.
+    pub epoch: u32,
+    /// When the key was created or imported (epoch seconds).
+    pub created_at: u64,
+    /// Raw ChaCha20-Poly1305 key.
+    key: [u8; 32],
+}
+
+impl PayloadKey {
+    fn generate(scope: KeyScope, epoch: u32) -> Self {
+        let key = *privstack_crypto::generate_random_key().as_bytes();
+        Self::from_bytes(scope, epoch, key)
+    }
+
+    fn from_bytes(scope: KeyScope, epoch: u32, key: [u8; 32]) -> Self {
+        Self {
+            key_id: fingerprint(&key),
+            scope,
+            epoch,
+            created_at: now_secs(),
+            key,
+        }
+    }
+
+    fn derived(&self) -> DerivedKey {
+        DerivedKey::from_bytes(self.key)
+    }
+}
+
+impl std::fmt::Debug for PayloadKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PayloadKey")
+            .field("key_id", &self.key_id)
+            .field("scope", &self.scope)
+            .field("epoch", &self.epoch)
+            .field("key", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// A payload key sealed for one recipient's exchange public key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedPayloadKey {
+    /// Fingerprint of the sealed key (checked after opening).
+    pub key_id: String,
+    /// What the key protects.
+    pub scope: KeyScope,
+    /// Rotation epoch of the key.
+    pub epoch: u32,
+    /// The key, sealed with X25519 + XSalsa20-Poly1305.
+    pub envelope: SealedEnvelope,
+}
+
+/// Plaintext inside an `EventPayload::Encrypted` ciphertext.
+///
+/// Carries the event and entity IDs so a ciphertext cannot be replayed
+/// under a different event.
+#[derive(Serialize, Deserialize)]
+struct SealedPayload {
+    event_id: EventId,
+    entity_id: EntityId,
+    payload: EventPayload,
+}
+
+/// Payload keys and the device's exchange keypair.
+///
+/// Persisted through `PairingManager`'s secret store, never in its JSON.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PayloadKeyring {
+    /// X25519 secret used to open keys other peers seal for us.
+    #[serde(default)]
+    exchange_secret: Option<[u8; 32]>,
+    /// All known keys by fingerprint.
+    #[serde(default)]
+    keys: HashMap<String, PayloadKey>,
+}
+
+impl std::fmt::Debug for PayloadKeyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PayloadKeyring")
+            .field("exchange_secret", &self.exchange_secret.map(|_| "[REDACTED]"))
+            .field("keys", &self.keys.values().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl PayloadKeyring {
+    /// Creates an empty keyring (keys are generated on first use).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns our exchange public key, generating the keypair if needed.
+    pub fn exchange_public_key(&mut self) -> [u8; 32] {
+        let secret = *self
+            .exchange_secret
+            .get_or_insert_with(|| privstack_crypto::generate_cloud_keypair().secret_bytes());
+        privstack_crypto::CloudKeyPair::from_secret_bytes(secret).public_bytes()
+    }
+
+    /// Returns the current key for a scope, if any.
+    pub fn current_key(&self, scope: &KeyScope) -> Option<&PayloadKey> {
+        self.keys
+            .values()
+            .filter(|k| k.scope == *scope)
+            .max_by(|a, b| (a.epoch, &a.key_id).cmp(&(b.epoch, &b.key_id)))
+    }
+
+    /// Fingerprints of the current key of every scope, sorted.
+    pub fn current_key_ids(&self) -> Vec<String> {
+        let scopes: std::collections::HashSet<&KeyScope> = self.keys.values().map(|k| &k.scope).collect();
+        let mut ids: Vec<String> = scopes
+            .into_iter()
+            .filter_map(|scope| self.current_key(scope))
+            .map(|k| k.key_id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Looks up a key by fingerprint.
+    pub fn key(&self, key_id: &str) -> Option<&PayloadKey> {
+        self.keys.get(key_id)
+    }
+
+    /// Merges another keyring into this one. Keys are combined; our
+    /// exchange keypair is kept if we have one.
+    pub fn merge(&mut self, other: PayloadKeyring) {
+        if self.exchange_secret.is_none() {
+            self.exchange_secret = other.exchange_secret;
+        }
+        for (key_id, key) in other.keys {
+            self.keys.entry(key_id).or_insert(key);
+        }
+    }
+
+    /// Changes whenever a key or the exchange keypair is added. Keys are
+    /// never removed, so this is enough to tell the keyring needs saving.
+    pub(crate) fn revision(&self) -> (bool, usize) {
+        (self.exchange_secret.is_some(), self.keys.len())
+    }
+
+    /// Number of keys held (all scopes and epochs).
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the keyring holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the current group key, creating epoch 1 if none exists.
+    pub fn ensure_group_key(&mut self) -> &PayloadKey {
+        self.ensure_key(KeyScope::Group)
+    }
+
+    /// Returns the current key for an entity, creating epoch 1 if none exists.
+    pub fn ensure_entity_key(&mut self, entity_id: EntityId) -> &PayloadKey {
+        self.ensure_key(KeyScope::Entity(entity_id))
+    }
+
+    fn ensure_key(&mut self, scope: KeyScope) -> &PayloadKey {
+        let key_id = match self.current_key(&scope) {
+            Some(k) => k.key_id.clone(),
+            None => {
+                let key = PayloadKey::generate(scope, 1);
+                let id = key.key_id.clone();
+                self.keys.insert(id.clone(), key);
+                id
+            }
+        };
+        &self.keys[&key_id]
+    }
+
+    /// Starts a new epoch for every scope that has a key.
+    ///
+    /// Old keys are kept for decrypting events already in flight, but are
+    /// never shared again. Returns the number of scopes rotated.
+    pub fn rotate(&mut self) -> usize {
+        let mut scopes: HashMap<KeyScope, u32> = HashMap::new();
+        for key in self.keys.values() {
+            let epoch = scopes.entry(key.scope).or_default();
+            *epoch = (*epoch).max(key.epoch);
+        }
+        for (scope, epoch) in &scopes {
+            let key = PayloadKey::generate(*scope, epoch + 1);
+            self.keys.insert(key.key_id.clone(), key);
+        }
+        scopes.len()
+    }
+
+    /// Seals the current key of every scope accepted by `include` for a
+    /// recipient's exchange public key.
+    pub fn seal_current_keys(
+        &self,
+        recipient_public_key: &[u8; 32],
+        include: impl Fn(&KeyScope) -> bool,
+    ) -> SyncResult<Vec<SealedPayloadKey>> {
+        let recipient = crypto_box::PublicKey::from(*recipient_public_key);
+        let mut scopes: Vec<KeyScope> = self.keys.values().map(|k| k.scope).collect();
+        scopes.sort_by_key(|s| match s {
+            KeyScope::Group => None,
+            KeyScope::Entity(id) => Some(id.to_string()),
+        });
+        scopes.dedup();
+
+        let mut sealed = Vec::new();
+        for scope in scopes.into_iter().filter(|s| include(s)) {
+            let Some(key) = self.current_key(&scope) else { continue };
+            let envelope = seal_dek(&key.key, &recipient)
+                .map_err(|e| SyncError::Encryption(e.to_string()))?;
+            sealed.push(SealedPayloadKey {
+                key_id: key.key_id.clone(),
+                scope,
+                epoch: key.epoch,
+                envelope,
+            });
+        }
+        Ok(sealed)
+    }
+
+    /// Opens keys sealed for our exchange key and adds them to the keyring.
+    ///
+    /// Keys that fail to open or whose fingerprint does not match are
+    /// skipped. Returns the number of keys that were new.
+    pub fn import(&mut self, sealed: &[SealedPayloadKey]) -> usize {
+        let Some(secret) = self.exchange_secret else {
+            return 0;
+        };
+        let secret = crypto_box::SecretKey::from(secret);
+
+        let mut added = 0;
+        for s in sealed {
+            if self.keys.contains_key(&s.key_id) {
+                continue;
+            }
+            let Ok(bytes) = open_dek(&s.envelope, &secret) else {
+                tracing::warn!("Failed to open payload key {}", s.key_id);
+                continue;
+            };
+            let Ok(key) = <[u8; 32]>::try_from(bytes.as_slice()) else {
+                tracing::warn!("Payload key {} has invalid length", s.key_id);
+                continue;
+            };
+            let key = PayloadKey::from_bytes(s.scope, s.epoch, key);
+            if key.key_id != s.key_id {
+                tracing::warn!("Payload key fingerprint mismatch for {}", s.key_id);
+                continue;
+            }
+            self.keys.insert(key.key_id.clone(), key);
+            added += 1;
+        }
+        added
+    }
+
+    /// Encrypts an event's payload with the current key for its entity
+    /// (falling back to the group key).
+    ///
+    /// Already-encrypted events are returned unchanged. Fails if no key is
+    /// available.
+    pub fn encrypt_event(&self, event: &Event) -> SyncResult<Event> {
+        if matches!(event.payload, EventPayload::Encrypted { .. }) {
+            return Ok(event.clone());
+        }
+        let key = self
+            .current_key(&KeyScope::Entity(event.entity_id))
+            .or_else(|| self.current_key(&KeyScope::Group))
+            .ok_or_else(|| SyncError::Encryption("no payload key available".to_string()))?;
+
+        let plaintext = serde_json::to_vec(&SealedPayload {
+            event_id: event.id,
+            entity_id: event.entity_id,
+            payload: event.payload.clone(),
+        })?;
+        let encrypted = encrypt(&key.derived(), &plaintext)
+            .map_err(|e| SyncError::Encryption(e.to_string()))?;
+
+        let mut sealed = event.clone();
+        sealed.payload = EventPayload::Encrypted {
+            key_id: key.key_id.clone(),
+            ciphertext: encrypted.to_base64(),
+        };
+        Ok(sealed)
+    }
+
+    /// Decrypts an `EventPayload::Encrypted` event. Plaintext events are
+    /// returned unchanged.
+    pub fn decrypt_event(&self, event: &Event) -> SyncResult<Event> {
+        let EventPayload::Encrypted { key_id, ciphertext } = &event.payload else {
+            return Ok(event.clone());
+        };
+        let key = self
+            .key(key_id)
+            .ok_or_else(|| SyncError::Encryption(format!("unknown payload key {key_id}")))?;
+
+        let encrypted = EncryptedData::from_base64(ciphertext)
+            .map_err(|e| SyncError::Encryption(e.to_string()))?;
+        let plaintext = decrypt(&key.derived(), &encrypted)
+            .map_err(|e| SyncError::Encryption(e.to_string()))?;
+        let sealed: SealedPayload = serde_json::from_slice(&plaintext)?;
+
+        if sealed.event_id != event.id || sealed.entity_id != event.entity_id {
+            return Err(SyncError::Encryption(format!(
+                "encrypted payload does not belong to event {}",
+                event.id
+            )));
+        }
+        if matches!(sealed.payload, EventPayload::Encrypted { .. }) {
+            return Err(SyncError::Encryption("nested encrypted payload".to_string()));
+        }
+
+        let mut opened = event.clone();
+        opened.payload = sealed.payload;
+        Ok(opened)
+    }
+
+    /// Encrypts a list of events. See [`Self::encrypt_event`].
+    pub fn encrypt_events(&self, events: &[Event]) -> SyncResult<Vec<Event>> {
+        events.iter().map(|e| self.encrypt_event(e)).collect()
+    }
+
+    /// Decrypts a list of events, dropping (and logging) any that cannot be
+    /// decrypted.
+    pub fn decrypt_events(&self, events: &[Event]) -> Vec<Event> {
+        events
+            .iter()
+            .filter_map(|e| match self.decrypt_event(e) {
+                Ok(opened) => Some(opened),
+                Err(err) => {
+                    tracing::warn!("Dropping undecryptable event {}: {}", e.id, err);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Fingerprint of key material: first 16 bytes of SHA-256, hex-encoded.
+fn fingerprint(key: &[u8; 32]) -> String {
+    let digest = Sha256::digest(key);
+    hex::encode(&digest[..16])
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -5,16 +5,20 @@
 
 use crate::acl_applicator::AclEventHandler;
 use crate::applicator::EventApplicator;
+use crate::dataset_applicator::DatasetEventHandler;
+use crate::diagnostics::BatchReport;
+use crate::error::{SyncError, SyncResult};
 use crate::policy::{AllowAllPolicy, SyncPolicy};
 use crate::protocol::{
     EventAckMessage, EventBatchMessage, HelloAckMessage, HelloMessage, SyncMessage,
-    SyncRequestMessage, SyncStateMessage, MAX_BATCH_SIZE, PROTOCOL_VERSION,
+    SyncRequestMessage, SyncStateMessage, WireCapabilities, MAX_BATCH_SIZE, PROTOCOL_VERSION,
 };
-use crate::state::{PeerSyncStatus, SyncState};
-use privstack_storage::{EntityStore, EventStore};
+use crate::signing::{DeviceKeyRegistry, DeviceSigningKey};
+use crate::state::{EntitySyncState, PeerSyncStatus, SyncState};
+use privstack_storage::{ConflictRecord, EntityStore, EventStore};
 use privstack_types::{EntityId, Event, EventId, PeerId};
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
@@ -55,6 +59,16 @@ pub struct SyncEngine {
     policy: Arc<dyn SyncPolicy>,
     /// Optional ACL event handler for ACL-as-CRDT propagation.
     acl_handler: Option<Arc<dyn AclEventHandler>>,
+    /// Optional handler applying replicated dataset changes.
+    dataset_handler: Option<Arc<dyn DatasetEventHandler>>,
+    /// Device keys used to verify the author of received events.
+    key_registry: Option<Arc<DeviceKeyRegistry>>,
+    /// This device's key for signing locally authored events.
+    signing_key: Option<DeviceSigningKey>,
+    /// Conflicts opened by received events, until the orchestrator reports them.
+    detected_conflicts: Mutex<Vec<ConflictRecord>>,
+    /// Filter and apply counters, until the orchestrator records them.
+    batch_report: Mutex<BatchReport>,
 }
 
 impl SyncEngine {
@@ -77,14 +91,82 @@ impl SyncEngine {
             peer_known_ids: Arc::new(RwLock::new(HashMap::new())),
             policy,
             acl_handler: None,
+            dataset_handler: None,
+            key_registry: None,
+            signing_key: None,
+            detected_conflicts: Mutex::new(Vec::new()),
+            batch_report: Mutex::new(BatchReport::default()),
         }
     }
 
+    /// Drains the conflicts opened by events applied since the last call.
+    pub fn take_conflicts(&self) -> Vec<ConflictRecord> {
+        std::mem::take(&mut *self.detected_conflicts.lock().unwrap())
+    }
+
+    /// Drains the filter and apply counters collected since the last call.
+    pub fn take_batch_report(&self) -> BatchReport {
+        std::mem::take(&mut *self.batch_report.lock().unwrap())
+    }
+
     /// Sets the ACL event handler for ACL-as-CRDT propagation.
     pub fn set_acl_handler(&mut self, handler: Arc<dyn AclEventHandler>) {
         self.acl_handler = Some(handler);
     }
 
+    /// Sets the dataset event handler for dataset replication.
+    pub fn set_dataset_handler(&mut self, handler: Arc<dyn DatasetEventHandler>) {
+        self.dataset_handler = Some(handler);
+    }
+
+    /// Returns the dataset event handler, if one is set.
+    pub fn dataset_handler(&self) -> Option<&Arc<dyn DatasetEventHandler>> {
+        self.dataset_handler.as_ref()
+    }
+
+    /// Sets the device key registry. Received events are then dropped unless
+    /// their signature matches a device of their claimed author.
+    pub fn set_key_registry(&mut self, registry: Arc<DeviceKeyRegistry>) {
+        self.key_registry = Some(registry);
+    }
+
+    /// Returns the device key registry, if one is set.
+    pub fn key_registry(&self) -> Option<&Arc<DeviceKeyRegistry>> {
+        self.key_registry.as_ref()
+    }
+
+    /// Sets this device's signing key and registers its public half for our
+    /// own peer ID, so relays cannot forge events in our name.
+    pub fn set_signing_key(&mut self, key: DeviceSigningKey) {
+        if let Some(registry) = &self.key_registry {
+            registry.register(self.peer_id, key.public_key());
+        }
+        self.signing_key = Some(key);
+    }
+
+    /// Returns this device's signing key, if one is set.
+    pub fn signing_key(&self) -> Option<&DeviceSigningKey> {
+        self.signing_key.as_ref()
+    }
+
+    /// Signs an event we authored, if a signing key is set and the event is
+    /// not signed yet. Events of other authors are left untouched.
+    pub fn sign_local_event(&self, event: &mut Event) -> SyncResult<()> {
+        match &self.signing_key {
+            Some(key) if event.peer_id == self.peer_id && !event.is_signed() => key.sign(event),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a received event is signed by a device of its author.
+    /// Always succeeds when no key registry is set.
+    pub fn verify_authorship(&self, event: &Event) -> SyncResult<()> {
+        match &self.key_registry {
+            Some(registry) => registry.check(event),
+            None => Ok(()),
+        }
+    }
+
     /// Returns a reference to the policy.
     pub fn policy(&self) -> &Arc<dyn SyncPolicy> {
         &self.policy
@@ -216,7 +298,18 @@ impl SyncEngine {
         status.connected = true;
         self.peers.write().await.insert(hello.peer_id, status);
 
-        self.make_hello_accept()
+        // Wire format negotiation: peers without capabilities stay on JSON
+        match &hello.wire {
+            Some(caps) => {
+                let format = WireCapabilities::supported().negotiate(caps);
+                debug!("Negotiated wire format {:?} with peer {}", format, hello.peer_id);
+                SyncMessage::HelloAck(
+                    HelloAckMessage::accept(self.peer_id, &self.config.device_name)
+                        .with_wire_format(format),
+                )
+            }
+            None => self.make_hello_accept(),
+        }
     }
 
     /// Handles a SyncRequest from a remote peer.
@@ -317,10 +410,16 @@ impl SyncEngine {
 
         // Policy gate: filter outgoing events
         if let Some(pid) = peer_id {
+            let before = missing.len();
             match self.policy.on_event_send(pid, &entity_id, &missing).await {
-                Ok(filtered) => missing = filtered,
+                Ok(filtered) => {
+                    self.batch_report.lock().unwrap().filtered_out +=
+                        before.saturating_sub(filtered.len()) as u64;
+                    missing = filtered;
+                }
                 Err(e) => {
                     warn!("Policy denied event send to {:?}: {}", pid, e);
+                    self.batch_report.lock().unwrap().filtered_out += before as u64;
                     return Vec::new();
                 }
             }
@@ -361,15 +460,36 @@ impl SyncEngine {
         entity_store: &Arc<EntityStore>,
         event_store: &Arc<EventStore>,
     ) -> (SyncMessage, Vec<EntityId>) {
+        // Authorship gate: drop events not signed by a device of their author
+        let authored_events: Vec<Event> = batch
+            .events
+            .iter()
+            .filter(|event| match self.verify_authorship(event) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Dropping event {:?} relayed by {}: {}", event.id, peer_id, e);
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        self.batch_report.lock().unwrap().rejected +=
+            (batch.events.len() - authored_events.len()) as u64;
+
         // Policy gate: filter incoming events
         let allowed_events = match self
             .policy
-            .on_event_receive(peer_id, &batch.entity_id, &batch.events)
+            .on_event_receive(peer_id, &batch.entity_id, &authored_events)
             .await
         {
-            Ok(evts) => evts,
+            Ok(evts) => {
+                self.batch_report.lock().unwrap().filtered_in +=
+                    authored_events.len().saturating_sub(evts.len()) as u64;
+                evts
+            }
             Err(e) => {
                 warn!("Policy denied event batch from {}: {}", peer_id, e);
+                self.batch_report.lock().unwrap().filtered_in += authored_events.len() as u64;
                 let ack = EventAckMessage {
                     entity_id: batch.entity_id,
                     batch_seq: batch.batch_seq,
@@ -415,6 +535,11 @@ impl SyncEngine {
                     Ok(false) => {
                         // Not an ACL event, fall through to normal applicator
                     }
+                    Err(SyncError::PolicyDenied { reason }) => {
+                        // Unauthorized ACL change — drop it so it is neither applied nor stored
+                        warn!("Rejected ACL event {:?}: {}", event.id, reason);
+                        continue;
+                    }
                     Err(e) => {
                         warn!("ACL handler error for event {:?}: {}", event.id, e);
                         // Fall through to normal applicator
@@ -422,19 +547,64 @@ impl SyncEngine {
                 }
             }
 
+            // Dataset row and column changes belong to the dataset store alone
+            if let Some(dataset_handler) = &self.dataset_handler {
+                match dataset_handler.handle_dataset_event(event).await {
+                    Ok(true) => {
+                        self.state
+                            .write()
+                            .await
+                            .record_event(batch.entity_id, event);
+
+                        let evs = event_store.clone();
+                        let ev = event.clone();
+                        let save_result = tokio::task::spawn_blocking(move || {
+                            evs.save_event(&ev)
+                        })
+                        .await;
+                        if let Err(e) = save_result.unwrap_or_else(|e| {
+                            warn!("spawn_blocking panicked saving dataset event: {}", e);
+                            Ok(())
+                        }) {
+                            warn!("Failed to save dataset event to store: {}", e);
+                        }
+
+                        applied += 1;
+                        updated_entities.insert(event.entity_id);
+                        debug!("Applied dataset event {:?} to dataset {}", event.id, event.entity_id);
+                        continue;
+                    }
+                    Ok(false) => {
+                        // Not a dataset row or column event
+                    }
+                    Err(e) => {
+                        warn!("Dataset handler error for event {:?}: {}", event.id, e);
+                        self.batch_report
+                            .lock()
+                            .unwrap()
+                            .apply_errors
+                            .push((event.entity_id, e.to_string()));
+                        // The name and category still reach the entity applicator
+                    }
+                }
+            }
+
             // Run the blocking apply_event on a dedicated thread
             let es = entity_store.clone();
             let ev = event.clone();
             let app_peer = self.peer_id;
             let apply_result = tokio::task::spawn_blocking(move || {
                 let applicator = EventApplicator::new(app_peer);
-                applicator.apply_event(&ev, &es, None, None)
+                applicator.apply_event_outcome(&ev, &es, None, None)
             })
             .await;
 
             match apply_result {
-                Ok(Ok(was_applied)) => {
-                    if was_applied {
+                Ok(Ok(outcome)) => {
+                    if let Some(conflict) = outcome.conflict {
+                        self.detected_conflicts.lock().unwrap().push(conflict);
+                    }
+                    if outcome.applied {
                         self.state
                             .write()
                             .await
@@ -460,6 +630,11 @@ impl SyncEngine {
                 }
                 Ok(Err(e)) => {
                     warn!("Failed to apply event {:?}: {}", event.id, e);
+                    self.batch_report
+                        .lock()
+                        .unwrap()
+                        .apply_errors
+                        .push((event.entity_id, e.to_string()));
                 }
                 Err(e) => {
                     warn!("spawn_blocking panicked for event {:?}: {}", event.id, e);
@@ -561,6 +736,25 @@ impl SyncEngine {
         self.state.write().await.record_event(event.entity_id, event);
     }
 
+    /// Returns per-entity state changed since the last call, for the
+    /// orchestrator to persist.
+    pub async fn take_dirty_states(&self) -> Vec<(EntityId, EntitySyncState)> {
+        self.state.write().await.take_dirty()
+    }
+
+    /// Restores per-entity state persisted by an earlier run.
+    pub async fn restore_states(&self, states: Vec<(EntityId, EntitySyncState)>) {
+        let mut state = self.state.write().await;
+        for (entity_id, entity_state) in states {
+            state.restore_entity(entity_id, entity_state);
+        }
+    }
+
+    /// Current sync state for an entity.
+    pub async fn entity_state(&self, entity_id: &EntityId) -> Option<EntitySyncState> {
+        self.state.read().await.get_entity(entity_id).cloned()
+    }
+
     /// Builds the set of known event IDs for an entity from the event store.
     pub async fn known_event_ids_from_store(
         &self,
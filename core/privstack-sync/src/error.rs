@@ -44,6 +44,10 @@ pub enum SyncError {
     #[error("channel closed")]
     ChannelClosed,
 
+    /// End-to-end payload encryption or decryption failed.
+    #[error("encryption error: {0}")]
+    Encryption(String),
+
     /// Policy denied the operation.
     #[error("policy denied: {reason}")]
     PolicyDenied { reason: String },
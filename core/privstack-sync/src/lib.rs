@@ -21,7 +21,7 @@
 //! ## Sync Process
 //!
 //! 1. **Discovery**: Find other peers (mDNS for LAN, DHT for WAN)
-//! 2. **Handshake**: Exchange peer info and protocol version
+//! 2. **Handshake**: Exchange peer info, protocol version and wire format
 //! 3. **State Exchange**: Share vector clocks to determine what's missing
 //! 4. **Event Sync**: Send missing events in batches
 //! 5. **Apply**: Apply received events using CRDT merge
@@ -43,7 +43,12 @@
 
 pub mod acl_applicator;
 pub mod applicator;
+pub mod audit;
 pub mod cloud;
+pub mod conflicts;
+pub mod dataset_applicator;
+pub mod diagnostics;
+pub mod e2e;
 mod engine;
 mod error;
 mod orchestrator;
@@ -52,28 +57,46 @@ pub mod pairing;
 pub mod policy;
 pub mod policy_store;
 pub mod protocol;
+pub mod signing;
 pub mod state;
 pub mod transport;
 
 pub use acl_applicator::{AclApplicator, AclEventHandler};
-pub use applicator::{create_event, ApplicatorError, ApplicatorResult, EventApplicator};
+pub use audit::{
+    AuditCheckpoint, AuditExportFormat, AuditFilter, AuditIssue, AuditRecord, AuditRetention,
+    AuditVerification,
+};
+pub use applicator::{
+    create_event, ApplicatorError, ApplicatorResult, ApplyOutcome, EventApplicator,
+};
+pub use dataset_applicator::{DatasetApplicator, DatasetEventHandler};
+pub use conflicts::{
+    diff_conflict, resolve_conflict, stamp_local_event, ConflictResolution, FieldDiff,
+};
 pub use orchestrator::{
     create_enterprise_orchestrator, create_orchestrator, create_orchestrator_with_pairing,
-    create_orchestrator_with_policy, create_personal_orchestrator, OrchestratorConfig,
-    OrchestratorHandle, SyncCommand, SyncEvent, SyncOrchestrator,
+    create_orchestrator_with_policy, create_personal_orchestrator, create_selective_orchestrator,
+    OrchestratorConfig, OrchestratorHandle, SyncCommand, SyncEvent, SyncOrchestrator,
 };
 
+pub use diagnostics::{
+    export_diagnostics, summarize_peers, DiagnosticsBundle, PeerSyncMetrics, SessionDirection,
+    SessionOutcome, SyncPhase, SyncSessionRecord, TransportKind,
+};
+pub use e2e::{KeyScope, PayloadKey, PayloadKeyring, SealedPayloadKey};
 pub use engine::{SyncConfig, SyncEngine};
 pub use error::{SyncError, SyncResult};
 pub use policy::{
     AllowAllPolicy, AuditAction, AuditDecision, AuditEntry, DeviceId, EntityAcl,
-    EnterpriseSyncPolicy, PersonalSyncPolicy, SyncPolicy, SyncRole, TeamId,
+    EnterpriseSyncPolicy, GrantLimits, GrantState, LapseReason, LapsedGrant, PersonalSyncPolicy,
+    ScopeSubject, SelectiveSyncPolicy, SyncPolicy, SyncRole, SyncScope, TeamId,
 };
 pub use policy_store::PolicyStore;
+pub use signing::{verify_message, verify_signature, Authorship, DeviceKeyRegistry, DeviceSigningKey};
 pub use protocol::{
     ErrorMessage, EventAckMessage, EventBatchMessage, EventNotifyMessage, HelloAckMessage,
-    HelloMessage, SubscribeMessage, SyncMessage, SyncRequestMessage, SyncStateMessage,
-    MAX_BATCH_SIZE, PROTOCOL_VERSION,
+    HelloMessage, KeyShareMessage, SubscribeMessage, SyncMessage, SyncRequestMessage, SyncStateMessage,
+    WireCapabilities, WireCompression, WireEncoding, WireFormat, MAX_BATCH_SIZE, PROTOCOL_VERSION,
 };
 pub use state::{EntitySyncState, PeerSyncStatus, SyncState};
 pub use transport::{
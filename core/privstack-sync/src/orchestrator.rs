@@ -7,19 +7,27 @@
 //!
 //! It owns all I/O. The engine is a pure state machine.
 
+use crate::applicator::now_millis;
+use crate::diagnostics::{
+    SessionDirection, SessionRecorder, SyncPhase, SyncSessionRecord, TransportKind,
+    DEFAULT_SESSION_HISTORY,
+};
+use crate::e2e::{KeyScope, PayloadKeyring};
 use crate::engine::SyncEngine;
 use crate::pairing::PairingManager;
-use crate::policy::{PersonalSyncPolicy, SyncPolicy};
+use crate::policy::{
+    EnterpriseSyncPolicy, PersonalSyncPolicy, SelectiveSyncPolicy, SyncPolicy, SyncScope,
+};
 use crate::protocol::{
-    ErrorMessage, SyncMessage, SyncStateMessage, PROTOCOL_VERSION,
+    ErrorMessage, KeyShareMessage, SyncMessage, SyncStateMessage, PROTOCOL_VERSION,
 };
 use crate::transport::SyncTransport;
 use crate::{SyncConfig, SyncError, SyncResult};
-use privstack_storage::{EntityStore, EventStore};
-use privstack_types::{EntityId, Event, EventId, PeerId};
-use std::collections::HashSet;
+use privstack_storage::{EntityStore, EventStore, SyncProgressRow};
+use privstack_types::{EntityId, Event, EventId, HybridTimestamp, PeerId};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 type TokioMutex<T> = tokio::sync::Mutex<T>;
 use tracing::{debug, error, info, warn};
@@ -32,11 +40,17 @@ pub enum SyncCommand {
     /// Sync a specific entity with all connected peers.
     SyncEntity { entity_id: EntityId },
     /// Record a local event (from user edit).
-    RecordLocalEvent { event: Event },
+    RecordLocalEvent { event: Box<Event> },
     /// Add an entity to the list of shared entities.
     ShareEntity { entity_id: EntityId },
     /// Share an entity with a specific peer (personal policy).
     ShareEntityWithPeer { entity_id: EntityId, peer_id: PeerId },
+    /// Fetch an entity outside this device's sync scope from peers and keep
+    /// it in sync from then on (selective policy).
+    FetchEntity { entity_id: EntityId },
+    /// This device's sync scope widened (selective policy): re-sync with
+    /// known peers so they learn the new scope and resend what it admits.
+    ScopeWidened,
     /// Stop the orchestrator.
     Shutdown,
 }
@@ -59,8 +73,23 @@ pub enum SyncEvent {
     },
     /// Sync failed with a peer.
     SyncFailed { peer_id: PeerId, error: String },
+    /// Progress of a large sync run with a peer, which may span several
+    /// cycles and restarts.
+    SyncProgress {
+        peer_id: PeerId,
+        entities_done: usize,
+        entities_total: usize,
+        /// Estimated seconds of sync time left, once there is a rate to go by.
+        eta_secs: Option<u64>,
+    },
     /// An entity was updated from sync.
     EntityUpdated { entity_id: EntityId },
+    /// A received edit was concurrent with local changes. The automatic
+    /// merge was applied; the conflict record keeps the other side.
+    ConflictDetected {
+        entity_id: EntityId,
+        conflict_id: String,
+    },
 }
 
 /// Configuration for the sync orchestrator.
@@ -103,7 +132,7 @@ impl OrchestratorHandle {
     /// Records a local event (call this when user makes an edit).
     pub async fn record_event(&self, event: Event) -> SyncResult<()> {
         self.command_tx
-            .send(SyncCommand::RecordLocalEvent { event })
+            .send(SyncCommand::RecordLocalEvent { event: Box::new(event) })
             .await
             .map_err(|_| SyncError::ChannelClosed)
     }
@@ -124,6 +153,24 @@ impl OrchestratorHandle {
             .map_err(|_| SyncError::ChannelClosed)
     }
 
+    /// Fetches an out-of-scope entity on demand, e.g. when the user opens
+    /// a link to it.
+    pub async fn fetch_entity(&self, entity_id: EntityId) -> SyncResult<()> {
+        self.command_tx
+            .send(SyncCommand::FetchEntity { entity_id })
+            .await
+            .map_err(|_| SyncError::ChannelClosed)
+    }
+
+    /// Tells the orchestrator this device's sync scope widened, e.g. when
+    /// `SelectiveSyncPolicy::set_scope` returns true.
+    pub async fn scope_widened(&self) -> SyncResult<()> {
+        self.command_tx
+            .send(SyncCommand::ScopeWidened)
+            .await
+            .map_err(|_| SyncError::ChannelClosed)
+    }
+
     /// Shares an entity for sync.
     pub async fn share_entity(&self, entity_id: EntityId) -> SyncResult<()> {
         self.command_tx
@@ -161,9 +208,68 @@ pub struct SyncOrchestrator {
     pairing_manager: Option<Arc<std::sync::Mutex<PairingManager>>>,
     /// Optional personal sync policy for per-peer entity sharing.
     personal_policy: Option<Arc<PersonalSyncPolicy>>,
+    /// Optional selective sync policy limiting what this device syncs.
+    selective_policy: Option<Arc<SelectiveSyncPolicy>>,
+    /// Optional enterprise policy, swept for lapsed grants.
+    enterprise_policy: Option<Arc<EnterpriseSyncPolicy>>,
+    /// Peers we exchanged payload keys with, and the current keys we last
+    /// shared with each; events to them are encrypted.
+    e2e_peers: HashMap<PeerId, Vec<String>>,
+    /// Sessions peers opened with us, recorded until they go idle.
+    inbound_sessions: HashMap<PeerId, SessionRecorder>,
+    /// Number of session records kept in the diagnostics history.
+    session_history_capacity: usize,
+}
+
+/// An inbound session is recorded once the peer has been quiet this long.
+const INBOUND_SESSION_IDLE: Duration = Duration::from_secs(10);
+
+/// Entities exchanged between checkpoints of the ledger and sync state.
+/// An interrupted session resumes from the last checkpoint.
+const CHECKPOINT_ENTITIES: usize = 25;
+
+/// Sync runs with fewer entities than this do not report progress.
+const PROGRESS_MIN_ENTITIES: u64 = 50;
+
+/// A sync run with one peer: the entities that needed syncing when it
+/// started, worked off across cycles. Persisted so a restart resumes it.
+struct SyncRun {
+    row: SyncProgressRow,
+    last_checkpoint: Instant,
+}
+
+impl SyncRun {
+    fn is_reported(&self) -> bool {
+        self.row.entities_total >= PROGRESS_MIN_ENTITIES
+    }
+
+    fn eta_secs(&self) -> Option<u64> {
+        let row = &self.row;
+        if row.entities_done == 0 || row.active_ms == 0 {
+            return None;
+        }
+        let left = row.entities_total.saturating_sub(row.entities_done);
+        Some(left * row.active_ms / row.entities_done / 1000)
+    }
 }
 
 impl SyncOrchestrator {
+    /// Sets this device's event signing key. Events we author are signed
+    /// with it and its public half is advertised in the handshake.
+    pub fn set_signing_key(&mut self, key: crate::signing::DeviceSigningKey) {
+        self.engine.set_signing_key(key);
+    }
+
+    /// Sets the handler that applies replicated dataset changes.
+    pub fn set_dataset_handler(&mut self, handler: Arc<dyn crate::dataset_applicator::DatasetEventHandler>) {
+        self.engine.set_dataset_handler(handler);
+    }
+
+    /// Sets how many sync session records the diagnostics history keeps.
+    pub fn set_session_history_capacity(&mut self, capacity: usize) {
+        self.session_history_capacity = capacity;
+    }
+
     /// Runs the orchestrator event loop with a transport.
     pub async fn run(
         mut self,
@@ -180,6 +286,7 @@ impl SyncOrchestrator {
             let entity_store = self.entity_store.clone();
             let event_store = self.event_store.clone();
             let peer_id = self.engine.peer_id();
+            let signing_key = self.engine.signing_key().cloned();
             match tokio::task::spawn_blocking(move || {
                 let ids = entity_store.list_all_entity_ids()?;
                 let mut needs_snapshot: Vec<(EntityId, String, String)> = Vec::new();
@@ -199,7 +306,12 @@ impl SyncOrchestrator {
                 // Create snapshot events for entities without events
                 let snapshot_count = needs_snapshot.len();
                 for (eid, etype, data) in needs_snapshot {
-                    let event = Event::full_snapshot(eid, peer_id, &etype, &data);
+                    let mut event = Event::full_snapshot(eid, peer_id, &etype, &data);
+                    if let Some(key) = &signing_key {
+                        if let Err(e) = key.sign(&mut event) {
+                            eprintln!("[SYNC] Failed to sign snapshot event for {}: {}", eid, e);
+                        }
+                    }
                     if let Err(e) = event_store.save_event(&event) {
                         eprintln!("[SYNC] Failed to create snapshot event for {}: {}", eid, e);
                     }
@@ -227,6 +339,8 @@ impl SyncOrchestrator {
             }
         }
 
+        self.restore_sync_state().await;
+
         info!("[SYNC] Orchestrator started for peer {}", self.engine.peer_id());
 
         loop {
@@ -236,10 +350,12 @@ impl SyncOrchestrator {
                     match cmd {
                         SyncCommand::Shutdown => {
                             info!("[SYNC] Orchestrator shutting down");
+                            self.flush_inbound_sessions(Duration::ZERO).await;
+                            self.checkpoint_sync_state().await;
                             break;
                         }
                         SyncCommand::RecordLocalEvent { event } => {
-                            self.handle_local_event(event).await;
+                            self.handle_local_event(*event).await;
                         }
                         SyncCommand::ShareEntity { entity_id } => {
                             self.shared_entities.insert(entity_id);
@@ -247,6 +363,11 @@ impl SyncOrchestrator {
                         }
                         SyncCommand::ShareEntityWithPeer { entity_id, peer_id } => {
                             self.shared_entities.insert(entity_id);
+                            if let Some(pm) = &self.pairing_manager {
+                                pm.lock().unwrap().update_keyring(|k| {
+                                    k.ensure_entity_key(entity_id);
+                                });
+                            }
                             if let Some(policy) = &self.personal_policy {
                                 policy.share(entity_id, peer_id).await;
                                 info!("[SYNC] Shared entity {} with peer {}", entity_id, peer_id);
@@ -254,6 +375,14 @@ impl SyncOrchestrator {
                                 info!("[SYNC] ShareEntityWithPeer ignored — no personal policy");
                             }
                         }
+                        SyncCommand::FetchEntity { entity_id } => {
+                            info!("[SYNC] FetchEntity command for {}", entity_id);
+                            self.fetch_entity(&transport, entity_id).await;
+                        }
+                        SyncCommand::ScopeWidened => {
+                            info!("[SYNC] ScopeWidened command");
+                            self.resync_widened_scope(&transport).await;
+                        }
                         SyncCommand::SyncEntity { entity_id } => {
                             info!("[SYNC] SyncEntity command for {}", entity_id);
                             self.sync_entity_to_all(&transport, entity_id).await;
@@ -277,11 +406,16 @@ impl SyncOrchestrator {
                 _ = discovery_interval.tick() => {
                     debug!("[SYNC] Discovery interval tick");
                     self.check_for_new_peers(&transport).await;
+                    self.flush_inbound_sessions(INBOUND_SESSION_IDLE).await;
                 }
 
                 _ = sync_interval.tick() => {
                     debug!("[SYNC] Sync interval tick");
+                    self.sweep_lapsed_grants().await;
+                    self.refresh_device_keys();
+                    self.rekey_peers(&transport).await;
                     self.periodic_sync(&transport).await;
+                    self.checkpoint_sync_state().await;
                 }
             }
         }
@@ -289,13 +423,243 @@ impl SyncOrchestrator {
         Ok(())
     }
 
-    async fn handle_local_event(&self, event: Event) {
+    /// Ends an outbound session that failed: tells the UI and records it.
+    async fn fail_session(&self, peer_id: PeerId, mut rec: SessionRecorder, error: String) {
+        rec.fail(error.clone());
+        let _ = self.event_tx.send(SyncEvent::SyncFailed { peer_id, error }).await;
+        self.store_session(rec.finish()).await;
+    }
+
+    /// Persists a finished session in the diagnostics history.
+    async fn store_session(&self, record: SyncSessionRecord) {
+        let store = self.entity_store.clone();
+        let capacity = self.session_history_capacity;
+        match tokio::task::spawn_blocking(move || {
+            crate::diagnostics::save_session(&store, &record, capacity)
+        }).await {
+            Ok(Err(e)) => warn!("[SYNC] Failed to record sync session: {}", e),
+            Err(e) => warn!("[SYNC] spawn_blocking panicked recording sync session: {}", e),
+            _ => {}
+        }
+    }
+
+    /// Loads the per-entity sync state checkpointed by an earlier run.
+    async fn restore_sync_state(&self) {
+        let store = self.entity_store.clone();
+        let rows = match tokio::task::spawn_blocking(move || store.load_sync_entity_states()).await {
+            Ok(Ok(rows)) => rows,
+            Ok(Err(e)) => {
+                warn!("[SYNC] Failed to load sync state checkpoint: {}", e);
+                return;
+            }
+            Err(e) => {
+                warn!("[SYNC] spawn_blocking panicked loading sync state: {}", e);
+                return;
+            }
+        };
+        let states: Vec<_> = rows
+            .iter()
+            .filter_map(|(id, json)| Some((id.parse::<EntityId>().ok()?, serde_json::from_str(json).ok()?)))
+            .collect();
+        if !states.is_empty() {
+            info!("[SYNC] Restored sync state for {} entities", states.len());
+            self.engine.restore_states(states).await;
+        }
+    }
+
+    /// Persists the per-entity sync state that changed since the last call.
+    async fn checkpoint_sync_state(&self) {
+        let dirty = self.engine.take_dirty_states().await;
+        if dirty.is_empty() {
+            return;
+        }
+        let rows: Vec<(String, String)> = dirty
+            .iter()
+            .filter_map(|(id, state)| Some((id.to_string(), serde_json::to_string(state).ok()?)))
+            .collect();
+        let store = self.entity_store.clone();
+        match tokio::task::spawn_blocking(move || store.save_sync_entity_states(&rows)).await {
+            Ok(Ok(())) => debug!("[SYNC] Checkpointed sync state for {} entities", dirty.len()),
+            Ok(Err(e)) => warn!("[SYNC] Failed to checkpoint sync state: {}", e),
+            Err(e) => warn!("[SYNC] spawn_blocking panicked checkpointing sync state: {}", e),
+        }
+    }
+
+    /// Starts or resumes the sync run with a peer given the entities that
+    /// still need syncing. Returns `None`, and forgets any stored run, when
+    /// there is nothing left.
+    async fn begin_sync_run(&self, peer_id_str: &str, pending: usize) -> Option<SyncRun> {
+        let store = self.entity_store.clone();
+        let pid = peer_id_str.to_string();
+        let now_ms = now_millis();
+        let result = tokio::task::spawn_blocking(move || {
+            if pending == 0 {
+                store.clear_sync_progress(&pid)?;
+                return Ok(None);
+            }
+            let mut row = store.get_sync_progress(&pid)?.unwrap_or(SyncProgressRow {
+                peer_id: pid,
+                entities_done: 0,
+                entities_total: 0,
+                active_ms: 0,
+                started_at: now_ms,
+                updated_at: now_ms,
+            });
+            // Entities may have changed since the last cycle; what is left
+            // now is the truth.
+            row.entities_total = row.entities_done + pending as u64;
+            row.updated_at = now_ms;
+            store.save_sync_progress(&row)?;
+            Ok::<_, privstack_storage::StorageError>(Some(row))
+        })
+        .await;
+        match result {
+            Ok(Ok(row)) => row.map(|row| SyncRun { row, last_checkpoint: Instant::now() }),
+            Ok(Err(e)) => {
+                warn!("[SYNC] Failed to load sync progress: {}", e);
+                None
+            }
+            Err(e) => {
+                warn!("[SYNC] spawn_blocking panicked loading sync progress: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Marks the entities exchanged since the last checkpoint as synced,
+    /// persists the sync state and advances the run's progress.
+    async fn checkpoint_session(
+        &self,
+        peer_id: PeerId,
+        synced: &mut Vec<String>,
+        synced_at_ms: i64,
+        run: Option<&mut SyncRun>,
+    ) {
+        let newly_synced = synced.len();
+        if newly_synced > 0 {
+            let store = self.entity_store.clone();
+            let pid = peer_id.to_string();
+            let ids = std::mem::take(synced);
+            match tokio::task::spawn_blocking(move || store.mark_entities_synced(&pid, &ids, synced_at_ms)).await {
+                Ok(Ok(())) => debug!("[SYNC] Updated sync ledger: {} entities marked synced with {}", newly_synced, peer_id),
+                Ok(Err(e)) => warn!("[SYNC] Failed to update sync ledger: {}", e),
+                Err(e) => warn!("[SYNC] spawn_blocking panicked updating sync ledger: {}", e),
+            }
+        }
+        self.checkpoint_sync_state().await;
+
+        let Some(run) = run else { return };
+        run.row.entities_done = (run.row.entities_done + newly_synced as u64).min(run.row.entities_total);
+        run.row.active_ms += run.last_checkpoint.elapsed().as_millis() as u64;
+        run.row.updated_at = now_millis();
+        run.last_checkpoint = Instant::now();
+
+        let store = self.entity_store.clone();
+        let row = run.row.clone();
+        let done = row.entities_done >= row.entities_total;
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(move || {
+            if done {
+                store.clear_sync_progress(&row.peer_id)
+            } else {
+                store.save_sync_progress(&row)
+            }
+        }).await {
+            warn!("[SYNC] Failed to save sync progress: {}", e);
+        }
+
+        if run.is_reported() {
+            let _ = self.event_tx.send(SyncEvent::SyncProgress {
+                peer_id,
+                entities_done: run.row.entities_done as usize,
+                entities_total: run.row.entities_total as usize,
+                eta_secs: run.eta_secs(),
+            }).await;
+        }
+    }
+
+    /// Records inbound sessions that have been idle for at least `idle`.
+    async fn flush_inbound_sessions(&mut self, idle: Duration) {
+        let done: Vec<PeerId> = self
+            .inbound_sessions
+            .iter()
+            .filter(|(_, rec)| rec.idle_for() >= idle)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in done {
+            if let Some(rec) = self.inbound_sessions.remove(&peer) {
+                self.store_session(rec.finish()).await;
+            }
+        }
+    }
+
+    /// How a peer is reached, from the transport's discovery table.
+    async fn peer_route(
+        &self,
+        transport: &Arc<TokioMutex<dyn SyncTransport>>,
+        peer_id: &PeerId,
+    ) -> (TransportKind, Option<String>) {
+        let discovered = {
+            let tg = transport.lock().await;
+            tg.discovered_peers_async().await
+        };
+        discovered
+            .iter()
+            .find(|p| p.peer_id == *peer_id)
+            .map(|p| (TransportKind::of_peer(p), p.device_name.clone()))
+            .unwrap_or((TransportKind::Unknown, None))
+    }
+
+    /// Revokes limited grants and shares that have lapsed. Enterprise
+    /// revocations are recorded as local ACL events so peers revoke too.
+    async fn sweep_lapsed_grants(&self) {
+        if let Some(policy) = &self.personal_policy {
+            for share in policy.sweep_lapsed_shares().await {
+                info!(
+                    "[SYNC] Share of entity {} with peer {} lapsed ({})",
+                    share.entity, share.peer, share.reason
+                );
+            }
+        }
+        if let Some(policy) = &self.enterprise_policy {
+            let peer_id = self.engine.peer_id();
+            for grant in policy.sweep_lapsed_grants().await {
+                // Every replica stops honouring the grant, but only one that
+                // may revoke it propagates the revocation; peers would reject
+                // a revoke from anyone else.
+                if policy.resolve_role(&peer_id, &grant.entity).await < Some(grant.required_role()) {
+                    debug!(
+                        "[SYNC] Grant on entity {} to peer {} lapsed ({}); not authorized to propagate the revoke",
+                        grant.entity, grant.peer, grant.reason
+                    );
+                    continue;
+                }
+                info!(
+                    "[SYNC] Grant on entity {} to peer {} lapsed ({}), revoking",
+                    grant.entity, grant.peer, grant.reason
+                );
+                let event = Event::new(grant.entity, peer_id, HybridTimestamp::now(), grant.revoke_payload());
+                self.handle_local_event(event).await;
+            }
+        }
+    }
+
+    async fn handle_local_event(&self, mut event: Event) {
+        // Sign events we authored that the caller did not sign already.
+        if let Err(e) = self.engine.sign_local_event(&mut event) {
+            warn!("[SYNC] Failed to sign local event {:?}: {}", event.id, e);
+        }
+
         // Save to event store on a blocking thread.
         // Note: the FFI layer also saves directly for immediacy; the duplicate
-        // INSERT OR IGNORE here is harmless.
+        // INSERT OR IGNORE here is harmless. A copy saved before signing gets
+        // the signature attached so peers receive the signed event.
         let store = self.event_store.clone();
         let ev = event.clone();
-        let save_result = tokio::task::spawn_blocking(move || store.save_event(&ev)).await;
+        let save_result = tokio::task::spawn_blocking(move || {
+            store.save_event(&ev)?;
+            store.attach_signature(&ev)
+        })
+        .await;
         match save_result {
             Ok(Ok(())) => {}
             Ok(Err(e)) => {
@@ -356,6 +720,338 @@ impl SyncOrchestrator {
         }
     }
 
+    /// Adds our E2E exchange key to an outgoing Hello or accepting HelloAck.
+    /// Payload encryption needs the keyring held by the pairing manager, so
+    /// without one the message is returned unchanged.
+    fn with_local_e2e_key(&self, message: SyncMessage) -> SyncMessage {
+        let Some(pm) = &self.pairing_manager else {
+            return message;
+        };
+        let key = pm.lock().unwrap().update_keyring(|keyring| {
+            keyring.ensure_group_key();
+            keyring.exchange_public_key()
+        });
+        match message {
+            SyncMessage::Hello(hello) => SyncMessage::Hello(hello.with_e2e_public_key(key)),
+            SyncMessage::HelloAck(ack) if ack.accepted => {
+                SyncMessage::HelloAck(ack.with_e2e_public_key(key))
+            }
+            other => other,
+        }
+    }
+
+    /// Adds our event signing key to an outgoing Hello or accepting HelloAck.
+    /// Without a signing key the message is returned unchanged.
+    fn with_local_signing_key(&self, message: SyncMessage) -> SyncMessage {
+        let Some(key) = self.engine.signing_key().map(|k| k.public_key()) else {
+            return message;
+        };
+        match message {
+            SyncMessage::Hello(hello) => SyncMessage::Hello(hello.with_signing_public_key(key)),
+            SyncMessage::HelloAck(ack) if ack.accepted => {
+                SyncMessage::HelloAck(ack.with_signing_public_key(key))
+            }
+            other => other,
+        }
+    }
+
+    /// Records the event signing key a peer advertised. The key is pinned on
+    /// the trusted peer record and becomes the only key registered for
+    /// verifying its events; a key that differs from the pinned one is
+    /// refused until the peer is approved again.
+    fn record_peer_signing_key(&self, peer_id: &PeerId, key: Option<[u8; 32]>) {
+        let (Some(key), Some(pm), Some(registry)) =
+            (key, &self.pairing_manager, self.engine.key_registry())
+        else {
+            return;
+        };
+        let pinned = pm.lock().unwrap().set_peer_signing_key(&peer_id.to_string(), key);
+        if pinned {
+            registry.remove_peer(peer_id);
+            registry.register(*peer_id, key);
+        } else {
+            warn!("[SYNC] Ignoring signing key from peer {}: not trusted or differs from pinned key", peer_id);
+        }
+    }
+
+    /// Adds our sync scope to an outgoing Hello or accepting HelloAck so
+    /// the peer only sends what this device keeps. Without a selective
+    /// policy the message is returned unchanged (everything is in scope).
+    async fn with_local_scope(&self, message: SyncMessage) -> SyncMessage {
+        let Some(policy) = &self.selective_policy else {
+            return message;
+        };
+        let scope = policy.scope().await;
+        match message {
+            SyncMessage::Hello(hello) => SyncMessage::Hello(hello.with_sync_scope(scope)),
+            SyncMessage::HelloAck(ack) if ack.accepted => {
+                SyncMessage::HelloAck(ack.with_sync_scope(scope))
+            }
+            other => other,
+        }
+    }
+
+    /// Records the sync scope a peer advertised. When its filters changed,
+    /// the peer's sync ledger is cleared so entities that were skipped as
+    /// out of scope are re-checked on the next cycle.
+    async fn record_peer_scope(&self, peer_id: &PeerId, scope: Option<SyncScope>) {
+        let Some(policy) = &self.selective_policy else {
+            return;
+        };
+        let filters = |scope: &Option<SyncScope>| {
+            scope.clone().map(|mut s| {
+                s.pinned.clear();
+                s
+            })
+        };
+        let previous = policy.peer_scope(peer_id).await;
+        let changed = filters(&previous) != filters(&scope);
+        policy.set_peer_scope(*peer_id, scope).await;
+        if changed {
+            let store = self.entity_store.clone();
+            let pid = peer_id.to_string();
+            match tokio::task::spawn_blocking(move || store.clear_sync_ledger_for_peer(&pid)).await {
+                Ok(Ok(())) => debug!("[SYNC] Sync scope of peer {} changed; ledger cleared", peer_id),
+                Ok(Err(e)) => warn!("[SYNC] Failed to clear sync ledger for peer {}: {}", peer_id, e),
+                Err(e) => warn!("[SYNC] spawn_blocking panicked clearing sync ledger: {}", e),
+            }
+        }
+    }
+
+    /// Records the E2E exchange key a peer advertised. Only trusted peers
+    /// take part in key exchange, and the first key seen is pinned. Returns
+    /// whether the key was recorded.
+    fn record_peer_e2e_key(&self, peer_id: &PeerId, key: [u8; 32]) -> bool {
+        let Some(pm) = &self.pairing_manager else {
+            return false;
+        };
+        let recorded = pm.lock().unwrap().set_peer_e2e_key(&peer_id.to_string(), key);
+        if !recorded {
+            warn!("[SYNC] Ignoring exchange key from peer {}: not trusted or differs from pinned key", peer_id);
+        }
+        recorded
+    }
+
+    /// Whether a peer must encrypt what it sends us, and be sent only
+    /// encrypted payloads: true once its exchange key is pinned.
+    fn peer_requires_e2e(&self, peer_id: &PeerId) -> bool {
+        self.pairing_manager
+            .as_ref()
+            .is_some_and(|pm| pm.lock().unwrap().requires_e2e(&peer_id.to_string()))
+    }
+
+    /// Fingerprints of the current payload keys, to tell when a peer needs
+    /// them shared again.
+    fn current_key_ids(&self) -> Vec<String> {
+        self.pairing_manager
+            .as_ref()
+            .map(|pm| pm.lock().unwrap().keyring().current_key_ids())
+            .unwrap_or_default()
+    }
+
+    /// Seals our current payload keys for a peer: the group key, plus the
+    /// keys of entities this peer may sync.
+    async fn make_key_share(&self, peer_id: &PeerId) -> SyncResult<KeyShareMessage> {
+        let shared: Option<HashSet<EntityId>> = match &self.personal_policy {
+            Some(policy) if policy.has_selective_sharing().await => {
+                Some(policy.shared_entities(peer_id).await.into_iter().collect())
+            }
+            _ => None,
+        };
+
+        let pm = self
+            .pairing_manager
+            .as_ref()
+            .ok_or_else(|| SyncError::Encryption("no payload keyring".to_string()))?;
+        let pm = pm.lock().unwrap();
+        let recipient = pm
+            .get_trusted_peer(&peer_id.to_string())
+            .and_then(|p| p.e2e_public_key)
+            .ok_or_else(|| SyncError::Encryption(format!("no exchange key for peer {peer_id}")))?;
+        let keys = pm.keyring().seal_current_keys(&recipient, |scope| match (scope, &shared) {
+            (KeyScope::Entity(id), Some(shared)) => shared.contains(id),
+            _ => true,
+        })?;
+        Ok(KeyShareMessage { keys })
+    }
+
+    /// Swaps payload keys with a peer after the handshake. On success, events
+    /// to this peer are encrypted; otherwise they are sent as before.
+    async fn exchange_payload_keys(
+        &mut self,
+        transport: &Arc<TokioMutex<dyn SyncTransport>>,
+        peer_id: PeerId,
+        peer_e2e_key: Option<[u8; 32]>,
+        rec: &mut SessionRecorder,
+    ) {
+        self.e2e_peers.remove(&peer_id);
+        let Some(key) = peer_e2e_key else {
+            debug!("[SYNC] Peer {} does not support payload encryption", peer_id);
+            return;
+        };
+        if !self.record_peer_e2e_key(&peer_id, key) {
+            return;
+        }
+
+        let share = match self.make_key_share(&peer_id).await {
+            Ok(share) => share,
+            Err(e) => {
+                warn!("[SYNC] Failed to seal payload keys for peer {}: {}", peer_id, e);
+                return;
+            }
+        };
+        let request = SyncMessage::KeyShare(share);
+        rec.message_sent(&request, 0);
+        let response = {
+            let tg = transport.lock().await;
+            tg.send_request(&peer_id, request).await
+        };
+        if let Ok(msg) = &response {
+            rec.message_received(msg, 0);
+        }
+
+        match response {
+            Ok(SyncMessage::KeyShare(theirs)) => {
+                self.import_payload_keys(&peer_id, &theirs);
+                self.e2e_peers.insert(peer_id, self.current_key_ids());
+                info!("[SYNC] Payload encryption enabled with peer {}", peer_id);
+            }
+            Ok(other) => warn!("[SYNC] Unexpected response to KeyShare: {:?}", other),
+            Err(e) => warn!("[SYNC] Failed to exchange payload keys with peer {}: {}", peer_id, e),
+        }
+    }
+
+    /// Handles an incoming KeyShare: imports the peer's keys and answers
+    /// with ours.
+    async fn handle_key_share(&mut self, peer_id: &PeerId, share: &KeyShareMessage) -> SyncMessage {
+        match self.make_key_share(peer_id).await {
+            Ok(ours) => {
+                self.import_payload_keys(peer_id, share);
+                self.e2e_peers.insert(*peer_id, self.current_key_ids());
+                SyncMessage::KeyShare(ours)
+            }
+            Err(e) => {
+                warn!("[SYNC] Rejecting KeyShare from peer {}: {}", peer_id, e);
+                SyncMessage::Error(ErrorMessage::new(403, e.to_string()))
+            }
+        }
+    }
+
+    fn import_payload_keys(&self, peer_id: &PeerId, share: &KeyShareMessage) {
+        if let Some(pm) = &self.pairing_manager {
+            let added = pm.lock().unwrap().update_keyring(|k| k.import(&share.keys));
+            debug!("[SYNC] Imported {} new payload keys from peer {}", added, peer_id);
+        }
+    }
+
+    /// Brings the device key registry in line with the pinned signing keys:
+    /// peers no longer trusted lose their keys, and a re-paired peer's old
+    /// key is replaced by the newly pinned one.
+    fn refresh_device_keys(&self) {
+        let (Some(pm), Some(registry)) = (&self.pairing_manager, self.engine.key_registry()) else {
+            return;
+        };
+        let pm = pm.lock().unwrap();
+        for peer_id in registry.peers() {
+            match pm.get_trusted_peer(&peer_id.to_string()) {
+                None => registry.remove_peer(&peer_id),
+                Some(peer) => {
+                    if let Some(pin) = peer.signing_public_key {
+                        if registry.keys_for(&peer_id) != [pin] {
+                            registry.remove_peer(&peer_id);
+                            registry.register(peer_id, pin);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Shares the payload keys again with peers that have not seen the
+    /// current ones, e.g. after a rotation for a removed peer, so they stop
+    /// encrypting with keys the removed peer holds. Peers no longer trusted
+    /// are dropped from sync.
+    async fn rekey_peers(&mut self, transport: &Arc<TokioMutex<dyn SyncTransport>>) {
+        let Some(pm) = self.pairing_manager.clone() else {
+            return;
+        };
+        let current = self.current_key_ids();
+        let peers: Vec<PeerId> = self.e2e_peers.keys().copied().collect();
+        for peer_id in peers {
+            let pinned = {
+                let pm = pm.lock().unwrap();
+                pm.get_trusted_peer(&peer_id.to_string()).map(|p| p.e2e_public_key)
+            };
+            let Some(pinned) = pinned else {
+                info!("[SYNC] Peer {} is no longer trusted; stopping sync with it", peer_id);
+                self.e2e_peers.remove(&peer_id);
+                self.synced_peers.remove(&peer_id);
+                continue;
+            };
+            if self.e2e_peers.get(&peer_id) == Some(&current) {
+                continue;
+            }
+            info!("[SYNC] Payload keys changed; sharing them again with peer {}", peer_id);
+            let mut rec = SessionRecorder::new(peer_id, SessionDirection::Outbound);
+            self.exchange_payload_keys(transport, peer_id, pinned, &mut rec).await;
+        }
+    }
+
+    /// Encrypts the events of an outgoing EventBatch, EventAck or
+    /// EventNotify if payload encryption is enabled with the peer. A peer
+    /// whose exchange key is pinned is never sent plaintext payloads.
+    fn seal_message(&self, peer_id: &PeerId, message: SyncMessage) -> SyncResult<SyncMessage> {
+        if !self.e2e_peers.contains_key(peer_id) {
+            if message.carries_events() && self.peer_requires_e2e(peer_id) {
+                return Err(SyncError::Encryption(format!(
+                    "no payload keys exchanged with peer {peer_id}"
+                )));
+            }
+            return Ok(message);
+        }
+        let Some(pm) = &self.pairing_manager else {
+            return Ok(message);
+        };
+        let pm = pm.lock().unwrap();
+        let keyring = pm.keyring();
+        Ok(match message {
+            SyncMessage::EventBatch(mut batch) => {
+                batch.events = keyring.encrypt_events(&batch.events)?;
+                SyncMessage::EventBatch(batch)
+            }
+            SyncMessage::EventAck(mut ack) => {
+                ack.events = keyring.encrypt_events(&ack.events)?;
+                SyncMessage::EventAck(ack)
+            }
+            SyncMessage::EventNotify(mut notify) => {
+                notify.event = keyring.encrypt_event(&notify.event)?;
+                SyncMessage::EventNotify(notify)
+            }
+            other => other,
+        })
+    }
+
+    /// Decrypts events received from a peer. Events that cannot be
+    /// decrypted are dropped, and so are plaintext events from a peer that
+    /// must encrypt; from other peers plaintext passes through.
+    fn open_events(&self, peer_id: &PeerId, events: &[Event]) -> Vec<Event> {
+        let Some(pm) = &self.pairing_manager else {
+            return PayloadKeyring::new().decrypt_events(events);
+        };
+        let pm = pm.lock().unwrap();
+        if !pm.requires_e2e(&peer_id.to_string()) {
+            return pm.keyring().decrypt_events(events);
+        }
+        let (sealed, plain): (Vec<Event>, Vec<Event>) = events
+            .iter()
+            .cloned()
+            .partition(|e| matches!(e.payload, privstack_types::EventPayload::Encrypted { .. }));
+        if !plain.is_empty() {
+            warn!("[SYNC] Dropping {} plaintext events from peer {}, which must encrypt", plain.len(), peer_id);
+        }
+        pm.keyring().decrypt_events(&sealed)
+    }
+
     async fn check_for_new_peers(&mut self, transport: &Arc<TokioMutex<dyn SyncTransport>>) {
         let transport_guard = transport.lock().await;
         let discovered = transport_guard.discovered_peers_async().await;
@@ -456,8 +1152,40 @@ impl SyncOrchestrator {
             return;
         }
 
-        // Query the ledger for entities that need syncing with this peer.
-        // Returns entities with no ledger entry (never synced) OR modified since last sync.
+        let peer_id_str = peer_id.to_string();
+        let mut entity_ids = self.entities_to_sync(&peer_id).await;
+        let run = self.begin_sync_run(&peer_id_str, entity_ids.len()).await;
+
+        if entity_ids.is_empty() {
+            debug!("[SYNC] No changed entities to sync with {}", peer_id);
+            if explicit {
+                let _ = self.event_tx.send(SyncEvent::SyncCompleted {
+                    peer_id,
+                    events_sent: 0,
+                    events_received: 0,
+                }).await;
+            }
+            return;
+        }
+
+        // Chunk large syncs. The ledger tracks per-entity, so remaining
+        // entities will be picked up on the next cycle automatically.
+        let total_needing_sync = entity_ids.len();
+        if self.config.max_entities_per_sync > 0 && entity_ids.len() > self.config.max_entities_per_sync {
+            entity_ids.truncate(self.config.max_entities_per_sync);
+            info!("[SYNC] Chunked sync: processing {}/{} entities this cycle", entity_ids.len(), total_needing_sync);
+        } else if total_needing_sync > 0 {
+            info!("[SYNC] Syncing {} entities with peer {}", total_needing_sync, peer_id);
+        }
+
+        let remaining = total_needing_sync.saturating_sub(entity_ids.len());
+        self.sync_entities_with_peer(transport, peer_id, entity_ids, remaining, run).await;
+    }
+
+    /// Returns the entities that need syncing with a peer: those with no
+    /// ledger entry (never synced) or modified since the last sync, limited
+    /// to the ones shared with the peer when selective sharing is on.
+    async fn entities_to_sync(&self, peer_id: &PeerId) -> Vec<EntityId> {
         let peer_id_str = peer_id.to_string();
         let store = self.entity_store.clone();
         let pid_str = peer_id_str.clone();
@@ -485,91 +1213,104 @@ impl SyncOrchestrator {
         if let Some(policy) = &self.personal_policy {
             if policy.has_selective_sharing().await {
                 let peer_entities: HashSet<EntityId> =
-                    policy.shared_entities(&peer_id).await.into_iter().collect();
+                    policy.shared_entities(peer_id).await.into_iter().collect();
                 entity_ids.retain(|eid| peer_entities.contains(eid));
             }
         }
+        entity_ids
+    }
 
-        if entity_ids.is_empty() {
-            debug!("[SYNC] No changed entities to sync with {}", peer_id);
-            if explicit {
-                let _ = self.event_tx.send(SyncEvent::SyncCompleted {
-                    peer_id,
-                    events_sent: 0,
-                    events_received: 0,
-                }).await;
-            }
-            return;
-        }
-
+    /// Runs one sync session with a peer for the given entities: handshake,
+    /// key exchange, state exchange and event batches in both directions.
+    /// Progress is checkpointed every few entities, so an interrupted session
+    /// resumes where it stopped. Returns the number of events received.
+    async fn sync_entities_with_peer(
+        &mut self,
+        transport: &Arc<TokioMutex<dyn SyncTransport>>,
+        peer_id: PeerId,
+        entity_ids: Vec<EntityId>,
+        remaining: usize,
+        mut run: Option<SyncRun>,
+    ) -> usize {
         let _ = self.event_tx.send(SyncEvent::SyncStarted { peer_id }).await;
 
-        // Chunk large syncs. The ledger tracks per-entity, so remaining
-        // entities will be picked up on the next cycle automatically.
-        let total_needing_sync = entity_ids.len();
-        if self.config.max_entities_per_sync > 0 && entity_ids.len() > self.config.max_entities_per_sync {
-            entity_ids.truncate(self.config.max_entities_per_sync);
-            info!("[SYNC] Chunked sync: processing {}/{} entities this cycle", entity_ids.len(), total_needing_sync);
-        } else if total_needing_sync > 0 {
-            info!("[SYNC] Syncing {} entities with peer {}", total_needing_sync, peer_id);
-        }
-
         let mut events_sent = 0;
         let mut events_received = 0;
 
+        let mut rec = SessionRecorder::new(peer_id, SessionDirection::Outbound);
+        let (route, device_name) = self.peer_route(transport, &peer_id).await;
+        rec.set_peer(route, device_name);
+        rec.entities_requested(entity_ids.len());
+
         // Step 1: Handshake
-        let hello = self.engine.make_hello(entity_ids.clone());
+        rec.begin_phase(SyncPhase::Handshake);
+        let hello = self.with_local_e2e_key(self.engine.make_hello(entity_ids.clone()));
+        let hello = self.with_local_signing_key(self.with_local_scope(hello).await);
         info!("[SYNC] Sending Hello to peer {} with {} entities", peer_id, entity_ids.len());
+        rec.message_sent(&hello, 0);
 
         let hello_response = {
             let tg = transport.lock().await;
             tg.send_request(&peer_id, hello).await
         };
+        if let Ok(msg) = &hello_response {
+            rec.message_received(msg, 0);
+        }
 
-        match hello_response {
+        let peer_e2e_key = match hello_response {
             Ok(SyncMessage::HelloAck(ack)) => {
                 if !ack.accepted {
                     warn!("[SYNC] Peer {} rejected: {:?}", peer_id, ack.reason);
-                    let _ = self.event_tx.send(SyncEvent::SyncFailed {
-                        peer_id,
-                        error: ack.reason.unwrap_or_else(|| "rejected".to_string()),
-                    }).await;
-                    return;
+                    let error = ack.reason.unwrap_or_else(|| "rejected".to_string());
+                    self.fail_session(peer_id, rec, error).await;
+                    return 0;
                 }
                 if ack.version != PROTOCOL_VERSION {
                     warn!("[SYNC] Version mismatch with peer {}", peer_id);
-                    let _ = self.event_tx.send(SyncEvent::SyncFailed {
-                        peer_id,
-                        error: format!("version mismatch: expected {PROTOCOL_VERSION}, got {}", ack.version),
-                    }).await;
-                    return;
+                    let error = format!("version mismatch: expected {PROTOCOL_VERSION}, got {}", ack.version);
+                    self.fail_session(peer_id, rec, error).await;
+                    return 0;
                 }
                 info!("[SYNC] Handshake accepted by peer {} ({})", peer_id, ack.device_name);
+                rec.set_peer(route, Some(ack.device_name.clone()));
+                self.record_peer_scope(&peer_id, ack.sync_scope).await;
+                self.record_peer_signing_key(&peer_id, ack.signing_public_key);
+                ack.e2e_public_key
             }
             Ok(other) => {
                 warn!("[SYNC] Unexpected response to Hello: {:?}", other);
-                let _ = self.event_tx.send(SyncEvent::SyncFailed {
-                    peer_id,
-                    error: "unexpected response to Hello".to_string(),
-                }).await;
-                return;
+                self.fail_session(peer_id, rec, "unexpected response to Hello".to_string()).await;
+                return 0;
             }
             Err(e) => {
                 warn!("[SYNC] Failed to send Hello to peer {}: {}", peer_id, e);
-                let _ = self.event_tx.send(SyncEvent::SyncFailed {
-                    peer_id,
-                    error: e.to_string(),
-                }).await;
-                return;
+                self.fail_session(peer_id, rec, e.to_string()).await;
+                return 0;
             }
+        };
+
+        // Step 1b: Exchange payload keys (E2E encryption). Once a peer's
+        // exchange key is pinned, there is no falling back to plaintext.
+        rec.begin_phase(SyncPhase::KeyExchange);
+        self.exchange_payload_keys(transport, peer_id, peer_e2e_key, &mut rec).await;
+        if !self.e2e_peers.contains_key(&peer_id) && self.peer_requires_e2e(&peer_id) {
+            warn!("[SYNC] Payload encryption with peer {} failed; not syncing in plaintext", peer_id);
+            let error = "payload encryption required".to_string();
+            self.fail_session(peer_id, rec, error).await;
+            return 0;
         }
 
         // Step 2: Request their sync state (include our known event IDs for bidirectional sync)
+        rec.begin_phase(SyncPhase::StateExchange);
         let sync_req = self.engine.make_sync_request(entity_ids.clone(), &self.event_store).await;
+        rec.message_sent(&sync_req, 0);
         let state_response = {
             let tg = transport.lock().await;
             tg.send_request(&peer_id, sync_req).await
         };
+        if let Ok(msg) = &state_response {
+            rec.message_received(msg, 0);
+        }
 
         let peer_state: SyncStateMessage = match state_response {
             Ok(SyncMessage::SyncState(state)) => {
@@ -588,14 +1329,18 @@ impl SyncOrchestrator {
 
         // Step 3: For each entity, compute and send missing events.
         // Track successfully synced entities to update the ledger.
+        rec.begin_phase(SyncPhase::EventExchange);
         let mut entities_skipped = 0usize;
-        let mut synced_entity_ids: Vec<String> = Vec::new();
-        let now_ms = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
+        let mut entities_synced = 0usize;
+        let mut unsaved_entity_ids: Vec<String> = Vec::new();
+        let mut disconnected = false;
+        let now_ms = now_millis();
+
+        for (i, eid) in entity_ids.iter().enumerate() {
+            if i > 0 && i % CHECKPOINT_ENTITIES == 0 {
+                self.checkpoint_session(peer_id, &mut unsaved_entity_ids, now_ms, run.as_mut()).await;
+            }
 
-        for eid in &entity_ids {
             // Use the peer's known event IDs from their SyncState for exact delta.
             let peer_known_ids: HashSet<EventId> = peer_state
                 .known_event_ids
@@ -609,6 +1354,7 @@ impl SyncOrchestrator {
                 &peer_known_ids,
                 &self.event_store,
             ).await;
+            rec.merge_report(self.engine.take_batch_report());
 
             // Skip round trip if we have nothing to send AND the peer has no
             // unknown events for this entity (nothing for a reverse-delta either).
@@ -622,9 +1368,11 @@ impl SyncOrchestrator {
                     // Don't write a ledger entry — let the entity come back next cycle
                     // once the snapshot event exists.
                     if !our_set.is_empty() {
-                        synced_entity_ids.push(eid.to_string());
+                        unsaved_entity_ids.push(eid.to_string());
+                        entities_synced += 1;
                     }
                     entities_skipped += 1;
+                    rec.entity_skipped();
                     continue;
                 }
                 // Peer has events we don't — send empty batch to trigger reverse delta
@@ -645,21 +1393,36 @@ impl SyncOrchestrator {
 
             let mut entity_synced = true;
             for batch_msg in batches {
+                let batch_msg = match self.seal_message(&peer_id, batch_msg) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        error!("[SYNC] Failed to encrypt events for peer {}: {}", peer_id, e);
+                        entity_synced = false;
+                        continue;
+                    }
+                };
+                rec.message_sent(&batch_msg, 0);
                 let batch_response = {
                     let tg = transport.lock().await;
                     tg.send_request(&peer_id, batch_msg).await
                 };
+                if let Ok(msg) = &batch_response {
+                    rec.message_received(msg, 0);
+                }
 
                 match batch_response {
                     Ok(SyncMessage::EventAck(ack)) => {
                         events_sent += ack.received_count;
 
                         // Handle bidirectional events from the ack
-                        for event in &ack.events {
-                            match self.apply_remote_event(&peer_id, event).await {
+                        for event in &self.open_events(&peer_id, &ack.events) {
+                            match self.apply_remote_event(&peer_id, event, &mut rec).await {
                                 Ok(true) => events_received += 1,
                                 Ok(false) => {}
-                                Err(e) => warn!("[SYNC] Failed to apply event from ack: {}", e),
+                                Err(e) => {
+                                    warn!("[SYNC] Failed to apply event from ack: {}", e);
+                                    rec.apply_error(event.entity_id, e);
+                                }
                             }
                         }
                     }
@@ -668,33 +1431,32 @@ impl SyncOrchestrator {
                         entity_synced = false;
                     }
                     Err(e) => {
+                        // The peer is gone; the next cycle resumes from the
+                        // last checkpoint instead of retrying every entity.
                         error!("[SYNC] Failed to send events to peer {}: {}", peer_id, e);
+                        rec.fail(e.to_string());
                         entity_synced = false;
+                        disconnected = true;
+                        break;
                     }
                 }
             }
 
             if entity_synced {
-                synced_entity_ids.push(eid.to_string());
+                unsaved_entity_ids.push(eid.to_string());
+                entities_synced += 1;
+                rec.entity_synced();
+            }
+            if disconnected {
+                break;
             }
         }
 
         self.synced_peers.insert(peer_id);
+        rec.begin_phase(SyncPhase::Finalize);
 
-        // Batch-update the sync ledger for all successfully synced entities
-        if !synced_entity_ids.is_empty() {
-            let store = self.entity_store.clone();
-            let pid = peer_id_str.clone();
-            let ids = synced_entity_ids.clone();
-            let ledger_count = ids.len();
-            if let Err(e) = tokio::task::spawn_blocking(move || {
-                store.mark_entities_synced(&pid, &ids, now_ms)
-            }).await {
-                warn!("[SYNC] Failed to update sync ledger: {}", e);
-            } else {
-                debug!("[SYNC] Updated sync ledger: {} entities marked synced with {}", ledger_count, peer_id);
-            }
-        }
+        // Update the sync ledger for the entities since the last checkpoint
+        self.checkpoint_session(peer_id, &mut unsaved_entity_ids, now_ms, run.as_mut()).await;
 
         // Update last_synced on TrustedPeer for UI display purposes
         if let Some(ref pm) = self.pairing_manager {
@@ -708,16 +1470,35 @@ impl SyncOrchestrator {
             events_received,
         }).await;
 
+        // Counts from the acks: the peer may drop events it already had.
+        let mut record = rec.finish();
+        record.sent.events = events_sent as u64;
+        record.received.events = events_received as u64;
+        self.store_session(record).await;
+
         info!(
             "[SYNC] Sync with peer {} complete: sent={}, received={}, synced={}, skipped={}, remaining={}",
-            peer_id, events_sent, events_received, synced_entity_ids.len(), entities_skipped,
-            total_needing_sync.saturating_sub(entity_ids.len())
+            peer_id, events_sent, events_received, entities_synced, entities_skipped,
+            remaining
         );
+        events_received
     }
 
     /// Applies a remote event (from sync) to local stores.
     /// The `sender` is the peer that sent us this event, used for policy gating.
-    async fn apply_remote_event(&self, sender: &PeerId, event: &Event) -> Result<bool, String> {
+    async fn apply_remote_event(
+        &self,
+        sender: &PeerId,
+        event: &Event,
+        rec: &mut SessionRecorder,
+    ) -> Result<bool, String> {
+        // Authorship gate: the event must be signed by a device of its author
+        if let Err(e) = self.engine.verify_authorship(event) {
+            warn!("[SYNC] Dropping event {:?} relayed by {}: {}", event.id, sender, e);
+            rec.rejected(1);
+            return Ok(false);
+        }
+
         // Policy gate: check if we should accept this event from sender
         match self
             .engine
@@ -730,6 +1511,7 @@ impl SyncOrchestrator {
                     "[SYNC] Policy filtered event {:?} from peer {} for entity {}",
                     event.id, sender, event.entity_id
                 );
+                rec.filtered_in(1);
                 return Ok(false);
             }
             Err(e) => {
@@ -737,24 +1519,44 @@ impl SyncOrchestrator {
                     "[SYNC] Policy denied event {:?} from peer {}: {}",
                     event.id, sender, e
                 );
+                rec.filtered_in(1);
                 return Ok(false);
             }
             _ => {}
         }
 
-        let peer_id = self.engine.peer_id();
-        let es = self.entity_store.clone();
-        let ev = event.clone();
+        // Dataset row and column changes belong to the dataset store alone
+        let dataset_applied = match self.engine.dataset_handler() {
+            Some(handler) => handler.handle_dataset_event(event).await.unwrap_or_else(|e| {
+                warn!("[SYNC] Dataset handler error for event {:?}: {}", event.id, e);
+                false
+            }),
+            None => false,
+        };
 
-        let apply_result = tokio::task::spawn_blocking(move || {
-            let applicator = crate::applicator::EventApplicator::new(peer_id);
-            applicator.apply_event(&ev, &es, None, None)
-        })
-        .await
-        .map_err(|e| format!("spawn_blocking panicked: {e}"))?;
+        let apply_result = if dataset_applied {
+            Ok(crate::applicator::ApplyOutcome { applied: true, conflict: None })
+        } else {
+            let peer_id = self.engine.peer_id();
+            let es = self.entity_store.clone();
+            let ev = event.clone();
+            tokio::task::spawn_blocking(move || {
+                let applicator = crate::applicator::EventApplicator::new(peer_id);
+                applicator.apply_event_outcome(&ev, &es, None, None)
+            })
+            .await
+            .map_err(|e| format!("spawn_blocking panicked: {e}"))?
+        };
 
         match apply_result {
-            Ok(was_applied) => {
+            Ok(outcome) => {
+                let was_applied = outcome.applied;
+                if let Some(conflict) = outcome.conflict {
+                    let _ = self.event_tx.send(SyncEvent::ConflictDetected {
+                        entity_id: event.entity_id,
+                        conflict_id: conflict.conflict_id,
+                    }).await;
+                }
                 if was_applied {
                     self.engine.record_local_event(event).await;
 
@@ -784,6 +1586,73 @@ impl SyncOrchestrator {
         }
     }
 
+    /// Fetches an entity outside this device's scope. The entity is pinned
+    /// first, so our advertised scope lets peers send it and incoming events
+    /// pass the policy; then known peers are asked for it one at a time until
+    /// one delivers events.
+    async fn fetch_entity(&mut self, transport: &Arc<TokioMutex<dyn SyncTransport>>, entity_id: EntityId) {
+        match &self.selective_policy {
+            Some(policy) => policy.pin(entity_id).await,
+            None => debug!("[SYNC] FetchEntity without selective policy — syncing {} as usual", entity_id),
+        }
+        self.shared_entities.insert(entity_id);
+
+        let peers = self.reachable_peers(transport).await;
+        for &peer_id in &peers {
+            let received = self.sync_entities_with_peer(transport, peer_id, vec![entity_id], 0, None).await;
+            if received > 0 {
+                info!("[SYNC] Fetched entity {} from peer {} ({} events)", entity_id, peer_id, received);
+                return;
+            }
+        }
+        if peers.is_empty() {
+            info!("[SYNC] No peers to fetch entity {} from; it will sync once one connects", entity_id);
+        } else {
+            warn!("[SYNC] No peer had events for entity {}", entity_id);
+        }
+    }
+
+    /// Peers we synced with, plus trusted peers currently in reach — a
+    /// device with a narrow scope may not have initiated a sync yet.
+    async fn reachable_peers(&self, transport: &Arc<TokioMutex<dyn SyncTransport>>) -> Vec<PeerId> {
+        let mut peers: Vec<PeerId> = self.synced_peers.iter().copied().collect();
+        let discovered = {
+            let tg = transport.lock().await;
+            tg.discovered_peers_async().await
+        };
+        for peer in discovered {
+            if !peers.contains(&peer.peer_id) && self.is_peer_trusted_sync(&peer.peer_id) {
+                peers.push(peer.peer_id);
+            }
+        }
+        peers
+    }
+
+    /// Re-syncs with every reachable peer after this device's scope widened.
+    /// Our ledger is cleared first, so entities whose events were dropped
+    /// while out of scope are asked for again; the handshake carries the new
+    /// scope, so each peer clears its ledger for us and resends the rest.
+    /// The handshake happens even when we have nothing to send.
+    async fn resync_widened_scope(&mut self, transport: &Arc<TokioMutex<dyn SyncTransport>>) {
+        for peer_id in self.reachable_peers(transport).await {
+            let store = self.entity_store.clone();
+            let pid = peer_id.to_string();
+            match tokio::task::spawn_blocking(move || store.clear_sync_ledger_for_peer(&pid)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("[SYNC] Failed to clear sync ledger for peer {}: {}", peer_id, e),
+                Err(e) => warn!("[SYNC] spawn_blocking panicked clearing sync ledger: {}", e),
+            }
+            let mut entity_ids = self.entities_to_sync(&peer_id).await;
+            let total = entity_ids.len();
+            if self.config.max_entities_per_sync > 0 {
+                entity_ids.truncate(self.config.max_entities_per_sync);
+            }
+            let remaining = total - entity_ids.len();
+            let run = self.begin_sync_run(&peer_id.to_string(), total).await;
+            self.sync_entities_with_peer(transport, peer_id, entity_ids, remaining, run).await;
+        }
+    }
+
     async fn sync_entity_to_all(&mut self, transport: &Arc<TokioMutex<dyn SyncTransport>>, _entity_id: EntityId) {
         let peers: Vec<PeerId> = self.synced_peers.iter().copied().collect();
         for peer_id in peers {
@@ -810,10 +1679,64 @@ impl SyncOrchestrator {
         let peer_id = request.peer_id;
         info!("[SYNC] Received incoming request from peer {}", peer_id);
 
+        // A Hello opens a new inbound session; anything else continues the
+        // current one (or starts one if the Hello predates a restart).
+        let is_hello = matches!(request.message, SyncMessage::Hello(_));
+        let mut rec = match self.inbound_sessions.remove(&peer_id) {
+            Some(previous) if is_hello => {
+                self.store_session(previous.finish()).await;
+                None
+            }
+            other => other,
+        };
+        if rec.is_none() {
+            let mut new = SessionRecorder::new(peer_id, SessionDirection::Inbound);
+            let (route, device_name) = self.peer_route(transport, &peer_id).await;
+            new.set_peer(route, device_name);
+            rec = Some(new);
+        }
+        let mut rec = rec.unwrap();
+        rec.begin_phase(match request.message {
+            SyncMessage::Hello(_) => SyncPhase::Handshake,
+            SyncMessage::KeyShare(_) => SyncPhase::KeyExchange,
+            SyncMessage::SyncRequest(_) => SyncPhase::StateExchange,
+            _ => SyncPhase::EventExchange,
+        });
+        rec.message_received(&request.message, 0);
+        let mut events_sent = 0;
+
         let response = match request.message {
             SyncMessage::Hello(ref hello) => {
                 info!("[SYNC] Received Hello from {} ({})", hello.peer_id, hello.device_name);
-                self.engine.handle_hello(hello).await
+                rec.entities_requested(hello.entity_ids.len());
+                let route = rec.record().transport;
+                rec.set_peer(route, Some(hello.device_name.clone()));
+                let ack = self.engine.handle_hello(hello).await;
+                let accepted = matches!(&ack, SyncMessage::HelloAck(a) if a.accepted);
+                self.e2e_peers.remove(&peer_id);
+                let ack = match hello.e2e_public_key {
+                    Some(key) if accepted && self.record_peer_e2e_key(&peer_id, key) => self.with_local_e2e_key(ack),
+                    // A peer with a pinned exchange key may not fall back to
+                    // plaintext, whether it left the key out or sent another.
+                    _ if accepted && self.peer_requires_e2e(&peer_id) => {
+                        warn!("[SYNC] Rejecting Hello from {}: payload encryption required", peer_id);
+                        SyncMessage::HelloAck(crate::protocol::HelloAckMessage::reject(
+                            self.engine.peer_id(),
+                            "payload encryption required",
+                        ))
+                    }
+                    _ => ack,
+                };
+                if matches!(&ack, SyncMessage::HelloAck(a) if a.accepted) {
+                    self.record_peer_scope(&peer_id, hello.sync_scope.clone()).await;
+                    self.record_peer_signing_key(&peer_id, hello.signing_public_key);
+                }
+                self.with_local_signing_key(self.with_local_scope(ack).await)
+            }
+
+            SyncMessage::KeyShare(ref share) => {
+                info!("[SYNC] Received {} payload keys from peer {}", share.keys.len(), peer_id);
+                self.handle_key_share(&peer_id, share).await
             }
 
             SyncMessage::SyncRequest(ref req) => {
@@ -823,12 +1746,19 @@ impl SyncOrchestrator {
 
             SyncMessage::EventBatch(ref batch) => {
                 info!("[SYNC] Received {} events for entity {} from peer {}", batch.events.len(), batch.entity_id, peer_id);
+                let mut batch = batch.clone();
+                batch.events = self.open_events(&peer_id, &batch.events);
                 let (ack, updated_entities) = self.engine.handle_event_batch(
                     &peer_id,
-                    batch,
+                    &batch,
                     &self.entity_store,
                     &self.event_store,
                 ).await;
+                rec.merge_report(self.engine.take_batch_report());
+                if let SyncMessage::EventAck(a) = &ack {
+                    rec.events_received(a.received_count);
+                    events_sent = a.events.len();
+                }
 
                 for eid in &updated_entities {
                     // Invalidate sync ledger so received events propagate to other peers.
@@ -845,9 +1775,19 @@ impl SyncOrchestrator {
                         entity_id: *eid,
                     }).await;
                 }
+                for conflict in self.engine.take_conflicts() {
+                    let Ok(entity_id) = EntityId::parse(&conflict.entity_id) else { continue };
+                    let _ = self.event_tx.send(SyncEvent::ConflictDetected {
+                        entity_id,
+                        conflict_id: conflict.conflict_id,
+                    }).await;
+                }
 
                 info!("[SYNC] Processed events from peer {}", peer_id);
-                ack
+                self.seal_message(&peer_id, ack).unwrap_or_else(|e| {
+                    error!("[SYNC] Failed to encrypt events for peer {}: {}", peer_id, e);
+                    SyncMessage::Error(ErrorMessage::internal(e.to_string()))
+                })
             }
 
             other => {
@@ -856,6 +1796,10 @@ impl SyncOrchestrator {
             }
         };
 
+        rec.message_sent(&response, events_sent);
+        rec.end_phase();
+        self.inbound_sessions.insert(peer_id, rec);
+
         let transport_guard = transport.lock().await;
         if let Err(e) = transport_guard.send_response(request.response_token, response).await {
             warn!("[SYNC] Failed to send response: {}", e);
@@ -915,11 +1859,32 @@ pub fn create_orchestrator_with_policy(
         event_tx,
         pairing_manager: None,
         personal_policy: None,
+        selective_policy: None,
+        enterprise_policy: None,
+        e2e_peers: HashMap::new(),
+        inbound_sessions: HashMap::new(),
+        session_history_capacity: DEFAULT_SESSION_HISTORY,
     };
 
     (handle, event_rx, command_rx, orchestrator)
 }
 
+/// Gives a pairing-gated engine a device key registry seeded with the pinned
+/// keys of trusted peers, and this device's signing key.
+fn attach_device_keys(engine: &mut SyncEngine, pairing_manager: &std::sync::Mutex<PairingManager>) {
+    let registry = Arc::new(crate::signing::DeviceKeyRegistry::new());
+    let mut pm = pairing_manager.lock().unwrap();
+    for peer in pm.trusted_peers() {
+        let (Some(key), Ok(peer_id)) = (peer.signing_public_key, peer.peer_id.parse::<PeerId>())
+        else {
+            continue;
+        };
+        registry.register(peer_id, key);
+    }
+    engine.set_key_registry(registry);
+    engine.set_signing_key(pm.signing_key());
+}
+
 /// Creates an orchestrator with AllowAllPolicy + pairing gate.
 /// Discovered peers must be trusted in the pairing manager before syncing.
 pub fn create_orchestrator_with_pairing(
@@ -934,11 +1899,12 @@ pub fn create_orchestrator_with_pairing(
     mpsc::Receiver<SyncCommand>,
     SyncOrchestrator,
 ) {
-    let engine = SyncEngine::with_policy(
+    let mut engine = SyncEngine::with_policy(
         peer_id,
         SyncConfig::default(),
         Arc::new(crate::policy::AllowAllPolicy),
     );
+    attach_device_keys(&mut engine, &pairing_manager);
     let (command_tx, command_rx) = mpsc::channel(32);
     let (event_tx, event_rx) = mpsc::channel(64);
 
@@ -956,6 +1922,11 @@ pub fn create_orchestrator_with_pairing(
         event_tx,
         pairing_manager: Some(pairing_manager),
         personal_policy: None,
+        selective_policy: None,
+        enterprise_policy: None,
+        e2e_peers: HashMap::new(),
+        inbound_sessions: HashMap::new(),
+        session_history_capacity: DEFAULT_SESSION_HISTORY,
     };
 
     (handle, event_rx, command_rx, orchestrator)
@@ -976,7 +1947,8 @@ pub fn create_personal_orchestrator(
     mpsc::Receiver<SyncCommand>,
     SyncOrchestrator,
 ) {
-    let engine = SyncEngine::with_policy(peer_id, SyncConfig::default(), policy.clone());
+    let mut engine = SyncEngine::with_policy(peer_id, SyncConfig::default(), policy.clone());
+    attach_device_keys(&mut engine, &pairing_manager);
     let (command_tx, command_rx) = mpsc::channel(32);
     let (event_tx, event_rx) = mpsc::channel(64);
 
@@ -994,6 +1966,58 @@ pub fn create_personal_orchestrator(
         event_tx,
         pairing_manager: Some(pairing_manager),
         personal_policy: Some(policy),
+        selective_policy: None,
+        enterprise_policy: None,
+        e2e_peers: HashMap::new(),
+        inbound_sessions: HashMap::new(),
+        session_history_capacity: DEFAULT_SESSION_HISTORY,
+    };
+
+    (handle, event_rx, command_rx, orchestrator)
+}
+
+/// Creates an orchestrator with per-device sync scopes + pairing gate.
+/// `selective` is the engine policy; build it with the personal policy as its
+/// inner policy (`SelectiveSyncPolicy::with_inner`) so per-peer sharing still
+/// applies underneath the scope.
+pub fn create_selective_orchestrator(
+    peer_id: PeerId,
+    entity_store: Arc<EntityStore>,
+    event_store: Arc<EventStore>,
+    config: OrchestratorConfig,
+    personal: Arc<PersonalSyncPolicy>,
+    selective: Arc<SelectiveSyncPolicy>,
+    pairing_manager: Arc<std::sync::Mutex<PairingManager>>,
+) -> (
+    OrchestratorHandle,
+    mpsc::Receiver<SyncEvent>,
+    mpsc::Receiver<SyncCommand>,
+    SyncOrchestrator,
+) {
+    let mut engine = SyncEngine::with_policy(peer_id, SyncConfig::default(), selective.clone());
+    attach_device_keys(&mut engine, &pairing_manager);
+    let (command_tx, command_rx) = mpsc::channel(32);
+    let (event_tx, event_rx) = mpsc::channel(64);
+
+    let handle = OrchestratorHandle {
+        command_tx: command_tx.clone(),
+    };
+
+    let orchestrator = SyncOrchestrator {
+        engine,
+        entity_store,
+        event_store,
+        config,
+        shared_entities: HashSet::new(),
+        synced_peers: HashSet::new(),
+        event_tx,
+        pairing_manager: Some(pairing_manager),
+        personal_policy: Some(personal),
+        selective_policy: Some(selective),
+        enterprise_policy: None,
+        e2e_peers: HashMap::new(),
+        inbound_sessions: HashMap::new(),
+        session_history_capacity: DEFAULT_SESSION_HISTORY,
     };
 
     (handle, event_rx, command_rx, orchestrator)
@@ -1006,7 +2030,7 @@ pub fn create_enterprise_orchestrator(
     entity_store: Arc<EntityStore>,
     event_store: Arc<EventStore>,
     config: OrchestratorConfig,
-    policy: Arc<crate::policy::EnterpriseSyncPolicy>,
+    policy: Arc<EnterpriseSyncPolicy>,
 ) -> (
     OrchestratorHandle,
     mpsc::Receiver<SyncEvent>,
@@ -1014,8 +2038,9 @@ pub fn create_enterprise_orchestrator(
     SyncOrchestrator,
 ) {
     let mut engine = SyncEngine::with_policy(peer_id, SyncConfig::default(), policy.clone());
+    engine.set_key_registry(policy.device_keys.clone());
 
-    let acl_applicator = Arc::new(crate::acl_applicator::AclApplicator::new(policy));
+    let acl_applicator = Arc::new(crate::acl_applicator::AclApplicator::new(policy.clone()));
     engine.set_acl_handler(acl_applicator);
 
     let (command_tx, command_rx) = mpsc::channel(32);
@@ -1035,6 +2060,11 @@ pub fn create_enterprise_orchestrator(
         event_tx,
         pairing_manager: None,
         personal_policy: None,
+        selective_policy: None,
+        enterprise_policy: Some(policy),
+        e2e_peers: HashMap::new(),
+        inbound_sessions: HashMap::new(),
+        session_history_capacity: DEFAULT_SESSION_HISTORY,
     };
 
     (handle, event_rx, command_rx, orchestrator)
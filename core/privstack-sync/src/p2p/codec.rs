@@ -1,25 +1,65 @@
 //! Codec for sync protocol messages over libp2p request-response.
+//!
+//! Every message is a 4-byte big-endian length prefix followed by a frame.
+//! A frame is either:
+//!
+//! - **JSON** — the raw JSON encoding of the `SyncMessage` (the original
+//!   format, still used for the Hello exchange and for legacy peers), or
+//! - **binary** — a 4-byte header followed by a CBOR payload, optionally
+//!   zstd-compressed:
+//!
+//! ```text
+//! [0xB5 magic][wire version][encoding][flags][payload ...]
+//! ```
+//!
+//! The magic byte can never start a JSON document, so readers detect the
+//! frame kind without any negotiation. Writers only emit binary frames to
+//! peers that advertised support in their `HelloMessage`.
 
-use crate::protocol::SyncMessage;
+use crate::protocol::{SyncMessage, WireCompression, WireEncoding, WireFormat};
 use async_trait::async_trait;
 use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use libp2p::request_response;
-use std::io;
+use std::io::{self, Read};
 
-/// Maximum message size (16 MB).
-const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// Maximum size of a frame on the wire (16 MB).
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum size of a message payload after decompression (64 MB).
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// First byte of a binary frame.
+pub const BINARY_FRAME_MAGIC: u8 = 0xB5;
+
+/// Version of the binary frame layout.
+pub const WIRE_FRAME_VERSION: u8 = 1;
+
+/// Encoded payloads smaller than this are sent uncompressed.
+const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// zstd level for event payloads (favours speed over ratio).
+const ZSTD_LEVEL: i32 = 3;
+
+const BINARY_HEADER_LEN: usize = 4;
+const ENCODING_CBOR: u8 = 1;
+/// The payload is zstd-compressed.
+const FLAG_ZSTD: u8 = 0b0000_0001;
+/// The sender can read zstd-compressed replies.
+const FLAG_ACCEPTS_ZSTD: u8 = 0b0000_0010;
 
 /// The sync protocol codec for request-response.
 #[derive(Debug, Clone, Default)]
 pub struct SyncCodec;
 
-/// Sync protocol request (wraps SyncMessage).
+/// Sync protocol request: the message and the wire format it is
+/// (or was) encoded with.
 #[derive(Debug, Clone)]
-pub struct SyncRequest(pub SyncMessage);
+pub struct SyncRequest(pub SyncMessage, pub WireFormat);
 
-/// Sync protocol response (wraps SyncMessage).
+/// Sync protocol response: the message and the wire format it is
+/// (or was) encoded with.
 #[derive(Debug, Clone)]
-pub struct SyncResponse(pub SyncMessage);
+pub struct SyncResponse(pub SyncMessage, pub WireFormat);
 
 #[async_trait]
 impl request_response::Codec for SyncCodec {
@@ -35,8 +75,8 @@ impl request_response::Codec for SyncCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let message = read_message(io).await?;
-        Ok(SyncRequest(message))
+        let (message, format) = read_message_with_format(io).await?;
+        Ok(SyncRequest(message, format))
     }
 
     async fn read_response<T>(
@@ -47,8 +87,8 @@ impl request_response::Codec for SyncCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let message = read_message(io).await?;
-        Ok(SyncResponse(message))
+        let (message, format) = read_message_with_format(io).await?;
+        Ok(SyncResponse(message, format))
     }
 
     async fn write_request<T>(
@@ -60,7 +100,7 @@ impl request_response::Codec for SyncCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        write_message(io, &req.0).await
+        write_message_as(io, &req.0, req.1).await
     }
 
     async fn write_response<T>(
@@ -72,63 +112,171 @@ impl request_response::Codec for SyncCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        write_message(io, &res.0).await
+        write_message_as(io, &res.0, res.1).await
     }
 }
 
-/// Reads a length-prefixed JSON message.
+/// Reads a length-prefixed message in any supported wire format.
 pub async fn read_message<T: AsyncRead + Unpin>(io: &mut T) -> io::Result<SyncMessage> {
+    read_message_with_format(io).await.map(|(message, _)| message)
+}
+
+/// Reads a length-prefixed message and reports the wire format the sender
+/// used, so a reply can be written in a format the sender understands.
+pub async fn read_message_with_format<T: AsyncRead + Unpin>(
+    io: &mut T,
+) -> io::Result<(SyncMessage, WireFormat)> {
     // Read 4-byte length prefix
     let mut len_bytes = [0u8; 4];
     io.read_exact(&mut len_bytes).await?;
     let len = u32::from_be_bytes(len_bytes) as usize;
 
-    // Validate size
-    if len > MAX_MESSAGE_SIZE {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("message too large: {len} bytes"),
-        ));
+    // Validate size before allocating
+    if len > MAX_FRAME_SIZE {
+        return Err(invalid_data(format!("message too large: {len} bytes")));
     }
 
-    // Read message body
+    // Read frame
     let mut buf = vec![0u8; len];
     io.read_exact(&mut buf).await?;
 
-    // Deserialize
-    serde_json::from_slice(&buf).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("JSON decode error: {e}"),
-        )
-    })
+    decode_message(&buf)
 }
 
 /// Writes a length-prefixed JSON message.
 pub async fn write_message<T: AsyncWrite + Unpin>(io: &mut T, message: &SyncMessage) -> io::Result<()> {
-    // Serialize to JSON
-    let data = serde_json::to_vec(message).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("JSON encode error: {e}"),
-        )
-    })?;
-
-    // Validate size
-    if data.len() > MAX_MESSAGE_SIZE {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("message too large: {} bytes", data.len()),
-        ));
-    }
+    write_message_as(io, message, WireFormat::JSON).await
+}
+
+/// Writes a length-prefixed message in the given wire format.
+pub async fn write_message_as<T: AsyncWrite + Unpin>(
+    io: &mut T,
+    message: &SyncMessage,
+    format: WireFormat,
+) -> io::Result<()> {
+    let data = encode_message(message, format)?;
 
     // Write length prefix
     let len_bytes = (data.len() as u32).to_be_bytes();
     io.write_all(&len_bytes).await?;
 
-    // Write message body
+    // Write frame
     io.write_all(&data).await?;
     io.flush().await?;
 
     Ok(())
 }
+
+/// Encodes a message into a frame (without the length prefix).
+pub fn encode_message(message: &SyncMessage, format: WireFormat) -> io::Result<Vec<u8>> {
+    let data = match format.encoding {
+        WireEncoding::Json => serde_json::to_vec(message)
+            .map_err(|e| invalid_data(format!("JSON encode error: {e}")))?,
+        WireEncoding::Cbor => encode_binary(message, format.compression)?,
+    };
+
+    if data.len() > MAX_FRAME_SIZE {
+        return Err(invalid_data(format!("message too large: {} bytes", data.len())));
+    }
+
+    Ok(data)
+}
+
+/// Decodes a frame (without the length prefix), detecting its wire format.
+pub fn decode_message(frame: &[u8]) -> io::Result<(SyncMessage, WireFormat)> {
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(invalid_data(format!("message too large: {} bytes", frame.len())));
+    }
+
+    if frame.first() == Some(&BINARY_FRAME_MAGIC) {
+        return decode_binary(frame);
+    }
+
+    let message = serde_json::from_slice(frame)
+        .map_err(|e| invalid_data(format!("JSON decode error: {e}")))?;
+    Ok((message, WireFormat::JSON))
+}
+
+fn encode_binary(message: &SyncMessage, compression: WireCompression) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    ciborium::into_writer(message, &mut payload)
+        .map_err(|e| invalid_data(format!("CBOR encode error: {e}")))?;
+
+    let accepts_zstd = compression == WireCompression::Zstd;
+    let mut flags = if accepts_zstd { FLAG_ACCEPTS_ZSTD } else { 0 };
+
+    if accepts_zstd && message.carries_events() && payload.len() >= COMPRESSION_THRESHOLD {
+        payload = zstd::bulk::compress(&payload, ZSTD_LEVEL)
+            .map_err(|e| invalid_data(format!("zstd compress error: {e}")))?;
+        flags |= FLAG_ZSTD;
+    }
+
+    let mut frame = Vec::with_capacity(BINARY_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&[BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION, ENCODING_CBOR, flags]);
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+fn decode_binary(frame: &[u8]) -> io::Result<(SyncMessage, WireFormat)> {
+    if frame.len() < BINARY_HEADER_LEN {
+        return Err(invalid_data("truncated binary frame header"));
+    }
+
+    let (version, encoding, flags) = (frame[1], frame[2], frame[3]);
+    if version != WIRE_FRAME_VERSION {
+        return Err(invalid_data(format!("unsupported wire frame version: {version}")));
+    }
+    if encoding != ENCODING_CBOR {
+        return Err(invalid_data(format!("unsupported wire encoding: {encoding}")));
+    }
+    if flags & !(FLAG_ZSTD | FLAG_ACCEPTS_ZSTD) != 0 {
+        return Err(invalid_data(format!("unknown wire frame flags: {flags:#04x}")));
+    }
+
+    let body = &frame[BINARY_HEADER_LEN..];
+    let decompressed;
+    let payload = if flags & FLAG_ZSTD != 0 {
+        decompressed = decompress_bounded(body)?;
+        &decompressed[..]
+    } else {
+        body
+    };
+
+    let message: SyncMessage = ciborium::from_reader(payload)
+        .map_err(|e| invalid_data(format!("CBOR decode error: {e}")))?;
+
+    let compression = if flags & FLAG_ACCEPTS_ZSTD != 0 {
+        WireCompression::Zstd
+    } else {
+        WireCompression::None
+    };
+    let format = WireFormat {
+        encoding: WireEncoding::Cbor,
+        compression,
+    };
+    Ok((message, format))
+}
+
+/// Decompresses a zstd payload, refusing to produce more than
+/// `MAX_MESSAGE_SIZE` bytes (guards against decompression bombs).
+fn decompress_bounded(body: &[u8]) -> io::Result<Vec<u8>> {
+    let decoder = zstd::stream::read::Decoder::new(body)
+        .map_err(|e| invalid_data(format!("zstd decode error: {e}")))?;
+
+    let mut out = Vec::new();
+    decoder
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| invalid_data(format!("zstd decode error: {e}")))?;
+
+    if out.len() > MAX_MESSAGE_SIZE {
+        return Err(invalid_data(format!(
+            "decompressed message too large: more than {MAX_MESSAGE_SIZE} bytes"
+        )));
+    }
+    Ok(out)
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
@@ -3,7 +3,7 @@
 use crate::error::{SyncError, SyncResult};
 use crate::p2p::behaviour::{SyncBehaviour, SyncBehaviourEvent};
 use crate::p2p::codec::{SyncRequest, SyncResponse};
-use crate::protocol::SyncMessage;
+use crate::protocol::{SyncMessage, WireCapabilities, WireFormat};
 use crate::transport::{
     DiscoveredPeer, DiscoveryMethod, IncomingSyncRequest, ResponseToken, SyncTransport,
 };
@@ -85,6 +85,8 @@ pub struct IncomingRequest {
     pub libp2p_peer_id: Libp2pPeerId,
     /// The request message.
     pub message: SyncMessage,
+    /// Wire format the request arrived in; the response is written in it too.
+    pub wire_format: WireFormat,
     /// Channel to send the response.
     pub response_channel: ResponseChannel<SyncResponse>,
 }
@@ -101,6 +103,7 @@ enum SwarmCommand {
     SendResponse {
         channel: ResponseChannel<SyncResponse>,
         message: SyncMessage,
+        format: WireFormat,
     },
     /// Publish our presence to the sync group DHT.
     PublishToSyncGroup {
@@ -249,6 +252,7 @@ impl P2pTransport {
         &self,
         channel: ResponseChannel<SyncResponse>,
         message: SyncMessage,
+        format: WireFormat,
     ) -> SyncResult<()> {
         let command_tx = self
             .command_tx
@@ -256,7 +260,7 @@ impl P2pTransport {
             .ok_or_else(|| SyncError::Network("transport not running".to_string()))?;
 
         command_tx
-            .send(SwarmCommand::SendResponse { channel, message })
+            .send(SwarmCommand::SendResponse { channel, message, format })
             .await
             .map_err(|_| SyncError::Network("command channel closed".to_string()))?;
 
@@ -316,6 +320,9 @@ impl P2pTransport {
             oneshot::Sender<SyncResult<SyncMessage>>,
         > = HashMap::new();
 
+        // Wire format negotiated per peer in the Hello exchange (JSON if absent)
+        let mut peer_formats: HashMap<Libp2pPeerId, WireFormat> = HashMap::new();
+
         // Track our listen addresses for DHT publishing
         let mut listen_addresses: Vec<Multiaddr> = Vec::new();
 
@@ -458,11 +465,22 @@ impl P2pTransport {
                                 request_response::Event::Message { peer, message, .. } => {
                                     match message {
                                         request_response::Message::Request { request, channel, .. } => {
+                                            let SyncRequest(message, wire_format) = request;
+                                            if let SyncMessage::Hello(ref hello) = message {
+                                                // Mirror the format the engine selects for its HelloAck
+                                                let format = hello
+                                                    .wire
+                                                    .as_ref()
+                                                    .map(|caps| WireCapabilities::supported().negotiate(caps))
+                                                    .unwrap_or_default();
+                                                peer_formats.insert(peer, format);
+                                            }
                                             let privstack_id = Self::map_peer_id(&peer);
                                             let incoming = IncomingRequest {
                                                 peer_id: privstack_id,
                                                 libp2p_peer_id: peer,
-                                                message: request.0,
+                                                message,
+                                                wire_format,
                                                 response_channel: channel,
                                             };
                                             if incoming_tx.send(incoming).await.is_err() {
@@ -470,8 +488,14 @@ impl P2pTransport {
                                             }
                                         }
                                         request_response::Message::Response { request_id, response } => {
+                                            let SyncResponse(message, _) = response;
+                                            if let SyncMessage::HelloAck(ref ack) = message {
+                                                if ack.accepted {
+                                                    peer_formats.insert(peer, ack.wire_format.unwrap_or_default());
+                                                }
+                                            }
                                             if let Some(response_tx) = pending_requests.remove(&request_id) {
-                                                let _ = response_tx.send(Ok(response.0));
+                                                let _ = response_tx.send(Ok(message));
                                             }
                                         }
                                     }
@@ -494,8 +518,12 @@ impl P2pTransport {
                         SwarmEvent::ConnectionEstablished { peer_id, .. } => {
                             info!("Connection established with {peer_id}");
                         }
-                        SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
+                        SwarmEvent::ConnectionClosed { peer_id, cause, num_established, .. } => {
                             info!("Connection closed with {peer_id}: {cause:?}");
+                            if num_established == 0 {
+                                // The peer may come back running a different build
+                                peer_formats.remove(&peer_id);
+                            }
                         }
                         SwarmEvent::IncomingConnection { local_addr, send_back_addr, .. } => {
                             debug!("Incoming connection from {send_back_addr} on {local_addr}");
@@ -512,17 +540,22 @@ impl P2pTransport {
                 Some(command) = command_rx.recv() => {
                     match command {
                         SwarmCommand::SendRequest { peer_id, message, response_tx } => {
+                            // Hello is always JSON so peers of any version can read it
+                            let format = match message {
+                                SyncMessage::Hello(_) => WireFormat::JSON,
+                                _ => peer_formats.get(&peer_id).copied().unwrap_or_default(),
+                            };
                             let request_id = swarm
                                 .behaviour_mut()
                                 .sync_protocol
-                                .send_request(&peer_id, SyncRequest(message));
+                                .send_request(&peer_id, SyncRequest(message, format));
                             pending_requests.insert(request_id, response_tx);
                         }
-                        SwarmCommand::SendResponse { channel, message } => {
+                        SwarmCommand::SendResponse { channel, message, format } => {
                             if swarm
                                 .behaviour_mut()
                                 .sync_protocol
-                                .send_response(channel, SyncResponse(message))
+                                .send_response(channel, SyncResponse(message, format))
                                 .is_err()
                             {
                                 warn!("Failed to send response (channel closed)");
@@ -676,7 +709,7 @@ impl SyncTransport for P2pTransport {
         Some(IncomingSyncRequest {
             peer_id: req.peer_id,
             message: req.message,
-            response_token: ResponseToken::new(req.response_channel),
+            response_token: ResponseToken::new((req.response_channel, req.wire_format)),
         })
     }
 
@@ -685,9 +718,9 @@ impl SyncTransport for P2pTransport {
         token: ResponseToken,
         message: SyncMessage,
     ) -> SyncResult<()> {
-        let channel: ResponseChannel<SyncResponse> = token
+        let (channel, format): (ResponseChannel<SyncResponse>, WireFormat) = token
             .downcast()
             .ok_or_else(|| SyncError::Network("invalid response token".to_string()))?;
-        self.send_response_inner(channel, message).await
+        self.send_response_inner(channel, message, format).await
     }
 }
@@ -5,10 +5,27 @@
 //! 2. Only devices with the same sync code can discover each other
 //! 3. Discovered devices must be approved before syncing
 //! 4. Approved devices become "trusted peers" that auto-sync
-
+//!
+//! The manager also owns the device's [`PayloadKeyring`]: payload keys are
+//! exchanged with trusted peers after the handshake and rotated whenever a
+//! trusted peer is removed.
+//!
+//! The keyring and the device's event signing key are secrets: they are
+//! never written to the pairing JSON, but kept in a [`PairingSecretStore`]
+//! (a vault or the OS keychain) and saved there as soon as they change.
+//!
+//! The signing and exchange keys a peer presents are pinned at the first
+//! handshake. A peer that later presents different keys (say, after a
+//! reinstall) is refused and offered for pairing again; approving it pins
+//! the new keys.
+
+use crate::e2e::PayloadKeyring;
+use crate::error::SyncResult;
+use crate::signing::DeviceSigningKey;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Word list for generating human-readable sync codes.
@@ -154,6 +171,12 @@ pub struct TrustedPeer {
     pub last_synced: Option<u64>,
     /// Known addresses for direct connection
     pub addresses: Vec<String>,
+    /// The peer's X25519 key for sealing payload keys (learned in the handshake)
+    #[serde(default)]
+    pub e2e_public_key: Option<[u8; 32]>,
+    /// The peer's Ed25519 event signing key, pinned at the first handshake
+    #[serde(default)]
+    pub signing_public_key: Option<[u8; 32]>,
 }
 
 impl TrustedPeer {
@@ -170,6 +193,8 @@ impl TrustedPeer {
             approved_at: now,
             last_synced: None,
             addresses: peer.addresses.clone(),
+            e2e_public_key: None,
+            signing_public_key: None,
         }
     }
 
@@ -184,6 +209,41 @@ impl TrustedPeer {
     }
 }
 
+/// Keeps the pairing secrets (the payload keyring and the event signing
+/// key) outside the pairing JSON.
+pub trait PairingSecretStore: Send + Sync {
+    /// Reads the stored secrets, or `None` if none were stored yet.
+    fn load(&self) -> SyncResult<Option<Vec<u8>>>;
+
+    /// Replaces the stored secrets.
+    fn save(&self, secrets: &[u8]) -> SyncResult<()>;
+}
+
+#[derive(Clone)]
+struct SecretStoreHandle(Arc<dyn PairingSecretStore>);
+
+impl std::fmt::Debug for SecretStoreHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PairingSecretStore")
+    }
+}
+
+/// What a [`PairingSecretStore`] holds.
+#[derive(Default, Serialize, Deserialize)]
+struct PairingSecrets {
+    #[serde(default)]
+    keyring: PayloadKeyring,
+    #[serde(default)]
+    signing_key: Option<DeviceSigningKey>,
+}
+
+/// Keys a trusted peer presented that differ from its pinned ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RepairOffer {
+    signing_public_key: Option<[u8; 32]>,
+    e2e_public_key: Option<[u8; 32]>,
+}
+
 /// Manages the pairing state and trusted peers.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PairingManager {
@@ -193,6 +253,19 @@ pub struct PairingManager {
     discovered_peers: HashMap<String, DiscoveredPeerInfo>,
     /// Fully trusted peers (persisted)
     trusted_peers: HashMap<String, TrustedPeer>,
+    /// New keys of trusted peers awaiting approval as a re-pair (persisted)
+    #[serde(default)]
+    repair_offers: HashMap<String, RepairOffer>,
+    /// Payload encryption keys, kept in the secret store. Read from older
+    /// pairing JSON so they migrate, but never written to it.
+    #[serde(default, skip_serializing)]
+    keyring: PayloadKeyring,
+    /// This device's event signing key, kept like the keyring.
+    #[serde(default, skip_serializing)]
+    signing_key: Option<DeviceSigningKey>,
+    /// Where the keyring and signing key are saved as they change.
+    #[serde(skip)]
+    secret_store: Option<SecretStoreHandle>,
 }
 
 impl PairingManager {
@@ -206,11 +279,71 @@ impl PairingManager {
         serde_json::from_str(json)
     }
 
-    /// Serializes pairing state to JSON.
+    /// Serializes pairing state to JSON. Secrets are not included.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
     }
 
+    /// Replaces the pairing state with one loaded from JSON, keeping this
+    /// manager's secrets and secret store. Secrets found in older JSON are
+    /// merged in and saved to the store.
+    pub fn load_json(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let mut loaded = Self::from_json(json)?;
+        self.current_code = loaded.current_code;
+        self.discovered_peers = loaded.discovered_peers;
+        self.trusted_peers = loaded.trusted_peers;
+        self.repair_offers = loaded.repair_offers;
+        self.keyring.merge(std::mem::take(&mut loaded.keyring));
+        if self.signing_key.is_none() || self.secret_store.is_none() {
+            self.signing_key = loaded.signing_key.or(self.signing_key.take());
+        }
+        self.persist_secrets();
+        Ok(())
+    }
+
+    /// Attaches the store that keeps this device's secrets and loads them.
+    ///
+    /// Stored secrets take precedence; keys held only in memory (created
+    /// before the store was attached, or read from older pairing JSON) are
+    /// merged in. The result is saved back, and from then on every change
+    /// to the keyring or signing key is saved as it happens.
+    pub fn attach_secret_store(&mut self, store: Arc<dyn PairingSecretStore>) -> SyncResult<()> {
+        if let Some(bytes) = store.load()? {
+            let stored: PairingSecrets = serde_json::from_slice(&bytes)?;
+            let held = std::mem::replace(&mut self.keyring, stored.keyring);
+            self.keyring.merge(held);
+            if stored.signing_key.is_some() {
+                self.signing_key = stored.signing_key;
+            }
+        }
+        self.secret_store = Some(SecretStoreHandle(store));
+        self.save_secrets()
+    }
+
+    /// Whether a secret store is attached.
+    pub fn has_secret_store(&self) -> bool {
+        self.secret_store.is_some()
+    }
+
+    fn save_secrets(&self) -> SyncResult<()> {
+        let Some(store) = &self.secret_store else {
+            return Ok(());
+        };
+        let secrets = PairingSecrets {
+            keyring: self.keyring.clone(),
+            signing_key: self.signing_key.clone(),
+        };
+        store.0.save(&serde_json::to_vec(&secrets)?)
+    }
+
+    /// Saves the secrets, logging on failure: the keys stay usable in
+    /// memory and the next change saves them again.
+    fn persist_secrets(&self) {
+        if let Err(e) = self.save_secrets() {
+            tracing::warn!("Failed to save pairing secrets: {}", e);
+        }
+    }
+
     /// Gets the current sync code, if any.
     pub fn current_code(&self) -> Option<&SyncCode> {
         self.current_code.as_ref()
@@ -248,9 +381,16 @@ impl PairingManager {
     }
 
     /// Approves a discovered peer, making them trusted.
+    ///
+    /// Approving a trusted peer offered for re-pairing pins the new keys it
+    /// presented in place of the old ones.
     pub fn approve_peer(&mut self, peer_id: &str) -> Option<TrustedPeer> {
         if let Some(peer) = self.discovered_peers.remove(peer_id) {
-            let trusted = TrustedPeer::from_discovered(&peer);
+            let mut trusted = TrustedPeer::from_discovered(&peer);
+            if let Some(offer) = self.repair_offers.remove(peer_id) {
+                trusted.signing_public_key = offer.signing_public_key;
+                trusted.e2e_public_key = offer.e2e_public_key;
+            }
             self.trusted_peers.insert(peer_id.to_string(), trusted.clone());
             Some(trusted)
         } else {
@@ -263,11 +403,13 @@ impl PairingManager {
         if let Some(peer) = self.discovered_peers.get_mut(peer_id) {
             peer.status = PairingStatus::Rejected;
         }
+        self.repair_offers.remove(peer_id);
     }
 
     /// Removes a discovered peer.
     pub fn remove_discovered_peer(&mut self, peer_id: &str) {
         self.discovered_peers.remove(peer_id);
+        self.repair_offers.remove(peer_id);
     }
 
     /// Gets all trusted peers.
@@ -290,9 +432,117 @@ impl PairingManager {
         self.trusted_peers.contains_key(peer_id)
     }
 
-    /// Removes a trusted peer.
+    /// Removes a trusted peer, along with its pinned keys.
+    ///
+    /// Rotates all payload keys so the removed peer cannot read events
+    /// encrypted from now on.
     pub fn remove_trusted_peer(&mut self, peer_id: &str) {
-        self.trusted_peers.remove(peer_id);
+        self.repair_offers.remove(peer_id);
+        self.discovered_peers.remove(peer_id);
+        if self.trusted_peers.remove(peer_id).is_some() {
+            self.keyring.rotate();
+            self.persist_secrets();
+        }
+    }
+
+    /// Pins the E2E exchange key a trusted peer advertised.
+    ///
+    /// The first key seen for a peer is kept. Returns false if the peer is
+    /// not trusted or advertises a different key than the pinned one; a
+    /// different key offers the peer for re-pairing.
+    pub fn set_peer_e2e_key(&mut self, peer_id: &str, public_key: [u8; 32]) -> bool {
+        let Some(peer) = self.trusted_peers.get_mut(peer_id) else {
+            return false;
+        };
+        match peer.e2e_public_key {
+            Some(pinned) if pinned == public_key => true,
+            Some(_) => {
+                self.offer_repair(peer_id).e2e_public_key = Some(public_key);
+                false
+            }
+            None => {
+                peer.e2e_public_key = Some(public_key);
+                true
+            }
+        }
+    }
+
+    /// Pins the event signing key a trusted peer advertised.
+    ///
+    /// The first key seen for a peer is kept. Returns false if the peer is
+    /// not trusted or advertises a different key than the pinned one; a
+    /// different key offers the peer for re-pairing.
+    pub fn set_peer_signing_key(&mut self, peer_id: &str, public_key: [u8; 32]) -> bool {
+        let Some(peer) = self.trusted_peers.get_mut(peer_id) else {
+            return false;
+        };
+        match peer.signing_public_key {
+            Some(pinned) if pinned == public_key => true,
+            Some(_) => {
+                self.offer_repair(peer_id).signing_public_key = Some(public_key);
+                false
+            }
+            None => {
+                peer.signing_public_key = Some(public_key);
+                true
+            }
+        }
+    }
+
+    /// Lists a trusted peer among the discovered peers again, pending local
+    /// approval, and returns the keys it will be re-pinned with.
+    fn offer_repair(&mut self, peer_id: &str) -> &mut RepairOffer {
+        if let Some(peer) = self.trusted_peers.get(peer_id) {
+            let discovered = DiscoveredPeerInfo {
+                peer_id: peer.peer_id.clone(),
+                device_name: peer.device_name.clone(),
+                discovered_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                status: PairingStatus::PendingLocalApproval,
+                addresses: peer.addresses.clone(),
+            };
+            self.discovered_peers
+                .entry(peer_id.to_string())
+                .or_insert(discovered);
+        }
+        self.repair_offers.entry(peer_id.to_string()).or_default()
+    }
+
+    /// Whether a trusted peer must encrypt its payloads: once its exchange
+    /// key is pinned, plaintext from it is refused.
+    pub fn requires_e2e(&self, peer_id: &str) -> bool {
+        self.trusted_peers
+            .get(peer_id)
+            .is_some_and(|p| p.e2e_public_key.is_some())
+    }
+
+    /// Gets this device's event signing key, generating it on first use.
+    pub fn signing_key(&mut self) -> DeviceSigningKey {
+        if let Some(key) = &self.signing_key {
+            return key.clone();
+        }
+        let key = DeviceSigningKey::generate();
+        self.signing_key = Some(key.clone());
+        self.persist_secrets();
+        key
+    }
+
+    /// Gets the payload keyring.
+    pub fn keyring(&self) -> &PayloadKeyring {
+        &self.keyring
+    }
+
+    /// Changes the payload keyring, saving it to the secret store if keys
+    /// were added.
+    pub fn update_keyring<R>(&mut self, update: impl FnOnce(&mut PayloadKeyring) -> R) -> R {
+        let before = self.keyring.revision();
+        let result = update(&mut self.keyring);
+        if self.keyring.revision() != before {
+            self.persist_secrets();
+        }
+        result
     }
 
     /// Updates a trusted peer's addresses.
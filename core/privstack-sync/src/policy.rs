@@ -3,14 +3,24 @@
 //! The `SyncPolicy` trait provides hooks at each stage of the sync protocol.
 //! `AllowAllPolicy` is the default (backward-compatible, no restrictions).
 //! `EnterpriseSyncPolicy` enforces ACLs, team membership, device limits, and audit trails.
+//! Entity ACLs are inherited along parent links: an entity's effective ACL is
+//! its own entries layered over those of its nearest ancestors, up to an
+//! inheritance break.
+//! Peer grants (and personal shares) may carry `GrantLimits`: an expiry and a
+//! maximum number of uses. Lapsed grants stop resolving immediately and are
+//! revoked by the policy's sweeper.
+//! `SelectiveSyncPolicy` limits what a device syncs to a declarative `SyncScope`.
 
 use crate::error::SyncError;
 use crate::policy_store::PolicyStore;
+use crate::signing::DeviceKeyRegistry;
 use async_trait::async_trait;
 use privstack_types::{EntityId, Event, EventPayload, PeerId};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 
 /// Unique identifier for a team.
@@ -21,6 +31,12 @@ impl TeamId {
     pub fn new() -> Self {
         Self(uuid::Uuid::new_v4())
     }
+
+    /// The entity whose ACL governs the team itself. It shares the team's
+    /// UUID; peers with `Admin` on it may change the team's membership.
+    pub fn admin_entity(&self) -> EntityId {
+        EntityId::from_uuid(self.0)
+    }
 }
 
 impl Default for TeamId {
@@ -77,6 +93,136 @@ impl fmt::Display for SyncRole {
     }
 }
 
+/// Maximum length of an ACL inheritance chain; further ancestors are ignored.
+pub const MAX_ACL_DEPTH: usize = 64;
+
+/// Read access to ACL state, shared by the live policy and the ACL
+/// applicator's replay view so both resolve roles identically.
+pub(crate) trait AclSource {
+    fn peer_role(&self, entity: EntityId, peer: PeerId) -> Option<SyncRole>;
+    /// Team grants on the entity itself (not inherited).
+    fn team_roles(&self, entity: EntityId) -> Vec<(TeamId, SyncRole)>;
+    fn default_role(&self, entity: EntityId) -> Option<SyncRole>;
+    fn is_member(&self, team: TeamId, peer: PeerId) -> bool;
+    fn parent(&self, entity: EntityId) -> Option<EntityId>;
+    /// False if the entity has an inheritance break.
+    fn inherits(&self, entity: EntityId) -> bool;
+}
+
+/// Returns `entity` followed by the ancestors it inherits ACL entries from,
+/// nearest first. The chain ends at an entity with an inheritance break, at a
+/// cycle, or at `MAX_ACL_DEPTH`.
+pub(crate) fn acl_ancestry(source: &impl AclSource, entity: EntityId) -> Vec<EntityId> {
+    let mut chain = vec![entity];
+    let mut current = entity;
+    while chain.len() < MAX_ACL_DEPTH && source.inherits(current) {
+        match source.parent(current) {
+            Some(parent) if !chain.contains(&parent) => {
+                chain.push(parent);
+                current = parent;
+            }
+            _ => break,
+        }
+    }
+    chain
+}
+
+/// Resolves a peer's role along the inheritance chain. Each subject's nearest
+/// entry wins; across subjects the usual precedence applies (peer entry, then
+/// highest team role, then default role).
+pub(crate) fn resolve_inherited_role(
+    source: &impl AclSource,
+    peer: PeerId,
+    entity: EntityId,
+) -> Option<SyncRole> {
+    let chain = acl_ancestry(source, entity);
+
+    // Peer-specific override takes precedence
+    if let Some(role) = chain.iter().find_map(|e| source.peer_role(*e, peer)) {
+        return Some(role);
+    }
+
+    // Team role (nearest entry per team, take highest)
+    let mut seen_teams = HashSet::new();
+    let mut best_team_role: Option<SyncRole> = None;
+    for e in &chain {
+        for (team, role) in source.team_roles(*e) {
+            if seen_teams.insert(team) && source.is_member(team, peer) {
+                best_team_role = best_team_role.max(Some(role));
+            }
+        }
+    }
+    if best_team_role.is_some() {
+        return best_team_role;
+    }
+
+    chain.iter().find_map(|e| source.default_role(*e))
+}
+
+/// Returns true if making `parent` the parent of `entity` would close a cycle.
+pub(crate) fn creates_acl_cycle(
+    entity: EntityId,
+    parent: EntityId,
+    parent_of: impl Fn(EntityId) -> Option<EntityId>,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut current = Some(parent);
+    while let Some(e) = current {
+        if e == entity {
+            return true;
+        }
+        if !visited.insert(e) {
+            return false;
+        }
+        current = parent_of(e);
+    }
+    false
+}
+
+/// Live policy state viewed as an [`AclSource`].
+struct LiveAcls<'a> {
+    acls: &'a HashMap<EntityId, EntityAcl>,
+    teams: &'a HashMap<TeamId, HashSet<PeerId>>,
+    parents: &'a HashMap<EntityId, EntityId>,
+    inheritance_breaks: &'a HashSet<EntityId>,
+    grant_limits: &'a HashMap<(EntityId, PeerId), GrantState>,
+    now: SystemTime,
+}
+
+impl AclSource for LiveAcls<'_> {
+    fn peer_role(&self, entity: EntityId, peer: PeerId) -> Option<SyncRole> {
+        let role = self.acls.get(&entity)?.peer_roles.get(&peer).copied()?;
+        // A lapsed grant no longer applies, even before the sweeper revokes it
+        match self.grant_limits.get(&(entity, peer)) {
+            Some(state) if state.is_lapsed(self.now) => None,
+            _ => Some(role),
+        }
+    }
+
+    fn team_roles(&self, entity: EntityId) -> Vec<(TeamId, SyncRole)> {
+        self.acls
+            .get(&entity)
+            .map(|acl| acl.team_roles.iter().map(|(t, r)| (*t, *r)).collect())
+            .unwrap_or_default()
+    }
+
+    fn default_role(&self, entity: EntityId) -> Option<SyncRole> {
+        self.acls.get(&entity)?.default_role
+    }
+
+    fn is_member(&self, team: TeamId, peer: PeerId) -> bool {
+        self.teams.get(&team).is_some_and(|members| members.contains(&peer))
+    }
+
+    fn parent(&self, entity: EntityId) -> Option<EntityId> {
+        self.parents.get(&entity).copied()
+    }
+
+    fn inherits(&self, entity: EntityId) -> bool {
+        !self.inheritance_breaks.contains(&entity)
+    }
+}
+
 /// Access control list for a single entity.
 pub struct EntityAcl {
     pub entity_id: EntityId,
@@ -114,6 +260,122 @@ impl EntityAcl {
     }
 }
 
+// ── Grant limits ────────────────────────────────────────────────
+
+/// Limits on a peer grant or personal share. A grant lapses at `expires_at`
+/// or once it has served `max_uses` sync requests, whichever comes first.
+///
+/// Use counts are not replicated: each device counts the requests it serves
+/// itself, so `max_uses` limits a grant per device, not across all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrantLimits {
+    pub expires_at: Option<SystemTime>,
+    pub max_uses: Option<u32>,
+}
+
+impl GrantLimits {
+    /// No limits: the grant lasts until revoked.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the expiry, truncated to the millisecond precision it is stored
+    /// and propagated at.
+    pub fn with_expires_at(mut self, at: SystemTime) -> Self {
+        let ms = crate::audit::millis(at);
+        self.expires_at = Some(std::time::UNIX_EPOCH + Duration::from_millis(ms));
+        self
+    }
+
+    pub fn with_expires_in(self, ttl: Duration) -> Self {
+        self.with_expires_at(SystemTime::now() + ttl)
+    }
+
+    pub fn with_max_uses(mut self, max_uses: u32) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    /// Returns true if neither an expiry nor a use limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.expires_at.is_none() && self.max_uses.is_none()
+    }
+}
+
+/// A limited grant together with how often it has been used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrantState {
+    pub limits: GrantLimits,
+    pub uses: u32,
+}
+
+impl GrantState {
+    pub fn new(limits: GrantLimits) -> Self {
+        Self { limits, uses: 0 }
+    }
+
+    /// Why the grant has lapsed at `now`, if it has.
+    pub fn lapse_reason(&self, now: SystemTime) -> Option<LapseReason> {
+        if self.limits.expires_at.is_some_and(|at| now >= at) {
+            Some(LapseReason::Expired)
+        } else if self.limits.max_uses.is_some_and(|max| self.uses >= max) {
+            Some(LapseReason::UsesExhausted)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_lapsed(&self, now: SystemTime) -> bool {
+        self.lapse_reason(now).is_some()
+    }
+}
+
+/// Why a limited grant lapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LapseReason {
+    Expired,
+    UsesExhausted,
+}
+
+impl fmt::Display for LapseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LapseReason::Expired => write!(f, "expired"),
+            LapseReason::UsesExhausted => write!(f, "use limit reached"),
+        }
+    }
+}
+
+/// A grant revoked by a sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LapsedGrant {
+    pub entity: EntityId,
+    pub peer: PeerId,
+    pub reason: LapseReason,
+    /// The role the grant gave (`None` for personal shares).
+    pub role: Option<SyncRole>,
+}
+
+impl LapsedGrant {
+    /// The role a peer needs on the entity for its revoke event to be
+    /// accepted: `Owner` for an `Owner` grant, `Admin` otherwise.
+    pub fn required_role(&self) -> SyncRole {
+        if self.role == Some(SyncRole::Owner) {
+            SyncRole::Owner
+        } else {
+            SyncRole::Admin
+        }
+    }
+
+    /// The ACL event payload that propagates the revocation.
+    pub fn revoke_payload(&self) -> EventPayload {
+        EventPayload::AclRevokePeer {
+            entity_id: self.entity.to_string(),
+            peer_id: self.peer.to_string(),
+        }
+    }
+}
+
 /// Action recorded in the audit log.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AuditAction {
@@ -122,6 +384,7 @@ pub enum AuditAction {
     EventSend,
     EventReceive,
     DeviceRegister,
+    AclChange,
 }
 
 impl fmt::Display for AuditAction {
@@ -132,6 +395,7 @@ impl fmt::Display for AuditAction {
             AuditAction::EventSend => write!(f, "event_send"),
             AuditAction::EventReceive => write!(f, "event_receive"),
             AuditAction::DeviceRegister => write!(f, "device_register"),
+            AuditAction::AclChange => write!(f, "acl_change"),
         }
     }
 }
@@ -246,6 +510,10 @@ pub struct EnterpriseSyncPolicy {
     pub acls: Arc<RwLock<HashMap<EntityId, EntityAcl>>>,
     /// Team membership: team → set of peers.
     pub teams: Arc<RwLock<HashMap<TeamId, HashSet<PeerId>>>>,
+    /// ACL inheritance: entity → the container it inherits entries from.
+    pub parents: Arc<RwLock<HashMap<EntityId, EntityId>>>,
+    /// Entities that do not inherit their ancestors' entries.
+    pub inheritance_breaks: Arc<RwLock<HashSet<EntityId>>>,
     /// Maximum devices per peer (from license tier).
     pub device_limits: Arc<RwLock<HashMap<PeerId, usize>>>,
     /// Currently active devices per peer.
@@ -254,6 +522,10 @@ pub struct EnterpriseSyncPolicy {
     pub known_peers: Arc<RwLock<HashSet<PeerId>>>,
     /// Audit log (in-memory).
     pub audit_log: Arc<RwLock<Vec<AuditEntry>>>,
+    /// Event signing keys of team members' devices.
+    pub device_keys: Arc<DeviceKeyRegistry>,
+    /// Limits on peer grants: (entity, peer) → limits and use count.
+    pub grant_limits: Arc<RwLock<HashMap<(EntityId, PeerId), GrantState>>>,
     /// Optional persistent store for audit + state.
     store: Option<Arc<PolicyStore>>,
     /// Maximum in-memory audit log entries before trimming.
@@ -265,10 +537,14 @@ impl EnterpriseSyncPolicy {
         Self {
             acls: Arc::new(RwLock::new(HashMap::new())),
             teams: Arc::new(RwLock::new(HashMap::new())),
+            parents: Arc::new(RwLock::new(HashMap::new())),
+            inheritance_breaks: Arc::new(RwLock::new(HashSet::new())),
             device_limits: Arc::new(RwLock::new(HashMap::new())),
             active_devices: Arc::new(RwLock::new(HashMap::new())),
             known_peers: Arc::new(RwLock::new(HashSet::new())),
             audit_log: Arc::new(RwLock::new(Vec::new())),
+            device_keys: Arc::new(DeviceKeyRegistry::new()),
+            grant_limits: Arc::new(RwLock::new(HashMap::new())),
             store: None,
             max_in_memory_log: 10_000,
         }
@@ -280,6 +556,12 @@ impl EnterpriseSyncPolicy {
         self
     }
 
+    /// Rejects events from peers without a registered device key.
+    pub fn with_required_signatures(mut self) -> Self {
+        self.device_keys = Arc::new(DeviceKeyRegistry::strict());
+        self
+    }
+
     /// Sets the maximum number of in-memory audit log entries.
     pub fn with_max_in_memory_log(mut self, max: usize) -> Self {
         self.max_in_memory_log = max;
@@ -318,6 +600,11 @@ impl EnterpriseSyncPolicy {
             acl.peer_roles.insert(peer_id, role);
         }
 
+        // Load grant limits
+        for (entity_id, peer_id, state) in store.load_grant_states()? {
+            policy.grant_limits.write().await.insert((entity_id, peer_id), state);
+        }
+
         // Load default roles
         for (entity_id, role) in store.load_default_roles()? {
             let mut acls = policy.acls.write().await;
@@ -344,6 +631,14 @@ impl EnterpriseSyncPolicy {
             teams.entry(team_id).or_default().insert(peer_id);
         }
 
+        // Load ACL inheritance
+        for (entity_id, parent_id) in store.load_acl_parents()? {
+            policy.parents.write().await.insert(entity_id, parent_id);
+        }
+        for entity_id in store.load_inheritance_breaks()? {
+            policy.inheritance_breaks.write().await.insert(entity_id);
+        }
+
         // Load device limits
         for (peer_id, max) in store.load_device_limits()? {
             policy.device_limits.write().await.insert(peer_id, max);
@@ -368,25 +663,58 @@ impl EnterpriseSyncPolicy {
                 .insert(device_id);
         }
 
+        // Load device signing keys
+        for (peer_id, key) in store.load_device_keys()? {
+            policy.device_keys.register(peer_id, key);
+        }
+
         Ok(policy)
     }
 
     // ── Persisting wrapper methods ───────────────────────────────
 
-    /// Grants a peer a role on an entity. Persists to store if attached.
+    /// Grants a peer a role on an entity, replacing any limits on an earlier
+    /// grant. Persists to store if attached.
     pub async fn grant_peer_role(
         &self,
         entity_id: EntityId,
         peer_id: PeerId,
         role: SyncRole,
+    ) {
+        self.grant_peer_role_limited(entity_id, peer_id, role, GrantLimits::new())
+            .await
+    }
+
+    /// Grants a peer a role on an entity that lapses per `limits`. The use
+    /// count starts at zero. Persists to store if attached.
+    pub async fn grant_peer_role_limited(
+        &self,
+        entity_id: EntityId,
+        peer_id: PeerId,
+        role: SyncRole,
+        limits: GrantLimits,
     ) {
         {
             let mut acls = self.acls.write().await;
             let acl = acls.entry(entity_id).or_insert_with(|| EntityAcl::new(entity_id));
             acl.peer_roles.insert(peer_id, role);
         }
+        let state = GrantState::new(limits);
+        {
+            let mut grant_limits = self.grant_limits.write().await;
+            if limits.is_unlimited() {
+                grant_limits.remove(&(entity_id, peer_id));
+            } else {
+                grant_limits.insert((entity_id, peer_id), state);
+            }
+        }
         if let Some(store) = &self.store {
             let _ = store.save_acl(&entity_id, &peer_id, role);
+            let _ = if limits.is_unlimited() {
+                store.remove_grant_state(&entity_id, &peer_id)
+            } else {
+                store.save_grant_state(&entity_id, &peer_id, &state)
+            };
         }
     }
 
@@ -398,11 +726,83 @@ impl EnterpriseSyncPolicy {
                 acl.peer_roles.remove(&peer_id);
             }
         }
+        self.grant_limits.write().await.remove(&(entity_id, peer_id));
         if let Some(store) = &self.store {
             let _ = store.remove_acl(&entity_id, &peer_id);
+            let _ = store.remove_grant_state(&entity_id, &peer_id);
+        }
+    }
+
+    /// Returns the limits and use count of a peer's grant on an entity, if
+    /// the grant is limited.
+    pub async fn grant_state(&self, entity_id: &EntityId, peer_id: &PeerId) -> Option<GrantState> {
+        self.grant_limits.read().await.get(&(*entity_id, *peer_id)).copied()
+    }
+
+    /// Counts a use of the limited grant `peer` holds its role on `entity`
+    /// through, if any (the nearest peer entry on the inheritance chain).
+    async fn record_grant_use(&self, peer: &PeerId, entity: &EntityId) {
+        let source = self
+            .with_live_acls(|live| {
+                acl_ancestry(live, *entity)
+                    .into_iter()
+                    .find(|e| live.peer_role(*e, *peer).is_some())
+            })
+            .await;
+        let Some(source) = source else {
+            return;
+        };
+        let state = {
+            let mut grant_limits = self.grant_limits.write().await;
+            match grant_limits.get_mut(&(source, *peer)) {
+                Some(state) => {
+                    state.uses = state.uses.saturating_add(1);
+                    *state
+                }
+                None => return,
+            }
+        };
+        if let Some(store) = &self.store {
+            let _ = store.save_grant_state(&source, peer, &state);
         }
     }
 
+    /// Revokes every limited grant that has expired or used up its uses and
+    /// audits each revocation. Returns the lapsed grants so the caller can
+    /// propagate them as revoke events (see [`LapsedGrant::revoke_payload`])
+    /// if it holds [`LapsedGrant::required_role`] on the entity.
+    pub async fn sweep_lapsed_grants(&self) -> Vec<LapsedGrant> {
+        let now = SystemTime::now();
+        let lapsed: Vec<LapsedGrant> = {
+            let acls = self.acls.read().await;
+            self.grant_limits
+                .read()
+                .await
+                .iter()
+                .filter_map(|((entity, peer), state)| {
+                    state.lapse_reason(now).map(|reason| LapsedGrant {
+                        entity: *entity,
+                        peer: *peer,
+                        reason,
+                        role: acls.get(entity).and_then(|acl| acl.peer_roles.get(peer)).copied(),
+                    })
+                })
+                .collect()
+        };
+        for grant in &lapsed {
+            self.revoke_peer_role(grant.entity, grant.peer).await;
+            self.log(
+                grant.peer,
+                Some(grant.entity),
+                AuditAction::AclChange,
+                AuditDecision::Allowed,
+                format!("grant lapsed ({}): peer {} role revoked", grant.reason, grant.peer),
+            )
+            .await;
+        }
+        lapsed
+    }
+
     /// Grants a team a role on an entity. Persists to store if attached.
     pub async fn grant_team_role(
         &self,
@@ -448,6 +848,73 @@ impl EnterpriseSyncPolicy {
         }
     }
 
+    /// Sets (or clears) the container an entity inherits ACL entries from.
+    /// Fails if the link would create a cycle. Persists to store if attached.
+    pub async fn set_parent(&self, entity_id: EntityId, parent_id: Option<EntityId>) -> Result<(), SyncError> {
+        {
+            let mut parents = self.parents.write().await;
+            match parent_id {
+                Some(parent) => {
+                    if creates_acl_cycle(entity_id, parent, |e| parents.get(&e).copied()) {
+                        return Err(SyncError::PolicyDenied {
+                            reason: format!("parent {parent} of {entity_id} would create an ACL cycle"),
+                        });
+                    }
+                    parents.insert(entity_id, parent);
+                }
+                None => {
+                    parents.remove(&entity_id);
+                }
+            }
+        }
+        if let Some(store) = &self.store {
+            let _ = match parent_id {
+                Some(parent) => store.save_acl_parent(&entity_id, &parent),
+                None => store.remove_acl_parent(&entity_id),
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the container an entity inherits ACL entries from.
+    pub async fn parent_of(&self, entity_id: &EntityId) -> Option<EntityId> {
+        self.parents.read().await.get(entity_id).copied()
+    }
+
+    /// Returns the entities whose parent is `entity_id`.
+    pub async fn children_of(&self, entity_id: &EntityId) -> Vec<EntityId> {
+        self.parents
+            .read()
+            .await
+            .iter()
+            .filter(|(_, parent)| *parent == entity_id)
+            .map(|(child, _)| *child)
+            .collect()
+    }
+
+    /// Stops an entity from inheriting its ancestors' ACL entries. Its own
+    /// entries (and its descendants' inheritance from it) are unaffected.
+    /// Persists to store if attached.
+    pub async fn break_inheritance(&self, entity_id: EntityId) {
+        self.inheritance_breaks.write().await.insert(entity_id);
+        if let Some(store) = &self.store {
+            let _ = store.save_inheritance_break(&entity_id);
+        }
+    }
+
+    /// Resumes inheritance for an entity. Persists to store if attached.
+    pub async fn restore_inheritance(&self, entity_id: EntityId) {
+        self.inheritance_breaks.write().await.remove(&entity_id);
+        if let Some(store) = &self.store {
+            let _ = store.remove_inheritance_break(&entity_id);
+        }
+    }
+
+    /// Returns false if the entity has an inheritance break.
+    pub async fn inherits(&self, entity_id: &EntityId) -> bool {
+        !self.inheritance_breaks.read().await.contains(entity_id)
+    }
+
     /// Adds a peer to a team. Persists to store if attached.
     pub async fn add_team_member(&self, team_id: TeamId, peer_id: PeerId) {
         {
@@ -460,16 +927,50 @@ impl EnterpriseSyncPolicy {
     }
 
     /// Removes a peer from a team. Persists to store if attached.
+    ///
+    /// A peer that leaves its last team loses its device keys, so events
+    /// signed by its devices are no longer accepted.
     pub async fn remove_team_member(&self, team_id: TeamId, peer_id: PeerId) {
-        {
+        let still_member = {
             let mut teams = self.teams.write().await;
             if let Some(members) = teams.get_mut(&team_id) {
                 members.remove(&peer_id);
             }
-        }
+            teams.values().any(|members| members.contains(&peer_id))
+        };
         if let Some(store) = &self.store {
             let _ = store.remove_team_member(&team_id.0.to_string(), &peer_id);
         }
+        if !still_member {
+            self.remove_device_keys(&peer_id).await;
+        }
+    }
+
+    /// Registers a device's event signing key for a team member. Returns
+    /// false (and registers nothing) if the peer is in no team.
+    pub async fn register_device_key(&self, peer_id: PeerId, device_key: [u8; 32]) -> bool {
+        let is_member = self
+            .teams
+            .read()
+            .await
+            .values()
+            .any(|members| members.contains(&peer_id));
+        if !is_member {
+            return false;
+        }
+        self.device_keys.register(peer_id, device_key);
+        if let Some(store) = &self.store {
+            let _ = store.save_device_key(&peer_id, &device_key);
+        }
+        true
+    }
+
+    /// Removes all device keys of a peer. Persists to store if attached.
+    pub async fn remove_device_keys(&self, peer_id: &PeerId) {
+        self.device_keys.remove_peer(peer_id);
+        if let Some(store) = &self.store {
+            let _ = store.remove_device_keys(peer_id);
+        }
     }
 
     /// Adds a peer to the known peers set. Persists to store if attached.
@@ -499,35 +1000,26 @@ impl EnterpriseSyncPolicy {
     /// Resolve the effective role for a peer on a given entity.
     /// Peer-specific role takes precedence over team role, which takes precedence over default.
     pub async fn resolve_role(&self, peer: &PeerId, entity: &EntityId) -> Option<SyncRole> {
-        let acls = self.acls.read().await;
-        let acl = match acls.get(entity) {
-            Some(a) => a,
-            None => return None,
-        };
-
-        // Peer-specific override takes precedence
-        if let Some(&role) = acl.peer_roles.get(peer) {
-            return Some(role);
-        }
+        self.with_live_acls(|live| resolve_inherited_role(live, *peer, *entity))
+            .await
+    }
 
-        // Team role (take highest)
+    /// Runs `f` against a consistent snapshot of the live ACL state.
+    async fn with_live_acls<R>(&self, f: impl FnOnce(&LiveAcls<'_>) -> R) -> R {
+        let acls = self.acls.read().await;
         let teams = self.teams.read().await;
-        let mut best_team_role: Option<SyncRole> = None;
-        for (team_id, role) in &acl.team_roles {
-            if let Some(members) = teams.get(team_id) {
-                if members.contains(peer) {
-                    best_team_role = Some(match best_team_role {
-                        Some(existing) if existing > *role => existing,
-                        _ => *role,
-                    });
-                }
-            }
-        }
-        if let Some(role) = best_team_role {
-            return Some(role);
-        }
-
-        acl.default_role
+        let parents = self.parents.read().await;
+        let inheritance_breaks = self.inheritance_breaks.read().await;
+        let grant_limits = self.grant_limits.read().await;
+        let live = LiveAcls {
+            acls: &acls,
+            teams: &teams,
+            parents: &parents,
+            inheritance_breaks: &inheritance_breaks,
+            grant_limits: &grant_limits,
+            now: SystemTime::now(),
+        };
+        f(&live)
     }
 
     /// Check if a peer can register a new device. Returns error if limit exceeded.
@@ -590,7 +1082,7 @@ impl EnterpriseSyncPolicy {
         active.remove(peer);
     }
 
-    async fn log(
+    pub(crate) async fn log(
         &self,
         peer: PeerId,
         entity: Option<EntityId>,
@@ -679,6 +1171,7 @@ impl SyncPolicy for EnterpriseSyncPolicy {
             let role = self.resolve_role(peer, eid).await;
             let decision = if role.is_some() {
                 allowed.push(*eid);
+                self.record_grant_use(peer, eid).await;
                 AuditDecision::Allowed
             } else {
                 AuditDecision::Denied
@@ -850,23 +1343,39 @@ impl SyncPolicy for EnterpriseSyncPolicy {
 pub struct PersonalSyncPolicy {
     /// peer → set of entities shared with that peer.
     peer_entities: RwLock<HashMap<PeerId, HashSet<EntityId>>>,
+    /// Limits on shares: (entity, peer) → limits and use count.
+    share_limits: RwLock<HashMap<(EntityId, PeerId), GrantState>>,
 }
 
 impl PersonalSyncPolicy {
     pub fn new() -> Self {
         Self {
             peer_entities: RwLock::new(HashMap::new()),
+            share_limits: RwLock::new(HashMap::new()),
         }
     }
 
-    /// Share an entity with a specific peer.
+    /// Share an entity with a specific peer, replacing any limits on an
+    /// earlier share.
     pub async fn share(&self, entity_id: EntityId, peer_id: PeerId) {
+        self.share_limited(entity_id, peer_id, GrantLimits::new()).await
+    }
+
+    /// Share an entity with a specific peer until the share lapses per
+    /// `limits`. The use count starts at zero.
+    pub async fn share_limited(&self, entity_id: EntityId, peer_id: PeerId, limits: GrantLimits) {
         self.peer_entities
             .write()
             .await
             .entry(peer_id)
             .or_default()
             .insert(entity_id);
+        let mut share_limits = self.share_limits.write().await;
+        if limits.is_unlimited() {
+            share_limits.remove(&(entity_id, peer_id));
+        } else {
+            share_limits.insert((entity_id, peer_id), GrantState::new(limits));
+        }
     }
 
     /// Unshare an entity from a specific peer.
@@ -877,23 +1386,77 @@ impl PersonalSyncPolicy {
             // Keep the empty set — an empty set means "no access for this peer",
             // whereas a missing key means "no policy configured" (allow all).
         }
+        self.share_limits.write().await.remove(&(entity_id, peer_id));
+    }
+
+    /// Returns the limits and use count of a share, if it is limited.
+    pub async fn share_state(&self, entity_id: &EntityId, peer_id: &PeerId) -> Option<GrantState> {
+        self.share_limits.read().await.get(&(*entity_id, *peer_id)).copied()
+    }
+
+    /// Unshares every limited share that has expired or used up its uses.
+    /// Returns the lapsed shares.
+    pub async fn sweep_lapsed_shares(&self) -> Vec<LapsedGrant> {
+        let now = SystemTime::now();
+        let lapsed: Vec<LapsedGrant> = self
+            .share_limits
+            .read()
+            .await
+            .iter()
+            .filter_map(|((entity, peer), state)| {
+                state.lapse_reason(now).map(|reason| LapsedGrant {
+                    entity: *entity,
+                    peer: *peer,
+                    reason,
+                    role: None,
+                })
+            })
+            .collect();
+        for share in &lapsed {
+            self.unshare(share.entity, share.peer).await;
+        }
+        lapsed
+    }
+
+    /// Returns true if the entity is shared with the peer and the share has
+    /// not lapsed.
+    async fn is_shared(
+        &self,
+        map: &HashMap<PeerId, HashSet<EntityId>>,
+        peer: &PeerId,
+        entity: &EntityId,
+    ) -> bool {
+        map.get(peer).is_some_and(|s| s.contains(entity))
+            && !self
+                .share_limits
+                .read()
+                .await
+                .get(&(*entity, *peer))
+                .is_some_and(|state| state.is_lapsed(SystemTime::now()))
     }
 
     /// Returns all peers that have access to a given entity.
     pub async fn shared_peers(&self, entity_id: &EntityId) -> Vec<PeerId> {
         let map = self.peer_entities.read().await;
-        map.iter()
-            .filter(|(_, entities)| entities.contains(entity_id))
-            .map(|(peer, _)| *peer)
-            .collect()
+        let mut peers = Vec::new();
+        for peer in map.keys() {
+            if self.is_shared(&map, peer, entity_id).await {
+                peers.push(*peer);
+            }
+        }
+        peers
     }
 
     /// Returns all entities shared with a given peer.
     pub async fn shared_entities(&self, peer_id: &PeerId) -> Vec<EntityId> {
         let map = self.peer_entities.read().await;
-        map.get(peer_id)
-            .map(|set| set.iter().copied().collect())
-            .unwrap_or_default()
+        let mut entities = Vec::new();
+        for entity in map.get(peer_id).into_iter().flatten() {
+            if self.is_shared(&map, peer_id, entity).await {
+                entities.push(*entity);
+            }
+        }
+        entities
     }
 
     /// Returns true if selective per-peer sharing is configured.
@@ -932,12 +1495,19 @@ impl SyncPolicy for PersonalSyncPolicy {
         if map.is_empty() {
             return Ok(entity_ids.to_vec());
         }
-        let peer_set = map.get(peer);
-        let allowed = entity_ids
-            .iter()
-            .filter(|eid| peer_set.is_some_and(|s| s.contains(eid)))
-            .copied()
-            .collect();
+        let mut allowed = Vec::new();
+        for eid in entity_ids {
+            if self.is_shared(&map, peer, eid).await {
+                allowed.push(*eid);
+            }
+        }
+        // Each request served counts as a use of a limited share
+        let mut share_limits = self.share_limits.write().await;
+        for eid in &allowed {
+            if let Some(state) = share_limits.get_mut(&(*eid, *peer)) {
+                state.uses = state.uses.saturating_add(1);
+            }
+        }
         Ok(allowed)
     }
 
@@ -952,7 +1522,7 @@ impl SyncPolicy for PersonalSyncPolicy {
         if map.is_empty() {
             return Ok(events.to_vec());
         }
-        let allowed = map.get(peer).is_some_and(|s| s.contains(entity));
+        let allowed = self.is_shared(&map, peer, entity).await;
         if allowed {
             Ok(events.to_vec())
         } else {
@@ -972,7 +1542,7 @@ impl SyncPolicy for PersonalSyncPolicy {
             return Ok(events.to_vec());
         }
         // Only accept events for entities we share with this peer
-        let allowed = map.get(peer).is_some_and(|s| s.contains(entity));
+        let allowed = self.is_shared(&map, peer, entity).await;
         if allowed {
             Ok(events.to_vec())
         } else {
@@ -986,14 +1556,449 @@ impl SyncPolicy for PersonalSyncPolicy {
     }
 }
 
-/// Extracts the target entity ID from an ACL event payload, if present.
+// ── SelectiveSyncPolicy ─────────────────────────────────────────
+
+/// Declarative description of what a device keeps in sync.
+///
+/// Empty include lists match everything; excludes win over includes.
+/// Pinned entities are always in scope — on-demand fetches land here so
+/// an entity the user opened keeps syncing afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncScope {
+    /// Entity types to sync. Empty = all types.
+    pub include_types: Vec<String>,
+    /// Entity types never synced.
+    pub exclude_types: Vec<String>,
+    /// Entities must carry at least one of these tags. Empty = any tags.
+    pub include_tags: Vec<String>,
+    /// Entities carrying any of these tags are not synced.
+    pub exclude_tags: Vec<String>,
+    /// Only sync entities modified within this many days.
+    pub modified_within_days: Option<u32>,
+    /// Entities always synced regardless of the filters.
+    pub pinned: HashSet<EntityId>,
+}
+
+impl SyncScope {
+    /// A scope that syncs everything.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Syncs only these entity types.
+    pub fn with_include_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Never syncs these entity types.
+    pub fn with_exclude_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Syncs only entities carrying at least one of these tags.
+    pub fn with_include_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Never syncs entities carrying any of these tags.
+    pub fn with_exclude_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Syncs only entities modified within the last `days` days.
+    pub fn with_modified_within_days(mut self, days: u32) -> Self {
+        self.modified_within_days = Some(days);
+        self
+    }
+
+    /// Returns true if `wider` may admit an entity this scope leaves out,
+    /// i.e. switching to it calls for a re-sync.
+    pub fn is_widened_by(&self, wider: &SyncScope) -> bool {
+        let dropped = |old: &[String], new: &[String]| old.iter().any(|v| !new.contains(v));
+        let loosened = |old: &[String], new: &[String]| {
+            !old.is_empty() && (new.is_empty() || dropped(new, old))
+        };
+        let window_grew = match (self.modified_within_days, wider.modified_within_days) {
+            (Some(_), None) => true,
+            (Some(old), Some(new)) => new > old,
+            (None, _) => false,
+        };
+        if self.is_unrestricted() {
+            return false;
+        }
+        wider.is_unrestricted()
+            || loosened(&self.include_types, &wider.include_types)
+            || dropped(&self.exclude_types, &wider.exclude_types)
+            || loosened(&self.include_tags, &wider.include_tags)
+            || dropped(&self.exclude_tags, &wider.exclude_tags)
+            || window_grew
+            || !wider.pinned.is_subset(&self.pinned)
+    }
+
+    /// Returns true if no filter is configured.
+    pub fn is_unrestricted(&self) -> bool {
+        self.include_types.is_empty()
+            && self.exclude_types.is_empty()
+            && self.include_tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.modified_within_days.is_none()
+    }
+
+    /// Returns true if an entity with the given attributes is in scope.
+    /// Attributes that are not known yet (`None`) do not filter.
+    pub fn matches(&self, entity_id: &EntityId, subject: &ScopeSubject, now_ms: u64) -> bool {
+        if self.pinned.contains(entity_id) || self.is_unrestricted() {
+            return true;
+        }
+
+        if let Some(entity_type) = &subject.entity_type {
+            if self.exclude_types.contains(entity_type) {
+                return false;
+            }
+            if !self.include_types.is_empty() && !self.include_types.contains(entity_type) {
+                return false;
+            }
+        }
+
+        if let Some(tags) = &subject.tags {
+            if tags.iter().any(|t| self.exclude_tags.contains(t)) {
+                return false;
+            }
+            if !self.include_tags.is_empty() && !tags.iter().any(|t| self.include_tags.contains(t))
+            {
+                return false;
+            }
+        }
+
+        if let Some(days) = self.modified_within_days {
+            let window_ms = u64::from(days) * 24 * 60 * 60 * 1000;
+            if subject.modified_at > 0 && subject.modified_at + window_ms < now_ms {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// What a scope knows about an entity, learned from its events.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeSubject {
+    /// Entity type, once any event carrying it has been seen.
+    pub entity_type: Option<String>,
+    /// The `tags` array of the latest entity data seen, if any.
+    pub tags: Option<Vec<String>>,
+    /// Newest event wall time (ms since the Unix epoch).
+    pub modified_at: u64,
+    /// Wall time of the event the tags came from.
+    data_at: u64,
+}
+
+impl ScopeSubject {
+    /// Folds an event into what we know about its entity.
+    pub fn observe(&mut self, event: &Event) {
+        let wall_time = event.timestamp.wall_time();
+        self.modified_at = self.modified_at.max(wall_time);
+
+        let (entity_type, json_data) = match &event.payload {
+            EventPayload::EntityCreated { entity_type, json_data }
+            | EventPayload::EntityUpdated { entity_type, json_data }
+            | EventPayload::FullSnapshot { entity_type, json_data }
+            | EventPayload::ConflictResolved { entity_type, json_data } => {
+                (entity_type, Some(json_data))
+            }
+            EventPayload::EntityDeleted { entity_type } => (entity_type, None),
+            _ => return,
+        };
+        self.entity_type = Some(entity_type.clone());
+
+        if let Some(json_data) = json_data {
+            if self.tags.is_none() || wall_time >= self.data_at {
+                self.tags = Some(extract_tags(json_data));
+                self.data_at = wall_time;
+            }
+        }
+    }
+}
+
+/// Reads the top-level `tags` string array of an entity's JSON data.
+fn extract_tags(json_data: &str) -> Vec<String> {
+    serde_json::from_str::<serde_json::Value>(json_data)
+        .ok()
+        .and_then(|data| {
+            data.get("tags").and_then(|tags| tags.as_array()).map(|tags| {
+                tags.iter()
+                    .filter_map(|t| t.as_str().map(str::to_string))
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Number of entities whose attributes a `SelectiveSyncPolicy` remembers
+/// by default.
+pub const DEFAULT_SCOPE_SUBJECTS: usize = 10_000;
+
+/// Entity attributes learned from events, forgotten oldest first once
+/// `capacity` entities are known.
+#[derive(Default)]
+struct Subjects {
+    capacity: usize,
+    known: HashMap<EntityId, ScopeSubject>,
+    /// Entities in the order they were first seen.
+    order: VecDeque<EntityId>,
+}
+
+impl Subjects {
+    fn entry(&mut self, entity_id: EntityId) -> &mut ScopeSubject {
+        if !self.known.contains_key(&entity_id) {
+            while self.order.len() >= self.capacity.max(1) {
+                let Some(oldest) = self.order.pop_front() else { break };
+                self.known.remove(&oldest);
+            }
+            self.order.push_back(entity_id);
+        }
+        self.known.entry(entity_id).or_default()
+    }
+}
+
+/// Per-device sync scopes, layered over another policy.
+///
+/// Incoming events are checked against the local scope, outgoing events
+/// against the scope the receiving peer advertised in its handshake.
+/// The inner policy (e.g. `PersonalSyncPolicy`) runs first.
+///
+/// What the policy learns about entities from their events is kept for at
+/// most [`DEFAULT_SCOPE_SUBJECTS`] entities (see `with_subject_capacity`).
+/// A forgotten entity's attributes are unknown until its next event, and
+/// unknown attributes do not filter.
+pub struct SelectiveSyncPolicy {
+    inner: Arc<dyn SyncPolicy>,
+    /// What this device keeps in sync.
+    scope: RwLock<SyncScope>,
+    /// Scopes advertised by peers. Peers without one get everything.
+    peer_scopes: RwLock<HashMap<PeerId, SyncScope>>,
+    /// Entity attributes learned from events passing through the policy.
+    subjects: RwLock<Subjects>,
+}
+
+impl SelectiveSyncPolicy {
+    /// Creates a policy keeping `scope` on this device, over a policy that
+    /// allows everything.
+    pub fn new(scope: SyncScope) -> Self {
+        Self {
+            inner: Arc::new(AllowAllPolicy),
+            scope: RwLock::new(scope),
+            peer_scopes: RwLock::new(HashMap::new()),
+            subjects: RwLock::new(Subjects {
+                capacity: DEFAULT_SCOPE_SUBJECTS,
+                ..Subjects::default()
+            }),
+        }
+    }
+
+    /// Layers the scopes over another policy.
+    pub fn with_inner(mut self, inner: Arc<dyn SyncPolicy>) -> Self {
+        self.inner = inner;
+        self
+    }
+
+    /// Sets how many entities' attributes the policy remembers.
+    pub fn with_subject_capacity(mut self, capacity: usize) -> Self {
+        self.subjects.get_mut().capacity = capacity;
+        self
+    }
+
+    /// Returns how many entities' attributes the policy currently remembers.
+    pub async fn known_subjects(&self) -> usize {
+        self.subjects.read().await.known.len()
+    }
+
+    /// Returns this device's scope.
+    pub async fn scope(&self) -> SyncScope {
+        self.scope.read().await.clone()
+    }
+
+    /// Replaces this device's scope. Returns true if the new scope may
+    /// admit entities the old one left out: peers should then be told,
+    /// and resend (see `SyncCommand::ScopeWidened`).
+    pub async fn set_scope(&self, scope: SyncScope) -> bool {
+        let mut current = self.scope.write().await;
+        let widened = current.is_widened_by(&scope);
+        *current = scope;
+        widened
+    }
+
+    /// Pins an entity so it syncs regardless of the scope filters.
+    pub async fn pin(&self, entity_id: EntityId) {
+        self.scope.write().await.pinned.insert(entity_id);
+    }
+
+    /// Removes an entity pin.
+    pub async fn unpin(&self, entity_id: &EntityId) {
+        self.scope.write().await.pinned.remove(entity_id);
+    }
+
+    /// Records the scope a peer advertised. `None` clears it.
+    pub async fn set_peer_scope(&self, peer_id: PeerId, scope: Option<SyncScope>) {
+        let mut scopes = self.peer_scopes.write().await;
+        match scope {
+            Some(scope) => scopes.insert(peer_id, scope),
+            None => scopes.remove(&peer_id),
+        };
+    }
+
+    /// Returns the scope a peer advertised, if any.
+    pub async fn peer_scope(&self, peer_id: &PeerId) -> Option<SyncScope> {
+        self.peer_scopes.read().await.get(peer_id).cloned()
+    }
+
+    /// Returns true if the entity is in this device's scope, judged by
+    /// the events seen for it so far.
+    pub async fn is_in_scope(&self, entity_id: &EntityId) -> bool {
+        let subject = self
+            .subjects
+            .read()
+            .await
+            .known
+            .get(entity_id)
+            .cloned()
+            .unwrap_or_default();
+        self.scope.read().await.matches(entity_id, &subject, now_ms())
+    }
+
+    /// Learns from `events` and returns what is known about the entity.
+    async fn observe(&self, entity_id: &EntityId, events: &[Event]) -> ScopeSubject {
+        let mut subjects = self.subjects.write().await;
+        let subject = subjects.entry(*entity_id);
+        for event in events.iter().filter(|e| e.entity_id == *entity_id) {
+            subject.observe(event);
+        }
+        subject.clone()
+    }
+}
+
+impl std::fmt::Debug for SelectiveSyncPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectiveSyncPolicy").finish()
+    }
+}
+
+#[async_trait]
+impl SyncPolicy for SelectiveSyncPolicy {
+    async fn on_handshake(&self, local: &PeerId, remote: &PeerId) -> Result<(), SyncError> {
+        self.inner.on_handshake(local, remote).await
+    }
+
+    async fn on_sync_request(
+        &self,
+        peer: &PeerId,
+        entity_ids: &[EntityId],
+    ) -> Result<Vec<EntityId>, SyncError> {
+        self.inner.on_sync_request(peer, entity_ids).await
+    }
+
+    async fn on_event_send(
+        &self,
+        peer: &PeerId,
+        entity: &EntityId,
+        events: &[Event],
+    ) -> Result<Vec<Event>, SyncError> {
+        let events = self.inner.on_event_send(peer, entity, events).await?;
+        if events.is_empty() {
+            return Ok(events);
+        }
+        let subject = self.observe(entity, &events).await;
+        let in_scope = match self.peer_scopes.read().await.get(peer) {
+            Some(scope) => scope.matches(entity, &subject, now_ms()),
+            None => true,
+        };
+        if in_scope {
+            Ok(events)
+        } else {
+            tracing::debug!("Entity {} is outside the sync scope of peer {}", entity, peer);
+            Ok(Vec::new())
+        }
+    }
+
+    async fn on_event_receive(
+        &self,
+        peer: &PeerId,
+        entity: &EntityId,
+        events: &[Event],
+    ) -> Result<Vec<Event>, SyncError> {
+        let events = self.inner.on_event_receive(peer, entity, events).await?;
+        if events.is_empty() {
+            return Ok(events);
+        }
+        let subject = self.observe(entity, &events).await;
+        if self.scope.read().await.matches(entity, &subject, now_ms()) {
+            Ok(events)
+        } else {
+            tracing::debug!("Dropping events for out-of-scope entity {} from {}", entity, peer);
+            Ok(Vec::new())
+        }
+    }
+
+    async fn on_device_check(
+        &self,
+        peer: &PeerId,
+        device_id: Option<&str>,
+    ) -> Result<(), SyncError> {
+        self.inner.on_device_check(peer, device_id).await
+    }
+
+    fn entities_for_peer(&self, peer: &PeerId) -> Option<Vec<EntityId>> {
+        self.inner.entities_for_peer(peer)
+    }
+}
+
+/// Extracts the target entity ID from an ACL event payload, if present. For
+/// team membership changes this is the team's admin entity.
 fn acl_target_entity(payload: &EventPayload) -> Option<EntityId> {
+    if let EventPayload::TeamAddPeer { team_id, .. } | EventPayload::TeamRemovePeer { team_id, .. } =
+        payload
+    {
+        return uuid::Uuid::parse_str(team_id).ok().map(|uuid| TeamId(uuid).admin_entity());
+    }
     let id_str = match payload {
         EventPayload::AclGrantPeer { entity_id, .. }
+        | EventPayload::AclGrantPeerLimited { entity_id, .. }
         | EventPayload::AclRevokePeer { entity_id, .. }
         | EventPayload::AclGrantTeam { entity_id, .. }
         | EventPayload::AclRevokeTeam { entity_id, .. }
-        | EventPayload::AclSetDefault { entity_id, .. } => Some(entity_id.as_str()),
+        | EventPayload::AclSetDefault { entity_id, .. }
+        | EventPayload::AclSetParent { entity_id, .. }
+        | EventPayload::AclBreakInheritance { entity_id }
+        | EventPayload::AclRestoreInheritance { entity_id } => Some(entity_id.as_str()),
         _ => None,
     };
     id_str.and_then(|s| s.parse::<EntityId>().ok())
@@ -1,16 +1,30 @@
 //! Persistent storage for enterprise sync policy state (ACLs, teams, audit log).
 //!
 //! Uses a separate SQLite file so policy data is isolated from entity/event stores.
+//! The audit log is hash-chained; see [`crate::audit`].
 
+use crate::audit::{
+    self, AuditCheckpoint, AuditExportFormat, AuditFilter, AuditIssue, AuditRecord,
+    AuditRetention, AuditVerification, StoredFields, GENESIS_HASH,
+};
 use crate::error::SyncError;
-use crate::policy::{AuditDecision, AuditEntry, AuditAction, SyncRole};
-use privstack_types::{EntityId, PeerId};
-use rusqlite::{params, Connection};
+use crate::policy::{AuditDecision, AuditEntry, AuditAction, GrantLimits, GrantState, SyncRole};
+use crate::signing::{verify_message, DeviceSigningKey};
+use privstack_types::{EntityId, Event, EventPayload, PeerId};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+/// Default number of audit entries between automatic checkpoints.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 1000;
+
 /// Persistent store for policy state backed by SQLite.
 pub struct PolicyStore {
     conn: Arc<Mutex<Connection>>,
+    /// Signs audit checkpoints, if set.
+    audit_signer: Option<DeviceSigningKey>,
+    /// Audit entries between automatic checkpoints (0 disables them).
+    checkpoint_interval: usize,
 }
 
 impl PolicyStore {
@@ -20,6 +34,8 @@ impl PolicyStore {
             .map_err(|e| SyncError::Storage(format!("failed to open policy store: {e}")))?;
         let store = Self {
             conn: Arc::new(Mutex::new(conn)),
+            audit_signer: None,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
         };
         store.init_schema()?;
         Ok(store)
@@ -31,11 +47,26 @@ impl PolicyStore {
             .map_err(|e| SyncError::Storage(format!("failed to open in-memory policy store: {e}")))?;
         let store = Self {
             conn: Arc::new(Mutex::new(conn)),
+            audit_signer: None,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
         };
         store.init_schema()?;
         Ok(store)
     }
 
+    /// Signs audit checkpoints with the given key.
+    pub fn with_audit_signer(mut self, key: DeviceSigningKey) -> Self {
+        self.audit_signer = Some(key);
+        self
+    }
+
+    /// Sets the number of audit entries between automatic checkpoints
+    /// (0 disables automatic checkpoints).
+    pub fn with_checkpoint_interval(mut self, interval: usize) -> Self {
+        self.checkpoint_interval = interval;
+        self
+    }
+
     fn init_schema(&self) -> Result<(), SyncError> {
         let conn = self.conn.lock().unwrap();
         conn.execute_batch(
@@ -69,6 +100,24 @@ impl PolicyStore {
                 UNIQUE(entity_id, team_id)
             );
 
+            CREATE TABLE IF NOT EXISTS acl_parents (
+                entity_id TEXT PRIMARY KEY,
+                parent_id TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS acl_inheritance_breaks (
+                entity_id TEXT PRIMARY KEY
+            );
+
+            CREATE TABLE IF NOT EXISTS acl_grant_limits (
+                entity_id TEXT NOT NULL,
+                peer_id TEXT NOT NULL,
+                expires_at INTEGER,
+                max_uses INTEGER,
+                uses INTEGER NOT NULL DEFAULT 0,
+                UNIQUE(entity_id, peer_id)
+            );
+
             CREATE TABLE IF NOT EXISTS teams (
                 team_id TEXT NOT NULL,
                 peer_id TEXT NOT NULL,
@@ -89,86 +138,105 @@ impl PolicyStore {
                 device_id TEXT NOT NULL,
                 UNIQUE(peer_id, device_id)
             );
+
+            CREATE TABLE IF NOT EXISTS device_keys (
+                peer_id TEXT NOT NULL,
+                device_key TEXT NOT NULL,
+                UNIQUE(peer_id, device_key)
+            );
+
+            CREATE TABLE IF NOT EXISTS audit_checkpoints (
+                seq INTEGER PRIMARY KEY,
+                hash TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                signer TEXT,
+                signature TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS audit_anchor (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                seq INTEGER NOT NULL,
+                hash TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS acl_history (
+                event_id TEXT PRIMARY KEY,
+                event TEXT NOT NULL,
+                prior TEXT NOT NULL,
+                accepted INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS acl_history_compacted (
+                register TEXT PRIMARY KEY,
+                event TEXT NOT NULL
+            );
             ",
         )
         .map_err(|e| SyncError::Storage(format!("failed to init policy schema: {e}")))?;
+
+        // Audit logs written before hash chaining gain the chain columns and
+        // have their existing rows sealed in order.
+        for column in ["prev_hash", "hash"] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('audit_log') WHERE name = ?1")
+                .and_then(|mut stmt| stmt.exists(params![column]))
+                .map_err(|e| SyncError::Storage(format!("failed to inspect audit schema: {e}")))?;
+            if !exists {
+                conn.execute(&format!("ALTER TABLE audit_log ADD COLUMN {column} TEXT"), [])
+                    .map_err(|e| SyncError::Storage(format!("failed to migrate audit schema: {e}")))?;
+            }
+        }
+        seal_unchained_audit_rows(&conn)?;
         Ok(())
     }
 
     // ── Audit log ────────────────────────────────────────────────
 
-    /// Saves an audit entry to the database.
+    /// Saves an audit entry to the database, appending it to the hash chain.
+    /// Writes a checkpoint every `checkpoint_interval` entries.
     pub fn save_audit_entry(&self, entry: &AuditEntry) -> Result<(), SyncError> {
         let conn = self.conn.lock().unwrap();
-        let ts = entry
-            .timestamp
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
-            .to_string();
+        let (head_seq, head_hash) = audit_head(&conn)?;
+        let seq = head_seq + 1;
+        let fields = StoredFields::from_entry(entry);
+        let hash = fields.hash(seq, &head_hash);
         conn.execute(
-            "INSERT INTO audit_log (peer, entity, action, decision, detail, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO audit_log (id, peer, entity, action, decision, detail, timestamp, prev_hash, hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
-                entry.peer.to_string(),
-                entry.entity.map(|e| e.to_string()),
-                entry.action.to_string(),
-                format!("{:?}", entry.decision),
-                entry.detail,
-                ts,
+                seq,
+                fields.peer,
+                fields.entity,
+                fields.action,
+                fields.decision,
+                fields.detail,
+                fields.timestamp,
+                hex::encode(head_hash),
+                hex::encode(hash),
             ],
         )
         .map_err(|e| SyncError::Storage(format!("failed to save audit entry: {e}")))?;
+
+        if self.checkpoint_interval > 0 && seq % self.checkpoint_interval as i64 == 0 {
+            self.write_checkpoint(&conn, seq, hash)?;
+        }
         Ok(())
     }
 
-    /// Loads audit log entries with pagination.
+    /// Loads audit log entries with pagination, newest first.
     pub fn load_audit_log(&self, limit: usize, offset: usize) -> Result<Vec<AuditEntry>, SyncError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn
-            .prepare("SELECT peer, entity, action, decision, detail, timestamp FROM audit_log ORDER BY id DESC LIMIT ?1 OFFSET ?2")
+            .prepare(&format!("{AUDIT_ROW_COLUMNS} FROM audit_log ORDER BY id DESC LIMIT ?1 OFFSET ?2"))
             .map_err(|e| SyncError::Storage(format!("failed to prepare audit query: {e}")))?;
 
-        let entries = stmt
-            .query_map(params![limit as i64, offset as i64], |row| {
-                let peer_str: String = row.get(0)?;
-                let entity_str: Option<String> = row.get(1)?;
-                let action_str: String = row.get(2)?;
-                let decision_str: String = row.get(3)?;
-                let detail: String = row.get(4)?;
-                let ts_str: String = row.get(5)?;
-
-                Ok((peer_str, entity_str, action_str, decision_str, detail, ts_str))
-            })
+        let rows = stmt
+            .query_map(params![limit as i64, offset as i64], AuditRow::from_row)
             .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
 
         let mut result = Vec::new();
-        for row in entries {
-            let (peer_str, entity_str, action_str, decision_str, detail, ts_str) =
-                row.map_err(|e| SyncError::Storage(format!("failed to read audit row: {e}")))?;
-
-            let peer: PeerId = peer_str
-                .parse()
-                .map_err(|e| SyncError::Storage(format!("invalid peer_id in audit: {e}")))?;
-            let entity: Option<EntityId> = match entity_str {
-                Some(s) => Some(
-                    s.parse()
-                        .map_err(|e| SyncError::Storage(format!("invalid entity_id in audit: {e}")))?,
-                ),
-                None => None,
-            };
-            let action = parse_audit_action(&action_str);
-            let decision = parse_audit_decision(&decision_str);
-            let ts_millis: u128 = ts_str.parse().unwrap_or(0);
-            let timestamp = std::time::UNIX_EPOCH + std::time::Duration::from_millis(ts_millis as u64);
-
-            result.push(AuditEntry {
-                peer,
-                entity,
-                action,
-                decision,
-                detail,
-                timestamp,
-            });
+        for row in rows {
+            let row = row.map_err(|e| SyncError::Storage(format!("failed to read audit row: {e}")))?;
+            result.push(row.entry()?);
         }
         Ok(result)
     }
@@ -182,6 +250,276 @@ impl PolicyStore {
         Ok(count as usize)
     }
 
+    /// Loads the audit records matching a filter, oldest first.
+    pub fn load_audit_records(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>, SyncError> {
+        let mut clauses = Vec::new();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(peer) = &filter.peer {
+            clauses.push("peer = ?");
+            values.push(Box::new(peer.to_string()));
+        }
+        if let Some(entity) = &filter.entity {
+            clauses.push("entity = ?");
+            values.push(Box::new(entity.to_string()));
+        }
+        if let Some(action) = &filter.action {
+            clauses.push("action = ?");
+            values.push(Box::new(action.to_string()));
+        }
+        if let Some(since) = filter.since {
+            clauses.push("CAST(timestamp AS INTEGER) >= ?");
+            values.push(Box::new(audit::millis(since) as i64));
+        }
+        if let Some(until) = filter.until {
+            clauses.push("CAST(timestamp AS INTEGER) < ?");
+            values.push(Box::new(audit::millis(until) as i64));
+        }
+        let where_clause = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!("{AUDIT_ROW_COLUMNS} FROM audit_log{where_clause} ORDER BY id"))
+            .map_err(|e| SyncError::Storage(format!("failed to prepare audit query: {e}")))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), AuditRow::from_row)
+            .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let row = row.map_err(|e| SyncError::Storage(format!("failed to read audit row: {e}")))?;
+            result.push(AuditRecord {
+                seq: row.seq,
+                entry: row.entry()?,
+                prev_hash: row.prev_hash.as_deref().and_then(decode_hash).unwrap_or_default(),
+                hash: row.hash.as_deref().and_then(decode_hash).unwrap_or_default(),
+            });
+        }
+        Ok(result)
+    }
+
+    /// Exports the audit records matching a filter. Returns the number of
+    /// records written.
+    pub fn export_audit_log<W: std::io::Write>(
+        &self,
+        filter: &AuditFilter,
+        format: AuditExportFormat,
+        out: &mut W,
+    ) -> Result<usize, SyncError> {
+        let records = self.load_audit_records(filter)?;
+        audit::write_records(&records, format, out)?;
+        Ok(records.len())
+    }
+
+    /// Records a checkpoint of the current audit chain head. Returns `None`
+    /// if the log is empty.
+    pub fn checkpoint_audit_log(&self) -> Result<Option<AuditCheckpoint>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let head = conn
+            .query_row("SELECT id, hash FROM audit_log ORDER BY id DESC LIMIT 1", [], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+            })
+            .optional()
+            .map_err(|e| SyncError::Storage(format!("failed to read audit head: {e}")))?;
+        let Some((seq, hash)) = head else {
+            return Ok(None);
+        };
+        let hash = hash.as_deref().and_then(decode_hash).unwrap_or_default();
+        self.write_checkpoint(&conn, seq, hash).map(Some)
+    }
+
+    /// Loads all audit checkpoints, oldest first.
+    pub fn load_audit_checkpoints(&self) -> Result<Vec<AuditCheckpoint>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        load_checkpoints(&conn)
+    }
+
+    /// Verifies the audit hash chain, its checkpoints and retention anchor.
+    ///
+    /// Checkpoint signatures are always checked when present. If
+    /// `trusted_signers` is non-empty, every checkpoint must also be signed by
+    /// one of those keys.
+    pub fn verify_audit_log(&self, trusted_signers: &[[u8; 32]]) -> Result<AuditVerification, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let anchor = load_anchor(&conn)?;
+        let mut report = AuditVerification::default();
+        let mut hashes = HashMap::new();
+
+        let (mut prev_seq, mut prev_hash) = anchor.unwrap_or((0, GENESIS_HASH));
+        let mut stmt = conn
+            .prepare(&format!("{AUDIT_ROW_COLUMNS} FROM audit_log ORDER BY id"))
+            .map_err(|e| SyncError::Storage(format!("failed to prepare audit query: {e}")))?;
+        let rows = stmt
+            .query_map([], AuditRow::from_row)
+            .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+        for row in rows {
+            let row = row.map_err(|e| SyncError::Storage(format!("failed to read audit row: {e}")))?;
+            if row.seq != prev_seq + 1 {
+                report.issues.push(AuditIssue::Gap { after: prev_seq, next: row.seq });
+            }
+            let stored_prev = row.prev_hash.as_deref().and_then(decode_hash);
+            let stored_hash = row.hash.as_deref().and_then(decode_hash);
+            if stored_prev != Some(prev_hash) {
+                report.issues.push(AuditIssue::BrokenLink { seq: row.seq });
+            }
+            let expected = row.fields.hash(row.seq, &stored_prev.unwrap_or_default());
+            if stored_hash != Some(expected) {
+                report.issues.push(AuditIssue::HashMismatch { seq: row.seq });
+            }
+            prev_seq = row.seq;
+            prev_hash = stored_hash.unwrap_or_default();
+            hashes.insert(row.seq, prev_hash);
+            report.entries_checked += 1;
+        }
+        if report.entries_checked > 0 {
+            report.head = Some((prev_seq, prev_hash));
+        }
+
+        let mut anchor_checkpointed = false;
+        for checkpoint in load_checkpoints(&conn)? {
+            let seq = checkpoint.seq;
+            match (&checkpoint.signer, &checkpoint.signature) {
+                (Some(signer), Some(signature)) => {
+                    if verify_message(signer, &checkpoint.signing_bytes(), signature).is_err() {
+                        report.issues.push(AuditIssue::BadCheckpointSignature { seq });
+                    } else if !trusted_signers.is_empty() && !trusted_signers.contains(signer) {
+                        report.issues.push(AuditIssue::UntrustedCheckpoint { seq });
+                    }
+                }
+                _ if !trusted_signers.is_empty() => {
+                    report.issues.push(AuditIssue::UntrustedCheckpoint { seq });
+                }
+                _ => {}
+            }
+
+            match anchor {
+                Some((anchor_seq, anchor_hash)) if anchor_seq == seq => {
+                    anchor_checkpointed = true;
+                    if checkpoint.hash != anchor_hash {
+                        report.issues.push(AuditIssue::AnchorMismatch { seq });
+                    }
+                }
+                _ if seq > prev_seq => {
+                    report.issues.push(AuditIssue::Truncated { checkpoint_seq: seq, last_seq: prev_seq });
+                }
+                _ => {
+                    if hashes.get(&seq) != Some(&checkpoint.hash) {
+                        report.issues.push(AuditIssue::CheckpointMismatch { seq });
+                    }
+                }
+            }
+            report.checkpoints_checked += 1;
+        }
+        if let Some((anchor_seq, _)) = anchor {
+            if !anchor_checkpointed {
+                report.issues.push(AuditIssue::AnchorMismatch { seq: anchor_seq });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Prunes audit entries beyond the retention limits. Returns the number of
+    /// entries removed.
+    ///
+    /// The last pruned entry becomes the chain's anchor and is checkpointed,
+    /// so the remaining entries stay verifiable. Pruned entries can no longer
+    /// be checked; run [`Self::verify_audit_log`] first if that matters.
+    pub fn prune_audit_log(&self, retention: &AuditRetention) -> Result<usize, SyncError> {
+        let mut conn = self.conn.lock().unwrap();
+        let mut cutoff: i64 = 0;
+        if let Some(max_age) = retention.max_age {
+            let threshold = std::time::SystemTime::now()
+                .checked_sub(max_age)
+                .unwrap_or(std::time::UNIX_EPOCH);
+            let seq: Option<i64> = conn
+                .query_row(
+                    "SELECT MAX(id) FROM audit_log WHERE CAST(timestamp AS INTEGER) < ?1",
+                    params![audit::millis(threshold) as i64],
+                    |row| row.get(0),
+                )
+                .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+            cutoff = cutoff.max(seq.unwrap_or(0));
+        }
+        if let Some(max_entries) = retention.max_entries {
+            let (count, last): (i64, Option<i64>) = conn
+                .query_row("SELECT COUNT(*), MAX(id) FROM audit_log", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+            if count > max_entries as i64 {
+                let seq: Option<i64> = conn
+                    .query_row(
+                        "SELECT id FROM audit_log ORDER BY id LIMIT 1 OFFSET ?1",
+                        params![count - max_entries as i64 - 1],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+                cutoff = cutoff.max(seq.or(last).unwrap_or(0));
+            }
+        }
+
+        let anchor_row = conn
+            .query_row(
+                "SELECT id, hash FROM audit_log WHERE id <= ?1 ORDER BY id DESC LIMIT 1",
+                params![cutoff],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()
+            .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?;
+        let Some((anchor_seq, anchor_hash)) = anchor_row else {
+            return Ok(0);
+        };
+        let anchor_hash = anchor_hash.as_deref().and_then(decode_hash).unwrap_or_default();
+
+        let tx = conn
+            .transaction()
+            .map_err(|e| SyncError::Storage(format!("failed to begin audit prune: {e}")))?;
+        self.write_checkpoint(&tx, anchor_seq, anchor_hash)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO audit_anchor (id, seq, hash) VALUES (1, ?1, ?2)",
+            params![anchor_seq, hex::encode(anchor_hash)],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save audit anchor: {e}")))?;
+        let removed = tx
+            .execute("DELETE FROM audit_log WHERE id <= ?1", params![anchor_seq])
+            .map_err(|e| SyncError::Storage(format!("failed to prune audit log: {e}")))?;
+        tx.execute("DELETE FROM audit_checkpoints WHERE seq < ?1", params![anchor_seq])
+            .map_err(|e| SyncError::Storage(format!("failed to prune audit checkpoints: {e}")))?;
+        tx.commit()
+            .map_err(|e| SyncError::Storage(format!("failed to commit audit prune: {e}")))?;
+        Ok(removed)
+    }
+
+    fn write_checkpoint(&self, conn: &Connection, seq: i64, hash: [u8; 32]) -> Result<AuditCheckpoint, SyncError> {
+        let mut checkpoint = AuditCheckpoint {
+            seq,
+            hash,
+            timestamp: std::time::SystemTime::now(),
+            signer: None,
+            signature: None,
+        };
+        if let Some(key) = &self.audit_signer {
+            checkpoint.signature = Some(key.sign_message(&checkpoint.signing_bytes()));
+            checkpoint.signer = Some(key.public_key());
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO audit_checkpoints (seq, hash, timestamp, signer, signature) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                seq,
+                hex::encode(hash),
+                audit::millis(checkpoint.timestamp).to_string(),
+                checkpoint.signer.map(hex::encode),
+                checkpoint.signature.as_ref().map(hex::encode),
+            ],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save audit checkpoint: {e}")))?;
+        Ok(checkpoint)
+    }
+
     // ── ACL persistence ──────────────────────────────────────────
 
     /// Saves a peer-level ACL entry.
@@ -329,6 +667,258 @@ impl PolicyStore {
         Ok(result)
     }
 
+    // ── ACL inheritance ──────────────────────────────────────────
+
+    /// Saves the parent an entity inherits ACL entries from.
+    pub fn save_acl_parent(&self, entity_id: &EntityId, parent_id: &EntityId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO acl_parents (entity_id, parent_id) VALUES (?1, ?2)",
+            params![entity_id.to_string(), parent_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save acl parent: {e}")))?;
+        Ok(())
+    }
+
+    /// Removes an entity's parent link.
+    pub fn remove_acl_parent(&self, entity_id: &EntityId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM acl_parents WHERE entity_id = ?1",
+            params![entity_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to remove acl parent: {e}")))?;
+        Ok(())
+    }
+
+    /// Loads all parent links. Returns (entity_id, parent_id) tuples.
+    pub fn load_acl_parents(&self) -> Result<Vec<(EntityId, EntityId)>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT entity_id, parent_id FROM acl_parents")
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let eid: String = row.get(0)?;
+                let pid: String = row.get(1)?;
+                Ok((eid, pid))
+            })
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (eid, pid) = row.map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let entity_id: EntityId = eid.parse().map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let parent_id: EntityId = pid.parse().map_err(|e| SyncError::Storage(format!("{e}")))?;
+            result.push((entity_id, parent_id));
+        }
+        Ok(result)
+    }
+
+    /// Records that an entity does not inherit its ancestors' ACL entries.
+    pub fn save_inheritance_break(&self, entity_id: &EntityId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO acl_inheritance_breaks (entity_id) VALUES (?1)",
+            params![entity_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save inheritance break: {e}")))?;
+        Ok(())
+    }
+
+    /// Removes an entity's inheritance break.
+    pub fn remove_inheritance_break(&self, entity_id: &EntityId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM acl_inheritance_breaks WHERE entity_id = ?1",
+            params![entity_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to remove inheritance break: {e}")))?;
+        Ok(())
+    }
+
+    /// Loads all entities with an inheritance break.
+    pub fn load_inheritance_breaks(&self) -> Result<Vec<EntityId>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT entity_id FROM acl_inheritance_breaks")
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let eid = row.map_err(|e| SyncError::Storage(format!("{e}")))?;
+            result.push(eid.parse().map_err(|e| SyncError::Storage(format!("{e}")))?);
+        }
+        Ok(result)
+    }
+
+    // ── ACL history ──────────────────────────────────────────────
+
+    /// Saves an ACL event of the causal history, with the value its register
+    /// held before the event (as the payload that would set it) and whether
+    /// the event was accepted.
+    pub fn save_acl_history_event(
+        &self,
+        event: &Event,
+        prior: &EventPayload,
+        accepted: bool,
+    ) -> Result<(), SyncError> {
+        let event_json = serde_json::to_string(event)?;
+        let prior_json = serde_json::to_string(prior)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO acl_history (event_id, event, prior, accepted) VALUES (?1, ?2, ?3, ?4)",
+            params![event.id.to_string(), event_json, prior_json, accepted],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save acl history event: {e}")))?;
+        Ok(())
+    }
+
+    /// Loads the causal ACL history. Returns (event, prior, accepted) tuples
+    /// in no particular order.
+    pub fn load_acl_history(&self) -> Result<Vec<(Event, EventPayload, bool)>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT event, prior, accepted FROM acl_history")
+            .map_err(|e| SyncError::Storage(format!("failed to prepare acl history query: {e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let event: String = row.get(0)?;
+                let prior: String = row.get(1)?;
+                let accepted: bool = row.get(2)?;
+                Ok((event, prior, accepted))
+            })
+            .map_err(|e| SyncError::Storage(format!("failed to query acl history: {e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (event, prior, accepted) =
+                row.map_err(|e| SyncError::Storage(format!("failed to read acl history row: {e}")))?;
+            let event = serde_json::from_str(&event)?;
+            let prior = serde_json::from_str(&prior)?;
+            result.push((event, prior, accepted));
+        }
+        Ok(result)
+    }
+
+    /// Drops an event from the causal ACL history once it is below the
+    /// compaction frontier. An accepted event is kept as the last compacted
+    /// write to its `register`.
+    pub fn compact_acl_history_event(
+        &self,
+        event: &Event,
+        register: Option<&str>,
+    ) -> Result<(), SyncError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| SyncError::Storage(format!("failed to begin acl history compaction: {e}")))?;
+        tx.execute("DELETE FROM acl_history WHERE event_id = ?1", params![event.id.to_string()])
+            .map_err(|e| SyncError::Storage(format!("failed to compact acl history: {e}")))?;
+        if let Some(register) = register {
+            let event_json = serde_json::to_string(event)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO acl_history_compacted (register, event) VALUES (?1, ?2)",
+                params![register, event_json],
+            )
+            .map_err(|e| SyncError::Storage(format!("failed to save compacted acl write: {e}")))?;
+        }
+        tx.commit()
+            .map_err(|e| SyncError::Storage(format!("failed to commit acl history compaction: {e}")))?;
+        Ok(())
+    }
+
+    /// Loads the last compacted write to each ACL register.
+    pub fn load_acl_compacted_writes(&self) -> Result<Vec<Event>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT event FROM acl_history_compacted")
+            .map_err(|e| SyncError::Storage(format!("failed to prepare acl history query: {e}")))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| SyncError::Storage(format!("failed to query compacted acl writes: {e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let event = row.map_err(|e| SyncError::Storage(format!("failed to read acl history row: {e}")))?;
+            result.push(serde_json::from_str(&event)?);
+        }
+        Ok(result)
+    }
+
+    // ── Grant limits ─────────────────────────────────────────────
+
+    /// Saves the limits and use count of a peer's grant on an entity.
+    pub fn save_grant_state(
+        &self,
+        entity_id: &EntityId,
+        peer_id: &PeerId,
+        state: &GrantState,
+    ) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO acl_grant_limits (entity_id, peer_id, expires_at, max_uses, uses)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entity_id.to_string(),
+                peer_id.to_string(),
+                state.limits.expires_at.map(|t| audit::millis(t) as i64),
+                state.limits.max_uses,
+                state.uses,
+            ],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to save grant limits: {e}")))?;
+        Ok(())
+    }
+
+    /// Removes the limits of a peer's grant on an entity.
+    pub fn remove_grant_state(&self, entity_id: &EntityId, peer_id: &PeerId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM acl_grant_limits WHERE entity_id = ?1 AND peer_id = ?2",
+            params![entity_id.to_string(), peer_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to remove grant limits: {e}")))?;
+        Ok(())
+    }
+
+    /// Loads all limited grants. Returns (entity_id, peer_id, state) tuples.
+    pub fn load_grant_states(&self) -> Result<Vec<(EntityId, PeerId, GrantState)>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT entity_id, peer_id, expires_at, max_uses, uses FROM acl_grant_limits")
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let eid: String = row.get(0)?;
+                let pid: String = row.get(1)?;
+                let expires_at: Option<i64> = row.get(2)?;
+                let max_uses: Option<u32> = row.get(3)?;
+                let uses: u32 = row.get(4)?;
+                Ok((eid, pid, expires_at, max_uses, uses))
+            })
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (eid, pid, expires_at, max_uses, uses) =
+                row.map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let entity_id: EntityId = eid.parse().map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let peer_id: PeerId = pid.parse().map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let limits = GrantLimits {
+                expires_at: expires_at.map(|ms| {
+                    std::time::UNIX_EPOCH + std::time::Duration::from_millis(ms as u64)
+                }),
+                max_uses,
+            };
+            result.push((entity_id, peer_id, GrantState { limits, uses }));
+        }
+        Ok(result)
+    }
+
     // ── Team membership ──────────────────────────────────────────
 
     /// Saves a team membership entry.
@@ -502,6 +1092,211 @@ impl PolicyStore {
         }
         Ok(result)
     }
+
+    // ── Device signing keys ──────────────────────────────────────
+
+    /// Saves an event signing key for a peer.
+    pub fn save_device_key(&self, peer_id: &PeerId, device_key: &[u8; 32]) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO device_keys (peer_id, device_key) VALUES (?1, ?2)",
+            params![peer_id.to_string(), hex::encode(device_key)],
+        )
+        .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        Ok(())
+    }
+
+    /// Removes all event signing keys of a peer.
+    pub fn remove_device_keys(&self, peer_id: &PeerId) -> Result<(), SyncError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM device_keys WHERE peer_id = ?1",
+            params![peer_id.to_string()],
+        )
+        .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        Ok(())
+    }
+
+    /// Loads all event signing keys. Returns (peer_id, device_key) tuples.
+    pub fn load_device_keys(&self) -> Result<Vec<(PeerId, [u8; 32])>, SyncError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT peer_id, device_key FROM device_keys")
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let pid: String = row.get(0)?;
+                let key: String = row.get(1)?;
+                Ok((pid, key))
+            })
+            .map_err(|e| SyncError::Storage(format!("{e}")))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (pid_str, key_hex) = row.map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let peer_id: PeerId = pid_str.parse().map_err(|e| SyncError::Storage(format!("{e}")))?;
+            let key: [u8; 32] = hex::decode(&key_hex)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| SyncError::Storage(format!("invalid device key: {key_hex}")))?;
+            result.push((peer_id, key));
+        }
+        Ok(result)
+    }
+}
+
+// ── Audit chain helpers ──────────────────────────────────────────
+
+const AUDIT_ROW_COLUMNS: &str =
+    "SELECT id, peer, entity, action, decision, detail, timestamp, prev_hash, hash";
+
+/// An audit row as stored, selected with [`AUDIT_ROW_COLUMNS`].
+struct AuditRow {
+    seq: i64,
+    fields: StoredFields,
+    prev_hash: Option<String>,
+    hash: Option<String>,
+}
+
+impl AuditRow {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            seq: row.get(0)?,
+            fields: StoredFields {
+                peer: row.get(1)?,
+                entity: row.get(2)?,
+                action: row.get(3)?,
+                decision: row.get(4)?,
+                detail: row.get(5)?,
+                timestamp: row.get(6)?,
+            },
+            prev_hash: row.get(7)?,
+            hash: row.get(8)?,
+        })
+    }
+
+    fn entry(&self) -> Result<AuditEntry, SyncError> {
+        let fields = &self.fields;
+        let peer: PeerId = fields
+            .peer
+            .parse()
+            .map_err(|e| SyncError::Storage(format!("invalid peer_id in audit: {e}")))?;
+        let entity: Option<EntityId> = match &fields.entity {
+            Some(s) => Some(
+                s.parse()
+                    .map_err(|e| SyncError::Storage(format!("invalid entity_id in audit: {e}")))?,
+            ),
+            None => None,
+        };
+        let ts_millis: u64 = fields.timestamp.parse().unwrap_or(0);
+        Ok(AuditEntry {
+            peer,
+            entity,
+            action: parse_audit_action(&fields.action),
+            decision: parse_audit_decision(&fields.decision),
+            detail: fields.detail.clone(),
+            timestamp: std::time::UNIX_EPOCH + std::time::Duration::from_millis(ts_millis),
+        })
+    }
+}
+
+fn decode_hash(s: &str) -> Option<[u8; 32]> {
+    hex::decode(s).ok().and_then(|bytes| bytes.try_into().ok())
+}
+
+/// Returns the sequence number and hash the next audit entry chains onto:
+/// the newest entry, else the retention anchor, else genesis.
+fn audit_head(conn: &Connection) -> Result<(i64, [u8; 32]), SyncError> {
+    let last = conn
+        .query_row("SELECT id, hash FROM audit_log ORDER BY id DESC LIMIT 1", [], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .optional()
+        .map_err(|e| SyncError::Storage(format!("failed to read audit head: {e}")))?;
+    match last {
+        Some((seq, hash)) => Ok((seq, hash.as_deref().and_then(decode_hash).unwrap_or_default())),
+        None => Ok(load_anchor(conn)?.unwrap_or((0, GENESIS_HASH))),
+    }
+}
+
+fn load_anchor(conn: &Connection) -> Result<Option<(i64, [u8; 32])>, SyncError> {
+    let anchor = conn
+        .query_row("SELECT seq, hash FROM audit_anchor WHERE id = 1", [], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .optional()
+        .map_err(|e| SyncError::Storage(format!("failed to read audit anchor: {e}")))?;
+    Ok(anchor.map(|(seq, hash)| (seq, decode_hash(&hash).unwrap_or_default())))
+}
+
+fn load_checkpoints(conn: &Connection) -> Result<Vec<AuditCheckpoint>, SyncError> {
+    let mut stmt = conn
+        .prepare("SELECT seq, hash, timestamp, signer, signature FROM audit_checkpoints ORDER BY seq")
+        .map_err(|e| SyncError::Storage(format!("failed to prepare checkpoint query: {e}")))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })
+        .map_err(|e| SyncError::Storage(format!("failed to query checkpoints: {e}")))?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let (seq, hash, ts, signer, signature) =
+            row.map_err(|e| SyncError::Storage(format!("failed to read checkpoint row: {e}")))?;
+        result.push(AuditCheckpoint {
+            seq,
+            hash: decode_hash(&hash).unwrap_or_default(),
+            timestamp: std::time::UNIX_EPOCH
+                + std::time::Duration::from_millis(ts.parse().unwrap_or(0)),
+            signer: signer.as_deref().and_then(decode_hash),
+            signature: signature.and_then(|s| hex::decode(s).ok()),
+        });
+    }
+    Ok(result)
+}
+
+/// Chains audit rows that predate hash chaining onto the current head, in
+/// row order.
+fn seal_unchained_audit_rows(conn: &Connection) -> Result<(), SyncError> {
+    let mut stmt = conn
+        .prepare(&format!("{AUDIT_ROW_COLUMNS} FROM audit_log WHERE hash IS NULL ORDER BY id"))
+        .map_err(|e| SyncError::Storage(format!("failed to prepare audit query: {e}")))?;
+    let unsealed = stmt
+        .query_map([], AuditRow::from_row)
+        .map_err(|e| SyncError::Storage(format!("failed to query audit log: {e}")))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| SyncError::Storage(format!("failed to read audit row: {e}")))?;
+    let Some(first) = unsealed.first() else {
+        return Ok(());
+    };
+
+    let mut prev_hash = conn
+        .query_row(
+            "SELECT hash FROM audit_log WHERE id < ?1 AND hash IS NOT NULL ORDER BY id DESC LIMIT 1",
+            params![first.seq],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| SyncError::Storage(format!("failed to read audit head: {e}")))?
+        .as_deref()
+        .and_then(decode_hash)
+        .unwrap_or(GENESIS_HASH);
+    for row in &unsealed {
+        let hash = row.fields.hash(row.seq, &prev_hash);
+        conn.execute(
+            "UPDATE audit_log SET prev_hash = ?1, hash = ?2 WHERE id = ?3",
+            params![hex::encode(prev_hash), hex::encode(hash), row.seq],
+        )
+        .map_err(|e| SyncError::Storage(format!("failed to seal audit row: {e}")))?;
+        prev_hash = hash;
+    }
+    Ok(())
 }
 
 fn parse_audit_action(s: &str) -> AuditAction {
@@ -511,6 +1306,7 @@ fn parse_audit_action(s: &str) -> AuditAction {
         "event_send" => AuditAction::EventSend,
         "event_receive" => AuditAction::EventReceive,
         "device_register" => AuditAction::DeviceRegister,
+        "acl_change" => AuditAction::AclChange,
         _ => AuditAction::Handshake, // fallback
     }
 }
@@ -8,6 +8,8 @@
 //! This is a CRDT-based sync, so events can be applied in any order
 //! and will converge to the same state.
 
+use crate::e2e::SealedPayloadKey;
+use crate::policy::SyncScope;
 use privstack_crdt::VectorClock;
 use privstack_types::{EntityId, Event, EventId, PeerId};
 use serde::{Deserialize, Serialize};
@@ -19,6 +21,117 @@ pub const PROTOCOL_VERSION: u32 = 1;
 /// Maximum number of events to send in a single batch.
 pub const MAX_BATCH_SIZE: usize = 100;
 
+/// Payload encodings a peer can read off the sync wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireEncoding {
+    /// Length-prefixed JSON (the original encoding, understood by every peer).
+    Json,
+    /// Length-prefixed CBOR inside a versioned binary frame.
+    Cbor,
+}
+
+/// Compression schemes a peer can apply to event-carrying messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireCompression {
+    /// No compression.
+    None,
+    /// zstd-compressed frame payload.
+    Zstd,
+}
+
+/// The wire format used when writing messages to a specific peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WireFormat {
+    /// Payload encoding.
+    pub encoding: WireEncoding,
+    /// Compression applied to `EventBatch`/`EventAck`/`EventNotify` payloads.
+    pub compression: WireCompression,
+}
+
+impl WireFormat {
+    /// Plain JSON, the format every peer understands.
+    pub const JSON: Self = Self {
+        encoding: WireEncoding::Json,
+        compression: WireCompression::None,
+    };
+
+    /// CBOR with zstd-compressed event batches.
+    pub const CBOR_ZSTD: Self = Self {
+        encoding: WireEncoding::Cbor,
+        compression: WireCompression::Zstd,
+    };
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        Self::JSON
+    }
+}
+
+/// Wire formats a peer supports, advertised in `HelloMessage`.
+///
+/// Lists are in order of preference. Peers that predate capability
+/// negotiation send no capabilities and are always spoken to in JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireCapabilities {
+    /// Supported payload encodings, most preferred first.
+    pub encodings: Vec<WireEncoding>,
+    /// Supported compression schemes, most preferred first.
+    #[serde(default)]
+    pub compression: Vec<WireCompression>,
+}
+
+impl WireCapabilities {
+    /// Capabilities of this build.
+    pub fn supported() -> Self {
+        Self {
+            encodings: vec![WireEncoding::Cbor, WireEncoding::Json],
+            compression: vec![WireCompression::Zstd, WireCompression::None],
+        }
+    }
+
+    /// Capabilities of a peer that only speaks JSON.
+    pub fn json_only() -> Self {
+        Self {
+            encodings: vec![WireEncoding::Json],
+            compression: vec![WireCompression::None],
+        }
+    }
+
+    /// Picks the format to use with a remote peer: our most preferred
+    /// encoding and compression that the remote also supports, falling
+    /// back to JSON without compression.
+    ///
+    /// The responder derives the format this way from the Hello's
+    /// capabilities; the initiator adopts it from `HelloAckMessage::wire_format`.
+    pub fn negotiate(&self, remote: &WireCapabilities) -> WireFormat {
+        let encoding = self
+            .encodings
+            .iter()
+            .copied()
+            .find(|e| remote.encodings.contains(e))
+            .unwrap_or(WireEncoding::Json);
+        let compression = if encoding == WireEncoding::Json {
+            WireCompression::None
+        } else {
+            self.compression
+                .iter()
+                .copied()
+                .find(|c| remote.compression.contains(c))
+                .unwrap_or(WireCompression::None)
+        };
+        WireFormat { encoding, compression }
+    }
+}
+
+impl Default for WireCapabilities {
+    fn default() -> Self {
+        Self::supported()
+    }
+}
+
 /// A sync protocol message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SyncMessage {
@@ -54,6 +167,20 @@ pub enum SyncMessage {
 
     /// Error message.
     Error(ErrorMessage),
+
+    /// Payload encryption keys sealed for the receiving peer.
+    KeyShare(KeyShareMessage),
+}
+
+impl SyncMessage {
+    /// Whether this message carries events (and is worth compressing).
+    pub fn carries_events(&self) -> bool {
+        match self {
+            SyncMessage::EventBatch(_) | SyncMessage::EventNotify(_) => true,
+            SyncMessage::EventAck(ack) => !ack.events.is_empty(),
+            _ => false,
+        }
+    }
 }
 
 /// Initial handshake message.
@@ -70,6 +197,20 @@ pub struct HelloMessage {
     /// Optional device identifier for device-limit enforcement.
     #[serde(default)]
     pub device_id: Option<String>,
+    /// Wire formats the sender can read. `None` for peers that predate
+    /// capability negotiation (JSON only).
+    #[serde(default)]
+    pub wire: Option<WireCapabilities>,
+    /// Sender's X25519 key for sealing payload keys. `None` if the sender
+    /// does not support end-to-end payload encryption.
+    #[serde(default)]
+    pub e2e_public_key: Option<[u8; 32]>,
+    /// What the sender keeps in sync. `None` means everything.
+    #[serde(default)]
+    pub sync_scope: Option<SyncScope>,
+    /// Sender's Ed25519 device key for event signatures, if it signs events.
+    #[serde(default)]
+    pub signing_public_key: Option<[u8; 32]>,
 }
 
 impl HelloMessage {
@@ -81,6 +222,10 @@ impl HelloMessage {
             device_name: device_name.into(),
             entity_ids: Vec::new(),
             device_id: None,
+            wire: Some(WireCapabilities::supported()),
+            e2e_public_key: None,
+            sync_scope: None,
+            signing_public_key: None,
         }
     }
 
@@ -95,6 +240,30 @@ impl HelloMessage {
         self.device_id = Some(device_id.into());
         self
     }
+
+    /// Overrides the advertised wire capabilities (`None` = JSON-only legacy peer).
+    pub fn with_wire(mut self, wire: Option<WireCapabilities>) -> Self {
+        self.wire = wire;
+        self
+    }
+
+    /// Advertises the sender's E2E exchange public key.
+    pub fn with_e2e_public_key(mut self, key: [u8; 32]) -> Self {
+        self.e2e_public_key = Some(key);
+        self
+    }
+
+    /// Advertises the sender's sync scope.
+    pub fn with_sync_scope(mut self, scope: SyncScope) -> Self {
+        self.sync_scope = Some(scope);
+        self
+    }
+
+    /// Advertises the sender's event signing key.
+    pub fn with_signing_public_key(mut self, key: [u8; 32]) -> Self {
+        self.signing_public_key = Some(key);
+        self
+    }
 }
 
 /// Response to Hello message.
@@ -110,6 +279,19 @@ pub struct HelloAckMessage {
     pub accepted: bool,
     /// Reason if not accepted.
     pub reason: Option<String>,
+    /// Wire format the responder selected from the Hello's capabilities.
+    /// `None` means JSON (legacy responder, or a Hello without capabilities).
+    #[serde(default)]
+    pub wire_format: Option<WireFormat>,
+    /// Responder's X25519 key for sealing payload keys, if supported.
+    #[serde(default)]
+    pub e2e_public_key: Option<[u8; 32]>,
+    /// What the responder keeps in sync. `None` means everything.
+    #[serde(default)]
+    pub sync_scope: Option<SyncScope>,
+    /// Responder's Ed25519 device key for event signatures, if it signs events.
+    #[serde(default)]
+    pub signing_public_key: Option<[u8; 32]>,
 }
 
 impl HelloAckMessage {
@@ -121,6 +303,10 @@ impl HelloAckMessage {
             device_name: device_name.into(),
             accepted: true,
             reason: None,
+            wire_format: None,
+            e2e_public_key: None,
+            sync_scope: None,
+            signing_public_key: None,
         }
     }
 
@@ -132,8 +318,36 @@ impl HelloAckMessage {
             device_name: String::new(),
             accepted: false,
             reason: Some(reason.into()),
+            wire_format: None,
+            e2e_public_key: None,
+            sync_scope: None,
+            signing_public_key: None,
         }
     }
+
+    /// Sets the negotiated wire format.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = Some(format);
+        self
+    }
+
+    /// Advertises the responder's E2E exchange public key.
+    pub fn with_e2e_public_key(mut self, key: [u8; 32]) -> Self {
+        self.e2e_public_key = Some(key);
+        self
+    }
+
+    /// Advertises the responder's sync scope.
+    pub fn with_sync_scope(mut self, scope: SyncScope) -> Self {
+        self.sync_scope = Some(scope);
+        self
+    }
+
+    /// Advertises the responder's event signing key.
+    pub fn with_signing_public_key(mut self, key: [u8; 32]) -> Self {
+        self.signing_public_key = Some(key);
+        self
+    }
 }
 
 /// Request sync state for documents.
@@ -249,6 +463,16 @@ pub struct EventNotifyMessage {
     pub event: Event,
 }
 
+/// Payload encryption keys sealed for the receiving peer.
+///
+/// Sent (and answered in kind) right after the handshake when both peers
+/// advertised an E2E exchange key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyShareMessage {
+    /// Current payload keys the recipient is allowed to hold.
+    pub keys: Vec<SealedPayloadKey>,
+}
+
 /// Error message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorMessage {
@@ -0,0 +1,260 @@
+//! Per-device event signatures.
+//!
+//! `Event::peer_id` names the author, but events are relayed by peers that
+//! did not create them. Each device signs the events it authors with an
+//! Ed25519 key, and receivers check the signature against the device keys
+//! registered for the claimed author before the event reaches the policy or
+//! the applicator.
+//!
+//! # Key binding
+//!
+//! - **Personal sync**: a trusted peer advertises its device key in the
+//!   handshake; the first key seen is pinned on its `TrustedPeer` record and
+//!   later mismatches are refused. A mismatch lists the peer for approval
+//!   again, and approving that re-pair pins the new key in place of the old
+//!   one. Removing a trusted peer drops its pin, so pairing it again later
+//!   starts afresh. The orchestrator keeps the registry to the pinned key.
+//! - **Enterprise sync**: device keys are registered with
+//!   `EnterpriseSyncPolicy` and dropped when the peer leaves its last team.
+//!
+//! # Verification
+//!
+//! An event whose author has registered keys must carry a valid signature by
+//! one of them. Events from authors without registered keys (legacy peers) are
+//! accepted unless the registry requires signatures. A signature that does not
+//! verify is always rejected.
+
+use crate::error::{SyncError, SyncResult};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use privstack_types::{Event, EventSignature, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+/// A device's Ed25519 signing key.
+///
+/// Persisted through `PairingManager`'s secret store, never in its JSON.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeviceSigningKey {
+    secret: [u8; 32],
+}
+
+impl std::fmt::Debug for DeviceSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceSigningKey")
+            .field("public_key", &hex::encode(self.public_key()))
+            .field("secret", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl DeviceSigningKey {
+    /// Generates a new random signing key.
+    pub fn generate() -> Self {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        Self {
+            secret: key.to_bytes(),
+        }
+    }
+
+    /// Restores a signing key from its 32-byte secret.
+    pub fn from_bytes(secret: [u8; 32]) -> Self {
+        Self { secret }
+    }
+
+    /// Returns the public half, which peers register for this device.
+    pub fn public_key(&self) -> [u8; 32] {
+        SigningKey::from_bytes(&self.secret).verifying_key().to_bytes()
+    }
+
+    /// Signs an event in place, replacing any existing signature.
+    pub fn sign(&self, event: &mut Event) -> SyncResult<()> {
+        let key = SigningKey::from_bytes(&self.secret);
+        let signature = key.sign(&signing_bytes(event)?);
+        event.signature = Some(EventSignature {
+            device_key: key.verifying_key().to_bytes(),
+            signature: signature.to_bytes().to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Returns a signed copy of an event.
+    pub fn signed(&self, event: &Event) -> SyncResult<Event> {
+        let mut event = event.clone();
+        self.sign(&mut event)?;
+        Ok(event)
+    }
+
+    /// Signs an arbitrary message (e.g. an audit checkpoint).
+    pub fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+        SigningKey::from_bytes(&self.secret)
+            .sign(message)
+            .to_bytes()
+            .to_vec()
+    }
+}
+
+/// Verifies a signature made with [`DeviceSigningKey::sign_message`].
+pub fn verify_message(public_key: &[u8; 32], message: &[u8], signature: &[u8]) -> SyncResult<()> {
+    let key = VerifyingKey::from_bytes(public_key)
+        .map_err(|e| SyncError::Auth(format!("invalid device key: {e}")))?;
+    let signature = Signature::from_slice(signature)
+        .map_err(|e| SyncError::Auth(format!("malformed signature: {e}")))?;
+    key.verify(message, &signature)
+        .map_err(|_| SyncError::Auth("bad signature".to_string()))
+}
+
+/// Checks an event's signature on its own, without regard to who may sign.
+///
+/// Returns the signing device key, `None` for unsigned events, or an error if
+/// the signature does not verify.
+pub fn verify_signature(event: &Event) -> SyncResult<Option<[u8; 32]>> {
+    let Some(sig) = &event.signature else {
+        return Ok(None);
+    };
+    let key = VerifyingKey::from_bytes(&sig.device_key)
+        .map_err(|e| SyncError::Auth(format!("invalid device key: {e}")))?;
+    let signature = Signature::from_slice(&sig.signature)
+        .map_err(|e| SyncError::Auth(format!("malformed signature: {e}")))?;
+    key.verify(&signing_bytes(event)?, &signature)
+        .map_err(|_| SyncError::Auth(format!("bad signature on event {}", event.id)))?;
+    Ok(Some(sig.device_key))
+}
+
+fn signing_bytes(event: &Event) -> SyncResult<Vec<u8>> {
+    event
+        .signing_bytes()
+        .map_err(|e| SyncError::Protocol(format!("cannot encode event for signing: {e}")))
+}
+
+/// How an event's claimed author relates to its signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorship {
+    /// Signed by a device registered to the claimed author.
+    Verified { device_key: [u8; 32] },
+    /// Validly signed, but by a device not registered to the claimed author.
+    UnknownDevice { device_key: [u8; 32] },
+    /// No signature.
+    Unsigned,
+    /// The signature does not verify.
+    Invalid { reason: String },
+}
+
+/// Device keys authorized to sign for each peer.
+#[derive(Debug, Default)]
+pub struct DeviceKeyRegistry {
+    keys: RwLock<HashMap<PeerId, HashSet<[u8; 32]>>>,
+    require_signatures: bool,
+}
+
+impl DeviceKeyRegistry {
+    /// Creates an empty registry that accepts events from unregistered authors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty registry that rejects events whose author has no
+    /// registered device key.
+    pub fn strict() -> Self {
+        Self {
+            require_signatures: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether events from unregistered authors are rejected.
+    pub fn requires_signatures(&self) -> bool {
+        self.require_signatures
+    }
+
+    /// Authorizes a device key to sign for a peer. Returns true if newly added.
+    pub fn register(&self, peer: PeerId, device_key: [u8; 32]) -> bool {
+        self.keys
+            .write()
+            .unwrap()
+            .entry(peer)
+            .or_default()
+            .insert(device_key)
+    }
+
+    /// Removes a single device key from a peer.
+    pub fn revoke(&self, peer: &PeerId, device_key: &[u8; 32]) {
+        let mut keys = self.keys.write().unwrap();
+        if let Some(set) = keys.get_mut(peer) {
+            set.remove(device_key);
+            if set.is_empty() {
+                keys.remove(peer);
+            }
+        }
+    }
+
+    /// Returns the peers with at least one registered device key.
+    pub fn peers(&self) -> Vec<PeerId> {
+        self.keys.read().unwrap().keys().copied().collect()
+    }
+
+    /// Removes every device key of a peer.
+    pub fn remove_peer(&self, peer: &PeerId) {
+        self.keys.write().unwrap().remove(peer);
+    }
+
+    /// Returns the device keys registered for a peer.
+    pub fn keys_for(&self, peer: &PeerId) -> Vec<[u8; 32]> {
+        self.keys
+            .read()
+            .unwrap()
+            .get(peer)
+            .map(|set| set.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the peer has at least one registered device key.
+    pub fn has_keys(&self, peer: &PeerId) -> bool {
+        self.keys.read().unwrap().contains_key(peer)
+    }
+
+    /// Classifies the authorship of an event.
+    pub fn authorship(&self, event: &Event) -> Authorship {
+        match verify_signature(event) {
+            Ok(None) => Authorship::Unsigned,
+            Ok(Some(device_key)) => {
+                let registered = self
+                    .keys
+                    .read()
+                    .unwrap()
+                    .get(&event.peer_id)
+                    .is_some_and(|set| set.contains(&device_key));
+                if registered {
+                    Authorship::Verified { device_key }
+                } else {
+                    Authorship::UnknownDevice { device_key }
+                }
+            }
+            Err(e) => Authorship::Invalid {
+                reason: e.to_string(),
+            },
+        }
+    }
+
+    /// Checks that an event may be accepted as authored by its `peer_id`.
+    pub fn check(&self, event: &Event) -> SyncResult<()> {
+        match self.authorship(event) {
+            Authorship::Verified { .. } => Ok(()),
+            Authorship::Invalid { reason } => Err(SyncError::Auth(reason)),
+            Authorship::Unsigned | Authorship::UnknownDevice { .. }
+                if self.has_keys(&event.peer_id) =>
+            {
+                Err(SyncError::Auth(format!(
+                    "event {} is not signed by a device of peer {}",
+                    event.id, event.peer_id
+                )))
+            }
+            _ if self.require_signatures => Err(SyncError::Auth(format!(
+                "no device key registered for peer {}",
+                event.peer_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
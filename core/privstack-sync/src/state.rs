@@ -16,6 +16,9 @@ pub struct SyncState {
     entities: HashMap<EntityId, EntitySyncState>,
     /// Our peer ID.
     local_peer_id: Option<PeerId>,
+    /// Entities changed since the last checkpoint.
+    #[serde(skip)]
+    dirty: HashSet<EntityId>,
 }
 
 impl SyncState {
@@ -24,6 +27,7 @@ impl SyncState {
         Self {
             entities: HashMap::new(),
             local_peer_id: Some(local_peer_id),
+            dirty: HashSet::new(),
         }
     }
 
@@ -60,7 +64,37 @@ impl SyncState {
     /// Records that an event was applied for a given entity.
     pub fn record_event(&mut self, entity_id: EntityId, event: &Event) {
         let entity_state = self.get_or_create_entity(entity_id);
-        entity_state.record_event(event);
+        if entity_state.record_event(event) {
+            self.dirty.insert(entity_id);
+        }
+    }
+
+    /// Returns the entities changed since the last call, for checkpointing.
+    pub fn take_dirty(&mut self) -> Vec<(EntityId, EntitySyncState)> {
+        let dirty = std::mem::take(&mut self.dirty);
+        dirty
+            .into_iter()
+            .filter_map(|id| self.entities.get(&id).map(|s| (id, s.clone())))
+            .collect()
+    }
+
+    /// Restores checkpointed state for an entity, merging it with anything
+    /// recorded since startup.
+    pub fn restore_entity(&mut self, entity_id: EntityId, restored: EntitySyncState) {
+        match self.entities.get_mut(&entity_id) {
+            Some(current) => {
+                current.merge_clock(&restored.clock);
+                current.seen_event_ids.extend(restored.seen_event_ids);
+                current.event_count = current.seen_event_ids.len();
+                for (peer, at) in restored.last_sync {
+                    current.last_sync.entry(peer).or_insert(at);
+                }
+                self.dirty.insert(entity_id);
+            }
+            None => {
+                self.entities.insert(entity_id, restored);
+            }
+        }
     }
 
     /// Gets the vector clock for an entity.
@@ -111,13 +145,16 @@ impl EntitySyncState {
         Self::default()
     }
 
-    /// Records that an event was seen/applied.
-    pub fn record_event(&mut self, event: &Event) {
+    /// Records that an event was seen/applied. Returns false if it had
+    /// been seen before.
+    pub fn record_event(&mut self, event: &Event) -> bool {
         if self.seen_event_ids.insert(event.id) {
             // New event — increment the counter for this peer
             self.clock.increment(event.peer_id);
             self.event_count += 1;
+            return true;
         }
+        false
     }
 
     /// Records a sync with a peer.
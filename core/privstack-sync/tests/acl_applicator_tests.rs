@@ -1,15 +1,28 @@
 //! Tests for acl_applicator.rs — ACL event handling and helper functions.
 
 use privstack_sync::acl_applicator::{is_acl_event, AclApplicator};
-use privstack_sync::policy::{EnterpriseSyncPolicy, SyncRole, TeamId};
-use privstack_sync::AclEventHandler;
+use privstack_sync::policy::{AuditAction, AuditDecision, EnterpriseSyncPolicy, SyncRole, TeamId};
+use privstack_sync::policy_store::PolicyStore;
+use privstack_sync::{AclEventHandler, SyncError};
 use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
 use std::sync::Arc;
+use std::time::Duration;
 
 fn make_acl_event(entity_id: EntityId, payload: EventPayload) -> Event {
     Event::new(entity_id, PeerId::new(), HybridTimestamp::now(), payload)
 }
 
+fn make_acl_event_from(sender: PeerId, entity_id: EntityId, payload: EventPayload) -> Event {
+    Event::new(entity_id, sender, HybridTimestamp::now(), payload)
+}
+
+/// Seeds a peer holding `Owner` on `entity`, authorized to send ACL changes.
+async fn seed_owner(policy: &EnterpriseSyncPolicy, entity: EntityId) -> PeerId {
+    let owner = PeerId::new();
+    policy.grant_peer_role(entity, owner, SyncRole::Owner).await;
+    owner
+}
+
 // ── is_acl_event ────────────────────────────────────────────────
 
 #[test]
@@ -120,8 +133,10 @@ async fn handle_acl_grant_peer() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let peer = PeerId::new();
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclGrantPeer {
             entity_id: entity.to_string(),
@@ -144,6 +159,7 @@ async fn handle_acl_revoke_peer() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let peer = PeerId::new();
 
     // Grant first
@@ -151,7 +167,8 @@ async fn handle_acl_revoke_peer() {
     assert_eq!(policy.resolve_role(&peer, &entity).await, Some(SyncRole::Editor));
 
     // Revoke via event
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclRevokePeer {
             entity_id: entity.to_string(),
@@ -170,8 +187,10 @@ async fn handle_acl_grant_team() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let team = TeamId::new();
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclGrantTeam {
             entity_id: entity.to_string(),
@@ -194,10 +213,12 @@ async fn handle_acl_revoke_team() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let team = TeamId::new();
     policy.grant_team_role(entity, team, SyncRole::Editor).await;
 
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclRevokeTeam {
             entity_id: entity.to_string(),
@@ -220,7 +241,9 @@ async fn handle_acl_set_default_with_role() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
-    let event = make_acl_event(
+    let owner = seed_owner(&policy, entity).await;
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclSetDefault {
             entity_id: entity.to_string(),
@@ -242,11 +265,13 @@ async fn handle_acl_set_default_with_none_role() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     // Set a default first
     policy.set_default_role(entity, Some(SyncRole::Editor)).await;
 
     // Clear via None
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclSetDefault {
             entity_id: entity.to_string(),
@@ -268,10 +293,12 @@ async fn handle_acl_set_default_with_empty_string() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     policy.set_default_role(entity, Some(SyncRole::Admin)).await;
 
     // Clear via empty string
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclSetDefault {
             entity_id: entity.to_string(),
@@ -292,10 +319,14 @@ async fn handle_team_add_peer() {
     let policy = Arc::new(EnterpriseSyncPolicy::new());
     let applicator = AclApplicator::new(policy.clone());
 
+    let entity = EntityId::new();
     let team = TeamId::new();
+    let owner = seed_owner(&policy, team.admin_entity()).await;
+
     let peer = PeerId::new();
-    let event = make_acl_event(
-        EntityId::new(),
+    let event = make_acl_event_from(
+        owner,
+        entity,
         EventPayload::TeamAddPeer {
             team_id: team.0.to_string(),
             peer_id: peer.to_string(),
@@ -314,12 +345,16 @@ async fn handle_team_remove_peer() {
     let policy = Arc::new(EnterpriseSyncPolicy::new());
     let applicator = AclApplicator::new(policy.clone());
 
+    let entity = EntityId::new();
     let team = TeamId::new();
+    let owner = seed_owner(&policy, team.admin_entity()).await;
+
     let peer = PeerId::new();
     policy.add_team_member(team, peer).await;
 
-    let event = make_acl_event(
-        EntityId::new(),
+    let event = make_acl_event_from(
+        owner,
+        entity,
         EventPayload::TeamRemovePeer {
             team_id: team.0.to_string(),
             peer_id: peer.to_string(),
@@ -456,8 +491,10 @@ async fn handle_acl_grant_peer_owner_role() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let peer = PeerId::new();
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclGrantPeer {
             entity_id: entity.to_string(),
@@ -478,8 +515,10 @@ async fn handle_acl_grant_peer_viewer_role() {
     let applicator = AclApplicator::new(policy.clone());
 
     let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
     let peer = PeerId::new();
-    let event = make_acl_event(
+    let event = make_acl_event_from(
+        owner,
         entity,
         EventPayload::AclGrantPeer {
             entity_id: entity.to_string(),
@@ -651,3 +690,544 @@ async fn invalid_entity_id_in_set_default_returns_error() {
     let result = applicator.handle_acl_event(&event).await;
     assert!(result.is_err());
 }
+
+// ── Authorization against the sender's role ─────────────────────
+
+fn grant_at(sender: PeerId, entity: EntityId, target: PeerId, role: &str, wall: u64) -> Event {
+    Event::new(
+        entity,
+        sender,
+        HybridTimestamp::new(wall, 0),
+        EventPayload::AclGrantPeer {
+            entity_id: entity.to_string(),
+            peer_id: target.to_string(),
+            role: role.to_string(),
+        },
+    )
+}
+
+fn revoke_at(sender: PeerId, entity: EntityId, target: PeerId, wall: u64) -> Event {
+    Event::new(
+        entity,
+        sender,
+        HybridTimestamp::new(wall, 0),
+        EventPayload::AclRevokePeer {
+            entity_id: entity.to_string(),
+            peer_id: target.to_string(),
+        },
+    )
+}
+
+async fn acl_audit(policy: &EnterpriseSyncPolicy, decision: AuditDecision) -> Vec<String> {
+    policy
+        .audit_log
+        .read()
+        .await
+        .iter()
+        .filter(|e| e.action == AuditAction::AclChange && e.decision == decision)
+        .map(|e| e.detail.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn grant_from_peer_without_role_rejected() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let outsider = PeerId::new();
+    let event = grant_at(outsider, entity, outsider, "Editor", 1_000);
+
+    let result = applicator.handle_acl_event(&event).await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&outsider, &entity).await, None);
+    assert_eq!(acl_audit(&policy, AuditDecision::Denied).await.len(), 1);
+}
+
+#[tokio::test]
+async fn editor_cannot_grant_roles() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let editor = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(entity, editor, SyncRole::Editor).await;
+
+    let result = applicator
+        .handle_acl_event(&grant_at(editor, entity, target, "Viewer", 1_000))
+        .await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+}
+
+#[tokio::test]
+async fn admin_self_grant_owner_rejected_and_audited() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let admin = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let result = applicator
+        .handle_acl_event(&grant_at(admin, entity, admin, "Owner", 1_000))
+        .await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&admin, &entity).await, Some(SyncRole::Admin));
+
+    let log = policy.audit_log.read().await;
+    let entry = log
+        .iter()
+        .find(|e| e.action == AuditAction::AclChange)
+        .expect("ACL change should be audited");
+    assert_eq!(entry.peer, admin);
+    assert_eq!(entry.entity, Some(entity));
+    assert_eq!(entry.decision, AuditDecision::Denied);
+}
+
+#[tokio::test]
+async fn admin_cannot_demote_owner() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let result = applicator
+        .handle_acl_event(&revoke_at(admin, entity, owner, 1_000))
+        .await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&owner, &entity).await, Some(SyncRole::Owner));
+}
+
+#[tokio::test]
+async fn admin_can_grant_below_owner() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let admin = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let handled = applicator
+        .handle_acl_event(&grant_at(admin, entity, target, "Admin", 1_000))
+        .await
+        .unwrap();
+    assert!(handled);
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Admin));
+    assert_eq!(acl_audit(&policy, AuditDecision::Allowed).await.len(), 1);
+}
+
+#[tokio::test]
+async fn admin_cannot_open_entity_to_everyone_as_admin() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let admin = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let event = make_acl_event_from(
+        admin,
+        entity,
+        EventPayload::AclSetDefault {
+            entity_id: entity.to_string(),
+            role: Some("Admin".to_string()),
+        },
+    );
+    let result = applicator.handle_acl_event(&event).await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&PeerId::new(), &entity).await, None);
+}
+
+#[tokio::test]
+async fn grant_chain_authorizes_later_events() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    let target = PeerId::new();
+
+    // The admin's grant arrives before the grant that made them admin.
+    let admin_grant = grant_at(admin, entity, target, "Editor", 2_000);
+    let promote = grant_at(owner, entity, admin, "Admin", 1_000);
+
+    assert!(applicator.handle_acl_event(&admin_grant).await.is_err());
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+
+    assert!(applicator.handle_acl_event(&promote).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Editor));
+    assert!(
+        acl_audit(&policy, AuditDecision::Allowed)
+            .await
+            .iter()
+            .any(|d| d.contains("authorized on replay"))
+    );
+}
+
+#[tokio::test]
+async fn late_revoke_invalidates_grants_issued_after_it() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    // Revoked at t=1000, but the admin kept granting at t=2000.
+    let revoke = revoke_at(owner, entity, admin, 1_000);
+    let stale_grant = grant_at(admin, entity, target, "Editor", 2_000);
+
+    assert!(applicator.handle_acl_event(&stale_grant).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Editor));
+
+    assert!(applicator.handle_acl_event(&revoke).await.unwrap());
+    assert_eq!(policy.resolve_role(&admin, &entity).await, None);
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+    assert!(
+        acl_audit(&policy, AuditDecision::Denied)
+            .await
+            .iter()
+            .any(|d| d.contains("invalidated on replay"))
+    );
+
+    // A redelivery of the stale grant is now rejected.
+    assert!(matches!(
+        applicator.handle_acl_event(&stale_grant).await,
+        Err(SyncError::PolicyDenied { .. })
+    ));
+}
+
+#[tokio::test]
+async fn grants_issued_before_revoke_survive() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let grant = grant_at(admin, entity, target, "Viewer", 1_000);
+    let revoke = revoke_at(owner, entity, admin, 2_000);
+
+    assert!(applicator.handle_acl_event(&grant).await.unwrap());
+    assert!(applicator.handle_acl_event(&revoke).await.unwrap());
+    assert_eq!(policy.resolve_role(&admin, &entity).await, None);
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Viewer));
+}
+
+#[tokio::test]
+async fn backdated_grant_after_revoke_rejected() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    let sock_puppet = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    assert!(applicator.handle_acl_event(&revoke_at(owner, entity, admin, 120_000)).await.unwrap());
+
+    // The revoked admin stamps a grant a minute before their revoke.
+    let backdated = grant_at(admin, entity, sock_puppet, "Admin", 60_000);
+    assert!(matches!(
+        applicator.handle_acl_event(&backdated).await,
+        Err(SyncError::PolicyDenied { .. })
+    ));
+    assert_eq!(policy.resolve_role(&sock_puppet, &entity).await, None);
+    assert!(
+        acl_audit(&policy, AuditDecision::Denied)
+            .await
+            .iter()
+            .any(|d| d.contains("predates the revoke of its author"))
+    );
+
+    // A demotion fences the author's earlier events the same way.
+    let editor = PeerId::new();
+    policy.grant_peer_role(entity, editor, SyncRole::Admin).await;
+    assert!(applicator
+        .handle_acl_event(&grant_at(owner, entity, editor, "Viewer", 200_000))
+        .await
+        .unwrap());
+    assert!(applicator
+        .handle_acl_event(&grant_at(editor, entity, sock_puppet, "Editor", 150_000))
+        .await
+        .is_err());
+    assert_eq!(policy.resolve_role(&sock_puppet, &entity).await, None);
+}
+
+#[tokio::test]
+async fn grant_seen_by_revoke_survives_late_delivery() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let admin = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let grant = grant_at(admin, entity, target, "Viewer", 1_000);
+    let mut revoke = revoke_at(owner, entity, admin, 60_000);
+    revoke.dependencies.push(grant.id);
+
+    assert!(applicator.handle_acl_event(&revoke).await.unwrap());
+    assert!(applicator.handle_acl_event(&grant).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Viewer));
+}
+
+#[tokio::test]
+async fn concurrent_grant_and_revoke_converge_regardless_of_arrival_order() {
+    let entity = EntityId::new();
+    let owner_a = PeerId::new();
+    let owner_b = PeerId::new();
+    let target = PeerId::new();
+
+    // Same wall time: the tie is broken by event id on every replica.
+    let grant = grant_at(owner_a, entity, target, "Editor", 5_000);
+    let revoke = revoke_at(owner_b, entity, target, 5_000);
+
+    let mut outcomes = Vec::new();
+    for order in [[&grant, &revoke], [&revoke, &grant]] {
+        let policy = Arc::new(EnterpriseSyncPolicy::new());
+        policy.grant_peer_role(entity, owner_a, SyncRole::Owner).await;
+        policy.grant_peer_role(entity, owner_b, SyncRole::Owner).await;
+        let applicator = AclApplicator::new(policy.clone());
+        for event in order {
+            applicator.handle_acl_event(event).await.unwrap();
+        }
+        outcomes.push(policy.resolve_role(&target, &entity).await);
+    }
+    assert_eq!(outcomes[0], outcomes[1]);
+
+    let expected = if grant.id.to_string() > revoke.id.to_string() {
+        Some(SyncRole::Editor)
+    } else {
+        None
+    };
+    assert_eq!(outcomes[0], expected);
+}
+
+#[tokio::test]
+async fn concurrent_demotions_converge_regardless_of_arrival_order() {
+    // Two owners demote each other concurrently; every replica must agree
+    // on which demotion wins and discard the loser's.
+    let entity = EntityId::new();
+    let owner_a = PeerId::new();
+    let owner_b = PeerId::new();
+
+    let a_demotes_b = grant_at(owner_a, entity, owner_b, "Viewer", 5_000);
+    let b_demotes_a = grant_at(owner_b, entity, owner_a, "Viewer", 5_001);
+
+    let mut outcomes = Vec::new();
+    for order in [[&a_demotes_b, &b_demotes_a], [&b_demotes_a, &a_demotes_b]] {
+        let policy = Arc::new(EnterpriseSyncPolicy::new());
+        policy.grant_peer_role(entity, owner_a, SyncRole::Owner).await;
+        policy.grant_peer_role(entity, owner_b, SyncRole::Owner).await;
+        let applicator = AclApplicator::new(policy.clone());
+        for event in order {
+            let _ = applicator.handle_acl_event(event).await;
+        }
+        outcomes.push((
+            policy.resolve_role(&owner_a, &entity).await,
+            policy.resolve_role(&owner_b, &entity).await,
+        ));
+    }
+    assert_eq!(outcomes[0], outcomes[1]);
+    // The earlier demotion strips owner_b before it can act.
+    assert_eq!(outcomes[0], (Some(SyncRole::Owner), Some(SyncRole::Viewer)));
+}
+
+#[tokio::test]
+async fn team_self_add_escalation_rejected() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let own_entity = EntityId::new();
+    let guarded_entity = EntityId::new();
+    let admin = PeerId::new();
+    let team = TeamId::new();
+
+    // Admin of the team and of one entity; the team is Owner of another the
+    // admin can't touch.
+    policy.grant_peer_role(own_entity, admin, SyncRole::Admin).await;
+    policy.grant_peer_role(team.admin_entity(), admin, SyncRole::Admin).await;
+    policy.grant_team_role(guarded_entity, team, SyncRole::Owner).await;
+
+    let event = make_acl_event_from(
+        admin,
+        own_entity,
+        EventPayload::TeamAddPeer {
+            team_id: team.0.to_string(),
+            peer_id: admin.to_string(),
+        },
+    );
+    let result = applicator.handle_acl_event(&event).await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.resolve_role(&admin, &guarded_entity).await, None);
+    let teams = policy.teams.read().await;
+    assert!(teams.get(&team).is_none_or(|m| !m.contains(&admin)));
+}
+
+#[tokio::test]
+async fn team_role_grants_authority_to_members() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let member = PeerId::new();
+    let target = PeerId::new();
+    let team = TeamId::new();
+    policy.grant_peer_role(team.admin_entity(), owner, SyncRole::Admin).await;
+
+    let team_grant = Event::new(
+        entity,
+        owner,
+        HybridTimestamp::new(1_000, 0),
+        EventPayload::AclGrantTeam {
+            entity_id: entity.to_string(),
+            team_id: team.0.to_string(),
+            role: "Admin".to_string(),
+        },
+    );
+    let join = Event::new(
+        entity,
+        owner,
+        HybridTimestamp::new(2_000, 0),
+        EventPayload::TeamAddPeer {
+            team_id: team.0.to_string(),
+            peer_id: member.to_string(),
+        },
+    );
+    let member_grant = grant_at(member, entity, target, "Viewer", 3_000);
+
+    assert!(applicator.handle_acl_event(&team_grant).await.unwrap());
+    assert!(applicator.handle_acl_event(&join).await.unwrap());
+    assert!(applicator.handle_acl_event(&member_grant).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Viewer));
+}
+
+#[tokio::test]
+async fn duplicate_delivery_is_idempotent() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let target = PeerId::new();
+    let event = grant_at(owner, entity, target, "Editor", 1_000);
+
+    assert!(applicator.handle_acl_event(&event).await.unwrap());
+    assert!(applicator.handle_acl_event(&event).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Editor));
+    assert_eq!(acl_audit(&policy, AuditDecision::Allowed).await.len(), 1);
+}
+
+#[tokio::test]
+async fn team_membership_requires_admin_on_team_entity() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+
+    // Owning the entity the event is filed under says nothing about the team.
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let team = TeamId::new();
+
+    let event = make_acl_event_from(
+        owner,
+        entity,
+        EventPayload::TeamAddPeer {
+            team_id: team.0.to_string(),
+            peer_id: owner.to_string(),
+        },
+    );
+    let result = applicator.handle_acl_event(&event).await;
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert!(policy.teams.read().await.get(&team).is_none());
+}
+
+// ── Persistence and compaction ──────────────────────────────────
+
+#[tokio::test]
+async fn history_survives_restart() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let entity = EntityId::new();
+    let owner = PeerId::new();
+    let admin = PeerId::new();
+    let target = PeerId::new();
+
+    {
+        let policy = Arc::new(EnterpriseSyncPolicy::new().with_store(store.clone()));
+        policy.grant_peer_role(entity, owner, SyncRole::Owner).await;
+        policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+        let applicator = AclApplicator::new(policy.clone());
+        let stale_grant = grant_at(admin, entity, target, "Editor", 2_000);
+        assert!(applicator.handle_acl_event(&stale_grant).await.unwrap());
+    }
+
+    // After a restart, a late revoke still invalidates the grant issued after it.
+    let policy = Arc::new(EnterpriseSyncPolicy::load(store.clone()).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, Some(SyncRole::Editor));
+    let applicator = AclApplicator::new(policy.clone());
+    let revoke = revoke_at(owner, entity, admin, 1_000);
+    assert!(applicator.handle_acl_event(&revoke).await.unwrap());
+    assert_eq!(policy.resolve_role(&admin, &entity).await, None);
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+    assert_eq!(store.load_acl_history().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn stable_events_are_compacted() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = Arc::new(EnterpriseSyncPolicy::new().with_store(store.clone()));
+    let applicator =
+        AclApplicator::new(policy.clone()).with_stability_window(Duration::from_secs(1));
+
+    let entity = EntityId::new();
+    let owner = seed_owner(&policy, entity).await;
+    let target = PeerId::new();
+    let other = PeerId::new();
+
+    for event in [
+        grant_at(owner, entity, target, "Editor", 1_000),
+        revoke_at(owner, entity, target, 3_000),
+        grant_at(owner, entity, other, "Viewer", 10_000),
+    ] {
+        assert!(applicator.handle_acl_event(&event).await.unwrap());
+    }
+    assert_eq!(store.load_acl_history().unwrap().len(), 1);
+    assert_eq!(store.load_acl_compacted_writes().unwrap().len(), 1);
+
+    // A late write older than the compacted revoke loses to it.
+    let late_grant = grant_at(owner, entity, target, "Admin", 2_000);
+    assert!(applicator.handle_acl_event(&late_grant).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+
+    // A late write to an untouched register predates the frontier and is
+    // rejected rather than authorized against state it never saw.
+    let late_other = PeerId::new();
+    let late_viewer = grant_at(owner, entity, late_other, "Viewer", 2_500);
+    assert!(matches!(
+        applicator.handle_acl_event(&late_viewer).await,
+        Err(SyncError::PolicyDenied { .. })
+    ));
+    assert_eq!(policy.resolve_role(&late_other, &entity).await, None);
+
+    // A restarted applicator keeps the compacted writes.
+    let reloaded =
+        AclApplicator::new(policy.clone()).with_stability_window(Duration::from_secs(1));
+    assert!(reloaded.handle_acl_event(&late_grant).await.unwrap());
+    assert_eq!(policy.resolve_role(&target, &entity).await, None);
+}
@@ -0,0 +1,396 @@
+//! Tests for hierarchical ACL inheritance: parent links, inheritance breaks,
+//! persistence, and the corresponding ACL events.
+
+use privstack_sync::acl_applicator::{is_acl_event, AclApplicator};
+use privstack_sync::policy::{EnterpriseSyncPolicy, SyncRole, TeamId};
+use privstack_sync::policy_store::PolicyStore;
+use privstack_sync::{AclEventHandler, SyncError};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::sync::Arc;
+
+fn event_from(sender: PeerId, entity: EntityId, payload: EventPayload) -> Event {
+    Event::new(entity, sender, HybridTimestamp::now(), payload)
+}
+
+fn set_parent(entity: EntityId, parent: Option<EntityId>) -> EventPayload {
+    EventPayload::AclSetParent {
+        entity_id: entity.to_string(),
+        parent_id: parent.map(|p| p.to_string()),
+    }
+}
+
+/// workspace → folder → page
+async fn hierarchy(policy: &EnterpriseSyncPolicy) -> (EntityId, EntityId, EntityId) {
+    let workspace = EntityId::new();
+    let folder = EntityId::new();
+    let page = EntityId::new();
+    policy.set_parent(folder, Some(workspace)).await.unwrap();
+    policy.set_parent(page, Some(folder)).await.unwrap();
+    (workspace, folder, page)
+}
+
+// ── Resolution ──────────────────────────────────────────────────
+
+#[tokio::test]
+async fn peer_role_inherited_from_ancestor() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, _, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Editor));
+}
+
+#[tokio::test]
+async fn nearest_entry_overrides_ancestor() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Admin).await;
+    policy.grant_peer_role(folder, peer, SyncRole::Viewer).await;
+
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Viewer));
+    assert_eq!(policy.resolve_role(&peer, &workspace).await, Some(SyncRole::Admin));
+}
+
+#[tokio::test]
+async fn inherited_peer_entry_beats_local_team_entry() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, _, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    let team = TeamId::new();
+    policy.add_team_member(team, peer).await;
+    policy.grant_peer_role(workspace, peer, SyncRole::Viewer).await;
+    policy.grant_team_role(page, team, SyncRole::Editor).await;
+
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Viewer));
+}
+
+#[tokio::test]
+async fn team_roles_inherited_and_nearest_per_team() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    let team_a = TeamId::new();
+    let team_b = TeamId::new();
+    policy.add_team_member(team_a, peer).await;
+    policy.add_team_member(team_b, peer).await;
+    policy.grant_team_role(workspace, team_a, SyncRole::Admin).await;
+    policy.grant_team_role(folder, team_a, SyncRole::Viewer).await;
+    policy.grant_team_role(workspace, team_b, SyncRole::Editor).await;
+
+    // team_a's nearest entry is Viewer; team_b inherits Editor; highest wins
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Editor));
+}
+
+#[tokio::test]
+async fn default_role_inherited() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    policy.set_default_role(workspace, Some(SyncRole::Viewer)).await;
+    let peer = PeerId::new();
+
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Viewer));
+
+    policy.set_default_role(folder, Some(SyncRole::Editor)).await;
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Editor));
+}
+
+#[tokio::test]
+async fn container_change_propagates_to_descendants() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, _, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Editor));
+
+    policy.revoke_peer_role(workspace, peer).await;
+    assert_eq!(policy.resolve_role(&peer, &page).await, None);
+}
+
+#[tokio::test]
+async fn clearing_parent_stops_inheritance() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+
+    policy.set_parent(folder, None).await.unwrap();
+
+    assert_eq!(policy.parent_of(&folder).await, None);
+    assert_eq!(policy.resolve_role(&peer, &page).await, None);
+}
+
+#[tokio::test]
+async fn children_of_lists_direct_children() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+
+    assert_eq!(policy.children_of(&workspace).await, vec![folder]);
+    assert_eq!(policy.children_of(&folder).await, vec![page]);
+    assert!(policy.children_of(&page).await.is_empty());
+}
+
+// ── Breaks ──────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn break_inheritance_hides_ancestors() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    let local = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+    policy.grant_peer_role(folder, local, SyncRole::Viewer).await;
+
+    policy.break_inheritance(folder).await;
+
+    assert!(!policy.inherits(&folder).await);
+    assert_eq!(policy.resolve_role(&peer, &folder).await, None);
+    assert_eq!(policy.resolve_role(&peer, &page).await, None);
+    // The broken entity's own entries still flow to its descendants
+    assert_eq!(policy.resolve_role(&local, &page).await, Some(SyncRole::Viewer));
+}
+
+#[tokio::test]
+async fn restore_inheritance_resumes() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+
+    policy.break_inheritance(folder).await;
+    policy.restore_inheritance(folder).await;
+
+    assert!(policy.inherits(&folder).await);
+    assert_eq!(policy.resolve_role(&peer, &page).await, Some(SyncRole::Editor));
+}
+
+// ── Cycles ──────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn set_parent_rejects_cycle() {
+    let policy = EnterpriseSyncPolicy::new();
+    let (workspace, _, page) = hierarchy(&policy).await;
+
+    let result = policy.set_parent(workspace, Some(page)).await;
+
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.parent_of(&workspace).await, None);
+}
+
+#[tokio::test]
+async fn set_parent_rejects_self() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+
+    assert!(policy.set_parent(entity, Some(entity)).await.is_err());
+}
+
+// ── Persistence ─────────────────────────────────────────────────
+
+#[test]
+fn store_saves_and_removes_parents() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let child = EntityId::new();
+    let parent = EntityId::new();
+
+    store.save_acl_parent(&child, &parent).unwrap();
+    assert_eq!(store.load_acl_parents().unwrap(), vec![(child, parent)]);
+
+    store.remove_acl_parent(&child).unwrap();
+    assert!(store.load_acl_parents().unwrap().is_empty());
+}
+
+#[test]
+fn store_saves_and_removes_inheritance_breaks() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let entity = EntityId::new();
+
+    store.save_inheritance_break(&entity).unwrap();
+    store.save_inheritance_break(&entity).unwrap();
+    assert_eq!(store.load_inheritance_breaks().unwrap(), vec![entity]);
+
+    store.remove_inheritance_break(&entity).unwrap();
+    assert!(store.load_inheritance_breaks().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn inheritance_survives_reload() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = EnterpriseSyncPolicy::new().with_store(store.clone());
+    let (workspace, folder, page) = hierarchy(&policy).await;
+    let peer = PeerId::new();
+    policy.grant_peer_role(workspace, peer, SyncRole::Editor).await;
+    policy.break_inheritance(page).await;
+
+    let reloaded = EnterpriseSyncPolicy::load(store).await.unwrap();
+
+    assert_eq!(reloaded.parent_of(&page).await, Some(folder));
+    assert_eq!(reloaded.resolve_role(&peer, &folder).await, Some(SyncRole::Editor));
+    assert!(!reloaded.inherits(&page).await);
+    assert_eq!(reloaded.resolve_role(&peer, &page).await, None);
+}
+
+// ── Events ──────────────────────────────────────────────────────
+
+#[test]
+fn inheritance_payloads_are_acl_events() {
+    let entity = EntityId::new();
+    assert!(is_acl_event(&set_parent(entity, Some(EntityId::new()))));
+    assert!(is_acl_event(&EventPayload::AclBreakInheritance {
+        entity_id: entity.to_string(),
+    }));
+    assert!(is_acl_event(&EventPayload::AclRestoreInheritance {
+        entity_id: entity.to_string(),
+    }));
+}
+
+#[tokio::test]
+async fn set_parent_event_applies_with_rights_on_both() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let admin = PeerId::new();
+    let page = EntityId::new();
+    let folder = EntityId::new();
+    policy.grant_peer_role(page, admin, SyncRole::Admin).await;
+    policy.grant_peer_role(folder, admin, SyncRole::Editor).await;
+
+    let event = event_from(admin, page, set_parent(page, Some(folder)));
+    assert!(applicator.handle_acl_event(&event).await.unwrap());
+
+    assert_eq!(policy.parent_of(&page).await, Some(folder));
+}
+
+#[tokio::test]
+async fn set_parent_event_denied_without_rights_on_parent() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let admin = PeerId::new();
+    let page = EntityId::new();
+    let folder = EntityId::new();
+    policy.grant_peer_role(page, admin, SyncRole::Admin).await;
+    policy.grant_peer_role(folder, admin, SyncRole::Viewer).await;
+
+    let event = event_from(admin, page, set_parent(page, Some(folder)));
+    let result = applicator.handle_acl_event(&event).await;
+
+    assert!(matches!(result, Err(SyncError::PolicyDenied { .. })));
+    assert_eq!(policy.parent_of(&page).await, None);
+}
+
+#[tokio::test]
+async fn set_parent_event_denied_without_admin_on_child() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let editor = PeerId::new();
+    let page = EntityId::new();
+    let folder = EntityId::new();
+    policy.grant_peer_role(page, editor, SyncRole::Editor).await;
+    policy.grant_peer_role(folder, editor, SyncRole::Owner).await;
+
+    let event = event_from(editor, page, set_parent(page, Some(folder)));
+
+    assert!(applicator.handle_acl_event(&event).await.is_err());
+    assert_eq!(policy.parent_of(&page).await, None);
+}
+
+#[tokio::test]
+async fn set_parent_event_creating_cycle_is_denied() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let (workspace, _, page) = hierarchy(&policy).await;
+    let owner = PeerId::new();
+    policy.grant_peer_role(workspace, owner, SyncRole::Owner).await;
+
+    let event = event_from(owner, workspace, set_parent(workspace, Some(page)));
+
+    assert!(applicator.handle_acl_event(&event).await.is_err());
+    assert_eq!(policy.parent_of(&workspace).await, None);
+}
+
+#[tokio::test]
+async fn concurrent_set_parent_events_converge_without_cycle() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let owner = PeerId::new();
+    let a = EntityId::new();
+    let b = EntityId::new();
+    policy.grant_peer_role(a, owner, SyncRole::Owner).await;
+    policy.grant_peer_role(b, owner, SyncRole::Owner).await;
+
+    let first = event_from(owner, a, set_parent(a, Some(b)));
+    let second = event_from(owner, b, set_parent(b, Some(a)));
+    // Deliver the later event first; the earlier one is authorized on replay
+    // and the later one then closes a cycle.
+    assert!(applicator.handle_acl_event(&second).await.is_ok());
+    assert!(applicator.handle_acl_event(&first).await.is_ok());
+
+    assert_eq!(policy.parent_of(&a).await, Some(b));
+    assert_eq!(policy.parent_of(&b).await, None);
+}
+
+#[tokio::test]
+async fn inherited_admin_authorizes_acl_change_on_descendant() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let (workspace, _, page) = hierarchy(&policy).await;
+    let admin = PeerId::new();
+    let target = PeerId::new();
+    policy.grant_peer_role(workspace, admin, SyncRole::Admin).await;
+
+    let event = event_from(
+        admin,
+        page,
+        EventPayload::AclGrantPeer {
+            entity_id: page.to_string(),
+            peer_id: target.to_string(),
+            role: "Editor".to_string(),
+        },
+    );
+    assert!(applicator.handle_acl_event(&event).await.unwrap());
+
+    assert_eq!(policy.resolve_role(&target, &page).await, Some(SyncRole::Editor));
+}
+
+#[tokio::test]
+async fn break_and_restore_events_require_admin() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let (workspace, folder, _) = hierarchy(&policy).await;
+    let admin = PeerId::new();
+    let editor = PeerId::new();
+    policy.grant_peer_role(workspace, admin, SyncRole::Admin).await;
+    policy.grant_peer_role(workspace, editor, SyncRole::Editor).await;
+
+    let denied = event_from(
+        editor,
+        folder,
+        EventPayload::AclBreakInheritance {
+            entity_id: folder.to_string(),
+        },
+    );
+    assert!(applicator.handle_acl_event(&denied).await.is_err());
+    assert!(policy.inherits(&folder).await);
+
+    let brk = event_from(
+        admin,
+        folder,
+        EventPayload::AclBreakInheritance {
+            entity_id: folder.to_string(),
+        },
+    );
+    assert!(applicator.handle_acl_event(&brk).await.unwrap());
+    assert!(!policy.inherits(&folder).await);
+    // The admin's role came from the workspace, so it is now cut off too
+    assert_eq!(policy.resolve_role(&admin, &folder).await, None);
+
+    let restore = event_from(
+        admin,
+        folder,
+        EventPayload::AclRestoreInheritance {
+            entity_id: folder.to_string(),
+        },
+    );
+    assert!(applicator.handle_acl_event(&restore).await.is_err());
+    assert!(!policy.inherits(&folder).await);
+}
@@ -0,0 +1,525 @@
+//! Tests for the hash-chained audit log: verification, checkpoints,
+//! retention and export.
+
+use privstack_sync::audit::entry_hash;
+use privstack_sync::policy::{AuditAction, AuditDecision, AuditEntry, EnterpriseSyncPolicy};
+use privstack_sync::policy_store::PolicyStore;
+use privstack_sync::{
+    AuditExportFormat, AuditFilter, AuditIssue, AuditRetention, DeviceSigningKey, SyncPolicy,
+};
+use privstack_types::{EntityId, PeerId};
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+fn make_entry(peer: PeerId, action: AuditAction, detail: &str) -> AuditEntry {
+    AuditEntry {
+        peer,
+        entity: None,
+        action,
+        decision: AuditDecision::Allowed,
+        detail: detail.to_string(),
+        timestamp: SystemTime::now(),
+    }
+}
+
+fn fill(store: &PolicyStore, count: usize) -> PeerId {
+    let peer = PeerId::new();
+    for i in 0..count {
+        store
+            .save_audit_entry(&make_entry(peer, AuditAction::SyncRequest, &format!("entry {i}")))
+            .unwrap();
+    }
+    peer
+}
+
+/// Opens a file-backed store plus a raw connection for tampering.
+fn file_store(dir: &tempfile::TempDir) -> (PolicyStore, Connection) {
+    let path = dir.path().join("policy.db");
+    let store = PolicyStore::new(path.to_str().unwrap()).unwrap();
+    let raw = Connection::open(&path).unwrap();
+    (store, raw)
+}
+
+// ── Chaining ─────────────────────────────────────────────────────
+
+#[test]
+fn empty_log_verifies() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(report.entries_checked, 0);
+    assert!(report.head.is_none());
+}
+
+#[test]
+fn records_are_chained() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    fill(&store, 5);
+
+    let records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    assert_eq!(records.len(), 5);
+    assert_eq!(records[0].seq, 1);
+    assert_eq!(records[0].prev_hash, [0u8; 32]);
+    for pair in records.windows(2) {
+        assert_eq!(pair[1].seq, pair[0].seq + 1);
+        assert_eq!(pair[1].prev_hash, pair[0].hash);
+    }
+    for record in &records {
+        assert_eq!(entry_hash(record.seq, &record.prev_hash, &record.entry), record.hash);
+    }
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    assert_eq!(report.entries_checked, 5);
+    assert_eq!(report.head, Some((5, records[4].hash)));
+}
+
+#[test]
+fn edited_entry_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    fill(&store, 4);
+
+    raw.execute("UPDATE audit_log SET detail = 'nothing to see' WHERE id = 2", [])
+        .unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::HashMismatch { seq: 2 }]);
+}
+
+#[test]
+fn edit_to_unparseable_decision_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    let peer = PeerId::new();
+    let mut entry = make_entry(peer, AuditAction::EventReceive, "denied event");
+    entry.decision = AuditDecision::Denied;
+    store.save_audit_entry(&entry).unwrap();
+
+    // Unknown decisions parse back to Denied; the raw text is still hashed.
+    raw.execute("UPDATE audit_log SET decision = 'Whatever' WHERE id = 1", [])
+        .unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::HashMismatch { seq: 1 }]);
+}
+
+#[test]
+fn deleted_entry_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    fill(&store, 5);
+
+    raw.execute("DELETE FROM audit_log WHERE id = 3", []).unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.issues.contains(&AuditIssue::Gap { after: 2, next: 4 }));
+    assert!(report.issues.contains(&AuditIssue::BrokenLink { seq: 4 }));
+}
+
+#[test]
+fn deleted_first_entry_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    fill(&store, 3);
+
+    raw.execute("DELETE FROM audit_log WHERE id = 1", []).unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.issues.contains(&AuditIssue::Gap { after: 0, next: 2 }));
+}
+
+#[test]
+fn rehashed_edit_breaks_the_next_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    fill(&store, 3);
+
+    // Recompute the edited entry's own hash; the successor still points at
+    // the original.
+    let mut records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    records[1].entry.detail = "rewritten".to_string();
+    let forged = entry_hash(2, &records[1].prev_hash, &records[1].entry);
+    raw.execute(
+        "UPDATE audit_log SET detail = 'rewritten', hash = ?1 WHERE id = 2",
+        params![hex::encode(forged)],
+    )
+    .unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::BrokenLink { seq: 3 }]);
+}
+
+#[test]
+fn legacy_rows_are_sealed_on_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("legacy.db");
+    {
+        let raw = Connection::open(&path).unwrap();
+        raw.execute_batch(
+            "CREATE TABLE audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                peer TEXT NOT NULL,
+                entity TEXT,
+                action TEXT NOT NULL,
+                decision TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                timestamp TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+        for i in 0..3 {
+            raw.execute(
+                "INSERT INTO audit_log (peer, entity, action, decision, detail, timestamp) VALUES (?1, NULL, 'handshake', 'Allowed', ?2, '1000')",
+                params![PeerId::new().to_string(), format!("legacy {i}")],
+            )
+            .unwrap();
+        }
+    }
+
+    let store = PolicyStore::new(path.to_str().unwrap()).unwrap();
+    fill(&store, 2);
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    assert_eq!(report.entries_checked, 5);
+}
+
+// ── Checkpoints ──────────────────────────────────────────────────
+
+#[test]
+fn checkpoints_written_at_interval() {
+    let store = PolicyStore::open_in_memory().unwrap().with_checkpoint_interval(3);
+    fill(&store, 7);
+
+    let checkpoints = store.load_audit_checkpoints().unwrap();
+    let seqs: Vec<i64> = checkpoints.iter().map(|c| c.seq).collect();
+    assert_eq!(seqs, vec![3, 6]);
+    assert!(checkpoints.iter().all(|c| c.signature.is_none()));
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(report.checkpoints_checked, 2);
+}
+
+#[test]
+fn manual_checkpoint_covers_head() {
+    let store = PolicyStore::open_in_memory().unwrap().with_checkpoint_interval(0);
+    assert!(store.checkpoint_audit_log().unwrap().is_none());
+
+    fill(&store, 4);
+    let checkpoint = store.checkpoint_audit_log().unwrap().unwrap();
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert_eq!(report.head, Some((4, checkpoint.hash)));
+}
+
+#[test]
+fn truncated_tail_is_detected_by_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    let store = store.with_checkpoint_interval(0);
+    fill(&store, 5);
+    store.checkpoint_audit_log().unwrap();
+
+    raw.execute("DELETE FROM audit_log WHERE id >= 4", []).unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert_eq!(
+        report.issues,
+        vec![AuditIssue::Truncated { checkpoint_seq: 5, last_seq: 3 }]
+    );
+}
+
+#[test]
+fn rebuilt_chain_is_detected_by_signed_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    let key = DeviceSigningKey::generate();
+    let store = store.with_audit_signer(key.clone()).with_checkpoint_interval(2);
+    fill(&store, 2);
+
+    let report = store.verify_audit_log(&[key.public_key()]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+
+    // Rewrite entry 2 and rehash it consistently; the signed checkpoint
+    // still holds the original hash.
+    let mut records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    records[1].entry.detail = "rewritten".to_string();
+    let forged = entry_hash(2, &records[1].prev_hash, &records[1].entry);
+    raw.execute(
+        "UPDATE audit_log SET detail = 'rewritten', hash = ?1 WHERE id = 2",
+        params![hex::encode(forged)],
+    )
+    .unwrap();
+    let report = store.verify_audit_log(&[key.public_key()]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::CheckpointMismatch { seq: 2 }]);
+
+    // Re-pointing the checkpoint invalidates its signature.
+    raw.execute(
+        "UPDATE audit_checkpoints SET hash = ?1 WHERE seq = 2",
+        params![hex::encode(forged)],
+    )
+    .unwrap();
+    let report = store.verify_audit_log(&[key.public_key()]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::BadCheckpointSignature { seq: 2 }]);
+}
+
+#[test]
+fn untrusted_checkpoint_signer_is_reported() {
+    let store = PolicyStore::open_in_memory()
+        .unwrap()
+        .with_audit_signer(DeviceSigningKey::generate())
+        .with_checkpoint_interval(1);
+    fill(&store, 1);
+
+    let trusted = DeviceSigningKey::generate().public_key();
+    let report = store.verify_audit_log(&[trusted]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::UntrustedCheckpoint { seq: 1 }]);
+
+    // Without a trust list any valid signature passes.
+    assert!(store.verify_audit_log(&[]).unwrap().is_valid());
+}
+
+#[test]
+fn unsigned_checkpoint_fails_when_signers_required() {
+    let store = PolicyStore::open_in_memory().unwrap().with_checkpoint_interval(1);
+    fill(&store, 1);
+
+    let trusted = DeviceSigningKey::generate().public_key();
+    let report = store.verify_audit_log(&[trusted]).unwrap();
+    assert_eq!(report.issues, vec![AuditIssue::UntrustedCheckpoint { seq: 1 }]);
+}
+
+// ── Retention ────────────────────────────────────────────────────
+
+#[test]
+fn prune_by_count_keeps_chain_verifiable() {
+    let key = DeviceSigningKey::generate();
+    let store = PolicyStore::open_in_memory()
+        .unwrap()
+        .with_audit_signer(key.clone())
+        .with_checkpoint_interval(3);
+    fill(&store, 10);
+
+    let removed = store
+        .prune_audit_log(&AuditRetention { max_age: None, max_entries: Some(4) })
+        .unwrap();
+    assert_eq!(removed, 6);
+    assert_eq!(store.audit_log_count().unwrap(), 4);
+
+    let records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    assert_eq!(records.first().unwrap().seq, 7);
+
+    let report = store.verify_audit_log(&[key.public_key()]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    assert_eq!(report.entries_checked, 4);
+
+    // New entries continue the chain after pruning.
+    fill(&store, 2);
+    let report = store.verify_audit_log(&[key.public_key()]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    assert_eq!(report.head.unwrap().0, 12);
+}
+
+#[test]
+fn prune_by_age_removes_old_entries() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let peer = PeerId::new();
+    let mut old = make_entry(peer, AuditAction::Handshake, "old");
+    old.timestamp = SystemTime::now() - Duration::from_secs(90 * 24 * 3600);
+    store.save_audit_entry(&old).unwrap();
+    store.save_audit_entry(&old).unwrap();
+    store
+        .save_audit_entry(&make_entry(peer, AuditAction::Handshake, "recent"))
+        .unwrap();
+
+    let removed = store
+        .prune_audit_log(&AuditRetention {
+            max_age: Some(Duration::from_secs(30 * 24 * 3600)),
+            max_entries: None,
+        })
+        .unwrap();
+    assert_eq!(removed, 2);
+
+    let records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].entry.detail, "recent");
+    assert!(store.verify_audit_log(&[]).unwrap().is_valid());
+}
+
+#[test]
+fn prune_within_limits_is_noop() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    fill(&store, 3);
+    let removed = store
+        .prune_audit_log(&AuditRetention {
+            max_age: Some(Duration::from_secs(3600)),
+            max_entries: Some(10),
+        })
+        .unwrap();
+    assert_eq!(removed, 0);
+    assert!(store.load_audit_checkpoints().unwrap().is_empty());
+}
+
+#[test]
+fn prune_everything_then_append() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    fill(&store, 3);
+    store
+        .prune_audit_log(&AuditRetention { max_age: None, max_entries: Some(0) })
+        .unwrap();
+    assert_eq!(store.audit_log_count().unwrap(), 0);
+    assert!(store.verify_audit_log(&[]).unwrap().is_valid());
+
+    fill(&store, 1);
+    let records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    assert_eq!(records[0].seq, 4);
+    assert!(store.verify_audit_log(&[]).unwrap().is_valid());
+}
+
+#[test]
+fn tampered_anchor_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, raw) = file_store(&dir);
+    fill(&store, 5);
+    store
+        .prune_audit_log(&AuditRetention { max_age: None, max_entries: Some(2) })
+        .unwrap();
+
+    // Hide further deletions by moving the anchor forward.
+    raw.execute("DELETE FROM audit_log WHERE id = 4", []).unwrap();
+    raw.execute("UPDATE audit_anchor SET seq = 4", []).unwrap();
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(!report.is_valid());
+    assert!(report.issues.contains(&AuditIssue::AnchorMismatch { seq: 4 }));
+}
+
+// ── Filtering & export ───────────────────────────────────────────
+
+#[test]
+fn filter_by_peer_entity_action_and_time() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let alice = PeerId::new();
+    let bob = PeerId::new();
+    let doc = EntityId::new();
+
+    let mut old = make_entry(alice, AuditAction::Handshake, "old");
+    old.timestamp = SystemTime::now() - Duration::from_secs(3600);
+    store.save_audit_entry(&old).unwrap();
+
+    let mut with_entity = make_entry(alice, AuditAction::EventReceive, "edit");
+    with_entity.entity = Some(doc);
+    store.save_audit_entry(&with_entity).unwrap();
+    store
+        .save_audit_entry(&make_entry(bob, AuditAction::EventReceive, "bob"))
+        .unwrap();
+
+    let by_peer = store.load_audit_records(&AuditFilter::new().with_peer(alice)).unwrap();
+    assert_eq!(by_peer.len(), 2);
+
+    let by_entity = store.load_audit_records(&AuditFilter::new().with_entity(doc)).unwrap();
+    assert_eq!(by_entity.len(), 1);
+    assert_eq!(by_entity[0].entry.detail, "edit");
+
+    let by_action = store
+        .load_audit_records(&AuditFilter::new().with_action(AuditAction::EventReceive))
+        .unwrap();
+    assert_eq!(by_action.len(), 2);
+
+    let recent = store
+        .load_audit_records(&AuditFilter::new().since(SystemTime::now() - Duration::from_secs(60)))
+        .unwrap();
+    assert_eq!(recent.len(), 2);
+
+    let older = store
+        .load_audit_records(&AuditFilter::new().until(SystemTime::now() - Duration::from_secs(60)))
+        .unwrap();
+    assert_eq!(older.len(), 1);
+    assert_eq!(older[0].entry.detail, "old");
+
+    let combined = store
+        .load_audit_records(
+            &AuditFilter::new()
+                .with_peer(alice)
+                .with_action(AuditAction::EventReceive),
+        )
+        .unwrap();
+    assert_eq!(combined.len(), 1);
+}
+
+#[test]
+fn export_json_lines() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    fill(&store, 3);
+
+    let mut out = Vec::new();
+    let written = store
+        .export_audit_log(&AuditFilter::new(), AuditExportFormat::JsonLines, &mut out)
+        .unwrap();
+    assert_eq!(written, 3);
+
+    let records = store.load_audit_records(&AuditFilter::new()).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<serde_json::Value> = text
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["seq"], 1);
+    assert_eq!(lines[0]["action"], "sync_request");
+    assert_eq!(lines[0]["decision"], "Allowed");
+    assert_eq!(lines[0]["entity"], serde_json::Value::Null);
+    assert_eq!(lines[2]["hash"], hex::encode(records[2].hash));
+    assert_eq!(lines[2]["prev_hash"], lines[1]["hash"]);
+}
+
+#[test]
+fn export_csv_escapes_fields() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let peer = PeerId::new();
+    store
+        .save_audit_entry(&make_entry(peer, AuditAction::AclChange, "role \"Admin\", granted\nby owner"))
+        .unwrap();
+
+    let mut out = Vec::new();
+    store
+        .export_audit_log(&AuditFilter::new(), AuditExportFormat::Csv, &mut out)
+        .unwrap();
+    let text = String::from_utf8(out).unwrap();
+
+    assert!(text.starts_with("seq,timestamp_ms,peer,entity,action,decision,detail,prev_hash,hash\n"));
+    assert!(text.contains(&format!(",{peer},,acl_change,Allowed,\"role \"\"Admin\"\", granted\nby owner\",")));
+}
+
+#[test]
+fn export_respects_filter() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let alice = fill(&store, 2);
+    fill(&store, 3);
+
+    let mut out = Vec::new();
+    let written = store
+        .export_audit_log(&AuditFilter::new().with_peer(alice), AuditExportFormat::Csv, &mut out)
+        .unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+}
+
+// ── Policy integration ───────────────────────────────────────────
+
+#[tokio::test]
+async fn enterprise_policy_audit_is_chained() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = EnterpriseSyncPolicy::new().with_store(store.clone());
+    let local = PeerId::new();
+
+    for _ in 0..3 {
+        let _ = policy.on_handshake(&local, &PeerId::new()).await;
+    }
+
+    let report = store.verify_audit_log(&[]).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues);
+    assert_eq!(report.entries_checked, 3);
+}
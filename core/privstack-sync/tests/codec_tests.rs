@@ -1,8 +1,15 @@
 //! Tests for p2p codec — targeting error handling and edge cases.
 
 use futures::io::Cursor;
-use privstack_sync::p2p::codec::{read_message, write_message};
-use privstack_sync::protocol::SyncMessage;
+use privstack_sync::p2p::codec::{
+    decode_message, encode_message, read_message, read_message_with_format, write_message,
+    write_message_as, BINARY_FRAME_MAGIC, MAX_FRAME_SIZE, MAX_MESSAGE_SIZE, WIRE_FRAME_VERSION,
+};
+use privstack_sync::protocol::{
+    EventBatchMessage, HelloMessage, SyncMessage, WireCompression, WireEncoding, WireFormat,
+};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use proptest::prelude::*;
 
 /// Helper: write a raw length-prefixed payload into a buffer.
 fn make_length_prefixed(payload: &[u8]) -> Vec<u8> {
@@ -109,3 +116,204 @@ async fn test_read_message_empty_stream() {
     let result = read_message(&mut reader).await;
     assert!(result.is_err());
 }
+
+// ── Binary wire format ──────────────────────────────────────────
+
+const CBOR_PLAIN: WireFormat = WireFormat {
+    encoding: WireEncoding::Cbor,
+    compression: WireCompression::None,
+};
+
+fn make_batch(events: usize) -> SyncMessage {
+    let entity_id = EntityId::new();
+    let peer_id = PeerId::new();
+    let events = (0..events)
+        .map(|i| {
+            Event::new(
+                entity_id,
+                peer_id,
+                HybridTimestamp::now(),
+                EventPayload::FullSnapshot {
+                    entity_type: "note".into(),
+                    json_data: format!(r#"{{"title":"note {i}","body":"some \"quoted\" text"}}"#),
+                },
+            )
+        })
+        .collect();
+    SyncMessage::EventBatch(EventBatchMessage::new(entity_id, events, 0).finalize())
+}
+
+#[tokio::test]
+async fn test_roundtrip_cbor_reports_format() {
+    let msg = make_batch(3);
+    let mut buf = Cursor::new(Vec::new());
+    write_message_as(&mut buf, &msg, CBOR_PLAIN).await.unwrap();
+
+    let written = buf.into_inner();
+    assert_eq!(written[4], BINARY_FRAME_MAGIC);
+    assert_eq!(written[5], WIRE_FRAME_VERSION);
+
+    let mut reader = Cursor::new(written);
+    let (decoded, format) = read_message_with_format(&mut reader).await.unwrap();
+    assert_eq!(format, CBOR_PLAIN);
+    match (decoded, msg) {
+        (SyncMessage::EventBatch(a), SyncMessage::EventBatch(b)) => {
+            assert_eq!(a.entity_id, b.entity_id);
+            assert_eq!(a.events, b.events);
+            assert!(a.is_final);
+        }
+        other => panic!("expected EventBatch, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_json_frame_reports_json_format() {
+    let mut buf = Cursor::new(Vec::new());
+    write_message(&mut buf, &SyncMessage::Ping(1)).await.unwrap();
+
+    let mut reader = Cursor::new(buf.into_inner());
+    let (_, format) = read_message_with_format(&mut reader).await.unwrap();
+    assert_eq!(format, WireFormat::JSON);
+}
+
+#[test]
+fn test_cbor_is_smaller_than_json_for_event_batches() {
+    let msg = make_batch(50);
+    let json = encode_message(&msg, WireFormat::JSON).unwrap();
+    let cbor = encode_message(&msg, CBOR_PLAIN).unwrap();
+    let zstd = encode_message(&msg, WireFormat::CBOR_ZSTD).unwrap();
+    assert!(cbor.len() < json.len());
+    assert!(zstd.len() < cbor.len());
+}
+
+#[test]
+fn test_zstd_roundtrip_event_batch() {
+    let msg = make_batch(50);
+    let frame = encode_message(&msg, WireFormat::CBOR_ZSTD).unwrap();
+    let (decoded, format) = decode_message(&frame).unwrap();
+    assert_eq!(format, WireFormat::CBOR_ZSTD);
+    assert!(matches!(decoded, SyncMessage::EventBatch(b) if b.events.len() == 50));
+}
+
+#[test]
+fn test_small_messages_are_not_compressed() {
+    let frame = encode_message(&SyncMessage::Ping(7), WireFormat::CBOR_ZSTD).unwrap();
+    // Compression flag unset, "accepts zstd" flag set
+    assert_eq!(frame[3] & 0b01, 0);
+    let (decoded, format) = decode_message(&frame).unwrap();
+    assert!(matches!(decoded, SyncMessage::Ping(7)));
+    // Replies to this peer may still be compressed
+    assert_eq!(format, WireFormat::CBOR_ZSTD);
+}
+
+#[test]
+fn test_hello_roundtrips_in_cbor() {
+    let hello = HelloMessage::new(PeerId::new(), "Laptop").with_entities(vec![EntityId::new()]);
+    let frame = encode_message(&SyncMessage::Hello(hello.clone()), CBOR_PLAIN).unwrap();
+    match decode_message(&frame).unwrap().0 {
+        SyncMessage::Hello(h) => {
+            assert_eq!(h.peer_id, hello.peer_id);
+            assert_eq!(h.entity_ids, hello.entity_ids);
+            assert_eq!(h.wire, hello.wire);
+        }
+        other => panic!("expected Hello, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_binary_frame_unknown_version_rejected() {
+    let mut frame = encode_message(&SyncMessage::Ping(1), CBOR_PLAIN).unwrap();
+    frame[1] = WIRE_FRAME_VERSION + 1;
+    let err = decode_message(&frame).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("unsupported wire frame version"));
+}
+
+#[test]
+fn test_binary_frame_unknown_encoding_rejected() {
+    let mut frame = encode_message(&SyncMessage::Ping(1), CBOR_PLAIN).unwrap();
+    frame[2] = 0x7f;
+    let err = decode_message(&frame).unwrap_err();
+    assert!(err.to_string().contains("unsupported wire encoding"));
+}
+
+#[test]
+fn test_binary_frame_unknown_flags_rejected() {
+    let mut frame = encode_message(&SyncMessage::Ping(1), CBOR_PLAIN).unwrap();
+    frame[3] = 0x80;
+    let err = decode_message(&frame).unwrap_err();
+    assert!(err.to_string().contains("unknown wire frame flags"));
+}
+
+#[test]
+fn test_binary_frame_truncated_header_rejected() {
+    let err = decode_message(&[BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION]).unwrap_err();
+    assert!(err.to_string().contains("truncated binary frame header"));
+}
+
+#[test]
+fn test_binary_frame_invalid_cbor_rejected() {
+    let frame = [BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION, 1, 0, 0xff, 0x00, 0x13];
+    let err = decode_message(&frame).unwrap_err();
+    assert!(err.to_string().contains("CBOR decode error"));
+}
+
+#[test]
+fn test_invalid_zstd_payload_rejected() {
+    let frame = [BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION, 1, 0b01, 1, 2, 3, 4, 5];
+    let err = decode_message(&frame).unwrap_err();
+    assert!(err.to_string().contains("zstd decode error"));
+}
+
+#[test]
+fn test_decompression_bomb_rejected() {
+    // Highly compressible payload that expands past MAX_MESSAGE_SIZE
+    let zeros = vec![0u8; MAX_MESSAGE_SIZE + 1024];
+    let compressed = zstd::bulk::compress(&zeros, 19).unwrap();
+    assert!(compressed.len() < MAX_FRAME_SIZE);
+
+    let mut frame = vec![BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION, 1, 0b01];
+    frame.extend_from_slice(&compressed);
+    let err = decode_message(&frame).unwrap_err();
+    assert!(err.to_string().contains("decompressed message too large"));
+}
+
+#[test]
+fn test_encode_rejects_oversized_frame() {
+    let huge = SyncMessage::Error(privstack_sync::protocol::ErrorMessage::new(
+        1,
+        "x".repeat(MAX_FRAME_SIZE + 1),
+    ));
+    let err = encode_message(&huge, WireFormat::JSON).unwrap_err();
+    assert!(err.to_string().contains("message too large"));
+}
+
+// ── Fuzz-style properties ───────────────────────────────────────
+//
+// The cargo-fuzz harness in `fuzz/` explores this much more deeply; these
+// run on every `cargo test` to catch regressions in the size checks.
+
+proptest! {
+    #[test]
+    fn read_message_never_panics_on_arbitrary_input(data in proptest::collection::vec(any::<u8>(), 0..2048)) {
+        let _ = futures::executor::block_on(read_message(&mut Cursor::new(data)));
+    }
+
+    #[test]
+    fn decode_never_panics_on_arbitrary_binary_frames(
+        flags in 0u8..4,
+        body in proptest::collection::vec(any::<u8>(), 0..2048),
+    ) {
+        let mut frame = vec![BINARY_FRAME_MAGIC, WIRE_FRAME_VERSION, 1, flags];
+        frame.extend_from_slice(&body);
+        let _ = decode_message(&frame);
+    }
+
+    #[test]
+    fn oversized_length_prefix_always_rejected(len in (MAX_FRAME_SIZE as u32 + 1)..=u32::MAX) {
+        let mut data = len.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 16]);
+        let err = futures::executor::block_on(read_message(&mut Cursor::new(data))).unwrap_err();
+        prop_assert!(err.to_string().contains("message too large"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Conflict detection in the applicator and the manual resolution API.
+//!
+//! Each test plays two devices against separate in-memory stores. Local
+//! edits are stamped with the entity's version heads the way the FFI does
+//! before signing; remote edits are applied through the applicator.
+
+use privstack_storage::EntityStore;
+use privstack_sync::applicator::EventApplicator;
+use privstack_sync::{diff_conflict, resolve_conflict, stamp_local_event, ConflictResolution};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use serde_json::json;
+
+struct Device {
+    peer: PeerId,
+    store: EntityStore,
+}
+
+impl Device {
+    fn new() -> Self {
+        Self {
+            peer: PeerId::new(),
+            store: EntityStore::open_in_memory().unwrap(),
+        }
+    }
+
+    /// Authors an edit here, as the FFI snapshot path does.
+    fn edit(&self, entity_id: EntityId, data: serde_json::Value) -> Event {
+        let mut event = Event::full_snapshot(entity_id, self.peer, "note", data.to_string());
+        stamp_local_event(&self.store, &mut event).unwrap();
+        EventApplicator::new(self.peer)
+            .apply_event(&event, &self.store, None, None)
+            .unwrap();
+        event
+    }
+
+    fn receive(&self, event: &Event) -> Option<privstack_storage::ConflictRecord> {
+        EventApplicator::new(self.peer)
+            .apply_event_outcome(event, &self.store, None, None)
+            .unwrap()
+            .conflict
+    }
+
+    fn data(&self, entity_id: EntityId) -> serde_json::Value {
+        let mut data = self.store.get_entity(&entity_id.to_string()).unwrap().unwrap().data;
+        data.as_object_mut().unwrap().remove("is_trashed");
+        data
+    }
+}
+
+/// Both devices hold the same base version of one entity.
+fn shared_entity() -> (Device, Device, EntityId) {
+    let (a, b) = (Device::new(), Device::new());
+    let id = EntityId::new();
+    let base = a.edit(id, json!({"title": "base", "body": "x"}));
+    assert!(b.receive(&base).is_none());
+    (a, b, id)
+}
+
+#[test]
+fn sequential_edits_do_not_conflict() {
+    let (a, b, id) = shared_entity();
+    let first = a.edit(id, json!({"title": "one", "body": "x"}));
+    assert!(b.receive(&first).is_none());
+    let reply = b.edit(id, json!({"title": "two", "body": "x"}));
+    assert!(a.receive(&reply).is_none());
+
+    assert_eq!(a.data(id), json!({"title": "two", "body": "x"}));
+    assert!(a.store.list_sync_conflicts(None, true).unwrap().is_empty());
+    assert!(b.store.list_sync_conflicts(None, true).unwrap().is_empty());
+}
+
+#[test]
+fn concurrent_edits_record_a_conflict_on_both_sides() {
+    let (a, b, id) = shared_entity();
+    let mine = a.edit(id, json!({"title": "from a", "body": "x"}));
+    let theirs = b.edit(id, json!({"title": "from b", "body": "x"}));
+
+    let on_a = a.receive(&theirs).expect("conflict on a");
+    assert_eq!(on_a.entity_id, id.to_string());
+    assert_eq!(on_a.local_heads, [mine.id.to_string()]);
+    assert_eq!(on_a.remote_event_id, theirs.id.to_string());
+    assert_eq!(on_a.remote_peer_id, b.peer.to_string());
+    assert_eq!(on_a.winner, "remote");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&on_a.local_json).unwrap(),
+        json!({"title": "from a", "body": "x"})
+    );
+    // The automatic merge still ran.
+    assert_eq!(a.data(id), json!({"title": "from b", "body": "x"}));
+
+    assert!(b.receive(&mine).is_some());
+    assert_eq!(a.store.entity_heads(&id.to_string()).unwrap().len(), 2);
+}
+
+#[test]
+fn further_concurrent_edits_fold_into_the_open_conflict() {
+    let (a, b, id) = shared_entity();
+    a.edit(id, json!({"title": "a1"}));
+    let b1 = b.edit(id, json!({"title": "b1"}));
+    let b2 = b.edit(id, json!({"title": "b2"}));
+
+    assert!(a.receive(&b1).is_some());
+    assert!(a.receive(&b2).is_none());
+
+    let open = a.store.list_sync_conflicts(None, false).unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].remote_event_id, b2.id.to_string());
+}
+
+#[test]
+fn replays_own_events_and_legacy_events_are_not_conflicts() {
+    let (a, b, id) = shared_entity();
+    let mine = a.edit(id, json!({"title": "a1"}));
+    let theirs = b.edit(id, json!({"title": "b1"}));
+    assert!(a.receive(&theirs).is_some());
+
+    // The same event again.
+    a.store.resolve_sync_conflict(&theirs.id.to_string(), "keep_theirs", 1).unwrap();
+    assert!(a.receive(&theirs).is_none());
+    // Our own event echoed back by a peer.
+    assert!(a.receive(&mine).is_none());
+    // An event from a peer that does not track versions.
+    let legacy = Event::new(
+        id,
+        b.peer,
+        HybridTimestamp::now(),
+        EventPayload::EntityUpdated {
+            entity_type: "note".into(),
+            json_data: json!({"title": "legacy"}).to_string(),
+        },
+    );
+    assert!(a.receive(&legacy).is_none());
+    assert!(a.store.list_sync_conflicts(None, false).unwrap().is_empty());
+}
+
+#[test]
+fn identical_concurrent_edits_are_not_conflicts() {
+    let (a, b, id) = shared_entity();
+    a.edit(id, json!({"title": "same"}));
+    let theirs = b.edit(id, json!({"title": "same"}));
+    assert!(a.receive(&theirs).is_none());
+}
+
+#[test]
+fn diff_lists_only_fields_that_differ() {
+    let (a, b, id) = shared_entity();
+    a.edit(id, json!({"title": "a", "body": "x", "pinned": true}));
+    let theirs = b.edit(id, json!({"title": "b", "body": "x", "tags": ["t"]}));
+    let conflict = a.receive(&theirs).unwrap();
+
+    let fields = diff_conflict(&conflict).unwrap();
+    let names: Vec<_> = fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(names, ["pinned", "tags", "title"]);
+    assert_eq!(fields[0].theirs, None);
+    assert_eq!(fields[1].mine, None);
+    assert_eq!(fields[2].mine, Some(json!("a")));
+    assert_eq!(fields[2].theirs, Some(json!("b")));
+}
+
+#[test]
+fn keep_mine_restores_local_state_and_closes_the_conflict() {
+    let (a, b, id) = shared_entity();
+    let mine = a.edit(id, json!({"title": "from a"}));
+    let theirs = b.edit(id, json!({"title": "from b"}));
+    let conflict = a.receive(&theirs).unwrap();
+
+    let event =
+        resolve_conflict(&a.store, &conflict.conflict_id, ConflictResolution::KeepMine, a.peer, None)
+            .unwrap();
+    assert!(matches!(event.payload, EventPayload::ConflictResolved { .. }));
+    assert!(event.dependencies.contains(&mine.id));
+    assert!(event.dependencies.contains(&theirs.id));
+
+    assert_eq!(a.data(id), json!({"title": "from a"}));
+    assert_eq!(a.store.entity_heads(&id.to_string()).unwrap(), [event.id.to_string()]);
+    let resolved = a.store.get_sync_conflict(&conflict.conflict_id).unwrap().unwrap();
+    assert_eq!(resolved.resolution.as_deref(), Some("keep_mine"));
+    assert!(resolved.resolved_at.is_some());
+
+    let again = resolve_conflict(
+        &a.store,
+        &conflict.conflict_id,
+        ConflictResolution::KeepTheirs,
+        a.peer,
+        None,
+    );
+    assert!(again.is_err());
+}
+
+#[test]
+fn resolution_propagates_and_closes_the_peer_conflict() {
+    let (a, b, id) = shared_entity();
+    let mine = a.edit(id, json!({"title": "from a"}));
+    let theirs = b.edit(id, json!({"title": "from b"}));
+    let on_a = a.receive(&theirs).unwrap();
+    let on_b = b.receive(&mine).unwrap();
+
+    let merged = json!({"title": "from a and b"});
+    let event = resolve_conflict(
+        &a.store,
+        &on_a.conflict_id,
+        ConflictResolution::Merged(merged.clone()),
+        a.peer,
+        None,
+    )
+    .unwrap();
+
+    assert!(b.receive(&event).is_none());
+    assert_eq!(b.data(id), merged);
+    let closed = b.store.get_sync_conflict(&on_b.conflict_id).unwrap().unwrap();
+    assert_eq!(closed.resolution.as_deref(), Some("remote"));
+
+    // Later edits on either side descend from the resolution.
+    let next = b.edit(id, json!({"title": "after"}));
+    assert!(a.receive(&next).is_none());
+}
+
+#[test]
+fn stamping_ignores_non_entity_events() {
+    let store = EntityStore::open_in_memory().unwrap();
+    let id = EntityId::new();
+    let mut deleted = Event::new(
+        id,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityDeleted { entity_type: "note".into() },
+    );
+    stamp_local_event(&store, &mut deleted).unwrap();
+    assert!(store.entity_heads(&id.to_string()).unwrap().is_empty());
+}
+
+#[test]
+fn resolution_serde_shape() {
+    let keep: ConflictResolution = serde_json::from_str(r#"{"resolution":"keep_mine"}"#).unwrap();
+    assert_eq!(keep, ConflictResolution::KeepMine);
+    let merged: ConflictResolution =
+        serde_json::from_str(r#"{"resolution":"merged","data":{"a":1}}"#).unwrap();
+    assert_eq!(merged, ConflictResolution::Merged(json!({"a": 1})));
+    assert_eq!(merged.as_str(), "merged");
+}
@@ -0,0 +1,101 @@
+//! Dataset replication through the sync engine.
+
+use privstack_datasets::{ColumnDef, DatasetStore};
+use privstack_storage::{EntityStore, EventStore};
+use privstack_sync::dataset_applicator::{
+    dataset_change_event, dataset_op_from_event, DATASET_ENTITY_TYPE,
+};
+use privstack_sync::protocol::EventBatchMessage;
+use privstack_sync::{DatasetApplicator, SyncConfig, SyncEngine};
+use privstack_types::{EntityId, Event, PeerId};
+use serde_json::json;
+use std::sync::Arc;
+
+fn replica(peer: PeerId) -> DatasetStore {
+    let store = DatasetStore::open_in_memory().unwrap();
+    store.enable_replication(&peer.to_string());
+    store
+}
+
+fn change_events(store: &DatasetStore, peer: PeerId) -> Vec<Event> {
+    store
+        .take_pending_changes(1000)
+        .unwrap()
+        .iter()
+        .map(|c| dataset_change_event(c, peer).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn dataset_events_replicate_through_the_engine() {
+    let (peer_a, peer_b) = (PeerId::new(), PeerId::new());
+    let a = replica(peer_a);
+    let meta = a
+        .create_empty(
+            "Budget",
+            &[
+                ColumnDef { name: "item".into(), column_type: "TEXT".into() },
+                ColumnDef { name: "cost".into(), column_type: "REAL".into() },
+            ],
+            None,
+        )
+        .unwrap();
+    a.insert_row(&meta.id, &[("item", json!("rent")), ("cost", json!(900.5))]).unwrap();
+    a.rename_column(&meta.id, "cost", "amount").unwrap();
+    let events = change_events(&a, peer_a);
+    let entity_id = events[0].entity_id;
+    assert!(events.iter().all(|e| e.entity_id == entity_id));
+
+    let b = replica(peer_b);
+    let mut engine = SyncEngine::new(peer_b, SyncConfig::default());
+    engine.set_dataset_handler(Arc::new(DatasetApplicator::new(b.clone())));
+    let entity_store = Arc::new(EntityStore::open_in_memory().unwrap());
+    let event_store = Arc::new(EventStore::open_in_memory().unwrap());
+    let batch = EventBatchMessage {
+        entity_id,
+        events: events.clone(),
+        is_final: true,
+        batch_seq: 0,
+    };
+    engine.handle_event_batch(&peer_a, &batch, &entity_store, &event_store).await;
+
+    // The table, its rows and its name all arrived.
+    let received = b.get(&meta.id).unwrap();
+    assert_eq!(received.name, "Budget");
+    assert_eq!(received.row_count, 1);
+    let rows = b.query_dataset(&meta.id, 0, 10, None, Some("item"), false).unwrap();
+    assert_eq!(rows.columns, ["item", "amount"]);
+    assert_eq!(rows.rows, vec![vec![json!("rent"), json!(900.5)]]);
+
+    // The dataset is an entity for entity sync, and every event is kept
+    // so it can be relayed to other peers.
+    let entity = entity_store.get_entity(&entity_id.to_string()).unwrap().unwrap();
+    assert_eq!(entity.entity_type, DATASET_ENTITY_TYPE);
+    assert_eq!(event_store.get_events_for_entity(&entity_id).unwrap().len(), events.len());
+
+    // Local edits on B stamp after what it received.
+    b.update_cell(&meta.id, 0, "amount", json!(950)).unwrap();
+    let reply = change_events(&b, peer_b);
+    assert!(reply[0].timestamp > events.last().unwrap().timestamp);
+    for event in &reply {
+        let op = dataset_op_from_event(event).unwrap().unwrap();
+        let stamp = privstack_datasets::OpStamp::new(
+            event.timestamp.wall_time(),
+            event.timestamp.logical(),
+            event.peer_id.to_string(),
+        );
+        a.apply_replicated(&meta.id, &op, &stamp).unwrap();
+    }
+    let rows = a.query_dataset(&meta.id, 0, 10, None, None, false).unwrap();
+    assert_eq!(rows.rows, vec![vec![json!("rent"), json!(950.0)]]);
+}
+
+#[test]
+fn unrelated_events_are_not_dataset_events() {
+    let peer = PeerId::new();
+    let note = Event::full_snapshot(EntityId::new(), peer, "note", r#"{"title":"x"}"#);
+    assert!(dataset_op_from_event(&note).unwrap().is_none());
+
+    let deleted = Event::entity_deleted(EntityId::new(), peer, DATASET_ENTITY_TYPE);
+    assert!(dataset_op_from_event(&deleted).unwrap().is_some());
+}
@@ -0,0 +1,162 @@
+//! Session recording, per-peer metrics and the redacted diagnostics bundle.
+
+use privstack_storage::EntityStore;
+use privstack_sync::diagnostics::{
+    load_sessions, save_session, BatchReport, SessionRecorder, DIAGNOSTICS_FORMAT_VERSION,
+};
+use privstack_sync::transport::{DiscoveredPeer, DiscoveryMethod};
+use privstack_sync::{
+    export_diagnostics, summarize_peers, SessionDirection, SessionOutcome, SyncMessage, SyncPhase,
+    SyncSessionRecord, TransportKind,
+};
+use privstack_types::{EntityId, PeerId};
+
+fn peer(method: DiscoveryMethod, addresses: &[&str]) -> DiscoveredPeer {
+    DiscoveredPeer {
+        peer_id: PeerId::new(),
+        device_name: None,
+        discovery_method: method,
+        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+fn session(peer_id: PeerId, started_at: i64, failed: bool) -> SyncSessionRecord {
+    let mut rec = SessionRecorder::new(peer_id, SessionDirection::Outbound);
+    rec.set_peer(TransportKind::Dht, Some("Desk".into()));
+    rec.message_sent(&SyncMessage::Ping(1), 3);
+    rec.message_received(&SyncMessage::Pong(1), 2);
+    if failed {
+        rec.fail("timed out");
+    }
+    let mut record = rec.finish();
+    record.started_at = started_at;
+    record.finished_at = started_at + 10;
+    record
+}
+
+#[test]
+fn transport_kind_prefers_the_relayed_path() {
+    assert_eq!(TransportKind::of_peer(&peer(DiscoveryMethod::Mdns, &[])), TransportKind::Mdns);
+    assert_eq!(TransportKind::of_peer(&peer(DiscoveryMethod::Dht, &[])), TransportKind::Dht);
+    assert_eq!(
+        TransportKind::of_peer(&peer(DiscoveryMethod::Dht, &["/ip4/1.2.3.4/tcp/1/p2p-circuit"])),
+        TransportKind::Relay
+    );
+    assert_eq!(
+        TransportKind::of_peer(&peer(DiscoveryMethod::CloudRelay, &[])),
+        TransportKind::Relay
+    );
+}
+
+#[test]
+fn recorder_tracks_phases_traffic_and_errors() {
+    let mut rec = SessionRecorder::new(PeerId::new(), SessionDirection::Inbound);
+    rec.begin_phase(SyncPhase::Handshake);
+    rec.message_sent(&SyncMessage::Ping(1), 0);
+    rec.begin_phase(SyncPhase::EventExchange);
+    rec.message_received(&SyncMessage::Ping(2), 4);
+    rec.begin_phase(SyncPhase::Handshake);
+
+    let entity = EntityId::new();
+    rec.merge_report(BatchReport {
+        filtered_out: 2,
+        filtered_in: 1,
+        rejected: 1,
+        apply_errors: vec![(entity, "bad json".into())],
+    });
+    for _ in 0..60 {
+        rec.apply_error(entity, "again");
+    }
+    rec.fail("peer went away");
+
+    let record = rec.finish();
+    // Re-entering a phase adds to its timing instead of a second entry.
+    let phases: Vec<_> = record.phases.iter().map(|p| p.phase).collect();
+    assert_eq!(phases, [SyncPhase::Handshake, SyncPhase::EventExchange]);
+    assert!(record.sent.bytes > 0);
+    assert_eq!(record.received.events, 4);
+    assert_eq!(record.sent.policy_filtered, 2);
+    assert_eq!(record.received.policy_filtered, 1);
+    assert_eq!(record.events_rejected, 1);
+    assert_eq!(record.apply_errors.len(), 50);
+    assert_eq!(record.apply_error_count(), 61);
+    assert_eq!(record.apply_errors[0].entity_id, entity.to_string());
+    assert_eq!(record.outcome, SessionOutcome::Failed);
+    assert_eq!(record.failed_phase, Some(SyncPhase::Handshake));
+}
+
+#[test]
+fn sessions_persist_in_a_bounded_history() {
+    let store = EntityStore::open_in_memory().unwrap();
+    let (a, b) = (PeerId::new(), PeerId::new());
+    for i in 0..4 {
+        save_session(&store, &session(if i == 3 { b } else { a }, i, false), 3).unwrap();
+    }
+
+    let all = load_sessions(&store, None, 10).unwrap();
+    assert_eq!(all.iter().map(|s| s.started_at).collect::<Vec<_>>(), [3, 2, 1]);
+    assert_eq!(load_sessions(&store, Some(&b.to_string()), 10).unwrap().len(), 1);
+    assert_eq!(all[0], session_roundtrip(&all[0]));
+}
+
+fn session_roundtrip(record: &SyncSessionRecord) -> SyncSessionRecord {
+    serde_json::from_str(&serde_json::to_string(record).unwrap()).unwrap()
+}
+
+#[test]
+fn peer_metrics_sum_the_history() {
+    let (a, b) = (PeerId::new(), PeerId::new());
+    let sessions = vec![session(a, 100, false), session(a, 200, true), session(b, 150, false)];
+
+    let metrics = summarize_peers(&sessions);
+    assert_eq!(metrics.len(), 2);
+    let first = &metrics[0];
+    assert_eq!(first.peer_id, a.to_string());
+    assert_eq!(first.sessions, 2);
+    assert_eq!(first.failed_sessions, 1);
+    assert_eq!(first.events_sent, 6);
+    assert_eq!(first.events_received, 4);
+    assert_eq!(first.last_sync_at, 210);
+    assert_eq!(first.last_success_at, Some(110));
+    assert_eq!(first.last_error.as_deref(), Some("timed out"));
+    assert_eq!(first.device_name.as_deref(), Some("Desk"));
+    assert_eq!(first.last_transport, TransportKind::Dht);
+    assert_eq!(metrics[1].peer_id, b.to_string());
+}
+
+#[test]
+fn diagnostics_bundle_is_redacted() {
+    let local = PeerId::new();
+    let remote = PeerId::new();
+    let entity = EntityId::new();
+
+    let mut rec = SessionRecorder::new(remote, SessionDirection::Outbound);
+    rec.set_peer(TransportKind::Relay, Some("Alice's phone".into()));
+    rec.apply_error(entity, format!("entity {entity} is broken"));
+    rec.fail(format!("dial /ip4/10.0.0.7/tcp/4001 failed for {remote}"));
+    let sessions = vec![rec.finish()];
+
+    let bundle = export_diagnostics(&local, &sessions);
+    assert_eq!(bundle.format_version, DIAGNOSTICS_FORMAT_VERSION);
+    let json = serde_json::to_string(&bundle).unwrap();
+    for secret in [
+        local.to_string(),
+        remote.to_string(),
+        entity.to_string(),
+        "Alice".to_string(),
+        "10.0.0.7".to_string(),
+    ] {
+        assert!(!json.contains(&secret), "bundle leaks {secret}");
+    }
+
+    let exported = &bundle.sessions[0];
+    assert!(exported.peer_id.starts_with("peer-"));
+    assert_eq!(bundle.peers[0].peer_id, exported.peer_id);
+    assert!(exported.apply_errors[0].entity_id.starts_with("entity-"));
+    assert!(exported.error.as_deref().unwrap().starts_with("dial <addr> failed for id-"));
+    assert_eq!(exported.transport, TransportKind::Relay);
+
+    // A fresh salt per bundle: the same peer cannot be linked across exports.
+    let again = export_diagnostics(&local, &sessions);
+    assert_ne!(again.sessions[0].peer_id, exported.peer_id);
+}
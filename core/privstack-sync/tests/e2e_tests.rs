@@ -0,0 +1,304 @@
+use privstack_sync::e2e::{KeyScope, PayloadKeyring};
+use privstack_sync::SyncError;
+use privstack_types::{EntityId, Event, EventId, EventPayload, PeerId};
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+fn make_event(entity_id: EntityId) -> Event {
+    Event::entity_created(entity_id, PeerId::new(), "note", r#"{"title":"secret"}"#)
+}
+
+/// Two keyrings that have exchanged their current keys.
+fn paired_keyrings() -> (PayloadKeyring, PayloadKeyring) {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    a.ensure_group_key();
+    b.ensure_group_key();
+    let a_pk = a.exchange_public_key();
+    let b_pk = b.exchange_public_key();
+
+    let for_b = a.seal_current_keys(&b_pk, |_| true).unwrap();
+    let for_a = b.seal_current_keys(&a_pk, |_| true).unwrap();
+    b.import(&for_b);
+    a.import(&for_a);
+    (a, b)
+}
+
+// ── Keys ────────────────────────────────────────────────────────
+
+#[test]
+fn ensure_group_key_is_stable() {
+    let mut ring = PayloadKeyring::new();
+    let first = ring.ensure_group_key().key_id.clone();
+    let second = ring.ensure_group_key().key_id.clone();
+    assert_eq!(first, second);
+    assert_eq!(ring.len(), 1);
+    assert_eq!(ring.current_key(&KeyScope::Group).unwrap().epoch, 1);
+}
+
+#[test]
+fn entity_keys_are_separate_from_group_key() {
+    let mut ring = PayloadKeyring::new();
+    let entity = EntityId::new();
+    let group = ring.ensure_group_key().key_id.clone();
+    let ent = ring.ensure_entity_key(entity).key_id.clone();
+    assert_ne!(group, ent);
+    assert_eq!(ring.current_key(&KeyScope::Entity(entity)).unwrap().key_id, ent);
+}
+
+#[test]
+fn rotate_bumps_epoch_for_every_scope() {
+    let mut ring = PayloadKeyring::new();
+    let entity = EntityId::new();
+    let old_group = ring.ensure_group_key().key_id.clone();
+    ring.ensure_entity_key(entity);
+
+    assert_eq!(ring.rotate(), 2);
+
+    let group = ring.current_key(&KeyScope::Group).unwrap();
+    assert_eq!(group.epoch, 2);
+    assert_ne!(group.key_id, old_group);
+    assert_eq!(ring.current_key(&KeyScope::Entity(entity)).unwrap().epoch, 2);
+    // Old keys are kept for in-flight events
+    assert!(ring.key(&old_group).is_some());
+    assert_eq!(ring.len(), 4);
+}
+
+#[test]
+fn rotate_empty_keyring_is_noop() {
+    let mut ring = PayloadKeyring::new();
+    assert_eq!(ring.rotate(), 0);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn debug_output_redacts_key_material() {
+    let mut ring = PayloadKeyring::new();
+    ring.exchange_public_key();
+    ring.ensure_group_key();
+    let debug = format!("{ring:?}");
+    assert!(debug.contains("REDACTED"));
+}
+
+// ── Encryption ──────────────────────────────────────────────────
+
+#[test]
+fn encrypt_decrypt_roundtrip() {
+    let mut ring = PayloadKeyring::new();
+    ring.ensure_group_key();
+    let event = make_event(EntityId::new());
+
+    let sealed = ring.encrypt_event(&event).unwrap();
+    assert!(matches!(sealed.payload, EventPayload::Encrypted { .. }));
+    assert_eq!(sealed.id, event.id);
+    assert_eq!(sealed.entity_id, event.entity_id);
+
+    let json = serde_json::to_string(&sealed).unwrap();
+    assert!(!json.contains("secret"), "ciphertext leaked plaintext");
+
+    let opened = ring.decrypt_event(&sealed).unwrap();
+    assert_eq!(opened, event);
+}
+
+#[test]
+fn encrypt_prefers_entity_key() {
+    let mut ring = PayloadKeyring::new();
+    let entity = EntityId::new();
+    ring.ensure_group_key();
+    let entity_key = ring.ensure_entity_key(entity).key_id.clone();
+
+    let sealed = ring.encrypt_event(&make_event(entity)).unwrap();
+    match sealed.payload {
+        EventPayload::Encrypted { key_id, .. } => assert_eq!(key_id, entity_key),
+        other => panic!("expected Encrypted, got {other:?}"),
+    }
+}
+
+#[test]
+fn encrypt_without_key_fails() {
+    let ring = PayloadKeyring::new();
+    let result = ring.encrypt_event(&make_event(EntityId::new()));
+    assert!(matches!(result, Err(SyncError::Encryption(_))));
+}
+
+#[test]
+fn encrypt_already_encrypted_is_unchanged() {
+    let mut ring = PayloadKeyring::new();
+    ring.ensure_group_key();
+    let sealed = ring.encrypt_event(&make_event(EntityId::new())).unwrap();
+    assert_eq!(ring.encrypt_event(&sealed).unwrap(), sealed);
+}
+
+#[test]
+fn decrypt_plaintext_is_passthrough() {
+    let ring = PayloadKeyring::new();
+    let event = make_event(EntityId::new());
+    assert_eq!(ring.decrypt_event(&event).unwrap(), event);
+}
+
+#[test]
+fn decrypt_with_unknown_key_fails() {
+    let mut sender = PayloadKeyring::new();
+    sender.ensure_group_key();
+    let sealed = sender.encrypt_event(&make_event(EntityId::new())).unwrap();
+
+    let stranger = PayloadKeyring::new();
+    let result = stranger.decrypt_event(&sealed);
+    assert!(matches!(result, Err(SyncError::Encryption(_))));
+}
+
+#[test]
+fn decrypt_rejects_payload_moved_to_another_event() {
+    let mut ring = PayloadKeyring::new();
+    ring.ensure_group_key();
+    let mut sealed = ring.encrypt_event(&make_event(EntityId::new())).unwrap();
+    sealed.id = EventId::new();
+
+    let result = ring.decrypt_event(&sealed);
+    assert!(matches!(result, Err(SyncError::Encryption(_))));
+}
+
+#[test]
+fn decrypt_rejects_tampered_ciphertext() {
+    let mut ring = PayloadKeyring::new();
+    ring.ensure_group_key();
+    let mut sealed = ring.encrypt_event(&make_event(EntityId::new())).unwrap();
+    if let EventPayload::Encrypted { ciphertext, .. } = &mut sealed.payload {
+        let mut chars: Vec<char> = ciphertext.chars().collect();
+        let last = chars.len() - 3;
+        chars[last] = if chars[last] == 'A' { 'B' } else { 'A' };
+        *ciphertext = chars.into_iter().collect();
+    }
+    assert!(ring.decrypt_event(&sealed).is_err());
+}
+
+#[test]
+fn decrypt_events_drops_undecryptable() {
+    let mut sender = PayloadKeyring::new();
+    sender.ensure_group_key();
+    let plain = make_event(EntityId::new());
+    let sealed = sender.encrypt_event(&make_event(EntityId::new())).unwrap();
+
+    let receiver = PayloadKeyring::new();
+    let opened = receiver.decrypt_events(&[plain.clone(), sealed]);
+    assert_eq!(opened, vec![plain]);
+}
+
+// ── Key exchange ────────────────────────────────────────────────
+
+#[test]
+fn exchanged_keys_decrypt_each_others_events() {
+    let (a, b) = paired_keyrings();
+    let event = make_event(EntityId::new());
+
+    let from_a = a.encrypt_event(&event).unwrap();
+    assert_eq!(b.decrypt_event(&from_a).unwrap(), event);
+
+    let from_b = b.encrypt_event(&event).unwrap();
+    assert_eq!(a.decrypt_event(&from_b).unwrap(), event);
+}
+
+#[test]
+fn exchanged_keyrings_converge_on_current_group_key() {
+    let (a, b) = paired_keyrings();
+    assert_eq!(
+        a.current_key(&KeyScope::Group).unwrap().key_id,
+        b.current_key(&KeyScope::Group).unwrap().key_id
+    );
+}
+
+#[test]
+fn seal_respects_scope_filter() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    let shared = EntityId::new();
+    let private = EntityId::new();
+    a.ensure_group_key();
+    a.ensure_entity_key(shared);
+    a.ensure_entity_key(private);
+
+    let sealed = a
+        .seal_current_keys(&b.exchange_public_key(), |scope| *scope != KeyScope::Entity(private))
+        .unwrap();
+    assert_eq!(sealed.len(), 2);
+    assert_eq!(b.import(&sealed), 2);
+    assert!(b.current_key(&KeyScope::Entity(shared)).is_some());
+    assert!(b.current_key(&KeyScope::Entity(private)).is_none());
+}
+
+#[test]
+fn seal_only_shares_current_epoch() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    a.ensure_group_key();
+    a.rotate();
+
+    let sealed = a.seal_current_keys(&b.exchange_public_key(), |_| true).unwrap();
+    assert_eq!(sealed.len(), 1);
+    assert_eq!(sealed[0].epoch, 2);
+}
+
+#[test]
+fn import_is_idempotent() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    a.ensure_group_key();
+    let sealed = a.seal_current_keys(&b.exchange_public_key(), |_| true).unwrap();
+    assert_eq!(b.import(&sealed), 1);
+    assert_eq!(b.import(&sealed), 0);
+}
+
+#[test]
+fn import_rejects_keys_sealed_for_someone_else() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    let mut c = PayloadKeyring::new();
+    a.ensure_group_key();
+    c.exchange_public_key();
+    let sealed = a.seal_current_keys(&b.exchange_public_key(), |_| true).unwrap();
+    assert_eq!(c.import(&sealed), 0);
+}
+
+#[test]
+fn import_rejects_fingerprint_mismatch() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    a.ensure_group_key();
+    let mut sealed = a.seal_current_keys(&b.exchange_public_key(), |_| true).unwrap();
+    sealed[0].key_id = "00".repeat(16);
+    assert_eq!(b.import(&sealed), 0);
+}
+
+#[test]
+fn import_without_exchange_key_is_noop() {
+    let mut a = PayloadKeyring::new();
+    let mut b = PayloadKeyring::new();
+    a.ensure_group_key();
+    let sealed = a.seal_current_keys(&b.exchange_public_key(), |_| true).unwrap();
+    let mut fresh = PayloadKeyring::new();
+    assert_eq!(fresh.import(&sealed), 0);
+}
+
+#[test]
+fn removed_peer_cannot_read_events_after_rotation() {
+    let (mut a, removed) = paired_keyrings();
+    a.rotate();
+
+    let sealed = a.encrypt_event(&make_event(EntityId::new())).unwrap();
+    assert!(removed.decrypt_event(&sealed).is_err());
+}
+
+// ── Persistence ─────────────────────────────────────────────────
+
+#[test]
+fn keyring_survives_serde_roundtrip() {
+    let mut ring = PayloadKeyring::new();
+    let pk = ring.exchange_public_key();
+    ring.ensure_group_key();
+    let sealed = ring.encrypt_event(&make_event(EntityId::new())).unwrap();
+
+    let json = serde_json::to_string(&ring).unwrap();
+    let mut restored: PayloadKeyring = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.exchange_public_key(), pk);
+    assert!(restored.decrypt_event(&sealed).is_ok());
+}
@@ -1,6 +1,7 @@
 use privstack_storage::{EntityStore, EventStore};
 use privstack_sync::protocol::{
-    EventBatchMessage, HelloMessage, SyncMessage, SyncRequestMessage, PROTOCOL_VERSION,
+    EventBatchMessage, HelloMessage, SyncMessage, SyncRequestMessage, WireFormat,
+    PROTOCOL_VERSION,
 };
 use privstack_sync::{SyncConfig, SyncEngine};
 use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
@@ -188,6 +189,34 @@ async fn handle_hello_rejects_wrong_version() {
     }
 }
 
+#[tokio::test]
+async fn handle_hello_negotiates_binary_wire_format() {
+    let engine = make_engine(PeerId::new());
+    let hello = HelloMessage::new(PeerId::new(), "Remote");
+
+    match engine.handle_hello(&hello).await {
+        SyncMessage::HelloAck(ack) => {
+            assert!(ack.accepted);
+            assert_eq!(ack.wire_format, Some(WireFormat::CBOR_ZSTD));
+        }
+        _ => panic!("Expected HelloAck"),
+    }
+}
+
+#[tokio::test]
+async fn handle_hello_from_legacy_peer_stays_on_json() {
+    let engine = make_engine(PeerId::new());
+    let hello = HelloMessage::new(PeerId::new(), "Old Remote").with_wire(None);
+
+    match engine.handle_hello(&hello).await {
+        SyncMessage::HelloAck(ack) => {
+            assert!(ack.accepted);
+            assert_eq!(ack.wire_format, None);
+        }
+        _ => panic!("Expected HelloAck"),
+    }
+}
+
 // ── Handle sync request ──────────────────────────────────────────
 
 #[tokio::test]
@@ -599,6 +628,57 @@ async fn set_acl_handler_routes_acl_events() {
     assert_eq!(role, Some(SyncRole::Viewer));
 }
 
+#[tokio::test]
+async fn unauthorized_acl_event_is_dropped() {
+    use privstack_sync::policy::{EnterpriseSyncPolicy, EntityAcl, SyncRole};
+    use privstack_sync::AclApplicator;
+
+    let policy = std::sync::Arc::new(EnterpriseSyncPolicy::new());
+    let local = PeerId::new();
+    let remote = PeerId::new();
+    let entity = EntityId::new();
+
+    policy.known_peers.write().await.insert(remote);
+    let acl = EntityAcl::new(entity).with_peer_role(remote, SyncRole::Admin);
+    policy.acls.write().await.insert(entity, acl);
+
+    let mut engine = SyncEngine::with_policy(local, SyncConfig::default(), policy.clone());
+    engine.set_acl_handler(std::sync::Arc::new(AclApplicator::new(policy.clone())));
+
+    let (entity_store, event_store) = make_stores();
+
+    // An Admin may relay ACL events, but may not promote itself to Owner
+    let escalation = Event::new(
+        entity,
+        remote,
+        HybridTimestamp::now(),
+        EventPayload::AclGrantPeer {
+            entity_id: entity.to_string(),
+            peer_id: remote.to_string(),
+            role: "Owner".to_string(),
+        },
+    );
+
+    let batch = EventBatchMessage {
+        entity_id: entity,
+        events: vec![escalation],
+        is_final: true,
+        batch_seq: 0,
+    };
+
+    let (ack, updated) = engine
+        .handle_event_batch(&remote, &batch, &entity_store, &event_store)
+        .await;
+
+    match ack {
+        SyncMessage::EventAck(a) => assert_eq!(a.received_count, 0),
+        other => panic!("Expected EventAck, got {:?}", other),
+    }
+    assert!(updated.is_empty());
+    assert!(event_store.get_events_for_entity(&entity).unwrap().is_empty());
+    assert_eq!(policy.resolve_role(&remote, &entity).await, Some(SyncRole::Admin));
+}
+
 // ── Peer disconnect preserving peer in all_peers ────────────────
 
 #[tokio::test]
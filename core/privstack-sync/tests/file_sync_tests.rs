@@ -0,0 +1,466 @@
+use privstack_crypto::KdfParams;
+use privstack_storage::{EntityStore, EventStore};
+use privstack_sync::applicator::EventApplicator;
+use privstack_sync::cloud::icloud::{ICloudConfig, ICloudStorage};
+use privstack_sync::cloud::{
+    CloudStorage, FileSyncConfig, FileSyncEngine, FileSyncState, FolderKey, SyncFileKind,
+    SyncFileName, FOLDER_MANIFEST_NAME, MAX_FOLDER_TIME_COST,
+};
+use privstack_sync::SyncError;
+use privstack_types::{EntityId, Event, PeerId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+fn fast_params() -> KdfParams {
+    KdfParams {
+        memory_cost: 1024,
+        time_cost: 1,
+        parallelism: 1,
+    }
+}
+
+fn make_storage(dir: &TempDir) -> ICloudStorage {
+    ICloudStorage::new(ICloudConfig {
+        container_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    })
+}
+
+struct Device {
+    peer_id: PeerId,
+    entity_store: Arc<EntityStore>,
+    event_store: Arc<EventStore>,
+    storage: ICloudStorage,
+    engine: FileSyncEngine,
+}
+
+impl Device {
+    async fn new(dir: &TempDir, config: FileSyncConfig) -> Self {
+        let peer_id = PeerId::new();
+        let entity_store = Arc::new(EntityStore::open_in_memory().unwrap());
+        let event_store = Arc::new(EventStore::open_in_memory().unwrap());
+        let storage = make_storage(dir);
+        let key = FolderKey::unlock(&storage, "correct horse", &fast_params())
+            .await
+            .unwrap();
+        let engine = FileSyncEngine::new(
+            peer_id,
+            key,
+            entity_store.clone(),
+            event_store.clone(),
+            config,
+            FileSyncState::default(),
+        );
+        Self {
+            peer_id,
+            entity_store,
+            event_store,
+            storage,
+            engine,
+        }
+    }
+
+    /// Records a local change the way the app does: apply, then store the event.
+    fn create(&self, title: &str) -> EntityId {
+        let entity_id = EntityId::new();
+        let event = Event::entity_created(
+            entity_id,
+            self.peer_id,
+            "note",
+            format!(r#"{{"title":"{title}"}}"#),
+        );
+        EventApplicator::new(self.peer_id)
+            .apply_event(&event, &self.entity_store, None, None)
+            .unwrap();
+        self.event_store.save_event(&event).unwrap();
+        entity_id
+    }
+
+    fn delete(&self, entity_id: EntityId) {
+        let event = Event::entity_deleted(entity_id, self.peer_id, "note");
+        EventApplicator::new(self.peer_id)
+            .apply_event(&event, &self.entity_store, None, None)
+            .unwrap();
+        self.event_store.save_event(&event).unwrap();
+    }
+
+    fn title(&self, entity_id: EntityId) -> Option<String> {
+        self.entity_store
+            .get_entity(&entity_id.to_string())
+            .unwrap()
+            .and_then(|e| e.data["title"].as_str().map(str::to_string))
+    }
+}
+
+// ── File names ──────────────────────────────────────────────────
+
+#[test]
+fn file_name_roundtrip() {
+    let name = SyncFileName {
+        peer_id: PeerId::new(),
+        seq: 42,
+        kind: SyncFileKind::Segment,
+    };
+    let formatted = name.to_name();
+    assert!(formatted.starts_with("ps-"));
+    assert!(formatted.ends_with(".000000000042.seg"));
+    assert_eq!(SyncFileName::parse(&formatted), Some(name));
+}
+
+#[test]
+fn file_name_snapshot_roundtrip() {
+    let name = SyncFileName {
+        peer_id: PeerId::new(),
+        seq: 7,
+        kind: SyncFileKind::Snapshot,
+    };
+    assert_eq!(SyncFileName::parse(&name.to_name()), Some(name));
+}
+
+#[test]
+fn file_name_rejects_foreign_files() {
+    assert!(SyncFileName::parse(FOLDER_MANIFEST_NAME).is_none());
+    assert!(SyncFileName::parse("notes.txt").is_none());
+    assert!(SyncFileName::parse("ps-not-a-peer.000000000001.seg").is_none());
+    let valid = SyncFileName {
+        peer_id: PeerId::new(),
+        seq: 1,
+        kind: SyncFileKind::Segment,
+    }
+    .to_name();
+    assert!(SyncFileName::parse(&valid.replace(".seg", ".tmp")).is_none());
+}
+
+// ── Folder key ──────────────────────────────────────────────────
+
+#[tokio::test]
+async fn unlock_creates_manifest_then_reopens() {
+    let dir = TempDir::new().unwrap();
+    let storage = make_storage(&dir);
+
+    let first = FolderKey::unlock(&storage, "pass", &fast_params()).await.unwrap();
+    let files = storage.list_files().await.unwrap();
+    assert!(files.iter().any(|f| f.name == FOLDER_MANIFEST_NAME));
+
+    let other = make_storage(&dir);
+    let second = FolderKey::unlock(&other, "pass", &fast_params()).await.unwrap();
+    assert_eq!(first.as_bytes(), second.as_bytes());
+}
+
+#[tokio::test]
+async fn unlock_wrong_passphrase_fails() {
+    let dir = TempDir::new().unwrap();
+    let storage = make_storage(&dir);
+    FolderKey::unlock(&storage, "pass", &fast_params()).await.unwrap();
+
+    let result = FolderKey::unlock(&storage, "wrong", &fast_params()).await;
+    assert!(matches!(result, Err(SyncError::Auth(_))));
+}
+
+#[tokio::test]
+async fn unlock_clamps_tampered_kdf_params() {
+    let dir = TempDir::new().unwrap();
+    let storage = make_storage(&dir);
+    FolderKey::unlock(&storage, "pass", &fast_params()).await.unwrap();
+
+    // Whoever can write the folder can ask every device for an absurd
+    // derivation; it's bounded instead of run.
+    let file = storage
+        .list_files()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|f| f.name == FOLDER_MANIFEST_NAME)
+        .unwrap();
+    let mut manifest: serde_json::Value =
+        serde_json::from_slice(&storage.download(&file.id).await.unwrap()).unwrap();
+    manifest["time_cost"] = u32::MAX.into();
+    manifest["parallelism"] = u32::MAX.into();
+    storage
+        .upload(FOLDER_MANIFEST_NAME, &serde_json::to_vec(&manifest).unwrap())
+        .await
+        .unwrap();
+
+    let result = FolderKey::unlock(&storage, "pass", &fast_params()).await;
+    assert!(matches!(result, Err(SyncError::Auth(_))));
+}
+
+#[tokio::test]
+async fn unlock_clamps_new_manifest_params() {
+    let dir = TempDir::new().unwrap();
+    let storage = make_storage(&dir);
+    let params = KdfParams {
+        time_cost: u32::MAX,
+        ..fast_params()
+    };
+    let first = FolderKey::unlock(&storage, "pass", &params).await.unwrap();
+
+    let bounded = KdfParams {
+        time_cost: MAX_FOLDER_TIME_COST,
+        ..fast_params()
+    };
+    let second = FolderKey::unlock(&storage, "pass", &bounded).await.unwrap();
+    assert_eq!(first.as_bytes(), second.as_bytes());
+}
+
+// ── Sync passes ─────────────────────────────────────────────────
+
+#[tokio::test]
+async fn two_devices_sync_through_folder() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    let mut b = Device::new(&dir, FileSyncConfig::default()).await;
+
+    let from_a = a.create("from a");
+    let from_b = b.create("from b");
+
+    let report = a.engine.sync(&a.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 1);
+    assert_eq!(report.segments_written, 1);
+    assert_eq!(report.events_applied, 0);
+
+    let report = b.engine.sync(&b.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 1);
+    assert_eq!(report.events_applied, 1);
+    assert_eq!(report.updated_entities, vec![from_a]);
+    assert_eq!(b.title(from_a).as_deref(), Some("from a"));
+
+    a.engine.sync(&a.storage).await.unwrap();
+    assert_eq!(a.title(from_b).as_deref(), Some("from b"));
+
+    // Received segment events are stored so they can propagate over P2P
+    assert_eq!(b.event_store.get_events_for_entity(&from_a).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn repeated_pass_is_idempotent() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    let mut b = Device::new(&dir, FileSyncConfig::default()).await;
+
+    a.create("once");
+    a.engine.sync(&a.storage).await.unwrap();
+    b.engine.sync(&b.storage).await.unwrap();
+
+    let report = a.engine.sync(&a.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 0);
+    let report = b.engine.sync(&b.storage).await.unwrap();
+    assert_eq!(report.files_read, 0);
+    assert_eq!(report.events_applied, 0);
+}
+
+#[tokio::test]
+async fn segments_are_split_by_size() {
+    let dir = TempDir::new().unwrap();
+    let config = FileSyncConfig {
+        max_events_per_segment: 2,
+        snapshot_interval: 0,
+    };
+    let mut a = Device::new(&dir, config.clone()).await;
+    for i in 0..5 {
+        a.create(&format!("note {i}"));
+    }
+
+    let report = a.engine.sync(&a.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 5);
+    assert_eq!(report.segments_written, 3);
+    assert_eq!(a.engine.state().next_seq(), 3);
+}
+
+#[tokio::test]
+async fn files_are_encrypted_at_rest() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    a.create("top secret title");
+    a.engine.sync(&a.storage).await.unwrap();
+
+    for file in a.storage.list_files().await.unwrap() {
+        if SyncFileName::parse(&file.name).is_none() {
+            continue;
+        }
+        let bytes = a.storage.download(&file.id).await.unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("top secret"));
+    }
+}
+
+#[tokio::test]
+async fn compaction_lets_new_device_start_from_snapshot() {
+    let dir = TempDir::new().unwrap();
+    let config = FileSyncConfig {
+        max_events_per_segment: 500,
+        snapshot_interval: 2,
+    };
+    let mut a = Device::new(&dir, config.clone()).await;
+    let first = a.create("first");
+    a.engine.sync(&a.storage).await.unwrap();
+    let second = a.create("second");
+    let report = a.engine.sync(&a.storage).await.unwrap();
+    assert!(report.compacted);
+
+    let names: Vec<SyncFileName> = a
+        .storage
+        .list_files()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|f| SyncFileName::parse(&f.name))
+        .collect();
+    assert_eq!(names.len(), 1, "older segments should be deleted");
+    assert_eq!(names[0].kind, SyncFileKind::Snapshot);
+
+    let mut late = Device::new(&dir, config).await;
+    let report = late.engine.sync(&late.storage).await.unwrap();
+    assert_eq!(report.files_read, 1);
+    assert_eq!(late.title(first).as_deref(), Some("first"));
+    assert_eq!(late.title(second).as_deref(), Some("second"));
+    assert_eq!(late.engine.state().applied_seq(&a.peer_id), Some(names[0].seq));
+}
+
+#[tokio::test]
+async fn deletions_survive_compaction() {
+    let dir = TempDir::new().unwrap();
+    let config = FileSyncConfig {
+        max_events_per_segment: 500,
+        snapshot_interval: 1,
+    };
+    let mut a = Device::new(&dir, config.clone()).await;
+    let mut b = Device::new(&dir, FileSyncConfig::default()).await;
+
+    let doomed = a.create("doomed");
+    let kept = a.create("kept");
+    a.engine.sync(&a.storage).await.unwrap();
+    b.engine.sync(&b.storage).await.unwrap();
+    assert_eq!(b.title(doomed).as_deref(), Some("doomed"));
+
+    // B is offline while A deletes, then compacts the delete's segment away.
+    a.delete(doomed);
+    a.engine.sync(&a.storage).await.unwrap();
+    let names: Vec<SyncFileName> = a
+        .storage
+        .list_files()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|f| SyncFileName::parse(&f.name))
+        .collect();
+    assert!(names.iter().all(|n| n.kind == SyncFileKind::Snapshot));
+
+    let report = b.engine.sync(&b.storage).await.unwrap();
+    assert_eq!(report.files_read, 1);
+    assert_eq!(b.title(doomed), None);
+    assert_eq!(b.title(kept).as_deref(), Some("kept"));
+    assert!(report.updated_entities.contains(&doomed));
+
+    // A device that never saw the entity learns of the delete without
+    // counting it, and carries it into its own snapshots.
+    let mut late = Device::new(&dir, config).await;
+    let report = late.engine.sync(&late.storage).await.unwrap();
+    assert_eq!(report.updated_entities, vec![kept]);
+    assert_eq!(late.event_store.get_deletions().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn snapshot_after_applied_segments_is_skipped() {
+    let dir = TempDir::new().unwrap();
+    let config = FileSyncConfig {
+        max_events_per_segment: 500,
+        snapshot_interval: 1,
+    };
+    let mut a = Device::new(&dir, config.clone()).await;
+    let mut b = Device::new(&dir, FileSyncConfig::default()).await;
+
+    a.create("note");
+    // Segment 0 is written, then compacted into snapshot 1 in the same pass
+    a.engine.sync(&a.storage).await.unwrap();
+    b.engine.sync(&b.storage).await.unwrap();
+    assert_eq!(b.engine.state().applied_seq(&a.peer_id), Some(1));
+
+    a.create("another");
+    a.engine.sync(&a.storage).await.unwrap();
+    let report = b.engine.sync(&b.storage).await.unwrap();
+    // Segment 2 is read; snapshot 3 directly follows it and is skipped
+    assert_eq!(report.files_read, 1);
+    assert_eq!(b.engine.state().applied_seq(&a.peer_id), Some(3));
+}
+
+#[tokio::test]
+async fn local_only_entities_are_not_uploaded() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    let mut b = Device::new(&dir, FileSyncConfig::default()).await;
+
+    let entity_id = EntityId::new();
+    let event = Event::entity_created(
+        entity_id,
+        a.peer_id,
+        "note",
+        r#"{"title":"private","local_only":true}"#,
+    );
+    EventApplicator::new(a.peer_id)
+        .apply_event(&event, &a.entity_store, None, None)
+        .unwrap();
+    a.event_store.save_event(&event).unwrap();
+
+    let report = a.engine.sync(&a.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 0);
+    b.engine.sync(&b.storage).await.unwrap();
+    assert!(b.title(entity_id).is_none());
+}
+
+#[tokio::test]
+async fn restored_state_does_not_reupload() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    a.create("persisted");
+    a.engine.sync(&a.storage).await.unwrap();
+
+    let json = a.engine.state().to_json().unwrap();
+    let state = FileSyncState::from_json(&json).unwrap();
+    assert_eq!(state.next_seq(), a.engine.state().next_seq());
+    assert_eq!(state.last_uploaded(), a.engine.state().last_uploaded());
+
+    let key = FolderKey::unlock(&a.storage, "correct horse", &fast_params())
+        .await
+        .unwrap();
+    let mut restarted = FileSyncEngine::new(
+        a.peer_id,
+        key,
+        a.entity_store.clone(),
+        a.event_store.clone(),
+        FileSyncConfig::default(),
+        state,
+    );
+    let report = restarted.sync(&a.storage).await.unwrap();
+    assert_eq!(report.events_uploaded, 0);
+}
+
+#[tokio::test]
+async fn lost_state_never_reuses_sequence_numbers() {
+    let dir = TempDir::new().unwrap();
+    let mut a = Device::new(&dir, FileSyncConfig::default()).await;
+    a.create("first");
+    a.engine.sync(&a.storage).await.unwrap();
+
+    let key = FolderKey::unlock(&a.storage, "correct horse", &fast_params())
+        .await
+        .unwrap();
+    let mut fresh = FileSyncEngine::new(
+        a.peer_id,
+        key,
+        a.entity_store.clone(),
+        a.event_store.clone(),
+        FileSyncConfig::default(),
+        FileSyncState::default(),
+    );
+    fresh.sync(&a.storage).await.unwrap();
+    assert!(fresh.state().next_seq() >= 2);
+}
+
+#[test]
+fn state_json_defaults_missing_fields() {
+    let state = FileSyncState::from_json("{}").unwrap();
+    assert_eq!(state.next_seq(), 0);
+    assert!(state.last_uploaded().is_none());
+}
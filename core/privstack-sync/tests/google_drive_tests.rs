@@ -1,8 +1,14 @@
+#[macro_use]
+mod support;
+
 use privstack_sync::cloud::google_drive::{GoogleDriveConfig, GoogleDriveStorage};
 use privstack_sync::cloud::CloudStorage;
+use support::{DriveTestServer, GoogleDriveFixture};
 use wiremock::matchers::{method, path, path_regex, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
+storage_conformance_tests!(GoogleDriveFixture::start().await);
+
 // ── Config defaults ─────────────────────────────────────────────
 
 #[test]
@@ -1274,3 +1280,17 @@ async fn get_changes_with_no_file_and_not_removed() {
     assert!(changes.deleted.is_empty());
     assert_eq!(changes.next_cursor, Some("cursor_2".to_string()));
 }
+
+// ── Against the in-process Drive server ─────────────────────────
+
+#[tokio::test]
+async fn reupload_with_quoted_name_updates_in_place() {
+    let server = DriveTestServer::start().await;
+    let storage = GoogleDriveStorage::new(server.config());
+    storage.set_tokens("token".to_string(), None).await;
+
+    let first = storage.upload("it's \\ done.txt", b"one").await.unwrap();
+    let second = storage.upload("it's \\ done.txt", b"two").await.unwrap();
+    assert_eq!(first.id, second.id);
+    assert_eq!(server.names(), vec!["PrivStack", "it's \\ done.txt", "sync"]);
+}
@@ -0,0 +1,390 @@
+//! Tests for time- and use-limited grants: enforcement, sweeping,
+//! persistence, and propagation through ACL events.
+
+use privstack_sync::acl_applicator::{is_acl_event, AclApplicator};
+use privstack_sync::policy::{
+    AuditAction, EnterpriseSyncPolicy, GrantLimits, GrantState, LapseReason, PersonalSyncPolicy,
+    SyncPolicy, SyncRole,
+};
+use privstack_sync::policy_store::PolicyStore;
+use privstack_sync::AclEventHandler;
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn expired() -> GrantLimits {
+    GrantLimits::new().with_expires_at(SystemTime::now() - Duration::from_secs(1))
+}
+
+fn hour() -> GrantLimits {
+    GrantLimits::new().with_expires_in(Duration::from_secs(3600))
+}
+
+fn event(entity: EntityId) -> Event {
+    Event::new(entity, PeerId::new(), HybridTimestamp::now(), EventPayload::FullSnapshot {
+        entity_type: "note".to_string(),
+        json_data: "{}".to_string(),
+    })
+}
+
+fn ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+// ── GrantState ──────────────────────────────────────────────────
+
+#[test]
+fn unlimited_state_never_lapses() {
+    let state = GrantState::new(GrantLimits::new());
+    assert!(GrantLimits::new().is_unlimited());
+    assert_eq!(state.lapse_reason(SystemTime::now() + Duration::from_secs(1_000_000)), None);
+}
+
+#[test]
+fn state_lapses_at_expiry() {
+    let at = SystemTime::now();
+    let state = GrantState::new(GrantLimits::new().with_expires_at(at));
+    assert!(!state.is_lapsed(at - Duration::from_millis(5)));
+    assert_eq!(state.lapse_reason(at + Duration::from_millis(5)), Some(LapseReason::Expired));
+}
+
+#[test]
+fn state_lapses_when_uses_exhausted() {
+    let mut state = GrantState::new(GrantLimits::new().with_max_uses(2));
+    state.uses = 1;
+    assert!(!state.is_lapsed(SystemTime::now()));
+    state.uses = 2;
+    assert_eq!(state.lapse_reason(SystemTime::now()), Some(LapseReason::UsesExhausted));
+}
+
+#[test]
+fn expiry_is_truncated_to_millis() {
+    let at = UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789);
+    let limits = GrantLimits::new().with_expires_at(at);
+    assert_eq!(limits.expires_at, Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)));
+}
+
+// ── Enterprise enforcement ──────────────────────────────────────
+
+#[tokio::test]
+async fn limited_grant_resolves_until_expiry() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let live = PeerId::new();
+    let lapsed = PeerId::new();
+    policy.grant_peer_role_limited(entity, live, SyncRole::Editor, hour()).await;
+    policy.grant_peer_role_limited(entity, lapsed, SyncRole::Editor, expired()).await;
+
+    assert_eq!(policy.resolve_role(&live, &entity).await, Some(SyncRole::Editor));
+    assert_eq!(policy.resolve_role(&lapsed, &entity).await, None);
+}
+
+#[tokio::test]
+async fn expired_grant_blocks_sync_request_and_send() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Viewer, expired()).await;
+
+    assert!(policy.on_sync_request(&peer, &[entity]).await.unwrap().is_empty());
+    let sent = policy.on_event_send(&peer, &entity, &[event(entity)]).await.unwrap();
+    assert!(sent.is_empty());
+}
+
+#[tokio::test]
+async fn use_limited_grant_serves_max_uses_requests() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    let limits = GrantLimits::new().with_max_uses(2);
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Viewer, limits).await;
+
+    assert_eq!(policy.on_sync_request(&peer, &[entity]).await.unwrap(), vec![entity]);
+    assert_eq!(policy.on_sync_request(&peer, &[entity]).await.unwrap(), vec![entity]);
+    assert!(policy.on_sync_request(&peer, &[entity]).await.unwrap().is_empty());
+    assert_eq!(policy.grant_state(&entity, &peer).await.unwrap().uses, 2);
+}
+
+#[tokio::test]
+async fn use_is_counted_on_inherited_grant() {
+    let policy = EnterpriseSyncPolicy::new();
+    let folder = EntityId::new();
+    let page = EntityId::new();
+    policy.set_parent(page, Some(folder)).await.unwrap();
+    let peer = PeerId::new();
+    let limits = GrantLimits::new().with_max_uses(1);
+    policy.grant_peer_role_limited(folder, peer, SyncRole::Viewer, limits).await;
+
+    assert_eq!(policy.on_sync_request(&peer, &[page]).await.unwrap(), vec![page]);
+    assert_eq!(policy.grant_state(&folder, &peer).await.unwrap().uses, 1);
+    assert_eq!(policy.resolve_role(&peer, &page).await, None);
+}
+
+#[tokio::test]
+async fn unlimited_regrant_clears_limits() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Editor, expired()).await;
+
+    policy.grant_peer_role(entity, peer, SyncRole::Editor).await;
+
+    assert_eq!(policy.grant_state(&entity, &peer).await, None);
+    assert_eq!(policy.resolve_role(&peer, &entity).await, Some(SyncRole::Editor));
+}
+
+// ── Sweeper ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn sweep_revokes_lapsed_grants_and_audits() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let lapsed = PeerId::new();
+    let live = PeerId::new();
+    policy.grant_peer_role_limited(entity, lapsed, SyncRole::Editor, expired()).await;
+    policy.grant_peer_role_limited(entity, live, SyncRole::Editor, hour()).await;
+
+    let swept = policy.sweep_lapsed_grants().await;
+
+    assert_eq!(swept.len(), 1);
+    assert_eq!(swept[0].peer, lapsed);
+    assert_eq!(swept[0].reason, LapseReason::Expired);
+    assert!(!policy.acls.read().await[&entity].peer_roles.contains_key(&lapsed));
+    assert_eq!(policy.grant_state(&entity, &lapsed).await, None);
+    assert_eq!(policy.resolve_role(&live, &entity).await, Some(SyncRole::Editor));
+
+    let log = policy.audit_log.read().await;
+    let entry = log.last().unwrap();
+    assert_eq!(entry.action, AuditAction::AclChange);
+    assert_eq!(entry.peer, lapsed);
+    assert!(entry.detail.contains("grant lapsed (expired)"));
+}
+
+#[tokio::test]
+async fn sweep_is_empty_without_lapsed_grants() {
+    let policy = EnterpriseSyncPolicy::new();
+    policy.grant_peer_role_limited(EntityId::new(), PeerId::new(), SyncRole::Viewer, hour()).await;
+
+    assert!(policy.sweep_lapsed_grants().await.is_empty());
+}
+
+#[tokio::test]
+async fn lapsed_grant_revoke_payload_is_acl_revoke() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Viewer, expired()).await;
+
+    let swept = policy.sweep_lapsed_grants().await;
+
+    match swept[0].revoke_payload() {
+        EventPayload::AclRevokePeer { entity_id, peer_id } => {
+            assert_eq!(entity_id, entity.to_string());
+            assert_eq!(peer_id, peer.to_string());
+        }
+        other => panic!("unexpected payload {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn lapsed_grant_reports_role_needed_to_revoke_it() {
+    let policy = EnterpriseSyncPolicy::new();
+    let entity = EntityId::new();
+    let editor = PeerId::new();
+    let owner = PeerId::new();
+    policy.grant_peer_role_limited(entity, editor, SyncRole::Editor, expired()).await;
+    policy.grant_peer_role_limited(entity, owner, SyncRole::Owner, expired()).await;
+
+    let swept = policy.sweep_lapsed_grants().await;
+
+    let editor_grant = swept.iter().find(|g| g.peer == editor).unwrap();
+    assert_eq!(editor_grant.role, Some(SyncRole::Editor));
+    assert_eq!(editor_grant.required_role(), SyncRole::Admin);
+    let owner_grant = swept.iter().find(|g| g.peer == owner).unwrap();
+    assert_eq!(owner_grant.role, Some(SyncRole::Owner));
+    assert_eq!(owner_grant.required_role(), SyncRole::Owner);
+}
+
+// ── Persistence ─────────────────────────────────────────────────
+
+#[test]
+fn store_saves_loads_and_removes_grant_state() {
+    let store = PolicyStore::open_in_memory().unwrap();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    let state = GrantState {
+        limits: hour().with_max_uses(5),
+        uses: 3,
+    };
+
+    store.save_grant_state(&entity, &peer, &state).unwrap();
+    assert_eq!(store.load_grant_states().unwrap(), vec![(entity, peer, state)]);
+
+    store.remove_grant_state(&entity, &peer).unwrap();
+    assert!(store.load_grant_states().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn grant_limits_and_uses_survive_reload() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = EnterpriseSyncPolicy::new().with_store(store.clone());
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    let limits = hour().with_max_uses(2);
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Viewer, limits).await;
+    policy.on_sync_request(&peer, &[entity]).await.unwrap();
+
+    let reloaded = EnterpriseSyncPolicy::load(store).await.unwrap();
+
+    let state = reloaded.grant_state(&entity, &peer).await.unwrap();
+    assert_eq!(state.limits, limits);
+    assert_eq!(state.uses, 1);
+    reloaded.on_sync_request(&peer, &[entity]).await.unwrap();
+    assert_eq!(reloaded.resolve_role(&peer, &entity).await, None);
+}
+
+#[tokio::test]
+async fn sweep_removes_persisted_grant() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = EnterpriseSyncPolicy::new().with_store(store.clone());
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.grant_peer_role_limited(entity, peer, SyncRole::Viewer, expired()).await;
+
+    policy.sweep_lapsed_grants().await;
+
+    assert!(store.load_grant_states().unwrap().is_empty());
+    assert!(store.load_acls().unwrap().is_empty());
+}
+
+// ── ACL events ──────────────────────────────────────────────────
+
+fn limited_grant(entity: EntityId, peer: PeerId, role: &str, limits: GrantLimits) -> EventPayload {
+    EventPayload::AclGrantPeerLimited {
+        entity_id: entity.to_string(),
+        peer_id: peer.to_string(),
+        role: role.to_string(),
+        expires_at_ms: limits.expires_at.map(ms),
+        max_uses: limits.max_uses,
+    }
+}
+
+#[test]
+fn limited_grant_payload_is_acl_event() {
+    let payload = limited_grant(EntityId::new(), PeerId::new(), "Viewer", hour());
+    assert!(is_acl_event(&payload));
+}
+
+#[tokio::test]
+async fn limited_grant_event_applies_limits() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let entity = EntityId::new();
+    let admin = PeerId::new();
+    let guest = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+    let limits = hour().with_max_uses(3);
+
+    let ev = Event::new(entity, admin, HybridTimestamp::now(), limited_grant(entity, guest, "Editor", limits));
+    assert!(applicator.handle_acl_event(&ev).await.unwrap());
+
+    assert_eq!(policy.resolve_role(&guest, &entity).await, Some(SyncRole::Editor));
+    assert_eq!(policy.grant_state(&entity, &guest).await, Some(GrantState::new(limits)));
+}
+
+#[tokio::test]
+async fn limited_grant_event_with_no_limits_is_plain_grant() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let entity = EntityId::new();
+    let admin = PeerId::new();
+    let guest = PeerId::new();
+    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+
+    let payload = limited_grant(entity, guest, "Viewer", GrantLimits::new());
+    let ev = Event::new(entity, admin, HybridTimestamp::now(), payload);
+    assert!(applicator.handle_acl_event(&ev).await.unwrap());
+
+    assert_eq!(policy.resolve_role(&guest, &entity).await, Some(SyncRole::Viewer));
+    assert_eq!(policy.grant_state(&entity, &guest).await, None);
+}
+
+#[tokio::test]
+async fn limited_admin_authorizes_only_before_expiry() {
+    let policy = Arc::new(EnterpriseSyncPolicy::new());
+    let applicator = AclApplicator::new(policy.clone());
+    let entity = EntityId::new();
+    let temp_admin = PeerId::new();
+    let limits = hour();
+    policy.grant_peer_role_limited(entity, temp_admin, SyncRole::Admin, limits).await;
+    let expiry = ms(limits.expires_at.unwrap());
+
+    let grant = |peer: PeerId| EventPayload::AclGrantPeer {
+        entity_id: entity.to_string(),
+        peer_id: peer.to_string(),
+        role: "Viewer".to_string(),
+    };
+    let before = Event::new(entity, temp_admin, HybridTimestamp::new(expiry - 1000, 0), grant(PeerId::new()));
+    let after = Event::new(entity, temp_admin, HybridTimestamp::new(expiry + 1000, 0), grant(PeerId::new()));
+
+    assert!(applicator.handle_acl_event(&before).await.unwrap());
+    assert!(applicator.handle_acl_event(&after).await.is_err());
+}
+
+// ── Personal shares ─────────────────────────────────────────────
+
+#[tokio::test]
+async fn expired_share_is_not_synced() {
+    let policy = PersonalSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.share_limited(entity, peer, expired()).await;
+
+    assert!(policy.on_sync_request(&peer, &[entity]).await.unwrap().is_empty());
+    assert!(policy.on_event_send(&peer, &entity, &[event(entity)]).await.unwrap().is_empty());
+    assert!(policy.on_event_receive(&peer, &entity, &[event(entity)]).await.unwrap().is_empty());
+    assert!(policy.shared_entities(&peer).await.is_empty());
+    assert!(policy.shared_peers(&entity).await.is_empty());
+}
+
+#[tokio::test]
+async fn use_limited_share_counts_sync_requests() {
+    let policy = PersonalSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.share_limited(entity, peer, GrantLimits::new().with_max_uses(1)).await;
+
+    assert_eq!(policy.on_sync_request(&peer, &[entity]).await.unwrap(), vec![entity]);
+    assert_eq!(policy.share_state(&entity, &peer).await.unwrap().uses, 1);
+    assert!(policy.on_sync_request(&peer, &[entity]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sweep_unshares_lapsed_shares() {
+    let policy = PersonalSyncPolicy::new();
+    let entity = EntityId::new();
+    let lapsed = PeerId::new();
+    let live = PeerId::new();
+    policy.share_limited(entity, lapsed, expired()).await;
+    policy.share_limited(entity, live, hour()).await;
+
+    let swept = policy.sweep_lapsed_shares().await;
+
+    assert_eq!(swept.len(), 1);
+    assert_eq!(swept[0].peer, lapsed);
+    assert_eq!(policy.share_state(&entity, &lapsed).await, None);
+    assert_eq!(policy.shared_peers(&entity).await, vec![live]);
+}
+
+#[tokio::test]
+async fn plain_share_clears_limits() {
+    let policy = PersonalSyncPolicy::new();
+    let entity = EntityId::new();
+    let peer = PeerId::new();
+    policy.share_limited(entity, peer, expired()).await;
+
+    policy.share(entity, peer).await;
+
+    assert_eq!(policy.share_state(&entity, &peer).await, None);
+    assert_eq!(policy.shared_entities(&peer).await, vec![entity]);
+}
@@ -1,7 +1,13 @@
+#[macro_use]
+mod support;
+
 use privstack_sync::cloud::icloud::{ICloudConfig, ICloudStorage};
 use privstack_sync::cloud::CloudStorage;
+use support::ICloudFixture;
 use tempfile::TempDir;
 
+storage_conformance_tests!(ICloudFixture::new());
+
 // ── Config defaults ─────────────────────────────────────────────
 
 #[test]
@@ -0,0 +1,141 @@
+#[macro_use]
+mod support;
+
+use privstack_sync::cloud::{CloudStorage, LocalFolderConfig, LocalFolderStorage};
+use support::LocalFolderFixture;
+use tempfile::TempDir;
+
+storage_conformance_tests!(LocalFolderFixture::new());
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+async fn open(dir: &TempDir) -> LocalFolderStorage {
+    let mut storage = LocalFolderStorage::new(LocalFolderConfig::new(dir.path()));
+    storage.authenticate().await.unwrap();
+    storage
+}
+
+// ── Config ──────────────────────────────────────────────────────
+
+#[test]
+fn config_new_uses_default_sync_folder() {
+    let cfg = LocalFolderConfig::new("/home/me/Sync");
+    assert_eq!(cfg.root.to_str(), Some("/home/me/Sync"));
+    assert_eq!(cfg.base.sync_folder, "PrivStack/sync");
+}
+
+#[test]
+fn config_serde_roundtrip() {
+    let cfg = LocalFolderConfig::new("/data/dropbox");
+    let json = serde_json::to_string(&cfg).unwrap();
+    assert!(json.contains("sync_folder"));
+    let back: LocalFolderConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.root, cfg.root);
+}
+
+// ── Authentication ──────────────────────────────────────────────
+
+#[test]
+fn provider_name() {
+    let storage = LocalFolderStorage::new(LocalFolderConfig::new("/tmp"));
+    assert_eq!(storage.provider_name(), "Local Folder");
+}
+
+#[tokio::test]
+async fn authenticate_fails_without_root() {
+    let mut storage = LocalFolderStorage::new(LocalFolderConfig::new("/nonexistent/privstack/sync/root"));
+    assert!(!storage.is_authenticated());
+    assert!(storage.authenticate().await.is_err());
+}
+
+#[tokio::test]
+async fn authenticate_creates_sync_folder() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    assert!(storage.is_authenticated());
+    assert!(dir.path().join("PrivStack/sync").is_dir());
+}
+
+// ── Files ───────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn upload_leaves_no_partial_files() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    storage.upload("data.bin", b"payload").await.unwrap();
+
+    let entries: Vec<String> = std::fs::read_dir(dir.path().join("PrivStack/sync"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(entries, vec!["data.bin".to_string()]);
+}
+
+#[tokio::test]
+async fn list_skips_hidden_files_and_directories() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    storage.upload("visible.txt", b"data").await.unwrap();
+
+    let folder = dir.path().join("PrivStack/sync");
+    std::fs::write(folder.join(".stfolder"), b"").unwrap();
+    std::fs::write(folder.join(".other.partial"), b"half").unwrap();
+    std::fs::create_dir(folder.join(".stversions")).unwrap();
+
+    let files = storage.list_files().await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].name, "visible.txt");
+}
+
+#[tokio::test]
+async fn file_ids_cannot_escape_sync_folder() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    std::fs::write(dir.path().join("outside.txt"), b"secret").unwrap();
+
+    assert!(storage.download("../outside.txt").await.is_err());
+    assert!(storage.delete("../outside.txt").await.is_err());
+    assert!(storage.upload("..", b"x").await.is_err());
+    assert!(dir.path().join("outside.txt").exists());
+}
+
+// ── Change detection ────────────────────────────────────────────
+
+#[tokio::test]
+async fn changes_detect_files_written_by_sync_tool() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    storage.get_changes(None).await.unwrap();
+
+    std::fs::write(dir.path().join("PrivStack/sync/replicated.seg"), b"from another device").unwrap();
+
+    let changes = storage.get_changes(None).await.unwrap();
+    assert_eq!(changes.changed.len(), 1);
+    assert_eq!(changes.changed[0].id, "replicated.seg");
+}
+
+#[tokio::test]
+async fn cursor_carries_state_across_instances() {
+    let dir = TempDir::new().unwrap();
+    let first = open(&dir).await;
+    first.upload("a.txt", b"a").await.unwrap();
+    let cursor = first.get_changes(None).await.unwrap().next_cursor;
+    assert!(cursor.is_some());
+
+    // A restarted client with the persisted cursor only sees new changes
+    let restarted = open(&dir).await;
+    restarted.upload("b.txt", b"b").await.unwrap();
+    let changes = restarted.get_changes(cursor.as_deref()).await.unwrap();
+    let names: Vec<&str> = changes.changed.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["b.txt"]);
+}
+
+#[tokio::test]
+async fn invalid_cursor_falls_back_to_full_listing() {
+    let dir = TempDir::new().unwrap();
+    let storage = open(&dir).await;
+    std::fs::write(dir.path().join("PrivStack/sync/x.txt"), b"x").unwrap();
+
+    let changes = storage.get_changes(Some("not json")).await.unwrap();
+    assert_eq!(changes.changed.len(), 1);
+}
@@ -116,6 +116,10 @@ fn make_hello_ack(peer_id: PeerId) -> SyncMessage {
         device_name: "MockPeer".to_string(),
         accepted: true,
         reason: None,
+        wire_format: None,
+        e2e_public_key: None,
+        sync_scope: None,
+        signing_public_key: None,
     })
 }
 
@@ -654,6 +658,10 @@ async fn run_sync_with_peer_rejected() {
             device_name: "Peer".to_string(),
             accepted: false,
             reason: Some("busy".to_string()),
+            wire_format: None,
+            e2e_public_key: None,
+            sync_scope: None,
+            signing_public_key: None,
         }),
     ];
 
@@ -721,6 +729,10 @@ async fn run_sync_with_peer_version_mismatch() {
             device_name: "Peer".to_string(),
             accepted: true,
             reason: None,
+            wire_format: None,
+            e2e_public_key: None,
+            sync_scope: None,
+            signing_public_key: None,
         }),
     ];
 
@@ -2244,7 +2256,556 @@ fn sync_command_record_local_event_debug() {
             json_data: "{}".to_string(),
         },
     );
-    let cmd = SyncCommand::RecordLocalEvent { event };
+    let cmd = SyncCommand::RecordLocalEvent { event: Box::new(event) };
     let debug = format!("{:?}", cmd);
     assert!(debug.contains("RecordLocalEvent"));
 }
+
+// ── End-to-end payload encryption ───────────────────────────────
+
+fn make_trusted_pairing(remote_peer: PeerId) -> Arc<std::sync::Mutex<privstack_sync::PairingManager>> {
+    let mut pm = privstack_sync::PairingManager::new();
+    pm.add_discovered_peer(privstack_sync::DiscoveredPeerInfo {
+        peer_id: remote_peer.to_string(),
+        device_name: "Remote".to_string(),
+        discovered_at: 0,
+        status: privstack_sync::PairingStatus::PendingLocalApproval,
+        addresses: vec![],
+    });
+    pm.approve_peer(&remote_peer.to_string());
+    Arc::new(std::sync::Mutex::new(pm))
+}
+
+#[tokio::test]
+async fn pairing_orchestrator_encrypts_outgoing_events() {
+    use privstack_sync::{create_orchestrator_with_pairing, KeyShareMessage, PayloadKeyring};
+
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let entity_id = EntityId::new();
+    let (es, ev) = make_stores();
+
+    let pm = make_trusted_pairing(remote_peer);
+    let local_pk = pm.lock().unwrap().update_keyring(|k| k.exchange_public_key());
+
+    let mut remote = PayloadKeyring::new();
+    let remote_pk = remote.exchange_public_key();
+    remote.ensure_group_key();
+    let remote_keys = remote.seal_current_keys(&local_pk, |_| true).unwrap();
+
+    let ack = match make_hello_ack(remote_peer) {
+        SyncMessage::HelloAck(ack) => ack.with_e2e_public_key(remote_pk),
+        _ => unreachable!(),
+    };
+    let responses = vec![
+        SyncMessage::HelloAck(ack),
+        SyncMessage::KeyShare(KeyShareMessage { keys: remote_keys }),
+        make_sync_state(),
+        make_event_ack_default(),
+    ];
+
+    let (_incoming_tx, incoming_rx) = mpsc::channel(16);
+    let mock = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        responses,
+        incoming_rx,
+    )));
+    let transport: Arc<Mutex<dyn SyncTransport>> = mock.clone();
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+
+    let (handle, mut event_rx, command_rx, orchestrator) =
+        create_orchestrator_with_pairing(local_peer, es.clone(), ev.clone(), config, pm.clone());
+
+    let join = tokio::spawn(async move {
+        orchestrator.run(transport, command_rx).await
+    });
+
+    let event = Event::entity_created(entity_id, local_peer, "note", r#"{"title":"secret"}"#);
+    record_event_with_stores(&handle, &es, &ev, local_peer, event.clone()).await;
+    handle.share_entity(entity_id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    handle.send(SyncCommand::SyncWithPeer { peer_id: remote_peer }).await.unwrap();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), event_rx.recv()).await {
+            Ok(Some(SyncEvent::SyncCompleted { .. })) => break,
+            Ok(Some(_)) => continue,
+            other => panic!("sync did not complete: {other:?}"),
+        }
+    }
+
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    let sent = mock.lock().await.sent_requests.lock().await.clone();
+    match &sent[0].1 {
+        SyncMessage::Hello(hello) => assert_eq!(hello.e2e_public_key, Some(local_pk)),
+        other => panic!("expected Hello, got {other:?}"),
+    }
+    let share = match &sent[1].1 {
+        SyncMessage::KeyShare(share) => share.clone(),
+        other => panic!("expected KeyShare, got {other:?}"),
+    };
+    assert_eq!(remote.import(&share.keys), 1);
+
+    let batch = sent
+        .iter()
+        .find_map(|(_, msg)| match msg {
+            SyncMessage::EventBatch(batch) => Some(batch.clone()),
+            _ => None,
+        })
+        .expect("event batch sent");
+    assert_eq!(batch.events.len(), 1);
+    assert!(matches!(batch.events[0].payload, EventPayload::Encrypted { .. }));
+    // Outgoing events are signed with the device key before sealing.
+    let opened = remote.decrypt_event(&batch.events[0]).unwrap();
+    let device_key = pm.lock().unwrap().signing_key().public_key();
+    assert_eq!(privstack_sync::verify_signature(&opened).unwrap(), Some(device_key));
+    assert_eq!(Event { signature: None, ..opened }, event);
+
+    let trusted = pm.lock().unwrap().get_trusted_peer(&remote_peer.to_string()).cloned().unwrap();
+    assert_eq!(trusted.e2e_public_key, Some(remote_pk));
+}
+
+#[tokio::test]
+async fn pairing_orchestrator_decrypts_incoming_events() {
+    use privstack_sync::{create_orchestrator_with_pairing, HelloMessage, KeyShareMessage, PayloadKeyring};
+
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let entity_id = EntityId::new();
+    let (es, ev) = make_stores();
+
+    let pm = make_trusted_pairing(remote_peer);
+    let local_pk = pm.lock().unwrap().update_keyring(|k| k.exchange_public_key());
+
+    let mut remote = PayloadKeyring::new();
+    let remote_pk = remote.exchange_public_key();
+    remote.ensure_group_key();
+
+    let (incoming_tx, incoming_rx) = mpsc::channel(16);
+    let mock = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        vec![],
+        incoming_rx,
+    )));
+    let transport: Arc<Mutex<dyn SyncTransport>> = mock.clone();
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+
+    let (handle, _event_rx, command_rx, orchestrator) =
+        create_orchestrator_with_pairing(local_peer, es, ev.clone(), config, pm.clone());
+
+    let join = tokio::spawn(async move {
+        orchestrator.run(transport, command_rx).await
+    });
+
+    let hello = HelloMessage::new(remote_peer, "Remote").with_e2e_public_key(remote_pk);
+    let share = KeyShareMessage {
+        keys: remote.seal_current_keys(&local_pk, |_| true).unwrap(),
+    };
+    let event = Event::entity_created(entity_id, remote_peer, "note", r#"{"title":"secret"}"#);
+    let batch = EventBatchMessage {
+        entity_id,
+        batch_seq: 0,
+        is_final: true,
+        events: vec![remote.encrypt_event(&event).unwrap()],
+    };
+
+    for message in [
+        SyncMessage::Hello(hello),
+        SyncMessage::KeyShare(share),
+        SyncMessage::EventBatch(batch),
+    ] {
+        incoming_tx.send(IncomingSyncRequest {
+            peer_id: remote_peer,
+            message,
+            response_token: ResponseToken::new(()),
+        }).await.unwrap();
+    }
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    let stored = ev.get_events_for_entity(&entity_id).unwrap();
+    assert_eq!(stored, vec![event]);
+
+    let responses = mock.lock().await.sent_responses.lock().await.clone();
+    match &responses[0] {
+        SyncMessage::HelloAck(ack) => assert_eq!(ack.e2e_public_key, Some(local_pk)),
+        other => panic!("expected HelloAck, got {other:?}"),
+    }
+    match &responses[1] {
+        SyncMessage::KeyShare(share) => assert_eq!(remote.import(&share.keys), 1),
+        other => panic!("expected KeyShare, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn pairing_orchestrator_refuses_plaintext_from_pinned_peer() {
+    use privstack_sync::{create_orchestrator_with_pairing, HelloMessage};
+
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let entity_id = EntityId::new();
+    let (es, ev) = make_stores();
+
+    let pm = make_trusted_pairing(remote_peer);
+    pm.lock().unwrap().set_peer_e2e_key(&remote_peer.to_string(), [7u8; 32]);
+
+    let (incoming_tx, incoming_rx) = mpsc::channel(16);
+    let mock = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        vec![],
+        incoming_rx,
+    )));
+    let transport: Arc<Mutex<dyn SyncTransport>> = mock.clone();
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+
+    let (handle, _event_rx, command_rx, orchestrator) =
+        create_orchestrator_with_pairing(local_peer, es, ev.clone(), config, pm.clone());
+
+    let join = tokio::spawn(async move {
+        orchestrator.run(transport, command_rx).await
+    });
+
+    // A Hello without the pinned exchange key, then plaintext events
+    let hello = HelloMessage::new(remote_peer, "Remote");
+    let event = Event::entity_created(entity_id, remote_peer, "note", r#"{"title":"forged"}"#);
+    let batch = EventBatchMessage {
+        entity_id,
+        batch_seq: 0,
+        is_final: true,
+        events: vec![event],
+    };
+
+    for message in [SyncMessage::Hello(hello), SyncMessage::EventBatch(batch)] {
+        incoming_tx.send(IncomingSyncRequest {
+            peer_id: remote_peer,
+            message,
+            response_token: ResponseToken::new(()),
+        }).await.unwrap();
+    }
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    assert!(ev.get_events_for_entity(&entity_id).unwrap().is_empty());
+    let responses = mock.lock().await.sent_responses.lock().await.clone();
+    match &responses[0] {
+        SyncMessage::HelloAck(ack) => assert!(!ack.accepted),
+        other => panic!("expected HelloAck, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn pairing_orchestrator_rejects_key_share_from_unknown_peer() {
+    use privstack_sync::{create_orchestrator_with_pairing, KeyShareMessage};
+
+    let local_peer = PeerId::new();
+    let (es, ev) = make_stores();
+
+    let (incoming_tx, incoming_rx) = mpsc::channel(16);
+    let mock = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        vec![],
+        incoming_rx,
+    )));
+    let transport: Arc<Mutex<dyn SyncTransport>> = mock.clone();
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+    let pm = Arc::new(std::sync::Mutex::new(privstack_sync::PairingManager::new()));
+
+    let (handle, _event_rx, command_rx, orchestrator) =
+        create_orchestrator_with_pairing(local_peer, es, ev, config, pm);
+
+    let join = tokio::spawn(async move {
+        orchestrator.run(transport, command_rx).await
+    });
+
+    incoming_tx.send(IncomingSyncRequest {
+        peer_id: PeerId::new(),
+        message: SyncMessage::KeyShare(KeyShareMessage::default()),
+        response_token: ResponseToken::new(()),
+    }).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    let responses = mock.lock().await.sent_responses.lock().await.clone();
+    assert!(matches!(responses[0], SyncMessage::Error(_)));
+}
+
+// ── Session diagnostics ─────────────────────────────────────────
+
+#[tokio::test]
+async fn sync_sessions_are_recorded() {
+    use privstack_sync::diagnostics::load_sessions;
+    use privstack_sync::{SessionDirection, SessionOutcome, SyncPhase, TransportKind};
+
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let entity_id = EntityId::new();
+    let (es, ev) = make_stores();
+
+    let (_incoming_tx, incoming_rx) = mpsc::channel(16);
+    let peers = vec![DiscoveredPeer {
+        peer_id: remote_peer,
+        device_name: Some("Laptop".to_string()),
+        discovery_method: DiscoveryMethod::Mdns,
+        addresses: vec![],
+    }];
+    let mut busy = make_hello_ack(remote_peer);
+    if let SyncMessage::HelloAck(ack) = &mut busy {
+        ack.accepted = false;
+        ack.reason = Some("busy".to_string());
+    }
+    let responses = vec![
+        busy,
+        make_hello_ack(remote_peer),
+        make_sync_state(),
+        make_event_ack_default(),
+    ];
+    let transport: Arc<Mutex<dyn SyncTransport>> = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        peers,
+        responses,
+        incoming_rx,
+    )));
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+    let (handle, mut event_rx, command_rx, orchestrator) =
+        create_orchestrator(local_peer, es.clone(), ev.clone(), config);
+    let join = tokio::spawn(async move { orchestrator.run(transport, command_rx).await });
+
+    handle.share_entity(entity_id).await.unwrap();
+    let event = Event::new(
+        entity_id,
+        local_peer,
+        HybridTimestamp::now(),
+        EventPayload::EntityCreated {
+            entity_type: "note".to_string(),
+            json_data: r#"{"title":"test"}"#.to_string(),
+        },
+    );
+    record_event_with_stores(&handle, &es, &ev, local_peer, event).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The peer turns the first attempt down and accepts the second.
+    for _ in 0..2 {
+        handle.send(SyncCommand::SyncWithPeer { peer_id: remote_peer }).await.unwrap();
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(2), event_rx.recv()).await.unwrap();
+        }
+    }
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    let sessions = load_sessions(&es, Some(&remote_peer.to_string()), 10).unwrap();
+    assert_eq!(sessions.len(), 2);
+    let (completed, failed) = (&sessions[0], &sessions[1]);
+
+    assert_eq!(completed.outcome, SessionOutcome::Completed);
+    assert_eq!(completed.direction, SessionDirection::Outbound);
+    assert_eq!(completed.transport, TransportKind::Mdns);
+    assert_eq!(completed.device_name.as_deref(), Some("MockPeer"));
+    assert_eq!(completed.entities_requested, 1);
+    assert!(completed.sent.bytes > 0 && completed.received.bytes > 0);
+    let phases: Vec<_> = completed.phases.iter().map(|p| p.phase).collect();
+    assert_eq!(
+        phases,
+        [
+            SyncPhase::Handshake,
+            SyncPhase::KeyExchange,
+            SyncPhase::StateExchange,
+            SyncPhase::EventExchange,
+            SyncPhase::Finalize,
+        ]
+    );
+
+    assert_eq!(failed.outcome, SessionOutcome::Failed);
+    assert_eq!(failed.failed_phase, Some(SyncPhase::Handshake));
+    assert_eq!(failed.error.as_deref(), Some("busy"));
+}
+
+#[tokio::test]
+async fn inbound_session_is_recorded_on_shutdown() {
+    use privstack_sync::diagnostics::load_sessions;
+    use privstack_sync::{SessionDirection, SyncPhase};
+
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let (es, ev) = make_stores();
+
+    let (incoming_tx, incoming_rx) = mpsc::channel(16);
+    let transport: Arc<Mutex<dyn SyncTransport>> = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        vec![],
+        incoming_rx,
+    )));
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+    let (handle, _event_rx, command_rx, orchestrator) =
+        create_orchestrator(local_peer, es.clone(), ev, config);
+    let join = tokio::spawn(async move { orchestrator.run(transport, command_rx).await });
+
+    let hello = privstack_sync::HelloMessage::new(remote_peer, "RemoteDevice".to_string());
+    incoming_tx.send(IncomingSyncRequest {
+        peer_id: remote_peer,
+        message: SyncMessage::Hello(hello),
+        response_token: ResponseToken::new(()),
+    }).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Still open until the peer goes quiet or we shut down.
+    assert!(load_sessions(&es, None, 10).unwrap().is_empty());
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+
+    let sessions = load_sessions(&es, None, 10).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].direction, SessionDirection::Inbound);
+    assert_eq!(sessions[0].device_name.as_deref(), Some("RemoteDevice"));
+    assert_eq!(sessions[0].phases[0].phase, SyncPhase::Handshake);
+}
+
+// ── Resumable sync ──────────────────────────────────────────────
+
+/// Runs one sync with `remote_peer` on a fresh orchestrator over the given
+/// stores, answering the first `acks` event batches. Returns the progress
+/// events seen before the sync completed.
+async fn sync_once(
+    local_peer: PeerId,
+    remote_peer: PeerId,
+    stores: (&Arc<EntityStore>, &Arc<EventStore>),
+    new_entities: usize,
+    acks: usize,
+) -> Vec<(usize, usize)> {
+    let (es, ev) = stores;
+    let mut responses = vec![make_hello_ack(remote_peer), make_sync_state()];
+    responses.extend((0..acks).map(|_| make_event_ack_default()));
+
+    let (_incoming_tx, incoming_rx) = mpsc::channel(16);
+    let transport: Arc<Mutex<dyn SyncTransport>> = Arc::new(Mutex::new(MockTransport::new(
+        local_peer,
+        vec![],
+        responses,
+        incoming_rx,
+    )));
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+    let (handle, mut event_rx, command_rx, orchestrator) =
+        create_orchestrator(local_peer, es.clone(), ev.clone(), config);
+    let join = tokio::spawn(async move { orchestrator.run(transport, command_rx).await });
+
+    for _ in 0..new_entities {
+        let entity_id = EntityId::new();
+        handle.share_entity(entity_id).await.unwrap();
+        let event = Event::new(
+            entity_id,
+            local_peer,
+            HybridTimestamp::now(),
+            EventPayload::EntityCreated {
+                entity_type: "note".to_string(),
+                json_data: r#"{"title":"bulk"}"#.to_string(),
+            },
+        );
+        record_event_with_stores(&handle, es, ev, local_peer, event).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    handle.send(SyncCommand::SyncWithPeer { peer_id: remote_peer }).await.unwrap();
+    let mut progress = Vec::new();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await.unwrap() {
+            Some(SyncEvent::SyncProgress { entities_done, entities_total, .. }) => {
+                progress.push((entities_done, entities_total));
+            }
+            Some(SyncEvent::SyncCompleted { .. }) | None => break,
+            Some(_) => {}
+        }
+    }
+    handle.shutdown().await.unwrap();
+    let _ = join.await;
+    progress
+}
+
+#[tokio::test]
+async fn interrupted_sync_resumes_after_restart() {
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let peer_str = remote_peer.to_string();
+    let (es, ev) = make_stores();
+
+    // The peer drops out after 30 of 60 entities.
+    let progress = sync_once(local_peer, remote_peer, (&es, &ev), 60, 30).await;
+    assert_eq!(progress, [(25, 60), (30, 60)]);
+    assert_eq!(es.entities_needing_sync(&peer_str).unwrap().len(), 30);
+    let row = es.get_sync_progress(&peer_str).unwrap().unwrap();
+    assert_eq!((row.entities_done, row.entities_total), (30, 60));
+    assert_eq!(es.load_sync_entity_states().unwrap().len(), 60);
+
+    // A new orchestrator over the same stores picks the run back up.
+    let progress = sync_once(local_peer, remote_peer, (&es, &ev), 0, 30).await;
+    assert_eq!(progress, [(55, 60), (60, 60)]);
+    assert!(es.entities_needing_sync(&peer_str).unwrap().is_empty());
+    assert!(es.get_sync_progress(&peer_str).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn small_syncs_do_not_report_progress() {
+    let local_peer = PeerId::new();
+    let remote_peer = PeerId::new();
+    let (es, ev) = make_stores();
+
+    let progress = sync_once(local_peer, remote_peer, (&es, &ev), 3, 3).await;
+    assert!(progress.is_empty());
+    assert!(es.get_sync_progress(&remote_peer.to_string()).unwrap().is_none());
+    assert_eq!(es.load_sync_entity_states().unwrap().len(), 3);
+}
@@ -1,7 +1,34 @@
 use privstack_sync::pairing::{
-    DiscoveredPeerInfo, PairingManager, PairingMessage, PairingStatus, SyncCode, SyncCodeError,
-    TrustedPeer,
+    DiscoveredPeerInfo, PairingManager, PairingMessage, PairingSecretStore, PairingStatus, SyncCode,
+    SyncCodeError, TrustedPeer,
 };
+use privstack_sync::SyncResult;
+use std::sync::{Arc, Mutex};
+
+/// Secret store that keeps the sealed blob in memory and counts saves.
+#[derive(Default)]
+struct MemorySecretStore {
+    blob: Mutex<Option<Vec<u8>>>,
+    saves: Mutex<usize>,
+}
+
+impl MemorySecretStore {
+    fn saves(&self) -> usize {
+        *self.saves.lock().unwrap()
+    }
+}
+
+impl PairingSecretStore for MemorySecretStore {
+    fn load(&self) -> SyncResult<Option<Vec<u8>>> {
+        Ok(self.blob.lock().unwrap().clone())
+    }
+
+    fn save(&self, secrets: &[u8]) -> SyncResult<()> {
+        *self.blob.lock().unwrap() = Some(secrets.to_vec());
+        *self.saves.lock().unwrap() += 1;
+        Ok(())
+    }
+}
 
 // ── SyncCode ────────────────────────────────────────────────────
 
@@ -416,6 +443,143 @@ fn pairing_manager_remove_trusted_peer_nonexistent_is_noop() {
     mgr.remove_trusted_peer("nope"); // should not panic
 }
 
+#[test]
+fn pairing_manager_remove_trusted_peer_rotates_payload_keys() {
+    use privstack_sync::e2e::KeyScope;
+
+    let mut mgr = PairingManager::new();
+    mgr.add_discovered_peer(make_discovered_peer("p1"));
+    mgr.approve_peer("p1");
+    let before = mgr.update_keyring(|k| k.ensure_group_key().key_id.clone());
+
+    mgr.remove_trusted_peer("p1");
+    let after = mgr.keyring().current_key(&KeyScope::Group).unwrap();
+    assert_ne!(after.key_id, before);
+    assert_eq!(after.epoch, 2);
+
+    // Removing an unknown peer does not rotate again
+    mgr.remove_trusted_peer("p1");
+    assert_eq!(mgr.keyring().current_key(&KeyScope::Group).unwrap().epoch, 2);
+}
+
+#[test]
+fn pairing_manager_set_peer_e2e_key() {
+    let mut mgr = PairingManager::new();
+    mgr.add_discovered_peer(make_discovered_peer("p1"));
+    mgr.approve_peer("p1");
+    assert!(mgr.get_trusted_peer("p1").unwrap().e2e_public_key.is_none());
+
+    assert!(!mgr.requires_e2e("p1"));
+    assert!(mgr.set_peer_e2e_key("p1", [7u8; 32]));
+    assert_eq!(mgr.get_trusted_peer("p1").unwrap().e2e_public_key, Some([7u8; 32]));
+    assert!(mgr.requires_e2e("p1"));
+    assert!(mgr.set_peer_e2e_key("p1", [7u8; 32]));
+
+    // Unknown peers are ignored
+    assert!(!mgr.set_peer_e2e_key("nope", [7u8; 32]));
+    assert!(mgr.get_trusted_peer("nope").is_none());
+}
+
+#[test]
+fn pairing_manager_e2e_key_mismatch_offers_repair() {
+    let mut mgr = PairingManager::new();
+    mgr.add_discovered_peer(make_discovered_peer("p1"));
+    mgr.approve_peer("p1");
+    mgr.set_peer_e2e_key("p1", [7u8; 32]);
+
+    // A different key keeps the pin and lists the peer for approval again
+    assert!(!mgr.set_peer_e2e_key("p1", [8u8; 32]));
+    assert_eq!(mgr.get_trusted_peer("p1").unwrap().e2e_public_key, Some([7u8; 32]));
+    assert_eq!(
+        mgr.get_discovered_peer("p1").unwrap().status,
+        PairingStatus::PendingLocalApproval
+    );
+
+    // Approving the re-pair pins the new key
+    mgr.approve_peer("p1");
+    assert_eq!(mgr.get_trusted_peer("p1").unwrap().e2e_public_key, Some([8u8; 32]));
+    assert!(mgr.set_peer_e2e_key("p1", [8u8; 32]));
+}
+
+#[test]
+fn pairing_manager_json_omits_secrets() {
+    let mut mgr = PairingManager::new();
+    mgr.update_keyring(|k| k.ensure_group_key().key_id.clone());
+    mgr.signing_key();
+
+    let json = mgr.to_json().unwrap();
+    assert!(!json.contains("keyring"));
+    assert!(!json.contains("signing_key"));
+    assert!(PairingManager::from_json(&json).unwrap().keyring().is_empty());
+}
+
+#[test]
+fn pairing_manager_secret_store_roundtrip() {
+    let store = Arc::new(MemorySecretStore::default());
+    let mut mgr = PairingManager::new();
+    mgr.attach_secret_store(store.clone()).unwrap();
+    assert!(mgr.has_secret_store());
+    let pk = mgr.update_keyring(|k| k.exchange_public_key());
+    let key_id = mgr.update_keyring(|k| k.ensure_group_key().key_id.clone());
+    let signing = mgr.signing_key().public_key();
+
+    let mut restored = PairingManager::from_json(&mgr.to_json().unwrap()).unwrap();
+    restored.attach_secret_store(store).unwrap();
+    assert_eq!(restored.update_keyring(|k| k.exchange_public_key()), pk);
+    assert!(restored.keyring().key(&key_id).is_some());
+    assert_eq!(restored.signing_key().public_key(), signing);
+}
+
+#[test]
+fn pairing_manager_saves_secrets_when_keys_change() {
+    let store = Arc::new(MemorySecretStore::default());
+    let mut mgr = PairingManager::new();
+    mgr.attach_secret_store(store.clone()).unwrap();
+    let saves = store.saves();
+
+    mgr.update_keyring(|k| k.ensure_group_key().key_id.clone());
+    assert_eq!(store.saves(), saves + 1);
+
+    // Reading keys does not save
+    mgr.update_keyring(|k| k.ensure_group_key().key_id.clone());
+    assert_eq!(store.saves(), saves + 1);
+
+    // Removing a peer rotates and saves
+    mgr.add_discovered_peer(make_discovered_peer("p1"));
+    mgr.approve_peer("p1");
+    mgr.remove_trusted_peer("p1");
+    assert_eq!(store.saves(), saves + 2);
+}
+
+#[test]
+fn pairing_manager_migrates_secrets_from_legacy_json() {
+    let mut legacy = PairingManager::new();
+    legacy.update_keyring(|k| k.ensure_group_key().key_id.clone());
+    let signing = legacy.signing_key();
+    let mut json: serde_json::Value = serde_json::from_str(&legacy.to_json().unwrap()).unwrap();
+    json["keyring"] = serde_json::to_value(legacy.keyring()).unwrap();
+    json["signing_key"] = serde_json::to_value(&signing).unwrap();
+
+    let store = Arc::new(MemorySecretStore::default());
+    let mut mgr = PairingManager::new();
+    mgr.attach_secret_store(store.clone()).unwrap();
+    mgr.load_json(&json.to_string()).unwrap();
+    assert_eq!(mgr.keyring().len(), 1);
+    assert_eq!(mgr.signing_key().public_key(), signing.public_key());
+
+    let mut restored = PairingManager::new();
+    restored.attach_secret_store(store).unwrap();
+    assert_eq!(restored.signing_key().public_key(), signing.public_key());
+}
+
+#[test]
+fn pairing_manager_from_json_without_keyring() {
+    let json = r#"{"current_code":null,"discovered_peers":{},"trusted_peers":{"p1":{"peer_id":"p1","device_name":"D","approved_at":1,"last_synced":null,"addresses":[]}}}"#;
+    let mgr = PairingManager::from_json(json).unwrap();
+    assert!(mgr.keyring().is_empty());
+    assert!(mgr.get_trusted_peer("p1").unwrap().e2e_public_key.is_none());
+}
+
 #[test]
 fn pairing_manager_update_peer_addresses() {
     let mut mgr = PairingManager::new();
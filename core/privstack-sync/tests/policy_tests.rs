@@ -2458,7 +2458,7 @@ async fn team_membership_propagates_via_event() {
     let entity = EntityId::new();
     let team = TeamId::new();
 
-    policy.grant_peer_role(entity, admin, SyncRole::Admin).await;
+    policy.grant_peer_role(team.admin_entity(), admin, SyncRole::Admin).await;
 
     let applicator = AclApplicator::new(policy.clone());
 
@@ -2925,11 +2925,12 @@ async fn on_event_receive_acl_revoke_peer_event() {
 }
 
 #[tokio::test]
-async fn on_event_receive_team_add_peer_event_no_entity_target() {
+async fn on_event_receive_team_add_peer_event_targets_team_entity() {
     let (policy, _local, remote, entity) = setup_enterprise().await;
 
-    // TeamAddPeer has no entity_id in payload, so acl_target_entity returns None
-    // It falls back to the `entity` argument. Give remote Admin on `entity`.
+    // TeamAddPeer is checked against the team's admin entity, not the
+    // `entity` argument.
+    let team = TeamId::new();
     let acl = EntityAcl::new(entity).with_peer_role(remote, SyncRole::Admin);
     policy.acls.write().await.insert(entity, acl);
 
@@ -2938,11 +2939,18 @@ async fn on_event_receive_team_add_peer_event_no_entity_target() {
         remote,
         HybridTimestamp::now(),
         EventPayload::TeamAddPeer {
-            team_id: uuid::Uuid::new_v4().to_string(),
+            team_id: team.0.to_string(),
             peer_id: PeerId::new().to_string(),
         },
     );
 
+    let result = policy
+        .on_event_receive(&remote, &entity, std::slice::from_ref(&acl_event))
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    policy.grant_peer_role(team.admin_entity(), remote, SyncRole::Admin).await;
     let result = policy.on_event_receive(&remote, &entity, &[acl_event]).await.unwrap();
     assert_eq!(result.len(), 1);
 }
@@ -1,9 +1,10 @@
 use privstack_crdt::VectorClock;
 use privstack_sync::protocol::{
     ErrorMessage, EventAckMessage, EventBatchMessage, EventNotifyMessage, HelloAckMessage,
-    HelloMessage, SubscribeMessage, SyncMessage, SyncRequestMessage, SyncStateMessage,
-    MAX_BATCH_SIZE, PROTOCOL_VERSION,
+    HelloMessage, KeyShareMessage, SubscribeMessage, SyncMessage, SyncRequestMessage, SyncStateMessage,
+    WireCapabilities, WireCompression, WireEncoding, WireFormat, MAX_BATCH_SIZE, PROTOCOL_VERSION,
 };
+use privstack_sync::PayloadKeyring;
 use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
 
 // ── Constants ────────────────────────────────────────────────────
@@ -103,6 +104,99 @@ fn hello_ack_serde_roundtrip() {
     assert_eq!(parsed.device_name, ack.device_name);
 }
 
+#[test]
+fn hello_from_legacy_peer_has_no_wire_capabilities() {
+    // JSON sent by a build that predates wire negotiation
+    let legacy = format!(
+        r#"{{"version":1,"peer_id":"{}","device_name":"Old","entity_ids":[]}}"#,
+        PeerId::new()
+    );
+    let parsed: HelloMessage = serde_json::from_str(&legacy).unwrap();
+    assert!(parsed.wire.is_none());
+    assert!(parsed.e2e_public_key.is_none());
+}
+
+#[test]
+fn hello_e2e_public_key_serde_roundtrip() {
+    let msg = HelloMessage::new(PeerId::new(), "Dev").with_e2e_public_key([3u8; 32]);
+    let json = serde_json::to_string(&msg).unwrap();
+    let parsed: HelloMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.e2e_public_key, Some([3u8; 32]));
+
+    let ack = HelloAckMessage::accept(PeerId::new(), "Dev").with_e2e_public_key([4u8; 32]);
+    let json = serde_json::to_string(&ack).unwrap();
+    let parsed: HelloAckMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.e2e_public_key, Some([4u8; 32]));
+}
+
+#[test]
+fn key_share_message_serde_roundtrip() {
+    let mut sender = PayloadKeyring::new();
+    let mut receiver = PayloadKeyring::new();
+    sender.ensure_group_key();
+    let keys = sender
+        .seal_current_keys(&receiver.exchange_public_key(), |_| true)
+        .unwrap();
+
+    let msg = SyncMessage::KeyShare(KeyShareMessage { keys });
+    let json = serde_json::to_string(&msg).unwrap();
+    match serde_json::from_str::<SyncMessage>(&json).unwrap() {
+        SyncMessage::KeyShare(share) => assert_eq!(receiver.import(&share.keys), 1),
+        other => panic!("expected KeyShare, got {other:?}"),
+    }
+}
+
+#[test]
+fn hello_advertises_supported_wire_capabilities() {
+    let msg = HelloMessage::new(PeerId::new(), "Dev");
+    assert_eq!(msg.wire, Some(WireCapabilities::supported()));
+}
+
+#[test]
+fn hello_ack_wire_format_serde_roundtrip() {
+    let ack = HelloAckMessage::accept(PeerId::new(), "Dev").with_wire_format(WireFormat::CBOR_ZSTD);
+
+    let json = serde_json::to_string(&ack).unwrap();
+    let parsed: HelloAckMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.wire_format, Some(WireFormat::CBOR_ZSTD));
+}
+
+// ── Wire negotiation ─────────────────────────────────────────────
+
+#[test]
+fn negotiate_prefers_cbor_with_zstd() {
+    let ours = WireCapabilities::supported();
+    assert_eq!(ours.negotiate(&WireCapabilities::supported()), WireFormat::CBOR_ZSTD);
+}
+
+#[test]
+fn negotiate_with_json_only_peer_falls_back_to_json() {
+    let ours = WireCapabilities::supported();
+    assert_eq!(ours.negotiate(&WireCapabilities::json_only()), WireFormat::JSON);
+}
+
+#[test]
+fn negotiate_cbor_without_common_compression() {
+    let ours = WireCapabilities::supported();
+    let remote = WireCapabilities {
+        encodings: vec![WireEncoding::Cbor],
+        compression: vec![],
+    };
+    let format = ours.negotiate(&remote);
+    assert_eq!(format.encoding, WireEncoding::Cbor);
+    assert_eq!(format.compression, WireCompression::None);
+}
+
+#[test]
+fn negotiate_with_no_common_encoding_uses_json() {
+    let ours = WireCapabilities::json_only();
+    let remote = WireCapabilities {
+        encodings: vec![WireEncoding::Cbor],
+        compression: vec![WireCompression::Zstd],
+    };
+    assert_eq!(ours.negotiate(&remote), WireFormat::JSON);
+}
+
 // ── SyncRequestMessage ───────────────────────────────────────────
 
 #[test]
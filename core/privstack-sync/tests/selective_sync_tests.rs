@@ -0,0 +1,768 @@
+//! Selective sync tests.
+//!
+//! Covers `SyncScope` matching, `SelectiveSyncPolicy` send/receive filtering,
+//! scope advertisement in the handshake, and on-demand fetch of out-of-scope
+//! entities through real orchestrators connected by an in-process bridge.
+
+use async_trait::async_trait;
+use privstack_storage::{EntityStore, EventStore};
+use privstack_sync::pairing::{DiscoveredPeerInfo, PairingManager, PairingStatus};
+use privstack_sync::policy::{
+    PersonalSyncPolicy, ScopeSubject, SelectiveSyncPolicy, SyncPolicy, SyncScope,
+};
+use privstack_sync::protocol::{HelloAckMessage, HelloMessage};
+use privstack_sync::transport::{
+    DiscoveredPeer, DiscoveryMethod, IncomingSyncRequest, ResponseToken, SyncTransport,
+};
+use privstack_sync::{
+    create_personal_orchestrator, create_selective_orchestrator, EventApplicator,
+    OrchestratorConfig, OrchestratorHandle, SyncCommand, SyncEvent, SyncMessage,
+    SyncOrchestrator, SyncResult,
+};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex as TokioMutex};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+fn now_ms() -> u64 {
+    HybridTimestamp::now().wall_time()
+}
+
+fn make_event_at(entity_id: EntityId, entity_type: &str, json: &str, wall_time: u64) -> Event {
+    Event::new(
+        entity_id,
+        PeerId::new(),
+        HybridTimestamp::new(wall_time, 0),
+        EventPayload::FullSnapshot {
+            entity_type: entity_type.into(),
+            json_data: json.into(),
+        },
+    )
+}
+
+fn make_event(entity_id: EntityId, entity_type: &str, json: &str) -> Event {
+    make_event_at(entity_id, entity_type, json, now_ms())
+}
+
+fn subject(entity_type: &str, tags: &[&str], modified_at: u64) -> ScopeSubject {
+    let tags: Vec<String> = tags.iter().map(|t| format!("\"{t}\"")).collect();
+    let json = format!(r#"{{"tags":[{}]}}"#, tags.join(","));
+    let mut subject = ScopeSubject::default();
+    subject.observe(&make_event_at(EntityId::new(), entity_type, &json, modified_at));
+    subject
+}
+
+// ── SyncScope matching ──────────────────────────────────────────
+
+#[test]
+fn unrestricted_scope_matches_everything() {
+    let scope = SyncScope::all();
+    assert!(scope.is_unrestricted());
+    assert!(scope.matches(&EntityId::new(), &subject("note", &[], 0), now_ms()));
+    assert!(scope.matches(&EntityId::new(), &ScopeSubject::default(), now_ms()));
+}
+
+#[test]
+fn include_types_limit_entity_types() {
+    let scope = SyncScope::all().with_include_types(["note", "task"]);
+    let now = now_ms();
+    assert!(scope.matches(&EntityId::new(), &subject("note", &[], now), now));
+    assert!(scope.matches(&EntityId::new(), &subject("task", &[], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("journal", &[], now), now));
+}
+
+#[test]
+fn exclude_types_win_over_include_types() {
+    let scope = SyncScope::all()
+        .with_include_types(["note", "journal"])
+        .with_exclude_types(["journal"]);
+    let now = now_ms();
+    assert!(scope.matches(&EntityId::new(), &subject("note", &[], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("journal", &[], now), now));
+}
+
+#[test]
+fn include_tags_require_one_matching_tag() {
+    let scope = SyncScope::all().with_include_tags(["work", "travel"]);
+    let now = now_ms();
+    assert!(scope.matches(&EntityId::new(), &subject("note", &["travel"], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("note", &["home"], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("note", &[], now), now));
+}
+
+#[test]
+fn exclude_tags_reject_any_matching_tag() {
+    let scope = SyncScope::all().with_exclude_tags(["archive"]);
+    let now = now_ms();
+    assert!(scope.matches(&EntityId::new(), &subject("note", &["work"], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("note", &["work", "archive"], now), now));
+}
+
+#[test]
+fn modified_within_days_uses_newest_event() {
+    let scope = SyncScope::all().with_modified_within_days(30);
+    let now = now_ms();
+    assert!(scope.matches(&EntityId::new(), &subject("note", &[], now - 29 * DAY_MS), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("note", &[], now - 31 * DAY_MS), now));
+}
+
+#[test]
+fn pinned_entities_bypass_filters() {
+    let pinned = EntityId::new();
+    let mut scope = SyncScope::all().with_exclude_types(["journal"]);
+    scope.pinned.insert(pinned);
+    let now = now_ms();
+    assert!(scope.matches(&pinned, &subject("journal", &[], now), now));
+    assert!(!scope.matches(&EntityId::new(), &subject("journal", &[], now), now));
+}
+
+#[test]
+fn unknown_attributes_do_not_filter() {
+    let scope = SyncScope::all()
+        .with_include_types(["note"])
+        .with_include_tags(["work"]);
+    assert!(scope.matches(&EntityId::new(), &ScopeSubject::default(), now_ms()));
+}
+
+#[test]
+fn scope_json_fields_are_optional() {
+    let scope: SyncScope = serde_json::from_str(r#"{"exclude_types":["journal"]}"#).unwrap();
+    assert_eq!(scope.exclude_types, vec!["journal".to_string()]);
+    assert!(scope.include_types.is_empty());
+    assert!(scope.modified_within_days.is_none());
+    assert!(scope.pinned.is_empty());
+
+    let roundtrip: SyncScope =
+        serde_json::from_str(&serde_json::to_string(&scope).unwrap()).unwrap();
+    assert_eq!(roundtrip, scope);
+}
+
+#[test]
+fn loosening_a_filter_widens_the_scope() {
+    let scope = SyncScope::all()
+        .with_include_types(["note"])
+        .with_exclude_tags(["archive"]);
+    assert!(scope.is_widened_by(&SyncScope::all()));
+    assert!(scope.is_widened_by(&scope.clone().with_include_types(["note", "task"])));
+    assert!(scope.is_widened_by(&scope.clone().with_include_types(Vec::<String>::new())));
+    assert!(scope.is_widened_by(&scope.clone().with_exclude_tags(Vec::<String>::new())));
+
+    let mut pinned = scope.clone();
+    pinned.pinned.insert(EntityId::new());
+    assert!(scope.is_widened_by(&pinned));
+}
+
+#[test]
+fn narrowing_or_keeping_a_filter_does_not_widen_the_scope() {
+    let scope = SyncScope::all()
+        .with_include_types(["note", "task"])
+        .with_modified_within_days(30);
+    assert!(!scope.is_widened_by(&scope));
+    assert!(!scope.is_widened_by(&scope.clone().with_include_types(["note"])));
+    assert!(!scope.is_widened_by(&scope.clone().with_exclude_tags(["archive"])));
+    assert!(!scope.is_widened_by(&scope.clone().with_modified_within_days(7)));
+    assert!(scope.is_widened_by(&scope.clone().with_modified_within_days(90)));
+
+    let unrestricted = SyncScope::all();
+    assert!(!unrestricted.is_widened_by(&SyncScope::all()));
+    assert!(!unrestricted.is_widened_by(&scope));
+}
+
+// ── ScopeSubject ────────────────────────────────────────────────
+
+#[test]
+fn subject_tracks_latest_tags_and_newest_time() {
+    let eid = EntityId::new();
+    let now = now_ms();
+    let mut subject = ScopeSubject::default();
+    subject.observe(&make_event_at(eid, "note", r#"{"tags":["new"]}"#, now));
+    subject.observe(&make_event_at(eid, "note", r#"{"tags":["old"]}"#, now - DAY_MS));
+
+    assert_eq!(subject.entity_type.as_deref(), Some("note"));
+    assert_eq!(subject.tags, Some(vec!["new".to_string()]));
+    assert_eq!(subject.modified_at, now);
+}
+
+#[test]
+fn subject_keeps_tags_across_deletes() {
+    let eid = EntityId::new();
+    let mut subject = ScopeSubject::default();
+    subject.observe(&make_event(eid, "note", r#"{"tags":["archive"]}"#));
+    subject.observe(&Event::new(
+        eid,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityDeleted {
+            entity_type: "note".into(),
+        },
+    ));
+    assert_eq!(subject.tags, Some(vec!["archive".to_string()]));
+}
+
+// ── SelectiveSyncPolicy ─────────────────────────────────────────
+
+#[tokio::test]
+async fn receive_drops_out_of_scope_entities() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all().with_exclude_types(["journal"]));
+    let peer = PeerId::new();
+
+    let note = EntityId::new();
+    let events = vec![make_event(note, "note", "{}")];
+    assert_eq!(policy.on_event_receive(&peer, &note, &events).await.unwrap().len(), 1);
+
+    let journal = EntityId::new();
+    let events = vec![make_event(journal, "journal", "{}")];
+    assert!(policy.on_event_receive(&peer, &journal, &events).await.unwrap().is_empty());
+    assert!(!policy.is_in_scope(&journal).await);
+    assert!(policy.is_in_scope(&note).await);
+}
+
+#[tokio::test]
+async fn receive_filters_deletes_by_learned_tags() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all().with_exclude_tags(["archive"]));
+    let peer = PeerId::new();
+    let eid = EntityId::new();
+
+    let created = vec![make_event(eid, "note", r#"{"tags":["archive"]}"#)];
+    assert!(policy.on_event_receive(&peer, &eid, &created).await.unwrap().is_empty());
+
+    let deleted = vec![Event::new(
+        eid,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityDeleted {
+            entity_type: "note".into(),
+        },
+    )];
+    assert!(policy.on_event_receive(&peer, &eid, &deleted).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn send_uses_peer_advertised_scope() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all());
+    let phone = PeerId::new();
+    let laptop = PeerId::new();
+    policy
+        .set_peer_scope(phone, Some(SyncScope::all().with_modified_within_days(7)))
+        .await;
+
+    let old = EntityId::new();
+    let events = vec![make_event_at(old, "note", "{}", now_ms() - 365 * DAY_MS)];
+    assert!(policy.on_event_send(&phone, &old, &events).await.unwrap().is_empty());
+    assert_eq!(policy.on_event_send(&laptop, &old, &events).await.unwrap().len(), 1);
+
+    policy.set_peer_scope(phone, None).await;
+    assert!(policy.peer_scope(&phone).await.is_none());
+    assert_eq!(policy.on_event_send(&phone, &old, &events).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn send_allows_entities_the_peer_pinned() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all());
+    let phone = PeerId::new();
+    let eid = EntityId::new();
+    let mut scope = SyncScope::all().with_exclude_types(["journal"]);
+    scope.pinned.insert(eid);
+    policy.set_peer_scope(phone, Some(scope)).await;
+
+    let events = vec![make_event(eid, "journal", "{}")];
+    assert_eq!(policy.on_event_send(&phone, &eid, &events).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn pin_and_unpin_local_entities() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all().with_include_types(["task"]));
+    let peer = PeerId::new();
+    let eid = EntityId::new();
+    let events = vec![make_event(eid, "note", "{}")];
+
+    assert!(policy.on_event_receive(&peer, &eid, &events).await.unwrap().is_empty());
+
+    policy.pin(eid).await;
+    assert!(policy.scope().await.pinned.contains(&eid));
+    assert_eq!(policy.on_event_receive(&peer, &eid, &events).await.unwrap().len(), 1);
+
+    policy.unpin(&eid).await;
+    assert!(policy.on_event_receive(&peer, &eid, &events).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn set_scope_replaces_filters() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all().with_exclude_types(["note"]));
+    let peer = PeerId::new();
+    let eid = EntityId::new();
+    let events = vec![make_event(eid, "note", "{}")];
+    assert!(policy.on_event_receive(&peer, &eid, &events).await.unwrap().is_empty());
+
+    assert!(policy.set_scope(SyncScope::all()).await);
+    assert_eq!(policy.on_event_receive(&peer, &eid, &events).await.unwrap().len(), 1);
+    assert!(!policy.set_scope(SyncScope::all().with_exclude_types(["task"])).await);
+}
+
+#[tokio::test]
+async fn learned_subjects_are_bounded() {
+    let policy = SelectiveSyncPolicy::new(SyncScope::all().with_exclude_tags(["archive"]))
+        .with_subject_capacity(2);
+    let peer = PeerId::new();
+    let first = EntityId::new();
+    let events = vec![make_event(first, "note", r#"{"tags":["archive"]}"#)];
+    assert!(policy.on_event_receive(&peer, &first, &events).await.unwrap().is_empty());
+
+    for _ in 0..2 {
+        let eid = EntityId::new();
+        let events = vec![make_event(eid, "note", r#"{"tags":["work"]}"#)];
+        policy.on_event_receive(&peer, &eid, &events).await.unwrap();
+    }
+    assert_eq!(policy.known_subjects().await, 2);
+
+    // The oldest subject was evicted, so its tags no longer filter a bare delete.
+    let delete = vec![Event::new(
+        first,
+        PeerId::new(),
+        HybridTimestamp::now(),
+        EventPayload::EntityDeleted {
+            entity_type: "note".into(),
+        },
+    )];
+    assert_eq!(policy.on_event_receive(&peer, &first, &delete).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn inner_policy_runs_first() {
+    let personal = Arc::new(PersonalSyncPolicy::new());
+    let friend = PeerId::new();
+    let shared = EntityId::new();
+    let private = EntityId::new();
+    personal.share(shared, friend).await;
+
+    let policy = SelectiveSyncPolicy::new(SyncScope::all()).with_inner(personal);
+
+    let events = vec![make_event(shared, "note", "{}")];
+    assert_eq!(policy.on_event_send(&friend, &shared, &events).await.unwrap().len(), 1);
+
+    let events = vec![make_event(private, "note", "{}")];
+    assert!(policy.on_event_send(&friend, &private, &events).await.unwrap().is_empty());
+    assert!(policy.on_event_receive(&friend, &private, &events).await.unwrap().is_empty());
+
+    let allowed = policy.on_sync_request(&friend, &[shared, private]).await.unwrap();
+    assert_eq!(allowed, vec![shared]);
+}
+
+// ── Protocol ────────────────────────────────────────────────────
+
+#[test]
+fn hello_carries_sync_scope() {
+    let scope = SyncScope::all().with_include_tags(["work"]);
+    let hello = HelloMessage::new(PeerId::new(), "Phone").with_sync_scope(scope.clone());
+    let json = serde_json::to_string(&hello).unwrap();
+    let decoded: HelloMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.sync_scope, Some(scope.clone()));
+
+    let ack = HelloAckMessage::accept(PeerId::new(), "Desktop").with_sync_scope(scope.clone());
+    let decoded: HelloAckMessage =
+        serde_json::from_str(&serde_json::to_string(&ack).unwrap()).unwrap();
+    assert_eq!(decoded.sync_scope, Some(scope));
+}
+
+#[test]
+fn hello_without_scope_from_older_peer() {
+    let hello = HelloMessage::new(PeerId::new(), "Old");
+    let mut value = serde_json::to_value(&hello).unwrap();
+    value.as_object_mut().unwrap().remove("sync_scope");
+    let decoded: HelloMessage = serde_json::from_value(value).unwrap();
+    assert!(decoded.sync_scope.is_none());
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Orchestrator: scope advertisement and on-demand fetch
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct BridgedRequest {
+    from: PeerId,
+    message: SyncMessage,
+    response_tx: oneshot::Sender<SyncMessage>,
+}
+
+/// Transport that bridges two orchestrators in-process and always reports
+/// the other side as discovered.
+struct BridgedTransport {
+    local_peer_id: PeerId,
+    remote_peer_id: PeerId,
+    outgoing_tx: mpsc::Sender<BridgedRequest>,
+    incoming_rx: TokioMutex<mpsc::Receiver<BridgedRequest>>,
+}
+
+impl BridgedTransport {
+    fn pair(peer_a: PeerId, peer_b: PeerId) -> (Self, Self) {
+        let (a_to_b_tx, a_to_b_rx) = mpsc::channel(64);
+        let (b_to_a_tx, b_to_a_rx) = mpsc::channel(64);
+        (
+            Self {
+                local_peer_id: peer_a,
+                remote_peer_id: peer_b,
+                outgoing_tx: a_to_b_tx,
+                incoming_rx: TokioMutex::new(b_to_a_rx),
+            },
+            Self {
+                local_peer_id: peer_b,
+                remote_peer_id: peer_a,
+                outgoing_tx: b_to_a_tx,
+                incoming_rx: TokioMutex::new(a_to_b_rx),
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl SyncTransport for BridgedTransport {
+    async fn start(&mut self) -> SyncResult<()> {
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> SyncResult<()> {
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        true
+    }
+
+    fn local_peer_id(&self) -> PeerId {
+        self.local_peer_id
+    }
+
+    fn discovered_peers(&self) -> Vec<DiscoveredPeer> {
+        vec![]
+    }
+
+    async fn discovered_peers_async(&self) -> Vec<DiscoveredPeer> {
+        vec![DiscoveredPeer {
+            peer_id: self.remote_peer_id,
+            device_name: None,
+            discovery_method: DiscoveryMethod::Mdns,
+            addresses: vec![],
+        }]
+    }
+
+    async fn send_request(&self, _peer_id: &PeerId, message: SyncMessage) -> SyncResult<SyncMessage> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.outgoing_tx
+            .send(BridgedRequest {
+                from: self.local_peer_id,
+                message,
+                response_tx,
+            })
+            .await
+            .map_err(|_| privstack_sync::SyncError::Network("bridge closed".into()))?;
+        response_rx
+            .await
+            .map_err(|_| privstack_sync::SyncError::Network("response dropped".into()))
+    }
+
+    async fn recv_request(&self) -> Option<IncomingSyncRequest> {
+        let bridged = self.incoming_rx.lock().await.recv().await?;
+        Some(IncomingSyncRequest {
+            peer_id: bridged.from,
+            message: bridged.message,
+            response_token: ResponseToken::new(bridged.response_tx),
+        })
+    }
+
+    async fn send_response(&self, token: ResponseToken, message: SyncMessage) -> SyncResult<()> {
+        let tx: oneshot::Sender<SyncMessage> = token
+            .downcast()
+            .ok_or_else(|| privstack_sync::SyncError::Network("invalid token".into()))?;
+        tx.send(message)
+            .map_err(|_| privstack_sync::SyncError::Network("response dropped".into()))
+    }
+}
+
+struct Device {
+    peer_id: PeerId,
+    handle: OrchestratorHandle,
+    events: mpsc::Receiver<SyncEvent>,
+    commands: Option<mpsc::Receiver<SyncCommand>>,
+    orchestrator: Option<SyncOrchestrator>,
+    policy: Arc<SelectiveSyncPolicy>,
+    entity_store: Arc<EntityStore>,
+    event_store: Arc<EventStore>,
+}
+
+fn setup_device(peer_id: PeerId, other: PeerId, scope: SyncScope) -> Device {
+    let entity_store = Arc::new(EntityStore::open_in_memory().unwrap());
+    let event_store = Arc::new(EventStore::open_in_memory().unwrap());
+    let personal = Arc::new(PersonalSyncPolicy::new());
+    let policy = Arc::new(SelectiveSyncPolicy::new(scope).with_inner(personal.clone()));
+
+    let pm = Arc::new(std::sync::Mutex::new(PairingManager::new()));
+    {
+        let mut pm = pm.lock().unwrap();
+        pm.add_discovered_peer(DiscoveredPeerInfo {
+            peer_id: other.to_string(),
+            device_name: "Other".to_string(),
+            discovered_at: 0,
+            status: PairingStatus::PendingLocalApproval,
+            addresses: vec![],
+        });
+        pm.approve_peer(&other.to_string());
+    }
+
+    let config = OrchestratorConfig {
+        sync_interval: Duration::from_secs(3600),
+        discovery_interval: Duration::from_secs(3600),
+        auto_sync: false,
+        max_entities_per_sync: 0,
+    };
+    let (handle, events, commands, orchestrator) = create_selective_orchestrator(
+        peer_id,
+        entity_store.clone(),
+        event_store.clone(),
+        config,
+        personal,
+        policy.clone(),
+        pm,
+    );
+
+    Device {
+        peer_id,
+        handle,
+        events,
+        commands: Some(commands),
+        orchestrator: Some(orchestrator),
+        policy,
+        entity_store,
+        event_store,
+    }
+}
+
+async fn record(device: &Device, event: Event) {
+    let evs = device.event_store.clone();
+    let ev = event.clone();
+    tokio::task::spawn_blocking(move || evs.save_event(&ev))
+        .await
+        .unwrap()
+        .unwrap();
+    let es = device.entity_store.clone();
+    let ev = event.clone();
+    let peer_id = device.peer_id;
+    tokio::task::spawn_blocking(move || EventApplicator::new(peer_id).apply_event(&ev, &es, None, None))
+        .await
+        .unwrap()
+        .unwrap();
+    device.handle.record_event(event).await.unwrap();
+}
+
+async fn wait_for(
+    rx: &mut mpsc::Receiver<SyncEvent>,
+    mut predicate: impl FnMut(&SyncEvent) -> bool,
+) -> Option<SyncEvent> {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(event)) if predicate(&event) => return Some(event),
+            Ok(Some(_)) => continue,
+            _ => return None,
+        }
+    }
+}
+
+fn start(
+    device: &mut Device,
+    transport: BridgedTransport,
+) -> tokio::task::JoinHandle<SyncResult<()>> {
+    let orchestrator = device.orchestrator.take().unwrap();
+    let commands = device.commands.take().unwrap();
+    let transport: Arc<TokioMutex<dyn SyncTransport>> = Arc::new(TokioMutex::new(transport));
+    tokio::spawn(async move { orchestrator.run(transport, commands).await })
+}
+
+fn event_count(device: &Device, entity_id: &EntityId) -> usize {
+    device.event_store.get_events_for_entity(entity_id).unwrap().len()
+}
+
+#[tokio::test]
+async fn desktop_skips_entities_outside_phone_scope_until_fetched() {
+    let desktop_id = PeerId::new();
+    let phone_id = PeerId::new();
+    let mut desktop = setup_device(desktop_id, phone_id, SyncScope::all());
+    let mut phone = setup_device(
+        phone_id,
+        desktop_id,
+        SyncScope::all().with_exclude_tags(["archive"]),
+    );
+
+    let note = EntityId::new();
+    let archived = EntityId::new();
+    let (t_desktop, t_phone) = BridgedTransport::pair(desktop_id, phone_id);
+    let join_desktop = start(&mut desktop, t_desktop);
+    let join_phone = start(&mut phone, t_phone);
+
+    record(&desktop, make_event(note, "note", r#"{"tags":["work"]}"#)).await;
+    record(&desktop, make_event(archived, "note", r#"{"tags":["archive"]}"#)).await;
+    desktop.handle.share_entity(note).await.unwrap();
+    desktop.handle.share_entity(archived).await.unwrap();
+
+    desktop
+        .handle
+        .send(SyncCommand::SyncWithPeer { peer_id: phone_id })
+        .await
+        .unwrap();
+    assert!(wait_for(&mut desktop.events, |e| matches!(e, SyncEvent::SyncCompleted { .. }))
+        .await
+        .is_some());
+
+    // The phone advertised its scope in the HelloAck; the desktop only sent the note.
+    assert_eq!(
+        desktop.policy.peer_scope(&phone_id).await.map(|s| s.exclude_tags),
+        Some(vec!["archive".to_string()])
+    );
+    assert_eq!(event_count(&phone, &note), 1);
+    assert_eq!(event_count(&phone, &archived), 0);
+
+    // Opening a link to the archived note fetches it on demand.
+    phone.handle.fetch_entity(archived).await.unwrap();
+    let fetched = wait_for(&mut phone.events, |e| {
+        matches!(e, SyncEvent::EntityUpdated { entity_id } if *entity_id == archived)
+    })
+    .await;
+    assert!(fetched.is_some(), "fetched entity should be applied on the phone");
+    assert_eq!(event_count(&phone, &archived), 1);
+    assert!(phone.policy.scope().await.pinned.contains(&archived));
+    assert!(phone.entity_store.get_entity(&archived.to_string()).unwrap().is_some());
+
+    desktop.handle.shutdown().await.unwrap();
+    phone.handle.shutdown().await.unwrap();
+    let _ = join_desktop.await;
+    let _ = join_phone.await;
+}
+
+#[tokio::test]
+async fn widening_the_phone_scope_resyncs_with_the_desktop() {
+    let desktop_id = PeerId::new();
+    let phone_id = PeerId::new();
+    let mut desktop = setup_device(desktop_id, phone_id, SyncScope::all());
+    let mut phone = setup_device(
+        phone_id,
+        desktop_id,
+        SyncScope::all().with_exclude_tags(["archive"]),
+    );
+
+    let archived = EntityId::new();
+    let (t_desktop, t_phone) = BridgedTransport::pair(desktop_id, phone_id);
+    let join_desktop = start(&mut desktop, t_desktop);
+    let join_phone = start(&mut phone, t_phone);
+
+    record(&desktop, make_event(archived, "note", r#"{"tags":["archive"]}"#)).await;
+    desktop.handle.share_entity(archived).await.unwrap();
+    desktop
+        .handle
+        .send(SyncCommand::SyncWithPeer { peer_id: phone_id })
+        .await
+        .unwrap();
+    assert!(wait_for(&mut desktop.events, |e| matches!(e, SyncEvent::SyncCompleted { .. }))
+        .await
+        .is_some());
+    assert_eq!(event_count(&phone, &archived), 0);
+
+    // Dropping the exclusion re-handshakes, so the desktop learns the new scope.
+    assert!(phone.policy.set_scope(SyncScope::all()).await);
+    phone.handle.scope_widened().await.unwrap();
+    assert!(wait_for(&mut phone.events, |e| matches!(e, SyncEvent::SyncCompleted { .. }))
+        .await
+        .is_some());
+    assert_eq!(
+        desktop.policy.peer_scope(&phone_id).await.map(|s| s.exclude_tags),
+        Some(vec![])
+    );
+
+    desktop
+        .handle
+        .send(SyncCommand::SyncWithPeer { peer_id: phone_id })
+        .await
+        .unwrap();
+    assert!(wait_for(&mut desktop.events, |e| matches!(e, SyncEvent::SyncCompleted { .. }))
+        .await
+        .is_some());
+    assert_eq!(event_count(&phone, &archived), 1);
+
+    desktop.handle.shutdown().await.unwrap();
+    phone.handle.shutdown().await.unwrap();
+    let _ = join_desktop.await;
+    let _ = join_phone.await;
+}
+
+#[tokio::test]
+async fn phone_drops_out_of_scope_events_from_unscoped_peers() {
+    // A peer without a selective policy ignores the advertised scope and
+    // sends everything; the phone's receive-side check keeps its store
+    // within scope.
+    let desktop_id = PeerId::new();
+    let phone_id = PeerId::new();
+    let mut desktop = setup_device(desktop_id, phone_id, SyncScope::all());
+    let mut phone = setup_device(
+        phone_id,
+        desktop_id,
+        SyncScope::all().with_include_types(["task"]),
+    );
+
+    let pm = Arc::new(std::sync::Mutex::new(PairingManager::new()));
+    {
+        let mut pm = pm.lock().unwrap();
+        pm.add_discovered_peer(DiscoveredPeerInfo {
+            peer_id: phone_id.to_string(),
+            device_name: "Phone".to_string(),
+            discovered_at: 0,
+            status: PairingStatus::PendingLocalApproval,
+            addresses: vec![],
+        });
+        pm.approve_peer(&phone_id.to_string());
+    }
+    let (handle, events, commands, orchestrator) = create_personal_orchestrator(
+        desktop_id,
+        desktop.entity_store.clone(),
+        desktop.event_store.clone(),
+        OrchestratorConfig {
+            sync_interval: Duration::from_secs(3600),
+            discovery_interval: Duration::from_secs(3600),
+            auto_sync: false,
+            max_entities_per_sync: 0,
+        },
+        Arc::new(PersonalSyncPolicy::new()),
+        pm,
+    );
+    desktop.handle = handle;
+    desktop.events = events;
+    desktop.commands = Some(commands);
+    desktop.orchestrator = Some(orchestrator);
+
+    let task = EntityId::new();
+    let note = EntityId::new();
+    let (t_desktop, t_phone) = BridgedTransport::pair(desktop_id, phone_id);
+    let join_desktop = start(&mut desktop, t_desktop);
+    let join_phone = start(&mut phone, t_phone);
+
+    record(&desktop, make_event(task, "task", "{}")).await;
+    record(&desktop, make_event(note, "note", "{}")).await;
+    desktop.handle.share_entity(task).await.unwrap();
+    desktop.handle.share_entity(note).await.unwrap();
+
+    desktop
+        .handle
+        .send(SyncCommand::SyncWithPeer { peer_id: phone_id })
+        .await
+        .unwrap();
+    assert!(wait_for(&mut desktop.events, |e| matches!(e, SyncEvent::SyncCompleted { .. }))
+        .await
+        .is_some());
+
+    assert_eq!(event_count(&phone, &task), 1);
+    assert_eq!(event_count(&phone, &note), 0);
+    assert!(phone.entity_store.get_entity(&note.to_string()).unwrap().is_none());
+
+    desktop.handle.shutdown().await.unwrap();
+    phone.handle.shutdown().await.unwrap();
+    let _ = join_desktop.await;
+    let _ = join_phone.await;
+}
@@ -0,0 +1,427 @@
+use privstack_storage::{EntityStore, EventStore};
+use privstack_sync::pairing::{DiscoveredPeerInfo, PairingManager, PairingStatus};
+use privstack_sync::policy::{EnterpriseSyncPolicy, TeamId};
+use privstack_sync::policy_store::PolicyStore;
+use privstack_sync::protocol::{EventBatchMessage, SyncMessage};
+use privstack_sync::{
+    verify_signature, Authorship, DeviceKeyRegistry, DeviceSigningKey, SyncConfig, SyncEngine,
+    SyncError,
+};
+use privstack_types::{EntityId, Event, EventPayload, HybridTimestamp, PeerId};
+use std::sync::Arc;
+
+fn make_event(entity_id: EntityId, peer_id: PeerId) -> Event {
+    Event::new(
+        entity_id,
+        peer_id,
+        HybridTimestamp::now(),
+        EventPayload::FullSnapshot {
+            entity_type: "note".into(),
+            json_data: r#"{"title":"test"}"#.into(),
+        },
+    )
+}
+
+fn make_stores() -> (Arc<EntityStore>, Arc<EventStore>) {
+    let entity_store = Arc::new(EntityStore::open_in_memory().unwrap());
+    let event_store = Arc::new(EventStore::open_in_memory().unwrap());
+    (entity_store, event_store)
+}
+
+fn make_discovered_peer(id: &str) -> DiscoveredPeerInfo {
+    DiscoveredPeerInfo {
+        peer_id: id.to_string(),
+        device_name: "Device".to_string(),
+        discovered_at: 1000,
+        status: PairingStatus::PendingLocalApproval,
+        addresses: vec![],
+    }
+}
+
+// ── Signing & verification ───────────────────────────────────────
+
+#[test]
+fn signed_event_verifies() {
+    let key = DeviceSigningKey::generate();
+    let event = key.signed(&make_event(EntityId::new(), PeerId::new())).unwrap();
+
+    assert!(event.is_signed());
+    assert_eq!(verify_signature(&event).unwrap(), Some(key.public_key()));
+}
+
+#[test]
+fn unsigned_event_verifies_as_none() {
+    let event = make_event(EntityId::new(), PeerId::new());
+    assert_eq!(verify_signature(&event).unwrap(), None);
+}
+
+#[test]
+fn tampered_payload_fails_verification() {
+    let key = DeviceSigningKey::generate();
+    let mut event = key.signed(&make_event(EntityId::new(), PeerId::new())).unwrap();
+    event.payload = EventPayload::FullSnapshot {
+        entity_type: "note".into(),
+        json_data: r#"{"title":"forged"}"#.into(),
+    };
+
+    assert!(matches!(verify_signature(&event), Err(SyncError::Auth(_))));
+}
+
+#[test]
+fn reattributed_event_fails_verification() {
+    let key = DeviceSigningKey::generate();
+    let mut event = key.signed(&make_event(EntityId::new(), PeerId::new())).unwrap();
+    event.peer_id = PeerId::new();
+
+    assert!(matches!(verify_signature(&event), Err(SyncError::Auth(_))));
+}
+
+#[test]
+fn signing_key_serde_roundtrip() {
+    let key = DeviceSigningKey::generate();
+    let json = serde_json::to_string(&key).unwrap();
+    let restored: DeviceSigningKey = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.public_key(), key.public_key());
+}
+
+#[test]
+fn signing_key_debug_redacts_secret() {
+    let key = DeviceSigningKey::generate();
+    let debug = format!("{key:?}");
+    assert!(debug.contains("REDACTED"));
+    assert!(debug.contains(&hex::encode(key.public_key())));
+}
+
+// ── Registry ─────────────────────────────────────────────────────
+
+#[test]
+fn registry_accepts_event_from_registered_device() {
+    let registry = DeviceKeyRegistry::new();
+    let author = PeerId::new();
+    let key = DeviceSigningKey::generate();
+    registry.register(author, key.public_key());
+
+    let event = key.signed(&make_event(EntityId::new(), author)).unwrap();
+    assert_eq!(
+        registry.authorship(&event),
+        Authorship::Verified { device_key: key.public_key() }
+    );
+    assert!(registry.check(&event).is_ok());
+}
+
+#[test]
+fn registry_rejects_forged_author() {
+    let registry = DeviceKeyRegistry::new();
+    let victim = PeerId::new();
+    registry.register(victim, DeviceSigningKey::generate().public_key());
+
+    // The forger signs validly with its own key but claims the victim's ID.
+    let forger_key = DeviceSigningKey::generate();
+    let event = forger_key.signed(&make_event(EntityId::new(), victim)).unwrap();
+
+    assert_eq!(
+        registry.authorship(&event),
+        Authorship::UnknownDevice { device_key: forger_key.public_key() }
+    );
+    assert!(matches!(registry.check(&event), Err(SyncError::Auth(_))));
+}
+
+#[test]
+fn registry_rejects_unsigned_event_from_keyed_author() {
+    let registry = DeviceKeyRegistry::new();
+    let author = PeerId::new();
+    registry.register(author, DeviceSigningKey::generate().public_key());
+
+    let event = make_event(EntityId::new(), author);
+    assert_eq!(registry.authorship(&event), Authorship::Unsigned);
+    assert!(registry.check(&event).is_err());
+}
+
+#[test]
+fn permissive_registry_accepts_unkeyed_authors() {
+    let registry = DeviceKeyRegistry::new();
+    let unsigned = make_event(EntityId::new(), PeerId::new());
+    let signed = DeviceSigningKey::generate()
+        .signed(&make_event(EntityId::new(), PeerId::new()))
+        .unwrap();
+
+    assert!(!registry.requires_signatures());
+    assert!(registry.check(&unsigned).is_ok());
+    assert!(registry.check(&signed).is_ok());
+}
+
+#[test]
+fn strict_registry_rejects_unkeyed_authors() {
+    let registry = DeviceKeyRegistry::strict();
+    let unsigned = make_event(EntityId::new(), PeerId::new());
+    let signed = DeviceSigningKey::generate()
+        .signed(&make_event(EntityId::new(), PeerId::new()))
+        .unwrap();
+
+    assert!(registry.requires_signatures());
+    assert!(registry.check(&unsigned).is_err());
+    assert!(registry.check(&signed).is_err());
+}
+
+#[test]
+fn registry_always_rejects_invalid_signature() {
+    let registry = DeviceKeyRegistry::new();
+    let key = DeviceSigningKey::generate();
+    let mut event = key.signed(&make_event(EntityId::new(), PeerId::new())).unwrap();
+    event.signature.as_mut().unwrap().signature[0] ^= 0xff;
+
+    assert!(matches!(registry.authorship(&event), Authorship::Invalid { .. }));
+    assert!(registry.check(&event).is_err());
+}
+
+#[test]
+fn revoked_device_key_is_rejected() {
+    let registry = DeviceKeyRegistry::new();
+    let author = PeerId::new();
+    let old_key = DeviceSigningKey::generate();
+    let new_key = DeviceSigningKey::generate();
+    registry.register(author, old_key.public_key());
+    registry.register(author, new_key.public_key());
+    registry.revoke(&author, &old_key.public_key());
+
+    let old = old_key.signed(&make_event(EntityId::new(), author)).unwrap();
+    let new = new_key.signed(&make_event(EntityId::new(), author)).unwrap();
+    assert!(registry.check(&old).is_err());
+    assert!(registry.check(&new).is_ok());
+    assert_eq!(registry.keys_for(&author), vec![new_key.public_key()]);
+}
+
+#[test]
+fn remove_peer_drops_all_keys() {
+    let registry = DeviceKeyRegistry::new();
+    let author = PeerId::new();
+    assert!(registry.register(author, DeviceSigningKey::generate().public_key()));
+    assert!(registry.has_keys(&author));
+    assert_eq!(registry.peers(), vec![author]);
+
+    registry.remove_peer(&author);
+    assert!(!registry.has_keys(&author));
+    assert!(registry.peers().is_empty());
+    assert!(registry.keys_for(&author).is_empty());
+}
+
+// ── Engine ───────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn engine_signs_only_own_events() {
+    let me = PeerId::new();
+    let mut engine = SyncEngine::new(me, SyncConfig::default());
+    engine.set_key_registry(Arc::new(DeviceKeyRegistry::new()));
+    let key = DeviceSigningKey::generate();
+    engine.set_signing_key(key.clone());
+
+    let mut own = make_event(EntityId::new(), me);
+    engine.sign_local_event(&mut own).unwrap();
+    assert_eq!(verify_signature(&own).unwrap(), Some(key.public_key()));
+
+    let mut foreign = make_event(EntityId::new(), PeerId::new());
+    engine.sign_local_event(&mut foreign).unwrap();
+    assert!(!foreign.is_signed());
+
+    // Our own public key is registered, so relays cannot forge in our name.
+    assert_eq!(engine.key_registry().unwrap().keys_for(&me), vec![key.public_key()]);
+}
+
+#[tokio::test]
+async fn engine_drops_forged_events_from_batch() {
+    let mut engine = SyncEngine::new(PeerId::new(), SyncConfig::default());
+    let registry = Arc::new(DeviceKeyRegistry::new());
+    engine.set_key_registry(registry.clone());
+    let (entity_store, event_store) = make_stores();
+
+    let author = PeerId::new();
+    let author_key = DeviceSigningKey::generate();
+    registry.register(author, author_key.public_key());
+
+    let eid = EntityId::new();
+    let genuine = author_key.signed(&make_event(eid, author)).unwrap();
+    let forged = DeviceSigningKey::generate().signed(&make_event(eid, author)).unwrap();
+    let unsigned = make_event(eid, author);
+
+    let batch = EventBatchMessage {
+        entity_id: eid,
+        events: vec![genuine.clone(), forged.clone(), unsigned.clone()],
+        is_final: true,
+        batch_seq: 0,
+    };
+    let relay = PeerId::new();
+    let (ack, updated) = engine
+        .handle_event_batch(&relay, &batch, &entity_store, &event_store)
+        .await;
+
+    match ack {
+        SyncMessage::EventAck(a) => assert_eq!(a.received_count, 1),
+        _ => panic!("Expected EventAck"),
+    }
+    assert_eq!(updated, vec![eid]);
+
+    let stored = event_store.get_events_for_entity(&eid).unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].id, genuine.id);
+    assert!(stored[0].is_signed());
+}
+
+#[tokio::test]
+async fn engine_without_registry_accepts_unsigned_events() {
+    let engine = SyncEngine::new(PeerId::new(), SyncConfig::default());
+    let (entity_store, event_store) = make_stores();
+    let eid = EntityId::new();
+
+    let batch = EventBatchMessage {
+        entity_id: eid,
+        events: vec![make_event(eid, PeerId::new())],
+        is_final: true,
+        batch_seq: 0,
+    };
+    let (_, updated) = engine
+        .handle_event_batch(&PeerId::new(), &batch, &entity_store, &event_store)
+        .await;
+    assert_eq!(updated, vec![eid]);
+}
+
+// ── Pairing ──────────────────────────────────────────────────────
+
+#[test]
+fn pairing_generates_stable_signing_key() {
+    let mut pm = PairingManager::new();
+    let first = pm.signing_key().public_key();
+    assert_eq!(pm.signing_key().public_key(), first);
+
+    // The secret stays out of the pairing JSON
+    let json = pm.to_json().unwrap();
+    let mut restored = PairingManager::from_json(&json).unwrap();
+    assert_ne!(restored.signing_key().public_key(), first);
+}
+
+#[test]
+fn pairing_pins_first_signing_key() {
+    let mut pm = PairingManager::new();
+    let peer = PeerId::new().to_string();
+    pm.add_discovered_peer(make_discovered_peer(&peer));
+    pm.approve_peer(&peer);
+
+    let key = DeviceSigningKey::generate().public_key();
+    assert!(pm.set_peer_signing_key(&peer, key));
+    assert!(pm.set_peer_signing_key(&peer, key));
+    assert!(!pm.set_peer_signing_key(&peer, DeviceSigningKey::generate().public_key()));
+
+    let trusted = pm.trusted_peers();
+    assert_eq!(trusted[0].signing_public_key, Some(key));
+}
+
+#[test]
+fn pairing_repins_signing_key_of_repaired_device() {
+    let mut pm = PairingManager::new();
+    let peer = PeerId::new().to_string();
+    pm.add_discovered_peer(make_discovered_peer(&peer));
+    pm.approve_peer(&peer);
+    let old_key = DeviceSigningKey::generate().public_key();
+    assert!(pm.set_peer_signing_key(&peer, old_key));
+
+    // The reinstalled device presents a new key: refused, but offered for approval
+    let new_key = DeviceSigningKey::generate().public_key();
+    assert!(!pm.set_peer_signing_key(&peer, new_key));
+    assert_eq!(pm.get_trusted_peer(&peer).unwrap().signing_public_key, Some(old_key));
+    assert_eq!(
+        pm.get_discovered_peer(&peer).unwrap().status,
+        PairingStatus::PendingLocalApproval
+    );
+
+    // Approving the re-pair pins the new key, and the old one is now refused
+    pm.approve_peer(&peer);
+    assert_eq!(pm.get_trusted_peer(&peer).unwrap().signing_public_key, Some(new_key));
+    assert!(pm.set_peer_signing_key(&peer, new_key));
+    assert!(!pm.set_peer_signing_key(&peer, old_key));
+}
+
+#[test]
+fn pairing_remove_trusted_peer_clears_signing_pin() {
+    let mut pm = PairingManager::new();
+    let peer = PeerId::new().to_string();
+    pm.add_discovered_peer(make_discovered_peer(&peer));
+    pm.approve_peer(&peer);
+    assert!(pm.set_peer_signing_key(&peer, DeviceSigningKey::generate().public_key()));
+
+    pm.remove_trusted_peer(&peer);
+    assert!(pm.get_trusted_peer(&peer).is_none());
+
+    // Pairing again pins whatever key the device presents next
+    pm.add_discovered_peer(make_discovered_peer(&peer));
+    pm.approve_peer(&peer);
+    let new_key = DeviceSigningKey::generate().public_key();
+    assert!(pm.set_peer_signing_key(&peer, new_key));
+    assert_eq!(pm.get_trusted_peer(&peer).unwrap().signing_public_key, Some(new_key));
+}
+
+#[test]
+fn pairing_refuses_signing_key_for_untrusted_peer() {
+    let mut pm = PairingManager::new();
+    let peer = PeerId::new().to_string();
+    pm.add_discovered_peer(make_discovered_peer(&peer));
+
+    assert!(!pm.set_peer_signing_key(&peer, DeviceSigningKey::generate().public_key()));
+}
+
+// ── Enterprise ───────────────────────────────────────────────────
+
+#[tokio::test]
+async fn enterprise_registers_keys_only_for_team_members() {
+    let policy = EnterpriseSyncPolicy::new();
+    let member = PeerId::new();
+    let outsider = PeerId::new();
+    policy.add_team_member(TeamId::new(), member).await;
+
+    let key = DeviceSigningKey::generate().public_key();
+    assert!(policy.register_device_key(member, key).await);
+    assert!(!policy.register_device_key(outsider, key).await);
+
+    assert!(policy.device_keys.has_keys(&member));
+    assert!(!policy.device_keys.has_keys(&outsider));
+}
+
+#[tokio::test]
+async fn enterprise_drops_keys_when_peer_leaves_last_team() {
+    let policy = EnterpriseSyncPolicy::new();
+    let peer = PeerId::new();
+    let (team_a, team_b) = (TeamId::new(), TeamId::new());
+    policy.add_team_member(team_a, peer).await;
+    policy.add_team_member(team_b, peer).await;
+    policy
+        .register_device_key(peer, DeviceSigningKey::generate().public_key())
+        .await;
+
+    policy.remove_team_member(team_a, peer).await;
+    assert!(policy.device_keys.has_keys(&peer));
+
+    policy.remove_team_member(team_b, peer).await;
+    assert!(!policy.device_keys.has_keys(&peer));
+}
+
+#[tokio::test]
+async fn enterprise_device_keys_persist() {
+    let store = Arc::new(PolicyStore::open_in_memory().unwrap());
+    let policy = EnterpriseSyncPolicy::new().with_store(store.clone());
+    let peer = PeerId::new();
+    let team = TeamId::new();
+    let key = DeviceSigningKey::generate().public_key();
+    policy.add_team_member(team, peer).await;
+    policy.register_device_key(peer, key).await;
+
+    let loaded = EnterpriseSyncPolicy::load(store.clone()).await.unwrap();
+    assert_eq!(loaded.device_keys.keys_for(&peer), vec![key]);
+
+    policy.remove_team_member(team, peer).await;
+    assert!(store.load_device_keys().unwrap().is_empty());
+}
+
+#[test]
+fn strict_enterprise_policy_requires_signatures() {
+    let policy = EnterpriseSyncPolicy::new().with_required_signatures();
+    assert!(policy.device_keys.requires_signatures());
+    assert!(!EnterpriseSyncPolicy::new().device_keys.requires_signatures());
+}
@@ -167,6 +167,54 @@ fn get_clock() {
     assert_eq!(clock.get(&peer), 1);
 }
 
+#[test]
+fn take_dirty_returns_changed_entities_once() {
+    let mut state = SyncState::new(PeerId::new());
+    let (a, b) = (EntityId::new(), EntityId::new());
+    let peer = PeerId::new();
+    let event = make_event(a, peer);
+
+    state.record_event(a, &event);
+    state.record_event(b, &make_event(b, peer));
+    let mut dirty: Vec<_> = state.take_dirty().into_iter().map(|(id, _)| id).collect();
+    dirty.sort_by_key(|id| id.to_string());
+    let mut expected = vec![a, b];
+    expected.sort_by_key(|id| id.to_string());
+    assert_eq!(dirty, expected);
+
+    // Nothing new, and a replayed event changes nothing.
+    state.record_event(a, &event);
+    assert!(state.take_dirty().is_empty());
+}
+
+#[test]
+fn restore_entity_merges_with_current_state() {
+    let peer = PeerId::new();
+    let eid = EntityId::new();
+    let old = make_event(eid, peer);
+
+    let mut saved = EntitySyncState::new();
+    saved.record_event(&old);
+    saved.record_sync(PeerId::new(), HybridTimestamp::now());
+
+    // Restored into an empty state it is taken as is, and is not dirty.
+    let mut fresh = SyncState::new(PeerId::new());
+    fresh.restore_entity(eid, saved.clone());
+    assert_eq!(fresh.get_entity(&eid).unwrap().event_count, 1);
+    assert!(fresh.take_dirty().is_empty());
+
+    // Restored after newer events were recorded, the two are merged.
+    let mut state = SyncState::new(PeerId::new());
+    state.record_event(eid, &make_event(eid, peer));
+    state.take_dirty();
+    state.restore_entity(eid, saved);
+    let merged = state.get_entity(&eid).unwrap();
+    assert_eq!(merged.event_count, 2);
+    assert!(merged.seen_event_ids.contains(&old.id));
+    assert_eq!(merged.last_sync.len(), 1);
+    assert_eq!(state.take_dirty().len(), 1);
+}
+
 // ── compute_missing_events ───────────────────────────────────────
 
 #[test]
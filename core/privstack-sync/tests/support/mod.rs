@@ -0,0 +1,964 @@
+//! Shared helpers for cloud storage backend tests: a conformance suite
+//! every `CloudStorage` backend must pass, and in-process WebDAV and Google
+//! Drive servers.
+#![allow(dead_code)]
+
+use md5::{Digest, Md5};
+use privstack_sync::cloud::{
+    CloudStorage, GoogleDriveConfig, GoogleDriveStorage, ICloudConfig, ICloudStorage,
+    LocalFolderConfig, LocalFolderStorage, WebDavConfig, WebDavStorage,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use wiremock::matchers::any;
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+// ── Fixtures ────────────────────────────────────────────────────
+
+/// A backing store that can hand out independent clients, so tests can
+/// play two devices against the same folder.
+#[allow(async_fn_in_trait)]
+pub trait StorageFixture {
+    /// Opens a new authenticated client on the shared backing store.
+    async fn open(&self) -> Box<dyn CloudStorage>;
+}
+
+pub struct ICloudFixture {
+    pub dir: TempDir,
+}
+
+impl ICloudFixture {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+}
+
+impl StorageFixture for ICloudFixture {
+    async fn open(&self) -> Box<dyn CloudStorage> {
+        let mut storage = ICloudStorage::new(ICloudConfig {
+            container_path: Some(self.dir.path().to_path_buf()),
+            ..Default::default()
+        });
+        storage.authenticate().await.unwrap();
+        Box::new(storage)
+    }
+}
+
+pub struct LocalFolderFixture {
+    pub dir: TempDir,
+}
+
+impl LocalFolderFixture {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+}
+
+impl StorageFixture for LocalFolderFixture {
+    async fn open(&self) -> Box<dyn CloudStorage> {
+        let mut storage = LocalFolderStorage::new(LocalFolderConfig::new(self.dir.path()));
+        storage.authenticate().await.unwrap();
+        Box::new(storage)
+    }
+}
+
+pub struct WebDavFixture {
+    pub server: WebDavTestServer,
+}
+
+impl WebDavFixture {
+    pub async fn start() -> Self {
+        Self {
+            server: WebDavTestServer::start(DavAuth::digest("alice", "s3cret")).await,
+        }
+    }
+}
+
+impl StorageFixture for WebDavFixture {
+    async fn open(&self) -> Box<dyn CloudStorage> {
+        let mut storage = WebDavStorage::new(self.server.config("alice", "s3cret"));
+        storage.authenticate().await.unwrap();
+        Box::new(storage)
+    }
+}
+
+pub struct GoogleDriveFixture {
+    pub server: DriveTestServer,
+}
+
+impl GoogleDriveFixture {
+    pub async fn start() -> Self {
+        Self {
+            server: DriveTestServer::start().await,
+        }
+    }
+}
+
+impl StorageFixture for GoogleDriveFixture {
+    async fn open(&self) -> Box<dyn CloudStorage> {
+        let storage = GoogleDriveStorage::new(self.server.config());
+        storage.set_tokens("token".to_string(), None).await;
+        Box::new(storage)
+    }
+}
+
+// ── Conformance suite ───────────────────────────────────────────
+
+/// Expands to one test per conformance check for the given fixture.
+macro_rules! storage_conformance_tests {
+    ($fixture:expr) => {
+        mod conformance {
+            use super::*;
+            use crate::support::conformance;
+
+            #[tokio::test]
+            async fn starts_empty() {
+                conformance::starts_empty(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn upload_download_roundtrip() {
+                conformance::upload_download_roundtrip(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn list_after_upload() {
+                conformance::list_after_upload(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn overwrite_keeps_one_file() {
+                conformance::overwrite_keeps_one_file(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn delete_removes_file() {
+                conformance::delete_removes_file(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn delete_missing_is_ok() {
+                conformance::delete_missing_is_ok(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn download_missing_fails() {
+                conformance::download_missing_fails(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn names_with_special_characters() {
+                conformance::names_with_special_characters(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn changes_report_new_files_from_other_client() {
+                conformance::changes_report_new_files_from_other_client(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn changes_report_overwrites() {
+                conformance::changes_report_overwrites(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn changes_report_deletions() {
+                conformance::changes_report_deletions(&$fixture).await;
+            }
+
+            #[tokio::test]
+            async fn changes_skip_unchanged_files() {
+                conformance::changes_skip_unchanged_files(&$fixture).await;
+            }
+        }
+    };
+}
+
+pub mod conformance {
+    use super::StorageFixture;
+    use privstack_sync::cloud::storage::ChangeSet;
+    use privstack_sync::cloud::CloudStorage;
+
+    /// Polls changes, carrying the cursor like a sync engine would.
+    async fn poll(storage: &dyn CloudStorage, cursor: &mut Option<String>) -> ChangeSet {
+        let changes = storage.get_changes(cursor.as_deref()).await.unwrap();
+        if changes.next_cursor.is_some() {
+            cursor.clone_from(&changes.next_cursor);
+        }
+        changes
+    }
+
+    pub async fn starts_empty(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        storage.ensure_sync_folder().await.unwrap();
+        assert!(storage.list_files().await.unwrap().is_empty());
+    }
+
+    pub async fn upload_download_roundtrip(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        let content: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+
+        let file = storage.upload("blob.bin", &content).await.unwrap();
+        assert_eq!(file.name, "blob.bin");
+        assert_eq!(file.size, content.len() as u64);
+
+        assert_eq!(storage.download(&file.id).await.unwrap(), content);
+    }
+
+    pub async fn list_after_upload(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        storage.upload("a.txt", b"aaa").await.unwrap();
+        storage.upload("b.txt", b"bbbb").await.unwrap();
+
+        let mut files = storage.list_files().await.unwrap();
+        files.sort_by(|x, y| x.name.cmp(&y.name));
+        let listed: Vec<(&str, u64)> = files.iter().map(|f| (f.name.as_str(), f.size)).collect();
+        assert_eq!(listed, vec![("a.txt", 3), ("b.txt", 4)]);
+    }
+
+    pub async fn overwrite_keeps_one_file(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        let first = storage.upload("same.txt", b"first").await.unwrap();
+        let second = storage.upload("same.txt", b"second version").await.unwrap();
+        assert_eq!(first.id, second.id);
+
+        assert_eq!(storage.download(&second.id).await.unwrap(), b"second version");
+        assert_eq!(storage.list_files().await.unwrap().len(), 1);
+    }
+
+    pub async fn delete_removes_file(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        let file = storage.upload("gone.txt", b"data").await.unwrap();
+        storage.delete(&file.id).await.unwrap();
+
+        assert!(storage.list_files().await.unwrap().is_empty());
+        assert!(storage.download(&file.id).await.is_err());
+    }
+
+    pub async fn delete_missing_is_ok(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        storage.ensure_sync_folder().await.unwrap();
+        storage.delete("never-existed").await.unwrap();
+    }
+
+    pub async fn download_missing_fails(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        storage.ensure_sync_folder().await.unwrap();
+        assert!(storage.download("never-existed").await.is_err());
+    }
+
+    pub async fn names_with_special_characters(fixture: &impl StorageFixture) {
+        let storage = fixture.open().await;
+        let name = "notes & ideas (2) 100%+ümlaut.json";
+        let file = storage.upload(name, b"{}").await.unwrap();
+        assert_eq!(file.name, name);
+
+        let listed = storage.list_files().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, name);
+        assert_eq!(storage.download(&listed[0].id).await.unwrap(), b"{}");
+    }
+
+    pub async fn changes_report_new_files_from_other_client(fixture: &impl StorageFixture) {
+        let writer = fixture.open().await;
+        let reader = fixture.open().await;
+        let mut cursor = None;
+        assert!(poll(reader.as_ref(), &mut cursor).await.changed.is_empty());
+
+        writer.upload("new.txt", b"hello").await.unwrap();
+
+        let changes = poll(reader.as_ref(), &mut cursor).await;
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].name, "new.txt");
+        assert!(changes.deleted.is_empty());
+    }
+
+    pub async fn changes_report_overwrites(fixture: &impl StorageFixture) {
+        let writer = fixture.open().await;
+        let reader = fixture.open().await;
+        writer.upload("doc.txt", b"v1").await.unwrap();
+        let mut cursor = None;
+        poll(reader.as_ref(), &mut cursor).await;
+
+        writer.upload("doc.txt", b"version two").await.unwrap();
+
+        let changes = poll(reader.as_ref(), &mut cursor).await;
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].name, "doc.txt");
+    }
+
+    pub async fn changes_report_deletions(fixture: &impl StorageFixture) {
+        let writer = fixture.open().await;
+        let reader = fixture.open().await;
+        let file = writer.upload("tmp.txt", b"tmp").await.unwrap();
+        let mut cursor = None;
+        poll(reader.as_ref(), &mut cursor).await;
+
+        writer.delete(&file.id).await.unwrap();
+
+        let changes = poll(reader.as_ref(), &mut cursor).await;
+        assert!(changes.changed.is_empty());
+        assert_eq!(changes.deleted, vec![file.id]);
+    }
+
+    pub async fn changes_skip_unchanged_files(fixture: &impl StorageFixture) {
+        let writer = fixture.open().await;
+        let reader = fixture.open().await;
+        writer.upload("stable.txt", b"content").await.unwrap();
+        let mut cursor = None;
+        poll(reader.as_ref(), &mut cursor).await;
+
+        let changes = poll(reader.as_ref(), &mut cursor).await;
+        assert!(changes.changed.is_empty());
+        assert!(changes.deleted.is_empty());
+    }
+}
+
+// ── WebDAV test server ──────────────────────────────────────────
+
+/// Authentication the test server demands.
+#[derive(Debug, Clone)]
+pub enum DavAuth {
+    None,
+    Basic { user: String, pass: String },
+    Digest { user: String, pass: String },
+}
+
+impl DavAuth {
+    pub fn basic(user: &str, pass: &str) -> Self {
+        Self::Basic {
+            user: user.to_string(),
+            pass: pass.to_string(),
+        }
+    }
+
+    pub fn digest(user: &str, pass: &str) -> Self {
+        Self::Digest {
+            user: user.to_string(),
+            pass: pass.to_string(),
+        }
+    }
+}
+
+struct DavFile {
+    content: Vec<u8>,
+    etag: String,
+    modified: String,
+}
+
+struct DavState {
+    auth: DavAuth,
+    nonce: String,
+    /// Collections by decoded path, without trailing slash.
+    collections: BTreeSet<String>,
+    files: BTreeMap<String, DavFile>,
+    next_etag: u64,
+    etag_on_put: bool,
+    /// Requests answered with 401.
+    challenges: usize,
+    /// Methods of all requests, in order.
+    methods: Vec<String>,
+}
+
+/// A minimal in-memory WebDAV server (RFC 4918 subset: PROPFIND, MKCOL,
+/// PUT, GET, DELETE) with Basic and Digest authentication.
+pub struct WebDavTestServer {
+    pub mock: MockServer,
+    state: Arc<Mutex<DavState>>,
+}
+
+struct DavResponder(Arc<Mutex<DavState>>);
+
+impl WebDavTestServer {
+    /// Starts a server with a `/dav` root collection.
+    pub async fn start(auth: DavAuth) -> Self {
+        let state = Arc::new(Mutex::new(DavState {
+            auth,
+            nonce: "nonce-1".to_string(),
+            collections: BTreeSet::from([String::new(), "/dav".to_string()]),
+            files: BTreeMap::new(),
+            next_etag: 1,
+            etag_on_put: true,
+            challenges: 0,
+            methods: Vec::new(),
+        }));
+        let mock = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(DavResponder(state.clone()))
+            .mount(&mock)
+            .await;
+        Self { mock, state }
+    }
+
+    /// WebDAV root URL for clients.
+    pub fn base_url(&self) -> String {
+        format!("{}/dav", self.mock.uri())
+    }
+
+    pub fn config(&self, user: &str, pass: &str) -> WebDavConfig {
+        WebDavConfig {
+            base_url: self.base_url(),
+            username: user.to_string(),
+            password: pass.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Issues a new Digest nonce; requests with the old one get `stale=true`.
+    pub fn rotate_nonce(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.nonce = format!("nonce-{}", state.next_etag + 1000);
+    }
+
+    /// Stops sending `ETag` on PUT, like some servers.
+    pub fn omit_etag_on_put(&self) {
+        self.state.lock().unwrap().etag_on_put = false;
+    }
+
+    pub fn challenges(&self) -> usize {
+        self.state.lock().unwrap().challenges
+    }
+
+    pub fn methods(&self) -> Vec<String> {
+        self.state.lock().unwrap().methods.clone()
+    }
+
+    pub fn has_collection(&self, path: &str) -> bool {
+        self.state.lock().unwrap().collections.contains(path)
+    }
+
+    /// Writes a file directly, as another client would.
+    pub fn put_file(&self, path: &str, content: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let etag = state.bump_etag();
+        state.files.insert(
+            path.to_string(),
+            DavFile {
+                content: content.to_vec(),
+                etag,
+                modified: http_date(),
+            },
+        );
+    }
+
+    pub fn file_paths(&self) -> Vec<String> {
+        self.state.lock().unwrap().files.keys().cloned().collect()
+    }
+}
+
+impl DavState {
+    fn bump_etag(&mut self) -> String {
+        self.next_etag += 1;
+        format!("\"etag-{}\"", self.next_etag)
+    }
+
+    /// Returns the 401 response when the request is not authorized.
+    fn check_auth(&self, request: &Request) -> Option<ResponseTemplate> {
+        let header = request
+            .headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+
+        match &self.auth {
+            DavAuth::None => None,
+            DavAuth::Basic { user, pass } => {
+                let expected = format!("Basic {}", base64_encode(format!("{user}:{pass}").as_bytes()));
+                (header != expected).then(|| {
+                    ResponseTemplate::new(401).insert_header("WWW-Authenticate", r#"Basic realm="dav""#)
+                })
+            }
+            DavAuth::Digest { user, pass } => {
+                let challenge = |stale: bool| {
+                    let mut value = format!(
+                        r#"Digest realm="dav", qop="auth", nonce="{}", opaque="op4que", algorithm=MD5"#,
+                        self.nonce
+                    );
+                    if stale {
+                        value.push_str(", stale=true");
+                    }
+                    ResponseTemplate::new(401).insert_header("WWW-Authenticate", value.as_str())
+                };
+                let Some(params) = header.strip_prefix("Digest ") else {
+                    return Some(challenge(false));
+                };
+                let params = parse_params(params);
+                let get = |k: &str| params.get(k).cloned().unwrap_or_default();
+
+                let ha1 = md5_hex(&format!("{}:dav:{pass}", get("username")));
+                let ha2 = md5_hex(&format!("{}:{}", request.method.as_str(), get("uri")));
+                let expected = md5_hex(&format!(
+                    "{ha1}:{}:{}:{}:{}:{ha2}",
+                    get("nonce"),
+                    get("nc"),
+                    get("cnonce"),
+                    get("qop")
+                ));
+                let uri_matches = get("uri") == request.url.path();
+                if get("username") != *user
+                    || get("response") != expected
+                    || get("opaque") != "op4que"
+                    || !uri_matches
+                {
+                    return Some(challenge(false));
+                }
+                (get("nonce") != self.nonce).then(|| challenge(true))
+            }
+        }
+    }
+}
+
+impl Respond for DavResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let mut state = self.0.lock().unwrap();
+        state.methods.push(request.method.as_str().to_string());
+        if let Some(challenge) = state.check_auth(request) {
+            state.challenges += 1;
+            return challenge;
+        }
+
+        let path = urlencoding::decode(request.url.path())
+            .unwrap()
+            .trim_end_matches('/')
+            .to_string();
+        let parent = path.rsplit_once('/').map(|(p, _)| p.to_string()).unwrap_or_default();
+
+        match request.method.as_str() {
+            "PROPFIND" => {
+                let depth = request
+                    .headers
+                    .get("depth")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("infinity");
+                let mut body = String::from(
+                    r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:">"#,
+                );
+                if let Some(file) = state.files.get(&path) {
+                    body.push_str(&file_entry(&path, file));
+                } else if state.collections.contains(&path) {
+                    body.push_str(&collection_entry(&path));
+                    if depth == "1" {
+                        let prefix = format!("{path}/");
+                        let is_child = |p: &str| {
+                            p.strip_prefix(&prefix).is_some_and(|rest| !rest.contains('/'))
+                        };
+                        for child in state.collections.iter().filter(|c| is_child(c)) {
+                            body.push_str(&collection_entry(child));
+                        }
+                        for (child, file) in state.files.iter().filter(|(p, _)| is_child(p)) {
+                            body.push_str(&file_entry(child, file));
+                        }
+                    }
+                } else {
+                    return ResponseTemplate::new(404);
+                }
+                body.push_str("</d:multistatus>");
+                ResponseTemplate::new(207).set_body_raw(body, "application/xml; charset=utf-8")
+            }
+            "MKCOL" => {
+                if state.collections.contains(&path) || state.files.contains_key(&path) {
+                    ResponseTemplate::new(405)
+                } else if !state.collections.contains(&parent) {
+                    ResponseTemplate::new(409)
+                } else {
+                    state.collections.insert(path);
+                    ResponseTemplate::new(201)
+                }
+            }
+            "PUT" => {
+                if !state.collections.contains(&parent) {
+                    return ResponseTemplate::new(409);
+                }
+                let etag = state.bump_etag();
+                let existed = state
+                    .files
+                    .insert(
+                        path,
+                        DavFile {
+                            content: request.body.clone(),
+                            etag: etag.clone(),
+                            modified: http_date(),
+                        },
+                    )
+                    .is_some();
+                let response = ResponseTemplate::new(if existed { 204 } else { 201 });
+                if state.etag_on_put {
+                    response.insert_header("ETag", etag.as_str())
+                } else {
+                    response
+                }
+            }
+            "GET" => match state.files.get(&path) {
+                Some(file) => ResponseTemplate::new(200).set_body_bytes(file.content.clone()),
+                None => ResponseTemplate::new(404),
+            },
+            "DELETE" => match state.files.remove(&path) {
+                Some(_) => ResponseTemplate::new(204),
+                None => ResponseTemplate::new(404),
+            },
+            _ => ResponseTemplate::new(405),
+        }
+    }
+}
+
+fn href(path: &str) -> String {
+    path.split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn collection_entry(path: &str) -> String {
+    format!(
+        "<d:response><d:href>{}/</d:href><d:propstat><d:prop>\
+         <d:resourcetype><d:collection/></d:resourcetype>\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        href(path)
+    )
+}
+
+fn file_entry(path: &str, file: &DavFile) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+         <d:resourcetype/><d:getcontentlength>{}</d:getcontentlength>\
+         <d:getlastmodified>{}</d:getlastmodified><d:getetag>{}</d:getetag>\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        href(path),
+        file.content.len(),
+        file.modified,
+        file.etag.replace('"', "&quot;")
+    )
+}
+
+fn http_date() -> String {
+    chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn md5_hex(input: &str) -> String {
+    hex::encode(Md5::digest(input.as_bytes()))
+}
+
+fn parse_params(input: &str) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else { break };
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (quoted[..end].to_string(), &quoted[(end + 1).min(quoted.len())..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+        params.insert(key, value);
+        rest = remaining.trim_start_matches(',').trim();
+    }
+    params
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// ── Google Drive test server ────────────────────────────────────
+
+const DRIVE_FOLDER: &str = "application/vnd.google-apps.folder";
+
+struct DriveFile {
+    name: String,
+    parent: String,
+    mime_type: String,
+    content: Vec<u8>,
+    modified: String,
+}
+
+#[derive(Default)]
+struct DriveState {
+    files: BTreeMap<String, DriveFile>,
+    next_id: u64,
+    /// Ids of changed files, in order; a change token is an index into it.
+    changes: Vec<String>,
+}
+
+/// A minimal in-memory Google Drive v3 API: the file search, create,
+/// multipart and media upload, download, delete and changes endpoints.
+/// Bearer tokens are accepted as is.
+pub struct DriveTestServer {
+    pub mock: MockServer,
+    state: Arc<Mutex<DriveState>>,
+}
+
+struct DriveResponder(Arc<Mutex<DriveState>>);
+
+impl DriveTestServer {
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(DriveState::default()));
+        let mock = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(DriveResponder(state.clone()))
+            .mount(&mock)
+            .await;
+        Self { mock, state }
+    }
+
+    pub fn config(&self) -> GoogleDriveConfig {
+        GoogleDriveConfig {
+            client_id: "test_client".to_string(),
+            client_secret: "test_secret".to_string(),
+            api_base_url: self.mock.uri(),
+            oauth_base_url: self.mock.uri(),
+            auth_base_url: self.mock.uri(),
+            ..Default::default()
+        }
+    }
+
+    /// Names of all files and folders, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut names: Vec<String> = state.files.values().map(|f| f.name.clone()).collect();
+        names.sort();
+        names
+    }
+}
+
+impl DriveState {
+    fn create(&mut self, name: &str, parent: &str, mime_type: &str, content: Vec<u8>) -> String {
+        self.next_id += 1;
+        let id = format!("drive-{}", self.next_id);
+        self.files.insert(
+            id.clone(),
+            DriveFile {
+                name: name.to_string(),
+                parent: parent.to_string(),
+                mime_type: mime_type.to_string(),
+                content,
+                modified: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            },
+        );
+        self.changes.push(id.clone());
+        id
+    }
+
+    fn resource(&self, id: &str) -> Value {
+        let file = &self.files[id];
+        json!({
+            "id": id,
+            "name": file.name,
+            "mimeType": file.mime_type,
+            "parents": [file.parent],
+            "size": file.content.len().to_string(),
+            "modifiedTime": file.modified,
+            "md5Checksum": hex::encode(Md5::digest(&file.content)),
+            "trashed": false,
+        })
+    }
+
+    /// Answers the `q` search terms the storage backend sends: name,
+    /// parent, and folder or non-folder MIME type.
+    fn search(&self, query: &str) -> Vec<Value> {
+        let mut name = None;
+        let mut parent = None;
+        let mut folders = None;
+        for term in split_query(query) {
+            if let Some(value) = term.strip_prefix("name = ") {
+                name = Some(unquote(value));
+            } else if let Some(value) = term.strip_suffix(" in parents") {
+                parent = Some(unquote(value));
+            } else if term.starts_with("mimeType = ") {
+                folders = Some(true);
+            } else if term.starts_with("mimeType != ") {
+                folders = Some(false);
+            }
+        }
+        self.files
+            .iter()
+            .filter(|(_, f)| name.as_ref().map_or(true, |n| f.name == *n))
+            .filter(|(_, f)| parent.as_ref().map_or(true, |p| f.parent == *p))
+            .filter(|(_, f)| folders.map_or(true, |d| (f.mime_type == DRIVE_FOLDER) == d))
+            .map(|(id, _)| self.resource(id))
+            .collect()
+    }
+
+    /// The latest change of each file changed since `token`.
+    fn changes_since(&self, token: usize) -> Vec<Value> {
+        let mut seen = BTreeSet::new();
+        let mut ids: Vec<&String> = self.changes[token.min(self.changes.len())..]
+            .iter()
+            .rev()
+            .filter(|id| seen.insert(id.as_str()))
+            .collect();
+        ids.reverse();
+        ids.into_iter()
+            .map(|id| match self.files.contains_key(id) {
+                true => json!({ "fileId": id, "removed": false, "file": self.resource(id) }),
+                false => json!({ "fileId": id, "removed": true }),
+            })
+            .collect()
+    }
+}
+
+impl Respond for DriveResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let mut state = self.0.lock().unwrap();
+        let path = request.url.path().to_string();
+        let param = |key: &str| {
+            request
+                .url
+                .query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.into_owned())
+        };
+        let file_id = path
+            .strip_prefix("/drive/v3/files/")
+            .or_else(|| path.strip_prefix("/upload/drive/v3/files/"))
+            .map(str::to_string);
+
+        match (request.method.as_str(), path.as_str()) {
+            ("GET", "/drive/v3/files") => {
+                let files = state.search(&param("q").unwrap_or_default());
+                ResponseTemplate::new(200).set_body_json(json!({ "files": files }))
+            }
+            ("POST", "/drive/v3/files") => {
+                let metadata: Value = serde_json::from_slice(&request.body).unwrap();
+                let id = state.create(
+                    metadata["name"].as_str().unwrap(),
+                    metadata["parents"][0].as_str().unwrap_or("root"),
+                    metadata["mimeType"].as_str().unwrap_or("application/octet-stream"),
+                    Vec::new(),
+                );
+                ResponseTemplate::new(200).set_body_json(state.resource(&id))
+            }
+            ("POST", "/upload/drive/v3/files") => {
+                let content_type = request
+                    .headers
+                    .get("content-type")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("");
+                let Some((_, boundary)) = content_type.split_once("boundary=") else {
+                    return ResponseTemplate::new(400);
+                };
+                let Some((metadata, content)) = split_multipart(&request.body, boundary) else {
+                    return ResponseTemplate::new(400);
+                };
+                let metadata: Value = serde_json::from_slice(&metadata).unwrap();
+                let id = state.create(
+                    metadata["name"].as_str().unwrap(),
+                    metadata["parents"][0].as_str().unwrap_or("root"),
+                    "application/octet-stream",
+                    content,
+                );
+                ResponseTemplate::new(200).set_body_json(state.resource(&id))
+            }
+            ("PATCH", _) => {
+                let id = file_id.unwrap_or_default();
+                let Some(file) = state.files.get_mut(&id) else {
+                    return ResponseTemplate::new(404);
+                };
+                file.content = request.body.clone();
+                file.modified = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+                state.changes.push(id.clone());
+                ResponseTemplate::new(200).set_body_json(state.resource(&id))
+            }
+            ("GET", "/drive/v3/changes/startPageToken") => ResponseTemplate::new(200)
+                .set_body_json(json!({ "startPageToken": state.changes.len().to_string() })),
+            ("GET", "/drive/v3/changes") => {
+                let token = param("pageToken").and_then(|t| t.parse().ok()).unwrap_or(0);
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "changes": state.changes_since(token),
+                    "newStartPageToken": state.changes.len().to_string(),
+                }))
+            }
+            ("GET", _) => match file_id.and_then(|id| state.files.get(&id)) {
+                Some(file) => ResponseTemplate::new(200).set_body_bytes(file.content.clone()),
+                None => ResponseTemplate::new(404),
+            },
+            ("DELETE", _) => {
+                let id = file_id.unwrap_or_default();
+                match state.files.remove(&id) {
+                    Some(_) => {
+                        state.changes.push(id);
+                        ResponseTemplate::new(204)
+                    }
+                    None => ResponseTemplate::new(404),
+                }
+            }
+            _ => ResponseTemplate::new(405),
+        }
+    }
+}
+
+/// Splits a Drive query on `and`, leaving quoted values intact.
+fn split_query(query: &str) -> Vec<String> {
+    let mut terms = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        let current = terms.last_mut().unwrap();
+        match c {
+            '\\' if quoted => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            '\'' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            _ => current.push(c),
+        }
+        if !quoted && current.ends_with(" and ") {
+            current.truncate(current.len() - " and ".len());
+            terms.push(String::new());
+        }
+    }
+    terms.iter().map(|t| t.trim().to_string()).collect()
+}
+
+/// Reads a quoted query value, undoing `\'` and `\\` escapes.
+fn unquote(value: &str) -> String {
+    let inner = value.trim().trim_start_matches('\'').strip_suffix('\'').unwrap_or("");
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        out.extend(if c == '\\' { chars.next() } else { Some(c) });
+    }
+    out
+}
+
+/// Splits a two-part `multipart/related` body into metadata and content.
+fn split_multipart(body: &[u8], boundary: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let find = |from: usize, needle: &[u8]| {
+        body[from..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|i| from + i)
+    };
+    let separator = format!("\r\n--{boundary}");
+    let metadata_start = find(0, b"\r\n\r\n")? + 4;
+    let metadata_end = find(metadata_start, separator.as_bytes())?;
+    let content_start = find(metadata_end + separator.len(), b"\r\n\r\n")? + 4;
+    let content_end = find(content_start, format!("{separator}--").as_bytes())?;
+    Some((
+        body[metadata_start..metadata_end].to_vec(),
+        body[content_start..content_end].to_vec(),
+    ))
+}
@@ -0,0 +1,278 @@
+#[macro_use]
+mod support;
+
+use privstack_crypto::KdfParams;
+use privstack_storage::{EntityStore, EventStore};
+use privstack_sync::applicator::EventApplicator;
+use privstack_sync::cloud::{
+    CloudStorage, FileSyncConfig, FileSyncEngine, FileSyncState, FolderKey, WebDavConfig,
+    WebDavStorage,
+};
+use privstack_sync::SyncError;
+use privstack_types::{EntityId, Event, PeerId};
+use std::sync::Arc;
+use support::{DavAuth, WebDavFixture, WebDavTestServer};
+
+storage_conformance_tests!(WebDavFixture::start().await);
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+async fn open(server: &WebDavTestServer, user: &str, pass: &str) -> WebDavStorage {
+    let mut storage = WebDavStorage::new(server.config(user, pass));
+    storage.authenticate().await.unwrap();
+    storage
+}
+
+// ── Config ──────────────────────────────────────────────────────
+
+#[test]
+fn config_default() {
+    let cfg = WebDavConfig::default();
+    assert!(cfg.base_url.is_empty());
+    assert_eq!(cfg.base.sync_folder, "PrivStack/sync");
+}
+
+#[test]
+fn config_debug_redacts_password() {
+    let cfg = WebDavConfig {
+        base_url: "https://cloud.example.com/remote.php/dav/files/alice".to_string(),
+        username: "alice".to_string(),
+        password: "hunter2".to_string(),
+        ..Default::default()
+    };
+    let debug = format!("{cfg:?}");
+    assert!(debug.contains("alice"));
+    assert!(!debug.contains("hunter2"));
+}
+
+#[test]
+fn config_serde_roundtrip() {
+    let cfg = WebDavConfig {
+        base_url: "https://dav.example.com".to_string(),
+        username: "bob".to_string(),
+        password: "pw".to_string(),
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&cfg).unwrap();
+    let back: WebDavConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.base_url, cfg.base_url);
+    assert_eq!(back.username, "bob");
+    assert_eq!(back.base.sync_folder, "PrivStack/sync");
+}
+
+#[test]
+fn provider_name_and_unauthenticated_by_default() {
+    let storage = WebDavStorage::new(WebDavConfig::default());
+    assert_eq!(storage.provider_name(), "WebDAV");
+    assert!(!storage.is_authenticated());
+}
+
+// ── Authentication ──────────────────────────────────────────────
+
+#[tokio::test]
+async fn digest_auth_challenges_only_once() {
+    let server = WebDavTestServer::start(DavAuth::digest("alice", "s3cret")).await;
+    let storage = open(&server, "alice", "s3cret").await;
+    assert!(storage.is_authenticated());
+
+    storage.upload("a.txt", b"a").await.unwrap();
+    storage.list_files().await.unwrap();
+    // Later requests reuse the challenge with an incremented nonce count
+    assert_eq!(server.challenges(), 1);
+}
+
+#[tokio::test]
+async fn digest_auth_recovers_from_stale_nonce() {
+    let server = WebDavTestServer::start(DavAuth::digest("alice", "s3cret")).await;
+    let storage = open(&server, "alice", "s3cret").await;
+
+    server.rotate_nonce();
+    storage.upload("after-rotation.txt", b"ok").await.unwrap();
+    assert_eq!(server.challenges(), 2);
+    assert_eq!(storage.download("after-rotation.txt").await.unwrap(), b"ok");
+}
+
+#[tokio::test]
+async fn basic_auth() {
+    let server = WebDavTestServer::start(DavAuth::basic("bob", "pw")).await;
+    let storage = open(&server, "bob", "pw").await;
+    storage.upload("b.txt", b"basic").await.unwrap();
+    assert_eq!(storage.download("b.txt").await.unwrap(), b"basic");
+    assert_eq!(server.challenges(), 1);
+}
+
+#[tokio::test]
+async fn no_auth_server() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let storage = open(&server, "", "").await;
+    storage.upload("open.txt", b"x").await.unwrap();
+    assert_eq!(server.challenges(), 0);
+}
+
+#[tokio::test]
+async fn wrong_password_is_auth_error() {
+    let server = WebDavTestServer::start(DavAuth::digest("alice", "s3cret")).await;
+    let mut storage = WebDavStorage::new(server.config("alice", "wrong"));
+
+    let result = storage.authenticate().await;
+    assert!(matches!(result, Err(SyncError::Auth(_))));
+    assert!(!storage.is_authenticated());
+}
+
+#[tokio::test]
+async fn wrong_basic_password_is_auth_error() {
+    let server = WebDavTestServer::start(DavAuth::basic("bob", "pw")).await;
+    let storage = WebDavStorage::new(server.config("bob", "nope"));
+    assert!(matches!(storage.list_files().await, Err(SyncError::Auth(_))));
+}
+
+// ── Folder handling ─────────────────────────────────────────────
+
+#[tokio::test]
+async fn authenticate_creates_nested_sync_folder() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    open(&server, "", "").await;
+    assert!(server.has_collection("/dav/PrivStack"));
+    assert!(server.has_collection("/dav/PrivStack/sync"));
+
+    // A second client finds the folder instead of creating it again
+    let before = server.methods().iter().filter(|m| *m == "MKCOL").count();
+    open(&server, "", "").await;
+    let after = server.methods().iter().filter(|m| *m == "MKCOL").count();
+    assert_eq!(before, after);
+}
+
+#[tokio::test]
+async fn custom_sync_folder() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let mut config = server.config("", "");
+    config.base.sync_folder = "Apps/My Notes".to_string();
+    let mut storage = WebDavStorage::new(config);
+    storage.authenticate().await.unwrap();
+
+    let file = storage.upload("n.txt", b"n").await.unwrap();
+    assert_eq!(file.path, "Apps/My Notes/n.txt");
+    assert_eq!(server.file_paths(), vec!["/dav/Apps/My Notes/n.txt".to_string()]);
+}
+
+#[tokio::test]
+async fn list_skips_hidden_files_and_subfolders() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let storage = open(&server, "", "").await;
+    storage.upload("visible.txt", b"v").await.unwrap();
+    server.put_file("/dav/PrivStack/sync/.hidden", b"h");
+    server.put_file("/dav/PrivStack/sync/sub/nested.txt", b"n");
+
+    let files = storage.list_files().await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].name, "visible.txt");
+}
+
+#[tokio::test]
+async fn file_ids_cannot_escape_sync_folder() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let storage = open(&server, "", "").await;
+    assert!(matches!(storage.download("../x").await, Err(SyncError::Storage(_))));
+    assert!(matches!(storage.delete("a/b").await, Err(SyncError::Storage(_))));
+}
+
+// ── ETags ───────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn upload_reports_etag_as_content_hash() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let storage = open(&server, "", "").await;
+    let first = storage.upload("e.txt", b"one").await.unwrap();
+    let second = storage.upload("e.txt", b"two").await.unwrap();
+
+    let (Some(first), Some(second)) = (first.content_hash, second.content_hash) else {
+        panic!("missing ETag");
+    };
+    assert!(!first.contains('"'));
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn upload_without_etag_header_falls_back_to_propfind() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    server.omit_etag_on_put();
+    let storage = open(&server, "", "").await;
+
+    let file = storage.upload("noetag.txt", b"data").await.unwrap();
+    assert!(file.content_hash.is_some());
+    assert_eq!(file.size, 4);
+    assert!(server.methods().ends_with(&["PUT".to_string(), "PROPFIND".to_string()]));
+}
+
+#[tokio::test]
+async fn same_size_rewrite_detected_by_etag() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let storage = open(&server, "", "").await;
+    server.put_file("/dav/PrivStack/sync/m.txt", b"aaaa");
+    let cursor = storage.get_changes(None).await.unwrap().next_cursor;
+
+    server.put_file("/dav/PrivStack/sync/m.txt", b"bbbb");
+    let changes = storage.get_changes(cursor.as_deref()).await.unwrap();
+    assert_eq!(changes.changed.len(), 1);
+}
+
+#[tokio::test]
+async fn cursor_carries_state_across_instances() {
+    let server = WebDavTestServer::start(DavAuth::None).await;
+    let first = open(&server, "", "").await;
+    first.upload("old.txt", b"o").await.unwrap();
+    let cursor = first.get_changes(None).await.unwrap().next_cursor;
+
+    let restarted = open(&server, "", "").await;
+    server.put_file("/dav/PrivStack/sync/new.txt", b"n");
+    let changes = restarted.get_changes(cursor.as_deref()).await.unwrap();
+    let names: Vec<&str> = changes.changed.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["new.txt"]);
+}
+
+// ── Folder sync over WebDAV ─────────────────────────────────────
+
+#[tokio::test]
+async fn file_sync_between_devices() {
+    let server = WebDavTestServer::start(DavAuth::digest("alice", "s3cret")).await;
+    let params = KdfParams {
+        memory_cost: 1024,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    let mut devices = Vec::new();
+    for _ in 0..2 {
+        let storage = open(&server, "alice", "s3cret").await;
+        let key = FolderKey::unlock(&storage, "passphrase", &params).await.unwrap();
+        let peer_id = PeerId::new();
+        let entity_store = Arc::new(EntityStore::open_in_memory().unwrap());
+        let event_store = Arc::new(EventStore::open_in_memory().unwrap());
+        let engine = FileSyncEngine::new(
+            peer_id,
+            key,
+            entity_store.clone(),
+            event_store.clone(),
+            FileSyncConfig::default(),
+            FileSyncState::default(),
+        );
+        devices.push((peer_id, entity_store, event_store, storage, engine));
+    }
+
+    let entity_id = EntityId::new();
+    {
+        let (peer_id, entity_store, event_store, storage, engine) = &mut devices[0];
+        let event = Event::entity_created(entity_id, *peer_id, "note", r#"{"title":"over dav"}"#);
+        EventApplicator::new(*peer_id)
+            .apply_event(&event, entity_store, None, None)
+            .unwrap();
+        event_store.save_event(&event).unwrap();
+        engine.sync(storage).await.unwrap();
+    }
+
+    let (_, entity_store, _, storage, engine) = &mut devices[1];
+    let report = engine.sync(storage).await.unwrap();
+    assert_eq!(report.updated_entities, vec![entity_id]);
+    let entity = entity_store.get_entity(&entity_id.to_string()).unwrap().unwrap();
+    assert_eq!(entity.data["title"], "over dav");
+}
@@ -87,6 +87,17 @@ pub enum EventPayload {
         json_data: String,
     },
 
+    /// A user resolved a sync conflict on an entity.
+    /// Carries the chosen state, which replaces the entity outright. The
+    /// event's dependencies name the versions it supersedes, so peers can
+    /// close their own records of the same conflict.
+    ConflictResolved {
+        /// The plugin-defined entity type.
+        entity_type: String,
+        /// Full JSON representation of the resolved entity.
+        json_data: String,
+    },
+
     // ── ACL propagation events ──────────────────────────────────
 
     /// Grant a peer a role on an entity.
@@ -96,6 +107,18 @@ pub enum EventPayload {
         role: String,
     },
 
+    /// Grant a peer a role on an entity that lapses at a deadline or after
+    /// a number of uses, whichever comes first.
+    AclGrantPeerLimited {
+        entity_id: String,
+        peer_id: String,
+        role: String,
+        /// Expiry in milliseconds since the Unix epoch, if any.
+        expires_at_ms: Option<u64>,
+        /// Maximum number of sync requests the grant serves, if limited.
+        max_uses: Option<u32>,
+    },
+
     /// Revoke a peer's role on an entity.
     AclRevokePeer {
         entity_id: String,
@@ -122,6 +145,23 @@ pub enum EventPayload {
         role: Option<String>,
     },
 
+    /// Set (or clear) the container an entity inherits ACL entries from.
+    AclSetParent {
+        entity_id: String,
+        /// Parent entity ID, or absent to detach.
+        parent_id: Option<String>,
+    },
+
+    /// Stop an entity from inheriting its ancestors' ACL entries.
+    AclBreakInheritance {
+        entity_id: String,
+    },
+
+    /// Resume inheriting ancestors' ACL entries after a break.
+    AclRestoreInheritance {
+        entity_id: String,
+    },
+
     /// Add a peer to a team.
     TeamAddPeer {
         team_id: String,
@@ -133,6 +173,55 @@ pub enum EventPayload {
         team_id: String,
         peer_id: String,
     },
+
+    // ── Dataset replication ─────────────────────────────────────
+    // The event's entity is the dataset; its name and category travel as
+    // a "dataset" entity snapshot. Rows and columns are addressed by
+    // stable IDs, and the event timestamp orders concurrent changes.
+
+    /// Insert a dataset row, or set some of its cells.
+    DatasetRowUpserted {
+        row_id: String,
+        /// JSON object of column ID → cell value.
+        cells_json: String,
+    },
+
+    /// Delete a dataset row. Final: later upserts of the row are ignored.
+    DatasetRowDeleted {
+        row_id: String,
+    },
+
+    /// Add a column to a dataset.
+    DatasetColumnAdded {
+        column_id: String,
+        name: String,
+        /// SQLite column type.
+        column_type: String,
+        default_value: Option<String>,
+    },
+
+    /// Rename a dataset column.
+    DatasetColumnRenamed {
+        column_id: String,
+        name: String,
+    },
+
+    /// Drop a dataset column. Final, like a row delete.
+    DatasetColumnDropped {
+        column_id: String,
+    },
+
+    // ── End-to-end encryption ───────────────────────────────────
+
+    /// An end-to-end encrypted payload.
+    /// Only exists on the wire: the receiving sync layer decrypts it back
+    /// to the original payload before the event is applied or stored.
+    Encrypted {
+        /// Fingerprint of the payload key used.
+        key_id: String,
+        /// Base64 nonce + ciphertext of the original payload.
+        ciphertext: String,
+    },
 }
 
 /// An event representing a change to an entity.
@@ -160,8 +249,36 @@ pub struct Event {
     /// Typically the previous event from the same peer.
     #[serde(default)]
     pub dependencies: Vec<EventId>,
+
+    /// Author's device signature over [`Event::signing_bytes`].
+    /// `None` for events from peers that predate event signing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<EventSignature>,
+}
+
+/// An Ed25519 signature binding an event to the device that created it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventSignature {
+    /// The signing device's Ed25519 public key.
+    pub device_key: [u8; 32],
+    /// The 64-byte Ed25519 signature.
+    pub signature: Vec<u8>,
+}
+
+/// The signed portion of an event: everything except the signature itself.
+#[derive(Serialize)]
+struct SignedContent<'a> {
+    id: &'a EventId,
+    entity_id: &'a EntityId,
+    peer_id: &'a PeerId,
+    timestamp: &'a HybridTimestamp,
+    payload: &'a EventPayload,
+    dependencies: &'a [EventId],
 }
 
+/// Domain separator so event signatures can't be replayed as other messages.
+const SIGNING_DOMAIN: &[u8] = b"privstack-event-v1\0";
+
 impl Event {
     /// Creates a new event.
     #[must_use]
@@ -178,6 +295,7 @@ impl Event {
             timestamp,
             payload,
             dependencies: Vec::new(),
+            signature: None,
         }
     }
 
@@ -255,9 +373,52 @@ impl Event {
         )
     }
 
+    /// Creates a conflict-resolution event.
+    #[must_use]
+    pub fn conflict_resolved(
+        entity_id: EntityId,
+        peer_id: PeerId,
+        entity_type: impl Into<String>,
+        json_data: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            entity_id,
+            peer_id,
+            HybridTimestamp::now(),
+            EventPayload::ConflictResolved {
+                entity_type: entity_type.into(),
+                json_data: json_data.into(),
+            },
+        )
+    }
+
     /// Adds a dependency to this event.
     pub fn with_dependency(mut self, dep: EventId) -> Self {
         self.dependencies.push(dep);
         self
     }
+
+    /// Returns the canonical bytes covered by the event signature.
+    ///
+    /// The payload must be the plaintext payload: encrypted events are
+    /// verified after decryption.
+    pub fn signing_bytes(&self) -> crate::Result<Vec<u8>> {
+        let content = SignedContent {
+            id: &self.id,
+            entity_id: &self.entity_id,
+            peer_id: &self.peer_id,
+            timestamp: &self.timestamp,
+            payload: &self.payload,
+            dependencies: &self.dependencies,
+        };
+        let mut bytes = SIGNING_DOMAIN.to_vec();
+        serde_json::to_writer(&mut bytes, &content)?;
+        Ok(bytes)
+    }
+
+    /// Returns true if the event carries a signature.
+    #[must_use]
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
 }
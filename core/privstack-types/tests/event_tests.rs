@@ -1,4 +1,4 @@
-use privstack_types::{EntityId, Event, EventId, EventPayload, HybridTimestamp, PeerId};
+use privstack_types::{EntityId, Event, EventId, EventPayload, EventSignature, HybridTimestamp, PeerId};
 use std::str::FromStr;
 
 // ── EventId ───────────────────────────────────────────────────────
@@ -93,6 +93,18 @@ fn payload_full_snapshot_serde() {
     assert_eq!(payload, parsed);
 }
 
+#[test]
+fn payload_conflict_resolved_serde() {
+    let payload = EventPayload::ConflictResolved {
+        entity_type: "note".into(),
+        json_data: r#"{"title":"merged"}"#.into(),
+    };
+    let json = serde_json::to_string(&payload).unwrap();
+    assert!(json.contains(r#""op":"ConflictResolved""#));
+    let parsed: EventPayload = serde_json::from_str(&json).unwrap();
+    assert_eq!(payload, parsed);
+}
+
 // ── Event factories ──────────────────────────────────────────────
 
 #[test]
@@ -142,6 +154,21 @@ fn event_entity_deleted() {
     }
 }
 
+#[test]
+fn event_conflict_resolved() {
+    let eid = EntityId::new();
+    let pid = PeerId::new();
+    let event = Event::conflict_resolved(eid, pid, "note", r#"{"x":2}"#);
+
+    match &event.payload {
+        EventPayload::ConflictResolved { entity_type, json_data } => {
+            assert_eq!(entity_type, "note");
+            assert_eq!(json_data, r#"{"x":2}"#);
+        }
+        _ => panic!("wrong variant"),
+    }
+}
+
 #[test]
 fn event_full_snapshot() {
     let eid = EntityId::new();
@@ -219,3 +246,57 @@ fn event_deserialize_without_dependencies_field() {
     let parsed: Event = serde_json::from_value(json).unwrap();
     assert!(parsed.dependencies.is_empty());
 }
+
+// ── Signatures ───────────────────────────────────────────────────
+
+#[test]
+fn unsigned_event_omits_signature_field() {
+    let event = Event::entity_created(EntityId::new(), PeerId::new(), "x", "{}");
+    assert!(!event.is_signed());
+    let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+    assert!(json.get("signature").is_none());
+}
+
+#[test]
+fn signed_event_serde_roundtrip() {
+    let mut event = Event::entity_created(EntityId::new(), PeerId::new(), "x", "{}");
+    event.signature = Some(EventSignature {
+        device_key: [7u8; 32],
+        signature: vec![1u8; 64],
+    });
+    let json = serde_json::to_string(&event).unwrap();
+    let parsed: Event = serde_json::from_str(&json).unwrap();
+    assert!(parsed.is_signed());
+    assert_eq!(parsed.signature, event.signature);
+}
+
+#[test]
+fn signing_bytes_exclude_signature() {
+    let mut event = Event::entity_created(EntityId::new(), PeerId::new(), "x", "{}");
+    let before = event.signing_bytes().unwrap();
+    event.signature = Some(EventSignature {
+        device_key: [7u8; 32],
+        signature: vec![1u8; 64],
+    });
+    assert_eq!(event.signing_bytes().unwrap(), before);
+}
+
+#[test]
+fn signing_bytes_cover_author_and_payload() {
+    let event = Event::entity_created(EntityId::new(), PeerId::new(), "x", "{}");
+    let bytes = event.signing_bytes().unwrap();
+
+    let mut forged_author = event.clone();
+    forged_author.peer_id = PeerId::new();
+    assert_ne!(forged_author.signing_bytes().unwrap(), bytes);
+
+    let mut forged_payload = event.clone();
+    forged_payload.payload = EventPayload::EntityDeleted {
+        entity_type: "x".into(),
+    };
+    assert_ne!(forged_payload.signing_bytes().unwrap(), bytes);
+
+    let mut forged_deps = event;
+    forged_deps.dependencies.push(EventId::new());
+    assert_ne!(forged_deps.signing_bytes().unwrap(), bytes);
+}
@@ -202,6 +202,7 @@ public class AppSettings
     [JsonPropertyName("sync_device_name")]
     public string? SyncDeviceName { get; set; }
 
+    // Trusted peers and pinned keys only; the device's sync keys live in the vault.
     [JsonPropertyName("sync_pairing_state")]
     public string? SyncPairingState { get; set; }
 
@@ -210,4 +210,16 @@ public class SyncEventDto
 
     [JsonPropertyName("json_data")]
     public string? JsonData { get; set; }
+
+    [JsonPropertyName("entities_done")]
+    public int? EntitiesDone { get; set; }
+
+    [JsonPropertyName("entities_total")]
+    public int? EntitiesTotal { get; set; }
+
+    /// <summary>
+    /// Estimated seconds left in a large sync, once a rate is known.
+    /// </summary>
+    [JsonPropertyName("eta_secs")]
+    public long? EtaSecs { get; set; }
 }
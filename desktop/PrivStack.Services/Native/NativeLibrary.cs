@@ -324,6 +324,12 @@ internal static partial class NativeLibrary
     [LibraryImport(LibraryName, EntryPoint = "privstack_sync_get_peer_id")]
     public static partial PrivStackError SyncGetPeerId(out nint outPeerId);
 
+    /// <summary>
+    /// Gets this device's event signing public key (hex).
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_get_device_key")]
+    public static partial PrivStackError SyncGetDeviceKey(out nint outDeviceKey);
+
     /// <summary>
     /// Gets discovered peers as JSON array.
     /// </summary>
@@ -343,6 +349,60 @@ internal static partial class NativeLibrary
     [LibraryImport(LibraryName, EntryPoint = "privstack_sync_share_document", StringMarshalling = StringMarshalling.Utf8)]
     public static partial PrivStackError SyncShareDocument(string documentId);
 
+    /// <summary>
+    /// Sets this device's sync scope (entity types, tags, modified-within window) from JSON.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_set_scope", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncSetScope(string scopeJson);
+
+    /// <summary>
+    /// Gets this device's sync scope as JSON. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_get_scope")]
+    public static partial PrivStackError SyncGetScope(out nint outJson);
+
+    /// <summary>
+    /// Fetches an entity outside the sync scope from peers (e.g. when opening a link to it).
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_fetch_entity", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncFetchEntity(string documentId);
+
+    /// <summary>
+    /// Lists sync conflicts as a JSON array, optionally for one entity. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_list_conflicts", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncListConflicts(string? entityId, [MarshalAs(UnmanagedType.U1)] bool includeResolved, out nint outJson);
+
+    /// <summary>
+    /// Gets a conflict and its per-field differences as JSON. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_conflict_diff", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncConflictDiff(string conflictId, out nint outJson);
+
+    /// <summary>
+    /// Resolves a conflict (keep_mine, keep_theirs or merged); the resolution syncs to peers.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_resolve_conflict", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncResolveConflict(string conflictId, string resolutionJson);
+
+    /// <summary>
+    /// Lists recorded sync sessions as a JSON array, newest first, optionally for one peer. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_sessions", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError SyncSessions(string? peerId, uint limit, out nint outJson);
+
+    /// <summary>
+    /// Gets per-peer sync totals as a JSON array. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_peer_metrics")]
+    public static partial PrivStackError SyncPeerMetrics(out nint outJson);
+
+    /// <summary>
+    /// Exports a redacted sync diagnostics bundle as JSON. Must be freed with FreeString.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_sync_export_diagnostics")]
+    public static partial PrivStackError SyncExportDiagnostics(out nint outJson);
+
     /// <summary>
     /// Records a local event for sync (call when user makes an edit).
     /// </summary>
@@ -478,6 +538,18 @@ internal static partial class NativeLibrary
     [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_init_icloud", StringMarshalling = StringMarshalling.Utf8)]
     public static partial PrivStackError CloudInitICloud(string? bundleId);
 
+    /// <summary>
+    /// Initializes WebDAV storage from a JSON config (base_url, username, password, optional sync_folder).
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_init_webdav", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError CloudInitWebDav(string configJson);
+
+    /// <summary>
+    /// Initializes storage in a local folder replicated by another tool (Syncthing, Dropbox).
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_init_local_folder", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError CloudInitLocalFolder(string rootPath);
+
     /// <summary>
     /// Starts authentication for a cloud provider.
     /// </summary>
@@ -527,6 +599,25 @@ internal static partial class NativeLibrary
     [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_provider_name")]
     public static partial nint CloudProviderName(CloudProvider provider);
 
+    /// <summary>
+    /// Unlocks the provider's sync folder with a passphrase and prepares folder sync.
+    /// Pass null state on first use, or the JSON from CloudFolderSyncState.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_folder_sync_init", StringMarshalling = StringMarshalling.Utf8)]
+    public static partial PrivStackError CloudFolderSyncInit(CloudProvider provider, string passphrase, string? stateJson);
+
+    /// <summary>
+    /// Runs one folder sync pass and returns a JSON report.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_folder_sync")]
+    public static partial PrivStackError CloudFolderSync(CloudProvider provider, out nint outJson);
+
+    /// <summary>
+    /// Gets the folder sync state as JSON for persistence.
+    /// </summary>
+    [LibraryImport(LibraryName, EntryPoint = "privstack_cloud_folder_sync_state")]
+    public static partial PrivStackError CloudFolderSyncState(out nint outJson);
+
     // ============================================================
     // Cloud Sync (S3-backed — privstack_cloudsync_* prefix)
     // ============================================================
@@ -912,7 +1003,9 @@ internal static partial class NativeLibrary
 public enum CloudProvider
 {
     GoogleDrive = 0,
-    ICloud = 1
+    ICloud = 1,
+    WebDav = 2,
+    LocalFolder = 3
 }
 
 /// <summary>
@@ -95,6 +95,11 @@ public sealed class SyncIngestionService : ISyncIngestionService, IDisposable
                     evt.PeerId, evt.EventsSent, evt.EventsReceived);
                 break;
 
+            case "sync_progress":
+                _log.Debug("Sync progress with {PeerId}: {Done}/{Total} entities, eta={Eta}s",
+                    evt.PeerId, evt.EntitiesDone, evt.EntitiesTotal, evt.EtaSecs);
+                break;
+
             case "sync_failed":
                 _log.Warning("Sync failed with {PeerId}: {Error}", evt.PeerId, evt.Error);
                 break;